@@ -0,0 +1,326 @@
+//! Improving a finished grouping by swapping members (`--optimize`).
+//!
+//! Starting from the groups formed by the strategy, two students of different
+//! groups are swapped at random for as long as the time budget allows. Swaps
+//! that lower the score are always kept, and worse ones now and then, less
+//! and less often as time runs out (simulated annealing), so that the search
+//! does not stop at the first grouping that no single swap improves.
+
+use crate::constraints::{Constraints, SwapCheck};
+use crate::group::{Group, Student};
+use crate::grouping::GroupingConfig;
+use crate::history::History;
+use crate::i18n::{message, Msg};
+use crate::log;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Time spent by `--optimize` when `--time-budget` is not given.
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(2);
+
+/// Score of an unsatisfied constraint, so that constraints always win over
+/// balance and repeats.
+const CONSTRAINT_PENALTY: f64 = 1_000_000.0;
+
+/// Temperature at the start of the search: a swap that adds one repeated
+/// pair is then kept about one time in three.
+const START_TEMPERATURE: f64 = 1.0;
+
+/// Temperature once the time budget is spent, when worse swaps are all but
+/// never kept.
+const END_TEMPERATURE: f64 = 0.01;
+
+/// Swaps tried between two looks at the clock.
+const CLOCK_INTERVAL: usize = 256;
+
+/// What the search lowers: broken constraints and the weights of the broken
+/// soft ones, and for each group, how far the values of the balanced
+/// attributes and the numeric weights are from those of the whole class, and
+/// how many of its pairs were grouped together before.
+pub(crate) struct Score<'a> {
+    constraints: &'a Constraints,
+    /// Balanced attributes, with the share of the class holding each value
+    shares: Vec<(String, Vec<(String, f64)>)>,
+    /// Numeric attributes to spread, with their mean and standard deviation
+    weights: Vec<(&'a str, f64, f64)>,
+    history: Option<&'a History>,
+}
+
+impl<'a> Score<'a> {
+    /// The score of `config`, with the class made of the students of `groups`.
+    pub(crate) fn new(groups: &[Group], config: &'a GroupingConfig) -> Self {
+        let students: Vec<&Student> = groups.iter().flat_map(|g| &g.members).collect();
+        let total = students.len().max(1) as f64;
+        let shares = config
+            .balance_keys()
+            .into_iter()
+            .map(|key| {
+                let mut counts: Vec<(String, f64)> = Vec::new();
+                for value in students.iter().filter_map(|s| s.attribute(&key)) {
+                    let value = value.to_lowercase();
+                    match counts.iter_mut().find(|(v, _)| *v == value) {
+                        Some((_, count)) => *count += 1.0,
+                        None => counts.push((value, 1.0)),
+                    }
+                }
+                for (_, count) in &mut counts {
+                    *count /= total;
+                }
+                (key, counts)
+            })
+            .collect();
+        let weights = config
+            .spread_weights
+            .iter()
+            .chain(&config.skill)
+            .filter_map(|key| {
+                let values: Vec<f64> = students.iter().filter_map(|s| weight(s, key)).collect();
+                if values.is_empty() {
+                    return None;
+                }
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+                (variance > 0.0).then(|| (key.as_str(), mean, variance.sqrt()))
+            })
+            .collect();
+        Score {
+            constraints: &config.constraints,
+            shares,
+            weights,
+            history: config.history.as_ref(),
+        }
+    }
+
+    /// The score of the whole grouping.
+    pub(crate) fn total(&self, groups: &[Group]) -> f64 {
+        broken(self.constraints.unsatisfied(groups).len(), self.constraints.penalty(groups))
+            + groups.iter().map(|g| self.of(g)).sum::<f64>()
+    }
+
+    /// The part of the score that comes from `group`, besides the constraints.
+    pub(crate) fn of(&self, group: &Group) -> f64 {
+        let size = group.members.len() as f64;
+        let mut score = 0.0;
+        for (key, shares) in &self.shares {
+            for (value, share) in shares {
+                let count = group
+                    .members
+                    .iter()
+                    .filter(|s| s.attribute(key).is_some_and(|v| v.to_lowercase() == *value))
+                    .count();
+                score += (count as f64 - share * size).powi(2);
+            }
+        }
+        for &(key, mean, deviation) in &self.weights {
+            // Students without a weight count as average
+            let offset: f64 = group.members.iter().filter_map(|s| weight(s, key)).map(|w| (w - mean) / deviation).sum();
+            score += offset.powi(2);
+        }
+        if let Some(history) = self.history {
+            score += history.repeat_count(std::slice::from_ref(group)) as f64;
+        }
+        score
+    }
+}
+
+/// The score that [`optimize`] lowers, for `groups` formed with `config`:
+/// 0 when no constraint (soft or not) is broken, every group mirrors the
+/// class and no pair is repeated.
+pub fn score(groups: &[Group], config: &GroupingConfig) -> f64 {
+    Score::new(groups, config).total(groups)
+}
+
+/// The part of the score that comes from `violations` broken constraints and
+/// the `penalty` of the broken soft ones.
+fn broken(violations: usize, penalty: u64) -> f64 {
+    violations as f64 * CONSTRAINT_PENALTY + penalty as f64
+}
+
+/// The numeric value of `key` for `student`, if it has one.
+fn weight(student: &Student, key: &str) -> Option<f64> {
+    student.attribute(key)?.trim().parse().ok()
+}
+
+/// Improve `groups` by simulated annealing for `budget`, lowering broken
+/// constraints first, then the weights of the broken soft constraints, the
+/// spread of the balanced attributes (and of `spread_weights` and `skill`)
+/// away from the class as a whole, and the pairings repeated from
+/// `config.history`. The best grouping seen is kept.
+///
+/// Groups equal to one in `kept` are never changed, and with
+/// `config.within_strata` only students of the same stratum are swapped.
+pub fn optimize(groups: &mut [Group], kept: &[Group], config: &GroupingConfig, budget: Duration, rng: &mut StdRng) {
+    let kept: HashSet<&[Student]> = kept.iter().map(|g| g.members.as_slice()).collect();
+    let movable: Vec<usize> = (0..groups.len())
+        .filter(|&i| !groups[i].members.is_empty() && !kept.contains(groups[i].members.as_slice()))
+        .collect();
+    if movable.len() < 2 {
+        return;
+    }
+    let score = Score::new(groups, config);
+    let stratum = |student: &Student| {
+        config
+            .stratify
+            .as_deref()
+            .filter(|_| config.within_strata)
+            .and_then(|key| student.attribute(key))
+            .map(str::to_lowercase)
+    };
+
+    let mut check = SwapCheck::new(&config.constraints, groups);
+    let mut constraints = broken(config.constraints.unsatisfied(groups).len(), config.constraints.penalty(groups));
+    let mut costs: Vec<f64> = groups.iter().map(|g| score.of(g)).collect();
+    let total = |costs: &[f64], constraints: f64| constraints + costs.iter().sum::<f64>();
+    let mut current = total(&costs, constraints);
+    let start = current;
+    let mut best = (current, groups.to_vec());
+
+    let began = Instant::now();
+    let mut temperature = START_TEMPERATURE;
+    let mut tries = 0;
+    while best.0 > 0.0 {
+        if tries % CLOCK_INTERVAL == 0 {
+            let elapsed = began.elapsed();
+            if elapsed >= budget {
+                break;
+            }
+            let progress = elapsed.as_secs_f64() / budget.as_secs_f64();
+            temperature = START_TEMPERATURE * (END_TEMPERATURE / START_TEMPERATURE).powf(progress);
+        }
+        tries += 1;
+
+        let picked: Vec<&usize> = movable.choose_multiple(rng, 2).collect();
+        let (i, j) = (*picked[0].min(picked[1]), *picked[0].max(picked[1]));
+        let a = rng.gen_range(0..groups[i].members.len());
+        let b = rng.gen_range(0..groups[j].members.len());
+        if stratum(&groups[i].members[a]) != stratum(&groups[j].members[b]) {
+            continue;
+        }
+        let (x, y) = (groups[i].members[a].id.clone(), groups[j].members[b].id.clone());
+
+        let around = |check: &SwapCheck| broken(check.violations_around(&x, &y), check.penalty_around(&x, &y));
+        let before = around(&check);
+        swap(groups, (i, a), (j, b));
+        check.swapped(&x, j, &y, i);
+        // Whole numbers, so adding up their differences loses nothing
+        let swapped_constraints = constraints - before + around(&check);
+        let (cost_i, cost_j) = (score.of(&groups[i]), score.of(&groups[j]));
+        let delta = swapped_constraints - constraints + cost_i + cost_j - costs[i] - costs[j];
+        if delta <= 0.0 || rng.gen::<f64>() < (-delta / temperature).exp() {
+            constraints = swapped_constraints;
+            costs[i] = cost_i;
+            costs[j] = cost_j;
+            // Summed again rather than adding up deltas, so that rounding errors do not build up
+            current = total(&costs, constraints);
+            if current < best.0 {
+                best = (current, groups.to_vec());
+            }
+        } else {
+            swap(groups, (i, a), (j, b));
+            check.swapped(&x, i, &y, j);
+        }
+    }
+    log::info(|| message(Msg::TraceSwaps, &[&tries, &format!("{:.2}", start), &format!("{:.2}", best.0)]));
+    groups.clone_from_slice(&best.1);
+}
+
+/// Swap member `a` of group `i` with member `b` of group `j`, where `i < j`.
+fn swap(groups: &mut [Group], (i, a): (usize, usize), (j, b): (usize, usize)) {
+    let (left, right) = groups.split_at_mut(j);
+    std::mem::swap(&mut left[i].members[a], &mut right[0].members[b]);
+}
+
+/// Parse a time budget such as `2s`, `500ms` or `1m`; a bare number is in seconds.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| !(c.is_ascii_digit() || c == '.')) {
+        Some(at) => text.split_at(at),
+        None => (text, "s"),
+    };
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => return None,
+    };
+    (seconds.is_finite() && seconds > 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Write a time budget the way [`parse_duration`] reads it.
+pub fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 && duration.subsec_nanos() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grouping;
+    use rand::SeedableRng;
+
+    fn student(id: &str, gender: &str) -> Student {
+        let mut student = Student::new(id);
+        student.attributes.insert("gender".to_string(), gender.to_string());
+        student
+    }
+
+    #[test]
+    fn test_optimize_balances_groups() {
+        // Every group starts with a single gender
+        let mut groups: Vec<Group> = ["m", "m", "f", "f"]
+            .iter()
+            .enumerate()
+            .map(|(g, gender)| Group::from_members((0..3).map(|k| student(&format!("S{}{}", g, k), gender)).collect()))
+            .collect();
+        let config = GroupingConfig {
+            balance: vec!["gender".to_string()],
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        optimize(&mut groups, &[], &config, Duration::from_millis(200), &mut rng);
+
+        assert_eq!(groups.iter().map(|g| g.members.len()).collect::<Vec<_>>(), vec![3, 3, 3, 3]);
+        for group in &groups {
+            let men = group.members.iter().filter(|s| s.attribute("gender") == Some("m")).count();
+            assert!((1..=2).contains(&men), "{:?}", group);
+        }
+    }
+
+    #[test]
+    fn test_optimize_keeps_groups() {
+        let students: Vec<Student> = (1..=9).map(|i| Student::new(format!("S{:03}", i))).collect();
+        let mut groups = grouping::split_into_small_groups(students);
+        let kept = vec![groups[0].clone()];
+        let mut past = History::default();
+        past.record(&groups);
+        let config = GroupingConfig {
+            history: Some(past.clone()),
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        optimize(&mut groups, &kept, &config, Duration::from_millis(200), &mut rng);
+
+        assert_eq!(groups[0], kept[0]);
+        // Six students from two past groups of three always repeat at least one pair per group
+        assert_eq!(past.repeat_count(&groups[1..]), 2);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1m"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_duration("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("0s"), None);
+        assert_eq!(parse_duration("2h"), None);
+        assert_eq!(format_duration(Duration::from_secs(2)), "2s");
+        assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+    }
+}
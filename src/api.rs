@@ -0,0 +1,323 @@
+//! Grouping requests and results as JSON, shared by the HTTP server and the
+//! WebAssembly entry points.
+
+use crate::constraints::Constraints;
+use crate::genetic;
+use crate::group::{Group, GroupNames};
+use crate::grouping::{self, GroupingConfig, Mode, Sizing};
+use crate::i18n::{message, Lang, Msg};
+use crate::json::Value;
+use crate::leader;
+use crate::output::{self, Metadata};
+use crate::preference::Preferences;
+use crate::solver;
+use crate::strategy;
+use crate::student::Student;
+use std::collections::{HashMap, HashSet};
+
+/// Group the students described by a JSON request and return the result in
+/// the same shape as `--format json`. `timestamp` is recorded in the result.
+///
+/// The request is a JSON object:
+/// - `students`: student IDs, or objects with `id`, optional `name` and `attributes`
+/// - `groups`: groups entered by hand (arrays of IDs); full groups are kept
+///   as in interactive mode. Used instead of `students`.
+/// - `seed`, `balance` (attribute names), `spread_weights` (numeric attribute
+///   whose high values are spread across groups), `constraints` (constraints file text),
+///   `group_names` (labels used instead of A, B, ...), `assign_leader` (bool),
+///   `pairs` (bool, groups of 2 instead of 3), `group_count` (number of groups,
+///   as even in size as possible), `stratify` (attribute such as a section that
+///   every group mixes), `within_strata` (bool, never mix `stratify` values instead),
+///   `strategy` (name of a [`strategy::GroupingStrategy`]), `skill` (numeric
+///   attribute rating the students, required by `mixed-skill`), `group_by`
+///   (attribute names whose similar values `similar` puts together), `no_shuffle`
+///   (bool, remake incomplete `groups` in the order given), `population` and
+///   `generations` (numbers up to [`genetic::MAX_POPULATION`] and
+///   [`genetic::MAX_GENERATIONS`], for `genetic`), `preferences` (preferences file
+///   text, required by `preferences`): optional
+pub fn group(request: &Value, timestamp: &str) -> Result<Value, String> {
+    if !matches!(request, Value::Object(_)) {
+        return Err(message(Msg::ApiNotObject, &[]));
+    }
+
+    let mut names = HashMap::new();
+    let (groups, mode) = if let Some(groups) = request.get("groups").filter(|v| !v.is_null()) {
+        let groups = groups
+            .as_array()
+            .ok_or_else(|| message(Msg::ApiGroupsArray, &[]))?
+            .iter()
+            .map(|group| {
+                let members = group.as_array().ok_or_else(|| message(Msg::ApiGroupMembers, &[]))?;
+                let ids = members
+                    .iter()
+                    .map(|m| m.as_str().map(String::from).ok_or_else(|| message(Msg::ApiIdString, &[])))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Group::from_members(ids))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        (groups, Mode::Interactive)
+    } else {
+        let students = request
+            .get("students")
+            .and_then(Value::as_array)
+            .ok_or_else(|| message(Msg::ApiNoStudents, &[]))?;
+        let mut members = Vec::new();
+        for student in students {
+            let student = parse_student(student)?;
+            if let Some(name) = &student.name {
+                names.insert(student.id.clone(), name.clone());
+            }
+            members.push(student);
+        }
+        (vec![Group::from_members(members)], Mode::Batch)
+    };
+    let groups: Vec<Group> = groups.into_iter().filter(|g| !g.members.is_empty()).collect();
+    if groups.is_empty() {
+        return Err(message(Msg::ApiEmpty, &[]));
+    }
+
+    let seed = match request.get("seed").filter(|v| !v.is_null()) {
+        Some(seed) => seed.as_u64().ok_or_else(|| message(Msg::ApiInteger, &[&"seed", &0]))?,
+        None => grouping::random_seed(),
+    };
+    let balance = match request.get("balance").filter(|v| !v.is_null()) {
+        Some(keys) => keys
+            .as_array()
+            .ok_or_else(|| message(Msg::ApiAttributes, &[&"balance"]))?
+            .iter()
+            .map(|k| k.as_str().map(String::from).ok_or_else(|| message(Msg::ApiAttributes, &[&"balance"])))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    let spread_weights = match request.get("spread_weights").filter(|v| !v.is_null()) {
+        Some(key) => Some(key.as_str().ok_or_else(|| message(Msg::ApiAttribute, &[&"spread_weights"]))?.to_string()),
+        None => None,
+    };
+    let stratify = match request.get("stratify").filter(|v| !v.is_null()) {
+        Some(key) => Some(key.as_str().ok_or_else(|| message(Msg::ApiAttribute, &[&"stratify"]))?.to_string()),
+        None => None,
+    };
+    let within_strata = match request.get("within_strata").filter(|v| !v.is_null()) {
+        Some(Value::Bool(within)) => *within,
+        Some(_) => return Err(message(Msg::ApiBool, &[&"within_strata"])),
+        None => false,
+    };
+    if within_strata && stratify.is_none() {
+        return Err(message(Msg::OptionRequires, &[&"within_strata", &"stratify"]));
+    }
+    let skill = match request.get("skill").filter(|v| !v.is_null()) {
+        Some(key) => Some(key.as_str().ok_or_else(|| message(Msg::ApiAttribute, &[&"skill"]))?.to_string()),
+        None => None,
+    };
+    let sizing = match request.get("pairs").filter(|v| !v.is_null()) {
+        Some(Value::Bool(true)) => Sizing::Pairs,
+        Some(Value::Bool(false)) | None => Sizing::Triples,
+        Some(_) => return Err(message(Msg::ApiBool, &[&"pairs"])),
+    };
+    let sizing = match request.get("group_count").filter(|v| !v.is_null()) {
+        Some(_) if sizing == Sizing::Pairs => return Err(message(Msg::OptionConflict, &[&"pairs", &"group_count"])),
+        Some(count) => Sizing::Groups(
+            count
+                .as_u64()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| message(Msg::ApiInteger, &[&"group_count", &1]))? as usize,
+        ),
+        None => sizing,
+    };
+    let strategy = match request.get("strategy").filter(|v| !v.is_null()) {
+        Some(name) => {
+            let name = name.as_str().ok_or_else(|| message(Msg::ApiString, &[&"strategy"]))?;
+            let strategy = strategy::by_name(name).ok_or_else(|| {
+                message(Msg::OneOf, &[&"strategy", &Lang::current().list(&strategy::names()), &name])
+            })?;
+            Some(strategy)
+        }
+        None => None,
+    };
+    let mixes_skill = strategy.is_some_and(|strategy| strategy.name() == "mixed-skill");
+    if mixes_skill != skill.is_some() {
+        return Err(message(Msg::ApiOnlyForStrategy, &[&"skill", &"mixed-skill"]));
+    }
+    let group_by = match request.get("group_by").filter(|v| !v.is_null()) {
+        Some(keys) => keys
+            .as_array()
+            .ok_or_else(|| message(Msg::ApiAttributes, &[&"group_by"]))?
+            .iter()
+            .map(|k| k.as_str().map(String::from).ok_or_else(|| message(Msg::ApiAttributes, &[&"group_by"])))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    let no_shuffle = match request.get("no_shuffle").filter(|v| !v.is_null()) {
+        Some(Value::Bool(no_shuffle)) => *no_shuffle,
+        Some(_) => return Err(message(Msg::ApiBool, &[&"no_shuffle"])),
+        None => false,
+    };
+    let groups_similar = strategy.is_some_and(|strategy| strategy.name() == "similar");
+    if groups_similar == group_by.is_empty() {
+        return Err(message(Msg::ApiOnlyForStrategy, &[&"group_by", &"similar"]));
+    }
+    let count = |key: &str, least: usize, most: usize| -> Result<Option<usize>, String> {
+        match request.get(key).filter(|v| !v.is_null()) {
+            Some(value) => match value.as_u64().map(|n| n as usize).filter(|n| (least..=most).contains(n)) {
+                Some(n) => Ok(Some(n)),
+                None => Err(message(Msg::ApiIntegerRange, &[&key, &least, &most])),
+            },
+            None => Ok(None),
+        }
+    };
+    let population = count("population", 2, genetic::MAX_POPULATION)?;
+    let generations = count("generations", 1, genetic::MAX_GENERATIONS)?;
+    let breeds = strategy.is_some_and(|strategy| strategy.name() == "genetic");
+    if !breeds && (population.is_some() || generations.is_some()) {
+        let keys = Lang::current().list(&["population", "generations"]);
+        return Err(message(Msg::ApiOnlyForStrategy, &[&keys, &"genetic"]));
+    }
+    let preferences = match request.get("preferences").filter(|v| !v.is_null()) {
+        Some(text) => {
+            let text = text.as_str().ok_or_else(|| message(Msg::ApiPreferences, &[]))?;
+            Some(Preferences::parse(text).map_err(|e| format!("preferences: {}", e))?)
+        }
+        None => None,
+    };
+    let grants = strategy.is_some_and(|strategy| strategy.name() == "preferences");
+    if grants != preferences.is_some() {
+        return Err(message(Msg::ApiOnlyForStrategy, &[&"preferences", &"preferences"]));
+    }
+    let constraints = match request.get("constraints").filter(|v| !v.is_null()) {
+        Some(text) => {
+            let text = text.as_str().ok_or_else(|| message(Msg::ApiConstraints, &[]))?;
+            Constraints::parse(text).map_err(|e| format!("constraints: {}", e))?
+        }
+        None => Constraints::default(),
+    };
+
+    let group_names = match request.get("group_names").filter(|v| !v.is_null()) {
+        Some(names) => {
+            let names = names
+                .as_array()
+                .ok_or_else(|| message(Msg::ApiGroupNames, &[]))?
+                .iter()
+                .map(|n| n.as_str().ok_or_else(|| message(Msg::ApiGroupNames, &[])))
+                .collect::<Result<Vec<_>, _>>()?;
+            GroupNames::parse(&names.join("\n")).map_err(|e| format!("group_names: {}", e))?
+        }
+        None => GroupNames::default(),
+    };
+
+    let config = GroupingConfig {
+        mode,
+        sizing,
+        seed: Some(seed),
+        constraints: constraints.clone(),
+        balance,
+        spread_weights,
+        stratify,
+        within_strata,
+        skill,
+        group_by,
+        no_shuffle,
+        history: None,
+        optimize_iterations: None,
+        optimize_time: None,
+        population,
+        generations,
+        preferences,
+        strategy,
+        size_overrides: HashMap::new(),
+        pinned: HashSet::new(),
+    };
+    let assign_leader = match request.get("assign_leader").filter(|v| !v.is_null()) {
+        Some(Value::Bool(assign)) => *assign,
+        Some(_) => return Err(message(Msg::ApiBool, &[&"assign_leader"])),
+        None => false,
+    };
+    let shuffled = config.uses_rng() || assign_leader;
+    let mut rng = config.rng();
+    let (mode, settings) = (config.mode, config.settings());
+    let final_groups = grouping::regroup(groups, config).map_err(|e| message(Msg::ApiGroupingFailed, &[&e]))?;
+    let leaders = if assign_leader {
+        leader::choose_leaders(&final_groups, None, &mut rng)
+    } else {
+        Vec::new()
+    };
+
+    let metadata = Metadata {
+        timestamp: timestamp.to_string(),
+        seed: if shuffled { Some(seed) } else { None },
+        group_names,
+        leaders,
+        mode: Some(mode),
+        config: settings,
+        ..Default::default()
+    };
+    let mut result = output::to_json(&final_groups, &names, &metadata);
+    let mut warnings: Vec<Value> = constraints
+        .unsatisfied(&final_groups)
+        .iter()
+        .map(|v| Value::from(message(Msg::ApiUnsatisfied, &[v])))
+        .collect();
+    if !warnings.is_empty() {
+        let students: Vec<Student> = final_groups.iter().flat_map(|g| g.members.clone()).collect();
+        if let Some(conflict) = solver::explain(&students, sizing, &constraints) {
+            let lines = conflict.to_string();
+            let lines: Vec<&str> = lines.lines().collect();
+            warnings.push(Value::from(message(Msg::ApiConflict, &[&lines.join(" / ")])));
+        }
+    }
+    if let (Value::Object(pairs), false) = (&mut result, warnings.is_empty()) {
+        pairs.push(("warnings".to_string(), Value::Array(warnings)));
+    }
+    Ok(result)
+}
+
+/// Read one entry of `students`: either an ID or `{"id", "name", "attributes"}`.
+fn parse_student(value: &Value) -> Result<Student, String> {
+    if let Some(id) = value.as_str() {
+        return Ok(Student::new(id));
+    }
+    let id = value
+        .get("id")
+        .and_then(Value::as_str)
+        .ok_or_else(|| message(Msg::ApiStudent, &[]))?;
+    let mut student = Student::new(id);
+    student.name = value.get("name").and_then(Value::as_str).map(String::from);
+    if let Some(Value::Object(attributes)) = value.get("attributes") {
+        for (key, value) in attributes {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            student.attributes.insert(key.clone(), value);
+        }
+    }
+    Ok(student)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_keeps_full_groups() {
+        let request = Value::parse(r#"{"groups": [["S001", "S002", "S003"], ["S004"], ["S005"]], "seed": 1}"#).unwrap();
+        let result = group(&request, "2025-04-01T09:00:00Z").unwrap();
+
+        assert_eq!(result.get("timestamp").and_then(Value::as_str), Some("2025-04-01T09:00:00Z"));
+        assert_eq!(result.get("total_groups").and_then(Value::as_u64), Some(2));
+        let first = &result.get("groups").and_then(Value::as_array).unwrap()[0];
+        assert_eq!(first.get("size").and_then(Value::as_u64), Some(3));
+    }
+
+    #[test]
+    fn test_group_rejects_bad_requests() {
+        assert!(group(&Value::parse("[]").unwrap(), "").is_err());
+        assert!(group(&Value::parse(r#"{"students": [1]}"#).unwrap(), "").is_err());
+        assert!(group(&Value::parse(r#"{"students": ["S001"], "seed": -1}"#).unwrap(), "").is_err());
+        let huge = r#"{"students": ["S001", "S002"], "strategy": "genetic", "population": 100000000000}"#;
+        assert!(group(&Value::parse(huge).unwrap(), "").is_err());
+        let endless = r#"{"students": ["S001", "S002"], "strategy": "genetic", "generations": 100000000000}"#;
+        assert!(group(&Value::parse(endless).unwrap(), "").is_err());
+        let listed = r#"{"students": ["S001", "S002"], "constraints": ["apart S001 S002"]}"#;
+        assert!(group(&Value::parse(listed).unwrap(), "").is_err());
+    }
+}
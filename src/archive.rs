@@ -0,0 +1,108 @@
+//! Dated copies of every result (`--archive <DIR>`).
+//!
+//! Each run writes its result to the folder as a JSON export named after the
+//! time it was made, with a hash of the input added, so that the folder
+//! becomes a record of what was grouped, from which students and with which
+//! seed and settings. The files read back like any other export, for example
+//! with `--history`.
+
+use crate::digest;
+use crate::group::{Group, StudentId};
+use crate::json::Value;
+use crate::output::{self, GroupingResult};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The SHA-256 of the students given to the grouping, in hex: one line per
+/// input group with its IDs in order, separated by commas. The same roster
+/// typed or read in the same order always gives the same hash.
+pub fn input_hash(groups: &[Group]) -> String {
+    let mut text = String::new();
+    for group in groups {
+        text.push_str(&group.ids().map(String::as_str).collect::<Vec<_>>().join(","));
+        text.push('\n');
+    }
+    digest::to_hex(&digest::sha256(text.as_bytes()))
+}
+
+/// The file name for a result made at `timestamp` (RFC 3339), without the
+/// extension: `2025-04-01_093000` for `2025-04-01T09:30:00Z`.
+fn file_stem(timestamp: &str) -> String {
+    let (date, time) = timestamp.split_once('T').unwrap_or((timestamp, ""));
+    let time: String = time.chars().filter(char::is_ascii_digit).collect();
+    match time.is_empty() {
+        true => date.to_string(),
+        false => format!("{}_{}", date, time),
+    }
+}
+
+/// The result as archived: the JSON export with `input_sha256` before the totals.
+pub fn to_json(result: &GroupingResult, names: &HashMap<StudentId, String>, input: &[Group]) -> Value {
+    let mut value = output::to_json(&result.groups, names, &result.metadata);
+    if let Value::Object(pairs) = &mut value {
+        let at = pairs.iter().position(|(key, _)| key == "total_students").unwrap_or(pairs.len());
+        pairs.insert(at, ("input_sha256".to_string(), Value::from(input_hash(input).as_str())));
+    }
+    value
+}
+
+/// Write `result`, made from the `input` groups, into `dir` (created when
+/// missing), and return the path written. A second result in the same second
+/// gets `-2`, `-3`, ... after its time instead of replacing the first.
+pub fn write(dir: &Path, result: &GroupingResult, names: &HashMap<StudentId, String>, input: &[Group]) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let text = to_json(result, names, input).to_pretty_string() + "\n";
+    let stem = file_stem(&result.metadata.timestamp);
+    for n in 1.. {
+        let path = match n {
+            1 => dir.join(format!("{}.json", stem)),
+            _ => dir.join(format!("{}-{}.json", stem, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("a free file name is always found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Metadata;
+
+    #[test]
+    fn test_write() {
+        let dir = std::env::temp_dir().join(format!("grouping-tool-archive-{}", std::process::id()));
+        let input = vec![Group::from_members(vec!["S001".to_string(), "S002".to_string(), "S003".to_string()])];
+        let metadata = Metadata {
+            timestamp: "2025-04-01T09:30:00Z".to_string(),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let result = GroupingResult::new(input.clone(), metadata);
+
+        let first = write(&dir, &result, &HashMap::new(), &input).unwrap();
+        let second = write(&dir, &result, &HashMap::new(), &input).unwrap();
+        assert_eq!(first.file_name().unwrap(), "2025-04-01_093000.json");
+        assert_eq!(second.file_name().unwrap(), "2025-04-01_093000-2.json");
+
+        let value = Value::parse(&std::fs::read_to_string(&first).unwrap()).unwrap();
+        assert_eq!(value.get("input_sha256").and_then(Value::as_str), Some(input_hash(&input).as_str()));
+        assert_eq!(value.get("seed").and_then(Value::as_u64), Some(42));
+        // Archives read back like any other export
+        let (groups, _, read) = output::from_json(&value).unwrap();
+        assert_eq!((groups, read.seed), (input.clone(), Some(42)));
+
+        // The order of the input matters, since the same seed shuffles another order differently
+        let reversed = vec![Group::from_members(vec!["S003".to_string(), "S002".to_string(), "S001".to_string()])];
+        assert_ne!(input_hash(&input), input_hash(&reversed));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
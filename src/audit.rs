@@ -0,0 +1,607 @@
+//! A log of the edits made while entering students (`--session-log`), and
+//! the entry rebuilt from it (`replay`).
+//!
+//! Every add, delete, move, swap, group completion and undo is appended to the file
+//! as one JSON line with the time it was made, so that changes made during
+//! class, by whoever was at the keyboard, can be traced afterwards. Groups are
+//! written as letters (`A`, `B`, ...) whatever `--group-names` shows. The
+//! edits are counted even without a file, for `--stats`.
+//!
+//! The log ends with the seed and hash of the grouping made from the entry,
+//! so that `replay` can make it again and tell whether it came out the same.
+
+use grouping_tool::i18n::{message, Msg};
+use grouping_tool::json::Value;
+use grouping_tool::stats::Edits;
+use grouping_tool::student::Student;
+use grouping_tool::time;
+use grouping_tool::{group_index_to_letter, group_letter_to_index, Group, StudentId};
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// One thing done while entering students.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Entry started with groups closing at `limit` students, full-screen or
+    /// line by line, after `groups` completed groups and `students` students
+    /// carried over (`--resume`, or going back to the entry from `--confirm`)
+    Start {
+        limit: Option<usize>,
+        screen: bool,
+        groups: usize,
+        students: usize,
+    },
+    /// A student was added to `group`, with the attributes typed after the ID
+    Add { student: Student, group: usize },
+    /// A student was removed from `group`
+    Delete { id: StudentId, group: usize },
+    /// A student was moved from group `from` to group `to`
+    Move { id: StudentId, from: usize, to: usize },
+    /// A student in `group` traded places with one in `with_group`
+    Swap {
+        id: StudentId,
+        group: usize,
+        with: StudentId,
+        with_group: usize,
+    },
+    /// `group` was completed with `size` students, by filling up or by the user
+    Complete { group: usize, size: usize },
+    /// The last add, delete, move, swap or completion was undone
+    Undo,
+    /// `group` was pinned (`!`, `pin:`), or unpinned, so that it is kept as entered
+    Pin { group: usize, pinned: bool },
+    /// The grouping made from the entry, with its seed (when shuffled) and
+    /// [`grouping_tool::output::content_hash`]
+    Result { seed: Option<u64>, hash: String },
+}
+
+impl Event {
+    /// The event as a line of the log, made at `timestamp`.
+    pub fn to_json(&self, timestamp: &str) -> Value {
+        let letter = |group: &usize| Value::from(group_index_to_letter(*group));
+        let mut pairs = vec![("timestamp", Value::from(timestamp))];
+        match self {
+            Event::Start {
+                limit,
+                screen,
+                groups,
+                students,
+            } => {
+                pairs.push(("action", Value::from("start")));
+                pairs.push(("limit", Value::from(limit.map(|limit| limit as u64))));
+                pairs.push(("ui", Value::from(if *screen { "screen" } else { "line" })));
+                pairs.push(("resumed_groups", Value::from(*groups as u64)));
+                pairs.push(("resumed_students", Value::from(*students as u64)));
+            }
+            Event::Add { student, group } => {
+                pairs.push(("action", Value::from("add")));
+                pairs.push(("student", Value::from(student.id.as_str())));
+                pairs.push(("group", letter(group)));
+                if !student.attributes.is_empty() {
+                    let attributes = student
+                        .attributes
+                        .iter()
+                        .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+                        .collect();
+                    pairs.push(("attributes", Value::Object(attributes)));
+                }
+            }
+            Event::Delete { id, group } => {
+                pairs.push(("action", Value::from("delete")));
+                pairs.push(("student", Value::from(id.as_str())));
+                pairs.push(("group", letter(group)));
+            }
+            Event::Move { id, from, to } => {
+                pairs.push(("action", Value::from("move")));
+                pairs.push(("student", Value::from(id.as_str())));
+                pairs.push(("from", letter(from)));
+                pairs.push(("to", letter(to)));
+            }
+            Event::Swap {
+                id,
+                group,
+                with,
+                with_group,
+            } => {
+                pairs.push(("action", Value::from("swap")));
+                pairs.push(("student", Value::from(id.as_str())));
+                pairs.push(("group", letter(group)));
+                pairs.push(("with", Value::from(with.as_str())));
+                pairs.push(("with_group", letter(with_group)));
+            }
+            Event::Complete { group, size } => {
+                pairs.push(("action", Value::from("complete")));
+                pairs.push(("group", letter(group)));
+                pairs.push(("size", Value::from(*size as u64)));
+            }
+            Event::Undo => pairs.push(("action", Value::from("undo"))),
+            Event::Pin { group, pinned } => {
+                pairs.push(("action", Value::from(if *pinned { "pin" } else { "unpin" })));
+                pairs.push(("group", letter(group)));
+            }
+            Event::Result { seed, hash } => {
+                pairs.push(("action", Value::from("result")));
+                pairs.push(("seed", Value::from(*seed)));
+                pairs.push(("hash", Value::from(hash.as_str())));
+            }
+        }
+        Value::object(pairs)
+    }
+
+    /// Read a line written by [`Event::to_json`].
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let text = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .ok_or_else(|| message(Msg::MissingKey, &[&key]))
+        };
+        let count = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_u64)
+                .map(|n| n as usize)
+                .ok_or_else(|| message(Msg::MissingKey, &[&key]))
+        };
+        let group = |key: &str| {
+            let label = text(key)?;
+            group_letter_to_index(label).ok_or_else(|| message(Msg::LogBadGroup, &[&key, &label]))
+        };
+        let event = match text("action")? {
+            "start" => Event::Start {
+                limit: value.get("limit").and_then(Value::as_u64).map(|n| n as usize),
+                screen: text("ui")? == "screen",
+                groups: count("resumed_groups")?,
+                students: count("resumed_students")?,
+            },
+            "add" => {
+                let mut student = Student::new(text("student")?);
+                if let Some(Value::Object(attributes)) = value.get("attributes") {
+                    for (name, value) in attributes {
+                        if let Some(value) = value.as_str() {
+                            student.attributes.insert(name.clone(), value.to_string());
+                        }
+                    }
+                }
+                Event::Add {
+                    student,
+                    group: group("group")?,
+                }
+            }
+            "delete" => Event::Delete {
+                id: text("student")?.to_string(),
+                group: group("group")?,
+            },
+            "move" => Event::Move {
+                id: text("student")?.to_string(),
+                from: group("from")?,
+                to: group("to")?,
+            },
+            "swap" => Event::Swap {
+                id: text("student")?.to_string(),
+                group: group("group")?,
+                with: text("with")?.to_string(),
+                with_group: group("with_group")?,
+            },
+            "complete" => Event::Complete {
+                group: group("group")?,
+                size: count("size")?,
+            },
+            "undo" => Event::Undo,
+            action @ ("pin" | "unpin") => Event::Pin {
+                group: group("group")?,
+                pinned: action == "pin",
+            },
+            "result" => Event::Result {
+                seed: value.get("seed").and_then(Value::as_u64),
+                hash: text("hash")?.to_string(),
+            },
+            action => return Err(message(Msg::LogUnknownAction, &[&action])),
+        };
+        Ok(event)
+    }
+}
+
+/// The edits of one entry, appended to the log file, if any, as they happen.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    path: Option<String>,
+    edits: Edits,
+}
+
+impl AuditLog {
+    /// A log appending to the file at `path`, or only counting when `None`.
+    pub fn new(path: Option<&str>) -> Self {
+        AuditLog {
+            path: path.map(String::from),
+            edits: Edits::default(),
+        }
+    }
+
+    /// Count `event` and append it to the file, created when missing.
+    pub fn record(&mut self, event: &Event) -> Result<(), String> {
+        count(&mut self.edits, event);
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let line = event.to_json(&time::now_rfc3339());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// The edits recorded so far.
+    pub fn edits(&self) -> Edits {
+        self.edits
+    }
+}
+
+/// Add `event` to `edits` when it is one.
+fn count(edits: &mut Edits, event: &Event) {
+    match event {
+        Event::Add { .. } => edits.added += 1,
+        Event::Delete { .. } => edits.deleted += 1,
+        Event::Move { .. } => edits.moved += 1,
+        Event::Swap { .. } => edits.swapped += 1,
+        Event::Complete { .. } => edits.completed += 1,
+        Event::Undo => edits.undone += 1,
+        Event::Start { .. } | Event::Pin { .. } | Event::Result { .. } => {}
+    }
+}
+
+/// An entry made again from its log (`replay`): the last one in the file,
+/// together with the entries it continued.
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// Groups entered, the current one last
+    groups: Vec<Group>,
+    /// Whether the entry was full-screen, where groups emptied by a delete or
+    /// a move close up
+    screen: bool,
+    limit: Option<usize>,
+    /// Groups before each edit, for undo
+    undo_log: Vec<Vec<Group>>,
+    /// Students entered with attributes, keyed by ID
+    pub students: HashMap<StudentId, Student>,
+    /// Groups pinned by index
+    pub pins: HashSet<usize>,
+    pub edits: Edits,
+    /// Seed of the grouping made from the entry, if logged and shuffled
+    pub seed: Option<u64>,
+    /// Hash of the grouping made from the entry, if logged
+    pub hash: Option<String>,
+}
+
+impl Replay {
+    /// Make the entries logged in `text` again.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut replay = Replay::default();
+        let mut started = false;
+        for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let event = Value::parse(line)
+                .and_then(|value| Event::from_json(&value))
+                .map_err(|e| message(Msg::AtLine, &[&(i + 1), &e]))?;
+            if !started && !matches!(event, Event::Start { .. }) {
+                return Err(message(Msg::AtLine, &[&(i + 1), &message(Msg::LogNoStart, &[])]));
+            }
+            started = true;
+            replay.apply(event).map_err(|e| message(Msg::AtLine, &[&(i + 1), &e]))?;
+        }
+        if !started {
+            return Err(message(Msg::LogEmpty, &[]));
+        }
+        Ok(replay)
+    }
+
+    /// Index of the group being entered.
+    fn current(&self) -> usize {
+        self.groups.len() - 1
+    }
+
+    fn is_full(&self, group: usize) -> bool {
+        self.limit.is_some_and(|limit| self.groups[group].members.len() >= limit)
+    }
+
+    fn apply(&mut self, event: Event) -> Result<(), String> {
+        let mismatch = || message(Msg::LogMismatch, &[]);
+        count(&mut self.edits, &event);
+        match event {
+            Event::Start {
+                limit,
+                screen,
+                groups,
+                students,
+            } => {
+                if students == 0 && groups == 0 {
+                    *self = Replay::default();
+                    self.groups.push(Group::new());
+                } else {
+                    // The entry goes on from the one before, with its groups as they were handed over
+                    if self.groups.is_empty() {
+                        return Err(message(Msg::LogNoPrevious, &[]));
+                    }
+                    self.groups = self.entered();
+                    // A resumed entry may go on adding to its last group
+                    if self.groups.len() == groups {
+                        self.groups.push(Group::new());
+                    }
+                    let entered: usize = self.groups.iter().map(|g| g.members.len()).sum();
+                    if entered != students || self.current() != groups {
+                        return Err(message(Msg::LogResumeMismatch, &[&self.current(), &entered]));
+                    }
+                    self.seed = None;
+                    self.hash = None;
+                }
+                self.limit = limit;
+                self.screen = screen;
+                self.undo_log.clear();
+            }
+            Event::Add { student, group } => {
+                // `add:ID:GROUP` adds to a completed group too
+                if group > self.current() {
+                    return Err(mismatch());
+                }
+                self.undo_log.push(self.groups.clone());
+                if !student.attributes.is_empty() {
+                    self.students.insert(student.id.clone(), student.clone());
+                }
+                self.groups[group].members.push(student);
+            }
+            Event::Complete { group, .. } => {
+                let current = self.current();
+                if self.is_full(current) {
+                    // Filled by the add or move just before, and undone with it
+                    self.groups.push(Group::new());
+                } else if !self.groups[current].members.is_empty() {
+                    if group != current {
+                        return Err(mismatch());
+                    }
+                    self.undo_log.push(self.groups.clone());
+                    self.groups.push(Group::new());
+                }
+                // Otherwise the full-screen entry already started a new group
+            }
+            Event::Delete { id, group } => {
+                let position = self
+                    .groups
+                    .get(group)
+                    .and_then(|g| g.members.iter().position(|member| member == &id))
+                    .ok_or_else(mismatch)?;
+                self.undo_log.push(self.groups.clone());
+                self.groups[group].members.remove(position);
+                self.close_up();
+            }
+            Event::Move { id, from, to } => {
+                let position = self
+                    .groups
+                    .get(from)
+                    .and_then(|g| g.members.iter().position(|member| member == &id))
+                    .ok_or_else(mismatch)?;
+                if to > self.groups.len() || (to == self.groups.len() && !self.screen) {
+                    return Err(mismatch());
+                }
+                self.undo_log.push(self.groups.clone());
+                if to == self.groups.len() {
+                    self.groups.push(Group::new());
+                }
+                let student = self.groups[from].members.remove(position);
+                self.groups[to].members.push(student);
+                self.close_up();
+            }
+            Event::Swap {
+                id,
+                group,
+                with,
+                with_group,
+            } => {
+                let position = |id: &str, group: usize| {
+                    self.groups
+                        .get(group)
+                        .and_then(|g| g.members.iter().position(|member| member == id))
+                        .ok_or_else(mismatch)
+                };
+                let (at, with_at) = (position(&id, group)?, position(&with, with_group)?);
+                if group == with_group {
+                    return Err(mismatch());
+                }
+                self.undo_log.push(self.groups.clone());
+                let student = self.groups[group].members[at].clone();
+                let other = std::mem::replace(&mut self.groups[with_group].members[with_at], student);
+                self.groups[group].members[at] = other;
+            }
+            Event::Undo => self.groups = self.undo_log.pop().ok_or_else(mismatch)?,
+            Event::Pin { group, pinned: true } => {
+                self.pins.insert(group);
+            }
+            Event::Pin { group, pinned: false } => {
+                self.pins.remove(&group);
+            }
+            Event::Result { seed, hash } => {
+                self.seed = seed;
+                self.hash = Some(hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the groups a full-screen entry empties, keeping a group to enter into last.
+    fn close_up(&mut self) {
+        if !self.screen {
+            return;
+        }
+        let last = self.current();
+        let mut index = 0;
+        self.groups.retain(|group| {
+            let keep = index == last || !group.members.is_empty();
+            index += 1;
+            keep
+        });
+        if self.is_full(self.current()) {
+            self.groups.push(Group::new());
+        }
+    }
+
+    /// The groups as the entry handed them over to the grouping.
+    pub fn entered(&self) -> Vec<Group> {
+        let (current, completed) = self.groups.split_last().expect("there is always a current group");
+        let mut groups: Vec<Group> = completed
+            .iter()
+            // The full-screen entry leaves out empty groups, the line-based one only an empty current group
+            .filter(|group| !self.screen || !group.members.is_empty())
+            .cloned()
+            .collect();
+        if !current.members.is_empty() {
+            groups.push(current.clone());
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(groups: &[Group]) -> Vec<Vec<&str>> {
+        groups.iter().map(|g| g.ids().map(String::as_str).collect()).collect()
+    }
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("grouping-tool-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut log = AuditLog::new(path.to_str());
+        let student = Student::parse_line("S001,female", &["gender".to_string()]).unwrap();
+        let events = [
+            Event::Start {
+                limit: Some(3),
+                screen: false,
+                groups: 0,
+                students: 0,
+            },
+            Event::Add { student, group: 0 },
+            Event::Move { id: "S001".to_string(), from: 0, to: 1 },
+            Event::Swap {
+                id: "S001".to_string(),
+                group: 1,
+                with: "S002".to_string(),
+                with_group: 0,
+            },
+            Event::Complete { group: 1, size: 3 },
+            Event::Undo,
+            Event::Result { seed: Some(u64::MAX), hash: "16be628861ec".to_string() },
+        ];
+        for event in &events {
+            log.record(event).unwrap();
+        }
+        assert_eq!(
+            log.edits(),
+            Edits {
+                added: 1,
+                moved: 1,
+                swapped: 1,
+                completed: 1,
+                undone: 1,
+                ..Default::default()
+            }
+        );
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text.lines().map(|line| Value::parse(line).unwrap()).collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines.iter().all(|line| line.get("timestamp").and_then(Value::as_str).is_some()));
+        assert_eq!(lines[0].get("limit").and_then(Value::as_u64), Some(3));
+        assert_eq!(lines[1].get("attributes").and_then(|a| a.get("gender")).and_then(Value::as_str), Some("female"));
+        assert_eq!(lines[2].get("to").and_then(Value::as_str), Some("B"));
+        assert_eq!(lines[3].get("with_group").and_then(Value::as_str), Some("A"));
+        assert_eq!(lines[5].get("action").and_then(Value::as_str), Some("undo"));
+        // Every line reads back as the event written
+        let read: Vec<Event> = lines.iter().map(|line| Event::from_json(line).unwrap()).collect();
+        assert_eq!(read, events);
+        std::fs::remove_file(&path).unwrap();
+
+        // Without a file the edits are only counted
+        let mut log = AuditLog::new(None);
+        log.record(&Event::Delete { id: "S001".to_string(), group: 0 }).unwrap();
+        assert_eq!(log.edits().deleted, 1);
+    }
+
+    #[test]
+    fn test_replay_line_entry() {
+        let log = r#"{"timestamp":"2025-04-08T09:30:00Z","action":"start","limit":3,"ui":"line","resumed_groups":0,"resumed_students":0}
+{"timestamp":"2025-04-08T09:30:01Z","action":"add","student":"S001","group":"A","attributes":{"gender":"female"}}
+{"timestamp":"2025-04-08T09:30:02Z","action":"add","student":"S002","group":"A"}
+{"timestamp":"2025-04-08T09:30:03Z","action":"add","student":"S003","group":"A"}
+{"timestamp":"2025-04-08T09:30:03Z","action":"complete","group":"A","size":3}
+{"timestamp":"2025-04-08T09:30:04Z","action":"add","student":"S004","group":"B"}
+{"timestamp":"2025-04-08T09:30:05Z","action":"undo"}
+{"timestamp":"2025-04-08T09:30:06Z","action":"move","student":"S002","from":"A","to":"B"}
+{"timestamp":"2025-04-08T09:30:06Z","action":"swap","student":"S003","group":"A","with":"S002","with_group":"B"}
+{"timestamp":"2025-04-08T09:30:07Z","action":"delete","student":"S001","group":"A"}
+{"timestamp":"2025-04-08T09:30:08Z","action":"delete","student":"S002","group":"A"}
+{"timestamp":"2025-04-08T09:30:09Z","action":"pin","group":"B"}
+{"timestamp":"2025-04-08T09:30:10Z","action":"result","seed":42,"hash":"abc"}
+"#;
+        let replay = Replay::parse(log).unwrap();
+        // A group emptied in line-based entry keeps its place
+        assert_eq!(ids(&replay.entered()), vec![vec![], vec!["S003"]]);
+        assert_eq!(replay.pins, HashSet::from([1]));
+        assert_eq!(replay.students["S001"].attributes["gender"], "female");
+        assert_eq!((replay.seed, replay.hash.as_deref()), (Some(42), Some("abc")));
+        assert_eq!((replay.edits.added, replay.edits.undone, replay.edits.deleted), (4, 1, 2));
+
+        // A later entry in the same file starts over
+        let again = format!(
+            "{}{}\n{}\n",
+            log,
+            r#"{"timestamp":"2025-04-09T09:30:00Z","action":"start","limit":2,"ui":"line","resumed_groups":0,"resumed_students":0}"#,
+            r#"{"timestamp":"2025-04-09T09:30:01Z","action":"add","student":"S009","group":"A"}"#
+        );
+        let replay = Replay::parse(&again).unwrap();
+        assert_eq!(ids(&replay.entered()), vec![vec!["S009"]]);
+        assert_eq!(replay.seed, None);
+
+        assert!(Replay::parse(r#"{"timestamp":"","action":"undo"}"#).is_err());
+        let wrong = log.replace(r#""student":"S004","group":"B""#, r#""student":"S004","group":"C""#);
+        assert!(Replay::parse(&wrong).unwrap_err().starts_with("6 行目"));
+    }
+
+    #[test]
+    fn test_replay_screen_entry() {
+        let start = |groups: usize, students: usize| Event::Start {
+            limit: Some(2),
+            screen: true,
+            groups,
+            students,
+        };
+        let add = |id: &str, group: usize| Event::Add {
+            student: Student::new(id),
+            group,
+        };
+        let mut replay = Replay::default();
+        for event in [
+            start(0, 0),
+            add("S001", 0),
+            add("S002", 0),
+            Event::Complete { group: 0, size: 2 },
+            add("S003", 1),
+            // Moving the only member of A away closes the groups up
+            Event::Move { id: "S001".to_string(), from: 0, to: 1 },
+            Event::Move { id: "S002".to_string(), from: 0, to: 2 },
+        ] {
+            replay.apply(event).unwrap();
+        }
+        assert_eq!(ids(&replay.groups), vec![vec!["S003", "S001"], vec!["S002"]]);
+        replay.apply(Event::Undo).unwrap();
+        assert_eq!(ids(&replay.groups), vec![vec!["S002"], vec!["S003", "S001"], vec![]]);
+
+        // Going on from the groups handed over, as after `--confirm`
+        let entered = replay.entered();
+        replay.apply(start(2, 3)).unwrap();
+        assert_eq!(ids(&replay.groups[..2]), ids(&entered));
+        assert!(replay.apply(start(2, 4)).is_err());
+    }
+}
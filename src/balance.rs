@@ -0,0 +1,383 @@
+//! Attribute-balanced grouping: spreading attribute values (e.g. gender,
+//! skill level) and numeric weights (e.g. participation scores) evenly across
+//! groups instead of shuffling uniformly.
+
+use crate::group::Group;
+use crate::grouping::{Mode, Sizing};
+use crate::student::Student;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::Ordering;
+
+/// Reorganize groups like [`crate::grouping::regroup`], distributing the values
+/// of the `keys` attributes, and the high values of the numeric `weight`
+/// attribute, evenly across the new groups.
+///
+/// Students are shuffled, sorted by their attribute values (and then by
+/// weight, highest first), and then dealt
+/// into the groups in snake order (A, B, C, C, B, A, ...), so each value is
+/// spread over as many groups as possible. In interactive mode full groups are
+/// kept as they are; in batch mode everyone is regrouped and the input order
+/// is not preserved.
+pub fn reorganize_balanced<R: Rng + ?Sized>(
+    groups: Vec<Group>,
+    mode: Mode,
+    sizing: Sizing,
+    keys: &[String],
+    weight: Option<&str>,
+    rng: &mut R,
+) -> Vec<Group> {
+    let (mut final_groups, pool) = open_pool(groups, mode, sizing);
+    final_groups.extend(deal_balanced(pool, sizing, keys, weight, rng));
+    final_groups
+}
+
+/// Deal students into groups by tiers of the numeric `skill` rating
+/// (`--strategy mixed-skill`). Full groups are kept in interactive mode, as
+/// with [`reorganize_balanced`].
+pub fn reorganize_mixed_skill<R: Rng + ?Sized>(
+    groups: Vec<Group>,
+    mode: Mode,
+    sizing: Sizing,
+    skill: Option<&str>,
+    rng: &mut R,
+) -> Vec<Group> {
+    let (mut final_groups, pool) = open_pool(groups, mode, sizing);
+    final_groups.extend(deal_by_skill(pool, sizing, skill, rng));
+    final_groups
+}
+
+/// Put students with similar values of `keys` together (`--strategy similar`):
+/// they are sorted by the values and cut into groups in that order. Full
+/// groups are kept in interactive mode, as with [`reorganize_balanced`].
+pub fn reorganize_similar<R: Rng + ?Sized>(
+    groups: Vec<Group>,
+    mode: Mode,
+    sizing: Sizing,
+    keys: &[String],
+    rng: &mut R,
+) -> Vec<Group> {
+    let (mut final_groups, mut pool) = open_pool(groups, mode, sizing);
+    // Shuffle first so that students with equal values are cut apart at random
+    pool.shuffle(rng);
+    pool.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| compare_values(a.attribute(key), b.attribute(key)))
+            .find(|order| order.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    final_groups.extend(sizing.split(pool));
+    final_groups
+}
+
+/// Order of two attribute values: numbers by value (so that level 2 comes
+/// between 1 and 3), other values alphabetically ignoring case, missing values last.
+fn compare_values(a: Option<&str>, b: Option<&str>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            _ => a.to_lowercase().cmp(&b.to_lowercase()),
+        },
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    }
+}
+
+/// Split `groups` into those kept as they are (full groups in interactive
+/// mode) and the students to regroup.
+pub(crate) fn open_pool(groups: Vec<Group>, mode: Mode, sizing: Sizing) -> (Vec<Group>, Vec<Student>) {
+    let mut final_groups = Vec::new();
+    let mut pool: Vec<Student> = Vec::new();
+
+    for group in groups {
+        if mode == Mode::Interactive && sizing.is_full(&group) {
+            final_groups.push(group);
+        } else {
+            pool.extend(group.members);
+        }
+    }
+    // A lone leftover student would form a singleton; reopen the last full
+    // group, or with `--min` as many as it takes
+    while sizing.too_few(pool.len()) {
+        let Some(last) = final_groups.pop() else {
+            break;
+        };
+        pool.extend(last.members);
+    }
+    (final_groups, pool)
+}
+
+/// Deal `pool` into groups of the sizes `sizing` gives, one tier at a time:
+/// the strongest students by `skill` form the first tier of one student per
+/// group, the next strongest the second, and so on. Each tier goes to the
+/// groups in random order, so that every group gets one of the strongest and
+/// no group is made of novices only. Missing or non-numeric ratings are lowest.
+pub fn deal_by_skill<R: Rng + ?Sized>(
+    mut pool: Vec<Student>,
+    sizing: Sizing,
+    skill: Option<&str>,
+    rng: &mut R,
+) -> Vec<Group> {
+    let sizes = sizing.sizes(pool.len());
+
+    // Shuffle first so that students with equal ratings end up in random tiers
+    pool.shuffle(rng);
+    if let Some(skill) = skill {
+        let rating = |student: &Student| student.weight(skill).unwrap_or(f64::NEG_INFINITY);
+        pool.sort_by(|a, b| rating(b).total_cmp(&rating(a)));
+    }
+
+    let mut groups: Vec<Group> = sizes.iter().map(|_| Group::new()).collect();
+    let mut pool = pool.into_iter().peekable();
+    while pool.peek().is_some() {
+        let mut open: Vec<usize> = (0..sizes.len()).filter(|&i| groups[i].members.len() < sizes[i]).collect();
+        open.shuffle(rng);
+        for (i, student) in open.into_iter().zip(pool.by_ref()) {
+            groups[i].members.push(student);
+        }
+    }
+    groups
+}
+
+/// Deal `pool` into groups of the sizes `sizing` gives, with balanced
+/// attribute values and weights.
+pub fn deal_balanced<R: Rng + ?Sized>(
+    mut pool: Vec<Student>,
+    sizing: Sizing,
+    keys: &[String],
+    weight: Option<&str>,
+    rng: &mut R,
+) -> Vec<Group> {
+    let sizes = sizing.sizes(pool.len());
+
+    // Shuffle first so that students with equal values end up in random groups
+    pool.shuffle(rng);
+    match weight {
+        // Within equal attribute values, highest weight first; missing weights sort last
+        Some(weight) => {
+            let weight_of = |student: &Student| student.weight(weight).unwrap_or(f64::NEG_INFINITY);
+            pool.sort_by(|a, b| {
+                balance_key(a, keys)
+                    .cmp(&balance_key(b, keys))
+                    .then(weight_of(b).total_cmp(&weight_of(a)))
+            });
+        }
+        None => pool.sort_by_cached_key(|student| balance_key(student, keys)),
+    }
+
+    let mut groups: Vec<Group> = sizes.iter().map(|_| Group::new()).collect();
+    let order: Vec<usize> = (0..sizes.len()).chain((0..sizes.len()).rev()).collect();
+    let mut turn = 0;
+    for student in pool {
+        // Skip groups that are already at their target size
+        while groups[order[turn % order.len()]].members.len() >= sizes[order[turn % order.len()]] {
+            turn += 1;
+        }
+        groups[order[turn % order.len()]].members.push(student);
+        turn += 1;
+    }
+    groups
+}
+
+/// Split `groups` by the value of the `key` attribute (`--within-strata`), the
+/// values in order of first appearance and students without one last. A group
+/// entered by hand that mixes values is split up, keeping its order.
+pub fn split_strata(groups: Vec<Group>, key: &str) -> Vec<Vec<Group>> {
+    let mut values: Vec<Option<String>> = Vec::new();
+    let mut strata: Vec<Vec<Group>> = Vec::new();
+    for group in groups {
+        let mut parts: Vec<(Option<String>, Vec<Student>)> = Vec::new();
+        for student in group.members {
+            let value = student.attribute(key).map(str::to_lowercase);
+            match parts.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, members)) => members.push(student),
+                None => parts.push((value, vec![student])),
+            }
+        }
+        for (value, members) in parts {
+            let index = match values.iter().position(|v| *v == value) {
+                Some(index) => index,
+                None => {
+                    values.push(value);
+                    strata.push(Vec::new());
+                    values.len() - 1
+                }
+            };
+            strata[index].push(Group::from_members(members));
+        }
+    }
+
+    // Students without a value form the last stratum
+    if let Some(missing) = values.iter().position(Option::is_none) {
+        let stratum = strata.remove(missing);
+        strata.push(stratum);
+    }
+    strata
+}
+
+/// The values of `keys` for a student, used as the sort key when dealing.
+/// Missing attributes sort last.
+fn balance_key(student: &Student, keys: &[String]) -> Vec<(bool, String)> {
+    keys.iter()
+        .map(|key| match student.attribute(key) {
+            Some(value) => (false, value.to_lowercase()),
+            None => (true, String::new()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn students(entries: &[(&str, &str, &str)]) -> Vec<Student> {
+        let names = vec!["gender".to_string(), "skill".to_string()];
+        entries
+            .iter()
+            .map(|(id, gender, skill)| Student::parse_line(&format!("{},{},{}", id, gender, skill), &names).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_balances_single_attribute() {
+        // 4 advanced students among 12 should end up one per group
+        let entries: Vec<(String, &str, &str)> = (1..=12)
+            .map(|i| (format!("S{:03}", i), "x", if i <= 4 { "advanced" } else { "beginner" }))
+            .collect();
+        let entries: Vec<(&str, &str, &str)> = entries.iter().map(|(id, g, s)| (id.as_str(), *g, *s)).collect();
+        let students = students(&entries);
+        let keys = vec!["skill".to_string()];
+
+        for seed in 0..10 {
+            let groups = vec![Group::from_members(students.clone())];
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_balanced(groups, Mode::Batch, Sizing::Triples, &keys, None, &mut rng);
+
+            assert_eq!(result.len(), 4);
+            for group in &result {
+                let advanced = group
+                    .members
+                    .iter()
+                    .filter(|student| student.attribute("skill") == Some("advanced"))
+                    .count();
+                assert_eq!(advanced, 1, "seed {}: {:?}", seed, result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_keeps_group_sizes_and_full_groups() {
+        let students = students(&[
+            ("S001", "f", "a"),
+            ("S002", "m", "a"),
+            ("S003", "f", "b"),
+            ("S004", "m", "b"),
+            ("S005", "f", "a"),
+            ("S006", "m", "b"),
+            ("S007", "f", "b"),
+        ]);
+        let keys = vec!["gender".to_string(), "skill".to_string()];
+        let full = Group::from_members(students[..3].to_vec());
+        let groups = vec![
+            full.clone(),
+            Group::from_members(students[3..5].to_vec()),
+            Group::from_members(students[5..6].to_vec()),
+            Group::from_members(students[6..].to_vec()),
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        let result = reorganize_balanced(groups, Mode::Interactive, Sizing::Triples, &keys, None, &mut rng);
+
+        assert_eq!(result[0], full);
+        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn test_spreads_high_weights() {
+        let names = vec!["score".to_string()];
+        let students: Vec<Student> = (1..=9)
+            .map(|i| Student::parse_line(&format!("S{:03},{}", i, i * 10), &names).unwrap())
+            .collect();
+
+        for seed in 0..10 {
+            let groups = vec![Group::from_members(students.clone())];
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_balanced(groups, Mode::Batch, Sizing::Triples, &[], Some("score"), &mut rng);
+
+            // Dealt 90, 80, 70 / 60, 50, 40 / 30, 20, 10 in snake order, so the
+            // three highest scores land in different groups
+            let totals: Vec<f64> = result
+                .iter()
+                .map(|g| g.members.iter().filter_map(|student| student.weight("score")).sum())
+                .collect();
+            assert_eq!(totals, vec![160.0, 150.0, 140.0], "seed {}: {:?}", seed, result);
+        }
+    }
+
+    #[test]
+    fn test_deals_one_per_skill_tier() {
+        let names = vec!["skill".to_string()];
+        // 11 students in groups of 3, 3, 3 and 2: tiers 5 5 5 4 / 4 3 3 2 / 2 1 1
+        let students: Vec<Student> = (1..=11)
+            .map(|i| Student::parse_line(&format!("S{:03},{}", i, [5, 4, 3, 2, 1][(i - 1) % 5]), &names).unwrap())
+            .collect();
+
+        for seed in 0..10 {
+            let groups = vec![Group::from_members(students.clone())];
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_mixed_skill(groups, Mode::Batch, Sizing::Triples, Some("skill"), &mut rng);
+
+            let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+            assert_eq!(sizes, vec![3, 3, 3, 2]);
+            for group in &result {
+                let mut ratings: Vec<f64> = group.members.iter().filter_map(|s| s.weight("skill")).collect();
+                ratings.sort_by(|a, b| b.total_cmp(a));
+                // One student of each of the first two tiers in every group
+                assert!(ratings[0] >= 4.0, "seed {}: {:?}", seed, result);
+                assert!(ratings[1] >= 2.0 && ratings[2..].iter().all(|&r| r <= 2.0), "seed {}: {:?}", seed, result);
+            }
+        }
+    }
+    #[test]
+    fn test_groups_similar_values() {
+        let names = vec!["topic".to_string(), "level".to_string()];
+        let entries = ["web,2", "AI,10", "web,1", "ai,3", ",1", "web,3", "ai,1", "Web,2", "ai,2"];
+        let students: Vec<Student> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, fields)| Student::parse_line(&format!("S{:03},{}", i + 1, fields), &names).unwrap())
+            .collect();
+        let keys = vec!["topic".to_string(), "level".to_string()];
+
+        for seed in 0..5 {
+            let groups = vec![Group::from_members(students.clone())];
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_similar(groups, Mode::Batch, Sizing::Triples, &keys, &mut rng);
+            let ids: Vec<Vec<&str>> = result.iter().map(|g| g.ids().map(String::as_str).collect()).collect();
+            // ai 1, 2, 3 / ai 10, web 1, web 2 / web 2, web 3, no topic; either web 2 may come first
+            assert_eq!(ids[0], vec!["S007", "S009", "S004"], "seed {}", seed);
+            assert_eq!(ids[1][..2], ["S002", "S003"], "seed {}", seed);
+            assert_eq!(ids[2][1..], ["S006", "S005"], "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_split_strata() {
+        // The gender column stands in for a section here
+        let mut students = students(&[("S001", "A", "x"), ("S002", "b", "x"), ("S003", "a", "x"), ("S004", "B", "x")]);
+        students.push(Student::new("S005"));
+        let group = |ids: &[&str]| Group::from_members(students.iter().filter(|s| ids.contains(&s.id.as_str())).cloned().collect());
+        let groups = vec![group(&["S001", "S002", "S005"]), group(&["S003"]), group(&["S004"])];
+        let strata = split_strata(groups, "gender");
+
+        assert_eq!(
+            strata,
+            vec![
+                vec![group(&["S001"]), group(&["S003"])],
+                vec![group(&["S002"]), group(&["S004"])],
+                vec![group(&["S005"])],
+            ]
+        );
+    }
+}
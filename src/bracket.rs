@@ -0,0 +1,322 @@
+//! Single- and double-elimination brackets between groups, for an in-class
+//! competition after the grouping (`bracket` subcommand).
+//!
+//! Groups are seeded in order (A is the first seed) and paired the usual way,
+//! so that the top seeds meet as late as possible. When the number of groups
+//! is not a power of two, the top seeds get a bye and start in the second round.
+
+use crate::group::{Group, GroupNames};
+use crate::i18n::{Lang, Msg};
+use crate::json::Value;
+
+/// Whether a team is out after one loss or after two.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Elimination {
+    #[default]
+    Single,
+    /// Losers drop into a losers bracket, whose winner meets the winners'
+    /// champion in the grand final
+    Double,
+}
+
+/// Part of the bracket a match belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Winners,
+    Losers,
+    GrandFinal,
+}
+
+/// One of the two teams of a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// The group at this index
+    Team(usize),
+    /// The winner of the match with this number
+    WinnerOf(usize),
+    /// The loser of the match with this number
+    LoserOf(usize),
+}
+
+/// A match of the bracket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Numbered from 1 in the order the matches can be played within each side
+    pub number: usize,
+    pub side: Side,
+    /// Round within `side`, from 1
+    pub round: usize,
+    pub home: Slot,
+    pub away: Slot,
+}
+
+/// The matches between `teams` groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bracket {
+    pub elimination: Elimination,
+    pub teams: usize,
+    pub matches: Vec<Match>,
+}
+
+impl Bracket {
+    /// Plan the matches between `teams` groups. `None` for fewer than two.
+    pub fn new(teams: usize, elimination: Elimination) -> Option<Self> {
+        if teams < 2 {
+            return None;
+        }
+        let mut bracket = Bracket {
+            elimination,
+            teams,
+            matches: Vec::new(),
+        };
+
+        // Round 1 slots in seed order; seeds beyond the number of teams are byes
+        let size = teams.next_power_of_two();
+        let mut slots: Vec<Option<Slot>> = seed_order(size)
+            .into_iter()
+            .map(|seed| (seed <= teams).then_some(Slot::Team(seed - 1)))
+            .collect();
+        // Losers of each winners round, for the losers bracket
+        let mut dropped: Vec<Vec<Option<Slot>>> = Vec::new();
+        let mut round = 0;
+        while slots.len() > 1 {
+            let (winners, losers) = bracket.play_round(Side::Winners, &mut round, &pair_up(&slots));
+            slots = winners;
+            dropped.push(losers);
+        }
+        let champion = slots[0];
+        if elimination == Elimination::Single {
+            return Some(bracket);
+        }
+
+        // The first losers round pairs the losers of round 1; each later winners round
+        // drops its losers in against the survivors, in reverse order to put off rematches
+        let mut round = 0;
+        let mut survivors = match dropped.len() {
+            1 => dropped[0].clone(),
+            _ => bracket.play_round(Side::Losers, &mut round, &pair_up(&dropped[0])).0,
+        };
+        for (i, losers) in dropped.iter().enumerate().skip(1) {
+            let pairs: Vec<_> = survivors.iter().copied().zip(losers.iter().rev().copied()).collect();
+            survivors = bracket.play_round(Side::Losers, &mut round, &pairs).0;
+            if i + 1 < dropped.len() {
+                survivors = bracket.play_round(Side::Losers, &mut round, &pair_up(&survivors)).0;
+            }
+        }
+        let mut round = 0;
+        bracket.play_round(Side::GrandFinal, &mut round, &[(champion, survivors[0])]);
+        Some(bracket)
+    }
+
+    /// Add a match for each pair where both teams are known. A team without
+    /// an opponent goes through. Returns the winners and losers of the round.
+    fn play_round(
+        &mut self,
+        side: Side,
+        round: &mut usize,
+        pairs: &[(Option<Slot>, Option<Slot>)],
+    ) -> (Vec<Option<Slot>>, Vec<Option<Slot>>) {
+        let first = self.matches.len();
+        let mut winners = Vec::new();
+        let mut losers = Vec::new();
+        for &pair in pairs {
+            match pair {
+                (Some(home), Some(away)) => {
+                    let number = self.matches.len() + 1;
+                    self.matches.push(Match {
+                        number,
+                        side,
+                        round: *round + 1,
+                        home,
+                        away,
+                    });
+                    winners.push(Some(Slot::WinnerOf(number)));
+                    losers.push(Some(Slot::LoserOf(number)));
+                }
+                (Some(team), None) | (None, Some(team)) => {
+                    winners.push(Some(team));
+                    losers.push(None);
+                }
+                (None, None) => {
+                    winners.push(None);
+                    losers.push(None);
+                }
+            }
+        }
+        // A round made only of byes is not counted
+        if self.matches.len() > first {
+            *round += 1;
+        }
+        (winners, losers)
+    }
+
+    /// Number of rounds of `side`.
+    fn rounds(&self, side: Side) -> usize {
+        self.matches.iter().filter(|m| m.side == side).map(|m| m.round).max().unwrap_or(0)
+    }
+}
+
+/// Seeds in bracket order for `size` (a power of two) slots: 1 meets `size`,
+/// and the top two seeds are in different halves.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let count = order.len() * 2;
+        order = order.iter().flat_map(|&seed| [seed, count + 1 - seed]).collect();
+    }
+    order
+}
+
+fn pair_up(slots: &[Option<Slot>]) -> Vec<(Option<Slot>, Option<Slot>)> {
+    slots.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Render the bracket as text, one heading per round, with groups labelled by `labels`.
+pub fn render_text(bracket: &Bracket, labels: &GroupNames, lang: Lang) -> String {
+    let kind = match bracket.elimination {
+        Elimination::Single => lang.text(Msg::BracketSingle),
+        Elimination::Double => lang.text(Msg::BracketDouble),
+    };
+    let mut out = format!("\n=== {} ===\n", lang.format(Msg::BracketTitle, &[&kind]));
+    let slot = |slot: Slot| match slot {
+        Slot::Team(i) => labels.label(i),
+        Slot::WinnerOf(n) => lang.format(Msg::BracketWinnerOf, &[&n]),
+        Slot::LoserOf(n) => lang.format(Msg::BracketLoserOf, &[&n]),
+    };
+
+    let mut heading = None;
+    for m in &bracket.matches {
+        let round = match (bracket.elimination, m.side) {
+            (Elimination::Single, Side::Winners) if m.round == bracket.rounds(Side::Winners) => {
+                lang.text(Msg::BracketFinal).to_string()
+            }
+            (Elimination::Single, _) => lang.format(Msg::BracketRound, &[&m.round]),
+            (Elimination::Double, Side::Winners) => lang.format(Msg::BracketWinnersRound, &[&m.round]),
+            (Elimination::Double, Side::Losers) => lang.format(Msg::BracketLosersRound, &[&m.round]),
+            (Elimination::Double, Side::GrandFinal) => lang.text(Msg::BracketGrandFinal).to_string(),
+        };
+        if heading.as_ref() != Some(&round) {
+            out.push_str(&round);
+            out.push('\n');
+            heading = Some(round);
+        }
+        out.push_str(&lang.format(Msg::BracketMatch, &[&m.number, &slot(m.home), &slot(m.away)]));
+        out.push('\n');
+    }
+    out.push_str(&format!("\n{}\n", lang.format(Msg::BracketTotal, &[&bracket.matches.len()])));
+    out
+}
+
+/// The bracket as JSON: the teams with their members, and the matches in order.
+pub fn to_json(bracket: &Bracket, groups: &[Group], labels: &GroupNames) -> Value {
+    let teams = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            Value::object(vec![
+                ("label", Value::from(labels.label(i))),
+                (
+                    "members",
+                    Value::Array(group.ids().map(|m| Value::from(m.as_str())).collect()),
+                ),
+            ])
+        })
+        .collect();
+    let slot = |slot: Slot| match slot {
+        Slot::Team(i) => Value::object(vec![("team", Value::from(labels.label(i)))]),
+        Slot::WinnerOf(n) => Value::object(vec![("winner_of", Value::from(n))]),
+        Slot::LoserOf(n) => Value::object(vec![("loser_of", Value::from(n))]),
+    };
+    let matches = bracket
+        .matches
+        .iter()
+        .map(|m| {
+            let side = match m.side {
+                Side::Winners => "winners",
+                Side::Losers => "losers",
+                Side::GrandFinal => "grand_final",
+            };
+            Value::object(vec![
+                ("match", Value::from(m.number)),
+                ("bracket", Value::from(side)),
+                ("round", Value::from(m.round)),
+                ("home", slot(m.home)),
+                ("away", slot(m.away)),
+            ])
+        })
+        .collect();
+    let elimination = match bracket.elimination {
+        Elimination::Single => "single",
+        Elimination::Double => "double",
+    };
+    Value::object(vec![
+        ("elimination", Value::from(elimination)),
+        ("teams", Value::Array(teams)),
+        ("matches", Value::Array(matches)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_order() {
+        assert_eq!(seed_order(1), vec![1]);
+        assert_eq!(seed_order(4), vec![1, 4, 2, 3]);
+        assert_eq!(seed_order(8), vec![1, 8, 4, 5, 2, 7, 3, 6]);
+    }
+
+    #[test]
+    fn test_single_elimination_with_byes() {
+        assert!(Bracket::new(1, Elimination::Single).is_none());
+
+        // Five groups: A, B and C wait for round 2; D meets E
+        let bracket = Bracket::new(5, Elimination::Single).unwrap();
+        assert_eq!(bracket.matches.len(), 4);
+        assert_eq!(bracket.matches[0].home, Slot::Team(3));
+        assert_eq!(bracket.matches[0].away, Slot::Team(4));
+        assert_eq!(bracket.matches[1].home, Slot::Team(0));
+        assert_eq!(bracket.matches[1].away, Slot::WinnerOf(1));
+        let last = bracket.matches.last().unwrap();
+        assert_eq!((last.round, last.home, last.away), (3, Slot::WinnerOf(2), Slot::WinnerOf(3)));
+
+        let text = render_text(&bracket, &GroupNames::default(), Lang::Ja);
+        assert!(text.contains("1 回戦\n  試合 1: D vs E\n"), "{}", text);
+        assert!(text.contains("決勝\n  試合 4: 試合 2 の勝者 vs 試合 3 の勝者\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_double_elimination() {
+        // n teams always take 2n - 2 matches without a bracket reset
+        for teams in 2..=9 {
+            let bracket = Bracket::new(teams, Elimination::Double).unwrap();
+            assert_eq!(bracket.matches.len(), 2 * teams - 2, "{} teams", teams);
+            assert_eq!(bracket.matches.last().unwrap().side, Side::GrandFinal);
+            // Every loser of the winners bracket plays again
+            for m in bracket.matches.iter().filter(|m| m.side == Side::Winners) {
+                let dropped = bracket.matches.iter().any(|later| {
+                    later.home == Slot::LoserOf(m.number) || later.away == Slot::LoserOf(m.number)
+                });
+                assert!(dropped, "{} teams, match {}", teams, m.number);
+            }
+        }
+
+        let bracket = Bracket::new(2, Elimination::Double).unwrap();
+        assert_eq!(bracket.matches[1].home, Slot::WinnerOf(1));
+        assert_eq!(bracket.matches[1].away, Slot::LoserOf(1));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let groups = vec![
+            Group::from_members(vec!["S001".to_string()]),
+            Group::from_members(vec!["S002".to_string()]),
+        ];
+        let bracket = Bracket::new(2, Elimination::Single).unwrap();
+        assert_eq!(
+            to_json(&bracket, &groups, &GroupNames::default()).to_string(),
+            r#"{"elimination":"single","teams":[{"label":"A","members":["S001"]},{"label":"B","members":["S002"]}],"matches":[{"match":1,"bracket":"winners","round":1,"home":{"team":"A"},"away":{"team":"B"}}]}"#
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Reading the students of a Google Classroom course (`--classroom`).
+//!
+//! Classroom has no student numbers, so each student is identified by the
+//! email address of their school account. Without the
+//! `classroom.profile.emails` scope the API leaves the address out, and the
+//! Google user ID is used instead. Authentication is shared with
+//! [`crate::sheets`].
+
+use crate::http;
+use crate::json::Value;
+use crate::roster::RosterEntry;
+
+const API_URL: &str = "https://classroom.googleapis.com/v1/courses";
+
+/// Read every student of `course_id`, following the pages of the response.
+pub fn read_roster(course_id: &str, token: &str) -> Result<Vec<RosterEntry>, String> {
+    let auth = format!("Bearer {}", token);
+    let mut entries = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut url = format!("{}/{}/students?pageSize=100", API_URL, http::percent_encode(course_id));
+        if let Some(page_token) = &page_token {
+            url.push_str(&format!("&pageToken={}", http::percent_encode(page_token)));
+        }
+        let response = crate::sheets::check(http::send("GET", &url, &[("Authorization", &auth)], None)?)?;
+        entries.extend(entries_from_json(&response));
+        page_token = response
+            .get("nextPageToken")
+            .and_then(Value::as_str)
+            .filter(|t| !t.is_empty())
+            .map(String::from);
+        if page_token.is_none() {
+            return Ok(entries);
+        }
+    }
+}
+
+/// The trimmed string `value`, if it is one and not blank.
+fn text(value: Option<&Value>) -> Option<&str> {
+    value.and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// The students of one page of a `courses.students.list` response.
+fn entries_from_json(value: &Value) -> Vec<RosterEntry> {
+    value
+        .get("students")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|student| {
+            let profile = student.get("profile");
+            let id = text(profile.and_then(|p| p.get("emailAddress")))
+                .or_else(|| text(student.get("userId")))?;
+            let name = text(profile.and_then(|p| p.get("name")).and_then(|n| n.get("fullName")));
+            Some(RosterEntry {
+                id: id.to_string(),
+                name: name.map(String::from),
+                attributes: Default::default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_from_json() {
+        let response = Value::parse(
+            r#"{"students": [
+                {"courseId": "1", "userId": "101", "profile": {"id": "101", "name": {"givenName": "太郎", "familyName": "山田", "fullName": "山田 太郎"}, "emailAddress": "s001@school.example.jp"}},
+                {"courseId": "1", "userId": "102", "profile": {"id": "102", "name": {"fullName": "鈴木 花子"}}},
+                {"courseId": "1", "profile": {}}
+            ], "nextPageToken": "abc"}"#,
+        )
+        .unwrap();
+        let entries = entries_from_json(&response);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, "s001@school.example.jp");
+        assert_eq!(entries[0].name.as_deref(), Some("山田 太郎"));
+        // Without the emails scope the user ID is used
+        assert_eq!(entries[1].id, "102");
+        // A course without students has no `students`
+        assert!(entries_from_json(&Value::parse("{}").unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,2296 @@
+//! Command-line option parsing.
+
+use grouping_tool::anneal;
+use grouping_tool::genetic;
+use grouping_tool::group::LabelTheme;
+use grouping_tool::grouping::Sizing;
+use grouping_tool::history;
+use grouping_tool::strategy::{self, GroupingStrategy};
+use grouping_tool::i18n::{message, Lang, Msg};
+use grouping_tool::output::OutputFormat;
+use grouping_tool::roster::RosterFormat;
+use grouping_tool::time;
+use std::time::Duration;
+
+/// What the program should do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Make one grouping, reading students the way stdin suggests (the default)
+    #[default]
+    Group,
+    /// Make one grouping from students typed at the terminal (`interactive`)
+    Interactive,
+    /// Make one grouping from blank-line separated groups on stdin (`batch`)
+    Batch,
+    /// Make a plan of several sessions (`schedule --sessions <N>`)
+    Schedule,
+    /// Make a plan for a whole term, improved over all sessions together, as
+    /// one file per session (`plan --sessions <N> [--out <DIR>]`)
+    Plan,
+    /// Run the HTTP API server (`serve --listen <ADDR>`)
+    Serve,
+    /// Import a roster into the database (`import <CSV> --db <FILE>`)
+    Import,
+    /// Show past sessions from the database (`query --db <FILE> [--student <ID>]`)
+    Query,
+    /// Write a recorded grouping again in any output format (`export [--session <N>]`)
+    Export,
+    /// Make a tournament bracket between the groups of a recorded grouping (`bracket [--session <N>]`)
+    Bracket,
+    /// Shuffle the groups of a recorded grouping into a presentation order (`order [--slot-minutes <N>]`)
+    Order,
+    /// Give points to a group of a recorded grouping (`score <GROUP> <POINTS>`)
+    Score,
+    /// Rank the groups, or the students, by their points (`leaderboard [--by-student]`)
+    Leaderboard,
+    /// Combine groupings exported with `--format json` into one (`merge <FILE> <FILE>...`)
+    Merge,
+    /// Compare two groupings exported with `--format json` (`diff <OLD> <NEW>`)
+    Diff,
+    /// Check a result against the signature `--sign` wrote (`verify <FILE> --sign <KEYFILE>`)
+    Verify,
+    /// Enter the students again from a session log and group them (`replay <LOG>`)
+    Replay,
+}
+
+impl Command {
+    /// Every subcommand with the name it is typed as.
+    const SUBCOMMANDS: [(Command, &'static str); 16] = [
+        (Command::Interactive, "interactive"),
+        (Command::Batch, "batch"),
+        (Command::Export, "export"),
+        (Command::Bracket, "bracket"),
+        (Command::Order, "order"),
+        (Command::Score, "score"),
+        (Command::Leaderboard, "leaderboard"),
+        (Command::Merge, "merge"),
+        (Command::Diff, "diff"),
+        (Command::Verify, "verify"),
+        (Command::Replay, "replay"),
+        (Command::Schedule, "schedule"),
+        (Command::Plan, "plan"),
+        (Command::Serve, "serve"),
+        (Command::Import, "import"),
+        (Command::Query, "query"),
+    ];
+
+    /// The subcommand typed as `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::SUBCOMMANDS.iter().find(|(_, n)| *n == name).map(|&(command, _)| command)
+    }
+
+    /// The subcommand as typed, empty for [`Command::Group`].
+    pub fn name(self) -> &'static str {
+        Self::SUBCOMMANDS.iter().find(|(c, _)| *c == self).map_or("", |&(_, name)| name)
+    }
+
+    /// The subcommand the arguments start with, even when the rest of them are
+    /// wrong, so that an error can point at its help.
+    pub fn of_args(args: &[String]) -> Self {
+        args.first().and_then(|arg| Self::from_name(arg)).unwrap_or_default()
+    }
+}
+
+/// How `--balanced` splits a count that 2-3 person groups cannot divide evenly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizePolicy {
+    /// As few groups as possible, differing by one person at most (8 make 3+3+2)
+    Fewest,
+    /// Groups all of one size whenever the count allows it (8 make 2+2+2+2)
+    Equal,
+}
+
+impl std::str::FromStr for SizePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fewest" => Ok(SizePolicy::Fewest),
+            "equal" => Ok(SizePolicy::Equal),
+            _ => Err(message(Msg::OneOf, &[&"--balanced", &list(&["fewest", "equal"]), &s])),
+        }
+    }
+}
+
+/// Options given on the command line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Subcommand given as the first argument
+    pub command: Command,
+    /// Number of sessions to plan with `schedule` or `plan` (`--sessions <N>`)
+    pub sessions: Option<usize>,
+    /// List the plan of `schedule` or `plan` student by student (`--per-student`)
+    pub per_student: bool,
+    /// Write the plan of `schedule` or `plan` as an iCalendar file (`--ics <FILE>`)
+    pub ics: Option<String>,
+    /// Date of the first session in the calendar, in days since 1970-01-01 (`--start-date <YYYY-MM-DD>`)
+    pub start_date: Option<i64>,
+    /// Put the sessions of the calendar a week apart instead of a day (`--weekly`)
+    pub weekly: bool,
+    /// Address for `serve` to listen on (`--listen <ADDR>`)
+    pub listen: Option<String>,
+    /// SQLite database of students and past sessions (`--db <FILE>`)
+    pub db: Option<String>,
+    /// Student whose past groups `query` shows (`--student <ID>`)
+    pub student: Option<String>,
+    /// Recorded session `export` and `bracket` read, the latest by default (`--session <N>`)
+    pub session: Option<usize>,
+    /// Make `bracket` double elimination instead of single (`--double-elimination`)
+    pub double_elimination: bool,
+    /// Minutes each group presents for in the `order` (`--slot-minutes <N>`)
+    pub slot_minutes: Option<u32>,
+    /// Group `score` gives points to, by its label
+    pub score_group: Option<String>,
+    /// Points `score` gives, negative to take points away
+    pub points: Option<i64>,
+    /// Rank students over all sessions instead of the groups of one (`--by-student`)
+    pub by_student: bool,
+    /// Exported groupings `merge` combines or `diff` compares, in order, the file `verify` checks or the log `replay` reads
+    pub result_files: Vec<String>,
+    /// Key file to sign the written results with, or to check them with in `verify` (`--sign <KEYFILE>`)
+    pub sign: Option<String>,
+    /// Seed for the random shuffle (`--seed <u64>`)
+    pub seed: Option<u64>,
+    /// Make a recorded grouping again from its seed, without recording it (`--replay <SEED>`)
+    pub replay: Option<u64>,
+    /// CSV roster to read instead of stdin (`--input <FILE>`)
+    pub input: Option<String>,
+    /// Read the `--input` roster as exported by this LMS (`--from moodle`)
+    pub from: Option<RosterFormat>,
+    /// Column of the roster holding the student ID (`--id-column`)
+    pub id_column: Option<String>,
+    /// Pattern typed-in student IDs must match (`--id-pattern <REGEX>`, empty to disable)
+    pub id_pattern: Option<String>,
+    /// Column of the roster holding the student name (`--name-column`)
+    pub name_column: Option<String>,
+    /// File of `ID,名前` lines naming typed-in students (`--names <FILE>`)
+    pub names: Option<String>,
+    /// The roster has no header row (`--no-header`)
+    pub no_header: bool,
+    /// Google Sheets spreadsheet to read the roster from (`--sheet <ID>`)
+    pub sheet: Option<String>,
+    /// Range of the spreadsheet holding the roster (`--sheet-range`, e.g. `名簿!A:D`)
+    pub sheet_range: Option<String>,
+    /// Range of the spreadsheet to write the result to (`--sheet-output`, e.g. `結果!A1`)
+    pub sheet_output: Option<String>,
+    /// Google Classroom course to read the students from (`--classroom <COURSE_ID>`)
+    pub classroom: Option<String>,
+    /// OAuth credentials for Google Sheets and Classroom (`--google-credentials <FILE>`)
+    pub google_credentials: Option<String>,
+    /// Format of the final grouping (`--format <text|csv|json|markdown|jsonl|yaml>`, alias `--output`)
+    pub format: OutputFormat,
+    /// Handlebars-style template for the final grouping instead of the text listing (`--template <FILE>`)
+    pub template: Option<String>,
+    /// File to write the final grouping to instead of stdout, or the folder
+    /// of the files of `plan` (`--out <FILE>`)
+    pub out: Option<String>,
+    /// Also write an HTML report to this file (`--output-html <FILE>`)
+    pub output_html: Option<String>,
+    /// Also write a printable PDF to this file (`--pdf <FILE>`)
+    pub pdf: Option<String>,
+    /// Add one page per group to the PDF (`--pdf-per-group`)
+    pub pdf_per_group: bool,
+    /// Also write an Excel workbook to this file (`--xlsx <FILE>`, or `--output <FILE>.xlsx`)
+    pub xlsx: Option<String>,
+    /// Copy the result to the system clipboard (`--clipboard`)
+    pub clipboard: bool,
+    /// Constraints file with `together:`/`apart:` lines (`--constraints <FILE>`)
+    pub constraints: Option<String>,
+    /// CSV file of pairs to keep in different groups (`--avoid-file <FILE>`)
+    pub avoid_file: Option<String>,
+    /// CSV file of pairs who asked to work together (`--prefer-file <FILE>`)
+    pub prefer_file: Option<String>,
+    /// Labels used instead of A, B, ... (`--group-names Red,Blue,Green`)
+    pub group_names: Option<String>,
+    /// File with one group name per line (`--group-names-file <FILE>`)
+    pub group_names_file: Option<String>,
+    /// Ready-made group names such as animals (`--labels <THEME>`)
+    pub labels: Option<LabelTheme>,
+    /// Number the groups 1, 2, 3, ... instead of lettering them (`--numeric-labels`)
+    pub numeric_labels: bool,
+    /// List the members of each group in natural ID order in every output (`--sort-members`)
+    pub sort_members: bool,
+    /// Attributes to spread evenly across groups (`--balance gender,skill`)
+    pub balance: Vec<String>,
+    /// Numeric attribute whose high values are spread across groups (`--spread-weights score`)
+    pub spread_weights: Option<String>,
+    /// Attribute such as a section that every group mixes (`--stratify section`)
+    pub stratify: Option<String>,
+    /// Never mix the `--stratify` values instead (`--within-strata`)
+    pub within_strata: bool,
+    /// Numeric skill rating dealt by tier with `--strategy mixed-skill` (`--skill <ATTR>`)
+    pub skill: Option<String>,
+    /// Attributes whose similar values `--strategy similar` puts together (`--group-by level,topic`)
+    pub group_by: Vec<String>,
+    /// Classmates each student wishes for, granted by `--strategy preferences` (`--preferences <FILE>`)
+    pub preferences: Option<String>,
+    /// Remake incomplete groups in input order instead of shuffling (`--no-shuffle`)
+    pub no_shuffle: bool,
+    /// Avoid pairings from previous sessions (`--avoid-repeats`)
+    pub avoid_repeats: bool,
+    /// Lower repeated pairings further by swapping students (`--optimize-repeats`)
+    pub optimize_repeats: bool,
+    /// Swaps tried by `--optimize-repeats` (`--iterations <N>`)
+    pub iterations: Option<usize>,
+    /// Improve the grouping by swapping members (`--optimize`)
+    pub optimize: bool,
+    /// Time spent by `--optimize` (`--time-budget <DURATION>`)
+    pub time_budget: Option<Duration>,
+    /// Groupings bred at once by `--strategy genetic` (`--population <N>`)
+    pub population: Option<usize>,
+    /// Generations bred by `--strategy genetic` (`--generations <N>`)
+    pub generations: Option<usize>,
+    /// Name of the grouping algorithm (`--strategy <NAME>`)
+    pub strategy: Option<String>,
+    /// Choose one leader per group (`--assign-leader`)
+    pub assign_leader: bool,
+    /// History file instead of `~/.grouping-tool/history.jsonl` (`--history-file <FILE>`)
+    pub history_file: Option<String>,
+    /// Folder of results exported with `--format json` or `csv`, read as past
+    /// groupings besides the history (`--history <DIR>`)
+    pub history_dir: Option<String>,
+    /// Do not record this run in the history file (`--no-save-history`)
+    pub no_save_history: bool,
+    /// Folder to keep a dated copy of every result in (`--archive <DIR>`)
+    pub archive: Option<String>,
+    /// Use the line-based input instead of the full-screen UI (`--line-mode`)
+    pub line_mode: bool,
+    /// Finish the entry when nothing is typed for this many seconds (`--timeout <SECS>`)
+    pub timeout: Option<u64>,
+    /// Save the entry so far to this file after every change (`--save-session <FILE>`)
+    pub save_session: Option<String>,
+    /// Continue the entry saved in this file (`--resume <FILE>`)
+    pub resume: Option<String>,
+    /// Append every edit made during interactive entry to this file (`--session-log <FILE>`)
+    pub session_log: Option<String>,
+    /// Roster file to group again each time it changes (`--watch <FILE>`)
+    pub watch: Option<String>,
+    /// Classroom to seat the groups in: `ROWSxCOLUMNS` or a template file (`--seating <SPEC>`)
+    pub seating: Option<String>,
+    /// CSV file the seating chart is written to (`--seating-out <FILE>`)
+    pub seating_out: Option<String>,
+    /// Grouping exported with `--format json` to add the students entered now to (`--append <FILE>`)
+    pub append: Option<String>,
+    /// Write pseudonyms instead of student IDs in every output (`--anonymize`)
+    pub anonymize: bool,
+    /// Mapping file of `--anonymize` instead of `~/.grouping-tool/pseudonyms.csv` (`--pseudonym-file <FILE>`)
+    pub pseudonym_file: Option<String>,
+    /// Print each group as soon as it is formed from piped input (`--stream`)
+    pub stream: bool,
+    /// Make pairs, with one group of 3 when the count is odd (`--pairs`)
+    pub pairs: bool,
+    /// Split into exactly this many groups, as even in size as possible (`--groups <N>`)
+    pub groups: Option<usize>,
+    /// Prefer evenly sized groups of 2-3 as the policy says (`--balanced <POLICY>`)
+    pub balanced: Option<SizePolicy>,
+    /// Smallest group to make, 2 unless given (`--min <N>`)
+    pub min_size: Option<usize>,
+    /// Largest group to make, 3 unless given (`--max <N>`)
+    pub max_size: Option<usize>,
+    /// Show the grouping for approval before writing anything (`--confirm`)
+    pub confirm: bool,
+    /// Report group sizes, repeated pairs and constraints after the result (`--stats`)
+    pub stats: bool,
+    /// Language of messages and results (`--lang <ja|en>`, detected from `LANG` otherwise)
+    pub lang: Option<Lang>,
+    /// Configuration file instead of `~/.config/grouping-tool/config.toml` (`--config <FILE>`)
+    pub config: Option<String>,
+    /// Slack incoming webhook to post the result to (`--slack-webhook <URL>`)
+    pub slack_webhook: Option<String>,
+    /// Discord webhook to post the result to (`--discord-webhook <URL>`)
+    pub discord_webhook: Option<String>,
+    /// How much of the reorganization to trace on stderr (`-v`, `-vv`)
+    pub verbose: u8,
+    /// Print only the result, without prompts or notices (`--quiet`, `-q`)
+    pub quiet: bool,
+    /// Print without colors even to a terminal (`--no-color`)
+    pub no_color: bool,
+    /// Microsoft Teams incoming webhook to post the result to (`--teams-webhook <URL>`)
+    pub teams_webhook: Option<String>,
+    /// Whether `--help` was requested
+    pub help: bool,
+}
+
+pub const USAGE: &str = "\
+使い方: grouping-tool [サブコマンド] [オプション]
+        grouping-tool schedule --sessions <回数> [オプション]
+        grouping-tool plan --sessions <回数> [--out <フォルダ>] [オプション]
+        grouping-tool export [--session <回>] [オプション]
+        grouping-tool bracket [--session <回>] [--double-elimination]
+        grouping-tool order [--session <回>] [--slot-minutes <分>]
+        grouping-tool score <グループ> <点数> [--session <回>]
+        grouping-tool leaderboard [--session <回>] [--by-student]
+        grouping-tool merge <結果JSON> <結果JSON>... [オプション]
+        grouping-tool diff <変更前JSON> <変更後JSON>
+        grouping-tool verify <ファイル> --sign <鍵ファイル>
+        grouping-tool replay <操作の記録> [オプション]
+        grouping-tool serve [--listen <アドレス>]
+        grouping-tool import <名簿CSV> --db <ファイル>
+        grouping-tool query --db <ファイル> [--student <学籍番号>]
+
+サブコマンド（省略すると、端末なら interactive、パイプなら batch と同じです）:
+  interactive            端末で学籍番号を入力してグループ分けします
+  batch                  標準入力から空行区切りのグループを読み込みます（端末に貼り付けるときにも使えます）
+  schedule               複数回分のグループ分けをまとめて作り、なるべく毎回違う人と組むようにします
+  plan                   学期全体のグループ分けを全回まとめて最適化し、回ごとのファイルと一覧を書き出します
+  export                 記録済みのグループ分けを、指定した形式（CSV、HTML、PDFなど）で書き出し直します
+  bracket                記録済みのグループ分けから、グループ対抗のトーナメント表を作ります
+  order                  記録済みのグループ分けのグループを、ランダムな発表順に並べます
+  score                  記録済みのグループ分けのグループに得点を記録します
+  leaderboard            記録した得点のランキングを表示します
+  merge                  --format json で書き出した複数の結果（クラスごとなど）を1つにまとめます
+  diff                   --format json で書き出した2つの結果を比べ、グループが変わった学生とペアの変化を表示します
+  verify                 --sign で署名した結果のファイルが、署名したときのままか確かめます
+  replay                 --session-log で記録した対話入力をそのまま入力し直し、グループ分けを作り直します
+  serve                  HTTPサーバーを起動し、POST /group でグループ分けを返します
+  import                 名簿（CSV、--sheet または --classroom）をデータベースに取り込みます
+  query                  データベースに記録された過去のグループ分けを表示します
+
+  詳しくは grouping-tool <サブコマンド> --help を見てください
+
+オプション:
+  --sessions <回数>      schedule、plan で作る回数
+  --per-student          schedule、plan で、学生ごとに各回のグループと一緒に組む人を一覧にします
+  --ics <ファイル>       schedule、plan で、各回のグループを iCalendar（.ics）に書き出します（--start-date が必要）
+  --start-date <日付>    --ics で最初の回の日付（YYYY-MM-DD）。以降は1日ごと
+  --weekly               --ics で各回を1週間ごとにします
+  --session <回>         export で書き出す回、bracket、order、score、leaderboard で使う回（既定: 最新）
+  --double-elimination   bracket をダブルエリミネーション（2敗で敗退）にします
+  --slot-minutes <分>    order で、各グループの発表時間を割り当てます
+  --by-student           leaderboard で、全回の合計点による学生のランキングを表示します
+  --listen <アドレス>    serve で待ち受けるアドレス（既定: 127.0.0.1:8080）
+  --db <ファイル>        SQLiteデータベースに名簿と結果を記録します（履歴ファイルの代わり）
+  --student <学籍番号>   query でその学生の過去のグループだけを表示します
+  --seed <数値>          乱数のシードを指定します（同じ入力とシードで同じグループ分けを再現できます）
+  --replay <シード>      記録されたグループ分けをそのシードで作り直し、記録と同じか確かめます（履歴には保存しません）
+  --id-pattern <正規表現> 入力した学籍番号の形式（既定: ^[A-Z]\\d{3,}$、空文字で確認しない）
+  --input <ファイル>     標準入力の代わりにCSV形式の名簿を読み込みます
+  --from moodle <ファイル> Moodle の参加者一覧などの書き出しを名簿として読み込みます（ID number を学籍番号にします）
+  --id-column <列>       学籍番号の列（列番号(1始まり)またはヘッダー名、既定: 1）
+  --name-column <列>     氏名の列（列番号(1始まり)またはヘッダー名）
+  --no-header            名簿の1行目をヘッダーとして読み飛ばしません
+  --names <ファイル>     学籍番号と氏名の対応（S001,山田太郎 の形式）を読み込み、結果や入力の確認に氏名を表示します
+  --sheet <ID>           Googleスプレッドシートから名簿を読み込みます（--id-column などはCSVと同じ）
+  --sheet-range <範囲>   名簿の範囲（例: 名簿!A:D、既定: 最初のシート全体）
+  --sheet-output <範囲>  結果を同じスプレッドシートに書き込みます（例: 結果!A1）
+  --classroom <コースID>  Google Classroom のコースの学生を名簿として読み込みます（メールアドレスを学籍番号にします）
+  --google-credentials <ファイル> OAuthの認証情報（既定: ~/.grouping-tool/google-oauth.json）
+  --group-names <名前,...> A, B, ... の代わりに使うグループ名（例: 赤,青,緑。足りない分は 赤 2 のように繰り返します）
+  --group-names-file <ファイル> グループ名を1行に1つずつ書いたファイル
+  --labels <種類>        用意されたグループ名を使います（animals: パンダ, コアラ, ...、colors、planets、kana: あ, い, ...）
+  --numeric-labels       グループを A, B, ... の代わりに 1, 2, 3, ... と番号で呼びます
+  --sort-members         各グループのメンバーを学籍番号順（S2 は S10 の前）に並べて出力します
+  --balance <属性,...>   指定した属性が各グループに均等に分かれるようにします（例: gender,skill）
+  --spread-weights <属性> 数値の属性（参加点など）が高い学生を各グループに分散させます
+                         標準入力では S001,female,advanced のように学籍番号の後に属性を書きます
+  --stratify <属性>      クラス（section）などの属性が各グループで混ざるようにします
+  --within-strata        --stratify の属性の値ごとにグループを作り、混ぜないようにします
+  --constraints <ファイル> グループ分けの制約ファイル（例: together: S001,S007 / apart: S003,S015）
+  --avoid-file <ファイル> 同じグループにしない2人を1行ずつ並べたCSV（例: S003,S015）
+  --prefer-file <ファイル> 一緒に組みたいと希望した2人を1行ずつ並べたCSV。--optimize などでなるべく叶えます
+  --avoid-repeats        過去のグループ分けで同じグループになったペアをなるべく避けます
+  --optimize-repeats     学生の入れ替えを繰り返し、過去と同じペアの数をさらに減らします
+  --iterations <回数>    --optimize-repeats で試す入れ替えの回数（既定: 10000）
+  --optimize             組んだ後に学生の入れ替えを繰り返し、制約・属性のバランス・過去との重複をまとめて改善します
+  --time-budget <時間>   --optimize にかける時間（例: 2s、500ms、1m、既定: 2s）
+  --strategy <名前>      グループ分けの方法: auto（既定）、random、balanced、optimized、mixed-skill、similar、genetic、preferences
+  --skill <属性>         mixed-skill で使う技能の評価（数値の属性。高いほど上級）
+  --group-by <属性,...>  similar で値の近い学生を同じグループにする属性（例: level,topic）
+  --preferences <ファイル> preferences で使う、各学生が組みたい相手の希望（例: S001: S005,S007）
+  --population <数>      genetic で一度に交配するグループ分けの数（既定: 50、最大: 1000）
+  --generations <数>     genetic で交配を繰り返す世代数（既定: 200、最大: 10000）
+  --no-shuffle           未完成のグループをシャッフルせず、入力した順に詰めて組み直します
+  --assign-leader        各グループのリーダーを1人選びます（--avoid-repeats と一緒に使うと過去のリーダーを避けます）
+  --history-file <ファイル> 履歴ファイル（既定: ~/.grouping-tool/history.jsonl）
+  --no-save-history      今回の結果を履歴ファイルに保存しません
+  --history <フォルダ>   --format json / csv で書き出した過去の結果を、履歴に加えて読み込みます
+  --archive <フォルダ>   毎回の結果を、設定・シード・入力のハッシュとともに日時の名前のファイルに保存します
+  --sign <鍵ファイル>    --out などで書き出した結果に署名します（<ファイル>.sig。鍵がなければ作ります）
+  --format <形式>        結果の出力形式（text, csv, json, markdown, jsonl, yaml、既定: text。--output も同じ）
+  --template <ファイル>  結果をテンプレート（{{#each groups}}{{label}}: ...{{/each}} の形式）に当てはめて出力します
+  --out <ファイル>       結果を標準出力の代わりにファイルへ書き出します
+  --output-html <ファイル> グループごとのカードを並べたHTMLレポートを書き出します
+  --pdf <ファイル>        印刷用のPDFを書き出します
+  --pdf-per-group        PDFにグループごとのページ（配布用）を追加します
+  --xlsx <ファイル>       割り当て表とグループごとの表をExcelファイルに書き出します（--output 結果.xlsx も同じ）
+  --slack-webhook <URL>  結果をSlackのチャンネルに投稿します（Incoming Webhook のURL）
+  --discord-webhook <URL> 結果をDiscordのチャンネルに投稿します（Webhook のURL）
+  --teams-webhook <URL>  結果をMicrosoft Teamsのチャンネルに投稿します（Incoming Webhook のURL）
+  --clipboard            結果（選択した出力形式）をクリップボードにコピーします
+  --line-mode            対話入力で全画面表示を使わず、1行ずつ入力します（delete:学籍番号 で削除、move:学籍番号:グループ で移動、swap:学籍番号:学籍番号 で入れ替え、add:学籍番号:グループ でグループを指定して追加）
+  --no-color             端末への表示に色を付けません（環境変数 NO_COLOR でも同じ）
+  -q, --quiet            入力の案内や確認のメッセージを出さず、結果だけを標準出力に書き出します
+  -v, -vv                グループを組み直す手順を標準エラー出力に書き出します（-vv はグループと学生ごとに詳しく）
+  --timeout <秒>         対話入力で指定した秒数だけ何も入力がないと、入力を終えて結果を表示します
+  --save-session <ファイル> 入力の途中経過を変更のたびにファイルへ保存します
+  --resume <ファイル>    保存した途中経過から入力を再開します（そのファイルへの保存も続けます）
+  --session-log <ファイル> 対話入力での追加・削除・移動・入れ替え・グループの完成・取り消しを、日時とともにファイルに追記します
+  --watch <ファイル>     名簿ファイル（--input と同じ形式）を監視し、保存するたびにグループ分けを表示し直します
+  --stream               パイプ入力をためずに読み込み、グループができるたびに出力します（履歴には保存されません。重複を見つけるため学籍番号だけは覚えておきます）
+  --anonymize            すべての出力で、学籍番号の代わりに仮名（P001 など）を使い、氏名を出しません
+  --pseudonym-file <ファイル> 学籍番号と仮名の対応を保存するファイル（既定: ~/.grouping-tool/pseudonyms.csv）
+  --append <ファイル>    --format json で書き出した結果に、入力した学生（遅れて来た学生）を追加します
+  --seating <行x列|ファイル> 教室の座席（5x6 または座席図のファイル）にグループごとに並べた座席表を表示します
+  --seating-out <ファイル> 座席表をCSVで書き出します
+  --pairs                2人組を作ります（人数が奇数のときは1組だけ3人）
+  --groups <数>          人数を決める代わりに、指定した数のグループにできるだけ均等に分けます
+  --balanced <方針>      2〜3人のグループの人数をそろえます（fewest: グループ数を最少に 8人→3+3+2、equal: 同じ人数に 8人→2+2+2+2）
+  --min <数>             グループの最少人数（既定: 2）。これより少ないグループは作りません
+  --max <数>             グループの最大人数（既定: 3）
+  --confirm              結果を出力する前に表示し、確定・組み直し・入力への戻りを選べるようにします
+  --stats                結果の後に、グループの人数、過去と重複したペア、満たした制約の数を表示します
+  --lang <言語>          メッセージと結果の言語（ja, en。既定: 環境変数 LANG から判定）
+  --config <ファイル>    既定値を書いた設定ファイル（既定: ~/.config/grouping-tool/config.toml）
+  -h, --help             このヘルプを表示します";
+
+pub const USAGE_EN: &str = "\
+Usage: grouping-tool [SUBCOMMAND] [OPTIONS]
+       grouping-tool schedule --sessions <N> [OPTIONS]
+       grouping-tool plan --sessions <N> [--out <DIR>] [OPTIONS]
+       grouping-tool export [--session <N>] [OPTIONS]
+       grouping-tool bracket [--session <N>] [--double-elimination]
+       grouping-tool order [--session <N>] [--slot-minutes <MIN>]
+       grouping-tool score <GROUP> <POINTS> [--session <N>]
+       grouping-tool leaderboard [--session <N>] [--by-student]
+       grouping-tool merge <RESULT_JSON> <RESULT_JSON>... [OPTIONS]
+       grouping-tool diff <OLD_JSON> <NEW_JSON>
+       grouping-tool verify <FILE> --sign <KEYFILE>
+       grouping-tool replay <SESSION_LOG> [OPTIONS]
+       grouping-tool serve [--listen <ADDR>]
+       grouping-tool import <ROSTER_CSV> --db <FILE>
+       grouping-tool query --db <FILE> [--student <ID>]
+
+Subcommands (without one, interactive is used at a terminal and batch for piped input):
+  interactive            Type student IDs at the terminal and group them
+  batch                  Read blank-line separated groups from stdin (also for pasting into a terminal)
+  schedule               Plan several sessions at once, pairing students with new people each time
+  plan                   Optimize the groupings of a whole term together and write one file per session and an overview
+  export                 Write a recorded grouping again in another format (CSV, HTML, PDF, ...)
+  bracket                Make a tournament bracket between the groups of a recorded grouping
+  order                  Shuffle the groups of a recorded grouping into a presentation order
+  score                  Record points for a group of a recorded grouping
+  leaderboard            Show the ranking of the recorded points
+  merge                  Combine results written with --format json (one per class, ...) into one
+  diff                   Compare two results written with --format json: who changed groups and which pairs changed
+  verify                 Check that a result signed with --sign is unchanged since it was written
+  replay                 Enter the students of an entry recorded with --session-log again and make its grouping again
+  serve                  Run an HTTP server that answers POST /group with a grouping
+  import                 Import a roster (CSV, --sheet or --classroom) into the database
+  query                  Show past groupings recorded in the database
+
+  See grouping-tool <SUBCOMMAND> --help for details
+
+Options:
+  --sessions <N>         Number of sessions for schedule and plan
+  --per-student          List each student's group and partners in every session of schedule and plan
+  --ics <FILE>           With schedule and plan, write the groups of each session as an iCalendar (.ics) file (needs --start-date)
+  --start-date <DATE>    Date of the first session in the --ics calendar (YYYY-MM-DD); the others follow a day apart
+  --weekly               Put the sessions of the --ics calendar a week apart
+  --session <N>          Session for export to write or bracket, order, score and leaderboard to use (default: the latest)
+  --double-elimination   Make the bracket double elimination (out after two losses)
+  --slot-minutes <MIN>   With order, give each group a time slot of this many minutes
+  --by-student           With leaderboard, rank students by their total points over all sessions
+
+  --listen <ADDR>        Address for serve to listen on (default: 127.0.0.1:8080)
+  --db <FILE>            Record the roster and results in an SQLite database (instead of the history file)
+  --student <ID>         With query, show only the past groups of this student
+  --seed <N>             Seed for the shuffle (the same input and seed give the same grouping)
+  --replay <SEED>        Make a recorded grouping again from its seed and check it against the record (not saved to the history)
+  --id-pattern <REGEX>   Format of typed-in student IDs (default: ^[A-Z]\\d{3,}$, empty to skip the check)
+  --input <FILE>         Read a CSV roster instead of stdin
+  --from moodle <FILE>   Read a Moodle export (participants, users or grades) as the roster, with the ID number as the student ID
+  --id-column <COLUMN>   Column of the student ID (1-based number or header name, default: 1)
+  --name-column <COLUMN> Column of the student name (1-based number or header name)
+  --no-header            Do not skip the first row of the roster as a header
+  --names <FILE>         Read IDs and names (lines like S001,Taro Yamada) and show the names in results and entry
+  --sheet <ID>           Read the roster from a Google Sheets spreadsheet (--id-column etc. work as for CSV)
+  --sheet-range <RANGE>  Range of the roster (e.g. Roster!A:D, default: the whole first sheet)
+  --sheet-output <RANGE> Write the result to the same spreadsheet (e.g. Result!A1)
+  --classroom <COURSE_ID> Read the students of a Google Classroom course (email addresses become the student IDs)
+  --google-credentials <FILE> OAuth credentials (default: ~/.grouping-tool/google-oauth.json)
+  --group-names <NAME,...> Group names to use instead of A, B, ... (e.g. Red,Blue,Green; repeated as Red 2 when they run out)
+  --group-names-file <FILE> File with one group name per line
+  --labels <THEME>       Use ready-made group names (animals: Panda, Koala, ...; colors; planets; kana: あ, い, ...)
+  --numeric-labels       Number the groups 1, 2, 3, ... instead of lettering them A, B, ...
+  --sort-members         List the members of each group in student ID order (S2 before S10)
+  --balance <ATTR,...>   Spread these attributes evenly across groups (e.g. gender,skill)
+  --spread-weights <ATTR> Spread students with high values of this numeric attribute (e.g. a participation score) across groups
+                         On stdin, write attributes after the ID, as in S001,female,advanced
+  --stratify <ATTR>      Mix the values of this attribute (e.g. section) within every group
+  --within-strata        Group each value of the --stratify attribute on its own, never mixing them
+  --constraints <FILE>   Constraints file (e.g. together: S001,S007 / apart: S003,S015)
+  --avoid-file <FILE>    CSV of pairs to keep in different groups, one pair per row (e.g. S003,S015)
+  --prefer-file <FILE>   CSV of pairs who asked to work together; --optimize and others honor as many as they can
+  --avoid-repeats        Avoid pairs who were grouped together in past sessions
+  --optimize-repeats     Swap students repeatedly to cut the number of repeated pairs further
+  --iterations <N>       Swaps tried by --optimize-repeats (default: 10000)
+  --optimize             Then swap students repeatedly to improve constraints, attribute balance and repeated pairs together
+  --time-budget <TIME>   Time spent by --optimize (e.g. 2s, 500ms, 1m; default: 2s)
+  --strategy <NAME>      Grouping algorithm: auto (default), random, balanced, optimized, mixed-skill, similar, genetic, preferences
+  --skill <ATTR>         Skill rating used by mixed-skill (a numeric attribute; higher is stronger)
+  --group-by <ATTR,...>  Attributes whose similar values similar puts together (e.g. level,topic)
+  --preferences <FILE>   Classmates each student wishes for, used by preferences (e.g. S001: S005,S007)
+  --population <N>       Groupings bred at once by genetic (default: 50, at most 1000)
+  --generations <N>      Generations bred by genetic (default: 200, at most 10000)
+  --no-shuffle           Remake incomplete groups in the order students were entered, without shuffling
+  --assign-leader        Choose one leader per group (with --avoid-repeats, past leaders are passed over)
+  --history-file <FILE>  History file (default: ~/.grouping-tool/history.jsonl)
+  --no-save-history      Do not save this result to the history file
+  --history <DIR>        Also read the past results exported into this folder with --format json or csv
+  --archive <DIR>        Keep every result in this folder, with its settings, seed and input hash, in a file named by date
+  --sign <KEYFILE>       Sign the results written with --out and the like (<FILE>.sig); the key is made when missing
+  --format <FORMAT>      Output format (text, csv, json, markdown, jsonl, yaml; default: text; --output is the same)
+  --template <FILE>      Fill in a template ({{#each groups}}{{label}}: ...{{/each}} style) with the result
+  --out <FILE>           Write the result to a file instead of stdout
+  --output-html <FILE>   Write an HTML report with one card per group
+  --pdf <FILE>           Write a printable PDF
+  --pdf-per-group        Add one page per group (for handing out) to the PDF
+  --xlsx <FILE>          Write an Excel workbook with the assignment table and one column per group (same as --output FILE.xlsx)
+  --slack-webhook <URL>  Post the result to a Slack channel (Incoming Webhook URL)
+  --discord-webhook <URL> Post the result to a Discord channel (Webhook URL)
+  --teams-webhook <URL>  Post the result to a Microsoft Teams channel (Incoming Webhook URL)
+  --clipboard            Copy the result (in the chosen format) to the clipboard
+  --line-mode            Enter students line by line instead of the full-screen UI (delete:ID to delete, move:ID:GROUP to move, swap:ID:ID to swap, add:ID:GROUP to add to a group)
+  --no-color             Do not color the output on a terminal (same as setting NO_COLOR)
+  -q, --quiet            Print only the result on stdout, without prompts or confirmation messages
+  -v, -vv                Trace how the groups are reorganized on stderr (-vv: every group and student)
+  --timeout <SECS>       Finish the interactive entry and show the result when nothing is typed for SECS seconds
+  --save-session <FILE>  Save the entry so far to a file after every change
+  --resume <FILE>        Continue the entry saved in a file (and keep saving to it)
+  --session-log <FILE>   Append every add, delete, move, swap, group completion and undo of the interactive entry to a file, with the time
+  --watch <FILE>         Watch a roster file (same format as --input) and print the grouping again each time it is saved
+  --stream               Read piped input without buffering it and print each group as soon as it is formed (not saved to the history; the IDs alone are kept, to catch duplicates)
+  --anonymize            Write pseudonyms (P001, ...) instead of student IDs, and no names, in every output
+  --pseudonym-file <FILE> File keeping the pseudonym of each student (default: ~/.grouping-tool/pseudonyms.csv)
+  --append <FILE>        Add the students entered now (late arrivals) to a grouping written with --format json
+  --seating <ROWSxCOLS|FILE> Seat each group together in the classroom (5x6 or a seat template file) and print the chart
+  --seating-out <FILE>   Write the seating chart as CSV
+  --pairs                Make pairs (with one group of 3 when the count is odd)
+  --groups <N>           Split into exactly N groups, as even in size as possible, instead of fixing the size
+  --balanced <POLICY>    Even out the sizes of 2-3 person groups (fewest: fewest groups, 8 make 3+3+2; equal: one size, 8 make 2+2+2+2)
+  --min <N>              Smallest group size (default: 2); no group is made smaller
+  --max <N>              Largest group size (default: 3)
+  --confirm              Preview the grouping and accept, reshuffle or go back to editing before it is written
+  --stats                After the result, report group sizes, pairs repeated from the history and satisfied constraints
+  --lang <LANG>          Language of messages and results (ja, en; default: detected from LANG)
+  --config <FILE>        Configuration file with default options (default: ~/.config/grouping-tool/config.toml)
+  -h, --help             Show this help";
+
+/// The help text for `command` in `lang`. Subcommands have a short help of
+/// their own; the default command shows every option.
+pub fn usage(command: Command, lang: Lang) -> &'static str {
+    match (command, lang) {
+        (Command::Group, Lang::Ja) => USAGE,
+        (Command::Group, Lang::En) => USAGE_EN,
+        (Command::Interactive, Lang::Ja) => "\
+使い方: grouping-tool interactive [オプション]
+
+端末で学籍番号を1人ずつ入力し、3人ごとのグループにします。Unix/Macでは全画面で入力し、
+--line-mode を指定すると1行ずつ入力します（delete:学籍番号、move:学籍番号:グループ、
+swap:学籍番号:学籍番号、add:学籍番号:グループ、undo が使えます）。
+標準入力が端末でない場合はエラーになります。
+
+よく使うオプション: --id-pattern, --group-names, --balance, --constraints, --avoid-repeats,
+--assign-leader, --format, --out（すべてのオプションは grouping-tool --help）",
+        (Command::Interactive, Lang::En) => "\
+Usage: grouping-tool interactive [OPTIONS]
+
+Type student IDs one at a time at the terminal; every 3 students make a group. On Unix/Mac the
+input is full-screen; with --line-mode it is line by line (delete:ID, move:ID:GROUP, swap:ID:ID,
+add:ID:GROUP and undo work).
+It is an error when stdin is not a terminal.
+
+Common options: --id-pattern, --group-names, --balance, --constraints, --avoid-repeats,
+--assign-leader, --format, --out (all options: grouping-tool --help)",
+        (Command::Batch, Lang::Ja) => "\
+使い方: grouping-tool batch [オプション] < 名簿.txt
+
+標準入力から1行に1人ずつ学籍番号を読み込みます。空行はグループの区切りです。
+読み込んだ学生は、入力したグループの人数に関係なく3人（または2人）のグループに分け直されます。
+端末から実行した場合も案内を表示せず、Ctrl+D まで読み込みます（貼り付け用）。
+
+よく使うオプション: --seed, --balance, --constraints, --avoid-repeats, --format, --out
+（すべてのオプションは grouping-tool --help）",
+        (Command::Batch, Lang::En) => "\
+Usage: grouping-tool batch [OPTIONS] < roster.txt
+
+Read one student ID per line from stdin. Blank lines separate groups.
+The students are regrouped into groups of 3 (or 2) whatever the size of the groups read.
+At a terminal no prompts are shown and input is read until Ctrl+D (for pasting).
+
+Common options: --seed, --balance, --constraints, --avoid-repeats, --format, --out
+(all options: grouping-tool --help)",
+        (Command::Schedule, Lang::Ja) => "\
+使い方: grouping-tool schedule --sessions <回数> [オプション]
+
+複数回分のグループ分けをまとめて作り、なるべく毎回違う人と組むようにします。
+学生は --input、--sheet、--classroom、標準入力のいずれかから読み込みます。結果は履歴に保存されません。
+--output-html、--pdf、--xlsx、--sheet-output、--slack-webhook、--discord-webhook、--teams-webhook、--assign-leader は使えません。",
+        (Command::Schedule, Lang::En) => "\
+Usage: grouping-tool schedule --sessions <N> [OPTIONS]
+
+Plan several sessions at once, pairing students with new people each time.
+Students are read from --input, --sheet, --classroom or stdin. The plan is not saved to the history.
+--output-html, --pdf, --xlsx, --sheet-output, --slack-webhook, --discord-webhook, --teams-webhook and --assign-leader are not available.",
+        (Command::Plan, Lang::Ja) => "\
+使い方: grouping-tool plan --sessions <回数> [--out <フォルダ>] [オプション]
+
+学期の全回のグループ分けを作り、1回ずつ前の回を避けるだけでなく、全回を通して同じ人と
+組む回数が少なくなるように、まとめて入れ替えて改善します。
+--out のフォルダ（既定: plan）に、回ごとのファイル（session-01.txt など）と全回の一覧
+（schedule.txt など。拡張子は --format に合わせます）を書き出します。結果は履歴に保存されません。
+使えないオプションは schedule と同じです。",
+        (Command::Plan, Lang::En) => "\
+Usage: grouping-tool plan --sessions <N> [--out <DIR>] [OPTIONS]
+
+Make the groupings of every session of a term and improve them together, so that pairs meet
+again as rarely as possible over the whole term rather than only avoiding the earlier sessions.
+One file per session (session-01.txt, ...) and an overview of all sessions (schedule.txt, ...;
+the extension follows --format) are written to the --out folder (default: plan). The plan is not
+saved to the history. The options schedule does not take are not available either.",
+        (Command::Export, Lang::Ja) => "\
+使い方: grouping-tool export [--session <回>] [オプション]
+
+記録済みのグループ分けを書き出し直します。--db を指定するとデータベースから、指定しないと
+履歴ファイル（--history-file）から読み込みます。--session を省略すると最新の回を書き出します。
+
+出力のオプション: --format, --template, --out, --output-html, --pdf, --pdf-per-group, --xlsx, --clipboard,
+--slack-webhook, --discord-webhook, --teams-webhook, --seating, --seating-out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Export, Lang::En) => "\
+Usage: grouping-tool export [--session <N>] [OPTIONS]
+
+Write a recorded grouping again. With --db it is read from the database, otherwise from the
+history file (--history-file). Without --session the latest session is written.
+
+Output options: --format, --template, --out, --output-html, --pdf, --pdf-per-group, --xlsx, --clipboard,
+--slack-webhook, --discord-webhook, --teams-webhook, --seating, --seating-out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Bracket, Lang::Ja) => "\
+使い方: grouping-tool bracket [--session <回>] [--double-elimination] [オプション]
+
+記録済みのグループ分けのグループ同士で対戦するトーナメント表を作ります。グループは A から順に
+シードされ、グループ数が2のべき乗でないときは上位のシードが1回戦を免除されます。
+--double-elimination を指定すると、2敗で敗退するダブルエリミネーションにします。
+読み込み元は export と同じです（--db または --history-file、--session）。
+
+出力のオプション: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Bracket, Lang::En) => "\
+Usage: grouping-tool bracket [--session <N>] [--double-elimination] [OPTIONS]
+
+Make a tournament bracket between the groups of a recorded grouping. Groups are seeded from A
+onwards; when the number of groups is not a power of two, the top seeds skip the first round.
+With --double-elimination a group is out after two losses instead of one.
+The grouping is read as by export (--db or --history-file, --session).
+
+Output options: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Order, Lang::Ja) => "\
+使い方: grouping-tool order [--session <回>] [--slot-minutes <分>] [オプション]
+
+記録済みのグループ分けのグループをランダムな順に並べ、発表順として表示します。
+--slot-minutes を指定すると、各グループに発表時間（開始〜終了）を割り当てます。
+--seed を指定すると同じ順番を再現できます。読み込み元は export と同じです（--db または --history-file、--session）。
+
+出力のオプション: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Order, Lang::En) => "\
+Usage: grouping-tool order [--session <N>] [--slot-minutes <MIN>] [OPTIONS]
+
+Shuffle the groups of a recorded grouping into a random presentation order.
+With --slot-minutes each group is given a time slot (start-end).
+--seed reproduces the same order. The grouping is read as by export (--db or --history-file, --session).
+
+Output options: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Score, Lang::Ja) => "\
+使い方: grouping-tool score <グループ> <点数> [--session <回>]
+
+記録済みのグループ分けのグループに得点を記録します。同じグループに何度でも記録でき、合計されます。
+マイナスの点数で減点や訂正ができます。グループは A、B、...（--group-names を使った回はその名前）で指定します。
+得点は --db を指定するとデータベースに、指定しないと履歴ファイル（--history-file）に記録します。",
+        (Command::Score, Lang::En) => "\
+Usage: grouping-tool score <GROUP> <POINTS> [--session <N>]
+
+Record points for a group of a recorded grouping. Points given to a group several times are added up,
+and negative points take points away. Give the group as A, B, ... (or its name with --group-names).
+The points are recorded in the database with --db, otherwise in the history file (--history-file).",
+        (Command::Leaderboard, Lang::Ja) => "\
+使い方: grouping-tool leaderboard [--session <回>] [--by-student]
+
+記録した得点の多い順にグループを並べます（同点は同じ順位）。--by-student を指定すると、
+全回を通して所属したグループの得点を合計し、学生のランキングを表示します。
+
+出力のオプション: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Leaderboard, Lang::En) => "\
+Usage: grouping-tool leaderboard [--session <N>] [--by-student]
+
+Rank the groups by their recorded points (equal points share a place). With --by-student,
+rank students by the points of their groups added up over all sessions.
+
+Output options: --format (text, json), --out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Merge, Lang::Ja) => "\
+使い方: grouping-tool merge <結果JSON> <結果JSON>... [オプション]
+
+--format json で書き出した結果を、指定した順に1つにまとめます。グループはそのままで、
+名前が重ならないように A から（--group-names を指定するとその名前で）付け直します。
+同じ学籍番号が複数の結果にあるとエラーになります。1人のグループや大きすぎるグループ、
+全員を分け直した場合と人数の組み合わせが違うときは警告を表示します（--pairs、--groups の基準で確認します）。
+結果は履歴に保存されません。
+
+出力のオプション: --format, --template, --out, --output-html, --pdf, --pdf-per-group, --xlsx, --clipboard,
+--slack-webhook, --discord-webhook, --teams-webhook, --seating, --seating-out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Merge, Lang::En) => "\
+Usage: grouping-tool merge <RESULT_JSON> <RESULT_JSON>... [OPTIONS]
+
+Combine results written with --format json, in the order given. The groups are kept as they are
+and labelled again from A (or with --group-names) so that no two share a label.
+A student found in more than one result is an error. Groups of one, groups that are too large, and
+sizes that differ from a fresh split of everyone are reported as warnings (judged by --pairs and --groups).
+The result is not saved to the history.
+
+Output options: --format, --template, --out, --output-html, --pdf, --pdf-per-group, --xlsx, --clipboard,
+--slack-webhook, --discord-webhook, --teams-webhook, --seating, --seating-out, --group-names, --labels, --numeric-labels, --sort-members, --lang",
+        (Command::Diff, Lang::Ja) => "\
+使い方: grouping-tool diff <変更前JSON> <変更後JSON>
+
+--format json で書き出した2つの結果を比べます。グループは名前ではなくメンバーで比べるので、
+グループの順番や名前が変わっただけでは変更になりません。一緒になる人が変わった学生、
+片方にだけいる学生、解消したペアと新しくできたペアを表示します。制約などを変えたときの確認に使えます。
+
+出力のオプション: --format (text, json), --out, --lang",
+        (Command::Diff, Lang::En) => "\
+Usage: grouping-tool diff <OLD_JSON> <NEW_JSON>
+
+Compare two results written with --format json. Groups are compared by their members, not their
+labels, so groups that were only reordered or renamed are the same. Shows the students whose
+groupmates changed, the students found in only one result, and the pairs broken up and newly made.
+Useful for checking the effect of changed constraints.
+
+Output options: --format (text, json), --out, --lang",
+        (Command::Verify, Lang::Ja) => "\
+使い方: grouping-tool verify <ファイル> --sign <鍵ファイル>
+
+--sign で署名して書き出した結果のファイルを、隣の <ファイル>.sig と署名に使った鍵で確かめます。
+ファイルが署名したときから1文字でも変わっているか、別の鍵で署名されていると、一致しないと表示して
+終了コード 7 で終わります。JSON の結果なら、確認用ハッシュとシードも表示します。",
+        (Command::Verify, Lang::En) => "\
+Usage: grouping-tool verify <FILE> --sign <KEYFILE>
+
+Check a result written with --sign against the <FILE>.sig next to it, with the key it was signed with.
+When the file changed by as much as one character since it was signed, or was signed with another key,
+the signature does not match and the exit status is 7. For a JSON result, the verification hash and
+the seed are shown as well.",
+        (Command::Replay, Lang::Ja) => "\
+使い方: grouping-tool replay <操作の記録> [オプション]
+
+--session-log で記録した対話入力の追加・削除・移動・入れ替え・グループの完成・取り消しを順に実行し直し、
+入力したグループを作り直してから、interactive と同じようにグループ分けします。何も入力しません。
+ファイルに複数回の入力があるときは最後の回を使います。記録したシードを使うので、同じオプションで
+実行すれば同じ結果になり、記録した結果と同じかどうかを表示します（--seed で別のシードも使えます）。
+異なるときは終了コード 8 で終了します。履歴には保存しません。問題の報告を受けたときの再現やテストに使えます。
+
+よく使うオプション: --seed, --replay, --stats, --constraints, --format, --out（すべてのオプションは grouping-tool --help）",
+        (Command::Replay, Lang::En) => "\
+Usage: grouping-tool replay <SESSION_LOG> [OPTIONS]
+
+Carry out the adds, deletes, moves, swaps, group completions and undos of an interactive entry recorded
+with --session-log again, then group the students as interactive would, without typing anything.
+When the file holds several entries, the last one is used. The recorded seed is used, so the same
+options give the same result, and whether it matches the recorded one is shown (--seed picks another
+seed). When it differs, the exit status is 8. Nothing is saved to the history. Useful for reproducing reported problems and for testing.
+
+Common options: --seed, --replay, --stats, --constraints, --format, --out (all options: grouping-tool --help)",
+        (Command::Serve, Lang::Ja) => "\
+使い方: grouping-tool serve [--listen <アドレス>]
+
+HTTPサーバーを起動し、POST /group でグループ分けを返します（既定: 127.0.0.1:8080）。",
+        (Command::Serve, Lang::En) => "\
+Usage: grouping-tool serve [--listen <ADDR>]
+
+Run an HTTP server that answers POST /group with a grouping (default: 127.0.0.1:8080).",
+        (Command::Import, Lang::Ja) => "\
+使い方: grouping-tool import <名簿CSV> --db <ファイル>
+
+名簿（CSV、--sheet または --classroom）をデータベースに取り込みます。列は --id-column、--name-column で指定します。",
+        (Command::Import, Lang::En) => "\
+Usage: grouping-tool import <ROSTER_CSV> --db <FILE>
+
+Import a roster (CSV, --sheet or --classroom) into the database. Choose columns with --id-column and --name-column.",
+        (Command::Query, Lang::Ja) => "\
+使い方: grouping-tool query --db <ファイル> [--student <学籍番号>]
+
+データベースに記録された過去のグループ分けを表示します。--student でその学生のグループだけを表示します。",
+        (Command::Query, Lang::En) => "\
+Usage: grouping-tool query --db <FILE> [--student <ID>]
+
+Show past groupings recorded in the database. With --student, only that student's groups are shown.",
+    }
+}
+
+/// Parse command-line arguments (excluding the program name).
+pub fn parse_args<I>(args: I) -> Result<Options, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    parse_args_with_defaults(Vec::new(), args)
+}
+
+/// The language of `--lang`, found even when the other arguments are wrong
+/// so that their errors can be written in it.
+pub fn lang_of_args(args: &[String]) -> Option<Lang> {
+    let mut lang = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--lang") {
+            Some("") => args.next().map(String::as_str),
+            Some(rest) => rest.strip_prefix('='),
+            None => continue,
+        };
+        lang = value.and_then(|value| value.parse().ok()).or(lang);
+    }
+    lang
+}
+
+/// Parse command-line arguments with `defaults` (flags from the
+/// configuration file) placed after the subcommand, so that the flags in
+/// `args` override them.
+pub fn parse_args_with_defaults<I>(defaults: Vec<String>, args: I) -> Result<Options, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut options = Options::default();
+    let mut args = args.into_iter().peekable();
+
+    if let Some(command) = args.peek().and_then(|arg| Command::from_name(arg)) {
+        options.command = command;
+    }
+    if options.command != Command::Group {
+        args.next();
+    }
+    let mut args = defaults.into_iter().chain(args);
+
+    while let Some(arg) = args.next() {
+        // Accept both `--flag value` and `--flag=value`
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+
+        match flag.as_str() {
+            "--seed" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let seed = value
+                    .parse::<u64>()
+                    .map_err(|_| message(Msg::NeedsInteger, &[&flag, &0, &value]))?;
+                options.seed = Some(seed);
+            }
+            "--replay" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let seed = value
+                    .parse::<u64>()
+                    .map_err(|_| message(Msg::NeedsReplaySeed, &[&value]))?;
+                options.replay = Some(seed);
+            }
+            "--per-student" => options.per_student = true,
+            "--ics" => options.ics = Some(take_value(&flag, inline_value, &mut args)?),
+            "--start-date" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                options.start_date = Some(time::parse_date(&value).map_err(|e| format!("--start-date: {}", e))?);
+            }
+            "--weekly" => options.weekly = true,
+            "--sessions" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let sessions = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsInteger, &[&flag, &1, &value]))?;
+                options.sessions = Some(sessions);
+            }
+            "--session" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let session = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsInteger, &[&flag, &1, &value]))?;
+                options.session = Some(session);
+            }
+            "--listen" => options.listen = Some(take_value(&flag, inline_value, &mut args)?),
+            "--db" => options.db = Some(take_value(&flag, inline_value, &mut args)?),
+            "--student" => options.student = Some(take_value(&flag, inline_value, &mut args)?),
+            "--id-pattern" => options.id_pattern = Some(take_value(&flag, inline_value, &mut args)?),
+            "--input" => options.input = Some(take_value(&flag, inline_value, &mut args)?),
+            "--from" => options.from = Some(take_value(&flag, inline_value, &mut args)?.parse()?),
+            "--names" => options.names = Some(take_value(&flag, inline_value, &mut args)?),
+            "--id-column" => options.id_column = Some(take_value(&flag, inline_value, &mut args)?),
+            "--name-column" => {
+                options.name_column = Some(take_value(&flag, inline_value, &mut args)?)
+            }
+            "--no-header" => options.no_header = true,
+            "--sheet" => options.sheet = Some(take_value(&flag, inline_value, &mut args)?),
+            "--sheet-range" => options.sheet_range = Some(take_value(&flag, inline_value, &mut args)?),
+            "--sheet-output" => options.sheet_output = Some(take_value(&flag, inline_value, &mut args)?),
+            "--classroom" => options.classroom = Some(take_value(&flag, inline_value, &mut args)?),
+            "--google-credentials" => {
+                options.google_credentials = Some(take_value(&flag, inline_value, &mut args)?)
+            }
+            // `--output results.xlsx` names a workbook rather than a format
+            "--output" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                if value.to_lowercase().ends_with(".xlsx") {
+                    options.xlsx = Some(value);
+                } else {
+                    options.format = value.parse()?;
+                }
+            }
+            "--format" => options.format = take_value(&flag, inline_value, &mut args)?.parse()?,
+            "--template" => options.template = Some(take_value(&flag, inline_value, &mut args)?),
+            "--xlsx" => options.xlsx = Some(take_value(&flag, inline_value, &mut args)?),
+            "--out" => options.out = Some(take_value(&flag, inline_value, &mut args)?),
+            "--output-html" => options.output_html = Some(take_value(&flag, inline_value, &mut args)?),
+            "--pdf" => options.pdf = Some(take_value(&flag, inline_value, &mut args)?),
+            "--pdf-per-group" => options.pdf_per_group = true,
+            "--clipboard" => options.clipboard = true,
+            "--slack-webhook" => options.slack_webhook = Some(take_value(&flag, inline_value, &mut args)?),
+            "--discord-webhook" => {
+                options.discord_webhook = Some(take_value(&flag, inline_value, &mut args)?)
+            }
+            "-q" | "--quiet" => options.quiet = true,
+            "-v" | "--verbose" => options.verbose = options.verbose.saturating_add(1),
+            "-vv" => options.verbose = options.verbose.saturating_add(2),
+            "--no-color" => options.no_color = true,
+            "--teams-webhook" => options.teams_webhook = Some(take_value(&flag, inline_value, &mut args)?),
+            "--group-names" => options.group_names = Some(take_value(&flag, inline_value, &mut args)?),
+            "--group-names-file" => {
+                options.group_names_file = Some(take_value(&flag, inline_value, &mut args)?)
+            }
+            "--labels" => options.labels = Some(take_value(&flag, inline_value, &mut args)?.parse()?),
+            "--numeric-labels" => options.numeric_labels = true,
+            "--sort-members" => options.sort_members = true,
+            "--constraints" => options.constraints = Some(take_value(&flag, inline_value, &mut args)?),
+            "--avoid-file" => options.avoid_file = Some(take_value(&flag, inline_value, &mut args)?),
+            "--prefer-file" => options.prefer_file = Some(take_value(&flag, inline_value, &mut args)?),
+            "--balance" => {
+                options.balance = take_value(&flag, inline_value, &mut args)?
+                    .split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect()
+            }
+            "--spread-weights" => {
+                let key = take_value(&flag, inline_value, &mut args)?.trim().to_string();
+                if key.is_empty() {
+                    return Err(message(Msg::NeedsAttribute, &[&flag]));
+                }
+                options.spread_weights = Some(key);
+            }
+            "--stratify" => {
+                let key = take_value(&flag, inline_value, &mut args)?.trim().to_string();
+                if key.is_empty() {
+                    return Err(message(Msg::NeedsAttribute, &[&flag]));
+                }
+                options.stratify = Some(key);
+            }
+            "--within-strata" => options.within_strata = true,
+            "--no-shuffle" => options.no_shuffle = true,
+            "--group-by" => {
+                options.group_by = take_value(&flag, inline_value, &mut args)?
+                    .split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect();
+                if options.group_by.is_empty() {
+                    return Err(message(Msg::NeedsAttribute, &[&flag]));
+                }
+            }
+            "--preferences" => options.preferences = Some(take_value(&flag, inline_value, &mut args)?),
+            "--skill" => {
+                let key = take_value(&flag, inline_value, &mut args)?.trim().to_string();
+                if key.is_empty() {
+                    return Err(message(Msg::NeedsAttribute, &[&flag]));
+                }
+                options.skill = Some(key);
+            }
+            "--population" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let population = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (2..=genetic::MAX_POPULATION).contains(n))
+                    .ok_or_else(|| message(Msg::NeedsIntegerRange, &[&flag, &2, &genetic::MAX_POPULATION, &value]))?;
+                options.population = Some(population);
+            }
+            "--generations" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let generations = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=genetic::MAX_GENERATIONS).contains(n))
+                    .ok_or_else(|| message(Msg::NeedsIntegerRange, &[&flag, &1, &genetic::MAX_GENERATIONS, &value]))?;
+                options.generations = Some(generations);
+            }
+            "--avoid-repeats" => options.avoid_repeats = true,
+            "--optimize-repeats" => options.optimize_repeats = true,
+            "--optimize" => options.optimize = true,
+            "--time-budget" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let budget = anneal::parse_duration(&value)
+                    .ok_or_else(|| message(Msg::NeedsDuration, &[&flag, &value]))?;
+                options.time_budget = Some(budget);
+            }
+            "--strategy" => {
+                let name = take_value(&flag, inline_value, &mut args)?;
+                if strategy::by_name(&name).is_none() {
+                    return Err(message(Msg::OneOf, &[&flag, &list(&strategy::names()), &name]));
+                }
+                options.strategy = Some(name);
+            }
+            "--iterations" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let iterations = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsInteger, &[&flag, &1, &value]))?;
+                options.iterations = Some(iterations);
+            }
+            "--assign-leader" => options.assign_leader = true,
+            "--history-file" => options.history_file = Some(take_value(&flag, inline_value, &mut args)?),
+            "--no-save-history" => options.no_save_history = true,
+            "--history" => options.history_dir = Some(take_value(&flag, inline_value, &mut args)?),
+            "--archive" => options.archive = Some(take_value(&flag, inline_value, &mut args)?),
+            "--sign" => options.sign = Some(take_value(&flag, inline_value, &mut args)?),
+            "--line-mode" => options.line_mode = true,
+            "--timeout" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let seconds = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsSeconds, &[&flag, &value]))?;
+                options.timeout = Some(seconds);
+            }
+            "--save-session" => options.save_session = Some(take_value(&flag, inline_value, &mut args)?),
+            "--resume" => options.resume = Some(take_value(&flag, inline_value, &mut args)?),
+            "--session-log" => options.session_log = Some(take_value(&flag, inline_value, &mut args)?),
+            "--watch" => options.watch = Some(take_value(&flag, inline_value, &mut args)?),
+            "--stream" => options.stream = true,
+            "--anonymize" => options.anonymize = true,
+            "--pseudonym-file" => options.pseudonym_file = Some(take_value(&flag, inline_value, &mut args)?),
+            "--append" => options.append = Some(take_value(&flag, inline_value, &mut args)?),
+            "--seating" => options.seating = Some(take_value(&flag, inline_value, &mut args)?),
+            "--seating-out" => options.seating_out = Some(take_value(&flag, inline_value, &mut args)?),
+            "--double-elimination" => options.double_elimination = true,
+            "--by-student" => options.by_student = true,
+            "--slot-minutes" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let minutes = value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsInteger, &[&flag, &1, &value]))?;
+                options.slot_minutes = Some(minutes);
+            }
+            "--confirm" => options.confirm = true,
+            "--pairs" => options.pairs = true,
+            "--groups" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let groups = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| message(Msg::NeedsInteger, &[&flag, &1, &value]))?;
+                options.groups = Some(groups);
+            }
+            "--balanced" => options.balanced = Some(take_value(&flag, inline_value, &mut args)?.parse()?),
+            "--min" | "--max" => {
+                let value = take_value(&flag, inline_value, &mut args)?;
+                let size = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n >= 2)
+                    .ok_or_else(|| message(Msg::NeedsGroupSize, &[&flag, &value]))?;
+                if flag == "--min" {
+                    options.min_size = Some(size);
+                } else {
+                    options.max_size = Some(size);
+                }
+            }
+            "--stats" => options.stats = true,
+            "--lang" => options.lang = Some(take_value(&flag, inline_value, &mut args)?.parse()?),
+            "--config" => options.config = Some(take_value(&flag, inline_value, &mut args)?),
+
+            "-h" | "--help" => options.help = true,
+            // `import roster.csv` is the same as `import --input roster.csv`, and so is `--from moodle roster.csv`
+            _ if (options.command == Command::Import || options.from.is_some())
+                && !arg.starts_with('-')
+                && options.input.is_none() =>
+            {
+                options.input = Some(arg)
+            }
+            _ if matches!(options.command, Command::Merge | Command::Diff | Command::Verify | Command::Replay)
+                && !arg.starts_with('-') =>
+            {
+                options.result_files.push(arg)
+            }
+            // `score A 10`: the group, then the points (which may be negative)
+            _ if options.command == Command::Score && !arg.starts_with('-') && options.score_group.is_none() => {
+                options.score_group = Some(arg)
+            }
+            _ if options.command == Command::Score && options.score_group.is_some() && options.points.is_none() => {
+                let points = arg
+                    .parse::<i64>()
+                    .map_err(|_| message(Msg::PointsNotInteger, &[&arg]))?;
+                options.points = Some(points);
+            }
+            _ => return Err(message(Msg::UnknownOption, &[&arg])),
+        }
+    }
+
+    if matches!(options.command, Command::Schedule | Command::Plan) {
+        let name = if options.command == Command::Plan { "plan" } else { "schedule" };
+        if options.sessions.is_none() && !options.help {
+            return Err(message(Msg::NeedsSessions, &[&name]));
+        }
+        if options.output_html.is_some()
+            || options.pdf.is_some()
+            || options.xlsx.is_some()
+            || options.sheet_output.is_some()
+            || options.slack_webhook.is_some()
+            || options.discord_webhook.is_some()
+            || options.teams_webhook.is_some()
+            || options.assign_leader
+        {
+            let flags = [
+                "--output-html",
+                "--pdf",
+                "--xlsx",
+                "--sheet-output",
+                "--slack-webhook",
+                "--discord-webhook",
+                "--teams-webhook",
+                "--assign-leader",
+            ];
+            return Err(message(Msg::NotWith, &[&name, &list(&flags)]));
+        }
+    }
+    if !matches!(options.command, Command::Schedule | Command::Plan) && options.sessions.is_some() {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--sessions", &list(&["schedule", "plan"])]));
+    }
+    if !matches!(options.command, Command::Schedule | Command::Plan) && options.per_student {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--per-student", &list(&["schedule", "plan"])]));
+    }
+    if !matches!(options.command, Command::Schedule | Command::Plan) && options.ics.is_some() {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--ics", &list(&["schedule", "plan"])]));
+    }
+    if options.ics.is_some() != options.start_date.is_some() {
+        return Err(message(Msg::IcsWithStartDate, &[]));
+    }
+    if options.weekly && options.start_date.is_none() {
+        return Err(message(Msg::WeeklyNeedsIcs, &[]));
+    }
+    if options.iterations.is_some() && !options.optimize_repeats {
+        return Err(message(Msg::OptionRequires, &[&"--iterations", &"--optimize-repeats"]));
+    }
+    if options.history_dir.is_some() && !options.uses_history() && !options.stats {
+        return Err(message(Msg::HistoryUnused, &[]));
+    }
+    if options.time_budget.is_some() && !options.optimize {
+        return Err(message(Msg::OptionRequires, &[&"--time-budget", &"--optimize"]));
+    }
+    // Swaps would only pull apart the students that similar and preferences put together
+    if let Some(name) = options.strategy.as_deref().filter(|name| ["similar", "preferences"].contains(name)) {
+        if options.optimize {
+            return Err(message(Msg::OptionConflict, &[&"--optimize", &format!("--strategy {}", name)]));
+        }
+    }
+    if options.within_strata && options.stratify.is_none() {
+        return Err(message(Msg::OptionRequires, &[&"--within-strata", &"--stratify"]));
+    }
+    if options.strategy.as_deref() == Some("mixed-skill") && options.skill.is_none() {
+        return Err(message(Msg::MixedSkillNeedsSkill, &[]));
+    }
+    if options.skill.is_some() && options.strategy.as_deref() != Some("mixed-skill") {
+        return Err(message(Msg::OptionTogether, &[&"--skill", &"--strategy mixed-skill"]));
+    }
+    if options.strategy.as_deref() == Some("similar") && options.group_by.is_empty() {
+        return Err(message(Msg::SimilarNeedsGroupBy, &[]));
+    }
+    if !options.group_by.is_empty() && options.strategy.as_deref() != Some("similar") {
+        return Err(message(Msg::OptionTogether, &[&"--group-by", &"--strategy similar"]));
+    }
+    if options.strategy.as_deref() == Some("preferences") && options.preferences.is_none() {
+        return Err(message(Msg::PreferencesNeedsFile, &[]));
+    }
+    if options.preferences.is_some() && options.strategy.as_deref() != Some("preferences") {
+        return Err(message(Msg::OptionTogether, &[&"--preferences", &"--strategy preferences"]));
+    }
+    if (options.population.is_some() || options.generations.is_some()) && options.strategy.as_deref() != Some("genetic") {
+        return Err(message(Msg::OptionTogether, &[&list(&["--population", "--generations"]), &"--strategy genetic"]));
+    }
+    if options.pairs && options.groups.is_some() {
+        return Err(message(Msg::OptionConflict, &[&"--pairs", &"--groups"]));
+    }
+    if options.balanced.is_some() && (options.pairs || options.groups.is_some()) {
+        return Err(message(Msg::OptionConflict, &[&"--balanced", &list(&["--pairs", "--groups"])]));
+    }
+    if options.min_size.is_some() || options.max_size.is_some() {
+        if options.pairs || options.groups.is_some() || options.balanced.is_some() {
+            let sizes = list(&["--pairs", "--groups", "--balanced"]);
+            return Err(message(Msg::OptionConflict, &[&list(&["--min", "--max"]), &sizes]));
+        }
+        if let (Some(min), Some(max)) = (options.min_size, options.max_size) {
+            if min > max {
+                return Err(message(Msg::MinOverMax, &[&min, &max]));
+            }
+        }
+    }
+    if options.group_names.is_some() && options.group_names_file.is_some() {
+        return Err(message(Msg::OptionConflict, &[&"--group-names", &"--group-names-file"]));
+    }
+    if options.labels.is_some() && (options.group_names.is_some() || options.group_names_file.is_some()) {
+        return Err(message(Msg::OptionConflict, &[&"--labels", &list(&["--group-names", "--group-names-file"])]));
+    }
+    if options.numeric_labels
+        && (options.group_names.is_some() || options.group_names_file.is_some() || options.labels.is_some())
+    {
+        let names = list(&["--group-names", "--group-names-file", "--labels"]);
+        return Err(message(Msg::OptionConflict, &[&"--numeric-labels", &names]));
+    }
+    if options.sheet.is_some() && options.input.is_some() {
+        return Err(message(Msg::OptionConflict, &[&"--sheet", &"--input"]));
+    }
+    if options.classroom.is_some() {
+        if options.input.is_some() || options.sheet.is_some() {
+            return Err(message(Msg::OptionConflict, &[&"--classroom", &list(&["--input", "--sheet"])]));
+        }
+        // Classroom gives names and email addresses rather than columns
+        if options.id_column.is_some() || options.name_column.is_some() || options.no_header {
+            let columns = list(&["--id-column", "--name-column", "--no-header"]);
+            return Err(message(Msg::OptionConflict, &[&"--classroom", &columns]));
+        }
+    }
+    if options.sheet.is_none() && (options.sheet_range.is_some() || options.sheet_output.is_some()) {
+        return Err(message(Msg::SheetRangeNeedsSheet, &[]));
+    }
+    if matches!(options.command, Command::Import | Command::Query) && options.db.is_none() && !options.help {
+        return Err(message(Msg::NeedsDb, &[]));
+    }
+    if options.command == Command::Import
+        && options.input.is_none()
+        && options.sheet.is_none()
+        && options.classroom.is_none()
+        && !options.help
+    {
+        return Err(message(Msg::ImportNeedsRoster, &[]));
+    }
+    if options.command != Command::Query && options.student.is_some() {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--student", &"query"]));
+    }
+    if options.command != Command::Serve && options.listen.is_some() {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--listen", &"serve"]));
+    }
+    if options.archive.is_some()
+        && !matches!(options.command, Command::Group | Command::Interactive | Command::Batch | Command::Replay)
+    {
+        return Err(message(Msg::OnlyForGrouping, &[&"--archive", &list(&["interactive", "batch", "replay"])]));
+    }
+    if options.confirm && !matches!(options.command, Command::Group | Command::Interactive | Command::Batch) {
+        return Err(message(Msg::OnlyForGrouping, &[&"--confirm", &list(&["interactive", "batch"])]));
+    }
+    // Both ask for something to be shown besides the result
+    if options.quiet && (options.confirm || options.stats) {
+        return Err(message(Msg::OptionConflict, &[&"--quiet", &list(&["--confirm", "--stats"])]));
+    }
+    if options.replay.is_some() {
+        if !matches!(options.command, Command::Group | Command::Interactive | Command::Batch | Command::Replay) {
+            return Err(message(Msg::OnlyForGrouping, &[&"--replay", &list(&["interactive", "batch", "replay"])]));
+        }
+        if options.seed.is_some() {
+            return Err(message(Msg::ReplayWithSeed, &[]));
+        }
+        if options.watch.is_some() || options.stream {
+            return Err(message(Msg::OptionConflict, &[&"--replay", &list(&["--watch", "--stream"])]));
+        }
+    }
+    if options.no_shuffle {
+        if !matches!(options.command, Command::Group | Command::Interactive | Command::Batch | Command::Replay) {
+            return Err(message(Msg::OnlyForGrouping, &[&"--no-shuffle", &list(&["interactive", "batch", "replay"])]));
+        }
+        // Options that decide the order of the students themselves
+        let reorders = [
+            ("--balance", !options.balance.is_empty()),
+            ("--spread-weights", options.spread_weights.is_some()),
+            ("--stratify", options.stratify.is_some() && !options.within_strata),
+            ("--constraints", options.constraints.is_some()),
+            ("--avoid-file", options.avoid_file.is_some()),
+            ("--prefer-file", options.prefer_file.is_some()),
+            ("--avoid-repeats", options.avoid_repeats),
+            ("--optimize-repeats", options.optimize_repeats),
+            ("--optimize", options.optimize),
+            ("--strategy", options.strategy.is_some()),
+        ];
+        if let Some((flag, _)) = reorders.iter().find(|(_, given)| *given) {
+            return Err(message(Msg::OptionConflict, &[&"--no-shuffle", flag]));
+        }
+    }
+    if options.stats && !matches!(options.command, Command::Group | Command::Interactive | Command::Batch | Command::Replay) {
+        return Err(message(Msg::OnlyForGrouping, &[&"--stats", &list(&["interactive", "batch", "replay"])]));
+    }
+    if (options.save_session.is_some() || options.resume.is_some())
+        && !matches!(options.command, Command::Group | Command::Interactive | Command::Batch)
+    {
+        let flags = list(&["--save-session", "--resume"]);
+        return Err(message(Msg::OnlyForGrouping, &[&flags, &list(&["interactive", "batch"])]));
+    }
+    if options.timeout.is_some() {
+        if !matches!(options.command, Command::Group | Command::Interactive) {
+            return Err(message(Msg::OnlyForEntry, &[&"--timeout"]));
+        }
+        if options.input.is_some() || options.sheet.is_some() || options.classroom.is_some() {
+            return Err(message(Msg::OptionConflict, &[&"--timeout", &list(&["--input", "--sheet", "--classroom"])]));
+        }
+    }
+    if options.session_log.is_some() {
+        if !matches!(options.command, Command::Group | Command::Interactive) {
+            return Err(message(Msg::OnlyForEntry, &[&"--session-log"]));
+        }
+        if options.input.is_some() || options.sheet.is_some() || options.classroom.is_some() {
+            let sources = list(&["--input", "--sheet", "--classroom"]);
+            return Err(message(Msg::OptionConflict, &[&"--session-log", &sources]));
+        }
+    }
+    if options.resume.is_some() && (options.input.is_some() || options.sheet.is_some() || options.classroom.is_some()) {
+        return Err(message(Msg::OptionConflict, &[&"--resume", &list(&["--input", "--sheet", "--classroom"])]));
+    }
+    if options.watch.is_some() {
+        if !matches!(options.command, Command::Group | Command::Batch) {
+            return Err(message(Msg::OnlyForGrouping, &[&"--watch", &"batch"]));
+        }
+        if options.input.is_some()
+            || options.sheet.is_some()
+            || options.classroom.is_some()
+            || options.resume.is_some()
+            || options.save_session.is_some()
+            || options.session_log.is_some()
+            || options.confirm
+            || options.slack_webhook.is_some()
+            || options.discord_webhook.is_some()
+            || options.teams_webhook.is_some()
+        {
+            let flags = [
+                "--input",
+                "--sheet",
+                "--classroom",
+                "--resume",
+                "--save-session",
+                "--session-log",
+                "--confirm",
+                "--slack-webhook",
+                "--discord-webhook",
+                "--teams-webhook",
+            ];
+            return Err(message(Msg::NotWith, &[&"--watch", &list(&flags)]));
+        }
+    }
+    if options.stream {
+        if !matches!(options.command, Command::Group | Command::Batch) {
+            return Err(message(Msg::OnlyForGrouping, &[&"--stream", &"batch"]));
+        }
+        // Options that need every student before the first group can be formed or written
+        let needs_whole_input = [
+            ("--input", options.input.is_some()),
+            ("--sheet", options.sheet.is_some()),
+            ("--classroom", options.classroom.is_some()),
+            ("--resume", options.resume.is_some()),
+            ("--save-session", options.save_session.is_some()),
+            ("--session-log", options.session_log.is_some()),
+            ("--watch", options.watch.is_some()),
+            ("--append", options.append.is_some()),
+            ("--seating", options.seating.is_some()),
+            ("--confirm", options.confirm),
+            ("--groups", options.groups.is_some()),
+            ("--balanced equal", options.balanced == Some(SizePolicy::Equal)),
+            ("--min", options.min_size.is_some()),
+            ("--max", options.max_size.is_some()),
+            ("--balance", !options.balance.is_empty()),
+            ("--spread-weights", options.spread_weights.is_some()),
+            ("--stratify", options.stratify.is_some()),
+            ("--skill", options.skill.is_some()),
+            ("--group-by", !options.group_by.is_empty()),
+            ("--preferences", options.preferences.is_some()),
+            ("--constraints", options.constraints.is_some()),
+            ("--avoid-file", options.avoid_file.is_some()),
+            ("--prefer-file", options.prefer_file.is_some()),
+            ("--avoid-repeats", options.avoid_repeats),
+            ("--optimize-repeats", options.optimize_repeats),
+            ("--optimize", options.optimize),
+            ("--strategy", options.strategy.is_some()),
+            ("--assign-leader", options.assign_leader),
+            ("--db", options.db.is_some()),
+            ("--stats", options.stats),
+            ("--archive", options.archive.is_some()),
+            ("--sign", options.sign.is_some()),
+            ("--output-html", options.output_html.is_some()),
+            ("--pdf", options.pdf.is_some()),
+            ("--xlsx", options.xlsx.is_some()),
+            ("--clipboard", options.clipboard),
+            ("--slack-webhook", options.slack_webhook.is_some()),
+            ("--discord-webhook", options.discord_webhook.is_some()),
+            ("--teams-webhook", options.teams_webhook.is_some()),
+            ("--format json", options.format == OutputFormat::Json),
+            ("--format yaml", options.format == OutputFormat::Yaml),
+            ("--template", options.template.is_some()),
+        ];
+        if let Some((flag, _)) = needs_whole_input.iter().find(|(_, given)| *given) {
+            return Err(message(Msg::OptionConflict, &[&"--stream", flag]));
+        }
+    }
+    if options.append.is_some() {
+        if !matches!(options.command, Command::Group | Command::Interactive | Command::Batch) {
+            return Err(message(Msg::OnlyForGrouping, &[&"--append", &list(&["interactive", "batch"])]));
+        }
+        // Options that decide how the whole class is grouped, which the exported grouping already fixed
+        let regroups = [
+            ("--watch", options.watch.is_some()),
+            ("--resume", options.resume.is_some()),
+            ("--confirm", options.confirm),
+            ("--balance", !options.balance.is_empty()),
+            ("--spread-weights", options.spread_weights.is_some()),
+            ("--stratify", options.stratify.is_some()),
+            ("--skill", options.skill.is_some()),
+            ("--group-by", !options.group_by.is_empty()),
+            ("--preferences", options.preferences.is_some()),
+            ("--constraints", options.constraints.is_some()),
+            ("--avoid-file", options.avoid_file.is_some()),
+            ("--prefer-file", options.prefer_file.is_some()),
+            ("--avoid-repeats", options.avoid_repeats),
+            ("--optimize-repeats", options.optimize_repeats),
+            ("--optimize", options.optimize),
+            ("--strategy", options.strategy.is_some()),
+            ("--replay", options.replay.is_some()),
+            ("--assign-leader", options.assign_leader),
+            ("--stats", options.stats),
+        ];
+        if let Some((flag, _)) = regroups.iter().find(|(_, given)| *given) {
+            return Err(message(Msg::OptionConflict, &[&"--append", flag]));
+        }
+    }
+    if options.seating.is_some()
+        && !matches!(
+            options.command,
+            Command::Group
+                | Command::Interactive
+                | Command::Batch
+                | Command::Replay
+                | Command::Export
+                | Command::Merge
+        )
+    {
+        let others = list(&["export", "merge"]);
+        return Err(message(Msg::OnlyForGroupingAnd, &[&"--seating", &list(&["interactive", "batch", "replay"]), &others]));
+    }
+    if options.template.is_some() {
+        if !matches!(
+            options.command,
+            Command::Group
+                | Command::Interactive
+                | Command::Batch
+                | Command::Replay
+                | Command::Export
+                | Command::Merge
+        ) {
+            let others = list(&["export", "merge"]);
+            return Err(message(Msg::OnlyForGroupingAnd, &[&"--template", &list(&["interactive", "batch", "replay"]), &others]));
+        }
+        if options.format != OutputFormat::Text {
+            return Err(message(Msg::OptionConflict, &[&"--template", &"--format"]));
+        }
+    }
+    if options.anonymize
+        && !matches!(
+            options.command,
+            Command::Group
+                | Command::Interactive
+                | Command::Batch
+                | Command::Schedule
+                | Command::Plan
+                | Command::Export
+                | Command::Merge
+        )
+    {
+        let others = list(&["schedule", "plan", "export", "merge"]);
+        return Err(message(Msg::OnlyForGroupingAnd, &[&"--anonymize", &list(&["interactive", "batch"]), &others]));
+    }
+    if options.pseudonym_file.is_some() && !options.anonymize {
+        return Err(message(Msg::OptionRequires, &[&"--pseudonym-file", &"--anonymize"]));
+    }
+    if options.from.is_some() {
+        if options.input.is_none() && options.watch.is_none() && !options.help {
+            return Err(message(Msg::FromNeedsRoster, &[]));
+        }
+        // The columns are found from the Moodle headers
+        if options.id_column.is_some() || options.name_column.is_some() || options.no_header || options.sheet.is_some() {
+            let columns = list(&["--id-column", "--name-column", "--no-header", "--sheet"]);
+            return Err(message(Msg::OptionConflict, &[&"--from", &columns]));
+        }
+    }
+    if options.seating_out.is_some() && options.seating.is_none() {
+        return Err(message(Msg::OptionRequires, &[&"--seating-out", &"--seating"]));
+    }
+    if options.command == Command::Merge && options.result_files.len() < 2 && !options.help {
+        return Err(message(Msg::MergeNeedsFiles, &[]));
+    }
+    if options.command == Command::Diff && options.result_files.len() != 2 && !options.help {
+        return Err(message(Msg::DiffNeedsFiles, &[]));
+    }
+    if options.command == Command::Verify && !options.help {
+        if options.result_files.len() != 1 {
+            return Err(message(Msg::VerifyNeedsFile, &[]));
+        }
+        if options.sign.is_none() {
+            return Err(message(Msg::VerifyNeedsKey, &[]));
+        }
+    }
+    if options.command == Command::Replay && !options.help {
+        if options.result_files.len() != 1 {
+            return Err(message(Msg::ReplayNeedsLog, &[]));
+        }
+        // The students come from the log, and nothing is typed
+        let entry = [
+            ("--resume", options.resume.is_some()),
+            ("--save-session", options.save_session.is_some()),
+            ("--confirm", options.confirm),
+            ("--append", options.append.is_some()),
+        ];
+        if let Some((flag, _)) = entry.iter().find(|(_, given)| *given) {
+            return Err(message(Msg::NotWith, &[&"replay", flag]));
+        }
+    }
+    if options.sign.is_some() && options.command != Command::Verify {
+        if !matches!(
+            options.command,
+            Command::Group
+                | Command::Interactive
+                | Command::Batch
+                | Command::Replay
+                | Command::Export
+                | Command::Merge
+        ) {
+            let others = list(&["export", "merge", "verify"]);
+            return Err(message(Msg::OnlyForGroupingAnd, &[&"--sign", &list(&["interactive", "batch", "replay"]), &others]));
+        }
+        if options.out.is_none()
+            && options.archive.is_none()
+            && options.output_html.is_none()
+            && options.pdf.is_none()
+            && options.xlsx.is_none()
+        {
+            return Err(message(Msg::SignNeedsFile, &[]));
+        }
+    }
+    if options.command == Command::Score && options.points.is_none() && !options.help {
+        return Err(message(Msg::ScoreNeedsPoints, &[]));
+    }
+    if !matches!(
+        options.command,
+        Command::Export | Command::Bracket | Command::Order | Command::Score | Command::Leaderboard
+    ) && options.session.is_some()
+    {
+        let commands = list(&["export", "bracket", "order", "score", "leaderboard"]);
+        return Err(message(Msg::OnlyForSubcommand, &[&"--session", &commands]));
+    }
+    if options.command != Command::Leaderboard && options.by_student {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--by-student", &"leaderboard"]));
+    }
+    if options.by_student && options.session.is_some() {
+        return Err(message(Msg::OptionConflict, &[&"--by-student", &"--session"]));
+    }
+    if options.command != Command::Order && options.slot_minutes.is_some() {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--slot-minutes", &"order"]));
+    }
+    if options.command != Command::Bracket && options.double_elimination {
+        return Err(message(Msg::OnlyForSubcommand, &[&"--double-elimination", &"bracket"]));
+    }
+    if matches!(options.command, Command::Bracket | Command::Order | Command::Leaderboard | Command::Diff)
+        && !matches!(options.format, OutputFormat::Text | OutputFormat::Json)
+    {
+        return Err(message(Msg::TextOrJson, &[]));
+    }
+    if matches!(
+        options.command,
+        Command::Interactive
+            | Command::Batch
+            | Command::Export
+            | Command::Bracket
+            | Command::Order
+            | Command::Score
+            | Command::Leaderboard
+            | Command::Merge
+            | Command::Diff
+            | Command::Verify
+            | Command::Replay
+    ) && (options.input.is_some() || options.sheet.is_some() || options.classroom.is_some())
+    {
+        let commands = [
+            "interactive",
+            "batch",
+            "export",
+            "bracket",
+            "order",
+            "score",
+            "leaderboard",
+            "merge",
+            "diff",
+            "verify",
+            "replay",
+        ];
+        let sources = list(&["--input", "--sheet", "--classroom"]);
+        return Err(message(Msg::NotWith, &[&list(&commands), &sources]));
+    }
+
+    Ok(options)
+}
+
+impl Options {
+    /// Seed for the random shuffle: that of `--seed`, or of the run `--replay` makes again.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed.or(self.replay)
+    }
+
+    /// Sizes of the groups to form.
+    pub fn sizing(&self) -> Sizing {
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let min = self.min_size.unwrap_or(2);
+            let max = self.max_size.unwrap_or(min.max(3));
+            // The usual groups of 2-3 keep their own, more flexible, rules
+            return if (min, max) == (2, 3) { Sizing::Triples } else { Sizing::Range { min, max } };
+        }
+        match (self.pairs, self.groups, self.balanced) {
+            (_, Some(count), _) => Sizing::Groups(count),
+            (true, None, _) => Sizing::Pairs,
+            (false, None, Some(SizePolicy::Equal)) => Sizing::Even,
+            // The usual split already uses the fewest groups
+            (false, None, Some(SizePolicy::Fewest) | None) => Sizing::Triples,
+        }
+    }
+
+    /// Whether past pairings are looked up to avoid them: `--avoid-repeats`,
+    /// `--optimize-repeats` which goes further, or a strategy that needs them.
+    pub fn uses_history(&self) -> bool {
+        self.avoid_repeats || self.optimize_repeats || self.strategy().is_some_and(|s| s.uses_history())
+    }
+
+    /// The algorithm chosen with `--strategy`.
+    pub fn strategy(&self) -> Option<&'static dyn GroupingStrategy> {
+        self.strategy.as_deref().and_then(strategy::by_name)
+    }
+
+    /// Swaps tried to lower repeated pairings, with `--optimize-repeats`.
+    pub fn optimize_iterations(&self) -> Option<usize> {
+        self.optimize_repeats
+            .then(|| self.iterations.unwrap_or(history::DEFAULT_ITERATIONS))
+    }
+
+    /// Time spent improving the grouping by swaps, with `--optimize`.
+    pub fn optimize_time(&self) -> Option<Duration> {
+        self.optimize
+            .then(|| self.time_budget.unwrap_or(anneal::DEFAULT_TIME_BUDGET))
+    }
+
+    /// How long the entry waits for input before finishing (`--timeout`).
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// File the entry is saved to: that of `--save-session`, or the one
+    /// resumed from so that it keeps being updated.
+    pub fn session_file(&self) -> Option<&str> {
+        self.save_session.as_deref().or(self.resume.as_deref())
+    }
+
+    /// Attributes written after the ID on stdin, in order: those of `--balance`,
+    /// then the `--spread-weights`, `--stratify`, `--skill` and `--group-by` attributes unless they are among them.
+    pub fn attribute_names(&self) -> Vec<String> {
+        let mut names = self.balance.clone();
+        let single = [&self.spread_weights, &self.stratify, &self.skill].into_iter().flatten();
+        for key in single.chain(&self.group_by) {
+            if !names.contains(key) {
+                names.push(key.clone());
+            }
+        }
+        names
+    }
+}
+
+/// Take the value of `flag`, either from `--flag=value` or from the next argument.
+fn take_value<I>(flag: &str, inline_value: Option<String>, args: &mut I) -> Result<String, String>
+where
+    I: Iterator<Item = String>,
+{
+    match inline_value {
+        Some(value) => Ok(value),
+        None => args.next().ok_or_else(|| message(Msg::NeedsValue, &[&flag])),
+    }
+}
+
+/// `items` as a list in the language of the messages.
+fn list(items: &[&str]) -> String {
+    Lang::current().list(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_no_args() {
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+    }
+
+    #[test]
+    fn test_lang_of_args() {
+        let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(lang_of_args(&args(&["--bogus", "--lang", "en"])), Some(Lang::En));
+        assert_eq!(lang_of_args(&args(&["batch", "--lang=ja", "--seed"])), Some(Lang::Ja));
+        assert_eq!(lang_of_args(&args(&["--lang", "fr"])), None);
+        assert_eq!(lang_of_args(&args(&["--language", "en"])), None);
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        assert_eq!(parse(&["--seed", "42"]).unwrap().seed, Some(42));
+        assert_eq!(parse(&["--seed=7"]).unwrap().seed, Some(7));
+        assert!(parse(&["--seed", "abc"]).is_err());
+        assert!(parse(&["--seed"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_no_shuffle() {
+        assert!(parse(&["interactive", "--no-shuffle"]).unwrap().no_shuffle);
+        assert!(parse(&["--no-shuffle", "--stratify", "section", "--within-strata"]).is_ok());
+        assert!(parse(&["--no-shuffle", "--stratify", "section"]).is_err());
+        assert!(parse(&["--no-shuffle", "--strategy", "random"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--no-shuffle"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_replay() {
+        let options = parse(&["batch", "--replay", "42"]).unwrap();
+        assert_eq!((options.replay, options.seed()), (Some(42), Some(42)));
+        assert_eq!(parse(&["--seed", "7"]).unwrap().seed(), Some(7));
+        assert!(parse(&["--replay", "42", "--seed", "42"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--replay", "42"]).is_err());
+        assert!(parse(&["--replay", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_input_options() {
+        let options = parse(&["--input", "roster.csv", "--id-column=2", "--name-column", "氏名"]).unwrap();
+        assert_eq!(options.input.as_deref(), Some("roster.csv"));
+        assert_eq!(options.id_column.as_deref(), Some("2"));
+        assert_eq!(options.name_column.as_deref(), Some("氏名"));
+        assert!(!options.no_header);
+    }
+
+    #[test]
+    fn test_parse_output_options() {
+        let options = parse(&["--output", "csv", "--out", "result.csv"]).unwrap();
+        assert_eq!(options.format, OutputFormat::Csv);
+        assert_eq!(options.out.as_deref(), Some("result.csv"));
+        assert!(parse(&["--output", "xml"]).is_err());
+        assert_eq!(parse(&["--format=json"]).unwrap().format, OutputFormat::Json);
+        let options = parse(&["--output-html", "report.html"]).unwrap();
+        assert_eq!(options.output_html.as_deref(), Some("report.html"));
+        assert_eq!(options.format, OutputFormat::Text);
+        let options = parse(&["--output", "results.xlsx"]).unwrap();
+        assert_eq!(options.xlsx.as_deref(), Some("results.xlsx"));
+        assert_eq!(options.format, OutputFormat::Text);
+        assert!(parse(&["--format", "results.xlsx"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_plan() {
+        let options = parse(&["plan", "--sessions", "12", "--out", "term", "--format", "csv"]).unwrap();
+        assert_eq!(options.command, Command::Plan);
+        assert_eq!((options.sessions, options.out.as_deref()), (Some(12), Some("term")));
+        assert!(parse(&["plan"]).is_err());
+        assert!(parse(&["plan", "--sessions", "2", "--pdf", "plan.pdf"]).is_err());
+        assert!(parse(&["plan", "--sessions", "2", "--stats"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let options = parse(&["plan", "--sessions", "4", "--ics", "term.ics", "--start-date", "2025-04-08", "--weekly"]).unwrap();
+        assert_eq!(options.ics.as_deref(), Some("term.ics"));
+        assert_eq!(options.start_date, Some(time::parse_date("2025-04-08").unwrap()));
+        assert!(options.weekly);
+        assert!(parse(&["schedule", "--sessions", "4", "--ics", "term.ics"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "4", "--start-date", "2025-04-08"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "4", "--ics", "term.ics", "--start-date", "2025-04-31"]).is_err());
+        assert!(parse(&["--ics", "term.ics", "--start-date", "2025-04-08"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "4", "--weekly"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_per_student() {
+        assert!(parse(&["schedule", "--sessions", "4", "--per-student"]).unwrap().per_student);
+        assert!(parse(&["plan", "--sessions", "4", "--per-student"]).unwrap().per_student);
+        assert!(parse(&["batch", "--per-student"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let options = parse(&["schedule", "--sessions", "4", "--seed=1"]).unwrap();
+        assert_eq!(options.command, Command::Schedule);
+        assert_eq!(options.sessions, Some(4));
+        assert!(parse(&["schedule"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "0"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--assign-leader"]).is_err());
+        assert!(parse(&["--sessions", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_serve() {
+        let options = parse(&["serve", "--listen", "0.0.0.0:3000"]).unwrap();
+        assert_eq!(options.command, Command::Serve);
+        assert_eq!(options.listen.as_deref(), Some("0.0.0.0:3000"));
+        assert!(parse(&["--listen", "0.0.0.0:3000"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sheet_options() {
+        let options = parse(&["--sheet", "abc123", "--sheet-range=名簿!A:D", "--sheet-output", "結果!A1"]).unwrap();
+        assert_eq!(options.sheet.as_deref(), Some("abc123"));
+        assert_eq!(options.sheet_range.as_deref(), Some("名簿!A:D"));
+        assert_eq!(options.sheet_output.as_deref(), Some("結果!A1"));
+        assert!(parse(&["--sheet-output", "結果!A1"]).is_err());
+        assert!(parse(&["--sheet", "abc123", "--input", "roster.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_quiet() {
+        assert!(parse(&["-q", "--format", "json"]).unwrap().quiet);
+        assert!(parse(&["batch", "--quiet"]).unwrap().quiet);
+        assert!(parse(&["--quiet", "--confirm"]).is_err());
+        assert!(parse(&["--quiet", "--stats"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        let options = parse(&["interactive", "--timeout", "90"]).unwrap();
+        assert_eq!(options.idle_timeout(), Some(Duration::from_secs(90)));
+        assert_eq!(parse(&[]).unwrap().idle_timeout(), None);
+        assert!(parse(&["--timeout", "0"]).is_err());
+        assert!(parse(&["batch", "--timeout", "60"]).is_err());
+        assert!(parse(&["--timeout", "60", "--input", "roster.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_verbose() {
+        assert_eq!(parse(&[]).unwrap().verbose, 0);
+        assert_eq!(parse(&["-v"]).unwrap().verbose, 1);
+        assert_eq!(parse(&["-vv"]).unwrap().verbose, 2);
+        assert_eq!(parse(&["-v", "--verbose", "-q"]).unwrap().verbose, 2);
+    }
+
+    #[test]
+    fn test_parse_classroom() {
+        let options = parse(&["import", "--classroom", "123456789", "--db", "groups.db"]).unwrap();
+        assert_eq!(options.classroom.as_deref(), Some("123456789"));
+        assert!(parse(&["--classroom", "123456789", "--sheet", "abc123"]).is_err());
+        assert!(parse(&["--classroom", "123456789", "--id-column", "2"]).is_err());
+        assert!(parse(&["batch", "--classroom", "123456789"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_db_subcommands() {
+        let options = parse(&["import", "roster.csv", "--db", "groups.db", "--name-column=氏名"]).unwrap();
+        assert_eq!(options.command, Command::Import);
+        assert_eq!(options.input.as_deref(), Some("roster.csv"));
+        assert_eq!(options.db.as_deref(), Some("groups.db"));
+        assert!(parse(&["import", "--db", "groups.db"]).is_err());
+        assert!(parse(&["import", "roster.csv"]).is_err());
+
+        let options = parse(&["query", "--db", "groups.db", "--student", "S001"]).unwrap();
+        assert_eq!(options.command, Command::Query);
+        assert_eq!(options.student.as_deref(), Some("S001"));
+        assert!(parse(&["--student", "S001"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_id_pattern() {
+        let options = parse(&["--id-pattern", r"^B\d{5}$"]).unwrap();
+        assert_eq!(options.id_pattern.as_deref(), Some(r"^B\d{5}$"));
+        assert_eq!(parse(&["--id-pattern="]).unwrap().id_pattern.as_deref(), Some(""));
+        assert_eq!(parse(&[]).unwrap().id_pattern, None);
+    }
+
+    #[test]
+    fn test_parse_group_names() {
+        let options = parse(&["--group-names", "Red,Blue,Green"]).unwrap();
+        assert_eq!(options.group_names.as_deref(), Some("Red,Blue,Green"));
+        assert!(parse(&["--group-names=Red", "--group-names-file", "names.txt"]).is_err());
+        assert_eq!(parse(&["--labels", "animals"]).unwrap().labels, Some(LabelTheme::Animals));
+        assert!(parse(&["--labels", "fruits"]).is_err());
+        assert!(parse(&["--labels=kana", "--group-names", "Red"]).is_err());
+        assert!(parse(&["--numeric-labels"]).unwrap().numeric_labels);
+        assert!(parse(&["--numeric-labels", "--labels", "colors"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_lang() {
+        assert_eq!(parse(&["--lang", "en"]).unwrap().lang, Some(Lang::En));
+        assert_eq!(parse(&["--lang=ja"]).unwrap().lang, Some(Lang::Ja));
+        assert_eq!(parse(&[]).unwrap().lang, None);
+        assert!(parse(&["--lang", "fr"]).is_err());
+    }
+
+    #[test]
+    fn test_command_names() {
+        assert_eq!(Command::from_name("plan"), Some(Command::Plan));
+        assert_eq!(Command::from_name("--plan"), None);
+        assert_eq!(Command::Replay.name(), "replay");
+        assert_eq!(Command::Group.name(), "");
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(Command::of_args(&args(&["plan", "--sessions", "0"])), Command::Plan);
+        assert_eq!(Command::of_args(&args(&["--pairs", "plan"])), Command::Group);
+    }
+
+    #[test]
+    fn test_parse_entry_subcommands() {
+        assert_eq!(parse(&["interactive", "--line-mode"]).unwrap().command, Command::Interactive);
+        assert!(parse(&["batch", "--confirm"]).unwrap().confirm);
+        assert_eq!(parse(&["--pairs"]).unwrap().sizing(), Sizing::Pairs);
+        assert_eq!(parse(&["batch", "--groups=4"]).unwrap().sizing(), Sizing::Groups(4));
+        assert!(parse(&["--groups", "0"]).is_err());
+        assert!(parse(&["--pairs", "--groups", "4"]).is_err());
+        assert_eq!(parse(&["--balanced", "equal"]).unwrap().sizing(), Sizing::Even);
+        assert_eq!(parse(&["--balanced=fewest"]).unwrap().sizing(), Sizing::Triples);
+        assert!(parse(&["--balanced", "even"]).is_err());
+        assert!(parse(&["--balanced", "equal", "--pairs"]).is_err());
+        assert!(parse(&["--stream", "--balanced", "equal"]).is_err());
+        assert_eq!(parse(&["--min", "2", "--max", "4"]).unwrap().sizing(), Sizing::Range { min: 2, max: 4 });
+        assert_eq!(parse(&["--min=4"]).unwrap().sizing(), Sizing::Range { min: 4, max: 4 });
+        assert_eq!(parse(&["--max", "3"]).unwrap().sizing(), Sizing::Triples);
+        assert!(parse(&["--min", "1"]).is_err());
+        assert!(parse(&["--min", "4", "--max", "3"]).is_err());
+        assert!(parse(&["--max", "4", "--pairs"]).is_err());
+        assert!(parse(&["--stream", "--max", "4"]).is_err());
+        let options = parse(&["--resume", "class.json"]).unwrap();
+        assert_eq!(options.session_file(), Some("class.json"));
+        assert_eq!(parse(&["--resume=a.json", "--save-session=b.json"]).unwrap().session_file(), Some("b.json"));
+        assert!(parse(&["--resume", "class.json", "--input", "roster.csv"]).is_err());
+        assert!(parse(&["export", "--save-session", "class.json"]).is_err());
+        assert_eq!(parse(&["--session-log", "class.log"]).unwrap().session_log.as_deref(), Some("class.log"));
+        assert!(parse(&["batch", "--session-log", "class.log"]).is_err());
+        assert!(parse(&["--session-log", "class.log", "--input", "roster.csv"]).is_err());
+        assert_eq!(parse(&["batch", "--watch", "roster.txt"]).unwrap().watch.as_deref(), Some("roster.txt"));
+        assert!(parse(&["--watch", "roster.txt", "--slack-webhook", "https://example.com"]).is_err());
+        assert!(parse(&["interactive", "--watch", "roster.txt"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--confirm"]).is_err());
+        assert_eq!(parse(&["batch", "--seed=1"]).unwrap().command, Command::Batch);
+        assert!(parse(&["batch", "--input", "roster.csv"]).is_err());
+
+        let options = parse(&["export", "--session", "3", "--format=csv"]).unwrap();
+        assert_eq!(options.command, Command::Export);
+        assert_eq!(options.session, Some(3));
+        assert!(parse(&["export", "--session", "0"]).is_err());
+        assert!(parse(&["--session", "3"]).is_err());
+        assert_ne!(usage(Command::Export, Lang::En), usage(Command::Group, Lang::En));
+    }
+
+    #[test]
+    fn test_parse_bracket() {
+        let options = parse(&["bracket", "--session", "2", "--double-elimination", "--format=json"]).unwrap();
+        assert_eq!(options.command, Command::Bracket);
+        assert_eq!(options.session, Some(2));
+        assert!(options.double_elimination);
+        assert!(parse(&["bracket", "--format", "csv"]).is_err());
+        assert!(parse(&["bracket", "--input", "roster.csv"]).is_err());
+        assert!(parse(&["export", "--double-elimination"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_score() {
+        let options = parse(&["score", "B", "10", "--session", "2"]).unwrap();
+        assert_eq!(options.command, Command::Score);
+        assert_eq!(options.score_group.as_deref(), Some("B"));
+        assert_eq!((options.points, options.session), (Some(10), Some(2)));
+        assert_eq!(parse(&["score", "A", "-3"]).unwrap().points, Some(-3));
+        assert!(parse(&["score", "A"]).is_err());
+        assert!(parse(&["score", "A", "ten"]).is_err());
+        assert!(parse(&["score", "A", "1", "2"]).is_err());
+
+        let options = parse(&["leaderboard", "--by-student", "--format=json"]).unwrap();
+        assert_eq!(options.command, Command::Leaderboard);
+        assert!(options.by_student);
+        assert!(parse(&["leaderboard", "--by-student", "--session", "1"]).is_err());
+        assert!(parse(&["--by-student"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_merge() {
+        let options = parse(&["merge", "1組.json", "2組.json", "--format", "csv"]).unwrap();
+        assert_eq!(options.command, Command::Merge);
+        assert_eq!(options.result_files, vec!["1組.json", "2組.json"]);
+        assert!(parse(&["merge", "1組.json"]).is_err());
+        assert!(parse(&["merge", "--help"]).is_ok());
+        assert!(parse(&["merge", "1組.json", "2組.json", "--seating", "5x6"]).is_ok());
+        assert!(parse(&["merge", "1組.json", "2組.json", "--input", "roster.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let options = parse(&["diff", "before.json", "after.json", "--format=json"]).unwrap();
+        assert_eq!(options.command, Command::Diff);
+        assert_eq!(options.result_files, vec!["before.json", "after.json"]);
+        assert!(parse(&["diff", "before.json"]).is_err());
+        assert!(parse(&["diff", "a.json", "b.json", "c.json"]).is_err());
+        assert!(parse(&["diff", "a.json", "b.json", "--format", "csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_order() {
+        let options = parse(&["order", "--slot-minutes", "10", "--seed=4"]).unwrap();
+        assert_eq!(options.command, Command::Order);
+        assert_eq!(options.slot_minutes, Some(10));
+        assert!(parse(&["order", "--slot-minutes", "0"]).is_err());
+        assert!(parse(&["--slot-minutes", "10"]).is_err());
+        assert!(parse(&["order", "--format", "markdown"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_with_defaults() {
+        let defaults = vec!["--format=csv".to_string(), "--seed=1".to_string()];
+        let args = ["schedule", "--sessions", "2", "--seed", "7"].iter().map(|s| s.to_string());
+        let options = parse_args_with_defaults(defaults, args).unwrap();
+        assert_eq!(options.command, Command::Schedule);
+        assert_eq!(options.format, OutputFormat::Csv);
+        assert_eq!(options.seed, Some(7));
+    }
+
+    #[test]
+    fn test_parse_balance() {
+        let options = parse(&["--balance", "gender, skill"]).unwrap();
+        assert_eq!(options.balance, vec!["gender", "skill"]);
+
+        let options = parse(&["--balance", "gender", "--spread-weights", "score"]).unwrap();
+        assert_eq!(options.attribute_names(), vec!["gender", "score"]);
+        assert!(parse(&["--spread-weights="]).is_err());
+    }
+
+    #[test]
+    fn test_parse_history_dir() {
+        let options = parse(&["--avoid-repeats", "--history", "exports"]).unwrap();
+        assert_eq!(options.history_dir.as_deref(), Some("exports"));
+        assert!(parse(&["--history=exports", "--stats"]).is_ok());
+        assert!(parse(&["--history", "exports"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sign_and_verify() {
+        let options = parse(&["verify", "result.json", "--sign", "key.txt"]).unwrap();
+        assert_eq!(options.command, Command::Verify);
+        assert_eq!((options.result_files, options.sign.as_deref()), (vec!["result.json".to_string()], Some("key.txt")));
+        assert!(parse(&["verify", "result.json"]).is_err());
+        assert!(parse(&["verify", "--sign", "key.txt"]).is_err());
+        assert!(parse(&["batch", "--out", "result.json", "--sign", "key.txt"]).is_ok());
+        assert!(parse(&["batch", "--archive", "runs", "--sign", "key.txt"]).is_ok());
+        // Nothing is written to a file to sign
+        assert!(parse(&["batch", "--sign", "key.txt"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--out", "plan.txt", "--sign", "key.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_replay_log() {
+        let options = parse(&["replay", "class.log", "--seed", "7"]).unwrap();
+        assert_eq!(options.command, Command::Replay);
+        assert_eq!((options.seed(), options.result_files), (Some(7), vec!["class.log".to_string()]));
+        assert!(parse(&["replay"]).is_err());
+        assert!(parse(&["replay", "a.log", "b.log"]).is_err());
+        // The students come from the log
+        assert!(parse(&["replay", "class.log", "--input", "roster.csv"]).is_err());
+        assert!(parse(&["replay", "class.log", "--confirm"]).is_err());
+        assert!(parse(&["replay", "class.log", "--stats", "--archive", "runs"]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_archive() {
+        assert_eq!(parse(&["batch", "--archive", "runs"]).unwrap().archive.as_deref(), Some("runs"));
+        assert!(parse(&["schedule", "--sessions", "2", "--archive", "runs"]).is_err());
+        assert!(parse(&["batch", "--stream", "--archive", "runs"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_avoid_file() {
+        let options = parse(&["--avoid-file", "conflicts.csv"]).unwrap();
+        assert_eq!(options.avoid_file.as_deref(), Some("conflicts.csv"));
+        assert!(parse(&["--avoid-file"]).is_err());
+        assert!(parse(&["--stream", "--avoid-file", "conflicts.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_prefer_file() {
+        let options = parse(&["--prefer-file=friends.csv", "--optimize"]).unwrap();
+        assert_eq!(options.prefer_file.as_deref(), Some("friends.csv"));
+        assert!(parse(&["--no-shuffle", "--prefer-file", "friends.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_optimize_repeats() {
+        let options = parse(&["--optimize-repeats", "--iterations", "500"]).unwrap();
+        assert!(options.uses_history());
+        assert_eq!(options.optimize_iterations(), Some(500));
+        assert_eq!(parse(&["--optimize-repeats"]).unwrap().optimize_iterations(), Some(10_000));
+        assert_eq!(parse(&["--avoid-repeats"]).unwrap().optimize_iterations(), None);
+        assert!(parse(&["--iterations", "500"]).is_err());
+        assert!(parse(&["--optimize-repeats", "--iterations=0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_optimize() {
+        let options = parse(&["--optimize", "--time-budget", "500ms"]).unwrap();
+        assert_eq!(options.optimize_time(), Some(Duration::from_millis(500)));
+        assert_eq!(parse(&["--optimize"]).unwrap().optimize_time(), Some(Duration::from_secs(2)));
+        assert_eq!(parse(&[]).unwrap().optimize_time(), None);
+        assert!(parse(&["--time-budget", "2s"]).is_err());
+        assert!(parse(&["--optimize", "--time-budget=soon"]).is_err());
+        assert!(parse(&["--optimize", "--strategy", "similar", "--group-by", "level"]).is_err());
+        assert!(parse(&["--optimize", "--no-shuffle"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let options = parse(&["batch", "--stream", "--pairs", "--format", "csv"]).unwrap();
+        assert!(options.stream);
+        assert_eq!(options.sizing(), Sizing::Pairs);
+        assert!(parse(&["--stream", "--groups", "4"]).is_err());
+        assert!(parse(&["--stream", "--format", "json"]).is_err());
+        assert!(parse(&["--stream", "--format", "yaml"]).is_err());
+        assert!(parse(&["--stream", "--avoid-repeats"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--stream"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_seating() {
+        let options = parse(&["--seating", "5x6", "--seating-out=seats.csv"]).unwrap();
+        assert_eq!(options.seating.as_deref(), Some("5x6"));
+        assert_eq!(options.seating_out.as_deref(), Some("seats.csv"));
+        assert!(parse(&["export", "--seating", "room.txt"]).is_ok());
+        assert!(parse(&["--seating-out", "seats.csv"]).is_err());
+        assert!(parse(&["schedule", "--sessions", "2", "--seating", "5x6"]).is_err());
+        assert!(parse(&["--stream", "--seating", "5x6"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let options = parse(&["--template", "announce.hbs"]).unwrap();
+        assert_eq!(options.template.as_deref(), Some("announce.hbs"));
+        assert!(parse(&["merge", "a.json", "b.json", "--template=announce.hbs"]).is_ok());
+        assert!(parse(&["--template", "announce.hbs", "--format", "csv"]).is_err());
+        assert!(parse(&["bracket", "--template", "announce.hbs"]).is_err());
+        assert!(parse(&["--stream", "--template", "announce.hbs"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_append() {
+        let options = parse(&["batch", "--append", "results.json"]).unwrap();
+        assert_eq!(options.append.as_deref(), Some("results.json"));
+        assert!(parse(&["--append=results.json", "--seating", "5x6"]).is_ok());
+        assert!(parse(&["export", "--append", "results.json"]).is_err());
+        assert!(parse(&["--append", "results.json", "--balance", "gender"]).is_err());
+        assert!(parse(&["--append", "results.json", "--stream"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_anonymize() {
+        let options = parse(&["export", "--anonymize", "--pseudonym-file=map.csv"]).unwrap();
+        assert!(options.anonymize);
+        assert_eq!(options.pseudonym_file.as_deref(), Some("map.csv"));
+        assert!(parse(&["--stream", "--anonymize"]).is_ok());
+        assert!(parse(&["--pseudonym-file", "map.csv"]).is_err());
+        assert!(parse(&["leaderboard", "--anonymize"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_from() {
+        let options = parse(&["--from", "moodle", "roster.csv", "--balance", "Groups"]).unwrap();
+        assert_eq!(options.from, Some(RosterFormat::Moodle));
+        assert_eq!(options.input.as_deref(), Some("roster.csv"));
+        let options = parse(&["import", "--from=Moodle", "roster.csv", "--db", "class.db"]).unwrap();
+        assert_eq!(options.input.as_deref(), Some("roster.csv"));
+        assert!(parse(&["batch", "--watch", "roster.csv", "--from", "moodle"]).is_ok());
+        assert!(parse(&["--from", "moodle"]).is_err());
+        assert!(parse(&["--from", "canvas", "roster.csv"]).is_err());
+        assert!(parse(&["--from", "moodle", "roster.csv", "--id-column", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        let options = parse(&["--strategy", "optimized"]).unwrap();
+        assert_eq!(options.strategy().map(|s| s.name()), Some("optimized"));
+        assert!(options.uses_history());
+        assert!(!parse(&["--strategy=random"]).unwrap().uses_history());
+        assert!(parse(&["--strategy", "annealing"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_skill() {
+        let options = parse(&["--strategy", "mixed-skill", "--skill", "level", "--balance", "gender"]).unwrap();
+        assert_eq!(options.skill.as_deref(), Some("level"));
+        assert_eq!(options.attribute_names(), vec!["gender", "level"]);
+        assert!(parse(&["--strategy", "mixed-skill"]).is_err());
+        assert!(parse(&["--skill", "level"]).is_err());
+        assert!(parse(&["--strategy", "mixed-skill", "--skill="]).is_err());
+    }
+
+    #[test]
+    fn test_parse_genetic() {
+        let options = parse(&["--strategy", "genetic", "--population", "80", "--generations=500"]).unwrap();
+        assert_eq!((options.population, options.generations), (Some(80), Some(500)));
+        assert!(parse(&["--population", "80"]).is_err());
+        assert!(parse(&["--strategy", "random", "--generations", "10"]).is_err());
+        assert!(parse(&["--strategy", "genetic", "--population", "1"]).is_err());
+        assert!(parse(&["--strategy", "genetic", "--generations", "0"]).is_err());
+        assert!(parse(&["--strategy", "genetic", "--population", "100000000000"]).is_err());
+        assert!(parse(&["--strategy", "genetic", "--generations", "100000000000"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_preferences() {
+        let options = parse(&["--strategy", "preferences", "--preferences", "wishes.txt"]).unwrap();
+        assert_eq!(options.preferences.as_deref(), Some("wishes.txt"));
+        assert!(parse(&["--strategy", "preferences"]).is_err());
+        assert!(parse(&["--preferences", "wishes.txt"]).is_err());
+        assert!(parse(&["--strategy", "preferences", "--preferences", "wishes.txt", "--optimize"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_group_by() {
+        let options = parse(&["--strategy", "similar", "--group-by", "level, topic", "--balance", "topic"]).unwrap();
+        assert_eq!(options.group_by, vec!["level", "topic"]);
+        assert_eq!(options.attribute_names(), vec!["topic", "level"]);
+        assert!(parse(&["--strategy", "similar"]).is_err());
+        assert!(parse(&["--group-by", "level"]).is_err());
+        assert!(parse(&["--strategy", "similar", "--group-by", ","]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stratify() {
+        let options = parse(&["--balance", "gender", "--stratify", "section"]).unwrap();
+        assert_eq!(options.attribute_names(), vec!["gender", "section"]);
+        assert!(!options.within_strata);
+
+        let options = parse(&["--stratify=section", "--within-strata"]).unwrap();
+        assert!(options.within_strata);
+        assert!(parse(&["--within-strata"]).is_err());
+        assert!(parse(&["--stratify="]).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! Copying text to the system clipboard using the platform's clipboard command.
+
+use crate::i18n::{message, Msg};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Clipboard commands to try, in order, for the current platform.
+fn candidates() -> &'static [(&'static str, &'static [&'static str])] {
+    if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    }
+}
+
+/// Place `text` on the system clipboard, returning the name of the command used.
+pub fn copy(text: &str) -> io::Result<&'static str> {
+    for (program, args) in candidates() {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            // Not installed; try the next one
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(program);
+        }
+        return Err(io::Error::new(io::ErrorKind::Other, message(Msg::CommandFailed, &[&program, &status])));
+    }
+
+    let names: Vec<&str> = candidates().iter().map(|(program, _)| *program).collect();
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        message(Msg::NoClipboard, &[&names.join(", ")]),
+    ))
+}
@@ -0,0 +1,267 @@
+//! Defaults read from `~/.config/grouping-tool/config.toml` (or `--config`).
+//!
+//! Each setting is turned into the command-line flag of the same name, so
+//! `avoid_repeats = true` means `--avoid-repeats`. The flags are placed
+//! before the ones actually typed, which therefore take precedence.
+
+use crate::cli::{Command, Options};
+use grouping_tool::i18n::{message, Lang, Msg};
+use grouping_tool::json::Value;
+use grouping_tool::toml;
+use std::path::{Path, PathBuf};
+
+/// How a setting is passed on as a flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// `--flag <value>` from a string or a number
+    Text,
+    /// `--flag <file>`, relative to the directory of the configuration file
+    Path,
+    /// `--flag a,b` from an array of strings (or one comma-separated string)
+    List,
+    /// `--flag` when `true`
+    Switch,
+}
+
+/// Settings allowed in the configuration file.
+const SETTINGS: &[(&str, Kind)] = &[
+    ("format", Kind::Text),
+    ("template", Kind::Path),
+    ("seed", Kind::Text),
+    ("lang", Kind::Text),
+    ("id_pattern", Kind::Text),
+    ("constraints", Kind::Path),
+    ("avoid_file", Kind::Path),
+    ("prefer_file", Kind::Path),
+    ("balance", Kind::List),
+    ("spread_weights", Kind::Text),
+    ("stratify", Kind::Text),
+    ("within_strata", Kind::Switch),
+    ("group_names", Kind::List),
+    ("group_names_file", Kind::Path),
+    ("labels", Kind::Text),
+    ("numeric_labels", Kind::Switch),
+    ("sort_members", Kind::Switch),
+    ("pairs", Kind::Switch),
+    ("groups", Kind::Text),
+    ("balanced", Kind::Text),
+    ("min", Kind::Text),
+    ("max", Kind::Text),
+    ("avoid_repeats", Kind::Switch),
+    ("optimize_repeats", Kind::Switch),
+    ("iterations", Kind::Text),
+    ("optimize", Kind::Switch),
+    ("time_budget", Kind::Text),
+    ("strategy", Kind::Text),
+    ("skill", Kind::Text),
+    ("group_by", Kind::List),
+    ("preferences", Kind::Path),
+    ("population", Kind::Text),
+    ("generations", Kind::Text),
+    ("no_shuffle", Kind::Switch),
+    ("assign_leader", Kind::Switch),
+    ("history_file", Kind::Path),
+    ("history", Kind::Path),
+    ("per_student", Kind::Switch),
+    ("weekly", Kind::Switch),
+    ("no_save_history", Kind::Switch),
+    ("archive", Kind::Path),
+    ("sign", Kind::Path),
+    ("names", Kind::Path),
+    ("save_session", Kind::Path),
+    ("session_log", Kind::Path),
+    ("db", Kind::Path),
+    ("line_mode", Kind::Switch),
+    ("timeout", Kind::Text),
+    ("stats", Kind::Switch),
+    ("seating", Kind::Text),
+    ("clipboard", Kind::Switch),
+    ("no_color", Kind::Switch),
+    ("google_credentials", Kind::Path),
+    ("slack_webhook", Kind::Text),
+    ("discord_webhook", Kind::Text),
+    ("teams_webhook", Kind::Text),
+];
+
+/// `$XDG_CONFIG_HOME/grouping-tool/config.toml`, or the same under `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(dir.join("grouping-tool").join("config.toml"))
+}
+
+/// Read the configuration file given with `--config`, or the default one
+/// when it exists, as flags to put before the command line. Settings that
+/// would clash with what was typed (`options`) are left out.
+pub fn load(options: &Options) -> Result<Vec<String>, String> {
+    let path = match &options.config {
+        Some(path) => PathBuf::from(path),
+        None => match default_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Vec::new()),
+        },
+    };
+    let text = std::fs::read_to_string(&path).map_err(|e| message(Msg::CannotOpen, &[&path.display(), &e]))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    to_args(&text, base, options).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Turn the settings in `text` into flags, resolving relative paths against `base`.
+fn to_args(text: &str, base: &Path, options: &Options) -> Result<Vec<String>, String> {
+    let Value::Object(settings) = toml::parse(text)? else {
+        unreachable!("a TOML document is a table");
+    };
+    let mut args = Vec::new();
+    for (key, value) in &settings {
+        let kind = SETTINGS
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| message(Msg::UnknownSetting, &[key]))?;
+        if overridden(key, options) {
+            continue;
+        }
+
+        let flag = format!("--{}", key.replace('_', "-"));
+        let invalid = |expected: Msg| message(Msg::SettingType, &[key, &Lang::current().text(expected)]);
+        match (kind, value) {
+            (Kind::Switch, Value::Bool(true)) => args.push(flag),
+            (Kind::Switch, Value::Bool(false)) => {}
+            (Kind::Switch, _) => return Err(invalid(Msg::SettingBool)),
+            (Kind::Text, Value::String(text) | Value::Number(text)) => args.push(format!("{}={}", flag, text)),
+            (Kind::Text, _) => return Err(invalid(Msg::SettingText)),
+            (Kind::Path, Value::String(path)) => args.push(format!("{}={}", flag, base.join(path).display())),
+            (Kind::Path, _) => return Err(invalid(Msg::SettingPath)),
+            (Kind::List, Value::String(text)) => args.push(format!("{}={}", flag, text)),
+            (Kind::List, Value::Array(items)) => {
+                let items: Option<Vec<&str>> = items.iter().map(Value::as_str).collect();
+                let items = items.ok_or_else(|| invalid(Msg::SettingList))?;
+                args.push(format!("{}={}", flag, items.join(",")));
+            }
+            (Kind::List, _) => return Err(invalid(Msg::SettingList)),
+        }
+    }
+    Ok(args)
+}
+
+/// Whether a setting must give way to something typed on the command line
+/// that cannot be combined with it.
+fn overridden(key: &str, options: &Options) -> bool {
+    match key {
+        "group_names" | "group_names_file" => options.group_names.is_some() || options.group_names_file.is_some(),
+        // Leaders are not chosen for a schedule
+        "assign_leader" => matches!(options.command, Command::Schedule | Command::Plan),
+        // Brackets, orders, leaderboards and diffs are only written as text or JSON
+        "format" => matches!(
+            options.command,
+            Command::Bracket | Command::Order | Command::Leaderboard | Command::Diff
+        ),
+        // A replay uses the seed it was given
+        "seed" => options.replay.is_some(),
+        // The skill rating, the attributes to group by, the breeding and the wishes only mean something to their strategy
+        "skill" => options.strategy.as_deref().is_some_and(|name| name != "mixed-skill"),
+        "group_by" => options.strategy.as_deref().is_some_and(|name| name != "similar"),
+        "preferences" => options.strategy.as_deref().is_some_and(|name| name != "preferences"),
+        "population" | "generations" => options.strategy.as_deref().is_some_and(|name| name != "genetic"),
+        // Only plans have sessions to list per student
+        "per_student" => !matches!(options.command, Command::Schedule | Command::Plan),
+        // Only whole groupings are archived
+        "archive" => {
+            options.stream
+                || !matches!(options.command, Command::Group | Command::Interactive | Command::Batch | Command::Replay)
+        }
+        // The key signs files only, and checks them in verify
+        "sign" => match options.command {
+            Command::Verify => false,
+            Command::Group | Command::Interactive | Command::Batch | Command::Replay | Command::Export | Command::Merge => {
+                options.stream
+                    || (options.out.is_none()
+                        && options.archive.is_none()
+                        && options.output_html.is_none()
+                        && options.pdf.is_none()
+                        && options.xlsx.is_none())
+            }
+            _ => true,
+        },
+        // Only edits typed at the terminal are logged
+        "session_log" => {
+            !matches!(options.command, Command::Group | Command::Interactive)
+                || options.input.is_some()
+                || options.sheet.is_some()
+                || options.classroom.is_some()
+                || options.watch.is_some()
+                || options.stream
+        }
+        // A replay types nothing, and has no entry to save
+        "timeout" | "save_session" => options.command == Command::Replay,
+        // Sessions are only dated in a calendar
+        "weekly" => options.start_date.is_none(),
+        // The classroom is the same for every grouping, but has no use elsewhere
+        "seating" => {
+            options.stream
+                || !matches!(
+                    options.command,
+                    Command::Group
+                        | Command::Interactive
+                        | Command::Batch
+                        | Command::Replay
+                        | Command::Export
+                        | Command::Merge
+                )
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_become_flags() {
+        let text = r#"
+format = "csv"
+seed = 42
+constraints = "rules.txt"
+balance = ["gender", "skill"]
+avoid_repeats = true
+clipboard = false
+"#;
+        let args = to_args(text, Path::new("/etc/gt"), &Options::default()).unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "--format=csv",
+                "--seed=42",
+                "--constraints=/etc/gt/rules.txt",
+                "--balance=gender,skill",
+                "--avoid-repeats",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_mistyped_settings() {
+        let options = Options::default();
+        assert!(to_args("colour = \"red\"", Path::new(""), &options).is_err());
+        assert!(to_args("avoid_repeats = \"yes\"", Path::new(""), &options).is_err());
+        assert!(to_args("balance = [1, 2]", Path::new(""), &options).is_err());
+    }
+
+    #[test]
+    fn test_command_line_wins() {
+        let options = Options {
+            group_names_file: Some("names.txt".to_string()),
+            command: Command::Schedule,
+            ..Default::default()
+        };
+        let args = to_args("group_names = [\"Red\"]\nassign_leader = true", Path::new(""), &options).unwrap();
+        assert!(args.is_empty());
+    }
+}
@@ -0,0 +1,903 @@
+//! Grouping constraints (students who must stay together or be kept apart)
+//! and the constraint-aware assignment pass that honors them.
+
+use crate::group::{Group, Student, StudentId, MAX_GROUP_SIZE};
+use crate::grouping::{Mode, Sizing};
+use crate::i18n::{message, Msg};
+use crate::roster;
+use crate::solver;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Constraints read from a constraints file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// Sets of students that must be placed in the same group
+    pub together: Vec<Vec<StudentId>>,
+    /// Sets of students of whom no two may be placed in the same group
+    pub apart: Vec<Vec<StudentId>>,
+    /// Constraints given a weight (`apart: S001,S002 weight=5`), which a
+    /// grouping may break at the cost of their weight
+    pub soft: Vec<SoftConstraint>,
+}
+
+/// A constraint with a weight: searches such as `--optimize` count its
+/// weight as a penalty when it is broken, instead of treating it as a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoftConstraint {
+    /// Whether the students should be together rather than apart
+    pub together: bool,
+    pub ids: Vec<StudentId>,
+    /// Penalty for breaking it; for `apart`, for each pair sharing a group
+    pub weight: u64,
+}
+
+/// Weight of a request to work together (`--prefer-file`): the same as one
+/// pair repeated from the history.
+pub const PREFERENCE_WEIGHT: u64 = 1;
+
+/// How many shuffled attempts the assignment pass makes to avoid `apart` conflicts.
+const MAX_ATTEMPTS: usize = 200;
+
+/// Students placed across all attempts; large classes get fewer attempts so
+/// that the run time stays roughly linear.
+const ATTEMPT_BUDGET: usize = 1_000_000;
+
+impl Constraints {
+    /// Whether there are no constraints at all.
+    pub fn is_empty(&self) -> bool {
+        !self.has_hard() && self.soft.is_empty()
+    }
+
+    /// Whether some constraints must be honored, rather than only weighed.
+    pub fn has_hard(&self) -> bool {
+        !self.together.is_empty() || !self.apart.is_empty()
+    }
+
+    /// Parse a constraints file. Each line is a directive such as
+    /// `together: S001,S007` or `apart: S003,S015`, optionally followed by
+    /// `weight=N` to make it a [`SoftConstraint`]; blank lines and `#`
+    /// comments are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut constraints = Constraints::default();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (directive, list) = line
+                .split_once(':')
+                .ok_or_else(|| error_at(line_no, Msg::ConstraintForm, &[]))?;
+            let (list, weight) = match list.rsplit_once("weight=") {
+                Some((list, weight)) if list.ends_with(char::is_whitespace) => {
+                    let weight = weight
+                        .trim()
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|&w| w > 0)
+                        .ok_or_else(|| error_at(line_no, Msg::ConstraintWeight, &[]))?;
+                    (list, Some(weight))
+                }
+                _ => (list, None),
+            };
+            let ids: Vec<StudentId> = list
+                .split(',')
+                .map(|id| id.trim())
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect();
+            if ids.len() < 2 {
+                return Err(error_at(line_no, Msg::ConstraintFewIds, &[]));
+            }
+
+            let together = match directive.trim().to_lowercase().as_str() {
+                "together" => true,
+                "apart" => false,
+                other => return Err(error_at(line_no, Msg::ConstraintUnknown, &[&other])),
+            };
+            match (weight, together) {
+                (Some(weight), _) => constraints.soft.push(SoftConstraint { together, ids, weight }),
+                (None, true) => constraints.together.push(ids),
+                (None, false) => constraints.apart.push(ids),
+            }
+        }
+
+        Ok(constraints)
+    }
+
+    /// Parse a pairs file given with `--avoid-file` or `--prefer-file`: each
+    /// CSV row holds two students. Blank lines and `#` comments are ignored.
+    pub fn parse_pairs(text: &str) -> Result<Vec<Vec<StudentId>>, String> {
+        let mut pairs = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let ids: Vec<StudentId> = roster::parse_csv_line(line)
+                .into_iter()
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            match ids.as_slice() {
+                [a, b] if a != b => pairs.push(ids),
+                [_, _] => return Err(error_at(line_no, Msg::ConstraintSameIds, &[])),
+                _ => return Err(error_at(line_no, Msg::ConstraintPair, &[])),
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Add pairs who asked to work together (`--prefer-file`) as `together`
+    /// [`SoftConstraint`]s of [`PREFERENCE_WEIGHT`].
+    pub fn prefer(&mut self, pairs: Vec<Vec<StudentId>>) {
+        self.soft.extend(pairs.into_iter().map(|ids| SoftConstraint {
+            together: true,
+            ids,
+            weight: PREFERENCE_WEIGHT,
+        }));
+    }
+
+    /// List the constraints that `groups` fails to satisfy, leaving out the
+    /// [`SoftConstraint`]s. Students that do not appear in any group are ignored.
+    pub fn unsatisfied(&self, groups: &[Group]) -> Vec<Violation> {
+        let group_of = group_index_by_member(groups);
+        let together = self.together.iter().flat_map(|ids| broken(ids, true, &group_of));
+        let apart = self.apart.iter().flat_map(|ids| broken(ids, false, &group_of));
+        together.chain(apart).collect()
+    }
+
+    /// The [`SoftConstraint`]s that `groups` breaks, each with its weight
+    /// (an `apart` set once for each pair sharing a group).
+    pub fn unsatisfied_soft(&self, groups: &[Group]) -> Vec<(Violation, u64)> {
+        let group_of = group_index_by_member(groups);
+        self.soft
+            .iter()
+            .flat_map(|soft| {
+                broken(&soft.ids, soft.together, &group_of)
+                    .into_iter()
+                    .map(move |violation| (violation, soft.weight))
+            })
+            .collect()
+    }
+
+    /// The total weight of the [`SoftConstraint`]s that `groups` breaks.
+    pub fn penalty(&self, groups: &[Group]) -> u64 {
+        self.unsatisfied_soft(groups).iter().map(|(_, weight)| weight).sum()
+    }
+
+    /// The constraints as they apply to the students in `pool`: other students
+    /// are left out, and sets with fewer than two students left are dropped.
+    pub fn restricted_to(&self, pool: &[Student]) -> Constraints {
+        let pool: HashSet<&str> = pool.iter().map(|s| s.id.as_str()).collect();
+        let restrict = |sets: &[Vec<StudentId>]| -> Vec<Vec<StudentId>> {
+            sets.iter()
+                .map(|ids| ids.iter().filter(|id| pool.contains(id.as_str())).cloned().collect::<Vec<_>>())
+                .filter(|ids| ids.len() >= 2)
+                .collect()
+        };
+        Constraints {
+            together: restrict(&self.together),
+            apart: restrict(&self.apart),
+            soft: self
+                .soft
+                .iter()
+                .filter_map(|soft| {
+                    let ids = restrict(std::slice::from_ref(&soft.ids)).pop()?;
+                    Some(SoftConstraint { ids, ..soft.clone() })
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether `a` and `b` must not share a group.
+    fn must_be_apart(&self, a: &str, b: &str) -> bool {
+        self.apart
+            .iter()
+            .any(|ids| a != b && ids.iter().any(|id| id == a) && ids.iter().any(|id| id == b))
+    }
+
+    /// Whether adding `unit` to a group with `members` would put two students
+    /// who must be kept apart together.
+    pub(crate) fn conflicts(&self, members: &[Student], unit: &[Student]) -> bool {
+        members
+            .iter()
+            .any(|m| unit.iter().any(|u| self.must_be_apart(&m.id, &u.id)))
+    }
+}
+
+/// An error at the 0-based `line_no` of a constraints or pairs file.
+fn error_at(line_no: usize, msg: Msg, args: &[&dyn fmt::Display]) -> String {
+    message(Msg::AtLine, &[&(line_no + 1), &message(msg, args)])
+}
+
+/// The constraints in the constraints file format, one directive per line.
+impl fmt::Display for Constraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ids in &self.together {
+            writeln!(f, "together: {}", ids.join(","))?;
+        }
+        for ids in &self.apart {
+            writeln!(f, "apart: {}", ids.join(","))?;
+        }
+        for soft in &self.soft {
+            let directive = if soft.together { "together" } else { "apart" };
+            writeln!(f, "{}: {} weight={}", directive, soft.ids.join(","), soft.weight)?;
+        }
+        Ok(())
+    }
+}
+
+/// A constraint that a grouping does not satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    Together(Vec<StudentId>),
+    /// Two students who must be kept apart share a group
+    Apart(StudentId, StudentId),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Together(ids) => write!(f, "together: {}", ids.join(",")),
+            Violation::Apart(a, b) => write!(f, "apart: {},{}", a, b),
+        }
+    }
+}
+
+/// How the set `ids` is broken by the groups of `group_of`: once when a
+/// `together` set is split, and once per pair sharing a group for `apart`.
+fn broken(ids: &[StudentId], together: bool, group_of: &HashMap<&str, usize>) -> Vec<Violation> {
+    if together {
+        let mut placed = ids.iter().filter_map(|id| group_of.get(id.as_str()));
+        return match placed.next() {
+            Some(first) if placed.any(|g| g != first) => vec![Violation::Together(ids.to_vec())],
+            _ => Vec::new(),
+        };
+    }
+    let mut violations = Vec::new();
+    for (i, a) in ids.iter().enumerate() {
+        for b in &ids[i + 1..] {
+            match (group_of.get(a.as_str()), group_of.get(b.as_str())) {
+                (Some(ga), Some(gb)) if ga == gb => violations.push(Violation::Apart(a.clone(), b.clone())),
+                _ => {}
+            }
+        }
+    }
+    violations
+}
+
+/// Map each student to the index of the group containing them.
+fn group_index_by_member(groups: &[Group]) -> HashMap<&str, usize> {
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.ids().map(move |id| (id.as_str(), i)))
+        .collect()
+}
+
+/// Whether all of `ids` share one of `groups`.
+pub fn kept_together(ids: &[StudentId], groups: &[Group]) -> bool {
+    groups.iter().any(|group| ids.iter().all(|id| group.contains(id)))
+}
+
+/// A constraint set, by its index in [`Constraints::together`],
+/// [`Constraints::apart`] or [`Constraints::soft`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SetRef {
+    Together(usize),
+    Apart(usize),
+    Soft(usize),
+}
+
+/// Counts the constraints broken by a grouping while students are swapped
+/// between groups, looking only at the sets of the swapped students instead
+/// of checking every constraint again.
+pub(crate) struct SwapCheck<'a> {
+    constraints: &'a Constraints,
+    group_of: HashMap<StudentId, usize>,
+    sets_of: HashMap<&'a str, Vec<SetRef>>,
+}
+
+impl<'a> SwapCheck<'a> {
+    pub(crate) fn new(constraints: &'a Constraints, groups: &[Group]) -> Self {
+        let mut sets_of: HashMap<&str, Vec<SetRef>> = HashMap::new();
+        for (i, ids) in constraints.together.iter().enumerate() {
+            for id in ids {
+                sets_of.entry(id.as_str()).or_default().push(SetRef::Together(i));
+            }
+        }
+        for (i, ids) in constraints.apart.iter().enumerate() {
+            for id in ids {
+                sets_of.entry(id.as_str()).or_default().push(SetRef::Apart(i));
+            }
+        }
+        for (i, soft) in constraints.soft.iter().enumerate() {
+            for id in &soft.ids {
+                sets_of.entry(id.as_str()).or_default().push(SetRef::Soft(i));
+            }
+        }
+        // Only the students named in constraints are looked up
+        let group_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(i, g)| g.ids().map(move |id| (id, i)))
+            .filter(|(id, _)| sets_of.contains_key(id.as_str()))
+            .map(|(id, i)| (id.clone(), i))
+            .collect();
+        SwapCheck {
+            constraints,
+            group_of,
+            sets_of,
+        }
+    }
+
+    /// Violations, as counted by [`Constraints::unsatisfied`], among the sets
+    /// that `x` or `y` belong to.
+    pub(crate) fn violations_around(&self, x: &str, y: &str) -> usize {
+        self.sets_around(x, y)
+            .into_iter()
+            .map(|set| match set {
+                SetRef::Together(i) => self.broken(&self.constraints.together[i], true),
+                SetRef::Apart(i) => self.broken(&self.constraints.apart[i], false),
+                SetRef::Soft(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Penalty, as counted by [`Constraints::penalty`], of the soft
+    /// constraints that `x` or `y` belong to.
+    pub(crate) fn penalty_around(&self, x: &str, y: &str) -> u64 {
+        self.sets_around(x, y)
+            .into_iter()
+            .map(|set| match set {
+                SetRef::Soft(i) => {
+                    let soft = &self.constraints.soft[i];
+                    self.broken(&soft.ids, soft.together) as u64 * soft.weight
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The sets that `x` or `y` belong to, each once.
+    fn sets_around(&self, x: &str, y: &str) -> Vec<SetRef> {
+        let mut sets: Vec<SetRef> = [x, y]
+            .iter()
+            .filter_map(|id| self.sets_of.get(id))
+            .flatten()
+            .copied()
+            .collect();
+        // A set holding both students counts once
+        sets.sort_unstable();
+        sets.dedup();
+        sets
+    }
+
+    /// How often the set `ids` is broken: once when a `together` set is
+    /// split, and once per pair sharing a group for `apart`.
+    fn broken(&self, ids: &[StudentId], together: bool) -> usize {
+        if together {
+            let mut placed = ids.iter().filter_map(|id| self.group_of.get(id));
+            return match placed.next() {
+                Some(first) if placed.any(|g| g != first) => 1,
+                _ => 0,
+            };
+        }
+        ids.iter()
+            .enumerate()
+            .map(|(k, a)| {
+                ids[k + 1..]
+                    .iter()
+                    .filter(|b| match (self.group_of.get(a), self.group_of.get(*b)) {
+                        (Some(ga), Some(gb)) => ga == gb,
+                        _ => false,
+                    })
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Record that `x` (now in group `gx`) and `y` (now in group `gy`) swapped places.
+    pub(crate) fn swapped(&mut self, x: &str, gx: usize, y: &str, gy: usize) {
+        if let Some(group) = self.group_of.get_mut(x) {
+            *group = gx;
+        }
+        if let Some(group) = self.group_of.get_mut(y) {
+            *group = gy;
+        }
+    }
+}
+
+/// Reorganize groups like [`crate::grouping::regroup`] while honoring `constraints`.
+///
+/// In interactive mode, full groups are kept unless they violate a constraint;
+/// everyone else is packed into groups of 2-3 people so that each `together`
+/// set ends up in one group and no `apart` students share a group. When that
+/// fails, the packing is retried with shuffled orders, and then searched for
+/// exhaustively by [`solver::find`]; if there is no way, the attempt with the
+/// fewest violations is kept. Constraints that cannot be honored (e.g. a
+/// `together` set of more than 3 students) show up in [`Constraints::unsatisfied`],
+/// and [`solver::explain`] tells which of them conflict.
+pub fn reorganize_with_constraints<R: Rng + ?Sized>(
+    groups: Vec<Group>,
+    mode: Mode,
+    sizing: Sizing,
+    constraints: &Constraints,
+    rng: &mut R,
+) -> Vec<Group> {
+    let mut final_groups = Vec::new();
+    let mut pool: Vec<Student> = Vec::new();
+
+    match mode {
+        Mode::Batch => {
+            for group in groups {
+                pool.extend(group.members);
+            }
+        }
+        Mode::Interactive => {
+            let present: HashSet<StudentId> = groups.iter().flat_map(Group::ids).cloned().collect();
+            for group in groups {
+                if sizing.is_full(&group)
+                    && !splits_together_set(&group, &present, constraints)
+                    && constraints.unsatisfied(std::slice::from_ref(&group)).is_empty()
+                {
+                    final_groups.push(group);
+                } else {
+                    pool.extend(group.members);
+                }
+            }
+            // A lone leftover student would form a singleton; reopen the last
+            // full group so that it can be split into 2+2 instead (with
+            // `--min`, as many full groups as it takes)
+            while sizing.too_few(pool.len()) {
+                let Some(last) = final_groups.pop() else {
+                    break;
+                };
+                pool.extend(last.members);
+            }
+        }
+    }
+
+    let (mut best, violations) = best_packing(&pool, mode, sizing, constraints, rng);
+    if violations > 0 && !final_groups.is_empty() {
+        // Solve around the conflict by also reopening the groups that were kept
+        for group in final_groups.drain(..).rev() {
+            pool.splice(0..0, group.members);
+        }
+        best = best_packing(&pool, mode, sizing, constraints, rng).0;
+    }
+
+    final_groups.extend(best);
+    final_groups
+}
+
+/// Pack `pool` into groups, retrying with shuffled orders until no constraint
+/// is violated, then falling back to an exhaustive search. Returns the best
+/// packing found and its number of violations.
+fn best_packing<R: Rng + ?Sized>(
+    pool: &[Student],
+    mode: Mode,
+    sizing: Sizing,
+    constraints: &Constraints,
+    rng: &mut R,
+) -> (Vec<Group>, usize) {
+    let largest = sizing.largest_size(pool.len());
+    let mut units = together_units(pool, largest, constraints);
+    if mode == Mode::Interactive {
+        units.shuffle(rng);
+    }
+
+    // The first attempt keeps the unit order (input order in batch mode)
+    let mut best = pack_units(units.clone(), sizing, constraints);
+    let mut best_violations = constraints.unsatisfied(&best).len();
+    let attempts = (ATTEMPT_BUDGET / pool.len().max(1)).clamp(1, MAX_ATTEMPTS);
+    for _ in 1..attempts {
+        if best_violations == 0 {
+            break;
+        }
+        units.shuffle(rng);
+        let attempt = pack_units(units.clone(), sizing, constraints);
+        let violations = constraints.unsatisfied(&attempt).len();
+        if violations < best_violations {
+            best = attempt;
+            best_violations = violations;
+        }
+    }
+    if best_violations > 0 {
+        // Greedy packing can miss a way that exists, e.g. when sets only fit in one arrangement
+        if let Some(found) = solver::find(&units.concat(), sizing, constraints) {
+            return (found, 0);
+        }
+    }
+    (best, best_violations)
+}
+
+/// Whether a full group holds only part of a `together` set whose other
+/// members were also entered.
+fn splits_together_set(group: &Group, present: &HashSet<StudentId>, constraints: &Constraints) -> bool {
+    constraints.together.iter().any(|ids| {
+        let inside = ids.iter().filter(|id| group.contains(id)).count();
+        let entered = ids.iter().filter(|id| present.contains(*id)).count();
+        inside > 0 && inside < entered
+    })
+}
+
+/// Partition `pool` into units of students that must stay together, in order of
+/// first appearance. Overlapping `together` sets are merged, and units larger
+/// than `largest` (the largest group) are broken up.
+fn together_units(pool: &[Student], largest: usize, constraints: &Constraints) -> Vec<Vec<Student>> {
+    // A unit larger than a group can never be placed; break it up
+    merge_together(pool, constraints)
+        .into_iter()
+        .flat_map(|unit| {
+            if unit.len() > largest.max(MAX_GROUP_SIZE) {
+                unit.into_iter().map(|student| vec![student]).collect()
+            } else {
+                vec![unit]
+            }
+        })
+        .collect()
+}
+
+/// Partition `pool` into units of students that must stay together, in order of
+/// first appearance, merging overlapping `together` sets.
+pub(crate) fn merge_together(pool: &[Student], constraints: &Constraints) -> Vec<Vec<Student>> {
+    // Union-find over positions in the pool
+    let index: HashMap<&str, usize> = pool.iter().enumerate().map(|(i, s)| (s.id.as_str(), i)).collect();
+    let mut parent: Vec<usize> = (0..pool.len()).collect();
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for ids in &constraints.together {
+        let positions: Vec<usize> = ids.iter().filter_map(|id| index.get(id.as_str()).copied()).collect();
+        for pair in positions.windows(2) {
+            let a = find(&mut parent, pair[0]);
+            let b = find(&mut parent, pair[1]);
+            if a != b {
+                parent[b.max(a)] = a.min(b);
+            }
+        }
+    }
+
+    let mut units: Vec<Vec<Student>> = Vec::new();
+    let mut unit_of_root: HashMap<usize, usize> = HashMap::new();
+    for (i, student) in pool.iter().enumerate() {
+        let root = find(&mut parent, i);
+        let unit = *unit_of_root.entry(root).or_insert_with(|| {
+            units.push(Vec::new());
+            units.len() - 1
+        });
+        units[unit].push(student.clone());
+    }
+    units
+}
+
+/// Pack units into groups of 2-3 people (of the sizes of `--groups` or
+/// `--min`/`--max` when given) without splitting any unit.
+///
+/// The fewest possible 2-person groups are used (with [`Sizing::Pairs`], the
+/// fewest 3-person groups); if the units cannot be packed at all, they are
+/// broken up into individual students.
+fn pack_units(units: Vec<Vec<Student>>, sizing: Sizing, constraints: &Constraints) -> Vec<Group> {
+    let n: usize = units.iter().map(Vec::len).sum();
+    if n == 0 {
+        return Vec::new();
+    }
+    if n == 1 {
+        return vec![Group::from_members(units.into_iter().flatten().collect())];
+    }
+
+    let count = |size: usize| units.iter().filter(|u| u.len() == size).count();
+    let (triples, pairs) = (count(3), count(2));
+
+    if let Sizing::Groups(_) | Sizing::Range { .. } = sizing {
+        let sizes = sizing.sizes(n);
+        return fill_sized_bins(&units, &sizes, constraints).unwrap_or_else(|| {
+            // Infeasible: fall back to ignoring `together`
+            let singles: Vec<Vec<Student>> = units.into_iter().flatten().map(|s| vec![s]).collect();
+            fill_sized_bins(&singles, &sizes, constraints).expect("single students always fit")
+        });
+    }
+
+    // Size plans run from the most 3-person groups downwards
+    let threes = |sizes: &[usize]| sizes.iter().filter(|&&size| size == 3).count();
+    let mut preferred = None;
+    for sizes in solver::size_plans(n, sizing) {
+        let (t, w) = (threes(&sizes), sizes.len() - threes(&sizes));
+        if triples <= t && pairs <= (t - triples) + w {
+            return fill_bins(units, t, w, constraints);
+        }
+        preferred.get_or_insert((t, w));
+    }
+
+    // Infeasible: fall back to ignoring the constraints
+    let (t, w) = preferred.expect("there is a size plan for two or more students");
+    let singles = units.into_iter().flatten().map(|s| vec![s]).collect();
+    fill_bins(singles, t, w, constraints)
+}
+
+/// Place units into `threes` 3-person bins followed by `twos` 2-person bins,
+/// avoiding `apart` conflicts where possible. The caller guarantees that the
+/// units fit.
+fn fill_bins(
+    units: Vec<Vec<Student>>,
+    threes: usize,
+    twos: usize,
+    constraints: &Constraints,
+) -> Vec<Group> {
+    let mut bins: Vec<(usize, Vec<Student>)> = (0..threes)
+        .map(|_| (3, Vec::new()))
+        .chain((0..twos).map(|_| (2, Vec::new())))
+        .collect();
+    // Units without any of these students cannot conflict, so they go into the first bin with room
+    let apart: HashSet<&str> = constraints.apart.iter().flatten().map(String::as_str).collect();
+    // Room only shrinks, so the bins before these positions never need looking at again:
+    // `open[size]` is the first bin that may have room for `size`, `empty_two` the first empty 2-person bin
+    let mut open = [0; MAX_GROUP_SIZE + 1];
+    let mut empty_two = threes;
+
+    // Larger units first: 3s into 3-bins, 2s into 2-bins before 3-bins, then singles
+    for size in [3, 2, 1] {
+        for unit in units.iter().filter(|u| u.len() == size) {
+            while empty_two < bins.len() && !bins[empty_two].1.is_empty() {
+                empty_two += 1;
+            }
+            while open[size] < bins.len() && bins[open[size]].0 - bins[open[size]].1.len() < size {
+                open[size] += 1;
+            }
+            // Keep 3-person bins free for triples and singles while 2-person bins remain
+            let twos_free = size == 2 && empty_two < bins.len();
+            let fits = |(cap, members): &(usize, Vec<Student>)| cap - members.len() >= size && (!twos_free || *cap == 2);
+            let start = if twos_free { empty_two } else { open[size] };
+            let first = (start..bins.len()).find(|&i| fits(&bins[i])).expect("units were checked to fit");
+            let bin = if unit.iter().any(|s| apart.contains(s.id.as_str())) {
+                (first..bins.len())
+                    .find(|&i| fits(&bins[i]) && !constraints.conflicts(&bins[i].1, unit))
+                    .unwrap_or(first)
+            } else {
+                first
+            };
+            bins[bin].1.extend(unit.iter().cloned());
+        }
+    }
+
+    bins.into_iter().map(|(_, members)| Group::from_members(members)).collect()
+}
+
+/// Place units into bins of the given `sizes`, largest units first, each into
+/// the bin with the most room that has no `apart` conflict (or any bin with
+/// room). Returns `None` when some unit does not fit.
+fn fill_sized_bins(units: &[Vec<Student>], sizes: &[usize], constraints: &Constraints) -> Option<Vec<Group>> {
+    let mut order: Vec<&Vec<Student>> = units.iter().collect();
+    order.sort_by_key(|unit| std::cmp::Reverse(unit.len()));
+
+    let apart: HashSet<&str> = constraints.apart.iter().flatten().map(String::as_str).collect();
+    let mut bins: Vec<Vec<Student>> = vec![Vec::new(); sizes.len()];
+    for unit in order {
+        let room = |i: usize, bins: &[Vec<Student>]| sizes[i] - bins[i].len();
+        let candidates: Vec<usize> = (0..sizes.len()).filter(|&i| room(i, &bins) >= unit.len()).collect();
+        let can_conflict = unit.iter().any(|s| apart.contains(s.id.as_str()));
+        let bin = candidates
+            .iter()
+            .copied()
+            .filter(|&i| !can_conflict || !constraints.conflicts(&bins[i], unit))
+            .max_by_key(|&i| (room(i, &bins), std::cmp::Reverse(i)))
+            .or_else(|| candidates.first().copied())?;
+        bins[bin].extend(unit.iter().cloned());
+    }
+    Some(bins.into_iter().map(Group::from_members).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn ids(range: std::ops::RangeInclusive<usize>) -> Vec<StudentId> {
+        range.map(|i| format!("S{:03}", i)).collect()
+    }
+
+    #[test]
+    fn test_parse_constraints() {
+        let text = "# pairs\ntogether: S001, S007\n\ntogether:S002,S003,S004  # trio\n";
+        let constraints = Constraints::parse(text).unwrap();
+        assert_eq!(constraints.together.len(), 2);
+        assert_eq!(constraints.together[0], vec!["S001", "S007"]);
+        assert_eq!(constraints.together[1], vec!["S002", "S003", "S004"]);
+
+        assert!(Constraints::parse("together: S001").is_err());
+        let constraints = Constraints::parse("apart: S003,S015").unwrap();
+        assert_eq!(constraints.apart, vec![vec!["S003", "S015"]]);
+        assert!(Constraints::parse("nearby: S001,S002").is_err());
+        assert!(Constraints::parse("S001,S002").is_err());
+    }
+
+    #[test]
+    fn test_parse_avoid_pairs() {
+        let text = "# known conflicts\nS001,S002\n\n S003 ,\"S004\"\n";
+        let pairs = Constraints::parse_pairs(text).unwrap();
+        assert_eq!(pairs, vec![vec!["S001", "S002"], vec!["S003", "S004"]]);
+
+        assert!(Constraints::parse_pairs("S001").is_err());
+        assert!(Constraints::parse_pairs("S001,S002,S003").is_err());
+        assert!(Constraints::parse_pairs("S001,S001").is_err());
+    }
+
+    #[test]
+    fn test_preferences() {
+        let mut constraints = Constraints::default();
+        constraints.prefer(Constraints::parse_pairs("S001,S002\nS003,S006").unwrap());
+        assert!(!constraints.has_hard());
+        assert_eq!(constraints.soft.len(), 2);
+
+        let groups = vec![Group::from_members(ids(1..=3)), Group::from_members(ids(4..=6))];
+        assert!(kept_together(&constraints.soft[0].ids, &groups));
+        assert!(!kept_together(&constraints.soft[1].ids, &groups));
+        assert_eq!(constraints.penalty(&groups), PREFERENCE_WEIGHT);
+    }
+
+    #[test]
+    fn test_together_honored_in_interactive_mode() {
+        let constraints = Constraints::parse("together: S001,S010\ntogether: S004,S005").unwrap();
+        for seed in 0..50 {
+            let groups: Vec<Group> = ids(1..=10).into_iter().map(|id| Group::from_members(vec![id])).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_with_constraints(groups, Mode::Interactive, Sizing::Triples, &constraints, &mut rng);
+
+            assert!(constraints.unsatisfied(&result).is_empty(), "seed {}", seed);
+            assert_eq!(result.iter().map(|g| g.members.len()).sum::<usize>(), 10);
+            assert!(result.iter().all(|g| (2..=3).contains(&g.members.len())));
+        }
+    }
+
+    #[test]
+    fn test_full_group_splitting_a_pair_is_dissolved() {
+        let constraints = Constraints::parse("together: S001,S004").unwrap();
+        let groups = vec![
+            Group::from_members(ids(1..=3)),
+            Group::from_members(ids(4..=5)),
+            Group::from_members(ids(6..=6)),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+        let result = reorganize_with_constraints(groups, Mode::Interactive, Sizing::Triples, &constraints, &mut rng);
+
+        assert!(constraints.unsatisfied(&result).is_empty());
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_batch_mode_uses_more_pairs_when_needed() {
+        // Three pairs among six students cannot fit into 3+3
+        let constraints = Constraints::parse("together: S001,S002\ntogether: S003,S004\ntogether: S005,S006").unwrap();
+        let groups = vec![Group::from_members(ids(1..=6))];
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = reorganize_with_constraints(groups, Mode::Batch, Sizing::Triples, &constraints, &mut rng);
+
+        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 2]);
+        assert!(constraints.unsatisfied(&result).is_empty());
+    }
+
+    #[test]
+    fn test_oversized_together_set_is_reported() {
+        let constraints = Constraints::parse("together: S001,S002,S003,S004").unwrap();
+        let groups = vec![Group::from_members(ids(1..=6))];
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = reorganize_with_constraints(groups, Mode::Batch, Sizing::Triples, &constraints, &mut rng);
+
+        assert_eq!(result.iter().map(|g| g.members.len()).sum::<usize>(), 6);
+        assert_eq!(
+            constraints.unsatisfied(&result),
+            vec![Violation::Together(ids(1..=4))]
+        );
+    }
+
+    #[test]
+    fn test_apart_honored() {
+        let constraints = Constraints::parse("apart: S001,S002,S003\napart: S004,S005").unwrap();
+        for seed in 0..20 {
+            let groups: Vec<Group> = ids(1..=9).into_iter().map(|id| Group::from_members(vec![id])).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_with_constraints(groups, Mode::Interactive, Sizing::Triples, &constraints, &mut rng);
+            assert!(constraints.unsatisfied(&result).is_empty(), "seed {}", seed);
+        }
+
+        // In batch mode the input order would put S001-S003 together
+        let groups = vec![Group::from_members(ids(1..=9))];
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = reorganize_with_constraints(groups, Mode::Batch, Sizing::Triples, &constraints, &mut rng);
+        assert!(constraints.unsatisfied(&result).is_empty());
+    }
+
+    #[test]
+    fn test_full_group_with_apart_pair_is_regrouped() {
+        let constraints = Constraints::parse("apart: S001,S002").unwrap();
+        let groups = vec![Group::from_members(ids(1..=3)), Group::from_members(ids(4..=6))];
+        let mut rng = StdRng::seed_from_u64(3);
+        let result = reorganize_with_constraints(groups, Mode::Interactive, Sizing::Triples, &constraints, &mut rng);
+
+        // The other full group is reopened so that S001 and S002 can be separated
+        assert!(constraints.unsatisfied(&result).is_empty());
+        assert_eq!(result.iter().map(|g| g.members.len()).sum::<usize>(), 6);
+    }
+
+    #[test]
+    fn test_unsatisfiable_apart_is_reported() {
+        // Four students who all must be apart cannot fit into two groups
+        let constraints = Constraints::parse("apart: S001,S002,S003,S004").unwrap();
+        let groups = vec![Group::from_members(ids(1..=4))];
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = reorganize_with_constraints(groups, Mode::Batch, Sizing::Triples, &constraints, &mut rng);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(constraints.unsatisfied(&result).len(), 2);
+    }
+
+    #[test]
+    fn test_fixed_group_count_honors_constraints() {
+        let constraints = Constraints::parse("together: S001,S002,S003,S004
+apart: S005,S006").unwrap();
+        for seed in 0..20 {
+            let groups = vec![Group::from_members(ids(1..=10))];
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = reorganize_with_constraints(groups, Mode::Batch, Sizing::Groups(2), &constraints, &mut rng);
+
+            let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+            assert_eq!(sizes, vec![5, 5], "seed {}", seed);
+            assert!(constraints.unsatisfied(&result).is_empty(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_swap_check_counts_like_unsatisfied() {
+        let constraints = Constraints::parse("together: S001,S002\napart: S001,S003,S005").unwrap();
+        let mut groups = vec![Group::from_members(ids(1..=3)), Group::from_members(ids(4..=6))];
+        let mut check = SwapCheck::new(&constraints, &groups);
+        assert_eq!(check.violations_around("S002", "S005"), 1);
+
+        // Swapping S002 and S005 splits the pair and puts S001, S003 and S005 together
+        groups[0].members[1] = "S005".into();
+        groups[1].members[1] = "S002".into();
+        check.swapped("S002", 1, "S005", 0);
+        assert_eq!(check.violations_around("S002", "S005"), 4);
+        assert_eq!(constraints.unsatisfied(&groups).len(), 4);
+    }
+
+    #[test]
+    fn test_soft_constraints() {
+        let text = "apart: S001,S002 weight=5\ntogether: S003, S004 weight=2  # friends\napart: S005,S006";
+        let constraints = Constraints::parse(text).unwrap();
+        assert_eq!(constraints.apart, vec![vec!["S005", "S006"]]);
+        assert_eq!(constraints.soft.len(), 2);
+        assert_eq!(constraints.soft[1].ids, vec!["S003", "S004"]);
+        assert!(constraints.has_hard());
+        assert_eq!(Constraints::parse(&constraints.to_string()).unwrap(), constraints);
+        assert!(Constraints::parse("apart: S001,S002 weight=0").is_err());
+        assert!(Constraints::parse("apart: S001,S002 weight=heavy").is_err());
+
+        let mut groups = vec![Group::from_members(ids(1..=3)), Group::from_members(ids(4..=6))];
+        // Soft constraints are weighed, never reported as failures
+        assert_eq!(constraints.unsatisfied(&groups).len(), 1);
+        assert_eq!(constraints.penalty(&groups), 5 + 2);
+        let mut check = SwapCheck::new(&constraints, &groups);
+        assert_eq!(check.penalty_around("S002", "S004"), 7);
+
+        groups[0].members[1] = "S004".into();
+        groups[1].members[0] = "S002".into();
+        check.swapped("S002", 1, "S004", 0);
+        assert_eq!(constraints.penalty(&groups), 0);
+        assert_eq!(check.penalty_around("S002", "S004"), 0);
+    }
+}
@@ -0,0 +1,393 @@
+//! A SQLite database of students, sessions, and group assignments.
+//!
+//! The database is accessed through the `sqlite3` command-line shell, so no
+//! SQLite library has to be linked in. Tables:
+//!
+//! - `students(id, name, attributes)`: the roster; `attributes` is a JSON object
+//! - `sessions(id, timestamp, seed)`: one row per grouping
+//! - `assignments(session_id, student_id, group_label)`: who was in which group
+//! - `leaders(session_id, student_id)`: group leaders chosen with `--assign-leader`
+//! - `scores(session_id, group_label, points, timestamp)`: points given with `score`
+
+use crate::group::{group_index_to_letter, group_letter_to_index, Group, Student, StudentId};
+use crate::history::History;
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+use crate::roster::RosterEntry;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS students (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    attributes TEXT NOT NULL DEFAULT '{}'
+);
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    -- Text, since seeds may not fit in SQLite's signed 64-bit integers
+    seed TEXT
+);
+CREATE TABLE IF NOT EXISTS assignments (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    student_id TEXT NOT NULL,
+    group_label TEXT NOT NULL,
+    PRIMARY KEY (session_id, student_id)
+);
+CREATE TABLE IF NOT EXISTS leaders (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    student_id TEXT NOT NULL,
+    PRIMARY KEY (session_id, student_id)
+);
+CREATE TABLE IF NOT EXISTS scores (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    group_label TEXT NOT NULL,
+    points INTEGER NOT NULL,
+    timestamp TEXT NOT NULL
+);
+";
+
+/// One recorded grouping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: u64,
+    pub timestamp: String,
+    pub seed: Option<u64>,
+    /// Groups in label order (A, B, ...)
+    pub groups: Vec<Group>,
+    /// Leaders of the groups, if any were chosen
+    pub leaders: Vec<StudentId>,
+    /// Total points given to each group with `score`, in group order
+    pub scores: Vec<i64>,
+}
+
+/// A SQLite database file.
+#[derive(Debug, Clone)]
+pub struct Database {
+    path: PathBuf,
+}
+
+impl Database {
+    /// Open the database, creating the file and tables if needed.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let db = Database {
+            path: path.to_path_buf(),
+        };
+        db.execute(SCHEMA)?;
+        Ok(db)
+    }
+
+    /// Add students to the roster, updating the name and attributes of
+    /// students that are already there. Returns the number of students.
+    pub fn import_students(&self, entries: &[RosterEntry]) -> Result<usize, String> {
+        let mut sql = String::from("BEGIN;\n");
+        for entry in entries {
+            let attributes = Value::Object(
+                entry
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Value::from(v.as_str())))
+                    .collect(),
+            );
+            sql.push_str(&format!(
+                "INSERT INTO students (id, name, attributes) VALUES ({}, {}, {}) \
+                 ON CONFLICT (id) DO UPDATE SET name = excluded.name, attributes = excluded.attributes;\n",
+                quote(&entry.id),
+                entry.name.as_deref().map_or("NULL".to_string(), quote),
+                quote(&attributes.to_string()),
+            ));
+        }
+        sql.push_str("COMMIT;\n");
+        self.execute(&sql)?;
+        Ok(entries.len())
+    }
+
+    /// All students in the roster, in the order they were imported.
+    pub fn students(&self) -> Result<Vec<RosterEntry>, String> {
+        let rows = self.query("SELECT id, name, attributes FROM students ORDER BY rowid;")?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let attributes: BTreeMap<String, String> = row
+                    .get("attributes")
+                    .and_then(Value::as_str)
+                    .and_then(|text| Value::parse(text).ok())
+                    .map(|value| match value {
+                        Value::Object(pairs) => pairs
+                            .into_iter()
+                            .filter_map(|(k, v)| v.as_str().map(|v| (k, v.to_string())))
+                            .collect(),
+                        _ => BTreeMap::new(),
+                    })
+                    .unwrap_or_default();
+                RosterEntry {
+                    id: text_column(row, "id"),
+                    name: row.get("name").and_then(Value::as_str).map(String::from),
+                    attributes,
+                }
+            })
+            .collect())
+    }
+
+    /// Record one grouping and its leaders. Returns the new session's number.
+    pub fn record_session(
+        &self,
+        groups: &[Group],
+        leaders: &[StudentId],
+        timestamp: &str,
+        seed: Option<u64>,
+    ) -> Result<u64, String> {
+        let mut sql = String::from("BEGIN;\n");
+        sql.push_str(&format!(
+            "INSERT INTO sessions (timestamp, seed) VALUES ({}, {});\n",
+            quote(timestamp),
+            seed.map_or("NULL".to_string(), |s| quote(&s.to_string()))
+        ));
+        for (i, group) in groups.iter().enumerate() {
+            let label = group_index_to_letter(i);
+            for member in &group.members {
+                sql.push_str(&format!(
+                    "INSERT OR REPLACE INTO assignments (session_id, student_id, group_label) \
+                     VALUES ((SELECT MAX(id) FROM sessions), {}, {});\n",
+                    quote(&member.id),
+                    quote(&label)
+                ));
+            }
+        }
+        for leader in leaders {
+            sql.push_str(&format!(
+                "INSERT OR IGNORE INTO leaders (session_id, student_id) VALUES ((SELECT MAX(id) FROM sessions), {});\n",
+                quote(leader)
+            ));
+        }
+        sql.push_str("COMMIT;\nSELECT MAX(id) AS id FROM sessions;\n");
+        let rows = self.query(&sql)?;
+        rows.first()
+            .and_then(|row| row.get("id"))
+            .and_then(Value::as_u64)
+            .ok_or_else(|| message(Msg::DbNoSession, &[]))
+    }
+
+    /// All recorded sessions, oldest first.
+    pub fn sessions(&self) -> Result<Vec<Session>, String> {
+        let rows = self.query(
+            "SELECT s.id, s.timestamp, s.seed, a.student_id, a.group_label \
+             FROM sessions s LEFT JOIN assignments a ON a.session_id = s.id \
+             ORDER BY s.id, length(a.group_label), a.group_label, a.rowid;",
+        )?;
+
+        let mut sessions: Vec<Session> = Vec::new();
+        let mut last_label: Option<String> = None;
+        for row in &rows {
+            let id = row.get("id").and_then(Value::as_u64).unwrap_or(0);
+            if sessions.last().map(|s| s.id) != Some(id) {
+                sessions.push(Session {
+                    id,
+                    timestamp: text_column(row, "timestamp"),
+                    seed: row.get("seed").and_then(Value::as_str).and_then(|s| s.parse().ok()),
+                    groups: Vec::new(),
+                    leaders: Vec::new(),
+                    scores: Vec::new(),
+                });
+                last_label = None;
+            }
+            let (Some(student), Some(label)) = (
+                row.get("student_id").and_then(Value::as_str),
+                row.get("group_label").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let session = sessions.last_mut().expect("a session was just pushed");
+            if last_label.as_deref() != Some(label) {
+                session.groups.push(Group::new());
+                last_label = Some(label.to_string());
+            }
+            if let Some(group) = session.groups.last_mut() {
+                group.members.push(Student::new(student));
+            }
+        }
+
+        for row in self.query("SELECT session_id, student_id FROM leaders ORDER BY session_id, rowid;")? {
+            let id = row.get("session_id").and_then(Value::as_u64);
+            if let Some(session) = sessions.iter_mut().find(|s| Some(s.id) == id) {
+                session.leaders.push(text_column(&row, "student_id"));
+            }
+        }
+
+        for session in &mut sessions {
+            session.scores = vec![0; session.groups.len()];
+        }
+        let rows = self.query(
+            "SELECT session_id, group_label, SUM(points) AS points FROM scores GROUP BY session_id, group_label;",
+        )?;
+        for row in rows {
+            let id = row.get("session_id").and_then(Value::as_u64);
+            let group = row.get("group_label").and_then(Value::as_str).and_then(group_letter_to_index);
+            let points = row.get("points").and_then(Value::as_i64).unwrap_or(0);
+            let session = sessions.iter_mut().find(|s| Some(s.id) == id);
+            if let Some(total) = session.zip(group).and_then(|(session, group)| session.scores.get_mut(group)) {
+                *total += points;
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// Give `points` to the group at index `group` of session `session`.
+    pub fn record_score(&self, session: u64, group: usize, points: i64, timestamp: &str) -> Result<(), String> {
+        self.execute(&format!(
+            "INSERT INTO scores (session_id, group_label, points, timestamp) VALUES ({}, {}, {}, {});\n",
+            session,
+            quote(&group_index_to_letter(group)),
+            points,
+            quote(timestamp)
+        ))
+    }
+
+    /// Pairing and leader history of all recorded sessions, for avoiding repeats.
+    pub fn history(&self) -> Result<History, String> {
+        Ok(History::from_sessions(&self.sessions()?))
+    }
+
+    /// Run SQL statements without reading any output.
+    fn execute(&self, sql: &str) -> Result<(), String> {
+        self.run(sql).map(|_| ())
+    }
+
+    /// Run SQL ending in one query and return its rows as JSON objects.
+    fn query(&self, sql: &str) -> Result<Vec<Value>, String> {
+        let output = self.run(&format!(".mode json\n{}", sql))?;
+        // A query without result rows prints nothing
+        let output = output.trim();
+        if output.is_empty() {
+            return Ok(Vec::new());
+        }
+        match Value::parse(output)? {
+            Value::Array(items) => Ok(items),
+            _ => Err(message(Msg::ProgramOutput, &[&"sqlite3"])),
+        }
+    }
+
+    /// Feed `sql` to the `sqlite3` shell and return what it printed.
+    fn run(&self, sql: &str) -> Result<String, String> {
+        let child = Command::new("sqlite3")
+            .arg("-batch")
+            .arg("-bail")
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(message(Msg::ProgramMissing, &[&"sqlite3"]))
+            }
+            Err(e) => return Err(message(Msg::ProgramFailed, &[&"sqlite3", &e])),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(format!("PRAGMA foreign_keys = ON;\n{}", sql).as_bytes())
+                .map_err(|e| message(Msg::CannotWrite, &[&"sqlite3", &e]))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| message(Msg::ProgramFailed, &[&"sqlite3", &e]))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{}: {}",
+                self.path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// A column that should hold text, as a string (empty when missing).
+fn text_column(row: &Value, key: &str) -> String {
+    match row.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Quote a string as an SQL literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("grouping-tool-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn sqlite_available() -> bool {
+        Command::new("sqlite3").arg("-version").output().is_ok()
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("O'Brien"), "'O''Brien'");
+    }
+
+    #[test]
+    fn test_import_and_record_sessions() {
+        if !sqlite_available() {
+            return;
+        }
+        let path = temp_db("sessions");
+        let db = Database::open(&path).unwrap();
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert("gender".to_string(), "female".to_string());
+        let entries = vec![
+            RosterEntry {
+                id: "S001".to_string(),
+                name: Some("山田'太郎".to_string()),
+                attributes,
+            },
+            RosterEntry {
+                id: "S002".to_string(),
+                name: None,
+                attributes: BTreeMap::new(),
+            },
+        ];
+        assert_eq!(db.import_students(&entries).unwrap(), 2);
+        // Importing again updates instead of duplicating
+        db.import_students(&entries[..1]).unwrap();
+        assert_eq!(db.students().unwrap(), entries);
+
+        let groups = vec![
+            Group::from_members(vec!["S001", "S002", "S003"]),
+            Group::from_members(vec!["S004", "S005"]),
+        ];
+        assert_eq!(db.record_session(&groups, &["S004".to_string()], "2025-04-01T09:00:00Z", Some(u64::MAX)).unwrap(), 1);
+        assert_eq!(db.record_session(&groups[..1], &[], "2025-04-08T09:00:00Z", None).unwrap(), 2);
+
+        let sessions = db.sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].groups, groups);
+        assert_eq!(sessions[0].seed, Some(u64::MAX));
+        assert_eq!(sessions[1].timestamp, "2025-04-08T09:00:00Z");
+        assert_eq!(sessions[0].leaders, vec!["S004"]);
+        assert_eq!(db.history().unwrap().pair_count("S001", "S002"), 2);
+        assert_eq!(db.history().unwrap().leader_count("S004"), 1);
+
+        db.record_score(1, 1, 10, "2025-04-01T10:00:00Z").unwrap();
+        db.record_score(1, 1, -3, "2025-04-01T10:05:00Z").unwrap();
+        let sessions = db.sessions().unwrap();
+        assert_eq!(sessions[0].scores, vec![0, 7]);
+        assert_eq!(sessions[1].scores, vec![0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
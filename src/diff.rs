@@ -0,0 +1,240 @@
+//! Differences between two groupings, such as before and after changing the
+//! constraints (`diff` subcommand).
+//!
+//! Groups are compared by their members rather than their labels: a student
+//! has changed groups when their groupmates are not the same, even if the
+//! group kept its label, and nobody has moved when the groups were only
+//! listed in another order. Only students in both groupings count as
+//! groupmates, so that someone joining or leaving a group moves nobody else.
+
+use crate::group::{Group, GroupNames, StudentId};
+use crate::i18n::{Lang, Msg};
+use crate::json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// A student found in both groupings whose groupmates changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub id: StudentId,
+    /// Index of the group in the old grouping
+    pub from: usize,
+    /// Index of the group in the new grouping
+    pub to: usize,
+}
+
+/// How the new grouping differs from the old one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Students with other groupmates, in the order of the new grouping
+    pub moved: Vec<Move>,
+    /// Students only in the new grouping, with their group there
+    pub added: Vec<(StudentId, usize)>,
+    /// Students only in the old grouping, with their group there
+    pub removed: Vec<(StudentId, usize)>,
+    /// Pairs together before but not any more, among students in both
+    pub broken: Vec<(StudentId, StudentId)>,
+    /// Pairs together now but not before, among students in both
+    pub created: Vec<(StudentId, StudentId)>,
+}
+
+impl Diff {
+    /// Whether the two groupings are the same.
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.broken.is_empty()
+            && self.created.is_empty()
+    }
+}
+
+/// The group index of every student.
+fn index(groups: &[Group]) -> HashMap<&str, usize> {
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, group)| group.ids().map(move |id| (id.as_str(), i)))
+        .collect()
+}
+
+/// Pairs of students in the same group, each written in sorted order, for
+/// the students that `keep` accepts.
+fn pairs(groups: &[Group], keep: impl Fn(&str) -> bool) -> BTreeSet<(StudentId, StudentId)> {
+    let mut pairs = BTreeSet::new();
+    for group in groups {
+        let members: Vec<&StudentId> = group.ids().filter(|m| keep(m)).collect();
+        for (i, a) in members.iter().enumerate() {
+            for b in &members[i + 1..] {
+                pairs.insert(if a <= b { ((*a).clone(), (*b).clone()) } else { ((*b).clone(), (*a).clone()) });
+            }
+        }
+    }
+    pairs
+}
+
+/// Compare `old` with `new`.
+pub fn diff(old: &[Group], new: &[Group]) -> Diff {
+    let old_index = index(old);
+    let new_index = index(new);
+    let in_both = |id: &str| old_index.contains_key(id) && new_index.contains_key(id);
+    let mates = |groups: &[Group], i: usize, id: &str| -> BTreeSet<StudentId> {
+        groups[i].ids().filter(|m| m.as_str() != id && in_both(m)).cloned().collect()
+    };
+
+    let mut result = Diff::default();
+    for (to, group) in new.iter().enumerate() {
+        for id in group.ids() {
+            match old_index.get(id.as_str()) {
+                Some(&from) if mates(old, from, id) != mates(new, to, id) => result.moved.push(Move {
+                    id: id.clone(),
+                    from,
+                    to,
+                }),
+                Some(_) => {}
+                None => result.added.push((id.clone(), to)),
+            }
+        }
+    }
+    for (from, group) in old.iter().enumerate() {
+        for id in group.ids().filter(|id| !new_index.contains_key(id.as_str())) {
+            result.removed.push((id.clone(), from));
+        }
+    }
+
+    let before = pairs(old, in_both);
+    let after = pairs(new, in_both);
+    result.broken = before.difference(&after).cloned().collect();
+    result.created = after.difference(&before).cloned().collect();
+    result
+}
+
+/// Render the differences as text. Groups are labelled by `old_labels` and
+/// `new_labels`, students by `member`.
+pub fn render_text(
+    diff: &Diff,
+    old_labels: &GroupNames,
+    new_labels: &GroupNames,
+    member: impl Fn(&str) -> String,
+    lang: Lang,
+) -> String {
+    let mut out = format!("\n=== {} ===\n", lang.text(Msg::DiffTitle));
+    if diff.is_empty() {
+        out.push_str(lang.text(Msg::DiffNone));
+        out.push('\n');
+        return out;
+    }
+    let heading = |labels: &GroupNames, i: usize| lang.format(Msg::GroupHeading, &[&labels.label(i)]);
+    let mut section = |title: String, lines: Vec<String>| {
+        if !lines.is_empty() {
+            out.push_str(&title);
+            out.push('\n');
+            for line in lines {
+                out.push_str(&format!("  {}\n", line));
+            }
+        }
+    };
+    section(
+        lang.format(Msg::DiffMoved, &[&diff.moved.len()]),
+        diff.moved
+            .iter()
+            .map(|m| format!("{}: {} → {}", member(&m.id), heading(old_labels, m.from), heading(new_labels, m.to)))
+            .collect(),
+    );
+    section(
+        lang.format(Msg::DiffAdded, &[&diff.added.len()]),
+        diff.added.iter().map(|(id, i)| format!("{}: {}", member(id), heading(new_labels, *i))).collect(),
+    );
+    section(
+        lang.format(Msg::DiffRemoved, &[&diff.removed.len()]),
+        diff.removed.iter().map(|(id, i)| format!("{}: {}", member(id), heading(old_labels, *i))).collect(),
+    );
+    let pair_lines = |pairs: &[(StudentId, StudentId)]| -> Vec<String> {
+        pairs.iter().map(|(a, b)| format!("{} - {}", member(a), member(b))).collect()
+    };
+    section(lang.format(Msg::DiffBroken, &[&diff.broken.len()]), pair_lines(&diff.broken));
+    section(lang.format(Msg::DiffCreated, &[&diff.created.len()]), pair_lines(&diff.created));
+    out
+}
+
+/// The differences as JSON, with groups given by their labels.
+pub fn to_json(diff: &Diff, old_labels: &GroupNames, new_labels: &GroupNames) -> Value {
+    let students = |entries: &[(StudentId, usize)], labels: &GroupNames| {
+        Value::Array(
+            entries
+                .iter()
+                .map(|(id, i)| Value::object(vec![("id", Value::from(id.as_str())), ("group", Value::from(labels.label(*i)))]))
+                .collect(),
+        )
+    };
+    let pairs = |pairs: &[(StudentId, StudentId)]| {
+        Value::Array(
+            pairs
+                .iter()
+                .map(|(a, b)| Value::Array(vec![Value::from(a.as_str()), Value::from(b.as_str())]))
+                .collect(),
+        )
+    };
+    let moved = diff
+        .moved
+        .iter()
+        .map(|m| {
+            Value::object(vec![
+                ("id", Value::from(m.id.as_str())),
+                ("from", Value::from(old_labels.label(m.from))),
+                ("to", Value::from(new_labels.label(m.to))),
+            ])
+        })
+        .collect();
+    Value::object(vec![
+        ("moved", Value::Array(moved)),
+        ("added", students(&diff.added, new_labels)),
+        ("removed", students(&diff.removed, old_labels)),
+        ("broken_pairs", pairs(&diff.broken)),
+        ("new_pairs", pairs(&diff.created)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(groups: &[&[&str]]) -> Vec<Group> {
+        groups
+            .iter()
+            .map(|g| Group::from_members(g.iter().map(|m| m.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reordered_groups_are_the_same() {
+        let old = groups(&[&["S001", "S002"], &["S003", "S004"]]);
+        let new = groups(&[&["S004", "S003"], &["S001", "S002"]]);
+        assert!(diff(&old, &new).is_empty());
+        let text = render_text(&diff(&old, &new), &GroupNames::default(), &GroupNames::default(), str::to_string, Lang::Ja);
+        assert!(text.ends_with("変更はありません\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = groups(&[&["S001", "S002", "S003"], &["S004", "S005"], &["S006", "S007"]]);
+        let new = groups(&[&["S001", "S002", "S004"], &["S003", "S005"], &["S006", "S008"]]);
+        let diff = diff(&old, &new);
+
+        let moved: Vec<(&str, usize, usize)> = diff.moved.iter().map(|m| (m.id.as_str(), m.from, m.to)).collect();
+        assert_eq!(moved, vec![("S001", 0, 0), ("S002", 0, 0), ("S004", 1, 0), ("S003", 0, 1), ("S005", 1, 1)]);
+        assert_eq!(diff.added, vec![("S008".to_string(), 2)]);
+        assert_eq!(diff.removed, vec![("S007".to_string(), 2)]);
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        assert_eq!(diff.broken, vec![pair("S001", "S003"), pair("S002", "S003"), pair("S004", "S005")]);
+        assert_eq!(diff.created, vec![pair("S001", "S004"), pair("S002", "S004"), pair("S003", "S005")]);
+
+        let text = render_text(&diff, &GroupNames::default(), &GroupNames::default(), str::to_string, Lang::Ja);
+        assert!(text.contains("グループが変わった学生: 5 人\n  S001: グループ A → グループ A\n"), "{}", text);
+        assert!(text.contains("いなくなった学生: 1 人\n  S007: グループ C\n"), "{}", text);
+        assert!(text.contains("解消したペア: 3 組\n  S001 - S003\n"), "{}", text);
+
+        let json = to_json(&diff, &GroupNames::default(), &GroupNames::default()).to_string();
+        assert!(json.contains(r#""added":[{"id":"S008","group":"C"}]"#), "{}", json);
+        assert!(json.contains(r#""new_pairs":[["S001","S004"],"#), "{}", json);
+    }
+}
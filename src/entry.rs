@@ -0,0 +1,526 @@
+//! Groups being entered line by line, with a log of actions for `undo`.
+
+use grouping_tool::{Group, Student, StudentId};
+use std::collections::HashMap;
+
+/// One change made while entering students, kept so it can be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    /// A student was added to the current group, which `completed` when it became full
+    Add { id: StudentId, completed: bool },
+    /// A student was added to the end of the completed group `group`
+    AddTo { id: StudentId, group: usize },
+    /// A student was removed from `group` (the current group when it equals the number of completed groups)
+    Delete { student: Student, group: usize, position: usize },
+    /// A student was moved from `group` to the end of `to`, which `completed` when it became full
+    Move { id: StudentId, group: usize, position: usize, to: usize, completed: bool },
+    /// Two students traded places, each given as (group, position) before the swap
+    Swap { first: (usize, usize), second: (usize, usize) },
+    /// The current group was closed by the user
+    Complete,
+}
+
+/// What `Entry::undo` reverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Undone {
+    Add(StudentId),
+    Delete(StudentId, usize),
+    Move(StudentId, usize),
+    Swap(StudentId, StudentId),
+    Complete(usize),
+}
+
+/// Why `Entry::move_to` could not move a student, `Entry::add_to` add one,
+/// or `Entry::swap` swap two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NotFound,
+    /// The target is neither a completed group nor the current one
+    NoSuchGroup,
+    SameGroup,
+    Full,
+}
+
+/// Groups entered so far.
+#[derive(Debug, Default)]
+pub struct Entry {
+    /// Completed groups
+    groups: Vec<Group>,
+    /// The group students are currently added to
+    current: Group,
+    /// Size at which groups close, `None` when they grow without limit (batch mode)
+    limit: Option<usize>,
+    log: Vec<Action>,
+    /// Index of the group holding each student, so that looking one up does
+    /// not scan every group (batch input can have 100,000 students)
+    group_index: HashMap<StudentId, usize>,
+    /// Number of changes so far, undos included
+    revision: usize,
+}
+
+impl Entry {
+    /// Continue entering after `groups`, which count as completed, adding to
+    /// `current` (`--resume`). Both are empty for a fresh entry.
+    pub fn resume(groups: Vec<Group>, current: Group, limit: Option<usize>) -> Self {
+        let group_index = groups
+            .iter()
+            .chain(std::iter::once(&current))
+            .enumerate()
+            .flat_map(|(i, group)| group.ids().map(move |id| (id.clone(), i)))
+            .collect();
+        Entry {
+            groups,
+            current,
+            limit,
+            group_index,
+            ..Default::default()
+        }
+    }
+
+    /// Completed groups.
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// The group students are currently added to.
+    pub fn current(&self) -> &Group {
+        &self.current
+    }
+
+    /// A number that changes whenever the entry does, to tell when it needs saving.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// Size at which groups close, if any.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Index of the current group.
+    pub fn current_index(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Index of the group holding `id`, counting the current group last.
+    pub fn group_of(&self, id: &str) -> Option<usize> {
+        self.group_index.get(id).copied()
+    }
+
+    /// Every ID entered so far, in entry order, for completing `delete:`, `move:` and `swap:`.
+    pub fn ids(&self) -> Vec<StudentId> {
+        self.members().cloned().collect()
+    }
+
+    fn members(&self) -> impl Iterator<Item = &StudentId> {
+        self.groups
+            .iter()
+            .chain(std::iter::once(&self.current))
+            .flat_map(Group::ids)
+    }
+
+    /// Entered IDs closest to `id`, which was not found, for suggesting what
+    /// was meant. Only IDs one typo away (two for IDs of 6 characters or
+    /// more) are considered; all equally close ones are returned in entry order.
+    pub fn similar_ids(&self, id: &str) -> Vec<StudentId> {
+        let limit = (id.chars().count() / 3).clamp(1, 2);
+        let scored: Vec<(usize, &StudentId)> = self
+            .members()
+            .map(|member| (edit_distance(id, member), member))
+            .filter(|&(distance, _)| distance <= limit)
+            .collect();
+        let best = scored.iter().map(|&(distance, _)| distance).min();
+        scored
+            .into_iter()
+            .filter(|&(distance, _)| Some(distance) == best)
+            .map(|(_, member)| member.clone())
+            .collect()
+    }
+
+    /// Add a student to the current group. Returns `true` when this filled
+    /// the group, which is then completed.
+    pub fn add(&mut self, id: StudentId) -> bool {
+        self.revision += 1;
+        self.group_index.insert(id.clone(), self.current_index());
+        self.current.members.push(Student::new(id.clone()));
+        let completed = self.limit.is_some_and(|limit| self.current.members.len() >= limit);
+        if completed {
+            self.groups.push(std::mem::take(&mut self.current));
+        }
+        self.log.push(Action::Add { id, completed });
+        completed
+    }
+
+    /// Add a student to group `to`, which may be a completed group with room
+    /// or the current one, as [`Entry::add`]. Returns whether this filled,
+    /// and so completed, the current group.
+    pub fn add_to(&mut self, id: StudentId, to: usize) -> Result<bool, MoveError> {
+        if to > self.current_index() {
+            return Err(MoveError::NoSuchGroup);
+        }
+        if to == self.current_index() {
+            return Ok(self.add(id));
+        }
+        if self.limit.is_some_and(|limit| self.groups[to].members.len() >= limit) {
+            return Err(MoveError::Full);
+        }
+        self.revision += 1;
+        self.group_index.insert(id.clone(), to);
+        self.groups[to].members.push(Student::new(id.clone()));
+        self.log.push(Action::AddTo { id, group: to });
+        Ok(false)
+    }
+
+    /// Remove a student from whichever group holds them, looking at the
+    /// current group first. Returns the group they were removed from.
+    pub fn delete(&mut self, id: &str) -> Option<usize> {
+        let group = self.group_of(id)?;
+        let members = self.members_mut(group);
+        let position = members.iter().position(|member| member == id)?;
+        let student = members.remove(position);
+        self.group_index.remove(&student.id);
+        self.revision += 1;
+        self.log.push(Action::Delete { student, group, position });
+        Some(group)
+    }
+
+    /// Move a student to the end of group `to`, which may be the current
+    /// group, without going over the size limit. Returns the group they came
+    /// from and whether the move filled, and so completed, the current group.
+    pub fn move_to(&mut self, id: &str, to: usize) -> Result<(usize, bool), MoveError> {
+        let group = self.group_of(id).ok_or(MoveError::NotFound)?;
+        if to > self.current_index() {
+            return Err(MoveError::NoSuchGroup);
+        }
+        if to == group {
+            return Err(MoveError::SameGroup);
+        }
+        if self.limit.is_some_and(|limit| self.members_mut(to).len() >= limit) {
+            return Err(MoveError::Full);
+        }
+
+        let members = self.members_mut(group);
+        let position = members.iter().position(|member| member == id).ok_or(MoveError::NotFound)?;
+        let student = members.remove(position);
+        let id = student.id.clone();
+        self.members_mut(to).push(student);
+        self.group_index.insert(id.clone(), to);
+        self.revision += 1;
+        // Filling the current group completes it, as adding would
+        let completed =
+            to == self.current_index() && self.limit.is_some_and(|limit| self.current.members.len() >= limit);
+        if completed {
+            self.groups.push(std::mem::take(&mut self.current));
+        }
+        self.log.push(Action::Move {
+            id,
+            group,
+            position,
+            to,
+            completed,
+        });
+        Ok((group, completed))
+    }
+
+    /// Exchange two students in different groups, each taking the other's
+    /// place, so that no group changes size. Returns their groups before the swap.
+    pub fn swap(&mut self, first: &str, second: &str) -> Result<(usize, usize), MoveError> {
+        let a = self.position_of(first).ok_or(MoveError::NotFound)?;
+        let b = self.position_of(second).ok_or(MoveError::NotFound)?;
+        if a.0 == b.0 {
+            return Err(MoveError::SameGroup);
+        }
+        self.exchange(a, b);
+        self.revision += 1;
+        self.log.push(Action::Swap { first: a, second: b });
+        Ok((a.0, b.0))
+    }
+
+    /// The group and position of `id` in it.
+    fn position_of(&self, id: &str) -> Option<(usize, usize)> {
+        let group = self.group_of(id)?;
+        let members = match group == self.current_index() {
+            true => &self.current.members,
+            false => &self.groups[group].members,
+        };
+        Some((group, members.iter().position(|member| member == id)?))
+    }
+
+    /// Exchange the students at two places in different groups.
+    fn exchange(&mut self, (group_a, at_a): (usize, usize), (group_b, at_b): (usize, usize)) {
+        let a = self.members_mut(group_a)[at_a].clone();
+        let b = std::mem::replace(&mut self.members_mut(group_b)[at_b], a.clone());
+        self.group_index.insert(a.id, group_b);
+        self.group_index.insert(b.id.clone(), group_a);
+        self.members_mut(group_a)[at_a] = b;
+    }
+
+    /// Close the current group. Returns `false` when it was empty.
+    pub fn complete(&mut self) -> bool {
+        if self.current.members.is_empty() {
+            return false;
+        }
+        self.groups.push(std::mem::take(&mut self.current));
+        self.log.push(Action::Complete);
+        self.revision += 1;
+        true
+    }
+
+    /// Revert the most recent action, if any.
+    pub fn undo(&mut self) -> Option<Undone> {
+        let action = self.log.pop()?;
+        self.revision += 1;
+        let undone = match action {
+            Action::Add { id, completed } => {
+                if completed {
+                    self.reopen_last_group();
+                }
+                self.current.members.pop();
+                self.group_index.remove(&id);
+                Undone::Add(id)
+            }
+            Action::AddTo { id, group } => {
+                self.groups[group].members.pop();
+                self.group_index.remove(&id);
+                Undone::Add(id)
+            }
+            Action::Delete { student, group, position } => {
+                let id = student.id.clone();
+                self.members_mut(group).insert(position, student);
+                self.group_index.insert(id.clone(), group);
+                Undone::Delete(id, group)
+            }
+            Action::Move {
+                id,
+                group,
+                position,
+                to,
+                completed,
+            } => {
+                if completed {
+                    self.reopen_last_group();
+                }
+                let student = self.members_mut(to).pop().expect("the moved student is last");
+                self.members_mut(group).insert(position, student);
+                self.group_index.insert(id.clone(), group);
+                Undone::Move(id, group)
+            }
+            Action::Swap { first, second } => {
+                self.exchange(first, second);
+                let first = self.members_mut(first.0)[first.1].id.clone();
+                let second = self.members_mut(second.0)[second.1].id.clone();
+                Undone::Swap(first, second)
+            }
+            Action::Complete => {
+                self.reopen_last_group();
+                Undone::Complete(self.current_index())
+            }
+        };
+        Some(undone)
+    }
+
+    fn members_mut(&mut self, group: usize) -> &mut Vec<Student> {
+        if group == self.current_index() {
+            &mut self.current.members
+        } else {
+            &mut self.groups[group].members
+        }
+    }
+
+    /// Make the last completed group the current one again.
+    fn reopen_last_group(&mut self) {
+        if let Some(group) = self.groups.pop() {
+            self.current = group;
+        }
+    }
+
+    /// All groups, including the current one when it has members.
+    pub fn into_groups(mut self) -> Vec<Group> {
+        if !self.current.members.is_empty() {
+            self.groups.push(self.current);
+        }
+        self.groups
+    }
+}
+
+/// Number of characters to insert, delete or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != *cb);
+            row.push(replace.min(previous[j + 1] + 1).min(row[j] + 1));
+        }
+        previous = row;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(entry: &Entry) -> Vec<Vec<&str>> {
+        entry
+            .groups()
+            .iter()
+            .chain(std::iter::once(entry.current()))
+            .map(|g| g.ids().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_add_and_completion() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        entry.add("S001".into());
+        entry.add("S002".into());
+        assert!(entry.add("S003".into()));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002", "S003"], vec![]]);
+
+        // Undoing the add that filled the group reopens it
+        assert_eq!(entry.undo(), Some(Undone::Add("S003".into())));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002"]]);
+
+        assert!(entry.complete());
+        assert_eq!(entry.undo(), Some(Undone::Complete(0)));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002"]]);
+    }
+
+    #[test]
+    fn test_undo_delete_restores_position() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        for id in ["S001", "S002", "S003", "S004"] {
+            entry.add(id.into());
+        }
+        assert_eq!(entry.delete("S002"), Some(0));
+        assert_eq!(entry.delete("S004"), Some(1));
+        assert_eq!(entry.delete("S009"), None);
+        assert_eq!(members(&entry), vec![vec!["S001", "S003"], vec![]]);
+
+        assert_eq!(entry.undo(), Some(Undone::Delete("S004".into(), 1)));
+        assert_eq!(entry.undo(), Some(Undone::Delete("S002".into(), 0)));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002", "S003"], vec!["S004"]]);
+
+        while entry.undo().is_some() {}
+        assert!(entry.into_groups().is_empty());
+    }
+
+    #[test]
+    fn test_similar_ids() {
+        assert_eq!(edit_distance("S0001", "S001"), 1);
+        assert_eq!(edit_distance("S0O1", "S001"), 1);
+        assert_eq!(edit_distance("", "S001"), 4);
+
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        for id in ["S001", "S010", "S002", "S123"] {
+            entry.add(id.into());
+        }
+        assert_eq!(entry.similar_ids("S0001"), vec!["S001"]);
+        assert_eq!(entry.similar_ids("S011"), vec!["S001", "S010"]);
+        assert!(entry.similar_ids("S999").is_empty());
+        assert_eq!(entry.ids(), vec!["S001", "S010", "S002", "S123"]);
+    }
+
+    #[test]
+    fn test_move_respects_size_limit() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        for id in ["S001", "S002", "S003", "S004", "S005"] {
+            entry.add(id.into());
+        }
+        assert_eq!(entry.move_to("S004", 0), Err(MoveError::Full));
+        assert_eq!(entry.move_to("S004", 1), Err(MoveError::SameGroup));
+        assert_eq!(entry.move_to("S004", 2), Err(MoveError::NoSuchGroup));
+        assert_eq!(entry.move_to("S009", 0), Err(MoveError::NotFound));
+
+        // Moving into the current group fills and completes it
+        assert_eq!(entry.move_to("S002", 1), Ok((0, true)));
+        assert_eq!(members(&entry), vec![vec!["S001", "S003"], vec!["S004", "S005", "S002"], vec![]]);
+
+        assert_eq!(entry.undo(), Some(Undone::Move("S002".into(), 0)));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002", "S003"], vec!["S004", "S005"]]);
+    }
+
+    #[test]
+    fn test_add_to_group_with_room() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        for id in ["S001", "S002", "S003", "S004"] {
+            entry.add(id.into());
+        }
+        assert_eq!(entry.delete("S002"), Some(0));
+        assert_eq!(entry.add_to("S005".into(), 0), Ok(false));
+        assert_eq!(entry.add_to("S006".into(), 0), Err(MoveError::Full));
+        assert_eq!(entry.add_to("S006".into(), 2), Err(MoveError::NoSuchGroup));
+        assert_eq!(entry.group_of("S005"), Some(0));
+        assert_eq!(members(&entry), vec![vec!["S001", "S003", "S005"], vec!["S004"]]);
+
+        // Adding to the current group fills and completes it as usual
+        assert_eq!(entry.add_to("S006".into(), 1), Ok(false));
+        assert_eq!(entry.add_to("S007".into(), 1), Ok(true));
+        assert_eq!(entry.current_index(), 2);
+
+        entry.undo();
+        entry.undo();
+        assert_eq!(entry.undo(), Some(Undone::Add("S005".into())));
+        assert_eq!(entry.group_of("S005"), None);
+        assert_eq!(members(&entry), vec![vec!["S001", "S003"], vec!["S004"]]);
+    }
+
+    #[test]
+    fn test_swap_keeps_sizes() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(3));
+        for id in ["S001", "S002", "S003", "S004", "S005"] {
+            entry.add(id.into());
+        }
+        // The full group A stays full, and the current group B does not complete
+        assert_eq!(entry.swap("S002", "S005"), Ok((0, 1)));
+        assert_eq!(members(&entry), vec![vec!["S001", "S005", "S003"], vec!["S004", "S002"]]);
+        assert_eq!((entry.group_of("S005"), entry.group_of("S002")), (Some(0), Some(1)));
+        assert_eq!(entry.swap("S001", "S003"), Err(MoveError::SameGroup));
+        assert_eq!(entry.swap("S001", "S009"), Err(MoveError::NotFound));
+
+        assert_eq!(entry.undo(), Some(Undone::Swap("S002".into(), "S005".into())));
+        assert_eq!(members(&entry), vec![vec!["S001", "S002", "S003"], vec!["S004", "S005"]]);
+        assert_eq!(entry.group_of("S005"), Some(1));
+    }
+
+    #[test]
+    fn test_unlimited_groups() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), None);
+        for id in ["S001", "S002", "S003", "S004"] {
+            assert!(!entry.add(id.into()));
+        }
+        assert_eq!(entry.group_of("S004"), Some(0));
+        assert_eq!(entry.into_groups().len(), 1);
+    }
+
+    #[test]
+    fn test_continue_after_groups() {
+        let done = Group::from_members(vec!["S001", "S002", "S003"]);
+        let current = Group::from_members(vec!["S004"]);
+        let mut entry = Entry::resume(vec![done], current, Some(3));
+        assert_eq!(entry.current_index(), 1);
+        assert_eq!(entry.group_of("S004"), Some(1));
+        assert_eq!(entry.move_to("S001", 1), Ok((0, false)));
+        assert_eq!(entry.group_of("S001"), Some(1));
+        assert_eq!(members(&entry), vec![vec!["S002", "S003"], vec!["S004", "S001"]]);
+
+        let revision = entry.revision();
+        assert_eq!(entry.undo(), Some(Undone::Move("S001".into(), 0)));
+        assert_eq!(entry.group_of("S001"), Some(0));
+        assert_ne!(entry.revision(), revision);
+        assert_eq!(entry.delete("S004"), Some(1));
+        assert_eq!(entry.group_of("S004"), None);
+        assert_eq!(entry.into_groups().len(), 1);
+    }
+
+    #[test]
+    fn test_pairs_close_at_two() {
+        let mut entry = Entry::resume(Vec::new(), Group::new(), Some(2));
+        assert!(!entry.add("S001".to_string()));
+        assert!(entry.add("S002".to_string()));
+        entry.add("S003".to_string());
+        assert_eq!(entry.move_to("S003", 0), Err(MoveError::Full));
+    }
+}
@@ -0,0 +1,73 @@
+//! Errors of the grouping library that callers are expected to handle, as
+//! opposed to malformed files and options, which are reported as messages.
+
+use crate::group::StudentId;
+use crate::i18n::{Lang, Msg};
+use std::fmt;
+
+/// Why students could not be put into groups as asked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupingError {
+    /// A student was added to a group that already has its members
+    GroupFull { student: StudentId, size: usize },
+    /// The same student was given more than once
+    DuplicateStudent(StudentId),
+    /// A fixed number of groups was asked for, but it was zero
+    NoGroups,
+    /// A group was given a size that would leave a student alone
+    GroupTooSmall(usize),
+    /// An input line has no student ID before its attributes
+    MissingId,
+    /// An input line has more fields than there are attribute names
+    ExtraFields { id: StudentId, expected: usize, found: usize },
+}
+
+impl GroupingError {
+    /// The message in `lang`.
+    pub fn message(&self, lang: Lang) -> String {
+        match self {
+            GroupingError::GroupFull { student, size } => lang.format(Msg::CannotAddToFull, &[student, size]),
+            GroupingError::DuplicateStudent(id) => lang.format(Msg::DuplicateStudent, &[id]),
+            GroupingError::NoGroups => lang.text(Msg::NoGroups).to_string(),
+            GroupingError::GroupTooSmall(size) => lang.format(Msg::GroupTooSmall, &[size]),
+            GroupingError::MissingId => lang.text(Msg::MissingId).to_string(),
+            GroupingError::ExtraFields { id, expected, found } => {
+                lang.format(Msg::ExtraFields, &[id, expected, found])
+            }
+        }
+    }
+}
+
+/// The message in the language set with [`crate::i18n::set_lang`].
+impl fmt::Display for GroupingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(Lang::current()))
+    }
+}
+
+impl std::error::Error for GroupingError {}
+
+/// Most of the crate reports errors as messages; this lets `?` pass a
+/// [`GroupingError`] on to them.
+impl From<GroupingError> for String {
+    fn from(error: GroupingError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let error = GroupingError::GroupFull {
+            student: "S004".to_string(),
+            size: 3,
+        };
+        assert_eq!(error.to_string(), "S004 を追加できません（グループは既に 3 人です）");
+        let message: String = GroupingError::DuplicateStudent("S001".to_string()).into();
+        assert_eq!(message, "学籍番号 S001 が2回以上あります");
+        assert_eq!(GroupingError::GroupTooSmall(1).message(Lang::En), "The group size 1 must be at least 2");
+    }
+}
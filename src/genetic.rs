@@ -0,0 +1,221 @@
+//! Genetic algorithm for large classes with many constraints
+//! (`--strategy genetic`).
+//!
+//! A grouping is an order of the students, cut into groups of the sizes
+//! [`crate::grouping::Sizing::split`] gives. A population of such orders is
+//! bred for a number of generations, starting from random orders and the
+//! grouping of the default strategy: two parents picked by tournament pass
+//! on some whole groups of one and the order of the rest of the other, and a
+//! few students of the child are swapped. Since many groupings are kept at
+//! once, the search does not stall around a single grouping the way swaps
+//! alone can.
+
+use crate::anneal::Score;
+use crate::balance;
+use crate::group::{Group, Student};
+use crate::grouping::{self, GroupingConfig};
+use crate::i18n::{message, Msg};
+use crate::log;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Groupings bred at once when `--population` is not given.
+pub const DEFAULT_POPULATION: usize = 50;
+
+/// Generations bred when `--generations` is not given.
+pub const DEFAULT_GENERATIONS: usize = 200;
+
+/// The most groupings bred at once; each one holds an order of the whole class.
+pub const MAX_POPULATION: usize = 1_000;
+
+/// The most generations bred, so that one grouping cannot run for hours.
+pub const MAX_GENERATIONS: usize = 10_000;
+
+/// Groupings compared to pick each parent.
+const TOURNAMENT: usize = 3;
+
+/// Best groupings carried over unchanged to the next generation.
+const ELITE: usize = 2;
+
+/// Chance that a child gets one more swap, so that it gets one on average.
+const MUTATION: f64 = 0.5;
+
+/// A grouping (the order of the students to regroup) and its score.
+type Individual = (f64, Vec<usize>);
+
+/// Reorganize groups like [`crate::grouping::regroup`], breeding
+/// `config.population` groupings for `config.generations` generations
+/// ([`DEFAULT_POPULATION`] and [`DEFAULT_GENERATIONS`] when not given) and
+/// keeping the one with the lowest [`crate::anneal::score`]. Full groups are
+/// kept in interactive mode, as with [`balance::reorganize_balanced`].
+pub fn reorganize_genetic(groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+    let mut rng = config.rng();
+    let entered = groups.clone();
+    let (mut final_groups, pool) = balance::open_pool(groups, config.mode, config.sizing);
+    if pool.is_empty() {
+        return final_groups;
+    }
+    // Where each group ends in an order
+    let ends: Vec<usize> = config
+        .sizing
+        .split(pool.clone())
+        .iter()
+        .scan(0, |end, group| {
+            *end += group.members.len();
+            Some(*end)
+        })
+        .collect();
+    let decode = |order: &[usize]| -> Vec<Group> {
+        let mut start = 0;
+        ends.iter()
+            .map(|&end| {
+                let group = Group::from_members(order[start..end].iter().map(|&k| pool[k].clone()).collect());
+                start = end;
+                group
+            })
+            .collect()
+    };
+    let everyone: Vec<Group> = final_groups.iter().cloned().chain([Group::from_members(pool.clone())]).collect();
+    let score = Score::new(&everyone, config);
+    let fitness = |order: &[usize]| {
+        let mut groups = final_groups.clone();
+        groups.extend(decode(order));
+        score.total(&groups)
+    };
+
+    let size = config.population.unwrap_or(DEFAULT_POPULATION).max(ELITE + 1);
+    let first = default_order(entered, config, &final_groups, &pool, &ends);
+    let mut population: Vec<Individual> = first
+        .into_iter()
+        .chain(std::iter::repeat_with(|| {
+            let mut order: Vec<usize> = (0..pool.len()).collect();
+            order.shuffle(&mut rng);
+            order
+        }))
+        .take(size)
+        .map(|order| (fitness(&order), order))
+        .collect();
+    let generations = config.generations.unwrap_or(DEFAULT_GENERATIONS);
+    let mut bred = 0;
+    while bred < generations {
+        population.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if population[0].0 <= 0.0 {
+            break;
+        }
+        let mut next: Vec<Individual> = population[..ELITE].to_vec();
+        while next.len() < size {
+            let a = tournament(&population, &mut rng);
+            let b = tournament(&population, &mut rng);
+            let mut child = crossover(a, b, &ends, &mut rng);
+            while rng.gen::<f64>() < MUTATION {
+                let (i, j) = (rng.gen_range(0..child.len()), rng.gen_range(0..child.len()));
+                child.swap(i, j);
+            }
+            next.push((fitness(&child), child));
+        }
+        population = next;
+        bred += 1;
+    }
+    population.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (best, order) = &population[0];
+    log::info(|| message(Msg::TraceBred, &[&bred, &format!("{:.2}", best)]));
+    final_groups.extend(decode(order));
+    final_groups
+}
+
+/// The grouping of the default strategy as an order, so that the search
+/// starts from one that already honors the constraints where it can; `None`
+/// when its groups do not have the sizes of `ends`.
+fn default_order(groups: Vec<Group>, config: &GroupingConfig, kept: &[Group], pool: &[Student], ends: &[usize]) -> Option<Vec<usize>> {
+    let config = GroupingConfig {
+        strategy: None,
+        ..config.clone()
+    };
+    let mut formed: Vec<Group> = grouping::reorganize(groups, config)
+        .into_iter()
+        .filter(|group| !kept.contains(group))
+        .collect();
+    let index: HashMap<&str, usize> = pool.iter().enumerate().map(|(k, s)| (s.id.as_str(), k)).collect();
+    let mut order = Vec::with_capacity(pool.len());
+    let mut start = 0;
+    for &end in ends {
+        let at = formed.iter().position(|group| group.members.len() == end - start)?;
+        for student in &formed.swap_remove(at).members {
+            order.push(*index.get(student.id.as_str())?);
+        }
+        start = end;
+    }
+    (formed.is_empty() && order.len() == pool.len()).then_some(order)
+}
+
+/// The best of [`TOURNAMENT`] groupings picked at random.
+fn tournament<'a, R: Rng + ?Sized>(population: &'a [Individual], rng: &mut R) -> &'a [usize] {
+    let picked = population.choose_multiple(rng, TOURNAMENT);
+    let best = picked.min_by(|a, b| a.0.total_cmp(&b.0)).expect("the population is not empty");
+    &best.1
+}
+
+/// A child of `a` and `b`: a run of whole groups (ending at `ends`) as they
+/// are in `a`, and the other students in the order they have in `b`.
+fn crossover<R: Rng + ?Sized>(a: &[usize], b: &[usize], ends: &[usize], rng: &mut R) -> Vec<usize> {
+    let first = rng.gen_range(0..ends.len());
+    let last = rng.gen_range(first..ends.len());
+    let start = if first == 0 { 0 } else { ends[first - 1] };
+    let end = ends[last];
+
+    let mut inherited = vec![false; a.len()];
+    for &k in &a[start..end] {
+        inherited[k] = true;
+    }
+    let mut rest = b.iter().copied().filter(|&k| !inherited[k]);
+    (0..a.len())
+        .map(|i| match i {
+            i if (start..end).contains(&i) => a[i],
+            _ => rest.next().expect("every student is placed once"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::Constraints;
+    use crate::grouping::Mode;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_crossover_keeps_every_student() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let a: Vec<usize> = (0..9).collect();
+        let b: Vec<usize> = (0..9).rev().collect();
+        for _ in 0..20 {
+            let mut child = crossover(&a, &b, &[3, 6, 9], &mut rng);
+            child.sort_unstable();
+            assert_eq!(child, a);
+        }
+    }
+
+    #[test]
+    fn test_satisfies_constraints() {
+        let students: Vec<Student> = (1..=30).map(|i| Student::new(format!("S{:03}", i))).collect();
+        let constraints = Constraints::parse(
+            "together: S001,S030\ntogether: S002,S029\napart: S001,S002,S003\napart: S010,S011,S012,S013",
+        )
+        .unwrap();
+        let config = GroupingConfig {
+            mode: Mode::Batch,
+            seed: Some(7),
+            constraints: constraints.clone(),
+            population: Some(30),
+            generations: Some(100),
+            ..Default::default()
+        };
+        let groups = reorganize_genetic(vec![Group::from_members(students)], &config);
+
+        assert_eq!(groups.iter().map(|g| g.members.len()).sum::<usize>(), 30);
+        assert!(groups.iter().all(|g| g.members.len() == 3));
+        assert!(constraints.unsatisfied(&groups).is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+//! The `Group` type and group labelling helpers.
+
+use crate::error::GroupingError;
+use crate::i18n::{message, Lang, Msg};
+pub use crate::student::Student;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Identifier of a single student (e.g. a student number such as `S001`). It
+/// keys the lookups of history, constraints and pseudonyms; groups hold whole
+/// [`Student`]s.
+pub type StudentId = String;
+
+/// Maximum number of members a group accepts during interactive entry.
+pub const MAX_GROUP_SIZE: usize = 3;
+
+/// Convert a group index (0-based) to a letter (A, B, C, ...).
+///
+/// Indices beyond `Z` continue as `AA`, `AB`, `AC`, ..., and beyond `ZZ` as
+/// `AAA`, `AAB`, ..., so that any number of groups has a label.
+pub fn group_index_to_letter(index: usize) -> String {
+    // Bijective base 26: A = 1, ..., Z = 26, AA = 27, ...
+    let mut letters = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        letters.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).expect("ASCII letters")
+}
+
+/// Convert a group label (`A`, `b`, `AA`, ...) back to its index, the
+/// inverse of [`group_index_to_letter`].
+pub fn group_letter_to_index(label: &str) -> Option<usize> {
+    let label = label.trim();
+    if label.is_empty() {
+        return None;
+    }
+    let mut n: usize = 0;
+    for b in label.bytes() {
+        let b = b.to_ascii_uppercase();
+        if !b.is_ascii_uppercase() {
+            return None;
+        }
+        n = n.checked_mul(26)?.checked_add((b - b'A') as usize + 1)?;
+    }
+    Some(n - 1)
+}
+
+/// Compare student IDs so that runs of digits count as numbers: `S2` comes
+/// before `S10`. IDs that only differ in leading zeros fall back to plain order.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    // The leading run of digits or of other characters, and the rest
+    fn split(s: &str) -> (bool, &str, &str) {
+        let digits = s.chars().next().is_some_and(|c| c.is_ascii_digit());
+        let end = s.find(|c: char| c.is_ascii_digit() != digits).unwrap_or(s.len());
+        (digits, &s[..end], &s[end..])
+    }
+    let (mut x, mut y) = (a, b);
+    loop {
+        let ordering = match (x.is_empty(), y.is_empty()) {
+            (true, true) => return a.cmp(b),
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            (false, false) => {
+                let ((x_digits, x_chunk, x_rest), (y_digits, y_chunk, y_rest)) = (split(x), split(y));
+                (x, y) = (x_rest, y_rest);
+                if x_digits && y_digits {
+                    let (x_num, y_num) = (x_chunk.trim_start_matches('0'), y_chunk.trim_start_matches('0'));
+                    x_num.len().cmp(&y_num.len()).then_with(|| x_num.cmp(y_num))
+                } else {
+                    x_chunk.cmp(y_chunk)
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// A ready-made set of group names chosen with `--labels`, friendlier for
+/// younger classes than letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelTheme {
+    Animals,
+    Colors,
+    Planets,
+    /// Hiragana in gojūon order (あ, い, う, ...)
+    Kana,
+}
+
+impl LabelTheme {
+    /// The names of the theme, in Japanese or English. Kana are the same in both.
+    pub fn names(self, lang: Lang) -> &'static [&'static str] {
+        match (self, lang) {
+            (LabelTheme::Animals, Lang::Ja) => &[
+                "パンダ", "コアラ", "ウサギ", "ライオン", "キリン", "ゾウ", "ペンギン", "イルカ",
+                "クマ", "リス", "カメ", "ネコ", "イヌ", "サル", "トラ", "フクロウ",
+            ],
+            (LabelTheme::Animals, Lang::En) => &[
+                "Panda", "Koala", "Rabbit", "Lion", "Giraffe", "Elephant", "Penguin", "Dolphin",
+                "Bear", "Squirrel", "Turtle", "Cat", "Dog", "Monkey", "Tiger", "Owl",
+            ],
+            (LabelTheme::Colors, Lang::Ja) => &["赤", "青", "黄", "緑", "紫", "橙", "桃", "水色", "茶", "白", "黒", "灰"],
+            (LabelTheme::Colors, Lang::En) => &[
+                "Red", "Blue", "Yellow", "Green", "Purple", "Orange", "Pink", "Cyan", "Brown", "White", "Black", "Gray",
+            ],
+            (LabelTheme::Planets, Lang::Ja) => &["水星", "金星", "地球", "火星", "木星", "土星", "天王星", "海王星"],
+            (LabelTheme::Planets, Lang::En) => &["Mercury", "Venus", "Earth", "Mars", "Jupiter", "Saturn", "Uranus", "Neptune"],
+            (LabelTheme::Kana, _) => &[
+                "あ", "い", "う", "え", "お", "か", "き", "く", "け", "こ", "さ", "し", "す", "せ", "そ", "た",
+                "ち", "つ", "て", "と", "な", "に", "ぬ", "ね", "の", "は", "ひ", "ふ", "へ", "ほ", "ま", "み",
+                "む", "め", "も", "や", "ゆ", "よ", "ら", "り", "る", "れ", "ろ", "わ", "を", "ん",
+            ],
+        }
+    }
+}
+
+impl FromStr for LabelTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "animals" => Ok(LabelTheme::Animals),
+            "colors" => Ok(LabelTheme::Colors),
+            "planets" => Ok(LabelTheme::Planets),
+            "kana" => Ok(LabelTheme::Kana),
+            _ => {
+                let themes = Lang::current().list(&["animals", "colors", "planets", "kana"]);
+                Err(message(Msg::OneOf, &[&"--labels", &themes, &s]))
+            }
+        }
+    }
+}
+
+/// How groups are labelled: `A`, `B`, ... by default, `1`, `2`, ... when
+/// numbered, or names such as `Red`, `Blue`, `Green`. When there are more
+/// groups than names, the names are reused with a number (`Red 2`, `Blue 2`, ...).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupNames {
+    names: Vec<String>,
+    /// Number the groups from 1 instead of lettering them (`--numeric-labels`)
+    numbered: bool,
+}
+
+impl GroupNames {
+    /// Names separated by commas or newlines, as given with `--group-names`
+    /// or in a file. Blank entries are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut names: Vec<String> = Vec::new();
+        for name in text.split([',', '\n']).map(str::trim) {
+            if name.is_empty() {
+                continue;
+            }
+            if names.iter().any(|n| n == name) {
+                return Err(message(Msg::GroupNameRepeated, &[&name]));
+            }
+            names.push(name.to_string());
+        }
+        if names.is_empty() {
+            return Err(message(Msg::NoGroupNames, &[]));
+        }
+        Ok(GroupNames { names, numbered: false })
+    }
+
+    /// Groups numbered `1`, `2`, `3`, ... (`--numeric-labels`).
+    pub fn numbered() -> Self {
+        GroupNames {
+            names: Vec::new(),
+            numbered: true,
+        }
+    }
+
+    /// The names of `theme` (`--labels`), in `lang`.
+    pub fn themed(theme: LabelTheme, lang: Lang) -> Self {
+        GroupNames {
+            names: theme.names(lang).iter().map(|name| name.to_string()).collect(),
+            numbered: false,
+        }
+    }
+
+    /// Labels read back from a result, one per group, used as they are.
+    pub fn from_labels(labels: Vec<String>) -> Self {
+        GroupNames {
+            names: labels,
+            numbered: false,
+        }
+    }
+
+    /// The label of the group at `index`.
+    pub fn label(&self, index: usize) -> String {
+        if self.numbered {
+            return (index + 1).to_string();
+        }
+        if self.names.is_empty() {
+            return group_index_to_letter(index);
+        }
+        let name = &self.names[index % self.names.len()];
+        match index / self.names.len() {
+            0 => name.clone(),
+            round => format!("{} {}", name, round + 1),
+        }
+    }
+
+    /// The index of the group labelled `label`, the inverse of [`GroupNames::label`].
+    pub fn index_of(&self, label: &str) -> Option<usize> {
+        if self.numbered {
+            return label.trim().parse::<usize>().ok()?.checked_sub(1);
+        }
+        if self.names.is_empty() {
+            return group_letter_to_index(label);
+        }
+        let label = label.trim();
+        let position = |name: &str| self.names.iter().position(|n| n.eq_ignore_ascii_case(name));
+        if let Some(index) = position(label) {
+            return Some(index);
+        }
+        let (name, round) = label.rsplit_once(' ')?;
+        let round = round.parse::<usize>().ok().filter(|&round| round >= 2)?;
+        Some((round - 1) * self.names.len() + position(name.trim())?)
+    }
+}
+
+/// A group of students.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    pub members: Vec<Student>,
+}
+
+impl Group {
+    /// Create an empty group.
+    pub fn new() -> Self {
+        Group {
+            members: Vec::new(),
+        }
+    }
+
+    /// The group with its members in [`natural_cmp`] order (`--sort-members`).
+    pub fn sorted(&self) -> Group {
+        let mut members = self.members.clone();
+        members.sort_by(|a, b| natural_cmp(&a.id, &b.id));
+        Group { members }
+    }
+
+    /// Create a group from an existing list of members (students or their
+    /// IDs), without any size limit.
+    pub fn from_members<S: Into<Student>>(members: Vec<S>) -> Self {
+        Group {
+            members: members.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Add a member, or fail when the group already has `MAX_GROUP_SIZE` members.
+    pub fn add_member(&mut self, student: impl Into<Student>) -> Result<(), GroupingError> {
+        let student = student.into();
+        if self.is_full() {
+            return Err(GroupingError::GroupFull {
+                student: student.id,
+                size: self.members.len(),
+            });
+        }
+        self.members.push(student);
+        Ok(())
+    }
+
+    /// The IDs of the members, in order.
+    pub fn ids(&self) -> impl Iterator<Item = &StudentId> {
+        self.members.iter().map(|m| &m.id)
+    }
+
+    /// Whether the student `id` is a member.
+    pub fn contains(&self, id: &str) -> bool {
+        self.members.iter().any(|m| m.id == id)
+    }
+
+    /// Index of the student `id` among the members.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.members.iter().position(|m| m.id == id)
+    }
+
+    /// Whether the group has reached `MAX_GROUP_SIZE` members.
+    pub fn is_full(&self) -> bool {
+        self.members.len() >= MAX_GROUP_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_creation() {
+        let mut group = Group::new();
+        assert!(!group.is_full());
+
+        group.add_member("S001".to_string()).unwrap();
+        assert!(!group.is_full());
+
+        group.add_member("S002".to_string()).unwrap();
+        assert!(!group.is_full());
+
+        group.add_member("S003".to_string()).unwrap();
+        assert!(group.is_full());
+
+        // A 4th member is refused
+        assert_eq!(
+            group.add_member("S004".to_string()),
+            Err(GroupingError::GroupFull {
+                student: "S004".to_string(),
+                size: 3
+            })
+        );
+        assert_eq!(group.members.len(), 3);
+    }
+
+    #[test]
+    fn test_group_index_to_letter() {
+        assert_eq!(group_index_to_letter(0), "A");
+        assert_eq!(group_index_to_letter(25), "Z");
+        assert_eq!(group_index_to_letter(26), "AA");
+        assert_eq!(group_index_to_letter(27), "AB");
+        assert_eq!(group_index_to_letter(701), "ZZ");
+        assert_eq!(group_index_to_letter(702), "AAA");
+    }
+
+    #[test]
+    fn test_group_names() {
+        let letters = GroupNames::default();
+        assert_eq!(letters.label(1), "B");
+        assert_eq!(letters.index_of("b"), Some(1));
+
+        let names = GroupNames::parse("Red, Blue,\nGreen\n").unwrap();
+        let labels: Vec<String> = (0..5).map(|i| names.label(i)).collect();
+        assert_eq!(labels, vec!["Red", "Blue", "Green", "Red 2", "Blue 2"]);
+        for i in 0..7 {
+            assert_eq!(names.index_of(&names.label(i)), Some(i));
+        }
+        assert_eq!(names.index_of("blue"), Some(1));
+        assert_eq!(names.index_of("Purple"), None);
+        assert_eq!(names.index_of("Red 1"), None);
+
+        let numbers = GroupNames::numbered();
+        assert_eq!(numbers.label(0), "1");
+        assert_eq!(numbers.label(26), "27");
+        assert_eq!(numbers.index_of(" 27"), Some(26));
+        assert_eq!(numbers.index_of("0"), None);
+        assert_eq!(numbers.index_of("A"), None);
+
+        assert!(GroupNames::parse("Red,Red").is_err());
+        assert!(GroupNames::parse(" , ").is_err());
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut ids = vec!["S10", "S2", "S1", "s3", "S02", "A100", "S", "B7"];
+        ids.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(ids, vec!["A100", "B7", "S", "S1", "S02", "S2", "S10", "s3"]);
+        let group = Group::from_members(vec!["2024-10".to_string(), "2024-9".to_string()]);
+        assert_eq!(group.sorted().members, vec!["2024-9", "2024-10"]);
+    }
+
+    #[test]
+    fn test_themed_group_names() {
+        let animals = GroupNames::themed("animals".parse().unwrap(), Lang::Ja);
+        assert_eq!(animals.label(0), "パンダ");
+        assert_eq!(animals.label(1), "コアラ");
+        assert_eq!(animals.label(16), "パンダ 2");
+        assert_eq!(animals.index_of("コアラ"), Some(1));
+        assert_eq!(GroupNames::themed(LabelTheme::Planets, Lang::En).label(2), "Earth");
+        for theme in [LabelTheme::Animals, LabelTheme::Colors, LabelTheme::Planets, LabelTheme::Kana] {
+            for lang in [Lang::Ja, Lang::En] {
+                // Every name must be usable with --group-names, which rejects duplicates
+                assert!(GroupNames::parse(&theme.names(lang).join(",")).is_ok(), "{:?} {:?}", theme, lang);
+            }
+        }
+        assert!("fruits".parse::<LabelTheme>().is_err());
+    }
+
+    #[test]
+    fn test_group_letter_to_index() {
+        for index in [0, 25, 26, 27, 100, 701, 702, 100_000] {
+            assert_eq!(group_letter_to_index(&group_index_to_letter(index)), Some(index));
+        }
+        assert_eq!(group_letter_to_index("b"), Some(1));
+        assert_eq!(group_letter_to_index(""), None);
+        assert_eq!(group_letter_to_index("A1"), None);
+    }
+}
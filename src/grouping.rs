@@ -0,0 +1,1575 @@
+//! Grouping algorithms: splitting students into groups of 2-3 people.
+
+use crate::anneal;
+use crate::balance;
+use crate::constraints::Constraints;
+use crate::error::GroupingError;
+use crate::group::{Group, MAX_GROUP_SIZE};
+use crate::history::History;
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+use crate::log;
+use crate::preference::Preferences;
+use crate::strategy::{self, GroupingStrategy};
+use crate::student::{self, Student};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// How the pre-entered groups passed to [`regroup`] should be treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Piped/redirected input: all members are regrouped in input order.
+    #[default]
+    Batch,
+    /// Interactive entry: full groups are kept, the rest are shuffled together.
+    Interactive,
+}
+
+impl Mode {
+    /// The name of the mode, as the subcommand that selects it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Batch => "batch",
+            Mode::Interactive => "interactive",
+        }
+    }
+
+    /// The mode called `name`, the inverse of [`Mode::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Mode::Batch, Mode::Interactive].into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// Sizes of the groups students are split into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sizing {
+    /// Groups of 3, with 2-person groups only when the count is not divisible by 3
+    #[default]
+    Triples,
+    /// Groups of 2, with one 3-person group when the count is odd (`--pairs`)
+    Pairs,
+    /// Groups of 2-3 that are all the same size whenever the count allows it:
+    /// 8 students make 2+2+2+2 instead of 3+3+2 (`--balanced equal`)
+    Even,
+    /// Groups of `min` to `max` people (`--min N --max N`): as few groups as
+    /// `max` allows, as even in size as possible, and none smaller than `min`
+    /// unless there are fewer students than that
+    Range { min: usize, max: usize },
+    /// Exactly this many groups, as even in size as possible (`--groups N`).
+    /// Groups entered by hand are not kept, since their sizes are not known in advance.
+    Groups(usize),
+}
+
+impl Sizing {
+    /// Number of members at which a group entered by hand is complete, if any.
+    pub fn max_size(self) -> Option<usize> {
+        match self {
+            Sizing::Triples | Sizing::Even => Some(MAX_GROUP_SIZE),
+            Sizing::Pairs => Some(2),
+            Sizing::Range { max, .. } => Some(max),
+            Sizing::Groups(_) => None,
+        }
+    }
+
+    /// Smallest group that may be formed while there are enough students.
+    pub fn min_size(self) -> usize {
+        match self {
+            Sizing::Range { min, .. } => min,
+            _ => 2,
+        }
+    }
+
+    /// Whether `n` students left over are too few for a group of their own,
+    /// so that a full group has to be reopened to take them in.
+    pub fn too_few(self, n: usize) -> bool {
+        (1..self.min_size()).contains(&n)
+    }
+
+    /// Whether `group` is complete and can be kept as entered.
+    pub fn is_full(self, group: &Group) -> bool {
+        self.max_size().is_some_and(|size| group.members.len() >= size)
+    }
+
+    /// Sizes of the groups that `n` students are split into.
+    pub fn sizes(self, n: usize) -> Vec<usize> {
+        match self {
+            Sizing::Triples => group_sizes(n),
+            Sizing::Pairs => pair_sizes(n),
+            Sizing::Even => equal_sizes(n),
+            Sizing::Range { min, max } => range_sizes(n, min, max),
+            Sizing::Groups(count) => even_sizes(n, count),
+        }
+    }
+
+    /// The bound that the sizes of `n` students break, if any: `--groups` asks
+    /// for more groups than there are pairs, or `--min` and `--max` cannot
+    /// both be met, so that the groups come out fewer or larger than asked.
+    pub fn shortfall(self, n: usize) -> Option<Shortfall> {
+        let sizes = self.sizes(n);
+        match self {
+            Sizing::Groups(count) if n > 0 && sizes.len() < count => {
+                Some(Shortfall::Groups { requested: count, made: sizes.len() })
+            }
+            Sizing::Range { min, max } => {
+                let largest = sizes.iter().copied().max().unwrap_or(0);
+                let smallest = sizes.iter().copied().min().unwrap_or(0);
+                if largest > max {
+                    Some(Shortfall::Max { max, largest })
+                } else if n > 0 && smallest < min {
+                    Some(Shortfall::Min { min, smallest })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The largest group that `n` students may be split into, including the
+    /// 3-person groups [`Sizing::Even`] falls back on when pairs cannot be made.
+    pub fn largest_size(self, n: usize) -> usize {
+        let sizing = if self == Sizing::Even { Sizing::Triples } else { self };
+        sizing.sizes(n).into_iter().max().unwrap_or(0)
+    }
+
+    /// Split `members` in order into groups of [`Sizing::sizes`].
+    pub fn split(self, members: Vec<Student>) -> Vec<Group> {
+        let mut members = members.into_iter();
+        self.sizes(members.len())
+            .into_iter()
+            .map(|size| Group::from_members(members.by_ref().take(size).collect()))
+            .collect()
+    }
+}
+
+/// A bound of the [`Sizing`] that the students at hand cannot meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortfall {
+    /// Only `made` of the `requested` groups, so that nobody is left alone
+    Groups { requested: usize, made: usize },
+    /// Groups of up to `largest`, over `--max`, so that none is under `--min`
+    Max { max: usize, largest: usize },
+    /// A group of `smallest`, under `--min`, since there are fewer students than that
+    Min { min: usize, smallest: usize },
+}
+
+/// Splits students into groups in input order as they are read (`--stream`).
+/// The groups are those of [`Sizing::split`] on the whole input, but only the
+/// last few students are held back, since their group sizes depend on the total.
+#[derive(Debug, Clone)]
+pub struct StreamSplit {
+    sizing: Sizing,
+    size: usize,
+    pending: Vec<Student>,
+}
+
+impl StreamSplit {
+    /// `None` for [`Sizing::Groups`], [`Sizing::Even`] and [`Sizing::Range`],
+    /// whose sizes need the total up front.
+    pub fn new(sizing: Sizing) -> Option<Self> {
+        if matches!(sizing, Sizing::Even | Sizing::Range { .. }) {
+            return None;
+        }
+        Some(StreamSplit {
+            sizing,
+            size: sizing.max_size()?,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Add the next student. Returns a group once its members are settled.
+    pub fn push(&mut self, student: Student) -> Option<Group> {
+        self.pending.push(student);
+        // Two students are always held back, so that the last group is never a singleton
+        (self.pending.len() >= self.size + 2).then(|| Group::from_members(self.pending.drain(..self.size).collect()))
+    }
+
+    /// The groups of the students held back, once the input has ended.
+    pub fn finish(self) -> Vec<Group> {
+        self.sizing.split(self.pending)
+    }
+}
+
+/// Options controlling how students are assigned to groups.
+#[derive(Debug, Clone, Default)]
+pub struct GroupingConfig {
+    pub mode: Mode,
+    /// Sizes of the groups formed
+    pub sizing: Sizing,
+    /// Seed for the random shuffle. The same seed and input always produce
+    /// the same groups; `None` picks a fresh seed from the OS.
+    pub seed: Option<u64>,
+    /// Constraints the grouping must honor, such as students who must stay together
+    pub constraints: Constraints,
+    /// Attributes to spread evenly across groups (e.g. `gender`, `skill`)
+    pub balance: Vec<String>,
+    /// Numeric attribute (e.g. a participation score) whose high values are
+    /// spread across groups instead of shuffled uniformly
+    pub spread_weights: Option<String>,
+    /// Attribute such as a section whose values every group should mix
+    pub stratify: Option<String>,
+    /// Never mix the values of `stratify` instead: each value is grouped on its own
+    pub within_strata: bool,
+    /// Numeric skill rating that [`strategy::MixedSkill`] deals by tier (`--skill`)
+    pub skill: Option<String>,
+    /// Attributes whose similar values [`strategy::Similar`] puts together (`--group-by`)
+    pub group_by: Vec<String>,
+    /// Fill the groups remade from incomplete ones in input order instead of
+    /// shuffling (`--no-shuffle`)
+    pub no_shuffle: bool,
+    /// Past groupings; when given, repeating past pairings is avoided
+    pub history: Option<History>,
+    /// Swaps tried to lower repeated pairings further (`--optimize-repeats`);
+    /// only used with `history`
+    pub optimize_iterations: Option<usize>,
+    /// Time spent improving the finished grouping by swapping members
+    /// (`--optimize`), with [`anneal::optimize`]
+    pub optimize_time: Option<Duration>,
+    /// Groupings bred at once by [`strategy::Genetic`] (`--population`)
+    pub population: Option<usize>,
+    /// Generations bred by [`strategy::Genetic`] (`--generations`)
+    pub generations: Option<usize>,
+    /// Classmates each student wishes to work with, granted by
+    /// [`strategy::Preferred`] (`--preferences`)
+    pub preferences: Option<Preferences>,
+    /// Algorithm that forms the groups (`--strategy`); `None` for [`strategy::Auto`]
+    pub strategy: Option<&'static dyn GroupingStrategy>,
+    /// Sizes asked for some of the entered groups, by their index (`@size=N`
+    /// in batch input). Those groups are kept apart with that many of their
+    /// members instead of being regrouped with everyone else.
+    pub size_overrides: HashMap<usize, usize>,
+    /// Entered groups pinned by their index (`!` or `pin:`), kept exactly as
+    /// they are: never split, merged or filled up with other students
+    pub pinned: HashSet<usize>,
+}
+
+impl GroupingConfig {
+    /// Whether the grouping depends on the random seed. Plain batch grouping
+    /// keeps the input order and uses no randomness.
+    pub fn uses_rng(&self) -> bool {
+        (self.mode == Mode::Interactive && !self.no_shuffle)
+            || !self.constraints.is_empty()
+            || !self.balance.is_empty()
+            || self.spread_weights.is_some()
+            || self.mixes_strata()
+            || self.history.is_some()
+            || self.strategy.is_some()
+            || self.optimize_time.is_some()
+    }
+
+    /// Whether the values of `stratify` are spread across groups like balanced attributes.
+    pub fn mixes_strata(&self) -> bool {
+        self.stratify.is_some() && !self.within_strata
+    }
+
+    /// Attributes to balance: the `stratify` attribute when mixing, then those of `balance`.
+    pub fn balance_keys(&self) -> Vec<String> {
+        self.stratify
+            .iter()
+            .filter(|_| !self.within_strata)
+            .chain(&self.balance)
+            .cloned()
+            .collect()
+    }
+
+    /// The settings that decide the grouping besides the seed and the input,
+    /// named like the keys of the configuration file. Defaults are left out,
+    /// and the constraints are given in the constraints file format.
+    pub fn settings(&self) -> Vec<(String, Value)> {
+        let mut settings: Vec<(&str, Value)> = Vec::new();
+        match self.sizing {
+            Sizing::Triples => {}
+            Sizing::Pairs => settings.push(("pairs", Value::Bool(true))),
+            Sizing::Even => settings.push(("balanced", Value::from("equal"))),
+            Sizing::Range { min, max } => {
+                settings.push(("min", Value::from(min)));
+                settings.push(("max", Value::from(max)));
+            }
+            Sizing::Groups(n) => settings.push(("groups", Value::from(n))),
+        }
+        if !self.balance.is_empty() {
+            settings.push(("balance", Value::Array(self.balance.iter().map(|key| Value::from(key.as_str())).collect())));
+        }
+        if let Some(key) = &self.spread_weights {
+            settings.push(("spread_weights", Value::from(key.as_str())));
+        }
+        if let Some(key) = &self.stratify {
+            settings.push(("stratify", Value::from(key.as_str())));
+        }
+        if self.within_strata {
+            settings.push(("within_strata", Value::Bool(true)));
+        }
+        if let Some(key) = &self.skill {
+            settings.push(("skill", Value::from(key.as_str())));
+        }
+        if self.no_shuffle {
+            settings.push(("no_shuffle", Value::Bool(true)));
+        }
+        if !self.group_by.is_empty() {
+            settings.push(("group_by", Value::Array(self.group_by.iter().map(|key| Value::from(key.as_str())).collect())));
+        }
+        if !self.constraints.is_empty() {
+            settings.push(("constraints", Value::from(self.constraints.to_string())));
+        }
+        if self.history.is_some() {
+            settings.push(("avoid_repeats", Value::Bool(true)));
+        }
+        if let Some(iterations) = self.optimize_iterations {
+            settings.push(("optimize_repeats", Value::Bool(true)));
+            settings.push(("iterations", Value::from(iterations)));
+        }
+        if let Some(strategy) = self.strategy {
+            settings.push(("strategy", Value::from(strategy.name())));
+        }
+        if let Some(population) = self.population {
+            settings.push(("population", Value::from(population)));
+        }
+        if let Some(generations) = self.generations {
+            settings.push(("generations", Value::from(generations)));
+        }
+        if let Some(preferences) = &self.preferences {
+            settings.push(("preferences", Value::from(preferences.to_string())));
+        }
+        if let Some(budget) = self.optimize_time {
+            settings.push(("optimize", Value::Bool(true)));
+            settings.push(("time_budget", Value::from(anneal::format_duration(budget))));
+        }
+        settings.into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+    }
+
+    /// Build the random number generator described by this configuration.
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed.unwrap_or_else(random_seed))
+    }
+}
+
+/// A fresh random seed from the OS.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// A fresh seed. `wasm32-unknown-unknown` has no OS entropy, so callers in
+/// the browser should pass a seed (e.g. from `crypto.getRandomValues`);
+/// without one, seeds only vary between calls within a page.
+#[cfg(target_arch = "wasm32")]
+pub fn random_seed() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(0x9e37_79b9_7f4a_7c15);
+    NEXT.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+}
+
+/// Assign a flat list of students to groups of 2-3 people.
+///
+/// In [`Mode::Batch`] the input order is preserved; in [`Mode::Interactive`]
+/// every student is treated as unassigned and the groups are formed randomly.
+/// Fails like [`regroup`].
+pub fn assign<S: Into<Student>>(students: Vec<S>, config: GroupingConfig) -> Result<Vec<Group>, GroupingError> {
+    let groups = match config.mode {
+        Mode::Batch => vec![Group::from_members(students)],
+        Mode::Interactive => students
+            .into_iter()
+            .map(|student| Group::from_members(vec![student]))
+            .collect(),
+    };
+    regroup(groups, config)
+}
+
+/// Reorganize groups that were entered by hand into the final grouping, with
+/// `config.strategy` ([`strategy::Auto`] when not given).
+///
+/// The default strategy uses [`history::reorganize_avoiding_repeats`] with
+/// `config.history`, the constraint-aware pass in
+/// [`constraints::reorganize_with_constraints`] when `config.constraints` is
+/// not empty, and otherwise [`balance::reorganize_balanced`] when
+/// `config.balance` names attributes or `config.spread_weights` is set.
+///
+/// With `config.within_strata`, each value of `config.stratify` is first set
+/// apart by [`balance::split_strata`] and grouped on its own; otherwise the
+/// `stratify` attribute is balanced like those of `config.balance`.
+///
+/// With `config.optimize_time`, the result is then improved by
+/// [`anneal::optimize`], leaving alone the groups the strategy keeps.
+///
+/// Fails when a student appears more than once, when zero groups are asked
+/// for, or when a pinned group has a single member, instead of returning a grouping that silently loses or repeats students.
+pub fn regroup(groups: Vec<Group>, config: GroupingConfig) -> Result<Vec<Group>, GroupingError> {
+    if config.sizing == Sizing::Groups(0) {
+        return Err(GroupingError::NoGroups);
+    }
+    if let Some(&size) = config.size_overrides.values().find(|&&size| size < 2) {
+        return Err(GroupingError::GroupTooSmall(size));
+    }
+    if let Some(group) = config.pinned.iter().filter_map(|&i| groups.get(i)).find(|g| g.members.len() == 1) {
+        return Err(GroupingError::GroupTooSmall(group.members.len()));
+    }
+    let mut seen = HashSet::new();
+    if let Some(id) = groups.iter().flat_map(Group::ids).find(|id| !seen.insert(*id)) {
+        return Err(GroupingError::DuplicateStudent(id.clone()));
+    }
+    let Some(budget) = config.optimize_time else {
+        return Ok(reorganize(groups, config));
+    };
+    let kept = kept_groups(&groups, &config);
+    let mut rng = config.rng();
+    let mut result = reorganize(groups, config.clone());
+    log::info(|| message(Msg::TraceOptimize, &[&anneal::format_duration(budget)]));
+    anneal::optimize(&mut result, &kept, &config, budget, &mut rng);
+    Ok(result)
+}
+
+/// The entered groups that [`reorganize`] leaves as they are: the pinned
+/// ones, those of `config.size_overrides` (up to their size), and in
+/// interactive mode the full ones.
+fn kept_groups(groups: &[Group], config: &GroupingConfig) -> Vec<Group> {
+    groups
+        .iter()
+        .enumerate()
+        .filter_map(|(i, group)| {
+            if config.pinned.contains(&i) || (config.mode == Mode::Interactive && config.sizing.is_full(group)) {
+                return Some(group.clone());
+            }
+            let size = *config.size_overrides.get(&i)?;
+            Some(Group::from_members(group.members.iter().take(size).cloned().collect()))
+        })
+        .collect()
+}
+
+/// [`regroup`] for groups that are already known to be valid, such as the
+/// candidates tried by [`history::reorganize_avoiding_repeats`].
+pub(crate) fn reorganize(groups: Vec<Group>, config: GroupingConfig) -> Vec<Group> {
+    if !config.size_overrides.is_empty() || !config.pinned.is_empty() {
+        return reorganize_with_sizes(groups, config);
+    }
+    if let (Some(key), true) = (&config.stratify, config.within_strata) {
+        let strata = balance::split_strata(groups, key);
+        log::info(|| message(Msg::TraceStrata, &[&key, &strata.len()]));
+        let base = GroupingConfig {
+            stratify: None,
+            within_strata: false,
+            ..config.clone()
+        };
+        return strata
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, stratum)| {
+                // A different seed per stratum, so that equal-sized strata are not shuffled alike
+                let seed = base.seed.map(|seed| seed.wrapping_add(i as u64));
+                reorganize(stratum, GroupingConfig { seed, ..base.clone() })
+            })
+            .collect();
+    }
+    let strategy = config.strategy.unwrap_or(&strategy::Auto);
+    log::info(|| message(Msg::TraceStrategy, &[&strategy.name(), &groups.len(), &log::sizes(&groups)]));
+    strategy.assign(groups, &config)
+}
+
+/// Keep the groups of `config.size_overrides` at their sizes and those of
+/// `config.pinned` whole, and regroup the rest, along with the members beyond
+/// those sizes, as usual. The kept groups stay where they were entered. With
+/// [`Sizing::Groups`], they count towards the total.
+fn reorganize_with_sizes(groups: Vec<Group>, config: GroupingConfig) -> Vec<Group> {
+    let mut kept: Vec<(usize, Group)> = Vec::new();
+    let mut rest: Vec<Group> = Vec::new();
+    let mut overflow: Vec<Student> = Vec::new();
+    for (i, group) in groups.into_iter().enumerate() {
+        if config.pinned.contains(&i) && !group.members.is_empty() {
+            log::info(|| message(Msg::TracePinned, &[&log::describe(i, &group)]));
+            kept.push((i, group));
+            continue;
+        }
+        let Some(&size) = config.size_overrides.get(&i) else {
+            rest.push(group);
+            continue;
+        };
+        let mut members = group.members;
+        overflow.extend(members.split_off(size.min(members.len())));
+        let group = Group::from_members(members);
+        log::info(|| message(Msg::TraceSizeOverride, &[&log::describe(i, &group), &size]));
+        kept.push((i, group));
+    }
+    if !overflow.is_empty() {
+        log::info(|| message(Msg::TraceOverflow, &[&overflow.len()]));
+        rest.push(Group::from_members(overflow));
+    }
+    // Students left over too few for a group of their own (a lone student,
+    // or fewer than `--min`) are regrouped with the last kept groups that are not pinned
+    while config.sizing.too_few(rest.iter().map(|g| g.members.len()).sum()) {
+        let Some(last) = kept.iter().rposition(|(i, _)| !config.pinned.contains(i)) else {
+            break;
+        };
+        let (i, last) = kept.remove(last);
+        log::info(|| message(Msg::TraceLeftover, &[&log::describe(i, &last)]));
+        rest.insert(0, last);
+    }
+
+    let sizing = match config.sizing {
+        Sizing::Groups(count) => Sizing::Groups(count.saturating_sub(kept.len()).max(1)),
+        sizing => sizing,
+    };
+    let config = GroupingConfig {
+        sizing,
+        size_overrides: HashMap::new(),
+        pinned: HashSet::new(),
+        ..config
+    };
+    let mut result = if rest.iter().all(|g| g.members.is_empty()) {
+        Vec::new()
+    } else {
+        reorganize(rest, config)
+    };
+    for (i, group) in kept {
+        result.insert(i.min(result.len()), group);
+    }
+    result
+}
+
+/// Sizes of the groups that `n` students are split into: as many 3-person groups
+/// as possible, with 2-person groups only when `n` is not divisible by 3.
+pub fn group_sizes(n: usize) -> Vec<usize> {
+    let mut sizes = Vec::new();
+
+    // Edge case: single member should not create a singleton
+    // This shouldn't happen in normal use since we only split groups > 3
+    if n == 1 {
+        sizes.push(1);
+        return sizes;
+    }
+
+    let mut idx = 0;
+    while idx < n {
+        let remaining = n - idx;
+
+        let group_size = if remaining >= 3 {
+            if remaining == 4 {
+                // 4 -> 2 + 2
+                2
+            } else {
+                3
+            }
+        } else {
+            // 2 remaining (1 is not possible when n >= 2 due to the algorithm)
+            remaining
+        };
+
+        sizes.push(group_size);
+        idx += group_size;
+    }
+
+    sizes
+}
+
+/// Sizes of the groups that `n` students are split into with `--pairs`: pairs,
+/// with one 3-person group when `n` is odd.
+pub fn pair_sizes(n: usize) -> Vec<usize> {
+    if n < 2 {
+        return vec![n; n];
+    }
+    let mut sizes = vec![2; n / 2];
+    if n % 2 == 1 {
+        *sizes.last_mut().expect("n >= 2") = 3;
+    }
+    sizes
+}
+
+/// Sizes of the groups that `n` students are split into with `--balanced equal`:
+/// all 3-person groups when `n` is divisible by 3, otherwise all pairs when `n`
+/// is even, and the sizes of [`group_sizes`] when neither is possible.
+pub fn equal_sizes(n: usize) -> Vec<usize> {
+    if n % 3 != 0 && n % 2 == 0 {
+        vec![2; n / 2]
+    } else {
+        group_sizes(n)
+    }
+}
+
+/// Sizes of the groups that `n` students are split into with `--min` and
+/// `--max`: as few groups as `max` allows, differing by at most one, larger
+/// groups first. When that would make groups smaller than `min`, fewer and
+/// larger groups are made instead (5 students with `--min 3 --max 4` make one
+/// group of 5), so that `min` is only broken by a class smaller than it.
+pub fn range_sizes(n: usize, min: usize, max: usize) -> Vec<usize> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let max = max.max(1);
+    let mut count = (n + max - 1) / max;
+    if n / count < min {
+        count = (n / min).max(1);
+    }
+    (0..count).map(|i| n / count + usize::from(i < n % count)).collect()
+}
+
+/// Sizes of `count` groups for `n` students, differing by at most one, larger
+/// groups first. Fewer groups are made when there are not enough students
+/// for two per group, so that nobody is left alone.
+pub fn even_sizes(n: usize, count: usize) -> Vec<usize> {
+    let count = count.min(n / 2).max(1.min(n));
+    (0..count)
+        .map(|i| n / count + usize::from(i < n % count))
+        .collect()
+}
+
+/// Add students who arrived after `groups` were made, without moving anyone
+/// already placed (`--append`).
+///
+/// Groups with room for the sizing take one student at a time, the smallest
+/// first, and two or more students left over (`--min` or more) make new groups
+/// of their own. Fewer join the smallest group instead of being alone, even
+/// when that makes it larger than usual. With [`Sizing::Groups`] every
+/// student joins the smallest group, so the number of groups stays the same.
+pub fn append_late(mut groups: Vec<Group>, late: Vec<Student>, sizing: Sizing) -> Vec<Group> {
+    let room = sizing.max_size().unwrap_or(usize::MAX);
+    let mut late = late.into_iter();
+    while let Some((i, group)) = groups
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, g)| g.members.len() < room)
+        .min_by_key(|(_, g)| g.members.len())
+    {
+        match late.next() {
+            Some(id) => {
+                log::debug(|| message(Msg::TraceFillGap, &[&id, &log::describe(i, group)]));
+                group.members.push(id);
+            }
+            None => return groups,
+        }
+    }
+
+    let rest: Vec<Student> = late.collect();
+    match groups.iter_mut().enumerate().min_by_key(|(_, g)| g.members.len()) {
+        Some((i, smallest)) if sizing.too_few(rest.len()) => {
+            log::info(|| message(Msg::TraceToSmallest, &[&student::join_ids(&rest, ", "), &log::describe(i, smallest)]));
+            smallest.members.extend(rest);
+        }
+        _ => {
+            let added = sizing.split(rest);
+            log::info(|| message(Msg::TraceNewGroups, &[&added.len(), &log::sizes(&added)]));
+            groups.extend(added);
+        }
+    }
+    groups
+}
+
+/// Helper function to split a list of members into groups of 2-3 people.
+/// [`Sizing::split`] splits into other sizes, such as those of `--min`/`--max`.
+pub fn split_into_small_groups(members: Vec<Student>) -> Vec<Group> {
+    Sizing::Triples.split(members)
+}
+
+/// Reorganize groups from batch mode - collect all members and create optimal groups
+/// 2-person groups are only created when the total is NOT divisible by 3 (at most 1-2 such groups)
+/// Maximizes 3-person groups as much as possible
+pub fn reorganize_batch_groups(groups: Vec<Group>) -> Vec<Group> {
+    if groups.is_empty() {
+        return groups;
+    }
+
+    // Collect all members from all groups
+    let mut all_members: Vec<Student> = Vec::new();
+    for group in groups {
+        all_members.extend(group.members);
+    }
+
+    // Use split_into_small_groups to create optimal groupings
+    // This maximizes 3-person groups and only creates 2-person groups when n % 3 != 0
+    split_into_small_groups(all_members)
+}
+
+/// Keep full groups as they are and randomly recombine the members of incomplete
+/// groups into new groups of 2-3 people, never leaving anyone alone. See
+/// [`reorganize_incomplete_sized`] for other sizes.
+pub fn reorganize_incomplete_groups(groups: Vec<Group>) -> Vec<Group> {
+    reorganize_incomplete_groups_with_rng(groups, &mut StdRng::seed_from_u64(random_seed()))
+}
+
+/// Same as [`reorganize_incomplete_groups`], drawing the shuffle from `rng`.
+pub fn reorganize_incomplete_groups_with_rng<R: Rng + ?Sized>(
+    groups: Vec<Group>,
+    rng: &mut R,
+) -> Vec<Group> {
+    reorganize_incomplete_sized(groups, Sizing::Triples, rng)
+}
+
+/// Keep the groups that are full for `sizing` and randomly recombine the
+/// members of the others into new groups of the sizes `sizing` gives.
+pub fn reorganize_incomplete_sized<R: Rng + ?Sized>(groups: Vec<Group>, sizing: Sizing, rng: &mut R) -> Vec<Group> {
+    reorganize_incomplete_with(groups, sizing, |members| members.shuffle(rng))
+}
+
+/// Same as [`reorganize_incomplete_sized`], but the members of the incomplete
+/// groups fill the new groups in the order they were entered (`--no-shuffle`).
+pub fn reorganize_incomplete_in_order(groups: Vec<Group>, sizing: Sizing) -> Vec<Group> {
+    reorganize_incomplete_with(groups, sizing, |_| {})
+}
+
+/// Keep the full groups and split the members of the others, put in order by
+/// `arrange`, into new groups.
+fn reorganize_incomplete_with(groups: Vec<Group>, sizing: Sizing, arrange: impl FnOnce(&mut Vec<Student>)) -> Vec<Group> {
+    let mut final_groups = Vec::new();
+    let mut incomplete_members = Vec::new();
+    let mut full_indices = Vec::new();
+    for (i, group) in groups.into_iter().enumerate() {
+        if sizing.is_full(&group) {
+            log::debug(|| message(Msg::TraceComplete, &[&log::describe(i, &group)]));
+            final_groups.push(group);
+            full_indices.push(i);
+        } else {
+            log::debug(|| message(Msg::TraceIncomplete, &[&log::describe(i, &group)]));
+            incomplete_members.extend(group.members);
+        }
+    }
+    // A lone leftover student would form a singleton; reopen the last full
+    // group so that it can be split again (3+1 becomes 2+2, 2+1 becomes 3).
+    // With `--min`, full groups are reopened until the leftovers reach it.
+    while sizing.too_few(incomplete_members.len()) {
+        let Some((i, last)) = full_indices.pop().zip(final_groups.pop()) else {
+            break;
+        };
+        log::info(|| message(Msg::TraceJoinComplete, &[&incomplete_members.len(), &log::describe(i, &last)]));
+        incomplete_members.extend(last.members);
+    }
+
+    arrange(&mut incomplete_members);
+    let regrouped = sizing.split(incomplete_members);
+    if !regrouped.is_empty() {
+        log::info(|| {
+            let members: usize = regrouped.iter().map(|g| g.members.len()).sum();
+            message(Msg::TraceRegroupIncomplete, &[&members, &log::sizes(&regrouped)])
+        });
+    }
+    final_groups.extend(regrouped);
+    final_groups
+}
+
+/// Indices of the groups [`reroll`] reshuffles: the `targets` and, except with
+/// [`Sizing::Groups`], every group that is not full.
+pub fn reroll_pool(groups: &[Group], targets: &[usize], sizing: Sizing) -> Vec<usize> {
+    let incomplete = |group: &Group| sizing.max_size().is_some() && !sizing.is_full(group);
+    (0..groups.len())
+        .filter(|&i| targets.contains(&i) || incomplete(&groups[i]))
+        .collect()
+}
+
+/// Shuffle the members of the groups in [`reroll_pool`], leaving the other
+/// groups as they are. The new groups take the places of the old ones, so
+/// untouched groups keep their position (and label).
+pub fn reroll<R: Rng + ?Sized>(groups: &[Group], targets: &[usize], sizing: Sizing, rng: &mut R) -> Vec<Group> {
+    let pooled = reroll_pool(groups, targets, sizing);
+    for &i in &pooled {
+        log::debug(|| message(Msg::TracePooled, &[&log::describe(i, &groups[i])]));
+    }
+    let mut pool: Vec<Student> = pooled.iter().flat_map(|&i| groups[i].members.clone()).collect();
+    pool.shuffle(rng);
+
+    // With a fixed number of groups, the pooled groups are remade as as many groups
+    let sizing = match sizing {
+        Sizing::Groups(_) => Sizing::Groups(pooled.len()),
+        sizing => sizing,
+    };
+    let mut rerolled = sizing.split(pool).into_iter();
+    let mut result = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        if !pooled.contains(&i) {
+            result.push(group.clone());
+        } else if let Some(group) = rerolled.next() {
+            result.push(group);
+        }
+    }
+    result.extend(rerolled);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::StudentId;
+
+    #[test]
+    fn test_reorganize_with_complete_groups() {
+        let mut group1 = Group::new();
+        group1.add_member("S001".to_string()).unwrap();
+        group1.add_member("S002".to_string()).unwrap();
+        group1.add_member("S003".to_string()).unwrap();
+
+        let mut group2 = Group::new();
+        group2.add_member("S004".to_string()).unwrap();
+        group2.add_member("S005".to_string()).unwrap();
+        group2.add_member("S006".to_string()).unwrap();
+
+        let groups = vec![group1, group2];
+        let result = reorganize_incomplete_groups(groups);
+
+        assert_eq!(result.len(), 2);
+        assert!(result[0].is_full());
+        assert!(result[1].is_full());
+    }
+
+    #[test]
+    fn test_reorganize_with_incomplete_groups() {
+        let mut group1 = Group::new();
+        group1.add_member("S001".to_string()).unwrap();
+        group1.add_member("S002".to_string()).unwrap();
+
+        let mut group2 = Group::new();
+        group2.add_member("S003".to_string()).unwrap();
+        group2.add_member("S004".to_string()).unwrap();
+
+        let groups = vec![group1, group2];
+        let result = reorganize_incomplete_groups(groups);
+
+        // 4 members should form 2 groups of 2 (not 3+1 which would create a singleton)
+        let total_members: usize = result.iter().map(|g| g.members.len()).sum();
+        assert_eq!(total_members, 4);
+        assert_eq!(result.len(), 2);
+        // Verify no single-person groups
+        for group in &result {
+            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+        }
+    }
+
+    #[test]
+    fn test_reorganize_allows_two_person_groups() {
+        let mut group1 = Group::new();
+        group1.add_member("S001".to_string()).unwrap();
+
+        let mut group2 = Group::new();
+        group2.add_member("S002".to_string()).unwrap();
+
+        let groups = vec![group1, group2];
+        let result = reorganize_incomplete_groups(groups);
+
+        // 2 members should form 1 group with 2 members
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].members.len(), 2);
+    }
+
+    #[test]
+    fn test_mixed_complete_and_incomplete_groups() {
+        let mut group1 = Group::new();
+        group1.add_member("S001".to_string()).unwrap();
+        group1.add_member("S002".to_string()).unwrap();
+        group1.add_member("S003".to_string()).unwrap();
+
+        let mut group2 = Group::new();
+        group2.add_member("S004".to_string()).unwrap();
+
+        let mut group3 = Group::new();
+        group3.add_member("S005".to_string()).unwrap();
+
+        let groups = vec![group1, group2, group3];
+        let result = reorganize_incomplete_groups(groups);
+
+        // Should have 1 complete group (unchanged) + 1 group with 2 members (no singletons)
+        assert_eq!(result.len(), 2);
+        let complete_groups = result.iter().filter(|g| g.is_full()).count();
+        assert_eq!(complete_groups, 1);
+        // Verify no single-person groups
+        for group in &result {
+            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+        }
+    }
+
+    #[test]
+    fn test_no_single_person_groups_with_seven_students() {
+        // Test with 7 students (would normally be 3+3+1)
+        let groups = vec![
+            {
+                let mut g = Group::new();
+                g.add_member("S001".to_string()).unwrap();
+                g.add_member("S002".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S003".to_string()).unwrap();
+                g.add_member("S004".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S005".to_string()).unwrap();
+                g.add_member("S006".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S007".to_string()).unwrap();
+                g
+            },
+        ];
+        
+        let result = reorganize_incomplete_groups(groups);
+
+        // Should not have any single-person groups
+        for group in &result {
+            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+        }
+        
+        // Total should still be 7
+        let total: usize = result.iter().map(|g| g.members.len()).sum();
+        assert_eq!(total, 7);
+    }
+
+    #[test]
+    fn test_no_single_person_groups_with_ten_students() {
+        // Test with 10 students all incomplete (would normally be 3+3+3+1)
+        let groups = vec![
+            {
+                let mut g = Group::new();
+                g.add_member("S001".to_string()).unwrap();
+                g.add_member("S002".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S003".to_string()).unwrap();
+                g.add_member("S004".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S005".to_string()).unwrap();
+                g.add_member("S006".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S007".to_string()).unwrap();
+                g.add_member("S008".to_string()).unwrap();
+                g
+            },
+            {
+                let mut g = Group::new();
+                g.add_member("S009".to_string()).unwrap();
+                g.add_member("S010".to_string()).unwrap();
+                g
+            },
+        ];
+
+        let result = reorganize_incomplete_groups(groups);
+
+        // Should not have any single-person groups
+        for group in &result {
+            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+        }
+        
+        // Total should still be 10
+        let total: usize = result.iter().map(|g| g.members.len()).sum();
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn test_single_student_with_complete_group() {
+        // Test 1 incomplete student with 1 complete group
+        let mut complete_group = Group::new();
+        complete_group.add_member("S001".to_string()).unwrap();
+        complete_group.add_member("S002".to_string()).unwrap();
+        complete_group.add_member("S003".to_string()).unwrap();
+
+        let mut single_group = Group::new();
+        single_group.add_member("S004".to_string()).unwrap();
+
+        let groups = vec![complete_group, single_group];
+        let result = reorganize_incomplete_groups(groups);
+
+        // Should create two 2-person groups instead of one 4-person group
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members.len(), 2);
+        assert_eq!(result[1].members.len(), 2);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_five_members() {
+        // Test that 5 members (5 % 3 == 2) creates 1 three-person + 1 two-person group
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into(), "B".into(), "C".into()];
+
+        let mut group2 = Group::new();
+        group2.members = vec!["D".into(), "E".into()];
+
+        let groups = vec![group1, group2];
+        let result = reorganize_batch_groups(groups);
+
+        // 5 members should become 3+2 (optimal grouping)
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members.len(), 3);
+        assert_eq!(result[1].members.len(), 2);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_three_members() {
+        // Test that 3 members form a single 3-person group
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into(), "B".into()];
+
+        let mut group2 = Group::new();
+        group2.members = vec!["C".into()]; // singleton
+
+        let groups = vec![group1, group2];
+        let result = reorganize_batch_groups(groups);
+
+        // 3 members should form one 3-person group
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].members.len(), 3);
+        assert!(result[0].contains("A"));
+        assert!(result[0].contains("B"));
+        assert!(result[0].contains("C"));
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_three_members_reverse_order() {
+        // Test that 3 members form a single 3-person group (different input order)
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into()]; // singleton at start
+
+        let mut group2 = Group::new();
+        group2.members = vec!["B".into(), "C".into()];
+
+        let groups = vec![group1, group2];
+        let result = reorganize_batch_groups(groups);
+
+        // 3 members should form one 3-person group
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].members.len(), 3);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_four_members() {
+        // Test that 4 members (4 % 3 == 1) creates 2 two-person groups
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into(), "B".into()];
+
+        let mut group2 = Group::new();
+        group2.members = vec!["C".into()]; // singleton
+
+        let mut group3 = Group::new();
+        group3.members = vec!["D".into()]; // singleton
+
+        let groups = vec![group1, group2, group3];
+        let result = reorganize_batch_groups(groups);
+
+        // 4 members should become 2+2 (two two-person groups)
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members.len(), 2);
+        assert_eq!(result[1].members.len(), 2);
+        // Order is preserved from input
+        assert!(result[0].contains("A"));
+        assert!(result[0].contains("B"));
+        assert!(result[1].contains("C"));
+        assert!(result[1].contains("D"));
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_splits_large_group() {
+        // Test that a 4-person group is split into 2+2
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into(), "B".into(), "C".into(), "D".into()];
+
+        let groups = vec![group1];
+        let result = reorganize_batch_groups(groups);
+
+        // 4-person group should be split into 2+2
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members.len(), 2);
+        assert_eq!(result[1].members.len(), 2);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_splits_five_person_group() {
+        // Test that a 5-person group is split into 3+2
+        let mut group1 = Group::new();
+        group1.members = vec!["A".into(), "B".into(), "C".into(), "D".into(), "E".into()];
+
+        let groups = vec![group1];
+        let result = reorganize_batch_groups(groups);
+
+        // 5-person group should be split into 3+2
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members.len(), 3);
+        assert_eq!(result[1].members.len(), 2);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_splits_seven_person_group() {
+        // Test that a 7-person group is split into 3+2+2
+        let mut group1 = Group::new();
+        group1.members = vec![
+            "A".into(), "B".into(), "C".into(), "D".into(),
+            "E".into(), "F".into(), "G".into()
+        ];
+
+        let groups = vec![group1];
+        let result = reorganize_batch_groups(groups);
+
+        // 7-person group should be split into 3+2+2
+        assert_eq!(result.len(), 3);
+        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn test_reorganize_batch_groups_no_single_person_groups() {
+        // Verify that no single-person groups are ever created
+        for total in 2..=20 {
+            let mut group = Group::new();
+            for i in 0..total {
+                group.members.push(format!("S{:03}", i).into());
+            }
+            let groups = vec![group];
+            let result = reorganize_batch_groups(groups);
+            
+            for g in &result {
+                assert!(g.members.len() >= 2, "Group with {} members found for total {}", g.members.len(), total);
+                assert!(g.members.len() <= 3, "Group with {} members found for total {}", g.members.len(), total);
+            }
+            
+            let total_after: usize = result.iter().map(|g| g.members.len()).sum();
+            assert_eq!(total_after, total, "Total members should be preserved");
+        }
+    }
+
+    #[test]
+    fn test_two_person_groups_only_when_not_divisible_by_three() {
+        // Verify that 2-person groups are only created when total % 3 != 0
+        // When total % 3 == 0: 0 two-person groups
+        // When total % 3 == 1: 2 two-person groups (to avoid 1-person groups)
+        // When total % 3 == 2: 1 two-person group
+        for total in 2..=30 {
+            let mut group = Group::new();
+            for i in 0..total {
+                group.members.push(format!("S{:03}", i).into());
+            }
+            let groups = vec![group];
+            let result = reorganize_batch_groups(groups);
+            
+            let two_person_count = result.iter().filter(|g| g.members.len() == 2).count();
+            let remainder = total % 3;
+            
+            let expected_two_person_groups = match remainder {
+                0 => 0, // Divisible by 3, no 2-person groups
+                1 => 2, // 1 remainder -> need 2 two-person groups (e.g., 4 = 2+2)
+                2 => 1, // 2 remainder -> need 1 two-person group (e.g., 5 = 3+2)
+                _ => unreachable!(),
+            };
+            
+            assert_eq!(
+                two_person_count, expected_two_person_groups,
+                "For {} members (remainder {}), expected {} two-person groups but got {}",
+                total, remainder, expected_two_person_groups, two_person_count
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_mode_maximizes_three_person_groups() {
+        // Test that batch mode now maximizes 3-person groups even with user-defined 2-person groups
+        // 12 members with user-defined 2-person groups should become 4 three-person groups
+        let groups: Vec<Group> = (0..6).map(|i| {
+            let mut g = Group::new();
+            g.members = vec![
+                format!("S{:03}", i * 2).into(),
+                format!("S{:03}", i * 2 + 1).into(),
+            ];
+            g
+        }).collect();
+        
+        let result = reorganize_batch_groups(groups);
+        
+        // 12 members (12 % 3 == 0) should all be 3-person groups
+        let three_person_count = result.iter().filter(|g| g.members.len() == 3).count();
+        let two_person_count = result.iter().filter(|g| g.members.len() == 2).count();
+        
+        assert_eq!(three_person_count, 4, "Should have 4 three-person groups");
+        assert_eq!(two_person_count, 0, "Should have 0 two-person groups when divisible by 3");
+        
+        // Verify total is preserved
+        let total: usize = result.iter().map(|g| g.members.len()).sum();
+        assert_eq!(total, 12);
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        assert_eq!(group_sizes(0), Vec::<usize>::new());
+        assert_eq!(group_sizes(1), vec![1]);
+        assert_eq!(group_sizes(4), vec![2, 2]);
+        assert_eq!(group_sizes(8), vec![3, 3, 2]);
+        assert_eq!(group_sizes(10), vec![3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn test_equal_sizes() {
+        assert_eq!(equal_sizes(8), vec![2, 2, 2, 2]);
+        assert_eq!(equal_sizes(9), vec![3, 3, 3]);
+        assert_eq!(equal_sizes(12), vec![3, 3, 3, 3]);
+        assert_eq!(equal_sizes(10), vec![2; 5]);
+        // Neither all pairs nor all triples: as for --balanced fewest
+        assert_eq!(equal_sizes(7), vec![3, 2, 2]);
+        assert_eq!(equal_sizes(1), vec![1]);
+        assert!(StreamSplit::new(Sizing::Even).is_none());
+    }
+
+    #[test]
+    fn test_range_sizes() {
+        assert_eq!(range_sizes(10, 2, 4), vec![4, 3, 3]);
+        assert_eq!(range_sizes(9, 2, 4), vec![3, 3, 3]);
+        assert_eq!(range_sizes(7, 2, 3), group_sizes(7));
+        assert_eq!(range_sizes(10, 4, 5), vec![5, 5]);
+        // 3+2 would break --min 3, so the 5 students stay together
+        assert_eq!(range_sizes(5, 3, 4), vec![5]);
+        assert_eq!(range_sizes(2, 3, 4), vec![2]);
+        assert_eq!(range_sizes(0, 3, 4), Vec::<usize>::new());
+
+        // Bounds that cannot both be met are reported
+        assert_eq!(Sizing::Range { min: 4, max: 4 }.shortfall(10), Some(Shortfall::Max { max: 4, largest: 5 }));
+        assert_eq!(Sizing::Range { min: 3, max: 4 }.shortfall(2), Some(Shortfall::Min { min: 3, smallest: 2 }));
+        assert_eq!(Sizing::Range { min: 2, max: 4 }.shortfall(10), None);
+    }
+
+    #[test]
+    fn test_reorganize_incomplete_with_min() {
+        let sizing = Sizing::Range { min: 3, max: 4 };
+        let ids = |range: std::ops::RangeInclusive<usize>| range.map(|i| format!("S{:03}", i)).collect::<Vec<_>>();
+        // Two full groups and two students left over: one full group is reopened for them
+        let groups = vec![
+            Group::from_members(ids(1..=4)),
+            Group::from_members(ids(5..=8)),
+            Group::from_members(ids(9..=10)),
+        ];
+        let result = reorganize_incomplete_sized(groups.clone(), sizing, &mut StdRng::seed_from_u64(3));
+        assert_eq!(result[0], groups[0]);
+        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![4, 3, 3]);
+    }
+
+    #[test]
+    fn test_assign_batch_preserves_order() {
+        let students: Vec<StudentId> = (1..=5).map(|i| format!("S{:03}", i)).collect();
+        let result = assign(students, GroupingConfig::default()).unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].members, vec!["S001", "S002", "S003"]);
+        assert_eq!(result[1].members, vec!["S004", "S005"]);
+    }
+
+    #[test]
+    fn test_regroup_rejects_invalid_input() {
+        let students: Vec<StudentId> = vec!["S001".into(), "S002".into(), "S001".into()];
+        assert_eq!(
+            assign(students, GroupingConfig::default()),
+            Err(GroupingError::DuplicateStudent("S001".to_string()))
+        );
+
+        let config = GroupingConfig {
+            sizing: Sizing::Groups(0),
+            ..Default::default()
+        };
+        assert_eq!(assign(vec!["S001"], config), Err(GroupingError::NoGroups));
+    }
+
+    #[test]
+    fn test_size_overrides() {
+        let members = |ids: &[&str]| Group::from_members(ids.iter().map(|id| id.to_string()).collect());
+        let groups = vec![
+            members(&["S001", "S002", "S003"]),
+            members(&["S004", "S005", "S006", "S007", "S008"]),
+            members(&["S009", "S010"]),
+        ];
+        let config = GroupingConfig {
+            size_overrides: HashMap::from([(1, 4)]),
+            ..Default::default()
+        };
+        let result = regroup(groups.clone(), config).unwrap();
+        // The second group keeps 4 members; S008 is grouped with the others
+        assert_eq!(
+            result,
+            vec![
+                members(&["S001", "S002", "S003"]),
+                members(&["S004", "S005", "S006", "S007"]),
+                members(&["S009", "S010", "S008"]),
+            ]
+        );
+
+        // A lone student left over is not put in a group of their own
+        let config = GroupingConfig {
+            size_overrides: HashMap::from([(0, 4)]),
+            ..Default::default()
+        };
+        let result = regroup(vec![members(&["S001", "S002", "S003", "S004", "S005"])], config).unwrap();
+        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![3, 2]);
+
+        let config = GroupingConfig {
+            size_overrides: HashMap::from([(0, 1)]),
+            ..Default::default()
+        };
+        assert_eq!(regroup(groups, config), Err(GroupingError::GroupTooSmall(1)));
+    }
+
+    #[test]
+    fn test_pinned_groups() {
+        let members = |ids: &[&str]| Group::from_members(ids.iter().map(|id| id.to_string()).collect());
+        let groups = vec![
+            members(&["S001", "S002", "S003", "S004", "S005"]),
+            members(&["S006"]),
+            members(&["S007", "S008"]),
+            members(&["S009", "S010"]),
+        ];
+        for mode in [Mode::Batch, Mode::Interactive] {
+            let config = GroupingConfig {
+                mode,
+                seed: Some(1),
+                pinned: HashSet::from([0, 2]),
+                ..Default::default()
+            };
+            let result = regroup(groups.clone(), config).unwrap();
+            // The pinned groups are neither split nor filled up with S006
+            assert_eq!(result.len(), 3, "{:?}", mode);
+            assert_eq!(result[0], groups[0]);
+            assert_eq!(result[2], groups[2]);
+            let mut rest = result[1].members.clone();
+            rest.sort();
+            assert_eq!(rest, vec!["S006", "S009", "S010"]);
+        }
+
+        // A lone student left over stays alone rather than joining a pinned group
+        let config = GroupingConfig {
+            pinned: HashSet::from([0]),
+            ..Default::default()
+        };
+        let result = regroup(vec![groups[0].clone(), groups[1].clone()], config).unwrap();
+        assert_eq!(result, vec![groups[0].clone(), groups[1].clone()]);
+
+        let config = GroupingConfig {
+            pinned: HashSet::from([1]),
+            ..Default::default()
+        };
+        assert_eq!(regroup(groups, config), Err(GroupingError::GroupTooSmall(1)));
+    }
+
+    #[test]
+    fn test_assign_interactive_keeps_everyone() {
+        let students: Vec<StudentId> = (1..=7).map(|i| format!("S{:03}", i)).collect();
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            ..Default::default()
+        };
+        let result = assign(students, config).unwrap();
+
+        let mut sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 2, 3]);
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_groups() {
+        let students: Vec<StudentId> = (1..=20).map(|i| format!("S{:03}", i)).collect();
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            seed: Some(42),
+            ..Default::default()
+        };
+
+        let first = assign(students.clone(), config.clone()).unwrap();
+        let second = assign(students, config).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_reroll_keeps_other_groups() {
+        let group = |ids: &[&str]| Group::from_members(ids.iter().map(|s| s.to_string()).collect());
+        let groups = vec![
+            group(&["S001", "S002", "S003"]),
+            group(&["S004", "S005", "S006"]),
+            group(&["S007", "S008", "S009"]),
+            group(&["S010", "S011"]),
+        ];
+        let result = reroll(&groups, &[0], Sizing::Triples, &mut StdRng::seed_from_u64(7));
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(result[1], groups[1]);
+        assert_eq!(result[2], groups[2]);
+        // Group A and the incomplete group D are shuffled together into 3 + 2
+        let mut pooled: Vec<Student> = [&result[0], &result[3]].iter().flat_map(|g| g.members.clone()).collect();
+        pooled.sort();
+        assert_eq!(pooled, vec!["S001", "S002", "S003", "S010", "S011"]);
+        assert_eq!(result[0].members.len(), 3);
+        assert_eq!(result[3].members.len(), 2);
+    }
+
+    #[test]
+    fn test_pairs() {
+        assert_eq!(pair_sizes(1), vec![1]);
+        assert_eq!(pair_sizes(3), vec![3]);
+        assert_eq!(pair_sizes(6), vec![2, 2, 2]);
+        assert_eq!(pair_sizes(7), vec![2, 2, 3]);
+
+        let students: Vec<StudentId> = (1..=9).map(|i| format!("S{:03}", i)).collect();
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            sizing: Sizing::Pairs,
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut sizes: Vec<usize> = assign(students, config).unwrap().iter().map(|g| g.members.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 2, 2, 3]);
+
+        // Pairs entered by hand are kept; a lone student joins the last one
+        let group = |ids: &[&str]| Group::from_members(ids.iter().map(|s| s.to_string()).collect());
+        let groups = vec![group(&["S001", "S002"]), group(&["S003", "S004"]), group(&["S005"])];
+        let result = reorganize_incomplete_sized(groups, Sizing::Pairs, &mut StdRng::seed_from_u64(1));
+        assert_eq!(result[0], group(&["S001", "S002"]));
+        assert_eq!(result[1].members.len(), 3);
+    }
+
+    #[test]
+    fn test_reorganize_in_order() {
+        let group = |ids: &[&str]| Group::from_members(ids.iter().map(|s| s.to_string()).collect());
+        let groups = vec![
+            group(&["S001", "S002"]),
+            group(&["S003", "S004", "S005"]),
+            group(&["S006"]),
+            group(&["S007", "S008"]),
+            group(&["S009"]),
+        ];
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            no_shuffle: true,
+            ..Default::default()
+        };
+        assert!(!config.uses_rng());
+        let result = regroup(groups, config).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                group(&["S003", "S004", "S005"]),
+                group(&["S001", "S002", "S006"]),
+                group(&["S007", "S008", "S009"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_split_matches_split() {
+        for sizing in [Sizing::Triples, Sizing::Pairs] {
+            for n in 0..=20 {
+                let students: Vec<Student> = (1..=n).map(|i| Student::new(format!("S{:03}", i))).collect();
+                let mut stream = StreamSplit::new(sizing).unwrap();
+                let mut groups: Vec<Group> = students.iter().filter_map(|id| stream.push(id.clone())).collect();
+                groups.extend(stream.finish());
+                assert_eq!(groups, sizing.split(students), "{:?} {}", sizing, n);
+            }
+        }
+        assert!(StreamSplit::new(Sizing::Groups(4)).is_none());
+    }
+
+    #[test]
+    fn test_append_late() {
+        let ids = |range: std::ops::RangeInclusive<usize>| -> Vec<Student> { range.map(|i| Student::new(format!("S{:03}", i))).collect() };
+        let sizes = |groups: &[Group]| groups.iter().map(|g| g.members.len()).collect::<Vec<_>>();
+        let existing = Sizing::Triples.split(ids(1..=7));
+        assert_eq!(sizes(&existing), vec![3, 2, 2]);
+
+        // The two pairs take a student each, the other three make a new group
+        let groups = append_late(existing.clone(), ids(8..=12), Sizing::Triples);
+        assert_eq!(sizes(&groups), vec![3, 3, 3, 3]);
+        assert_eq!(groups[0], existing[0]);
+        assert_eq!(groups[1].members, vec!["S004", "S005", "S008"]);
+        assert_eq!(groups[3].members, ids(10..=12));
+
+        // A lone latecomer joins a group rather than sit alone
+        let groups = append_late(Sizing::Triples.split(ids(1..=6)), ids(7..=7), Sizing::Triples);
+        assert_eq!(sizes(&groups), vec![4, 3]);
+
+        // A fixed number of groups stays fixed
+        let groups = append_late(Sizing::Groups(2).split(ids(1..=5)), ids(6..=8), Sizing::Groups(2));
+        assert_eq!(sizes(&groups), vec![4, 4]);
+        assert_eq!(append_late(Vec::new(), ids(1..=4), Sizing::Pairs).len(), 2);
+    }
+
+    #[test]
+    fn test_fixed_group_count() {
+        assert_eq!(even_sizes(10, 4), vec![3, 3, 2, 2]);
+        assert_eq!(even_sizes(9, 3), vec![3, 3, 3]);
+        // No more groups than keeps everyone with a partner
+        assert_eq!(even_sizes(5, 4), vec![3, 2]);
+        assert_eq!(even_sizes(1, 2), vec![1]);
+        assert_eq!(even_sizes(0, 2), Vec::<usize>::new());
+        assert_eq!(Sizing::Groups(20).shortfall(10), Some(Shortfall::Groups { requested: 20, made: 5 }));
+        assert_eq!(Sizing::Groups(5).shortfall(10), None);
+        assert_eq!(Sizing::Groups(2).shortfall(0), None);
+
+        // Groups entered by hand are pooled, since none of them is ever full
+        let students: Vec<StudentId> = (1..=11).map(|i| format!("S{:03}", i)).collect();
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            sizing: Sizing::Groups(2),
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut sizes: Vec<usize> = assign(students, config).unwrap().iter().map(|g| g.members.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![5, 6]);
+    }
+
+    #[test]
+    fn test_stratify() {
+        let names = vec!["section".to_string()];
+        let students: Vec<Student> = (1..=12)
+            .map(|i| {
+                let section = if i <= 6 { "A" } else { "B" };
+                Student::parse_line(&format!("S{:03},{}", i, section), &names).unwrap()
+            })
+            .collect();
+        let sections = |group: &Group| {
+            let mut sections: Vec<&str> = group.members.iter().filter_map(|s| s.attribute("section")).collect();
+            sections.sort();
+            sections.dedup();
+            sections.len()
+        };
+
+        for within_strata in [false, true] {
+            let config = GroupingConfig {
+                seed: Some(3),
+                stratify: Some("section".to_string()),
+                within_strata,
+                ..Default::default()
+            };
+            let result = regroup(vec![Group::from_members(students.clone())], config).unwrap();
+            assert_eq!(result.len(), 4);
+            for group in &result {
+                assert_eq!(sections(group), if within_strata { 1 } else { 2 }, "{:?}", result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        assert!(GroupingConfig::default().settings().is_empty());
+        let config = GroupingConfig {
+            sizing: Sizing::Range { min: 3, max: 5 },
+            seed: Some(1),
+            balance: vec!["gender".to_string(), "skill".to_string()],
+            within_strata: true,
+            optimize_iterations: Some(500),
+            optimize_time: Some(Duration::from_millis(500)),
+            constraints: Constraints::parse("together: S001, S002").unwrap(),
+            ..Default::default()
+        };
+        let settings = Value::Object(config.settings()).to_string();
+        assert_eq!(
+            settings,
+            concat!(
+                r#"{"min":3,"max":5,"balance":["gender","skill"],"within_strata":true,"#,
+                r#""constraints":"together: S001,S002\n","optimize_repeats":true,"iterations":500,"#,
+                r#""optimize":true,"time_budget":"500ms"}"#
+            )
+        );
+        assert_eq!(Mode::from_name(Mode::Interactive.name()), Some(Mode::Interactive));
+        assert_eq!(Mode::from_name("other"), None);
+    }
+}
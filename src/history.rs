@@ -0,0 +1,591 @@
+//! Pairing history: past groupings stored as JSON Lines, used to avoid putting
+//! the same students together again.
+
+use crate::constraints::SwapCheck;
+use crate::db::Session;
+use crate::i18n::{message, Msg};
+use crate::group::{group_index_to_letter, group_letter_to_index, Group, Student, StudentId};
+use crate::grouping::{self, GroupingConfig, Mode};
+use crate::json::Value;
+use crate::output;
+use crate::roster;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// How many candidate groupings are compared when avoiding repeats.
+const CANDIDATES: usize = 200;
+
+/// Students placed across all candidates; fewer candidates are compared for
+/// very large classes so that the run time stays roughly linear.
+const CANDIDATE_BUDGET: usize = 200_000;
+
+/// Penalty for an unsatisfied constraint, so that constraints always win over repeats.
+const CONSTRAINT_PENALTY: usize = 1_000_000;
+
+/// Swaps tried by `--optimize-repeats` when `--iterations` is not given.
+pub const DEFAULT_ITERATIONS: usize = 10_000;
+
+/// How often each pair of students has been in the same group before, and
+/// how often each student has been a group leader.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    pair_counts: HashMap<(StudentId, StudentId), usize>,
+    leader_counts: HashMap<StudentId, usize>,
+    sessions: usize,
+}
+
+impl History {
+    /// Load the history file. A missing file is an empty history.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Ok(History::from_sessions(&read_sessions(path)?))
+    }
+
+    /// The history made of the pairings and leaders of `sessions`.
+    pub fn from_sessions(sessions: &[Session]) -> Self {
+        let mut history = History::default();
+        for session in sessions {
+            history.record(&session.groups);
+            history.record_leaders(&session.leaders);
+        }
+        history
+    }
+
+    /// Add one session's groups to the history.
+    pub fn record(&mut self, groups: &[Group]) {
+        for group in groups {
+            for (i, a) in group.members.iter().enumerate() {
+                for b in &group.members[i + 1..] {
+                    *self.pair_counts.entry(pair_key(&a.id, &b.id)).or_insert(0) += 1;
+                }
+            }
+        }
+        self.sessions += 1;
+    }
+
+    /// Add the leaders of one session to the history.
+    pub fn record_leaders(&mut self, leaders: &[StudentId]) {
+        for leader in leaders {
+            *self.leader_counts.entry(leader.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// How many times `id` was a group leader.
+    pub fn leader_count(&self, id: &str) -> usize {
+        self.leader_counts.get(id).copied().unwrap_or(0)
+    }
+
+    /// Number of sessions in the history.
+    pub fn sessions(&self) -> usize {
+        self.sessions
+    }
+
+    /// Number of different pairs that have been in the same group at least once.
+    pub fn distinct_pairs(&self) -> usize {
+        self.pair_counts.len()
+    }
+
+    /// How many times `a` and `b` were in the same group.
+    pub fn pair_count(&self, a: &str, b: &str) -> usize {
+        self.pair_counts
+            .get(&pair_key(a, b))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Total number of times pairs in `groups` were already grouped together before.
+    pub fn repeat_count(&self, groups: &[Group]) -> usize {
+        groups
+            .iter()
+            .map(|group| {
+                let mut count = 0;
+                for (i, a) in group.members.iter().enumerate() {
+                    for b in &group.members[i + 1..] {
+                        count += self.pair_count(&a.id, &b.id);
+                    }
+                }
+                count
+            })
+            .sum()
+    }
+
+    /// Past meetings between `student` and the members of `group`, ignoring `leaving`.
+    pub fn repeats_with(&self, student: &str, group: &Group, leaving: &str) -> usize {
+        group
+            .ids()
+            .filter(|m| m.as_str() != leaving && m.as_str() != student)
+            .map(|m| self.pair_count(student, m))
+            .sum()
+    }
+}
+
+/// Order a pair so that `(a, b)` and `(b, a)` share a key.
+fn pair_key(a: &str, b: &str) -> (StudentId, StudentId) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// Read every run in the history file, oldest first, numbered from 1. A
+/// missing file has no runs.
+///
+/// Lines with `points` are not runs but scores given with `score`, added to
+/// the group of the run they name.
+pub fn read_sessions(path: &Path) -> Result<Vec<Session>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(message(Msg::CannotOpen, &[&path.display(), &e])),
+    };
+
+    let mut sessions: Vec<Session> = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at_line = |error: &dyn std::fmt::Display| message(Msg::FileAtLine, &[&path.display(), &(line_no + 1), error]);
+        let record = Value::parse(line).map_err(|e| at_line(&e))?;
+        if let Some(points) = record.get("points") {
+            let total = points
+                .as_i64()
+                .zip(record.get("session").and_then(Value::as_u64))
+                .zip(record.get("group").and_then(Value::as_str).and_then(group_letter_to_index))
+                .and_then(|((points, session), group)| {
+                    let total = sessions.get_mut((session as usize).checked_sub(1)?)?.scores.get_mut(group)?;
+                    Some((total, points))
+                });
+            let (total, points) =
+                total.ok_or_else(|| at_line(&message(Msg::HistoryBadScore, &[])))?;
+            *total += points;
+            continue;
+        }
+        let groups = record
+            .get("groups")
+            .and_then(Value::as_array)
+            .ok_or_else(|| at_line(&message(Msg::MissingKey, &[&"groups"])))?;
+        let groups: Vec<Group> = groups
+            .iter()
+            .map(|g| {
+                let members = g.as_array().unwrap_or_default();
+                Group::from_members(members.iter().filter_map(Value::as_str).map(String::from).collect())
+            })
+            .collect();
+        // Leaders are only present for runs with --assign-leader
+        let leaders: Vec<StudentId> = record
+            .get("leaders")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect();
+        sessions.push(Session {
+            id: sessions.len() as u64 + 1,
+            timestamp: record.get("timestamp").and_then(Value::as_str).unwrap_or_default().to_string(),
+            seed: record.get("seed").and_then(Value::as_u64),
+            scores: vec![0; groups.len()],
+            groups,
+            leaders,
+        });
+    }
+    Ok(sessions)
+}
+
+/// Read the results exported with `--format json` or `--format csv` into
+/// `dir` as runs, in the order of their file names, numbered from 1. Other
+/// files are skipped.
+pub fn read_exports(dir: &Path) -> Result<Vec<Session>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| message(Msg::CannotOpen, &[&dir.display(), &e]))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    let mut sessions = Vec::new();
+    for path in paths {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+        if !matches!(extension.as_deref(), Some("json" | "csv")) {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|e| message(Msg::CannotOpen, &[&path.display(), &e]))?;
+        let (groups, leaders, timestamp, seed) = match extension.as_deref() {
+            Some("json") => {
+                let value = Value::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let (groups, _, metadata) = output::from_json(&value).map_err(|e| format!("{}: {}", path.display(), e))?;
+                (groups, metadata.leaders, metadata.timestamp, metadata.seed)
+            }
+            _ => {
+                let (groups, leaders) = groups_from_csv(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+                (groups, leaders, String::new(), None)
+            }
+        };
+        sessions.push(Session {
+            id: sessions.len() as u64 + 1,
+            timestamp,
+            seed,
+            scores: vec![0; groups.len()],
+            groups,
+            leaders,
+        });
+    }
+    Ok(sessions)
+}
+
+/// The groups and leaders of a result written with `--format csv`: one row
+/// per student under a header with `student_id`, `group` and optionally
+/// `leader` columns. Groups are in the order they first appear.
+fn groups_from_csv(text: &str) -> Result<(Vec<Group>, Vec<StudentId>), String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = roster::parse_csv_line(lines.next().unwrap_or_default());
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+    let (id, group) = column("student_id")
+        .zip(column("group"))
+        .ok_or_else(|| message(Msg::HistoryCsvColumns, &[]))?;
+    let leader = column("leader");
+
+    let mut labels: Vec<String> = Vec::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut leaders = Vec::new();
+    for (row_no, line) in lines.enumerate() {
+        let row = roster::parse_csv_line(line);
+        let (Some(member), Some(label)) = (row.get(id), row.get(group)) else {
+            return Err(message(Msg::AtLine, &[&(row_no + 2), &message(Msg::MissingColumns, &[])]));
+        };
+        let index = match labels.iter().position(|l| l == label) {
+            Some(index) => index,
+            None => {
+                labels.push(label.clone());
+                groups.push(Group::new());
+                labels.len() - 1
+            }
+        };
+        groups[index].members.push(Student::new(member.trim()));
+        if leader.and_then(|k| row.get(k)).is_some_and(|value| value == "true") {
+            leaders.push(member.trim().to_string());
+        }
+    }
+    Ok((groups, leaders))
+}
+
+/// The default history file, `~/.grouping-tool/history.jsonl`.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".grouping-tool").join("history.jsonl"))
+}
+
+/// Append one run's groups and leaders (if any) to the history file,
+/// creating it if needed.
+pub fn append(path: &Path, groups: &[Group], leaders: &[StudentId], timestamp: &str, seed: Option<u64>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut pairs = vec![
+        ("timestamp", Value::from(timestamp)),
+        ("seed", Value::from(seed)),
+        (
+            "groups",
+            Value::Array(
+                groups
+                    .iter()
+                    .map(|g| Value::Array(g.ids().map(|m| Value::from(m.as_str())).collect()))
+                    .collect(),
+            ),
+        ),
+    ];
+    if !leaders.is_empty() {
+        pairs.push((
+            "leaders",
+            Value::Array(leaders.iter().map(|l| Value::from(l.as_str())).collect()),
+        ));
+    }
+    let record = Value::object(pairs);
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", record)
+}
+
+/// Append `points` for the group at index `group` of run `session` to the
+/// history file.
+pub fn append_score(path: &Path, session: u64, group: usize, points: i64, timestamp: &str) -> io::Result<()> {
+    let record = Value::object(vec![
+        ("timestamp", Value::from(timestamp)),
+        ("session", Value::from(session)),
+        ("group", Value::from(group_index_to_letter(group))),
+        ("points", Value::from(points)),
+    ]);
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    writeln!(file, "{}", record)
+}
+
+/// Reorganize groups like [`grouping::regroup`], preferring groupings that
+/// repeat as few past pairings as possible.
+///
+/// Many candidate groupings are generated with different seeds and the one
+/// with the fewest repeated pairs is kept (unsatisfied constraints count far
+/// more than repeats, and broken soft constraints as many as their weight).
+/// In plain batch mode the input order is shuffled, since otherwise every
+/// candidate would be the same.
+///
+/// With `config.optimize_iterations`, the best candidate is then improved by
+/// [`optimize_repeats`]. Full groups entered by hand are left alone.
+pub fn reorganize_avoiding_repeats(groups: Vec<Group>, config: &GroupingConfig, history: &History) -> Vec<Group> {
+    let mut rng = config.rng();
+    let base = GroupingConfig {
+        history: None,
+        ..config.clone()
+    };
+    let shuffle_input = config.mode == Mode::Batch && !base.uses_rng();
+
+    let kept: Vec<Group> = match config.mode {
+        Mode::Interactive => groups.iter().filter(|g| config.sizing.is_full(g)).cloned().collect(),
+        Mode::Batch => Vec::new(),
+    };
+
+    let mut best: Option<(usize, Vec<Group>)> = None;
+    let students: usize = groups.iter().map(|g| g.members.len()).sum();
+    let candidates = (CANDIDATE_BUDGET / students.max(1)).clamp(1, CANDIDATES);
+    for _ in 0..candidates {
+        let candidate_config = GroupingConfig {
+            seed: Some(rng.gen()),
+            ..base.clone()
+        };
+        let mut input = groups.clone();
+        if shuffle_input {
+            let mut members: Vec<Student> = input.into_iter().flat_map(|g| g.members).collect();
+            members.shuffle(&mut rng);
+            input = vec![Group::from_members(members)];
+        }
+
+        let candidate = grouping::reorganize(input, candidate_config);
+        let score = config.constraints.unsatisfied(&candidate).len() * CONSTRAINT_PENALTY
+            + config.constraints.penalty(&candidate) as usize
+            + history.repeat_count(&candidate);
+        if best.as_ref().map_or(true, |(best_score, _)| score < *best_score) {
+            let done = score == 0;
+            best = Some((score, candidate));
+            if done {
+                break;
+            }
+        }
+    }
+    let mut result = best.map(|(_, groups)| groups).unwrap_or_default();
+    if let Some(iterations) = config.optimize_iterations {
+        optimize_repeats(&mut result, &kept, config, history, iterations, &mut rng);
+    }
+    result
+}
+
+/// Lower the number of repeated pairings in `groups` by hill climbing: for
+/// `iterations` rounds, two students of different groups are picked at random
+/// and swapped unless that makes the grouping worse. Swaps that keep the score
+/// are accepted too, so that the search can move across plateaus.
+///
+/// Groups equal to one in `kept` are never changed. Only students with the
+/// same values of the balanced attributes (and of `spread_weights`) are
+/// swapped, so an attribute spread stays as it was, and a swap never breaks
+/// more constraints than it satisfies.
+pub fn optimize_repeats(
+    groups: &mut [Group],
+    kept: &[Group],
+    config: &GroupingConfig,
+    history: &History,
+    iterations: usize,
+    rng: &mut StdRng,
+) {
+    let kept: HashSet<&[Student]> = kept.iter().map(|g| g.members.as_slice()).collect();
+    let movable: Vec<usize> = (0..groups.len())
+        .filter(|&i| !kept.contains(groups[i].members.as_slice()))
+        .collect();
+    if movable.len() < 2 {
+        return;
+    }
+    let mut keys = config.balance_keys();
+    keys.extend(config.spread_weights.clone());
+    let profile = |student: &Student| -> Vec<Option<String>> {
+        keys.iter()
+            .map(|key| student.attribute(key).map(str::to_lowercase))
+            .collect()
+    };
+
+    let mut violations = config.constraints.unsatisfied(groups).len();
+    let mut check = SwapCheck::new(&config.constraints, groups);
+    let mut repeats = history.repeat_count(groups);
+    for _ in 0..iterations {
+        if repeats == 0 && violations == 0 {
+            break;
+        }
+        let picked: Vec<&usize> = movable.choose_multiple(rng, 2).collect();
+        let (i, j) = (*picked[0].min(picked[1]), *picked[0].max(picked[1]));
+        if groups[i].members.is_empty() || groups[j].members.is_empty() {
+            continue;
+        }
+        let a = rng.gen_range(0..groups[i].members.len());
+        let b = rng.gen_range(0..groups[j].members.len());
+        let (x, y) = (groups[i].members[a].clone(), groups[j].members[b].clone());
+        if profile(&x) != profile(&y) {
+            continue;
+        }
+
+        let (xid, yid) = (x.id.as_str(), y.id.as_str());
+        let before = history.repeats_with(xid, &groups[i], xid) + history.repeats_with(yid, &groups[j], yid);
+        let after = history.repeats_with(yid, &groups[i], xid) + history.repeats_with(xid, &groups[j], yid);
+        if after > before {
+            continue;
+        }
+        let around = check.violations_around(xid, yid);
+        check.swapped(xid, j, yid, i);
+        let swapped_violations = violations - around + check.violations_around(xid, yid);
+        if swapped_violations > violations {
+            check.swapped(xid, i, yid, j);
+        } else {
+            groups[i].members[a] = y;
+            groups[j].members[b] = x;
+            violations = swapped_violations;
+            repeats = repeats + after - before;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::student::Student;
+    use rand::SeedableRng;
+
+    fn group(ids: &[&str]) -> Group {
+        Group::from_members(ids.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_record_and_count_pairs() {
+        let mut history = History::default();
+        history.record(&[group(&["A", "B", "C"]), group(&["D", "E"])]);
+        history.record(&[group(&["B", "A"])]);
+
+        assert_eq!(history.sessions(), 2);
+        assert_eq!(history.pair_count("A", "B"), 2);
+        assert_eq!(history.pair_count("C", "A"), 1);
+        assert_eq!(history.pair_count("A", "D"), 0);
+        assert_eq!(history.repeat_count(&[group(&["A", "B", "D"])]), 2);
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let dir = std::env::temp_dir().join(format!("grouping-tool-history-{}", std::process::id()));
+        let path = dir.join("history.jsonl");
+        let _ = fs::remove_file(&path);
+
+        append(&path, &[group(&["S001", "S002", "S003"])], &[], "2025-04-01T09:00:00Z", Some(1)).unwrap();
+        append(&path, &[group(&["S001", "S002"])], &["S002".to_string()], "2025-04-08T09:00:00Z", None).unwrap();
+        let history = History::load(&path).unwrap();
+
+        assert_eq!(history.sessions(), 2);
+        assert_eq!(history.pair_count("S001", "S002"), 2);
+        assert_eq!(history.pair_count("S002", "S003"), 1);
+        assert_eq!(history.leader_count("S002"), 1);
+        assert_eq!(history.leader_count("S001"), 0);
+
+        let sessions = read_sessions(&path).unwrap();
+        assert_eq!(sessions[1].id, 2);
+        assert_eq!(sessions[1].timestamp, "2025-04-08T09:00:00Z");
+        assert_eq!((sessions[0].seed, sessions[1].seed), (Some(1), None));
+        assert_eq!(sessions[1].leaders, vec!["S002"]);
+
+        // Scores are added to the run they name, and do not count as runs
+        append_score(&path, 1, 0, 10, "2025-04-01T10:00:00Z").unwrap();
+        append_score(&path, 1, 0, 5, "2025-04-01T10:05:00Z").unwrap();
+        let sessions = read_sessions(&path).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].scores.clone(), sessions[1].scores.clone()), (vec![15], vec![0]));
+        assert_eq!(History::load(&path).unwrap().sessions(), 2);
+        append_score(&path, 3, 0, 1, "2025-04-01T10:05:00Z").unwrap();
+        assert!(read_sessions(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        // A missing file is an empty history
+        assert_eq!(History::load(&path).unwrap(), History::default());
+    }
+
+    #[test]
+    fn test_read_exports() {
+        let dir = std::env::temp_dir().join(format!("grouping-tool-exports-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("week1.json"),
+            r#"{"timestamp": "2025-04-01T09:00:00Z", "seed": 3, "groups": [{"label": "A", "members": ["S001", "S002"], "leader": "S002"}, {"label": "B", "members": ["S003"]}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("week2.csv"), "student_id,group\nS001,A\nS003,A\nS002,B\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a result").unwrap();
+
+        let sessions = read_exports(&dir).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].seed, sessions[0].leaders.clone()), (Some(3), vec!["S002".to_string()]));
+        assert_eq!(sessions[1].groups, vec![group(&["S001", "S003"]), group(&["S002"])]);
+        let history = History::from_sessions(&sessions);
+        assert_eq!(history.pair_count("S001", "S002"), 1);
+        assert_eq!(history.pair_count("S001", "S003"), 1);
+
+        fs::write(dir.join("week3.csv"), "id,team\nS001,A\n").unwrap();
+        assert!(read_exports(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(read_exports(&dir).is_err());
+    }
+
+    #[test]
+    fn test_avoids_previous_groups() {
+        let ids: Vec<Student> = (1..=9).map(|i| Student::new(format!("S{:03}", i))).collect();
+        let previous = grouping::split_into_small_groups(ids.clone());
+        let mut history = History::default();
+        history.record(&previous);
+
+        let config = GroupingConfig {
+            mode: Mode::Batch,
+            seed: Some(5),
+            ..Default::default()
+        };
+        let result = reorganize_avoiding_repeats(vec![Group::from_members(ids)], &config, &history);
+
+        assert_eq!(history.repeat_count(&result), 0);
+        assert_eq!(result.iter().map(|g| g.members.len()).sum::<usize>(), 9);
+    }
+
+    #[test]
+    fn test_optimize_repeats() {
+        // S001-S006 are "x" and S007-S012 "y"; swaps must keep one of each per group
+        let names = vec!["skill".to_string()];
+        let students: Vec<Student> = (1..=12)
+            .map(|i| {
+                let skill = if i <= 6 { "x" } else { "y" };
+                Student::parse_line(&format!("S{:03},{}", i, skill), &names).unwrap()
+            })
+            .collect();
+        let config = GroupingConfig {
+            balance: names.clone(),
+            ..Default::default()
+        };
+
+        let pair = |a: usize, b: usize| Group::from_members(vec![students[a].clone(), students[b].clone()]);
+        let mut groups: Vec<Group> = (0..6).map(|i| pair(i, i + 6)).collect();
+        let mut history = History::default();
+        history.record(&groups);
+        let kept = vec![pair(4, 10)];
+        let mut rng = StdRng::seed_from_u64(1);
+        optimize_repeats(&mut groups, &kept, &config, &history, 1000, &mut rng);
+
+        // Only the kept pair is still repeated
+        assert_eq!(history.repeat_count(&groups), 1);
+        assert!(groups.contains(&pair(4, 10)));
+        for g in &groups {
+            let skills: Vec<&str> = g.members.iter().map(|s| s.attribute("skill").unwrap()).collect();
+            assert_eq!(skills, vec!["x", "y"], "{:?}", groups);
+        }
+    }
+}
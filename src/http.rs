@@ -0,0 +1,140 @@
+//! Minimal HTTPS client that runs `curl`, which ships with macOS, Windows 10
+//! and later, and most Linux distributions.
+//!
+//! All options are passed to curl on stdin as a config file, so tokens and
+//! webhook URLs never appear in the process list.
+
+use crate::i18n::{message, Msg};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Status code and body of an HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    /// Whether the status is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Send a request and wait for the response.
+pub fn send(method: &str, url: &str, headers: &[(&str, &str)], body: Option<&str>) -> Result<Response, String> {
+    let child = Command::new("curl")
+        .args(["--silent", "--show-error", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(message(Msg::ProgramMissing, &[&"curl"]))
+        }
+        Err(e) => return Err(message(Msg::ProgramFailed, &[&"curl", &e])),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(curl_config(method, url, headers, body).as_bytes())
+            .map_err(|e| message(Msg::CannotWrite, &[&"curl", &e]))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| message(Msg::ProgramFailed, &[&"curl", &e]))?;
+    if !output.status.success() {
+        return Err(message(Msg::RequestFailed, &[&String::from_utf8_lossy(&output.stderr).trim()]));
+    }
+
+    // The status code is written after the body (see `write-out` below)
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let status = status
+        .trim()
+        .parse()
+        .map_err(|_| format!("{}: {}", message(Msg::ProgramOutput, &[&"curl"]), status))?;
+    Ok(Response {
+        status,
+        body: body.to_string(),
+    })
+}
+
+/// Build the curl config file describing the request.
+fn curl_config(method: &str, url: &str, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let mut config = String::new();
+    config.push_str(&format!("url = {}\n", config_quote(url)));
+    config.push_str(&format!("request = {}\n", config_quote(method)));
+    for (name, value) in headers {
+        config.push_str(&format!("header = {}\n", config_quote(&format!("{}: {}", name, value))));
+    }
+    if let Some(body) = body {
+        // data-raw, unlike data-binary, never treats a leading `@` as a file name
+        config.push_str(&format!("data-raw = {}\n", config_quote(body)));
+    }
+    config.push_str("write-out = \"\\n%{http_code}\"\n");
+    config
+}
+
+/// Quote a value for a curl config file.
+fn config_quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Percent-encode `value` for use in a URL path segment or query parameter.
+pub fn percent_encode(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curl_config_quotes_values() {
+        let config = curl_config(
+            "POST",
+            "https://example.com/x",
+            &[("Content-Type", "application/json")],
+            Some("{\"text\":\"a\\nb\"}"),
+        );
+        assert_eq!(
+            config,
+            concat!(
+                "url = \"https://example.com/x\"\n",
+                "request = \"POST\"\n",
+                "header = \"Content-Type: application/json\"\n",
+                "data-raw = \"{\\\"text\\\":\\\"a\\\\nb\\\"}\"\n",
+                "write-out = \"\\n%{http_code}\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("結果!A1"), "%E7%B5%90%E6%9E%9C%21A1");
+        assert_eq!(percent_encode("A:Z"), "A%3AZ");
+    }
+}
@@ -0,0 +1,1722 @@
+//! Message catalogs for the user interface and the rendered results.
+//!
+//! Every message has a Japanese and an English text. `{}` placeholders are
+//! filled in order by [`Lang::format`], so both texts take the same arguments
+//! in the same order.
+//!
+//! The results take their language as an argument. Error messages of the
+//! library use the language set once for the whole process with
+//! [`set_lang`], so that the functions do not need to pass it around.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+/// Use `lang` for the messages of [`message`] and of the library errors.
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+/// The text of a message in the language set with [`set_lang`], with its
+/// `{}` placeholders filled with `args`.
+pub fn message(msg: Msg, args: &[&dyn fmt::Display]) -> String {
+    Lang::current().format(msg, args)
+}
+
+/// Language of messages and results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    Ja = 0,
+    En = 1,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ja" | "japanese" => Ok(Lang::Ja),
+            "en" | "english" => Ok(Lang::En),
+            _ => Err(message(Msg::OneOf, &[&"--lang", &Lang::current().list(&["ja", "en"]), &s])),
+        }
+    }
+}
+
+impl Lang {
+    /// The language of a POSIX locale such as `ja_JP.UTF-8` or `en_US.UTF-8`.
+    /// `C`, `POSIX` and empty locales say nothing about the language.
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let locale = locale.trim();
+        if locale.is_empty() || locale == "C" || locale.starts_with("C.") || locale == "POSIX" {
+            None
+        } else if locale.to_lowercase().starts_with("ja") {
+            Some(Lang::Ja)
+        } else {
+            // Anyone who set another language is better served by English
+            Some(Lang::En)
+        }
+    }
+
+    /// The language from `LC_ALL`, `LC_MESSAGES` or `LANG`, in that order,
+    /// falling back to Japanese.
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find_map(|value| Lang::from_locale(&value))
+            .unwrap_or_default()
+    }
+
+    /// The language set with [`set_lang`], Japanese until then.
+    pub fn current() -> Self {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => Lang::En,
+            _ => Lang::Ja,
+        }
+    }
+
+    /// `items` as a list: `a、b、c` in Japanese and `a, b, c` in English.
+    pub fn list(self, items: &[&str]) -> String {
+        match self {
+            Lang::Ja => items.join("、"),
+            Lang::En => items.join(", "),
+        }
+    }
+
+    /// The `lang` attribute for HTML documents.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::Ja => "ja",
+            Lang::En => "en",
+        }
+    }
+
+    /// The text of a message without placeholders.
+    pub fn text(self, msg: Msg) -> &'static str {
+        let (ja, en) = msg.texts();
+        match self {
+            Lang::Ja => ja,
+            Lang::En => en,
+        }
+    }
+
+    /// The text of a message with its `{}` placeholders filled with `args`.
+    pub fn format(self, msg: Msg, args: &[&dyn fmt::Display]) -> String {
+        let mut out = String::new();
+        let mut args = args.iter();
+        let mut parts = self.text(msg).split("{}");
+        if let Some(first) = parts.next() {
+            out.push_str(first);
+        }
+        for part in parts {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+}
+
+/// A message shown to the user or written into a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    // Line-based entry
+    EntryIntro,
+    EntryIntroLater,
+    EntryHelpFinish,
+    EntryHelpQuit,
+    EntryHelpDelete,
+    EntryHelpMove,
+    EntryHelpSwap,
+    EntryHelpAdd,
+    EntryHelpPin,
+    EntryHelpUndo,
+    EntryGroupHeading,
+    Added,
+    GroupCompleted,
+    GroupSaved,
+    DeletedFromCurrent,
+    DeletedFromGroup,
+    NotFound,
+    DidYouMean,
+    DeleteSuggestion,
+    InvalidId,
+    InvalidIdSkipped,
+    InvalidLine,
+    TimedOut,
+    InvalidLineSkipped,
+    Duplicate,
+    DuplicateSkipped,
+    SizeHeaderSkipped,
+    Pinned,
+    Unpinned,
+    PinIgnored,
+    MoveUsage,
+    Moved,
+    AddUsage,
+    AddedTo,
+    SwapUsage,
+    Swapped,
+    SameGroupSwap,
+    NoSuchGroup,
+    AlreadyInGroup,
+    GroupFull,
+    UndoneAdd,
+    UndoneDelete,
+    UndoneMove,
+    UndoneSwap,
+    UndoneComplete,
+    NothingToUndo,
+    NowEntering,
+    Interrupted,
+    NoInput,
+    TuiUnavailable,
+    SessionResumed,
+    WatchStarted,
+    WatchReloaded,
+    WatchStopped,
+    SessionSaveFailed,
+    SessionLogFailed,
+
+    // Full-screen entry
+    TuiTitle,
+    TuiTitleNote,
+    TuiTitleNoteLater,
+    TuiEntering,
+    TuiInputHelp,
+    TuiListHelp,
+    TuiPrompt,
+    TuiEnterId,
+    TuiGroupSaved,
+    TuiInvalidId,
+    TuiDuplicate,
+    TuiAdded,
+    TuiGroupCompleted,
+    TuiDeleted,
+    TuiGroupFull,
+    TuiMoved,
+    TuiSwapUsage,
+    TuiSwapped,
+    TuiSameGroupSwap,
+    TuiNotFound,
+    TuiAddUsage,
+    TuiAddedTo,
+    TuiNoSuchGroup,
+    TuiUndone,
+    TuiNothingToUndo,
+
+    // Results
+    ResultTitle,
+    GroupLine,
+    GroupHeading,
+    GroupTitle,
+    Counts,
+    Members,
+    TotalGroups,
+    ContentHash,
+    Summary,
+    Leader,
+    ScheduleTitle,
+    PerStudentTitle,
+    SessionHeading,
+    SeedFooter,
+    BracketTitle,
+    BracketSingle,
+    BracketDouble,
+    BracketRound,
+    BracketFinal,
+    BracketWinnersRound,
+    BracketLosersRound,
+    BracketGrandFinal,
+    BracketMatch,
+    BracketWinnerOf,
+    BracketLoserOf,
+    BracketTotal,
+    SeatingTitle,
+    PresentationTitle,
+    PresentationSlot,
+    PresentationSlotTimed,
+    PresentationTotal,
+    LeaderboardSession,
+    LeaderboardStudents,
+    LeaderboardLine,
+    SeatingFront,
+
+    // After the result
+    Error,
+    UsageHint,
+    NeedsTerminal,
+    StreamNeedsPipe,
+    ConfigInvalid,
+    Listening,
+    ListenFailed,
+    BalanceIgnored,
+    DbRosterFailed,
+    SavedTo,
+    Copied,
+    CopyFailed,
+    HtmlSaved,
+    PdfSaved,
+    XlsxSaved,
+    XlsxAssignments,
+    XlsxGroups,
+    SeatingSaved,
+    ScoreRecorded,
+    AppendAlreadyPlaced,
+    AppendJoined,
+    MergeRelabeled,
+    MergeGroupSize,
+    MergeSizesDiffer,
+    DiffTitle,
+    DiffNone,
+    DiffMoved,
+    DiffAdded,
+    DiffRemoved,
+    DiffBroken,
+    DiffCreated,
+    SeatingFailed,
+    SlackPosted,
+    SlackFailed,
+    DiscordPosted,
+    DiscordFailed,
+    TeamsPosted,
+    TeamsFailed,
+    ReadFailed,
+    WriteFailed,
+    Unsatisfied,
+    FewerGroups,
+    OverMax,
+    UnderMin,
+    GroupingFailed,
+    ConstraintConflict,
+    UnsatisfiedInSession,
+    Seed,
+    SeedReplay,
+    ReplayMatches,
+    ReplayDiffers,
+    ReplayNotRecorded,
+    LogReplayed,
+    LogMatches,
+    LogDiffers,
+    LogWithoutResult,
+    PairSummary,
+    RepeatSummary,
+    SoftPenalty,
+    SoftIgnored,
+    PreferencesHonored,
+    WishesGranted,
+    ExportsRead,
+    PlanWritten,
+    CalendarEvent,
+    CalendarWritten,
+    SheetWritten,
+    SheetWriteFailed,
+    DbRecorded,
+    DbRecordFailed,
+    HistorySaveFailed,
+    Archived,
+    ArchiveFailed,
+    KeyCreated,
+    Signed,
+    SignatureValid,
+    SignatureInvalid,
+    RerollPrompt,
+    RerollUsage,
+    RerollAlone,
+    Rerolled,
+    ConfirmPrompt,
+    ConfirmUsage,
+    ConfirmSameOrder,
+    ConfirmNoEdit,
+    WeightInvalid,
+    SkillInvalid,
+    StatsTitle,
+    StatsCounts,
+    StatsSizes,
+    StatsSize,
+    StatsRepeats,
+    StatsNoHistory,
+    StatsConstraints,
+    StatsNoConstraints,
+    StatsFitness,
+    StatsEdits,
+
+    // Command-line arguments
+    UnknownOption,
+    NeedsValue,
+    NeedsInteger,
+    NeedsIntegerRange,
+    NeedsReplaySeed,
+    NeedsDuration,
+    NeedsSeconds,
+    NeedsGroupSize,
+    NeedsAttribute,
+    OneOf,
+    FromMoodle,
+    PointsNotInteger,
+    InvalidDate,
+    OptionConflict,
+    OptionRequires,
+    OptionTogether,
+    NotWith,
+    OnlyForGrouping,
+    OnlyForGroupingAnd,
+    OnlyForSubcommand,
+    OnlyForEntry,
+    NeedsSessions,
+    IcsWithStartDate,
+    WeeklyNeedsIcs,
+    HistoryUnused,
+    MixedSkillNeedsSkill,
+    SimilarNeedsGroupBy,
+    PreferencesNeedsFile,
+    MinOverMax,
+    SheetRangeNeedsSheet,
+    NeedsDb,
+    ImportNeedsRoster,
+    ReplayWithSeed,
+    FromNeedsRoster,
+    MergeNeedsFiles,
+    DiffNeedsFiles,
+    VerifyNeedsFile,
+    VerifyNeedsKey,
+    ReplayNeedsLog,
+    SignNeedsFile,
+    ScoreNeedsPoints,
+    TextOrJson,
+
+    // Errors
+    CannotAddToFull,
+    DuplicateStudent,
+    NoGroups,
+    GroupTooSmall,
+    MissingId,
+    ExtraFields,
+    AtLine,
+    TemplateLine,
+    TemplateUnclosedTag,
+    TemplateUnknownBlock,
+    TemplateNoName,
+    TemplateMismatched,
+    TemplateUnopened,
+    TemplateElse,
+    TemplateUnclosed,
+    ConstraintForm,
+    ConstraintWeight,
+    ConstraintFewIds,
+    ConstraintUnknown,
+    ConstraintSameIds,
+    ConstraintPair,
+    PatternUnmatchedParen,
+    PatternUnclosedParen,
+    PatternNothingToRepeat,
+    PatternIncomplete,
+    PatternTrailingBackslash,
+    PatternUnclosedClass,
+    PatternReversedRange,
+    PatternUnclosedBrace,
+    PatternBadCount,
+    PatternRepeatedAnchor,
+    PatternTooLong,
+    ColumnFromOne,
+    ColumnEmpty,
+    ColumnNotFound,
+    NoIdColumn,
+    GroupNameRepeated,
+    NoGroupNames,
+    ResultNoGroups,
+    ResultNoMembers,
+    ResultMembers,
+    CannotOpen,
+    CannotWrite,
+    ApiNotObject,
+    ApiGroupsArray,
+    ApiGroupMembers,
+    ApiIdString,
+    ApiNoStudents,
+    ApiEmpty,
+    ApiStudent,
+    ApiInteger,
+    ApiIntegerRange,
+    ApiAttributes,
+    ApiAttribute,
+    ApiBool,
+    ApiString,
+    ApiOnlyForStrategy,
+    ApiPreferences,
+    ApiConstraints,
+    ApiGroupNames,
+    ApiGroupingFailed,
+    ApiUnsatisfied,
+    ApiConflict,
+    CannotOpenSignature,
+    HistoryUnknown,
+    PseudonymsUnknown,
+    NoSessions,
+    SessionNotRecorded,
+    SessionNoGroup,
+    BracketTooFew,
+    NoRoster,
+    DbImported,
+    QuerySession,
+    QueryGroup,
+    MissingKey,
+    MissingKeyIn,
+    LogBadGroup,
+    LogUnknownAction,
+    LogNoStart,
+    LogEmpty,
+    LogMismatch,
+    LogNoPrevious,
+    LogResumeMismatch,
+    CommandFailed,
+    NoClipboard,
+    UnknownSetting,
+    SettingType,
+    SettingBool,
+    SettingText,
+    SettingPath,
+    SettingList,
+    DbNoSession,
+    ProgramMissing,
+    ProgramFailed,
+    ProgramOutput,
+    RequestFailed,
+    ServiceError,
+    JsonTrailing,
+    JsonExpected,
+    JsonEndExpected,
+    JsonUnexpected,
+    JsonEnd,
+    JsonNumber,
+    JsonEscape,
+    JsonUnclosedString,
+    JsonUnicodeEscape,
+    JsonArraySeparator,
+    JsonUnclosedArray,
+    JsonObjectSeparator,
+    JsonUnclosedObject,
+    JsonTooDeep,
+    MergeDuplicate,
+    MoodleUtf16,
+    MoodleNotUtf8,
+    MoodleNoIdColumn,
+    MoodleNoIds,
+    PreferenceForm,
+    PreferenceNoChoices,
+    PreferenceRepeated,
+    PreferenceSelf,
+    PreferenceTooMany,
+    PseudonymForm,
+    IdRepeated,
+    SeatingChar,
+    NoSeats,
+    TooFewSeats,
+    MethodNotAllowed,
+    PathNotFound,
+    RequestHandleFailed,
+    AcceptFailed,
+    RequestTooLarge,
+    RequestNotUtf8,
+    SessionGroupIds,
+    SessionGroupIndex,
+    CannotSave,
+    NoGoogleCredentials,
+    NoAccessToken,
+    KeyUnreadable,
+    KeyEmpty,
+    SignatureForm,
+    SignatureScheme,
+    TomlDuplicateTable,
+    TomlDuplicateKey,
+    TomlExpected,
+    TomlExpectedEnd,
+    TomlTrailing,
+    TomlNoKey,
+    TomlBadValue,
+    TomlNoValue,
+    TomlBadNumber,
+    TomlBadEscape,
+    TomlUnclosedString,
+    TomlArrayComma,
+    FileAtLine,
+    HistoryBadScore,
+    HistoryCsvColumns,
+    MissingColumns,
+
+    // Verbose trace (`-v`, `-vv`)
+    TraceGroup,
+    TraceOptimize,
+    TraceStrata,
+    TraceStrategy,
+    TracePinned,
+    TraceSizeOverride,
+    TraceOverflow,
+    TraceLeftover,
+    TraceFillGap,
+    TraceToSmallest,
+    TraceNewGroups,
+    TraceComplete,
+    TraceIncomplete,
+    TraceJoinComplete,
+    TraceRegroupIncomplete,
+    TracePooled,
+    TraceAvoidRepeats,
+    TraceConstraints,
+    TraceBalance,
+    TraceShuffle,
+    TraceSplit,
+    TraceSkillTiers,
+    TraceSkill,
+    TraceSimilar,
+    TraceGenetic,
+    TracePreferences,
+    TraceSwaps,
+    TraceBred,
+    TraceWishes,
+    TraceTermSwaps,
+}
+
+impl Msg {
+    /// The (Japanese, English) texts.
+    fn texts(self) -> (&'static str, &'static str) {
+        match self {
+            Msg::EntryIntro => (
+                "学籍番号を入力してください ({}人ごとにグループになります):",
+                "Enter student IDs (every {} students make a group):",
+            ),
+            Msg::EntryIntroLater => (
+                "学籍番号を入力してください (入力が終わるとグループに分けます):",
+                "Enter student IDs (they are split into groups after entry):",
+            ),
+            Msg::EntryHelpFinish => (
+                "  - Ctrl+D (Unix/Mac) または Ctrl+Z+Enter (Windows): 現在のグループを終了して次のグループへ",
+                "  - Ctrl+D (Unix/Mac) or Ctrl+Z+Enter (Windows): finish the current group and start the next",
+            ),
+            Msg::EntryHelpQuit => ("  - Ctrl+C: プログラムを終了", "  - Ctrl+C: quit"),
+            Msg::EntryHelpDelete => (
+                "  - 'delete:学籍番号' と入力すると、その学籍番号を削除できます（例: delete:S001）",
+                "  - Type 'delete:ID' to remove a student (e.g. delete:S001)",
+            ),
+            Msg::EntryHelpMove => (
+                "  - 'move:学籍番号:グループ' と入力すると、その学生を別のグループに移動できます（例: move:S001:B）",
+                "  - Type 'move:ID:GROUP' to move a student to another group (e.g. move:S001:B)",
+            ),
+            Msg::EntryHelpSwap => (
+                "  - 'swap:学籍番号:学籍番号' と入力すると、別々のグループの2人を入れ替えられます（例: swap:S001:S014）",
+                "  - Type 'swap:ID:ID' to exchange two students in different groups (e.g. swap:S001:S014)",
+            ),
+            Msg::EntryHelpAdd => (
+                "  - 'add:学籍番号:グループ' と入力すると、入力中のグループではなく、空きのあるグループに直接追加できます（例: add:S001:B）",
+                "  - Type 'add:ID:GROUP' to add a student straight to a group with room instead of the current one (e.g. add:S001:B)",
+            ),
+            Msg::EntryHelpPin => (
+                "  - '!' または 'pin:グループ' と入力すると、そのグループを組み直さずにそのまま残せます（'unpin:グループ' で解除）",
+                "  - Type '!' or 'pin:GROUP' to keep a group exactly as entered ('unpin:GROUP' to undo)",
+            ),
+            Msg::EntryHelpUndo => (
+                "  - 'undo' と入力すると、直前の操作（追加・削除・移動・入れ替え・グループの終了）を取り消せます",
+                "  - Type 'undo' to undo the last action (add, delete, move, swap or finishing a group)",
+            ),
+            Msg::EntryGroupHeading => ("=== グループ {} の入力 ===", "=== Entering group {} ==="),
+            Msg::Added => ("  追加: {}", "  Added: {}"),
+            Msg::GroupCompleted => ("  ✓ グループ {} が完成しました ({}人)", "  ✓ Group {} is complete ({} students)"),
+            Msg::GroupSaved => ("  ✓ グループ {} を保存しました ({} 人)", "  ✓ Saved group {} ({} student(s))"),
+            Msg::DeletedFromCurrent => (
+                "  ✓ 削除しました: {} (現在のグループから)",
+                "  ✓ Deleted {} (from the current group)",
+            ),
+            Msg::DeletedFromGroup => ("  ✓ 削除しました: {} (グループ {} から)", "  ✓ Deleted {} (from group {})"),
+            Msg::NotFound => ("  ✗ エラー: {} は見つかりませんでした", "  ✗ Error: {} was not found"),
+            Msg::DidYouMean => ("  もしかして: {}", "  Did you mean: {}"),
+            Msg::DeleteSuggestion => (
+                "  もしかして {} ですか？ 削除する場合は y を入力してください (y/N): ",
+                "  Did you mean {}? Enter y to delete it (y/N): ",
+            ),
+            Msg::InvalidId => (
+                "  ✗ エラー: {} は学籍番号の形式 ({}) に合いません。入力し直してください",
+                "  ✗ Error: {} does not match the student ID format ({}); please type it again",
+            ),
+            Msg::InvalidIdSkipped => (
+                "エラー: {} 行目の {} は学籍番号の形式 ({}) に合わないため読み飛ばします",
+                "Error: skipping line {}: {} does not match the student ID format ({})",
+            ),
+            Msg::InvalidLine => (
+                "  ✗ エラー: {}。入力し直してください",
+                "  ✗ Error: {}; please type it again",
+            ),
+            Msg::TimedOut => (
+                "{} 秒間入力がなかったため、入力を終えて結果を表示します",
+                "No input for {} seconds; finishing the entry and showing the result",
+            ),
+            Msg::InvalidLineSkipped => (
+                "エラー: {} 行目を読み飛ばします: {}",
+                "Error: skipping line {}: {}",
+            ),
+            Msg::Duplicate => (
+                "  ✗ エラー: {} は既にグループ {} にいます（別のグループにするには move:{}:グループ で移動してください）",
+                "  ✗ Error: {} is already in group {} (use move:{}:GROUP to move them)",
+            ),
+            Msg::DuplicateSkipped => (
+                "警告: {} 行目の {} は既にグループ {} にいるため読み飛ばします",
+                "Warning: skipping line {}: {} is already in group {}",
+            ),
+            Msg::SizeHeaderSkipped => (
+                "警告: {} 行目の {} はグループの人数（2以上）として読めないため読み飛ばします",
+                "Warning: skipping line {}: {} is not a group size of 2 or more",
+            ),
+            Msg::Pinned => (
+                "  ✓ グループ {} を固定しました（組み直しません）",
+                "  ✓ Pinned group {} (it will be kept as entered)",
+            ),
+            Msg::Unpinned => ("  ✓ グループ {} の固定を外しました", "  ✓ Unpinned group {}"),
+            Msg::PinIgnored => (
+                "警告: グループ {} は1人しかいないため、固定せずにほかの学生と組みます",
+                "Warning: group {} has a single student, so it is not pinned and is regrouped",
+            ),
+            Msg::MoveUsage => (
+                "  ✗ エラー: move:学籍番号:グループ の形式で入力してください（例: move:S001:B）",
+                "  ✗ Error: type move:ID:GROUP (e.g. move:S001:B)",
+            ),
+            Msg::SwapUsage => (
+                "  ✗ エラー: swap:学籍番号:学籍番号 の形式で入力してください（例: swap:S001:S014）",
+                "  ✗ Error: type swap:ID:ID (e.g. swap:S001:S014)",
+            ),
+            Msg::Swapped => (
+                "  ✓ 入れ替えました: {} (グループ {} → {}) と {} (グループ {} → {})",
+                "  ✓ Swapped {} (group {} → {}) and {} (group {} → {})",
+            ),
+            Msg::SameGroupSwap => (
+                "  ✗ エラー: {} と {} はどちらもグループ {} にいます",
+                "  ✗ Error: {} and {} are both in group {}",
+            ),
+            Msg::AddUsage => (
+                "  ✗ エラー: add:学籍番号:グループ の形式で入力してください（例: add:S001:B）",
+                "  ✗ Error: type add:ID:GROUP (e.g. add:S001:B)",
+            ),
+            Msg::AddedTo => ("  追加: {} (グループ {})", "  Added: {} (group {})"),
+            Msg::Moved => ("  ✓ 移動しました: {} (グループ {} → {})", "  ✓ Moved {} (group {} → {})"),
+            Msg::NoSuchGroup => ("  ✗ エラー: グループ {} は存在しません", "  ✗ Error: group {} does not exist"),
+            Msg::AlreadyInGroup => ("  ✗ エラー: {} は既にグループ {} にいます", "  ✗ Error: {} is already in group {}"),
+            Msg::GroupFull => ("  ✗ エラー: グループ {} は満員です", "  ✗ Error: group {} is full"),
+            Msg::UndoneAdd => ("  ↶ 取り消しました: {} の追加", "  ↶ Undid adding {}"),
+            Msg::UndoneDelete => (
+                "  ↶ 取り消しました: {} の削除 (グループ {} に戻しました)",
+                "  ↶ Undid deleting {} (back in group {})",
+            ),
+            Msg::UndoneMove => (
+                "  ↶ 取り消しました: {} の移動 (グループ {} に戻しました)",
+                "  ↶ Undid moving {} (back in group {})",
+            ),
+            Msg::UndoneSwap => ("  ↶ 取り消しました: {} と {} の入れ替え", "  ↶ Undid swapping {} and {}"),
+            Msg::UndoneComplete => ("  ↶ 取り消しました: グループ {} の終了", "  ↶ Undid finishing group {}"),
+            Msg::NothingToUndo => ("  ✗ エラー: 取り消せる操作がありません", "  ✗ Error: nothing to undo"),
+            Msg::NowEntering => ("  (現在はグループ {} を入力中)", "  (now entering group {})"),
+            Msg::Interrupted => (
+                "\n\nCtrl+C が押されました。プログラムを終了します...グループ分けの結果を表示するには、Enterを押してください。",
+                "\n\nCtrl+C pressed. Finishing... press Enter to show the grouping result.",
+            ),
+            Msg::NoInput => ("\n入力されたデータがありません。", "\nNo students were entered."),
+            Msg::WatchStarted => (
+                "{} を監視しています。保存するたびにグループ分けをやり直します (Ctrl+C で終了)",
+                "Watching {}; the grouping is redone each time it is saved (Ctrl+C to quit)",
+            ),
+            Msg::WatchReloaded => ("\n--- {} を読み込みました ({}) ---", "\n--- Loaded {} ({}) ---"),
+            Msg::WatchStopped => ("\n監視を終了しました。", "\nStopped watching."),
+            Msg::SessionResumed => ("{} の続きから入力します（入力済み: {} 人）", "Resuming {} ({} student(s) entered)"),
+            Msg::SessionSaveFailed => ("  ✗ 途中経過を保存できません: {}", "  ✗ Could not save the session: {}"),
+            Msg::SessionLogFailed => ("  ✗ 操作の記録を書き込めません: {}", "  ✗ Could not write the session log: {}"),
+            Msg::TuiUnavailable => (
+                "警告: 全画面表示を開始できませんでした ({})。1行ずつの入力に切り替えます",
+                "Warning: could not start the full-screen input ({}); using line-by-line input",
+            ),
+
+            Msg::TuiTitle => ("グループ分け入力", "Group entry"),
+            Msg::TuiTitleNote => ("({}人ごとにグループになります)", "(every {} students make a group)"),
+            Msg::TuiTitleNoteLater => (
+                "(入力が終わるとグループに分けます)",
+                "(students are split into groups after entry)",
+            ),
+
+            Msg::TuiEntering => ("(入力中)", "(entering)"),
+            Msg::TuiInputHelp => (
+                "Enter: 追加 / 空のままEnter: 次のグループへ / swap:学籍番号:学籍番号: 入れ替え / add:学籍番号:グループ: グループに追加 / undo: 取り消し / Tab: 一覧の操作 / Ctrl+D: 入力を終了",
+                "Enter: add / empty Enter: next group / swap:ID:ID: swap / add:ID:GROUP: add to a group / undo: undo / Tab: edit the list / Ctrl+D: finish",
+            ),
+            Msg::TuiListHelp => (
+                "↑↓: 選択 / ←→: 前後のグループへ移動 / d: 削除 / u: 取り消し / Tab: 入力に戻る / q: 入力を終了",
+                "↑↓: select / ←→: move to the previous or next group / d: delete / u: undo / Tab: back to input / q: finish",
+            ),
+            Msg::TuiPrompt => ("学籍番号> ", "Student ID> "),
+            Msg::TuiEnterId => ("学籍番号を入力してください", "Type a student ID"),
+            Msg::TuiGroupSaved => ("グループ {} を保存しました ({} 人)", "Saved group {} ({} student(s))"),
+            Msg::TuiInvalidId => (
+                "{} は学籍番号の形式 ({}) に合いません",
+                "{} does not match the student ID format ({})",
+            ),
+            Msg::TuiDuplicate => (
+                "{} は既にグループ {} にいます（Tab で一覧に移ると移動できます）",
+                "{} is already in group {} (press Tab to move them in the list)",
+            ),
+            Msg::TuiAdded => ("追加: {}", "Added: {}"),
+            Msg::TuiGroupCompleted => ("グループ {} が完成しました ({}人)", "Group {} is complete ({} students)"),
+            Msg::TuiDeleted => ("削除しました: {} (グループ {} から)", "Deleted {} (from group {})"),
+            Msg::TuiGroupFull => ("グループ {} は満員です", "Group {} is full"),
+            Msg::TuiMoved => ("{} をグループ {} に移動しました", "Moved {} to group {}"),
+            Msg::TuiSwapUsage => (
+                "swap:学籍番号:学籍番号 の形式で入力してください（例: swap:S001:S014）",
+                "Type swap:ID:ID (e.g. swap:S001:S014)",
+            ),
+            Msg::TuiSwapped => ("{} と {} を入れ替えました (グループ {} ⇄ {})", "Swapped {} and {} (groups {} ⇄ {})"),
+            Msg::TuiSameGroupSwap => ("{} と {} はどちらもグループ {} にいます", "{} and {} are both in group {}"),
+            Msg::TuiNotFound => ("{} は見つかりませんでした", "{} was not found"),
+            Msg::TuiAddUsage => (
+                "add:学籍番号:グループ の形式で入力してください（例: add:S001:B）",
+                "Type add:ID:GROUP (e.g. add:S001:B)",
+            ),
+            Msg::TuiAddedTo => ("追加: {} (グループ {})", "Added: {} (group {})"),
+            Msg::TuiNoSuchGroup => ("グループ {} は存在しません", "Group {} does not exist"),
+            Msg::TuiUndone => ("直前の操作を取り消しました", "Undid the last action"),
+            Msg::TuiNothingToUndo => ("取り消せる操作がありません", "Nothing to undo"),
+
+            Msg::ResultTitle => ("グループ分け結果", "Grouping result"),
+            Msg::GroupLine => ("グループ {}: {} 人", "Group {}: {} student(s)"),
+            Msg::GroupHeading => ("グループ {}", "Group {}"),
+            Msg::GroupTitle => ("グループ {} ({} 人)", "Group {} ({} student(s))"),
+            Msg::Counts => ("{} グループ / {} 人", "{} group(s) / {} student(s)"),
+
+            Msg::Members => ("{} 人", "{} student(s)"),
+            Msg::TotalGroups => ("合計: {} グループ", "Total: {} group(s)"),
+            Msg::ContentHash => ("確認用ハッシュ: {}", "Verification hash: {}"),
+            Msg::Summary => ("合計: {} グループ / {} 人", "Total: {} group(s) / {} student(s)"),
+            Msg::Leader => ("(リーダー)", "(leader)"),
+            Msg::ScheduleTitle => ("{} 回分のグループ分け", "Groupings for {} sessions"),
+            Msg::SessionHeading => ("第 {} 回", "Session {}"),
+            Msg::PerStudentTitle => ("学生ごとの予定（全 {} 回）", "Schedule per student ({} sessions)"),
+            Msg::SeedFooter => ("シード: {}", "Seed: {}"),
+            Msg::BracketTitle => ("トーナメント表（{}）", "Tournament bracket ({})"),
+            Msg::BracketSingle => ("シングルエリミネーション", "single elimination"),
+            Msg::BracketDouble => ("ダブルエリミネーション", "double elimination"),
+            Msg::BracketRound => ("{} 回戦", "Round {}"),
+            Msg::BracketFinal => ("決勝", "Final"),
+            Msg::BracketWinnersRound => ("勝者側 {} 回戦", "Winners round {}"),
+            Msg::BracketLosersRound => ("敗者側 {} 回戦", "Losers round {}"),
+            Msg::BracketGrandFinal => ("グランドファイナル", "Grand final"),
+            Msg::BracketMatch => ("  試合 {}: {} vs {}", "  Match {}: {} vs {}"),
+            Msg::BracketWinnerOf => ("試合 {} の勝者", "winner of match {}"),
+            Msg::BracketLoserOf => ("試合 {} の敗者", "loser of match {}"),
+            Msg::BracketTotal => ("合計: {} 試合", "Total: {} match(es)"),
+            Msg::SeatingTitle => ("座席表", "Seating chart"),
+            Msg::PresentationTitle => ("発表順", "Presentation order"),
+            Msg::PresentationSlot => ("{}. グループ {}", "{}. Group {}"),
+            Msg::PresentationSlotTimed => ("{}. グループ {}（{}〜{}）", "{}. Group {} ({}-{})"),
+            Msg::PresentationTotal => ("合計: {} 分", "Total: {} minutes"),
+            Msg::LeaderboardSession => ("得点ランキング（第 {} 回）", "Leaderboard (session {})"),
+            Msg::LeaderboardStudents => (
+                "得点ランキング（学生別、全 {} 回の合計）",
+                "Leaderboard (students, total over {} sessions)",
+            ),
+            Msg::LeaderboardLine => ("{} 位  {}  {} 点", "{}. {}  {} point(s)"),
+            Msg::SeatingFront => ("（前）", "(front)"),
+
+            Msg::Error => ("エラー: {}", "Error: {}"),
+            Msg::UsageHint => ("使い方は grouping-tool{} --help で確認できます", "See grouping-tool{} --help for usage"),
+            Msg::NeedsTerminal => (
+                "エラー: interactive は端末から実行してください（パイプで渡すときは batch を使います）",
+                "Error: run interactive from a terminal (use batch for piped input)",
+            ),
+            Msg::StreamNeedsPipe => (
+                "エラー: --stream はパイプやリダイレクトで渡した入力に使います（端末から貼り付けるときは batch を使います）",
+                "Error: --stream reads piped or redirected input (use batch to paste at a terminal)",
+            ),
+
+            Msg::ConfigInvalid => ("エラー: 設定ファイル: {}", "Error: configuration file: {}"),
+
+            Msg::Listening => (
+                "http://{} でグループ分けAPIを待ち受けています (POST /group、Ctrl+Cで終了)",
+                "Serving the grouping API on http://{} (POST /group, Ctrl+C to stop)",
+            ),
+            Msg::ListenFailed => (
+
+                "エラー: {} で待ち受けられませんでした: {}",
+                "Error: could not listen on {}: {}",
+            ),
+            Msg::BalanceIgnored => (
+                "警告: 制約ファイルを指定した場合、--balance、--spread-weights、--stratify は使用されません（制約を優先します）",
+                "Warning: --balance, --spread-weights and --stratify are not used together with a constraints file (the constraints take priority)",
+            ),
+            Msg::DbRosterFailed => (
+                "警告: データベースの名簿を読み込めませんでした: {}",
+                "Warning: could not read the roster from the database: {}",
+            ),
+
+            Msg::SavedTo => ("\n結果を {} に保存しました", "\nSaved the result to {}"),
+            Msg::Copied => ("結果をクリップボードにコピーしました", "Copied the result to the clipboard"),
+            Msg::CopyFailed => (
+                "警告: クリップボードにコピーできませんでした: {}",
+                "Warning: could not copy to the clipboard: {}",
+            ),
+            Msg::HtmlSaved => ("HTMLレポートを {} に保存しました", "Saved the HTML report to {}"),
+            Msg::PdfSaved => ("PDFを {} に保存しました", "Saved the PDF to {}"),
+            Msg::XlsxSaved => ("Excelファイルを {} に保存しました", "Saved the Excel workbook to {}"),
+            Msg::XlsxAssignments => ("割り当て", "Assignments"),
+            Msg::XlsxGroups => ("グループ", "Groups"),
+            Msg::ScoreRecorded => (
+                "第 {} 回のグループ {} に {} 点を記録しました（合計 {} 点）",
+                "Recorded for session {}, group {}: {} point(s) (total {})",
+            ),
+            Msg::AppendAlreadyPlaced => (
+                "警告: {} はすでにグループ {} にいるため追加しません",
+                "Warning: {} is already in group {} and was not added",
+            ),
+            Msg::AppendJoined => ("{} をグループ {} に追加しました", "Added {} to group {}"),
+            Msg::MergeRelabeled => ("{} のグループ → グループ {}〜{}", "Groups of {} → groups {} to {}"),
+            Msg::MergeGroupSize => ("警告: グループ {} は {} 人です", "Warning: group {} has {} student(s)"),
+            Msg::MergeSizesDiffer => (
+                "注意: グループの人数は {} ですが、全員を分け直すと {} になります",
+                "Note: the groups have {} students, but a fresh split of everyone would give {}",
+            ),
+            Msg::DiffTitle => ("比較結果", "Differences"),
+            Msg::DiffNone => ("変更はありません", "No changes"),
+            Msg::DiffMoved => ("グループが変わった学生: {} 人", "Students who changed groups: {}"),
+            Msg::DiffAdded => ("追加された学生: {} 人", "Students added: {}"),
+            Msg::DiffRemoved => ("いなくなった学生: {} 人", "Students removed: {}"),
+            Msg::DiffBroken => ("解消したペア: {} 組", "Pairs broken up: {}"),
+            Msg::DiffCreated => ("新しくできたペア: {} 組", "New pairs: {}"),
+            Msg::SeatingSaved => ("座席表を {} に保存しました", "Saved the seating chart to {}"),
+            Msg::SeatingFailed => (
+                "警告: 座席表を作れませんでした: {}",
+                "Warning: could not make the seating chart: {}",
+            ),
+            Msg::SlackPosted => ("結果をSlackに投稿しました", "Posted the result to Slack"),
+            Msg::SlackFailed => ("警告: Slackに投稿できませんでした: {}", "Warning: could not post to Slack: {}"),
+            Msg::DiscordPosted => ("結果をDiscordに投稿しました", "Posted the result to Discord"),
+            Msg::DiscordFailed => (
+                "警告: Discordに投稿できませんでした: {}",
+                "Warning: could not post to Discord: {}",
+            ),
+            Msg::TeamsPosted => ("結果をTeamsに投稿しました", "Posted the result to Teams"),
+            Msg::TeamsFailed => (
+                "警告: Teamsに投稿できませんでした: {}",
+                "Warning: could not post to Teams: {}",
+            ),
+            Msg::ReadFailed => ("エラー: 入力を読み込めませんでした: {}", "Error: could not read the input: {}"),
+            Msg::WriteFailed => ("エラー: 結果を書き出せませんでした: {}", "Error: could not write the result: {}"),
+            Msg::Unsatisfied => ("警告: 制約を満たせませんでした: {}", "Warning: constraint not satisfied: {}"),
+            Msg::FewerGroups => (
+                "警告: {} 人を {} グループに分けると1人のグループができるため、{} グループにしました",
+                "Warning: {} students cannot make {} groups without someone alone, so {} groups were made",
+            ),
+            Msg::OverMax => (
+                "警告: {} 人を {} 人以上 {} 人以下のグループには分けられないため、--max を超える {} 人のグループを作りました",
+                "Warning: {} students cannot be split into groups of {} to {}, so --max is broken with groups of {}",
+            ),
+            Msg::UnderMin => (
+                "警告: {} 人しかいないため、--min より少ない {} 人のグループになりました",
+                "Warning: there are only {} students, so --min is broken with a group of {}",
+            ),
+            Msg::GroupingFailed => ("エラー: グループを組めません: {}", "Error: cannot form the groups: {}"),
+            Msg::ConstraintConflict => (
+                "次の制約は同時には満たせません（どれか1つを外せば満たせます）:",
+                "These constraints cannot all be met at once (dropping any one of them would do):",
+            ),
+            Msg::UnsatisfiedInSession => (
+                "警告: 第 {} 回で制約を満たせませんでした: {}",
+                "Warning: constraint not satisfied in session {}: {}",
+            ),
+            Msg::Seed => (
+                "シード: {} (--seed {} で同じ結果を再現できます)",
+                "Seed: {} (use --seed {} to reproduce this result)",
+            ),
+            Msg::SeedReplay => (
+                "シード: {} (--replay {} で同じ入力から同じ結果を作り直せます)",
+                "Seed: {} (use --replay {} with the same input to make this result again)",
+            ),
+            Msg::ReplayMatches => ("第 {} 回の記録と同じ結果になりました", "The result matches recorded session {}"),
+            Msg::ReplayDiffers => (
+                "警告: 第 {} 回の記録と異なる結果になりました（入力や設定が記録したときと同じか確認してください）",
+                "Warning: the result differs from recorded session {} (check that the input and settings are the same as then)",
+            ),
+            Msg::LogReplayed => (
+                "{} から {} 人の学生の入力をやり直しました",
+                "Entered the students from {} again ({} students)",
+            ),
+            Msg::LogMatches => ("記録した結果と同じグループ分けになりました", "The grouping matches the logged result"),
+            Msg::LogDiffers => (
+                "警告: 記録した結果と異なるグループ分けになりました（記録 {}、今回 {}。オプションや履歴が記録したときと同じか確認してください）",
+                "Warning: the grouping differs from the logged result (logged {}, now {}; check that the options and the history are the same as then)",
+            ),
+            Msg::LogWithoutResult => (
+                "記録に結果が含まれていないため、同じグループ分けかどうかは確かめられません",
+                "The log holds no result, so whether the grouping is the same cannot be checked",
+            ),
+            Msg::ReplayNotRecorded => (
+                "警告: シード {} の記録が見つからないため、今の履歴で作り直します（記録との照合は行いません）",
+                "Warning: no session was recorded with seed {}, so the current history is used and nothing is checked",
+            ),
+            Msg::PairSummary => (
+                "同じグループになったペア: {} 組 (うち2回目以降の重複: {} 回)",
+                "Pairs grouped together: {} (repeats: {})",
+            ),
+            Msg::RepeatSummary => (
+                "過去 {} 回のグループ分けと重複したペア: {} 組",
+                "Pairs repeated from the past {} sessions: {}",
+            ),
+            Msg::SoftPenalty => (
+                "重みつき制約のペナルティ: {}（守れなかった重みつき制約 {} 件）",
+                "Soft constraint penalty: {} ({} weighted constraints broken)",
+            ),
+            Msg::SoftIgnored => (
+                "警告: 重みつきの制約（weight=）と --prefer-file の希望は --optimize、--strategy genetic、--avoid-repeats を指定したときだけ考慮されます",
+                "Warning: weighted constraints (weight=) and --prefer-file requests are only weighed with --optimize, --strategy genetic or --avoid-repeats",
+            ),
+            Msg::PreferencesHonored => (
+                "希望したペアのうち {} / {} 組が同じグループになりました:",
+                "{} of {} requested pairs were grouped together:",
+            ),
+            Msg::PlanWritten => (
+                "{} 回分のグループ分けと一覧を {} に書き出しました",
+                "Wrote {} sessions and the overview to {}",
+            ),
+            Msg::CalendarEvent => ("グループ分け（第 {} 回）", "Groups (session {})"),
+            Msg::CalendarWritten => ("カレンダーを {} に書き出しました", "Wrote the calendar to {}"),
+            Msg::ExportsRead => (
+                "{} から過去の結果を {} 回分読み込みました",
+                "Read past results from {}: {} sessions",
+            ),
+            Msg::WishesGranted => (
+                "希望した相手のうち {} / {} 人と同じグループになりました（学生ごと）:",
+                "{} of {} wished classmates were grouped together, per student:",
+            ),
+            Msg::SheetWritten => (
+                "結果をスプレッドシートの {} に書き込みました",
+                "Wrote the result to {} in the spreadsheet",
+            ),
+            Msg::SheetWriteFailed => (
+                "エラー: スプレッドシートに書き込めませんでした: {}",
+                "Error: could not write to the spreadsheet: {}",
+            ),
+            Msg::DbRecorded => ("第 {} 回としてデータベースに記録しました", "Recorded as session {} in the database"),
+            Msg::DbRecordFailed => (
+                "警告: データベースに記録できませんでした: {}",
+                "Warning: could not record in the database: {}",
+            ),
+            Msg::Archived => ("結果を {} に保存しました", "Archived the result to {}"),
+            Msg::ArchiveFailed => (
+                "警告: 結果を {} に保存できませんでした: {}",
+                "Warning: could not archive the result to {}: {}",
+            ),
+            Msg::KeyCreated => (
+                "署名の鍵を {} に作りました。鍵は他の人に渡さず、結果を確かめるときのために保管してください",
+                "Created the signing key {}. Do not hand it out; keep it for checking the results later",
+            ),
+            Msg::Signed => ("署名を {} に書き出しました", "Wrote the signature to {}"),
+            Msg::SignatureValid => (
+                "{} の署名は正しく、署名したときのままです",
+                "The signature of {} is valid: the file is as it was signed",
+            ),
+            Msg::SignatureInvalid => (
+                "{} の署名が一致しません。署名した後に書き換えられたか、別の鍵で署名されています",
+                "The signature of {} does not match: the file was changed after it was signed, or signed with another key",
+            ),
+            Msg::HistorySaveFailed => (
+                "警告: 履歴を {} に保存できませんでした: {}",
+                "Warning: could not save the history to {}: {}",
+            ),
+            Msg::RerollPrompt => (
+                "組み直すグループがあれば reroll B のように入力してください (Enter で確定): ",
+                "To redo a group, type e.g. reroll B (Enter to accept): ",
+            ),
+            Msg::RerollUsage => (
+                "  ✗ エラー: reroll の後にグループを指定してください (例: reroll B C)",
+                "  ✗ Error: give the groups after reroll (e.g. reroll B C)",
+            ),
+            Msg::RerollAlone => (
+                "  ✗ エラー: グループ {} だけでは顔ぶれが変わりません。複数のグループを指定してください",
+                "  ✗ Error: group {} alone would get the same members; give more than one group",
+            ),
+            Msg::Rerolled => ("  ✓ 組み直しました: グループ {}", "  ✓ Redid group(s) {}"),
+            Msg::ConfirmPrompt => (
+                "この結果で確定しますか？ (Enter: 確定 / r: 組み直し / e: 入力に戻る): ",
+                "Accept this grouping? (Enter: accept / r: reshuffle / e: back to editing): ",
+            ),
+            Msg::ConfirmUsage => (
+                "  ✗ エラー: Enter、r、e のいずれかを入力してください",
+                "  ✗ Error: type Enter, r or e",
+            ),
+            Msg::ConfirmSameOrder => (
+                "  ✗ エラー: この入力は入力順にグループ分けするため、組み直しても同じ結果になります",
+                "  ✗ Error: this input is grouped in the order given, so reshuffling gives the same result",
+            ),
+            Msg::ConfirmNoEdit => (
+                "  ✗ エラー: パイプや名簿から読み込んだ場合は入力に戻れません",
+                "  ✗ Error: input read from a pipe or a roster cannot be edited",
+            ),
+            Msg::StatsTitle => ("=== 統計 ===", "=== Statistics ==="),
+            Msg::StatsCounts => ("学生数: {} 人 / グループ数: {}", "Students: {} / groups: {}"),
+            Msg::StatsSizes => ("グループの人数: {}", "Group sizes: {}"),
+            Msg::StatsSize => ("{}人 × {}", "{} × {}"),
+            Msg::StatsRepeats => (
+                "過去に同じグループだったペア: {} / {} 組（延べ {} 回、過去 {} 回分の履歴）",
+                "Pairs grouped together before: {} of {} ({} past meetings in {} sessions of history)",
+            ),
+            Msg::StatsNoHistory => ("過去に同じグループだったペア: 履歴なし", "Pairs grouped together before: no history"),
+            Msg::StatsConstraints => ("満たした制約: {} / {} 件", "Constraints satisfied: {} / {}"),
+            Msg::StatsNoConstraints => ("満たした制約: 制約なし", "Constraints satisfied: no constraints"),
+            Msg::StatsFitness => (
+                "評価値: {}（0 が最良。満たしていない制約、属性の偏り、過去と同じペアから計算）",
+                "Score: {} (0 is best; counts broken constraints, attribute imbalance and repeated pairs)",
+            ),
+            Msg::StatsEdits => (
+                "入力中の操作: 追加 {} / 削除 {} / 移動 {} / 入れ替え {} / グループの完成 {} / 取り消し {}",
+                "Edits during entry: {} added / {} deleted / {} moved / {} swapped / {} groups completed / {} undone",
+            ),
+            Msg::WeightInvalid => (
+                "警告: {} の重み \"{}\" は数値ではないため、最も低いものとして扱います",
+                "Warning: the weight of {} (\"{}\") is not a number and is treated as the lowest",
+            ),
+            Msg::SkillInvalid => (
+                "警告: {} の技能 \"{}\" は数値ではないため、最も低い段として扱います",
+                "Warning: the skill of {} (\"{}\") is not a number and is put in the lowest tier",
+            ),
+
+            Msg::UnknownOption => ("不明なオプションです: {}", "Unknown option: {}"),
+            Msg::NeedsValue => ("{} には値が必要です", "{} needs a value"),
+            Msg::NeedsInteger => ("{} には{}以上の整数を指定してください: {}", "{} needs an integer of at least {}: {}"),
+            Msg::NeedsIntegerRange => ("{} には{}以上{}以下の整数を指定してください: {}", "{} needs an integer from {} to {}: {}"),
+            Msg::NeedsReplaySeed => (
+                "--replay にはシード（0以上の整数）を指定してください: {}",
+                "--replay needs a seed (an integer of at least 0): {}",
+            ),
+            Msg::NeedsDuration => ("{} には 2s、500ms のような時間を指定してください: {}", "{} needs a duration such as 2s or 500ms: {}"),
+            Msg::NeedsSeconds => ("{} には1以上の秒数を指定してください: {}", "{} needs a number of seconds of at least 1: {}"),
+            Msg::NeedsGroupSize => ("{} には2以上の人数を指定してください: {}", "{} needs a group size of at least 2: {}"),
+            Msg::NeedsAttribute => ("{} には属性名を指定してください", "{} needs an attribute name"),
+            Msg::OneOf => ("{} には {} のいずれかを指定してください: {}", "{} must be one of {}: {}"),
+            Msg::FromMoodle => ("--from には moodle を指定してください: {}", "--from must be moodle: {}"),
+            Msg::PointsNotInteger => ("点数は整数で指定してください: {}", "Points must be an integer: {}"),
+            Msg::InvalidDate => ("日付は YYYY-MM-DD の形で指定してください: {}", "Dates must be in the form YYYY-MM-DD: {}"),
+            Msg::OptionConflict => ("{} と {} は同時に指定できません", "{} cannot be used with {}"),
+            Msg::OptionRequires => ("{} には {} が必要です", "{} requires {}"),
+            Msg::OptionTogether => ("{} は {} と一緒に指定してください", "{} must be used with {}"),
+            Msg::NotWith => ("{} では {} は使用できません", "{} cannot be combined with {}"),
+            Msg::OnlyForGrouping => ("{} はグループ分け（{}）でのみ使用できます", "{} can only be used when grouping ({})"),
+            Msg::OnlyForGroupingAnd => (
+                "{} はグループ分け（{}）と {} でのみ使用できます",
+                "{} can only be used when grouping ({}) and with {}",
+            ),
+            Msg::OnlyForSubcommand => ("{} は {} サブコマンドでのみ使用できます", "{} can only be used with {}"),
+            Msg::OnlyForEntry => (
+                "{} は対話入力（interactive）でのみ使用できます",
+                "{} can only be used when typing in IDs (interactive)",
+            ),
+            Msg::NeedsSessions => ("{} には --sessions <回数> が必要です", "{} requires --sessions <N>"),
+            Msg::IcsWithStartDate => (
+                "--ics と --start-date <YYYY-MM-DD> は一緒に指定してください",
+                "--ics and --start-date <YYYY-MM-DD> must be given together",
+            ),
+            Msg::WeeklyNeedsIcs => (
+                "--weekly には --ics と --start-date が必要です",
+                "--weekly requires --ics and --start-date",
+            ),
+            Msg::HistoryUnused => (
+                "--history には --avoid-repeats、--optimize-repeats、--stats などの履歴を使うオプションが必要です",
+                "--history requires an option that uses the history, such as --avoid-repeats, --optimize-repeats or --stats",
+            ),
+            Msg::MixedSkillNeedsSkill => (
+                "--strategy mixed-skill には --skill で技能の属性を指定してください",
+                "--strategy mixed-skill needs the skill attribute given with --skill",
+            ),
+            Msg::SimilarNeedsGroupBy => (
+                "--strategy similar には --group-by で属性を指定してください",
+                "--strategy similar needs the attributes given with --group-by",
+            ),
+            Msg::PreferencesNeedsFile => (
+                "--strategy preferences には --preferences で希望のファイルを指定してください",
+                "--strategy preferences needs the file of wishes given with --preferences",
+            ),
+            Msg::MinOverMax => ("--min ({}) は --max ({}) 以下にしてください", "--min ({}) must not be greater than --max ({})"),
+            Msg::SheetRangeNeedsSheet => (
+                "--sheet-range と --sheet-output には --sheet が必要です",
+                "--sheet-range and --sheet-output require --sheet",
+            ),
+            Msg::NeedsDb => ("import と query には --db <ファイル> が必要です", "import and query require --db <FILE>"),
+            Msg::ImportNeedsRoster => (
+                "import には名簿のCSVファイル（または --sheet、--classroom）が必要です",
+                "import requires a roster CSV file (or --sheet or --classroom)",
+            ),
+            Msg::ReplayWithSeed => (
+                "--replay と --seed は同時に指定できません（--replay のシードを使います）",
+                "--replay cannot be used with --seed (the seed of --replay is used)",
+            ),
+            Msg::FromNeedsRoster => (
+                "--from には名簿のファイルが必要です（例: --from moodle roster.csv）",
+                "--from requires a roster file (e.g. --from moodle roster.csv)",
+            ),
+            Msg::MergeNeedsFiles => (
+                "merge には結果のファイルが2つ以上必要です（例: merge 1組.json 2組.json）",
+                "merge requires two or more result files (e.g. merge class1.json class2.json)",
+            ),
+            Msg::DiffNeedsFiles => (
+                "diff には変更前と変更後の結果のファイルを1つずつ指定してください（例: diff 前.json 後.json）",
+                "diff requires one result file from before the change and one from after (e.g. diff before.json after.json)",
+            ),
+            Msg::VerifyNeedsFile => (
+                "verify には確かめる結果のファイルを1つ指定してください（例: verify result.json --sign key.txt）",
+                "verify requires one result file to check (e.g. verify result.json --sign key.txt)",
+            ),
+            Msg::VerifyNeedsKey => (
+                "verify には署名に使った鍵のファイル（--sign <鍵ファイル>）が必要です",
+                "verify requires the key file used for signing (--sign <KEY FILE>)",
+            ),
+            Msg::ReplayNeedsLog => (
+                "replay には操作の記録のファイルを1つ指定してください（例: replay class.log）",
+                "replay requires one session log file (e.g. replay class.log)",
+            ),
+            Msg::SignNeedsFile => (
+                "--sign には署名するファイル（--out、--archive、--output-html、--pdf、--xlsx）が必要です",
+                "--sign requires a file to sign (--out, --archive, --output-html, --pdf or --xlsx)",
+            ),
+            Msg::ScoreNeedsPoints => (
+                "score にはグループと点数が必要です（例: score A 10）",
+                "score requires a group and points (e.g. score A 10)",
+            ),
+            Msg::TextOrJson => (
+                "bracket、order、leaderboard、diff の --format は text か json で指定してください",
+                "The --format of bracket, order, leaderboard and diff must be text or json",
+            ),
+
+            Msg::CannotAddToFull => ("{} を追加できません（グループは既に {} 人です）", "Cannot add {} (the group already has {} members)"),
+            Msg::DuplicateStudent => ("学籍番号 {} が2回以上あります", "Student ID {} appears more than once"),
+            Msg::NoGroups => ("グループ数には1以上を指定してください", "The number of groups must be at least 1"),
+            Msg::GroupTooSmall => ("グループの人数 {} は2以上にしてください", "The group size {} must be at least 2"),
+            Msg::MissingId => ("学籍番号がありません", "The student ID is missing"),
+            Msg::ExtraFields => (
+                "{} の属性が多すぎます（{} 個の予定が {} 個あります）",
+                "{} has too many attributes ({} expected, {} found)",
+            ),
+            Msg::AtLine => ("{} 行目: {}", "Line {}: {}"),
+            Msg::TemplateLine => ("テンプレートの{}行目: {}", "Template line {}: {}"),
+            Msg::TemplateUnclosedTag => ("{{ が閉じられていません", "{{ is not closed"),
+            Msg::TemplateUnknownBlock => ("不明なブロックです: {{#{}}}", "Unknown block: {{#{}}}"),
+            Msg::TemplateNoName => ("{} に値の名前がありません", "{} has no value name"),
+            Msg::TemplateMismatched => (
+                "{}行目の {{#{}}} が {{/{}}} で閉じられています",
+                "The block opened on line {} as {{#{}}} is closed by {{/{}}}",
+            ),
+            Msg::TemplateUnopened => ("{{/{}}} に対応するブロックがありません", "{{/{}}} has no matching block"),
+            Msg::TemplateElse => ("{{else}} は {{#if}} の中で使ってください", "{{else}} must be inside {{#if}}"),
+            Msg::TemplateUnclosed => ("{{#{}}} が閉じられていません", "{{#{}}} is not closed"),
+            Msg::ConstraintForm => ("'together: S001,S002' の形式で記述してください", "Write it in the form 'together: S001,S002'"),
+            Msg::ConstraintWeight => ("weight には1以上の整数を指定してください", "weight must be an integer of at least 1"),
+            Msg::ConstraintFewIds => ("学籍番号を2つ以上指定してください", "Give two or more student IDs"),
+            Msg::ConstraintUnknown => ("不明な制約です: {}", "Unknown constraint: {}"),
+            Msg::ConstraintSameIds => ("異なる学籍番号を2つ指定してください", "Give two different student IDs"),
+            Msg::ConstraintPair => ("'S001,S002' のように学籍番号を2つ指定してください", "Give two student IDs, as in 'S001,S002'"),
+            Msg::PatternUnmatchedParen => ("パターン {} の ')' が対応していません", "The ')' in the pattern {} has no matching '('"),
+            Msg::PatternUnclosedParen => ("パターンの '(' が閉じられていません", "A '(' in the pattern is not closed"),
+            Msg::PatternNothingToRepeat => (
+                "パターンの '{}' の前に繰り返す文字がありません",
+                "Nothing to repeat before '{}' in the pattern",
+            ),
+            Msg::PatternIncomplete => ("パターンが途中で終わっています", "The pattern ends too early"),
+            Msg::PatternTrailingBackslash => ("パターンが '\\' で終わっています", "The pattern ends with '\\'"),
+            Msg::PatternUnclosedClass => ("パターンの '[' が閉じられていません", "A '[' in the pattern is not closed"),
+            Msg::PatternReversedRange => ("パターンの範囲 {}-{} が逆です", "The range {}-{} in the pattern is reversed"),
+            Msg::PatternUnclosedBrace => ("パターンの '{' が閉じられていません", "A '{' in the pattern is not closed"),
+            Msg::PatternBadCount => ("パターンの繰り返し回数 {} が正しくありません", "The repetition count {} in the pattern is not valid"),
+            Msg::PatternRepeatedAnchor => ("パターンの '^' や '$' は繰り返せません", "'^' and '$' cannot be repeated in a pattern"),
+            Msg::PatternTooLong => ("パターンが長すぎます（繰り返し回数を減らしてください）", "The pattern is too long (use fewer repetitions)"),
+            Msg::ColumnFromOne => ("列番号は1から始まります", "Column numbers start at 1"),
+            Msg::ColumnEmpty => ("列名が空です", "The column name is empty"),
+            Msg::ColumnNotFound => ("列 '{}' がヘッダーに見つかりません", "Column '{}' is not in the header"),
+            Msg::NoIdColumn => ("学籍番号の列がありません", "The student ID column is missing"),
+            Msg::GroupNameRepeated => ("グループ名 {} が重複しています", "The group name {} appears more than once"),
+            Msg::NoGroupNames => ("グループ名がありません", "No group names are given"),
+            Msg::ResultNoGroups => (
+                "groups がありません（--format json で書き出した結果を指定してください）",
+                "groups is missing (give a result written with --format json)",
+            ),
+            Msg::ResultNoMembers => ("groups の要素に members がありません", "An element of groups has no members"),
+            Msg::ResultMembers => ("members は学籍番号の配列で指定してください", "members must be an array of student IDs"),
+            Msg::CannotOpen => ("{} を開けません: {}", "Cannot open {}: {}"),
+            Msg::CannotWrite => ("{} に書き込めません: {}", "Cannot write to {}: {}"),
+            Msg::ApiNotObject => ("リクエストはJSONオブジェクトで指定してください", "The request must be a JSON object"),
+            Msg::ApiGroupsArray => ("groups は配列で指定してください", "groups must be an array"),
+            Msg::ApiGroupMembers => (
+                "groups の要素は学籍番号の配列で指定してください",
+                "Each element of groups must be an array of student IDs",
+            ),
+            Msg::ApiIdString => ("学籍番号は文字列で指定してください", "Student IDs must be strings"),
+            Msg::ApiNoStudents => ("students (学籍番号の配列) がありません", "students (an array of student IDs) is missing"),
+            Msg::ApiEmpty => ("学生が指定されていません", "No students are given"),
+            Msg::ApiStudent => (
+                "students の要素は学籍番号か {\"id\": ...} で指定してください",
+                "Each element of students must be a student ID or {\"id\": ...}",
+            ),
+            Msg::ApiInteger => ("{} には{}以上の整数を指定してください", "{} must be an integer of at least {}"),
+            Msg::ApiIntegerRange => ("{} には{}以上{}以下の整数を指定してください", "{} must be an integer from {} to {}"),
+            Msg::ApiAttributes => ("{} は属性名の配列で指定してください", "{} must be an array of attribute names"),
+            Msg::ApiAttribute => ("{} は属性名で指定してください", "{} must be an attribute name"),
+            Msg::ApiBool => ("{} は true か false で指定してください", "{} must be true or false"),
+            Msg::ApiString => ("{} は文字列で指定してください", "{} must be a string"),
+            Msg::ApiOnlyForStrategy => ("{} は strategy が {} のときに指定してください", "{} can only be given when strategy is {}"),
+            Msg::ApiPreferences => (
+                "preferences は希望ファイルと同じ形式の文字列で指定してください",
+                "preferences must be a string in the format of the preferences file",
+            ),
+            Msg::ApiConstraints => (
+                "constraints は制約ファイルと同じ形式の文字列で指定してください",
+                "constraints must be a string in the format of the constraints file",
+            ),
+            Msg::ApiGroupNames => ("group_names はグループ名の配列で指定してください", "group_names must be an array of group names"),
+            Msg::ApiGroupingFailed => ("グループを組めません: {}", "Cannot form the groups: {}"),
+            Msg::ApiUnsatisfied => ("制約を満たせませんでした: {}", "Constraint not satisfied: {}"),
+            Msg::ApiConflict => ("同時には満たせない制約です: {}", "These constraints cannot all be met at once: {}"),
+            Msg::CannotOpenSignature => ("署名のファイル {} を開けません: {}", "Cannot open the signature file {}: {}"),
+            Msg::HistoryUnknown => (
+                "履歴ファイルの場所を決められません (--history-file を指定してください)",
+                "Cannot tell where the history file is (give --history-file)",
+            ),
+            Msg::PseudonymsUnknown => (
+                "仮名のファイルの場所を決められません (--pseudonym-file を指定してください)",
+                "Cannot tell where the pseudonym file is (give --pseudonym-file)",
+            ),
+            Msg::NoSessions => ("記録されたグループ分けはありません", "No groupings are recorded"),
+            Msg::SessionNotRecorded => ("第 {} 回の記録はありません", "Session {} is not recorded"),
+            Msg::SessionNoGroup => ("第 {} 回にグループ {} はありません", "Session {} has no group {}"),
+            Msg::BracketTooFew => ("トーナメントには2つ以上のグループが必要です", "A bracket needs two or more groups"),
+            Msg::NoRoster => ("名簿が指定されていません", "No roster is given"),
+            Msg::DbImported => ("{} 人の学生をデータベースに取り込みました", "Imported {} students into the database"),
+            Msg::QuerySession => ("第 {} 回 ({})", "Session {} ({})"),
+            Msg::QueryGroup => ("  グループ {}: {}", "  Group {}: {}"),
+            Msg::MissingKey => ("{} がありません", "{} is missing"),
+            Msg::MissingKeyIn => ("{} に {} がありません", "{} has no {}"),
+            Msg::LogBadGroup => ("{} のグループ {} が正しくありません", "{} names a group that is not valid: {}"),
+            Msg::LogUnknownAction => ("不明な操作です: {}", "Unknown action: {}"),
+            Msg::LogNoStart => (
+                "記録が入力の開始（start）から始まっていません",
+                "The log does not begin with the start of an entry (start)",
+            ),
+            Msg::LogEmpty => ("操作が1つも記録されていません", "No actions are recorded"),
+            Msg::LogMismatch => ("記録がこれまでの操作と一致しません", "The log does not match the actions before it"),
+            Msg::LogNoPrevious => (
+                "続きから入力した記録ですが、その前の記録がありません",
+                "The log goes on from an earlier entry, but the log of that entry is missing",
+            ),
+            Msg::LogResumeMismatch => (
+                "続きから入力した記録ですが、その前の記録（{} グループ、{} 人）と一致しません",
+                "The log goes on from an earlier entry, but does not match its log ({} groups, {} students)",
+            ),
+            Msg::CommandFailed => ("{} が失敗しました ({})", "{} failed ({})"),
+            Msg::NoClipboard => ("クリップボードのコマンドが見つかりません ({})", "No clipboard command was found ({})"),
+            Msg::UnknownSetting => ("不明な設定です: {}", "Unknown setting: {}"),
+            Msg::SettingType => ("{} には{}を指定してください", "{} must be {}"),
+            Msg::SettingBool => ("true または false", "true or false"),
+            Msg::SettingText => ("文字列", "a string"),
+            Msg::SettingPath => ("ファイル名", "a file name"),
+            Msg::SettingList => ("文字列の配列", "an array of strings"),
+            Msg::DbNoSession => ("セッションを記録できませんでした", "Could not record the session"),
+            Msg::ProgramMissing => ("{} コマンドが見つかりません。インストールしてください", "The {} command was not found. Please install it"),
+            Msg::ProgramFailed => ("{} を実行できません: {}", "Cannot run {}: {}"),
+            Msg::ProgramOutput => ("{} の出力を読み取れません", "Cannot read the output of {}"),
+            Msg::RequestFailed => ("通信に失敗しました: {}", "The request failed: {}"),
+            Msg::ServiceError => ("{} エラー ({}): {}", "{} error ({}): {}"),
+            Msg::JsonTrailing => ("JSONの {} 文字目以降に余分なデータがあります", "Extra data from character {} of the JSON"),
+            Msg::JsonExpected => (
+                "JSONの {} 文字目: '{}' が必要ですが '{}' でした",
+                "Character {} of the JSON: expected '{}' but found '{}'",
+            ),
+            Msg::JsonEndExpected => ("JSONが途中で終わっています ('{}' が必要です)", "The JSON ends too early ('{}' expected)"),
+            Msg::JsonUnexpected => ("JSONの {} 文字目: 予期しない文字 '{}'", "Character {} of the JSON: unexpected '{}'"),
+            Msg::JsonEnd => ("JSONが途中で終わっています", "The JSON ends too early"),
+            Msg::JsonNumber => ("JSONの {} 文字目: 不正な数値 '{}'", "Character {} of the JSON: invalid number '{}'"),
+            Msg::JsonEscape => ("JSONの {} 文字目: 不正なエスケープ '\\{}'", "Character {} of the JSON: invalid escape '\\{}'"),
+            Msg::JsonUnclosedString => ("JSONの文字列が閉じられていません", "A string in the JSON is not closed"),
+            Msg::JsonUnicodeEscape => ("JSONの \\u エスケープが不正です", "A \\u escape in the JSON is not valid"),
+            Msg::JsonArraySeparator => (
+                "JSONの {} 文字目: ',' か ']' が必要ですが '{}' でした",
+                "Character {} of the JSON: expected ',' or ']' but found '{}'",
+            ),
+            Msg::JsonUnclosedArray => ("JSONの配列が閉じられていません", "An array in the JSON is not closed"),
+            Msg::JsonObjectSeparator => (
+                "JSONの {} 文字目: ',' か '}' が必要ですが '{}' でした",
+                "Character {} of the JSON: expected ',' or '}' but found '{}'",
+            ),
+            Msg::JsonUnclosedObject => ("JSONのオブジェクトが閉じられていません", "An object in the JSON is not closed"),
+            Msg::JsonTooDeep => ("JSONの {} 文字目: 入れ子が {} 段を超えています", "Character {} of the JSON: nested more than {} levels deep"),
+            Msg::MergeDuplicate => ("学籍番号 {} が複数の結果に含まれています", "Student ID {} is in more than one result"),
+            Msg::MoodleUtf16 => ("UTF-16 として読めません", "Cannot be read as UTF-16"),
+            Msg::MoodleNotUtf8 => (
+                "文字コードが UTF-8 ではありません（Shift_JIS など）。Moodle から書き出すときに UTF-8 を選んでください",
+                "The file is not in UTF-8 (but in Shift_JIS or the like). Choose UTF-8 when exporting from Moodle",
+            ),
+            Msg::MoodleNoIdColumn => (
+                "Moodle の「ID number」（IDナンバー）列が見つかりません。参加者一覧、ユーザの一括ダウンロード、評定のエクスポートで書き出したファイルを指定してください",
+                "The \"ID number\" column of Moodle was not found. Give a file exported from the participants page, the bulk user download or the grade export",
+            ),
+            Msg::MoodleNoIds => (
+                "ID number（IDナンバー）が入力された学生がいません（{} 人）。Moodle のユーザプロファイルに学籍番号を設定してください",
+                "None of the students has an ID number ({} students). Set the student IDs in the Moodle user profiles",
+            ),
+            Msg::PreferenceForm => ("'S001: S005,S007' の形式で記述してください", "Write it in the form 'S001: S005,S007'"),
+            Msg::PreferenceNoChoices => ("希望する学生の学籍番号がありません", "The IDs of the students wished for are missing"),
+            Msg::PreferenceRepeated => ("{} の希望はすでに書かれています", "The wishes of {} are already given"),
+            Msg::PreferenceSelf => ("自分自身は希望できません", "Students cannot wish for themselves"),
+            Msg::PreferenceTooMany => ("希望できるのは {} 人までです", "At most {} students can be wished for"),
+            Msg::PseudonymForm => ("学籍番号,仮名 の形式で書いてください", "Write it in the form ID,pseudonym"),
+            Msg::IdRepeated => ("学籍番号 {} が重複しています", "Student ID {} is given more than once"),
+            Msg::SeatingChar => ("座席は o、通路は . で書いてください: {}", "Write seats as o and aisles as .: {}"),
+            Msg::NoSeats => ("座席がありません", "There are no seats"),
+            Msg::TooFewSeats => ("座席が足りません（座席 {} 席、学生 {} 人）", "Not enough seats ({} seats, {} students)"),
+            Msg::MethodNotAllowed => ("{} は使用できません", "{} is not allowed"),
+            Msg::PathNotFound => ("{} は見つかりません", "{} was not found"),
+            Msg::RequestHandleFailed => ("警告: リクエストの処理に失敗しました: {}", "Warning: could not handle a request: {}"),
+            Msg::AcceptFailed => ("警告: 接続を受け付けられませんでした: {}", "Warning: could not accept a connection: {}"),
+            Msg::RequestTooLarge => ("リクエストが大きすぎます", "The request is too large"),
+            Msg::RequestNotUtf8 => ("リクエストはUTF-8で送信してください", "Send the request in UTF-8"),
+            Msg::SessionGroupIds => ("グループは学籍番号の配列で指定してください", "Groups must be arrays of student IDs"),
+            Msg::SessionGroupIndex => (
+                "group_index が groups の数と一致しません",
+                "group_index does not match the number of groups",
+            ),
+            Msg::CannotSave => ("{} に保存できません: {}", "Cannot save to {}: {}"),
+            Msg::NoGoogleCredentials => (
+                "Googleの認証情報がありません ({} を設定するか --google-credentials を指定してください)",
+                "No Google credentials (set {} or give --google-credentials)",
+            ),
+            Msg::NoAccessToken => ("アクセストークンを取得できませんでした", "Could not get an access token"),
+            Msg::KeyUnreadable => ("鍵のファイル {} を読めません: {}", "Cannot read the key file {}: {}"),
+            Msg::KeyEmpty => ("鍵のファイル {} が空です", "The key file {} is empty"),
+            Msg::SignatureForm => (
+                "署名の形式が正しくありません（hmac-sha256 <16進数> の形で書かれていません）",
+                "The signature is not valid (it is not written as hmac-sha256 <hex>)",
+            ),
+            Msg::SignatureScheme => ("対応していない署名の方式です: {}", "Unsupported signature scheme: {}"),
+            Msg::TomlDuplicateTable => ("[{}] が重複しています", "[{}] is given more than once"),
+            Msg::TomlDuplicateKey => ("{} が重複しています", "{} is given more than once"),
+            Msg::TomlExpected => ("'{}' が必要ですが '{}' でした", "Expected '{}' but found '{}'"),
+            Msg::TomlExpectedEnd => ("'{}' が必要です", "'{}' is expected"),
+            Msg::TomlTrailing => ("値の後に余分な文字 '{}' があります", "Extra character '{}' after the value"),
+            Msg::TomlNoKey => ("キーがありません", "The key is missing"),
+            Msg::TomlBadValue => ("不正な値です: {}", "Invalid value: {}"),
+            Msg::TomlNoValue => ("値がありません", "The value is missing"),
+            Msg::TomlBadNumber => ("不正な数値です: {}", "Invalid number: {}"),
+            Msg::TomlBadEscape => ("不正なエスケープです: {}", "Invalid escape: {}"),
+            Msg::TomlUnclosedString => ("文字列が閉じられていません", "A string is not closed"),
+            Msg::TomlArrayComma => ("配列の要素の間には ',' が必要です", "Array elements must be separated by ','"),
+            Msg::FileAtLine => ("{} {} 行目: {}", "{} line {}: {}"),
+            Msg::HistoryBadScore => ("得点の記録が正しくありません", "The score record is not valid"),
+            Msg::HistoryCsvColumns => (
+                "student_id と group の列がありません（--format csv で書き出した結果を指定してください）",
+                "The student_id and group columns are missing (give a result written with --format csv)",
+            ),
+            Msg::MissingColumns => ("列が足りません", "Some columns are missing"),
+
+            Msg::TraceGroup => ("グループ {} ({})", "Group {} ({})"),
+            Msg::TraceOptimize => ("{} のあいだ入れ替えを試してグループを改善します", "Trying swaps for {} to improve the groups"),
+            Msg::TraceStrata => ("{} の値ごとに {} つに分けて組みます", "Grouping each value of {} apart, in {} parts"),
+            Msg::TraceStrategy => ("方法 {} で {} グループ ({}) を組み直します", "With the {} strategy, regrouping {} groups ({})"),
+            Msg::TracePinned => ("{} は固定されているのでそのままにします", "Keeping {}, which is pinned"),
+            Msg::TraceSizeOverride => ("{} は指定どおり {} 人までで組みます", "Keeping {} to at most {} students as given"),
+            Msg::TraceOverflow => (
+                "指定した人数を超えた {} 人はほかの学生と組みます",
+                "Grouping the {} students beyond the given size with the others",
+            ),
+            Msg::TraceLeftover => (
+                "余った学生が少ないため、{} も一緒に組み直します",
+                "Regrouping {} too, since too few students are left over",
+            ),
+            Msg::TraceFillGap => ("{} を空きのある {} に加えます", "Adding {} to {}, which has room"),
+            Msg::TraceToSmallest => (
+                "{} だけが余るため、いちばん小さい {} に加えます",
+                "Adding {}, the only ones left over, to the smallest group, {}",
+            ),
+            Msg::TraceNewGroups => (
+                "空きに入らなかった学生で {} グループ ({}) を作ります",
+                "Making {} groups ({}) of the students who did not fit in",
+            ),
+            Msg::TraceComplete => ("{} は完成しているのでそのままにします", "Keeping {}, which is complete"),
+            Msg::TraceIncomplete => ("{} は未完成なので組み直します", "Regrouping {}, which is incomplete"),
+            Msg::TraceJoinComplete => (
+                "{} 人だけが余るため、完成していた {} と一緒に組み直します",
+                "Regrouping the {} students left over together with {}, which was complete",
+            ),
+            Msg::TraceRegroupIncomplete => (
+                "未完成のグループの {} 人を {} 人のグループに分けます",
+                "Splitting the {} students of the incomplete groups into groups of {}",
+            ),
+            Msg::TracePooled => ("{} を組み直しに加えます", "Adding {} to the regrouping"),
+            Msg::TraceAvoidRepeats => ("過去のグループとの重複を避けて組みます", "Grouping so as to avoid past pairings"),
+            Msg::TraceConstraints => ("制約を満たすように組みます", "Grouping so as to meet the constraints"),
+            Msg::TraceBalance => ("属性や重みのバランスをとって組みます", "Grouping so as to balance the attributes and weights"),
+            Msg::TraceShuffle => ("{} 人の順番をシャッフルします", "Shuffling the order of {} students"),
+            Msg::TraceSplit => ("{} 人を並んでいる順に {} 人のグループに分けます", "Splitting {} students in order into groups of {}"),
+            Msg::TraceSkillTiers => (
+                "{} の高い順に段に分け、各段から1人ずつ配ります",
+                "Putting the students in tiers by {}, highest first, and dealing one from each tier",
+            ),
+            Msg::TraceSkill => ("技能", "skill"),
+            Msg::TraceSimilar => (
+                "{} の値が近い学生を同じグループにします",
+                "Putting students with close values of {} in the same group",
+            ),
+            Msg::TraceGenetic => ("{} 通りのグループ分けを {} 世代かけて交配します", "Breeding {} groupings over {} generations"),
+            Msg::TracePreferences => ("学生の希望をなるべく多く叶えるように組みます", "Grouping so as to grant as many wishes as possible"),
+            Msg::TraceSwaps => ("入れ替えを {} 回試しました（評価値 {} → {}）", "Tried {} swaps (score {} → {})"),
+            Msg::TraceBred => ("{} 世代のうち評価値 {} のグループ分けを選びました", "Out of {} generations, chose the grouping scored {}"),
+            Msg::TraceWishes => (
+                "希望を {} 件叶える組み合わせから、入れ替えで {} 件にしました",
+                "Swaps turned a matching that grants {} wishes into one that grants {}",
+            ),
+            Msg::TraceTermSwaps => (
+                "学期全体で入れ替えを {} 回試しました（重複の評価 {} → {}）",
+                "Tried {} swaps over the whole term (repeat score {} → {})",
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Lang::Ja.format(Msg::Moved, &[&"S001", &"A", &"B"]), "  ✓ 移動しました: S001 (グループ A → B)");
+        assert_eq!(Lang::En.format(Msg::GroupLine, &[&"A", &3]), "Group A: 3 student(s)");
+        assert_eq!(Lang::En.text(Msg::Copied), "Copied the result to the clipboard");
+    }
+
+    #[test]
+    fn test_from_locale() {
+        assert_eq!(Lang::from_locale("ja_JP.UTF-8"), Some(Lang::Ja));
+        assert_eq!(Lang::from_locale("en_US.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("de_DE.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C.UTF-8"), None);
+        assert_eq!(Lang::from_locale(""), None);
+        assert_eq!("EN".parse::<Lang>(), Ok(Lang::En));
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_placeholders_match() {
+        // Both texts of every message must take the same number of arguments
+        let all = [
+            Msg::MissingKey,
+            Msg::MissingKeyIn,
+            Msg::LogBadGroup,
+            Msg::LogUnknownAction,
+            Msg::LogResumeMismatch,
+            Msg::CommandFailed,
+            Msg::NoClipboard,
+            Msg::UnknownSetting,
+            Msg::SettingType,
+            Msg::ProgramMissing,
+            Msg::ProgramFailed,
+            Msg::ProgramOutput,
+            Msg::RequestFailed,
+            Msg::ServiceError,
+            Msg::JsonTrailing,
+            Msg::JsonExpected,
+            Msg::JsonEndExpected,
+            Msg::JsonUnexpected,
+            Msg::JsonNumber,
+            Msg::JsonEscape,
+            Msg::JsonArraySeparator,
+            Msg::JsonObjectSeparator,
+            Msg::JsonTooDeep,
+            Msg::MergeDuplicate,
+            Msg::MoodleNoIds,
+            Msg::PreferenceRepeated,
+            Msg::PreferenceTooMany,
+            Msg::IdRepeated,
+            Msg::SeatingChar,
+            Msg::TooFewSeats,
+            Msg::MethodNotAllowed,
+            Msg::PathNotFound,
+            Msg::RequestHandleFailed,
+            Msg::AcceptFailed,
+            Msg::CannotSave,
+            Msg::NoGoogleCredentials,
+            Msg::KeyUnreadable,
+            Msg::KeyEmpty,
+            Msg::SignatureScheme,
+            Msg::TomlDuplicateTable,
+            Msg::TomlDuplicateKey,
+            Msg::TomlExpected,
+            Msg::TomlExpectedEnd,
+            Msg::TomlTrailing,
+            Msg::TomlBadValue,
+            Msg::TomlBadNumber,
+            Msg::TomlBadEscape,
+            Msg::FileAtLine,
+            Msg::TraceGroup,
+            Msg::TraceOptimize,
+            Msg::TraceStrata,
+            Msg::TraceStrategy,
+            Msg::TracePinned,
+            Msg::TraceSizeOverride,
+            Msg::TraceOverflow,
+            Msg::TraceLeftover,
+            Msg::TraceFillGap,
+            Msg::TraceToSmallest,
+            Msg::TraceNewGroups,
+            Msg::TraceComplete,
+            Msg::TraceIncomplete,
+            Msg::TraceJoinComplete,
+            Msg::TraceRegroupIncomplete,
+            Msg::TracePooled,
+            Msg::TraceShuffle,
+            Msg::TraceSplit,
+            Msg::TraceSkillTiers,
+            Msg::TraceSimilar,
+            Msg::TraceGenetic,
+            Msg::TraceSwaps,
+            Msg::TraceBred,
+            Msg::TraceWishes,
+            Msg::TraceTermSwaps,
+            Msg::CannotOpenSignature,
+            Msg::SessionNotRecorded,
+            Msg::SessionNoGroup,
+            Msg::DbImported,
+            Msg::QuerySession,
+            Msg::QueryGroup,
+            Msg::UnknownOption,
+            Msg::NeedsValue,
+            Msg::NeedsInteger,
+            Msg::NeedsIntegerRange,
+            Msg::NeedsReplaySeed,
+            Msg::NeedsDuration,
+            Msg::NeedsSeconds,
+            Msg::NeedsGroupSize,
+            Msg::NeedsAttribute,
+            Msg::OneOf,
+            Msg::FromMoodle,
+            Msg::PointsNotInteger,
+            Msg::InvalidDate,
+            Msg::OptionConflict,
+            Msg::OptionRequires,
+            Msg::OptionTogether,
+            Msg::NotWith,
+            Msg::OnlyForGrouping,
+            Msg::OnlyForGroupingAnd,
+            Msg::OnlyForSubcommand,
+            Msg::OnlyForEntry,
+            Msg::NeedsSessions,
+            Msg::MinOverMax,
+            Msg::CannotAddToFull,
+            Msg::DuplicateStudent,
+            Msg::GroupTooSmall,
+            Msg::ExtraFields,
+            Msg::AtLine,
+            Msg::TemplateLine,
+            Msg::TemplateUnknownBlock,
+            Msg::TemplateNoName,
+            Msg::TemplateMismatched,
+            Msg::TemplateUnopened,
+            Msg::TemplateUnclosed,
+            Msg::ConstraintUnknown,
+            Msg::PatternUnmatchedParen,
+            Msg::PatternNothingToRepeat,
+            Msg::PatternReversedRange,
+            Msg::PatternBadCount,
+            Msg::ColumnNotFound,
+            Msg::GroupNameRepeated,
+            Msg::CannotOpen,
+            Msg::CannotWrite,
+            Msg::ApiInteger,
+            Msg::ApiAttributes,
+            Msg::ApiAttribute,
+            Msg::ApiBool,
+            Msg::ApiString,
+            Msg::ApiOnlyForStrategy,
+            Msg::ApiGroupingFailed,
+            Msg::ApiUnsatisfied,
+            Msg::ApiConflict,
+            Msg::EntryGroupHeading,
+            Msg::InvalidId,
+            Msg::InvalidIdSkipped,
+            Msg::InvalidLineSkipped,
+            Msg::TimedOut,
+            Msg::Duplicate,
+            Msg::DuplicateSkipped,
+            Msg::SizeHeaderSkipped,
+            Msg::PinIgnored,
+            Msg::Moved,
+            Msg::AddedTo,
+            Msg::Swapped,
+            Msg::SameGroupSwap,
+            Msg::UndoneDelete,
+            Msg::UndoneMove,
+            Msg::UndoneSwap,
+            Msg::TuiGroupSaved,
+            Msg::TuiDuplicate,
+            Msg::TuiSwapped,
+            Msg::TuiSameGroupSwap,
+            Msg::TuiAddedTo,
+            Msg::GroupLine,
+            Msg::Summary,
+            Msg::Seed,
+            Msg::PairSummary,
+            Msg::RepeatSummary,
+            Msg::SoftPenalty,
+            Msg::StatsFitness,
+            Msg::StatsEdits,
+            Msg::SessionLogFailed,
+            Msg::LogReplayed,
+            Msg::LogDiffers,
+            Msg::PreferencesHonored,
+            Msg::WishesGranted,
+            Msg::ExportsRead,
+            Msg::PlanWritten,
+            Msg::ContentHash,
+            Msg::CalendarEvent,
+            Msg::CalendarWritten,
+            Msg::HistorySaveFailed,
+            Msg::Archived,
+            Msg::ArchiveFailed,
+            Msg::KeyCreated,
+            Msg::Signed,
+            Msg::SignatureValid,
+            Msg::SignatureInvalid,
+            Msg::UnsatisfiedInSession,
+            Msg::FewerGroups,
+            Msg::UsageHint,
+            Msg::OverMax,
+            Msg::UnderMin,
+            Msg::RerollAlone,
+            Msg::Rerolled,
+            Msg::EntryIntro,
+            Msg::GroupCompleted,
+            Msg::TuiTitleNote,
+            Msg::TuiGroupCompleted,
+            Msg::WeightInvalid,
+            Msg::SessionResumed,
+            Msg::WatchReloaded,
+            Msg::StatsRepeats,
+            Msg::BracketMatch,
+            Msg::PresentationSlotTimed,
+            Msg::ScoreRecorded,
+            Msg::AppendAlreadyPlaced,
+            Msg::AppendJoined,
+            Msg::MergeRelabeled,
+            Msg::MergeGroupSize,
+            Msg::MergeSizesDiffer,
+            Msg::DiffMoved,
+            Msg::DiffAdded,
+            Msg::DiffRemoved,
+            Msg::DiffBroken,
+            Msg::DiffCreated,
+            Msg::ApiIntegerRange,
+        ];
+        for msg in all {
+            let (ja, en) = msg.texts();
+            assert_eq!(ja.matches("{}").count(), en.matches("{}").count(), "{:?}", msg);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! iCalendar (`.ics`) export of a schedule (`--ics`).
+//!
+//! Each session becomes an all-day event on its date, with the groups of
+//! that session in the description, so that the file can be imported into
+//! the course calendar. Lines are folded at 75 octets and end with CRLF, as
+//! RFC 5545 requires.
+
+use crate::group::{Group, StudentId};
+use crate::i18n::Msg;
+use crate::output::{MemberLabels, Metadata};
+use crate::time;
+use std::collections::HashMap;
+
+/// The dates of a schedule: the first session on `start` (days since
+/// 1970-01-01), and each one after `interval` more days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dates {
+    pub start: i64,
+    pub interval: i64,
+}
+
+impl Dates {
+    /// The date of session `index` (counted from 0), in days since 1970-01-01.
+    pub fn of(&self, index: usize) -> i64 {
+        self.start + self.interval * index as i64
+    }
+}
+
+/// Render `sessions` as a calendar with one event per session.
+pub fn render(
+    sessions: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    dates: Dates,
+    metadata: &Metadata,
+) -> String {
+    let lang = metadata.lang;
+    let labels = MemberLabels::new(names, metadata);
+    // The time the plan was made, as a DTSTAMP (20250401T093000Z)
+    let stamp: String = metadata.timestamp.chars().filter(|c| !matches!(c, '-' | ':')).collect();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//grouping-tool//grouping-tool//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for (n, groups) in sessions.iter().enumerate() {
+        let description = groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| {
+                let members: Vec<String> = group.members.iter().map(|member| labels.label(member)).collect();
+                format!("{}: {}", lang.format(Msg::GroupHeading, &[&metadata.group_names.label(i)]), members.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let uid = match metadata.seed {
+            Some(seed) => format!("{}-{}-{}@grouping-tool", stamp, seed, n + 1),
+            None => format!("{}-{}@grouping-tool", stamp, n + 1),
+        };
+        let date = dates.of(n);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", uid),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", compact_date(date)),
+            format!("DTEND;VALUE=DATE:{}", compact_date(date + 1)),
+            format!("SUMMARY:{}", escape(&lang.format(Msg::CalendarEvent, &[&(n + 1)]))),
+            format!("DESCRIPTION:{}", escape(&description)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// A date as iCalendar writes it (20250408).
+fn compact_date(days: i64) -> String {
+    time::format_date(days).replace('-', "")
+}
+
+/// Escape a TEXT value: backslashes, semicolons, commas and line breaks.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fold `line` into lines of at most 75 octets, without splitting a
+/// character, each continuation starting with a space, and end it with CRLF.
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Lang;
+
+    #[test]
+    fn test_render() {
+        let group = |ids: &[&str]| Group::from_members(ids.iter().map(|id| id.to_string()).collect());
+        let sessions = vec![
+            vec![group(&["S001", "S002"]), group(&["S003", "S004"])],
+            vec![group(&["S001", "S003"]), group(&["S002", "S004"])],
+        ];
+        let names = HashMap::from([("S001".to_string(), "山田, 太郎".to_string())]);
+        let metadata = Metadata {
+            timestamp: "2025-04-01T09:30:00Z".to_string(),
+            seed: Some(7),
+            lang: Lang::En,
+            ..Default::default()
+        };
+        let dates = Dates { start: time::parse_date("2025-04-08").unwrap(), interval: 7 };
+        let ics = render(&sessions, &names, dates, &metadata);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("UID:20250401T093000Z-7-2@grouping-tool\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250415\r\nDTEND;VALUE=DATE:20250416\r\n"));
+        assert!(ics.contains("SUMMARY:Groups (session 1)\r\n"));
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains("DESCRIPTION:Group A: S001 山田\\, 太郎\\, S002\\nGroup B: S003\\, S004\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    fn test_fold() {
+        let line = "あ".repeat(30);
+        let folded = fold(&line);
+        assert_eq!(folded.split("\r\n").map(str::len).collect::<Vec<_>>(), vec![75, 16, 0]);
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
@@ -0,0 +1,453 @@
+//! Minimal JSON value type with a serializer and parser, used by the JSON
+//! export and the history file.
+
+use crate::i18n::{message, Msg};
+use std::fmt::{self, Write};
+
+/// Arrays and objects nested deeper than this are rejected, so that a hostile
+/// document cannot overflow the stack of the recursive parser.
+pub const MAX_DEPTH: usize = 128;
+
+/// A JSON value. Object keys keep their insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// A number, kept as its literal text so that large integers (e.g. seeds) stay exact
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Build an object from key/value pairs.
+    pub fn object<K: Into<String>>(pairs: Vec<(K, Value)>) -> Self {
+        Value::Object(pairs.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /// Parse a JSON document.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: text.char_indices().peekable(),
+            text,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some((pos, _)) => Err(message(Msg::JsonTrailing, &[&pos])),
+        }
+    }
+
+    /// Look up `key` in an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Serialize with two-space indentation.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) -> fmt::Result {
+        match self {
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    item.write_pretty(out, indent + 1)?;
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                push_indent(out, indent);
+                out.push(']');
+            }
+            Value::Object(pairs) if !pairs.is_empty() => {
+                out.push_str("{\n");
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    write_string(out, key)?;
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 1)?;
+                    out.push_str(if i + 1 < pairs.len() { ",\n" } else { "\n" });
+                }
+                push_indent(out, indent);
+                out.push('}');
+            }
+            _ => write!(out, "{}", self)?,
+        }
+        Ok(())
+    }
+}
+
+/// Compact serialization (no whitespace).
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => f.write_str(n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Value::Object(pairs) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Value::Number(n.to_string())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Number(n.to_string())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Value::Number(n.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Recursive-descent parser over the characters of a JSON document.
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+    /// Arrays and objects currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(message(Msg::JsonExpected, &[&pos, &expected, &c])),
+            None => Err(message(Msg::JsonEndExpected, &[&expected])),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some((pos, c @ ('{' | '['))) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(message(Msg::JsonTooDeep, &[&pos, &MAX_DEPTH]));
+                }
+                self.depth += 1;
+                let value = if c == '{' { self.object() } else { self.array() };
+                self.depth -= 1;
+                value
+            }
+            Some((_, '"')) => self.string().map(Value::String),
+            Some((_, 't')) => self.literal("true", Value::Bool(true)),
+            Some((_, 'f')) => self.literal("false", Value::Bool(false)),
+            Some((_, 'n')) => self.literal("null", Value::Null),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some((pos, c)) => Err(message(Msg::JsonUnexpected, &[&pos, &c])),
+            None => Err(message(Msg::JsonEnd, &[])),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.chars.peek().map(|(pos, _)| *pos).unwrap_or(0);
+        let mut end = start;
+        while let Some(&(pos, c)) = self.chars.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                end = pos + c.len_utf8();
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        let literal = &self.text[start..end];
+        literal
+            .parse::<f64>()
+            .map(|_| Value::Number(literal.to_string()))
+            .map_err(|_| message(Msg::JsonNumber, &[&start, &literal]))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(out),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => out.push('"'),
+                    Some((_, '\\')) => out.push('\\'),
+                    Some((_, '/')) => out.push('/'),
+                    Some((_, 'b')) => out.push('\u{8}'),
+                    Some((_, 'f')) => out.push('\u{c}'),
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 'r')) => out.push('\r'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, 'u')) => {
+                        let unit = self.hex4()?;
+                        // Surrogate pairs encode characters outside the BMP
+                        let c = if (0xD800..0xDC00).contains(&unit) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.hex4()?;
+                            char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low.wrapping_sub(0xDC00)))
+                        } else {
+                            char::from_u32(unit)
+                        };
+                        out.push(c.unwrap_or('\u{fffd}'));
+                    }
+                    Some((pos, c)) => return Err(message(Msg::JsonEscape, &[&pos, &c])),
+                    None => return Err(message(Msg::JsonUnclosedString, &[])),
+                },
+                Some((_, c)) => out.push(c),
+                None => return Err(message(Msg::JsonUnclosedString, &[])),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|(_, c)| c.to_digit(16))
+                .ok_or_else(|| message(Msg::JsonUnicodeEscape, &[]))?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek().is_some_and(|(_, c)| *c == ']') {
+            self.chars.next();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Value::Array(items)),
+                Some((pos, c)) => return Err(message(Msg::JsonArraySeparator, &[&pos, &c])),
+                None => return Err(message(Msg::JsonUnclosedArray, &[])),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek().is_some_and(|(_, c)| *c == '}') {
+            self.chars.next();
+            return Ok(Value::Object(pairs));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            pairs.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Value::Object(pairs)),
+                Some((pos, c)) => return Err(message(Msg::JsonObjectSeparator, &[&pos, &c])),
+                None => return Err(message(Msg::JsonUnclosedObject, &[])),
+            }
+        }
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+/// Write `s` as a quoted JSON string with the required escapes.
+fn write_string<W: Write>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_serialization() {
+        let value = Value::object(vec![
+            ("name", Value::from("a\"b\n")),
+            ("seed", Value::from(u64::MAX)),
+            ("none", Value::from(None::<u64>)),
+            ("list", Value::Array(vec![Value::Bool(true), Value::Array(vec![])])),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"a\"b\n","seed":18446744073709551615,"none":null,"list":[true,[]]}"#
+        );
+    }
+
+    #[test]
+    fn test_pretty_serialization() {
+        let value = Value::object(vec![("members", Value::Array(vec!["S001".into(), "S002".into()]))]);
+        assert_eq!(
+            value.to_pretty_string(),
+            "{\n  \"members\": [\n    \"S001\",\n    \"S002\"\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let value = Value::object(vec![
+            ("name", Value::from("山田 \"太郎\"\n")),
+            ("seed", Value::from(u64::MAX)),
+            ("none", Value::Null),
+            ("flags", Value::Array(vec![Value::Bool(true), Value::Bool(false)])),
+            ("empty", Value::object(Vec::<(String, Value)>::new())),
+        ]);
+        assert_eq!(Value::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(Value::parse(&value.to_pretty_string()).unwrap(), value);
+        assert_eq!(Value::parse(&value.to_string()).unwrap().get("seed").unwrap().as_u64(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_parse_escapes_and_errors() {
+        let value = Value::parse(r#"["\u3042\ud83d\ude00", -1.5e3]"#).unwrap();
+        let items = value.as_array().unwrap();
+        assert_eq!(items[0].as_str(), Some("あ😀"));
+        assert_eq!(items[1].as_f64(), Some(-1500.0));
+
+        assert!(Value::parse("[1, 2").is_err());
+        assert!(Value::parse("{\"a\" 1}").is_err());
+        assert!(Value::parse("nul").is_err());
+        assert!(Value::parse("[] x").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Value::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Value::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Value::parse(&"{\"a\":".repeat(100_000)).is_err());
+    }
+}
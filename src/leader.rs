@@ -0,0 +1,88 @@
+//! Choosing one leader per group.
+
+use crate::group::{Group, StudentId};
+use crate::history::History;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+/// Pick a random leader for each non-empty group, in group order. With a
+/// history, only the members who have led the fewest times before are
+/// considered, so that the role rotates across sessions.
+pub fn choose_leaders<R: Rng + ?Sized>(groups: &[Group], history: Option<&History>, rng: &mut R) -> Vec<StudentId> {
+    groups
+        .iter()
+        .filter_map(|group| {
+            let times_led = |id: &StudentId| history.map_or(0, |h| h.leader_count(id));
+            let fewest = group.ids().map(times_led).min()?;
+            let candidates: Vec<&StudentId> = group.ids().filter(|m| times_led(m) == fewest).collect();
+            candidates.choose(rng).map(|id| (*id).clone())
+        })
+        .collect()
+}
+
+/// Leaders after some groups changed: a group keeps its leader when exactly
+/// one of `previous` is still among its members, and gets a new one otherwise.
+pub fn update_leaders<R: Rng + ?Sized>(
+    groups: &[Group],
+    previous: &[StudentId],
+    history: Option<&History>,
+    rng: &mut R,
+) -> Vec<StudentId> {
+    let previous: HashSet<&StudentId> = previous.iter().collect();
+    groups
+        .iter()
+        .flat_map(|group| {
+            let kept: Vec<&StudentId> = group.ids().filter(|id| previous.contains(id)).collect();
+            match kept.as_slice() {
+                [leader] => vec![(*leader).clone()],
+                _ => choose_leaders(std::slice::from_ref(group), history, rng),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn group(ids: &[&str]) -> Group {
+        Group::from_members(ids.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_one_leader_per_group() {
+        let groups = vec![group(&["S001", "S002", "S003"]), group(&[]), group(&["S004", "S005"])];
+        let leaders = choose_leaders(&groups, None, &mut StdRng::seed_from_u64(1));
+        assert_eq!(leaders.len(), 2);
+        assert!(groups[0].contains(&leaders[0]));
+        assert!(groups[2].contains(&leaders[1]));
+    }
+
+    #[test]
+    fn test_avoids_previous_leaders() {
+        let mut history = History::default();
+        history.record_leaders(&["S001".to_string(), "S002".to_string()]);
+        let groups = vec![group(&["S001", "S002", "S003"])];
+        for seed in 0..20 {
+            let leaders = choose_leaders(&groups, Some(&history), &mut StdRng::seed_from_u64(seed));
+            assert_eq!(leaders, vec!["S003"]);
+        }
+    }
+
+    #[test]
+    fn test_update_keeps_leaders_of_unchanged_groups() {
+        let groups = vec![group(&["S001", "S002", "S003"]), group(&["S004", "S005", "S006"])];
+        let previous = vec!["S002".to_string(), "S003".to_string()];
+        let leaders = update_leaders(&groups, &previous, None, &mut StdRng::seed_from_u64(1));
+        // The second group lost its leader and the first now has two, so both are chosen anew
+        assert_eq!(leaders.len(), 2);
+        assert!(groups[0].contains(&leaders[0]));
+        assert!(groups[1].contains(&leaders[1]));
+
+        let previous = vec!["S002".to_string(), "S005".to_string()];
+        assert_eq!(update_leaders(&groups, &previous, None, &mut StdRng::seed_from_u64(1)), previous);
+    }
+}
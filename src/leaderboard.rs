@@ -0,0 +1,127 @@
+//! Rankings of the points given to groups with `score` (`leaderboard` subcommand).
+
+use crate::db::Session;
+use crate::group::StudentId;
+use crate::i18n::{Lang, Msg};
+use crate::json::Value;
+
+/// One place in a ranking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    /// 1 for the most points; equal points share a rank
+    pub rank: usize,
+    pub label: String,
+    pub points: i64,
+}
+
+/// Rank `entries` by points, most first. Entries with equal points keep
+/// their order and share a rank, and the next rank skips accordingly (1, 1, 3).
+pub fn rank(entries: Vec<(String, i64)>) -> Vec<Standing> {
+    let mut entries = entries;
+    entries.sort_by_key(|(_, points)| std::cmp::Reverse(*points));
+    let mut standings: Vec<Standing> = Vec::with_capacity(entries.len());
+    for (i, (label, points)) in entries.into_iter().enumerate() {
+        let rank = match standings.last() {
+            Some(last) if last.points == points => last.rank,
+            _ => i + 1,
+        };
+        standings.push(Standing { rank, label, points });
+    }
+    standings
+}
+
+/// The groups of `session` ranked by their points, labelled by `label`.
+pub fn group_standings(session: &Session, label: impl Fn(usize) -> String) -> Vec<Standing> {
+    let entries = (0..session.groups.len())
+        .map(|i| (label(i), session.scores.get(i).copied().unwrap_or(0)))
+        .collect();
+    rank(entries)
+}
+
+/// Students ranked by the points of their groups over all `sessions`, labelled by `label`.
+pub fn student_standings(sessions: &[Session], label: impl Fn(&str) -> String) -> Vec<Standing> {
+    let mut totals: Vec<(StudentId, i64)> = Vec::new();
+    let mut index = std::collections::HashMap::new();
+    for session in sessions {
+        for (group, points) in session.groups.iter().zip(&session.scores) {
+            for member in group.ids() {
+                let i = *index.entry(member.clone()).or_insert_with(|| {
+                    totals.push((member.clone(), 0));
+                    totals.len() - 1
+                });
+                totals[i].1 += points;
+            }
+        }
+    }
+    rank(totals.into_iter().map(|(id, points)| (label(&id), points)).collect())
+}
+
+/// Render a ranking as text under `title`.
+pub fn render_text(title: &str, standings: &[Standing], lang: Lang) -> String {
+    let mut out = format!("\n=== {} ===\n", title);
+    for standing in standings {
+        out.push_str(&lang.format(Msg::LeaderboardLine, &[&standing.rank, &standing.label, &standing.points]));
+        out.push('\n');
+    }
+    out
+}
+
+/// A ranking as a JSON array of `{"rank", "label", "points"}`.
+pub fn to_json(standings: &[Standing]) -> Value {
+    Value::Array(
+        standings
+            .iter()
+            .map(|standing| {
+                Value::object(vec![
+                    ("rank", Value::from(standing.rank)),
+                    ("label", Value::from(standing.label.as_str())),
+                    ("points", Value::from(standing.points)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::Group;
+
+    fn session(groups: &[&[&str]], scores: Vec<i64>) -> Session {
+        Session {
+            id: 1,
+            timestamp: String::new(),
+            seed: None,
+            groups: groups
+                .iter()
+                .map(|g| Group::from_members(g.iter().map(|m| m.to_string()).collect()))
+                .collect(),
+            leaders: Vec::new(),
+            scores,
+        }
+    }
+
+    #[test]
+    fn test_rank_shares_ties() {
+        let entries = vec![("A".to_string(), 5), ("B".to_string(), 10), ("C".to_string(), 5), ("D".to_string(), 0)];
+        let standings = rank(entries);
+        let ranks: Vec<(usize, &str)> = standings.iter().map(|s| (s.rank, s.label.as_str())).collect();
+        assert_eq!(ranks, vec![(1, "B"), (2, "A"), (2, "C"), (4, "D")]);
+    }
+
+    #[test]
+    fn test_standings() {
+        let first = session(&[&["S001", "S002"], &["S003", "S004"]], vec![3, 8]);
+        let second = session(&[&["S001", "S003"], &["S002", "S004"]], vec![5, -2]);
+
+        let groups = group_standings(&first, |i| format!("グループ {}", ["A", "B"][i]));
+        assert_eq!(groups[0], Standing { rank: 1, label: "グループ B".to_string(), points: 8 });
+        let text = render_text("得点", &groups, Lang::Ja);
+        assert!(text.ends_with("1 位  グループ B  8 点\n2 位  グループ A  3 点\n"), "{}", text);
+
+        let students = student_standings(&[first, second], str::to_string);
+        let totals: Vec<(&str, i64)> = students.iter().map(|s| (s.label.as_str(), s.points)).collect();
+        assert_eq!(totals, vec![("S003", 13), ("S001", 8), ("S004", 6), ("S002", 1)]);
+        assert_eq!(to_json(&students[..1]).to_string(), r#"[{"rank":1,"label":"S003","points":13}]"#);
+    }
+}
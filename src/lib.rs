@@ -0,0 +1,70 @@
+//! Grouping library behind the `grouping-tool` command.
+//!
+//! Students are split into groups of 3 people, allowing 2-person groups only
+//! when the total is not divisible by 3. Single-person groups are never created.
+//!
+//! ```
+//! use grouping_tool::grouping::{self, GroupingConfig};
+//!
+//! let students = vec!["S001", "S002", "S003", "S004", "S005"]
+//!     .into_iter()
+//!     .map(String::from)
+//!     .collect();
+//! let groups = grouping::assign(students, GroupingConfig::default()).unwrap();
+//! assert_eq!(groups.len(), 2);
+//! ```
+
+pub mod anneal;
+pub mod api;
+pub mod archive;
+pub mod balance;
+pub mod bracket;
+pub mod classroom;
+pub mod clipboard;
+pub mod constraints;
+pub mod db;
+pub mod diff;
+pub mod digest;
+pub mod error;
+pub mod genetic;
+pub mod group;
+pub mod grouping;
+pub mod history;
+pub mod http;
+pub mod ical;
+pub mod i18n;
+pub mod json;
+pub mod leader;
+pub mod leaderboard;
+pub mod log;
+pub mod merge;
+pub mod moodle;
+pub mod output;
+pub mod pattern;
+pub mod pdf;
+pub mod preference;
+pub mod presentation;
+pub mod pseudonym;
+pub mod roster;
+pub mod schedule;
+pub mod seating;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod sheets;
+pub mod signature;
+pub mod solver;
+pub mod stats;
+pub mod strategy;
+pub mod style;
+pub mod student;
+pub mod template;
+pub mod time;
+pub mod toml;
+pub mod wasm;
+pub mod webhook;
+pub mod xlsx;
+pub mod yaml;
+
+pub use error::GroupingError;
+pub use group::{group_index_to_letter, group_letter_to_index, Group, GroupNames, Student, StudentId};
+pub use grouping::{assign, regroup, GroupingConfig, Mode};
@@ -0,0 +1,381 @@
+//! Line editing for the line-based entry (`--line-mode`) at a terminal.
+//!
+//! Keys are read one at a time with echo off, so that Backspace removes what
+//! was pasted as well as what was typed, Ctrl+U clears the line and the up and
+//! down arrows recall the lines entered before. Tab completes the ID after
+//! `delete:` and `move:`. Ctrl+C is still a signal.
+
+use crate::tui::{self, Key, RawMode};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+/// Reads edited lines from `/dev/tty`, remembering them for the up arrow.
+pub struct LineEditor {
+    tty: File,
+    history: Vec<String>,
+    /// Keys read but not handled yet, such as the lines after a pasted newline
+    pending: VecDeque<Key>,
+    /// IDs that Tab completes after `delete:` and `move:`
+    completions: Vec<String>,
+    running: Arc<AtomicBool>,
+    timeout: Option<Duration>,
+}
+
+impl LineEditor {
+    /// Open the terminal. Fails when it cannot be switched out of line mode,
+    /// in which case lines should be read as they are.
+    pub fn open(running: Arc<AtomicBool>, timeout: Option<Duration>) -> io::Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        RawMode::keys(tty.as_raw_fd())?;
+        Ok(LineEditor {
+            tty,
+            history: Vec::new(),
+            pending: VecDeque::new(),
+            completions: Vec::new(),
+            running,
+            timeout,
+        })
+    }
+
+    /// IDs offered by Tab from now on.
+    pub fn set_completions(&mut self, ids: Vec<String>) {
+        self.completions = ids;
+    }
+
+    /// Read one line, or `None` at Ctrl+D on an empty line. Fails with
+    /// `TimedOut` after `--timeout` without a key, and with `Interrupted`
+    /// once Ctrl+C has been pressed.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let fd = self.tty.as_raw_fd();
+        let _raw = RawMode::keys(fd)?;
+        let mut line = Line::new(self.history.len());
+        let mut buf = [0u8; 256];
+        loop {
+            let Some(key) = self.pending.pop_front() else {
+                if !tui::wait_for_input(fd, self.timeout)? {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                let n = self.tty.read(&mut buf)?;
+                if !self.running.load(Ordering::SeqCst) {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                if n == 0 {
+                    return Ok(None);
+                }
+                self.pending.extend(tui::parse_keys(&buf[..n]));
+                continue;
+            };
+            let mut echo = String::new();
+            let edit = line.handle(key, &self.history, &self.completions, &mut echo);
+            // Echo to the terminal the keys came from, even when stdout is redirected
+            self.tty.write_all(echo.as_bytes())?;
+            self.tty.flush()?;
+            match edit {
+                Edit::Continue => {}
+                Edit::Eof => return Ok(None),
+                Edit::Submit(text) => {
+                    if !text.trim().is_empty() && self.history.last() != Some(&text) {
+                        self.history.push(text.clone());
+                    }
+                    return Ok(Some(text));
+                }
+            }
+        }
+    }
+}
+
+/// What a key press did to the line being edited.
+#[derive(Debug, PartialEq, Eq)]
+enum Edit {
+    Continue,
+    Submit(String),
+    Eof,
+}
+
+/// The line being edited and where it is in the history.
+struct Line {
+    chars: Vec<char>,
+    cursor: usize,
+    /// Index of the recalled history line; the history length while editing a new one
+    recalled: usize,
+    /// The new line, kept while history lines are shown
+    draft: Vec<char>,
+}
+
+impl Line {
+    fn new(history_len: usize) -> Self {
+        Line {
+            chars: Vec::new(),
+            cursor: 0,
+            recalled: history_len,
+            draft: Vec::new(),
+        }
+    }
+
+    /// Apply `key`, appending to `echo` what redraws the line on the terminal.
+    fn handle(&mut self, key: Key, history: &[String], completions: &[String], echo: &mut String) -> Edit {
+        match key {
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+                echo.push(c);
+                self.redraw_tail(echo);
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let removed = self.chars.remove(self.cursor);
+                echo.push_str(&left(char_width(removed)));
+                self.redraw_tail(echo);
+            }
+            Key::Delete if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+                self.redraw_tail(echo);
+            }
+            Key::CtrlU => {
+                echo.push_str(&left(width(&self.chars[..self.cursor])));
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+                self.redraw_tail(echo);
+            }
+            Key::Left if self.cursor > 0 => {
+                self.cursor -= 1;
+                echo.push_str(&left(char_width(self.chars[self.cursor])));
+            }
+            Key::Right if self.cursor < self.chars.len() => {
+                echo.push_str(&format!("\x1b[{}C", char_width(self.chars[self.cursor])));
+                self.cursor += 1;
+            }
+            Key::Up if self.recalled > 0 => {
+                if self.recalled == history.len() {
+                    self.draft = self.chars.clone();
+                }
+                self.recalled -= 1;
+                self.replace(history[self.recalled].chars().collect(), echo);
+            }
+            Key::Down if self.recalled < history.len() => {
+                self.recalled += 1;
+                let text = match history.get(self.recalled) {
+                    Some(text) => text.chars().collect(),
+                    None => std::mem::take(&mut self.draft),
+                };
+                self.replace(text, echo);
+            }
+            Key::Enter => {
+                echo.push('\n');
+                return Edit::Submit(self.chars.iter().collect());
+            }
+            Key::Tab => self.complete(completions, echo),
+            Key::CtrlD if self.chars.is_empty() => return Edit::Eof,
+            _ => {}
+        }
+        Edit::Continue
+    }
+
+    /// Write the characters after the cursor over the old ones and move back.
+    fn redraw_tail(&self, echo: &mut String) {
+        let tail = &self.chars[self.cursor..];
+        echo.extend(tail);
+        echo.push_str("\x1b[K");
+        echo.push_str(&left(width(tail)));
+    }
+
+    /// Show `chars` instead of the current line, with the cursor at its end.
+    fn replace(&mut self, chars: Vec<char>, echo: &mut String) {
+        let cursor = chars.len();
+        self.set(chars, cursor, echo);
+    }
+
+    /// Show `chars` instead of the current line, with the cursor at `cursor`.
+    fn set(&mut self, chars: Vec<char>, cursor: usize, echo: &mut String) {
+        echo.push_str(&left(width(&self.chars[..self.cursor])));
+        echo.extend(&chars);
+        echo.push_str("\x1b[K");
+        echo.push_str(&left(width(&chars[cursor..])));
+        self.chars = chars;
+        self.cursor = cursor;
+    }
+
+    /// Complete the ID before the cursor after `delete:`, `move:` or `swap:` from `ids`:
+    /// fully when only one matches, as far as they agree otherwise. When that
+    /// adds nothing, the matching IDs are listed below the line.
+    fn complete(&mut self, ids: &[String], echo: &mut String) {
+        let before: String = self.chars[..self.cursor].iter().collect();
+        let Some((start, goes_on)) = completion_start(&before) else {
+            return;
+        };
+        let fragment = &before[start..];
+        let matches: Vec<&String> = ids
+            .iter()
+            .filter(|id| id.get(..fragment.len()).is_some_and(|p| p.eq_ignore_ascii_case(fragment)))
+            .collect();
+        let completed = match matches.as_slice() {
+            [] => return,
+            // `move:` goes on with the group, `swap:` with the second ID
+            [id] if goes_on => format!("{}:", id),
+            [id] => id.to_string(),
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.as_str(), |common, id| common_prefix(common, id));
+                if common.len() <= fragment.len() {
+                    let list: Vec<&str> = matches.iter().map(|id| id.as_str()).collect();
+                    echo.push_str(&format!("\n{}\n", list.join("  ")));
+                    echo.extend(&self.chars);
+                    echo.push_str(&left(width(&self.chars[self.cursor..])));
+                    return;
+                }
+                common.to_string()
+            }
+        };
+        let mut chars: Vec<char> = before[..start].chars().chain(completed.chars()).collect();
+        let cursor = chars.len();
+        chars.extend(&self.chars[self.cursor..]);
+        self.set(chars, cursor, echo);
+    }
+}
+
+/// Where the ID to complete starts in `before`, the line up to the cursor, and
+/// whether a `:` follows it, as after the first ID of `move:` and `swap:`.
+/// `None` outside `delete:`, `move:` and `swap:`.
+fn completion_start(before: &str) -> Option<(usize, bool)> {
+    for command in ["delete:", "move:", "swap:"] {
+        if !before.get(..command.len()).is_some_and(|p| p.eq_ignore_ascii_case(command)) {
+            continue;
+        }
+        let rest = &before[command.len()..];
+        return match (command, rest.rfind(':')) {
+            ("delete:", _) => Some((before.len() - rest.trim_start().len(), false)),
+            // After `move:ID:` the group is being typed
+            ("move:", Some(_)) => None,
+            // After `swap:ID:` the second ID is
+            ("swap:", Some(colon)) => {
+                let second = &rest[colon + 1..];
+                Some((before.len() - second.trim_start().len(), false))
+            }
+            _ => Some((before.len() - rest.trim_start().len(), true)),
+        };
+    }
+    None
+}
+
+/// The longest common prefix of `a` and `b`, ignoring ASCII case as IDs are matched.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| !x.eq_ignore_ascii_case(y))
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    &a[..end]
+}
+
+/// Escape sequence moving the cursor `columns` to the left.
+fn left(columns: usize) -> String {
+    match columns {
+        0 => String::new(),
+        n => format!("\x1b[{}D", n),
+    }
+}
+
+fn width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| char_width(c)).sum()
+}
+
+/// Columns `c` takes on the terminal: two for CJK and full-width characters.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `keys` to a new line and return what Enter submits.
+    fn type_keys(keys: &[Key], history: &[String]) -> Edit {
+        let mut line = Line::new(history.len());
+        let mut echo = String::new();
+        for &key in keys {
+            if let edit @ (Edit::Submit(_) | Edit::Eof) = line.handle(key, history, &[], &mut echo) {
+                return edit;
+            }
+        }
+        Edit::Continue
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut keys: Vec<Key> = tui::parse_keys("S0x01".as_bytes());
+        // Remove the pasted `x` from the middle
+        keys.extend([Key::Left, Key::Left, Key::Backspace, Key::Enter]);
+        assert_eq!(type_keys(&keys, &[]), Edit::Submit("S001".to_string()));
+
+        let keys = [Key::Char('x'), Key::Char('y'), Key::CtrlU, Key::Char('S'), Key::Enter];
+        assert_eq!(type_keys(&keys, &[]), Edit::Submit("S".to_string()));
+        assert_eq!(type_keys(&[Key::CtrlD], &[]), Edit::Eof);
+        assert_eq!(type_keys(&[Key::Char('S'), Key::CtrlD], &[]), Edit::Continue);
+    }
+
+    #[test]
+    fn test_history() {
+        let history = vec!["S001".to_string(), "S002".to_string()];
+        assert_eq!(type_keys(&[Key::Up, Key::Up, Key::Enter], &history), Edit::Submit("S001".to_string()));
+        // Going back down past the newest line restores what was being typed
+        let keys = [Key::Char('S'), Key::Up, Key::Down, Key::Char('3'), Key::Enter];
+        assert_eq!(type_keys(&keys, &history), Edit::Submit("S3".to_string()));
+        assert_eq!(type_keys(&[Key::Up, Key::Up, Key::Up, Key::Enter], &history), Edit::Submit("S001".to_string()));
+    }
+
+    /// The line after typing `text` and pressing Tab with `ids` to complete.
+    fn tab(text: &str, ids: &[&str]) -> String {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        let mut line = Line::new(0);
+        let mut echo = String::new();
+        for c in text.chars() {
+            line.handle(Key::Char(c), &[], &ids, &mut echo);
+        }
+        line.handle(Key::Tab, &[], &ids, &mut echo);
+        line.chars.iter().collect()
+    }
+
+    #[test]
+    fn test_completion() {
+        let ids = ["S001", "S010", "S1234567"];
+        assert_eq!(tab("delete:S12", &ids), "delete:S1234567");
+        assert_eq!(tab("DELETE: s12", &ids), "DELETE: S1234567");
+        assert_eq!(tab("move:S12", &ids), "move:S1234567:");
+        // Completed as far as the matches agree
+        assert_eq!(tab("delete:S", &ids), "delete:S");
+        assert_eq!(tab("delete:S0", &ids), "delete:S0");
+        assert_eq!(tab("move:S00", &ids), "move:S001:");
+        assert_eq!(tab("delete:X", &ids), "delete:X");
+        assert_eq!(tab("move:S001:", &ids), "move:S001:");
+        assert_eq!(tab("S00", &ids), "S00");
+        assert_eq!(tab("swap:S00", &ids), "swap:S001:");
+        assert_eq!(tab("swap:S001:S12", &ids), "swap:S001:S1234567");
+        assert_eq!(tab("delete:", &["S1001", "S1002"]), "delete:S100");
+    }
+
+    #[test]
+    fn test_echo_moves_over_wide_characters() {
+        let mut line = Line::new(0);
+        let mut echo = String::new();
+        line.handle(Key::Char('学'), &[], &[], &mut echo);
+        echo.clear();
+        line.handle(Key::Backspace, &[], &[], &mut echo);
+        assert_eq!(echo, "\x1b[2D\x1b[K");
+    }
+}
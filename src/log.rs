@@ -0,0 +1,74 @@
+//! Tracing of the steps that shape the final grouping (`-v`, `-vv`).
+//!
+//! Reorganizing groups can move students in ways that are hard to follow
+//! from the result alone, such as a lone student joining a full group. With
+//! `-v` each step is written to stderr, and with `-vv` every group and
+//! student it touches as well. The level is set once for the whole process,
+//! so that the library functions do not need to pass it around.
+
+use crate::group::{group_index_to_letter, Group};
+use crate::i18n::{message, Msg};
+use crate::student::join_ids;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// How much detail a message carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// A step of the reorganization (`-v`)
+    Info = 1,
+    /// A group or student within a step (`-vv`)
+    Debug = 2,
+}
+
+/// Show messages up to `verbosity` (0: none, 1: [`Level::Info`], 2 or more: [`Level::Debug`]).
+pub fn set_verbosity(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+/// Whether messages of `level` are shown.
+pub fn enabled(level: Level) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Write a step. The message is only built when it is shown.
+pub fn info(message: impl FnOnce() -> String) {
+    if enabled(Level::Info) {
+        eprintln!("[info] {}", message());
+    }
+}
+
+/// Write a detail of a step. The message is only built when it is shown.
+pub fn debug(message: impl FnOnce() -> String) {
+    if enabled(Level::Debug) {
+        eprintln!("[debug] {}", message());
+    }
+}
+
+/// `グループ C (S007, S008)`, for the group at `index`.
+pub fn describe(index: usize, group: &Group) -> String {
+    message(Msg::TraceGroup, &[&group_index_to_letter(index), &join_ids(&group.members, ", ")])
+}
+
+/// Group sizes such as `3+3+2`.
+pub fn sizes(groups: &[Group]) -> String {
+    let sizes: Vec<String> = groups.iter().map(|g| g.members.len().to_string()).collect();
+    sizes.join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_and_descriptions() {
+        assert!(Level::Info < Level::Debug);
+        let groups = vec![
+            Group::from_members(vec!["S001".to_string(), "S002".to_string(), "S003".to_string()]),
+            Group::from_members(vec!["S004".to_string()]),
+        ];
+        assert_eq!(describe(1, &groups[1]), "グループ B (S004)");
+        assert_eq!(sizes(&groups), "3+1");
+    }
+}
@@ -1,66 +1,108 @@
-use rand::seq::SliceRandom;
+mod audit;
+mod cli;
+mod config;
+mod entry;
+#[cfg(unix)]
+mod lineedit;
+mod session;
+#[cfg(unix)]
+mod tui;
+
+use audit::{AuditLog, Event, Replay};
+use entry::{Entry, MoveError, Undone};
+use session::Session;
+use grouping_tool::anneal;
+use grouping_tool::archive;
+use grouping_tool::bracket::{self, Bracket, Elimination};
+use grouping_tool::classroom;
+use grouping_tool::constraints::{self, Constraints};
+use grouping_tool::db::{self, Database};
+use grouping_tool::error::GroupingError;
+use grouping_tool::history::{self, History};
+use grouping_tool::i18n::{self, Lang, Msg};
+use grouping_tool::ical;
+use grouping_tool::leader;
+use grouping_tool::leaderboard::{self, Standing};
+use grouping_tool::grouping::{self, GroupingConfig, Mode, Shortfall, Sizing, StreamSplit};
+use grouping_tool::clipboard;
+use grouping_tool::output::{self, GroupWriter, GroupingResult, MemberLabels, Metadata, OutputFormat};
+use grouping_tool::pattern::{Pattern, DEFAULT_ID_PATTERN};
+use grouping_tool::pdf;
+use grouping_tool::preference::Preferences;
+use grouping_tool::presentation;
+use grouping_tool::pseudonym::{self, Pseudonyms};
+use grouping_tool::moodle;
+use grouping_tool::roster::{self, Column, CsvOptions, RosterEntry, RosterFormat};
+use grouping_tool::schedule;
+use grouping_tool::seating::{self, Layout};
+use grouping_tool::server;
+use grouping_tool::sheets;
+use grouping_tool::signature;
+use grouping_tool::solver;
+use grouping_tool::stats::{Edits, Stats};
+use grouping_tool::style::Style;
+use grouping_tool::template::Template;
+use grouping_tool::webhook;
+use grouping_tool::xlsx;
+use grouping_tool::student::Student;
+use grouping_tool::{group_index_to_letter, Group, GroupNames, StudentId};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use rand::SeedableRng;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
-
-type StudentId = String;
-
-// Convert a group index (0-based) to a letter (A, B, C, ...)
-fn group_index_to_letter(index: usize) -> String {
-    let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-    if index < alphabet.len() {
-        alphabet.chars().nth(index).unwrap().to_string()
-    } else {
-        // For indices beyond Z, use AA, AB, AC, etc.
-        let first = (index / 26) - 1;
-        let second = index % 26;
-        format!(
-            "{}{}",
-            alphabet.chars().nth(first).unwrap(),
-            alphabet.chars().nth(second).unwrap()
-        )
-    }
-}
-
-#[derive(Debug, Clone)]
-struct Group {
-    members: Vec<StudentId>,
-}
-
-impl Group {
-    fn new() -> Self {
-        Group {
-            members: Vec::new(),
-        }
-    }
-
-    fn add_member(&mut self, student_id: StudentId) {
-        if self.members.len() < 3 {
-            self.members.push(student_id);
-        }
-    }
-
-    fn is_full(&self) -> bool {
-        self.members.len() >= 3
-    }
+use std::time::Duration;
+
+// Exit statuses besides 0, 1 (other errors) and 2 (invalid options), so that
+// wrapper scripts can tell the failures apart
+/// No students were given
+const EXIT_NO_INPUT: i32 = 3;
+/// The grouping was written, but some constraints could not be satisfied
+const EXIT_UNSATISFIED: i32 = 4;
+/// The grouping was written, but lines with invalid IDs were skipped
+const EXIT_INVALID_ID: i32 = 5;
+/// Reading the input or writing the result failed
+const EXIT_IO: i32 = 6;
+/// The file given to `verify` does not match its signature
+const EXIT_BAD_SIGNATURE: i32 = 7;
+/// `replay` made a grouping different from the one in the session log
+const EXIT_REPLAY_DIFFERS: i32 = 8;
+
+/// Folder `plan` writes its files to when `--out` is not given.
+const DEFAULT_PLAN_DIR: &str = "plan";
+
+/// Students read from stdin or from a roster file.
+struct Input {
+    groups: Vec<Group>,
+    batch_mode: bool,
+    /// Display names found in the roster
+    names: HashMap<StudentId, String>,
+    /// Attributes given for each student, used by `--balance`
+    students: HashMap<StudentId, Student>,
+    /// Sizes given before groups in batch input (`@size=N` or `#N`), by group index
+    sizes: HashMap<usize, usize>,
+    /// Groups pinned with `!` or `pin:`, by group index
+    pins: HashSet<usize>,
+    /// Lines of batch input skipped for an invalid ID or attributes
+    rejected: usize,
+    /// Edits made while the students were entered interactively, for `--stats`
+    edits: Option<Edits>,
 }
 
-fn read_student_ids(running: Arc<AtomicBool>) -> (Vec<Group>, bool) {
-    let mut groups = Vec::new();
-    let mut current_group = Group::new();
-
-    // Check if stdin is a TTY (interactive terminal)
+/// Check if stdin is a TTY (interactive terminal)
+fn stdin_is_tty() -> bool {
     #[cfg(unix)]
-    let is_tty = {
+    {
         use std::os::unix::io::AsRawFd;
         unsafe { libc::isatty(io::stdin().as_raw_fd()) == 1 }
-    };
+    }
 
     #[cfg(windows)]
-    let is_tty = {
+    {
         use std::os::windows::io::AsRawHandle;
         let handle = io::stdin().as_raw_handle();
         let mut mode: u32 = 0;
@@ -72,820 +114,2723 @@ fn read_student_ids(running: Arc<AtomicBool>) -> (Vec<Group>, bool) {
             }
             GetConsoleMode(handle as *mut std::ffi::c_void, &mut mode) != 0
         }
-    };
+    }
 
     #[cfg(not(any(unix, windows)))]
-    let is_tty = true;
+    {
+        true
+    }
+}
+
+/// Colors for what is printed to the terminal, unless `--no-color` is given.
+fn terminal_style(options: &cli::Options) -> Style {
+    // The Windows console only shows ANSI colors once they are switched on
+    Style::detect(options.no_color, cfg!(unix) && io::stdout().is_terminal())
+}
+
+/// The same for prompts and messages, which go to stderr.
+fn message_style(options: &cli::Options) -> Style {
+    Style::detect(options.no_color, cfg!(unix) && io::stderr().is_terminal())
+}
+
+/// Lines typed during the line-based entry. With a `timeout` (`--timeout`),
+/// they end early when nothing arrives for that long, and stay ended.
+struct EntryLines {
+    reader: BufReader<Box<dyn Read>>,
+    fd: Option<i32>,
+    /// Line editing at the terminal, where it can be switched on
+    #[cfg(unix)]
+    editor: Option<lineedit::LineEditor>,
+    timeout: Option<Duration>,
+    timed_out: bool,
+}
+
+impl EntryLines {
+    /// Lines edited on `/dev/tty` when `is_tty` on Unix, read from stdin
+    /// otherwise. Ctrl+C, noted in `running`, ends the editing.
+    fn open(is_tty: bool, timeout: Option<Duration>, running: Arc<AtomicBool>) -> Self {
+        #[cfg(unix)]
+        let editor = is_tty.then(|| lineedit::LineEditor::open(running, timeout).ok()).flatten();
+        #[cfg(not(unix))]
+        let _ = running;
+        #[cfg(unix)]
+        let (reader, fd): (Box<dyn Read>, Option<i32>) = {
+            use std::os::unix::io::AsRawFd;
+            match (is_tty && editor.is_none()).then(|| File::open("/dev/tty").ok()).flatten() {
+                Some(tty) => {
+                    let fd = tty.as_raw_fd();
+                    (Box::new(tty), Some(fd))
+                }
+                None => (Box::new(io::stdin()), Some(io::stdin().as_raw_fd())),
+            }
+        };
+        #[cfg(not(unix))]
+        let (reader, fd): (Box<dyn Read>, Option<i32>) = {
+            let _ = is_tty;
+            (Box::new(io::stdin()), None)
+        };
+        EntryLines {
+            reader: BufReader::new(reader),
+            fd,
+            #[cfg(unix)]
+            editor,
+            timeout,
+            timed_out: false,
+        }
+    }
+
+    /// IDs that Tab completes after `delete:` and `move:`, where lines are edited.
+    /// They are only listed when there is an editor, so that piped input stays linear.
+    fn set_completions(&mut self, ids: impl FnOnce() -> Vec<StudentId>) {
+        #[cfg(unix)]
+        if let Some(editor) = &mut self.editor {
+            editor.set_completions(ids());
+        }
+        #[cfg(not(unix))]
+        let _ = ids;
+    }
+
+    /// Whether input can be read before the timeout, if any.
+    fn ready(&self) -> bool {
+        if !self.reader.buffer().is_empty() {
+            return true;
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.fd {
+            return tui::wait_for_input(fd, self.timeout).unwrap_or(true);
+        }
+        true
+    }
+}
+
+impl Iterator for EntryLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.timed_out {
+            return None;
+        }
+        #[cfg(unix)]
+        if let Some(editor) = &mut self.editor {
+            return match editor.read_line() {
+                Ok(line) => line.map(Ok),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    self.timed_out = true;
+                    None
+                }
+                Err(e) => Some(Err(e)),
+            };
+        }
+        if !self.ready() {
+            self.timed_out = true;
+            return None;
+        }
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                let end = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(end);
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Read student IDs from stdin. When `attribute_names` is not empty, each line
+/// may carry attributes after the ID (e.g. `S001,female,advanced`). IDs not
+/// matching `id_pattern` are rejected. Groups are labelled with `labels` in
+/// prompts, which are shown in `lang`. In batch mode (piped input) there are
+/// no prompts and blank lines separate groups. Entry continues from `initial`,
+/// and added students are confirmed with their `names`, if known.
+fn read_student_ids(
+    options: &cli::Options,
+    running: Arc<AtomicBool>,
+    id_pattern: Option<&Pattern>,
+    labels: &GroupNames,
+    lang: Lang,
+    initial: Session,
+    names: &HashMap<StudentId, String>,
+) -> Input {
+    let batch_mode = options.command == cli::Command::Batch || !stdin_is_tty();
+    let attribute_names = &options.attribute_names();
+    // In batch mode, groups are unlimited in size
+    let limit = if batch_mode { None } else { options.sizing().max_size() };
+    let mut entry = Entry::resume(initial.groups, initial.current, limit);
+    let mut students = initial.students;
+    let mut sizes = HashMap::new();
+    let mut pins = HashSet::new();
+    let mut rejected = 0;
+    // A resumed entry is already in its file
+    let mut saved = entry.revision();
+    // Nothing is edited by hand in batch mode
+    let mut audit = AuditLog::new(options.session_log.as_deref().filter(|_| !batch_mode));
+
+    // Groups are typed one after another at the terminal, ending each with EOF
+    let is_tty = !batch_mode && stdin_is_tty();
+    let style = message_style(options);
+    let timeout = if batch_mode { None } else { options.idle_timeout() };
 
-    // In batch mode (non-interactive), blank lines separate groups
-    let batch_mode = !is_tty;
 
     if !batch_mode {
-        println!("学籍番号を入力してください (3人ごとにグループになります):");
-        println!("  - Ctrl+D (Unix/Mac) または Ctrl+Z+Enter (Windows): 現在のグループを終了して次のグループへ");
-        println!("  - Ctrl+C: プログラムを終了");
-        println!("  - 'delete:学籍番号' と入力すると、その学籍番号を削除できます（例: delete:S001）");
-        println!();
+        match entry.limit() {
+            Some(limit) => say(options, &lang.format(Msg::EntryIntro, &[&limit])),
+            None => say(options, lang.text(Msg::EntryIntroLater)),
+        }
+        say(options, lang.text(Msg::EntryHelpFinish));
+        say(options, lang.text(Msg::EntryHelpQuit));
+        say(options, lang.text(Msg::EntryHelpDelete));
+        say(options, lang.text(Msg::EntryHelpMove));
+        say(options, lang.text(Msg::EntryHelpSwap));
+        say(options, lang.text(Msg::EntryHelpAdd));
+        say(options, lang.text(Msg::EntryHelpPin));
+        say(options, lang.text(Msg::EntryHelpUndo));
+        say(options, "");
+        let start = Event::Start {
+            limit: entry.limit(),
+            screen: false,
+            groups: entry.current_index(),
+            students: entry.ids().len(),
+        };
+        log_edit(&mut audit, start, lang);
     }
 
-    let mut group_index = 0;
+    // Read input - use /dev/tty only in interactive mode on Unix. Once open, the
+    // lines typed for earlier groups can be recalled in later ones.
+    let mut lines = EntryLines::open(is_tty, timeout, running.clone());
+    let mut line_number = 0;
     if !batch_mode {
-        println!(
-            "=== グループ {} の入力 ===",
-            group_index_to_letter(group_index)
-        );
+        say(options, &style.heading(&lang.format(Msg::EntryGroupHeading, &[&labels.label(entry.current_index())])));
     }
 
-    loop {
+    'input: loop {
         // Check if Ctrl+C was pressed
         if !running.load(Ordering::SeqCst) {
-            // Save current group if it has members before exiting
-            if !current_group.members.is_empty() {
-                groups.push(current_group.clone());
-            }
             break;
         }
 
-        // Read input - use /dev/tty only in interactive mode on Unix
-        let reader: Box<dyn BufRead> = if is_tty && cfg!(unix) {
-            File::open("/dev/tty")
-                .map(|f| Box::new(BufReader::new(f)) as Box<dyn BufRead>)
-                .unwrap_or_else(|_| Box::new(BufReader::new(io::stdin())))
-        } else {
-            Box::new(BufReader::new(io::stdin()))
-        };
+        loop {
+            // Save before waiting for the next line, so nothing typed is lost
+            if let Some(path) = options.session_file() {
+                checkpoint(path, &entry, &students, &mut saved, lang);
+            }
+            lines.set_completions(|| entry.ids());
+            let Some(line) = lines.next() else {
+                break;
+            };
 
-        let mut eof_encountered = false;
-        for line in reader.lines() {
             // Check if Ctrl+C was pressed
             if !running.load(Ordering::SeqCst) {
-                if !current_group.members.is_empty() {
-                    groups.push(current_group.clone());
+                break 'input;
+            }
+
+            line_number += 1;
+            let student_id = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("{}", lang.format(Msg::ReadFailed, &[&e]));
+                    std::process::exit(EXIT_IO);
                 }
-                return (groups, batch_mode);
-            }
-
-            match line {
-                Ok(student_id) => {
-                    let student_id = student_id.trim().to_string();
-                    
-                    // In batch mode, empty lines separate groups
-                    if student_id.is_empty() {
-                        if batch_mode && !current_group.members.is_empty() {
-                            // Save current group and start a new one
-                            groups.push(current_group.clone());
-                            current_group = Group::new();
-                            group_index += 1;
-                        }
-                        continue;
+            };
+            let student_id = student_id.trim().to_string();
+
+            // In batch mode, empty lines separate groups
+            if student_id.is_empty() {
+                if batch_mode {
+                    entry.complete();
+                }
+                continue;
+            }
+
+            // A header such as `@size=4` or `#4` sets the size of the group it starts
+            if let Some(size) = size_header(&student_id).filter(|_| batch_mode) {
+                match size {
+                    Some(size) => {
+                        sizes.insert(entry.current_index(), size);
                     }
-                    
-                    // Check if this is a delete command
-                    if student_id.to_lowercase().starts_with("delete:") {
-                        let id_to_delete = student_id[7..].trim().to_string();
-                        
-                        // Try to delete from current group first
-                        let mut deleted = false;
-                        if let Some(pos) = current_group.members.iter().position(|x| x == &id_to_delete) {
-                            current_group.members.remove(pos);
-                            println!("  ✓ 削除しました: {} (現在のグループから)", id_to_delete);
-                            deleted = true;
+                    None => eprintln!("{}", lang.format(Msg::SizeHeaderSkipped, &[&line_number, &student_id])),
+                }
+                continue;
+            }
+
+            // `!` pins the group being entered, and `pin:B` / `unpin:B` any group
+            if let Some((pin, label)) = pin_command(&student_id).filter(|(_, label)| !batch_mode || label.is_none()) {
+                let group = match label {
+                    None => entry.current_index(),
+                    Some(label) => match labels.index_of(label).filter(|&i| i <= entry.current_index()) {
+                        Some(group) => group,
+                        None => {
+                            say(options, &style.message(&lang.format(Msg::NoSuchGroup, &[&label])));
+                            continue;
                         }
-                        
-                        // If not found in current group, search in completed groups
-                        if !deleted {
-                            for (i, group) in groups.iter_mut().enumerate() {
-                                if let Some(pos) = group.members.iter().position(|x| x == &id_to_delete) {
-                                    group.members.remove(pos);
-                                    println!(
-                                        "  ✓ 削除しました: {} (グループ {} から)",
-                                        id_to_delete,
-                                        group_index_to_letter(i)
-                                    );
-                                    deleted = true;
-                                    break;
+                    },
+                };
+                let msg = if pin {
+                    pins.insert(group);
+                    Msg::Pinned
+                } else {
+                    pins.remove(&group);
+                    Msg::Unpinned
+                };
+                if !batch_mode {
+                    say(options, &style.message(&lang.format(msg, &[&labels.label(group)])));
+                    log_edit(&mut audit, Event::Pin { group, pinned: pin }, lang);
+                }
+                continue;
+            }
+
+            if !batch_mode && student_id.eq_ignore_ascii_case("undo") {
+                let undone = entry.undo();
+                if undone.is_some() {
+                    log_edit(&mut audit, Event::Undo, lang);
+                }
+                match undone {
+                    Some(Undone::Add(id)) => {
+                        students.remove(&id);
+                        say(options, &lang.format(Msg::UndoneAdd, &[&id]));
+                    }
+                    Some(Undone::Delete(id, group)) => {
+                        say(options, &lang.format(Msg::UndoneDelete, &[&id, &labels.label(group)]));
+                    }
+                    Some(Undone::Move(id, group)) => {
+                        say(options, &lang.format(Msg::UndoneMove, &[&id, &labels.label(group)]));
+                    }
+                    Some(Undone::Swap(first, second)) => {
+                        say(options, &lang.format(Msg::UndoneSwap, &[&first, &second]));
+                    }
+                    Some(Undone::Complete(group)) => {
+                        say(options, &lang.format(Msg::UndoneComplete, &[&labels.label(group)]));
+                    }
+                    None => say(options, &style.message(lang.text(Msg::NothingToUndo))),
+                }
+                say(options, &lang.format(Msg::NowEntering, &[&labels.label(entry.current_index())]));
+                continue;
+            }
+
+            // Check if this is a delete command
+            if student_id.to_lowercase().starts_with("delete:") {
+                let id_to_delete = student_id[7..].trim().to_string();
+                let deleted = match entry.delete(&id_to_delete) {
+                    Some(group) => Some((id_to_delete, group)),
+                    None => {
+                        say(options, &style.message(&lang.format(Msg::NotFound, &[&id_to_delete])));
+                        // Offer to delete the ID that was probably meant
+                        let similar = entry.similar_ids(&id_to_delete);
+                        match similar.as_slice() {
+                            [] => None,
+                            [id] if !batch_mode && !options.quiet => {
+                                eprint!("{}", lang.format(Msg::DeleteSuggestion, &[id]));
+                                let _ = io::stderr().flush();
+                                line_number += 1;
+                                match lines.next() {
+                                    Some(Ok(answer)) if answer.trim().eq_ignore_ascii_case("y") => {
+                                        entry.delete(id).map(|group| (id.clone(), group))
+                                    }
+                                    _ => None,
                                 }
                             }
+                            ids => {
+                                say(options, &lang.format(Msg::DidYouMean, &[&ids.join(", ")]));
+                                None
+                            }
                         }
-                        
-                        if !deleted {
-                            println!("  ✗ エラー: {} は見つかりませんでした", id_to_delete);
-                        }
-                    } else {
-                        // Normal student ID addition
+                    }
+                };
+                if let Some((id, group)) = &deleted {
+                    log_edit(&mut audit, Event::Delete { id: id.clone(), group: *group }, lang);
+                }
+                match deleted {
+                    Some((id, group)) if group == entry.current_index() => {
+                        say(options, &style.message(&lang.format(Msg::DeletedFromCurrent, &[&id])));
+                    }
+                    Some((id, group)) => {
+                        say(options, &style.message(&lang.format(Msg::DeletedFromGroup, &[&id, &labels.label(group)])));
+                    }
+                    None => {}
+                }
+                continue;
+            }
+
+            // Move command such as `move:S001:B`
+            if student_id.to_lowercase().starts_with("move:") {
+                move_student(&mut entry, &mut audit, &student_id[5..], options, labels, lang, style);
+                continue;
+            }
+
+            // Swap command such as `swap:S001:S014`
+            if student_id.to_lowercase().starts_with("swap:") {
+                swap_students(&mut entry, &mut audit, &student_id[5..], options, labels, lang, style);
+                continue;
+            }
+
+            // `add:S001:B` adds to group B instead of the group being entered
+            let mut target = None;
+            let student_id = if student_id.to_lowercase().starts_with("add:") {
+                let Some((line, label)) = student_id[4..].rsplit_once(':').filter(|(line, _)| !line.trim().is_empty()) else {
+                    say(options, &style.message(lang.text(Msg::AddUsage)));
+                    continue;
+                };
+                match labels.index_of(label).filter(|&i| i <= entry.current_index()) {
+                    Some(group) => target = Some(group),
+                    None => {
+                        say(options, &style.message(&lang.format(Msg::NoSuchGroup, &[&label.trim()])));
+                        continue;
+                    }
+                }
+                line.trim().to_string()
+            } else {
+                student_id
+            };
+
+            // Split off attributes such as `S001,female,advanced`
+            let student = if attribute_names.is_empty() {
+                None
+            } else {
+                match Student::parse_line(&student_id, attribute_names) {
+                    Ok(student) => Some(student),
+                    Err(e) => {
                         if batch_mode {
-                            // In batch mode, groups are unlimited in size (no 3-person limit)
-                            current_group.members.push(student_id.clone());
+                            eprintln!("{}", lang.format(Msg::InvalidLineSkipped, &[&line_number, &e]));
+                            rejected += 1;
                         } else {
-                            // In interactive mode, use the 3-person limit
-                            current_group.add_member(student_id.clone());
-                            println!("  追加: {}", student_id);
-
-                            if current_group.is_full() {
-                                println!(
-                                    "  ✓ グループ {} が完成しました (3人)",
-                                    group_index_to_letter(group_index)
-                                );
-                                groups.push(current_group.clone());
-                                current_group = Group::new();
-                                group_index += 1;
-                                println!(
-                                    "\n=== グループ {} の入力 ===",
-                                    group_index_to_letter(group_index)
-                                );
-                            }
+                            say(options, &style.message(&lang.format(Msg::InvalidLine, &[&e])));
                         }
+                        continue;
                     }
                 }
+            };
+            let student_id = student.as_ref().map_or(student_id, |s| s.id.clone());
+
+            // Reject typos such as `S0O1` before they end up in a group
+            if let Some(pattern) = id_pattern.filter(|p| !p.is_match(&student_id)) {
+                if batch_mode {
+                    eprintln!("{}", lang.format(Msg::InvalidIdSkipped, &[&line_number, &student_id, pattern]));
+                    rejected += 1;
+                } else {
+                    say(options, &style.message(&lang.format(Msg::InvalidId, &[&student_id, pattern])));
+                }
+                continue;
+            }
+
+            // The same student cannot be in two groups
+            if let Some(index) = entry.group_of(&student_id) {
+                let label = labels.label(index);
+                if batch_mode {
+                    eprintln!("{}", lang.format(Msg::DuplicateSkipped, &[&line_number, &student_id, &label]));
+                } else {
+                    say(options, &style.message(&lang.format(Msg::Duplicate, &[&student_id, &label, &student_id])));
+                }
+                continue;
+            }
+            let group_index = target.unwrap_or(entry.current_index());
+            let completed = match entry.add_to(student_id.clone(), group_index) {
+                Ok(completed) => completed,
                 Err(_) => {
-                    eof_encountered = true;
-                    break;
+                    say(options, &style.message(&lang.format(Msg::GroupFull, &[&labels.label(group_index)])));
+                    continue;
+                }
+            };
+            if let Some(student) = student {
+                students.insert(student_id.clone(), student);
+            }
+            if !batch_mode {
+                let added = match names.get(&student_id) {
+                    Some(name) => format!("{} {}", student_id, name),
+                    None => student_id.clone(),
+                };
+                match target {
+                    Some(group) => say(options, &lang.format(Msg::AddedTo, &[&added, &labels.label(group)])),
+                    None => say(options, &lang.format(Msg::Added, &[&added])),
+                }
+                let student = students.get(&student_id).cloned().unwrap_or_else(|| Student::new(student_id.clone()));
+                log_edit(&mut audit, Event::Add { student, group: group_index }, lang);
+                if completed {
+                    let size = entry.groups()[group_index].members.len();
+                    log_edit(&mut audit, Event::Complete { group: group_index, size }, lang);
+                    say(options, &style.message(&lang.format(Msg::GroupCompleted, &[&labels.label(group_index), &size])));
+                    say(options, &format!("\n{}", style.heading(&lang.format(Msg::EntryGroupHeading, &[&labels.label(entry.current_index())]))));
                 }
             }
         }
 
-        // EOF was encountered
-        if !eof_encountered {
-            // lines iterator ended naturally (EOF)
-            eof_encountered = true;
+        // With --timeout, nobody is typing any more: save the current group and finish
+        if lines.timed_out {
+            say(options, &format!("\n{}", lang.format(Msg::TimedOut, &[&options.timeout.unwrap_or_default()])));
         }
 
-        if eof_encountered {
-            // Save current group if it has members
-            if !current_group.members.is_empty() {
-                if !batch_mode {
-                    println!(
-                        "  ✓ グループ {} を保存しました ({} 人)",
-                        group_index_to_letter(group_index),
-                        current_group.members.len()
-                    );
-                }
-                groups.push(current_group.clone());
-                current_group = Group::new();
-                group_index += 1;
-
-                // Only continue for multiple groups if we're in interactive TTY mode with /dev/tty
-                if is_tty && cfg!(unix) && File::open("/dev/tty").is_ok() {
-                    println!(
-                        "\n=== グループ {} の入力 ===",
-                        group_index_to_letter(group_index)
-                    );
-                    // Continue loop to read next group
-                    continue;
-                }
+        // EOF was encountered: save the current group if it has members
+        let group_index = entry.current_index();
+        let count = entry.current().members.len();
+        if entry.complete() {
+            if !batch_mode {
+                say(options, &style.message(&lang.format(Msg::GroupSaved, &[&labels.label(group_index), &count])));
+                log_edit(&mut audit, Event::Complete { group: group_index, size: count }, lang);
             }
 
-            // Exit the loop if not in interactive mode
-            break;
+            // Only continue for multiple groups if we're in interactive TTY mode with /dev/tty
+            if is_tty && !lines.timed_out && cfg!(unix) && File::open("/dev/tty").is_ok() {
+                say(options, &format!("\n{}", style.heading(&lang.format(Msg::EntryGroupHeading, &[&labels.label(entry.current_index())]))));
+                // Continue loop to read next group
+                continue;
+            }
         }
-    }
 
-    // Save the current group if it has any members
-    if !current_group.members.is_empty() {
-        groups.push(current_group);
+        // Exit the loop if not in interactive mode
+        break;
     }
 
-    (groups, batch_mode)
+    Input {
+        groups: entry.into_groups(),
+        batch_mode,
+        names: HashMap::new(),
+        students,
+        sizes,
+        pins,
+        rejected,
+        edits: (!batch_mode).then(|| audit.edits()),
+    }
 }
 
-/// Helper function to split a list of members into groups of 2-3 people
-fn split_into_small_groups(members: Vec<StudentId>) -> Vec<Group> {
-    let mut result: Vec<Group> = Vec::new();
-    let n = members.len();
-    
-    if n == 0 {
-        return result;
+/// A pin command: `!` or `pin:` pins the current group, `pin:B` group B and
+/// `unpin:`/`unpin:B` undo them. Returns whether to pin and the group given, if any.
+fn pin_command(line: &str) -> Option<(bool, Option<&str>)> {
+    if line == "!" {
+        return Some((true, None));
     }
-    
-    // Edge case: single member should not create a singleton
-    // This shouldn't happen in normal use since we only split groups > 3
-    if n == 1 {
-        let mut group = Group::new();
-        group.members.push(members[0].clone());
-        result.push(group);
-        return result;
+    let (command, label) = line.split_once(':')?;
+    let pin = match command.trim().to_lowercase().as_str() {
+        "pin" => true,
+        "unpin" => false,
+        _ => return None,
+    };
+    let label = label.trim();
+    Some((pin, Some(label).filter(|label| !label.is_empty())))
+}
+
+/// The size in a group header of batch input, `@size=N` or `#N`: `None` for
+/// other lines, `Some(None)` when the size is not a number of at least 2.
+fn size_header(line: &str) -> Option<Option<usize>> {
+    let value = match line.get(..6) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("@size=") => &line[6..],
+        _ => line.strip_prefix('#').filter(|rest| rest.trim().starts_with(|c: char| c.is_ascii_digit()))?,
+    };
+    Some(value.trim().parse().ok().filter(|&size| size >= 2))
+}
+
+/// Write the entry to `path` (`--save-session`) when it changed since the
+/// revision `saved`.
+fn checkpoint(path: &str, entry: &Entry, students: &HashMap<StudentId, Student>, saved: &mut usize, lang: Lang) {
+    if entry.revision() == *saved {
+        return;
     }
-    
-    let mut idx = 0;
-    while idx < n {
-        let remaining = n - idx;
-        
-        let group_size = if remaining >= 3 {
-            if remaining == 4 {
-                // 4 -> 2 + 2
-                2
-            } else {
-                3
-            }
-        } else {
-            // 2 remaining (1 is not possible when n >= 2 due to the algorithm)
-            remaining
-        };
-        
-        let mut new_group = Group::new();
-        for i in 0..group_size {
-            new_group.members.push(members[idx + i].clone());
-        }
-        idx += group_size;
-        result.push(new_group);
+    let session = Session {
+        groups: entry.groups().to_vec(),
+        current: entry.current().clone(),
+        students: students.clone(),
+    };
+    if let Err(e) = session.save(path) {
+        eprintln!("{}", lang.format(Msg::SessionSaveFailed, &[&e]));
     }
-    
-    result
+    *saved = entry.revision();
 }
 
-/// Reorganize groups from batch mode - collect all members and create optimal groups
-/// 2-person groups are only created when the total is NOT divisible by 3 (at most 1-2 such groups)
-/// Maximizes 3-person groups as much as possible
-fn reorganize_batch_groups(groups: Vec<Group>) -> Vec<Group> {
-    if groups.is_empty() {
-        return groups;
+/// Record `event` in the session log (`--session-log`), warning when it cannot be written.
+fn log_edit(audit: &mut AuditLog, event: Event, lang: Lang) {
+    if let Err(e) = audit.record(&event) {
+        eprintln!("{}", lang.format(Msg::SessionLogFailed, &[&e]));
     }
+}
 
-    // Collect all members from all groups
-    let mut all_members: Vec<StudentId> = Vec::new();
-    for group in groups {
-        all_members.extend(group.members);
+/// Handle `move:<ID>:<GROUP>` given as `<ID>:<GROUP>`.
+fn move_student(
+    entry: &mut Entry,
+    audit: &mut AuditLog,
+    args: &str,
+    options: &cli::Options,
+    labels: &GroupNames,
+    lang: Lang,
+    style: Style,
+) {
+    let Some((id, label)) = args.rsplit_once(':') else {
+        say(options, &style.message(lang.text(Msg::MoveUsage)));
+        return;
+    };
+    let id = id.trim();
+    let Some(to) = labels.index_of(label) else {
+        say(options, &style.message(&lang.format(Msg::NoSuchGroup, &[&label.trim()])));
+        return;
+    };
+    match entry.move_to(id, to) {
+        Ok((from, completed)) => {
+            log_edit(audit, Event::Move { id: id.to_string(), from, to }, lang);
+            say(options, &style.message(&lang.format(Msg::Moved, &[&id, &labels.label(from), &labels.label(to)])));
+            if completed {
+                let size = entry.groups()[to].members.len();
+                log_edit(audit, Event::Complete { group: to, size }, lang);
+                say(options, &style.message(&lang.format(Msg::GroupCompleted, &[&labels.label(to), &size])));
+                say(options, &format!("\n{}", style.heading(&lang.format(Msg::EntryGroupHeading, &[&labels.label(entry.current_index())]))));
+            }
+        }
+        Err(MoveError::NotFound) => say(options, &style.message(&lang.format(Msg::NotFound, &[&id]))),
+        Err(MoveError::NoSuchGroup) => say(options, &style.message(&lang.format(Msg::NoSuchGroup, &[&labels.label(to)]))),
+        Err(MoveError::SameGroup) => {
+            say(options, &style.message(&lang.format(Msg::AlreadyInGroup, &[&id, &labels.label(to)])))
+        }
+        Err(MoveError::Full) => say(options, &style.message(&lang.format(Msg::GroupFull, &[&labels.label(to)]))),
     }
-
-    // Use split_into_small_groups to create optimal groupings
-    // This maximizes 3-person groups and only creates 2-person groups when n % 3 != 0
-    split_into_small_groups(all_members)
 }
 
-fn reorganize_incomplete_groups(groups: Vec<Group>) -> Vec<Group> {
-    let mut final_groups = Vec::new();
-    let mut incomplete_members = Vec::new();
-
-    // Separate complete and incomplete groups (requirements 3 and 4)
-    for group in groups {
-        if group.is_full() {
-            // Requirement 3: Don't modify groups with 3 members
-            final_groups.push(group);
-        } else {
-            // Collect members from incomplete groups
-            incomplete_members.extend(group.members);
-        }
-    }
-
-    // Requirement 4: Randomly combine incomplete group members
-    let mut rng = rand::thread_rng();
-    incomplete_members.shuffle(&mut rng);
-
-    let n = incomplete_members.len();
-    
-    // NEW REQUIREMENT: Never create single-person groups
-    // UPDATED: Prefer groups of 2-3 people, not 4
-    if n == 1 {
-        // If we have exactly 1 incomplete member and at least one complete group,
-        // we should avoid creating a 4-person group
-        if !final_groups.is_empty() {
-            if let Some(last_group) = final_groups.last_mut() {
-                // Take 1 member from the last complete group and pair with the singleton
-                // to create two 2-person groups instead of one 4-person group
-                if last_group.members.len() == 3 {
-                    let member_from_last = last_group.members.pop().unwrap();
-                    let mut new_group = Group::new();
-                    new_group.members.push(member_from_last);
-                    new_group.members.push(incomplete_members.into_iter().next().unwrap());
-                    final_groups.push(new_group);
-                } else {
-                    // If the last group doesn't have 3 members, just add to it
-                    last_group.members.push(incomplete_members.into_iter().next().unwrap());
-                }
-            }
-        } else {
-            // If we have no complete groups and only 1 member total, we cannot form valid groups
-            // This case should be handled by the caller
-            println!("警告: 1人だけではグループを作成できません。最低2人必要です。");
-        }
-        return final_groups;
-    }
-
-    // Create new groups from the shuffled members
-    // Strategy: create groups of 3, but ensure the last group has at least 2 members
-    let mut idx = 0;
-    while idx < n {
-        let remaining = n - idx;
-        
-        if remaining >= 3 {
-            // If we can make a group of 3 or more
-            let group_size = if remaining == 4 {
-                // Special case: 4 remaining should be split into 2+2, not 3+1
-                2
-            } else {
-                3
+/// Handle `swap:<ID>:<ID>` given as `<ID>:<ID>`.
+fn swap_students(
+    entry: &mut Entry,
+    audit: &mut AuditLog,
+    args: &str,
+    options: &cli::Options,
+    labels: &GroupNames,
+    lang: Lang,
+    style: Style,
+) {
+    let Some((first, second)) = args.split_once(':').filter(|(a, b)| !a.trim().is_empty() && !b.trim().is_empty()) else {
+        say(options, &style.message(lang.text(Msg::SwapUsage)));
+        return;
+    };
+    let (first, second) = (first.trim(), second.trim());
+    match entry.swap(first, second) {
+        Ok((group, with_group)) => {
+            let event = Event::Swap {
+                id: first.to_string(),
+                group,
+                with: second.to_string(),
+                with_group,
             };
-            
-            let mut new_group = Group::new();
-            for _ in 0..group_size {
-                if idx < n {
-                    new_group.members.push(incomplete_members[idx].clone());
-                    idx += 1;
-                }
-            }
-            final_groups.push(new_group);
-        } else {
-            // remaining is 1 or 2
-            if remaining == 2 {
-                // Make a 2-person group
-                let mut new_group = Group::new();
-                new_group.members.push(incomplete_members[idx].clone());
-                new_group.members.push(incomplete_members[idx + 1].clone());
-                final_groups.push(new_group);
-                idx += 2;
-            } else {
-                // remaining == 1: add to the last group instead of creating a singleton
-                if let Some(last_group) = final_groups.last_mut() {
-                    last_group.members.push(incomplete_members[idx].clone());
-                    idx += 1;
-                }
-            }
+            log_edit(audit, event, lang);
+            let (from, to) = (labels.label(group), labels.label(with_group));
+            say(options, &style.message(&lang.format(Msg::Swapped, &[&first, &from, &to, &second, &to, &from])));
+        }
+        Err(MoveError::SameGroup) => {
+            let group = entry.group_of(first).map(|g| labels.label(g)).unwrap_or_default();
+            say(options, &style.message(&lang.format(Msg::SameGroupSwap, &[&first, &second, &group])));
+        }
+        Err(_) => {
+            let missing = if entry.group_of(first).is_none() { first } else { second };
+            say(options, &style.message(&lang.format(Msg::NotFound, &[&missing])));
         }
     }
-
-    final_groups
 }
 
-fn print_groups(groups: &[Group]) {
-    println!("\n=== グループ分け結果 ===");
-    for (i, group) in groups.iter().enumerate() {
-        println!(
-            "グループ {}: {} 人",
-            group_index_to_letter(i),
-            group.members.len()
-        );
-        for member in &group.members {
-            println!("  - {}", member);
+/// Read students with the full-screen UI when stdin is a terminal. Returns
+/// `None` when the line-based input should be used instead.
+#[cfg(unix)]
+fn read_with_tui(
+    options: &cli::Options,
+    id_pattern: Option<&Pattern>,
+    labels: &GroupNames,
+    lang: Lang,
+    initial: Session,
+    names: &HashMap<StudentId, String>,
+) -> Option<Input> {
+    if options.line_mode || options.command == cli::Command::Batch || !stdin_is_tty() {
+        return None;
+    }
+    match tui::run(options, id_pattern, labels, lang, initial, names) {
+        Ok((groups, students, edits)) => Some(Input {
+            groups,
+            batch_mode: false,
+            names: HashMap::new(),
+            students,
+            sizes: HashMap::new(),
+            pins: HashSet::new(),
+            rejected: 0,
+            edits: Some(edits),
+        }),
+        Err(e) => {
+            eprintln!("{}", lang.format(Msg::TuiUnavailable, &[&e]));
+            None
         }
     }
-    println!("\n合計: {} グループ", groups.len());
 }
 
-fn main() {
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-
-    // Set up Ctrl+C handler
-    ctrlc::set_handler(move || {
-        println!("\n\nCtrl+C が押されました。プログラムを終了します...グループ分けの結果を表示するには、Enterを押してください。");
-        r.store(false, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
-
-    let (groups, batch_mode) = read_student_ids(running);
+#[cfg(not(unix))]
+fn read_with_tui(
+    _options: &cli::Options,
+    _id_pattern: Option<&Pattern>,
+    _labels: &GroupNames,
+    _lang: Lang,
+    _initial: Session,
+    _names: &HashMap<StudentId, String>,
+) -> Option<Input> {
+    None
+}
 
-    if groups.is_empty() {
-        println!("\n入力されたデータがありません。");
-        return;
+/// Read students from the terminal (full-screen when possible) or from
+/// piped stdin, continuing from `initial`.
+fn read_entered(
+    options: &cli::Options,
+    running: Arc<AtomicBool>,
+    id_pattern: Option<&Pattern>,
+    labels: &GroupNames,
+    lang: Lang,
+    initial: Session,
+    names: &HashMap<StudentId, String>,
+) -> Input {
+    match read_with_tui(options, id_pattern, labels, lang, initial.clone(), names) {
+        Some(input) => input,
+        None => read_student_ids(options, running, id_pattern, labels, lang, initial, names),
     }
-
-    // Use different reorganization logic based on mode
-    let final_groups = if batch_mode {
-        // Batch mode: preserve group structure, only merge singletons
-        reorganize_batch_groups(groups)
-    } else {
-        // Interactive mode: reorganize incomplete groups
-        reorganize_incomplete_groups(groups)
-    };
-    print_groups(&final_groups);
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_group_creation() {
-        let mut group = Group::new();
-        assert!(!group.is_full());
-
-        group.add_member("S001".to_string());
-        assert!(!group.is_full());
-
-        group.add_member("S002".to_string());
-        assert!(!group.is_full());
-
-        group.add_member("S003".to_string());
-        assert!(group.is_full());
-
-        // Try to add a 4th member (should not be added)
-        group.add_member("S004".to_string());
-        assert_eq!(group.members.len(), 3);
+/// Group labels from `--group-names`, `--group-names-file`, `--labels` (in
+/// `lang`) or `--numeric-labels`, letters otherwise.
+fn group_names(options: &cli::Options, lang: Lang) -> Result<GroupNames, String> {
+    if let Some(theme) = options.labels {
+        return Ok(GroupNames::themed(theme, lang));
     }
+    if options.numeric_labels {
+        return Ok(GroupNames::numbered());
+    }
+    match (&options.group_names, &options.group_names_file) {
+        (Some(names), _) => GroupNames::parse(names).map_err(|e| format!("--group-names: {}", e)),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))
+            .and_then(|text| GroupNames::parse(&text).map_err(|e| format!("{}: {}", path, e))),
+        (None, None) => Ok(GroupNames::default()),
+    }
+}
 
-    #[test]
-    fn test_reorganize_with_complete_groups() {
-        let mut group1 = Group::new();
-        group1.add_member("S001".to_string());
-        group1.add_member("S002".to_string());
-        group1.add_member("S003".to_string());
-
-        let mut group2 = Group::new();
-        group2.add_member("S004".to_string());
-        group2.add_member("S005".to_string());
-        group2.add_member("S006".to_string());
+/// How to read roster columns, from `--id-column`, `--name-column` and `--no-header`.
+fn csv_options(options: &cli::Options) -> Result<CsvOptions, String> {
+    Ok(CsvOptions {
+        id_column: match &options.id_column {
+            Some(spec) => Column::parse(spec)?,
+            None => Column::Index(0),
+        },
+        name_column: options.name_column.as_deref().map(Column::parse).transpose()?,
+        has_header: !options.no_header,
+    })
+}
 
-        let groups = vec![group1, group2];
-        let result = reorganize_incomplete_groups(groups);
+/// Read the roster from `--input`, `--sheet` or `--classroom`, if one was given.
+fn read_roster(options: &cli::Options, google_token: Option<&str>) -> Option<Result<Vec<RosterEntry>, String>> {
+    match (&options.input, &options.sheet, &options.classroom, google_token) {
+        (Some(path), _, _, _) => Some(read_roster_file(options, path)),
+        (None, Some(spreadsheet_id), _, Some(token)) => Some(read_roster_sheet(options, spreadsheet_id, token)),
+        (None, None, Some(course_id), Some(token)) => {
+            Some(classroom::read_roster(course_id, token).map_err(|e| format!("Google Classroom: {}", e)))
+        }
+        _ => None,
+    }
+}
 
-        assert_eq!(result.len(), 2);
-        assert!(result[0].is_full());
-        assert!(result[1].is_full());
+/// Read the roster CSV given by `--input`, or the LMS export given with `--from`.
+fn read_roster_file(options: &cli::Options, path: &str) -> Result<Vec<RosterEntry>, String> {
+    if let Some(RosterFormat::Moodle) = options.from {
+        let bytes = std::fs::read(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+        return moodle::read_roster(&bytes).map_err(|e| format!("{}: {}", path, e));
     }
+    let file = File::open(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+    roster::read_csv_roster(BufReader::new(file), &csv_options(options)?).map_err(|e| format!("{}: {}", path, e))
+}
 
-    #[test]
-    fn test_reorganize_with_incomplete_groups() {
-        let mut group1 = Group::new();
-        group1.add_member("S001".to_string());
-        group1.add_member("S002".to_string());
+/// Read the `ID,名前` lines of the `--names` file.
+fn read_names(path: &str) -> Result<HashMap<StudentId, String>, String> {
+    let file = File::open(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+    let options = CsvOptions {
+        id_column: Column::Index(0),
+        name_column: Some(Column::Index(1)),
+        has_header: false,
+    };
+    let entries = roster::read_csv_roster(BufReader::new(file), &options).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| entry.name.map(|name| (entry.id, name)))
+        .collect())
+}
 
-        let mut group2 = Group::new();
-        group2.add_member("S003".to_string());
-        group2.add_member("S004".to_string());
+/// Read the roster from the Google Sheets spreadsheet given by `--sheet`.
+fn read_roster_sheet(options: &cli::Options, spreadsheet_id: &str, token: &str) -> Result<Vec<RosterEntry>, String> {
+    let range = options.sheet_range.as_deref().unwrap_or("A:Z");
+    let rows = sheets::read_rows(spreadsheet_id, range, token)?;
+    roster::roster_from_rows(rows, &csv_options(options)?).map_err(|e| format!("{}: {}", range, e))
+}
 
-        let groups = vec![group1, group2];
-        let result = reorganize_incomplete_groups(groups);
+/// Print past sessions from the database, or only the groups of `--student`.
+fn print_query(db: &Database, student: Option<&str>) -> Result<(), String> {
+    let names: HashMap<StudentId, String> = db
+        .students()?
+        .into_iter()
+        .filter_map(|entry| entry.name.map(|name| (entry.id, name)))
+        .collect();
+    let label = |id: &str| match names.get(id) {
+        Some(name) => format!("{} {}", id, name),
+        None => id.to_string(),
+    };
 
-        // 4 members should form 2 groups of 2 (not 3+1 which would create a singleton)
-        let total_members: usize = result.iter().map(|g| g.members.len()).sum();
-        assert_eq!(total_members, 4);
-        assert_eq!(result.len(), 2);
-        // Verify no single-person groups
-        for group in &result {
-            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+    let sessions = db.sessions()?;
+    if sessions.is_empty() {
+        println!("{}", Lang::current().text(Msg::NoSessions));
+        return Ok(());
+    }
+    for session in &sessions {
+        let groups: Vec<(usize, &Group)> = session
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| student.map_or(true, |id| g.members.iter().any(|m| m == id)))
+            .collect();
+        if groups.is_empty() {
+            continue;
+        }
+        println!("{}", i18n::message(Msg::QuerySession, &[&session.id, &session.timestamp]));
+        for (i, group) in groups {
+            let members: Vec<String> = group.ids().map(|m| label(m)).collect();
+            let letter = group_index_to_letter(i);
+            println!("{}", i18n::message(Msg::QueryGroup, &[&letter, &members.join(", ")]));
         }
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_reorganize_allows_two_person_groups() {
-        let mut group1 = Group::new();
-        group1.add_member("S001".to_string());
-
-        let mut group2 = Group::new();
-        group2.add_member("S002".to_string());
-
-        let groups = vec![group1, group2];
-        let result = reorganize_incomplete_groups(groups);
+/// A subcommand that reads recorded sessions, such as `export`.
+type RecordedCommand = fn(&cli::Options, Option<&Database>, Option<&Path>, GroupNames, Lang) -> Result<(), String>;
 
-        // 2 members should form 1 group with 2 members
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].members.len(), 2);
+/// Every recorded session, from the database or the history file.
+fn recorded_sessions(db: Option<&Database>, history_path: Option<&Path>) -> Result<Vec<db::Session>, String> {
+    match (db, history_path) {
+        (Some(db), _) => db.sessions(),
+        (None, Some(path)) => history::read_sessions(path),
+        (None, None) => Err(i18n::message(Msg::HistoryUnknown, &[])),
     }
+}
 
-    #[test]
-    fn test_mixed_complete_and_incomplete_groups() {
-        let mut group1 = Group::new();
-        group1.add_member("S001".to_string());
-        group1.add_member("S002".to_string());
-        group1.add_member("S003".to_string());
+/// The run `--replay` makes again: the last session recorded with `seed`,
+/// after the sessions recorded before it. `None` when no session has that seed.
+fn replayed_session(
+    seed: u64,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+) -> Result<Option<(Vec<db::Session>, db::Session)>, String> {
+    let mut sessions = recorded_sessions(db, history_path)?;
+    Ok(sessions.iter().rposition(|session| session.seed == Some(seed)).map(|i| {
+        let session = sessions.remove(i);
+        sessions.truncate(i);
+        (sessions, session)
+    }))
+}
 
-        let mut group2 = Group::new();
-        group2.add_member("S004".to_string());
+/// The session chosen with `--session` (the latest by default), from the
+/// database or the history file.
+fn recorded_session(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+) -> Result<db::Session, String> {
+    let sessions = recorded_sessions(db, history_path)?;
+    match options.session {
+        Some(n) => sessions
+            .into_iter()
+            .find(|session| session.id == n as u64)
+            .ok_or_else(|| i18n::message(Msg::SessionNotRecorded, &[&n])),
+        None => sessions
+            .into_iter()
+            .last()
+            .ok_or_else(|| i18n::message(Msg::NoSessions, &[])),
+    }
+}
 
-        let mut group3 = Group::new();
-        group3.add_member("S005".to_string());
+/// Write a recorded session again, from the database or the history file.
+fn export(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+    group_names: GroupNames,
+    lang: Lang,
+) -> Result<(), String> {
+    let session = recorded_session(options, db, history_path)?;
+    let names = recorded_names(db)?;
+    let metadata = Metadata {
+        timestamp: session.timestamp,
+        seed: session.seed,
+        group_names,
+        leaders: session.leaders,
+        lang,
+        ..Default::default()
+    };
+    let result = GroupingResult::new(session.groups, metadata);
+    print_groups(&result, &names, options).map_err(|e| e.to_string())?;
+    post_groups(&result.groups, &names, options, &result.metadata);
+    Ok(())
+}
 
-        let groups = vec![group1, group2, group3];
-        let result = reorganize_incomplete_groups(groups);
+/// Combine exported groupings and write them as one. Like `--append`, the
+/// result is not recorded, since each part was recorded when it was made.
+fn merge(options: &cli::Options, group_names: GroupNames, lang: Lang) -> Result<(), String> {
+    let parts = options
+        .result_files
+        .iter()
+        .map(|path| load_exported(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let counts: Vec<usize> = parts.iter().map(|(groups, _, _)| groups.len()).collect();
+    let (groups, names, merged) = grouping_tool::merge::merge(parts)?;
+    for i in grouping_tool::merge::unusual_groups(&groups, options.sizing()) {
+        eprintln!("{}", lang.format(Msg::MergeGroupSize, &[&group_names.label(i), &groups[i].members.len()]));
+    }
+    if let Some((sizes, expected)) = grouping_tool::merge::size_difference(&groups, options.sizing()) {
+        let join = |sizes: Vec<usize>| sizes.iter().map(usize::to_string).collect::<Vec<_>>().join("+");
+        eprintln!("{}", lang.format(Msg::MergeSizesDiffer, &[&join(sizes), &join(expected)]));
+    }
 
-        // Should have 1 complete group (unchanged) + 1 group with 2 members (no singletons)
-        assert_eq!(result.len(), 2);
-        let complete_groups = result.iter().filter(|g| g.is_full()).count();
-        assert_eq!(complete_groups, 1);
-        // Verify no single-person groups
-        for group in &result {
-            assert!(group.members.len() >= 2, "No group should have less than 2 members");
+    let metadata = Metadata {
+        timestamp: grouping_tool::time::now_rfc3339(),
+        seed: None,
+        group_names,
+        leaders: merged.leaders,
+        lang,
+        ..Default::default()
+    };
+    let result = GroupingResult::new(groups, metadata);
+    print_groups(&result, &names, options).map_err(|e| e.to_string())?;
+    post_groups(&result.groups, &names, options, &result.metadata);
+    let mut first = 0;
+    for (path, count) in options.result_files.iter().zip(counts) {
+        if count > 0 {
+            let labels = (result.metadata.group_names.label(first), result.metadata.group_names.label(first + count - 1));
+            say(options, &lang.format(Msg::MergeRelabeled, &[path, &labels.0, &labels.1]));
         }
+        first += count;
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_no_single_person_groups_with_seven_students() {
-        // Test with 7 students (would normally be 3+3+1)
-        let groups = vec![
-            {
-                let mut g = Group::new();
-                g.add_member("S001".to_string());
-                g.add_member("S002".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S003".to_string());
-                g.add_member("S004".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S005".to_string());
-                g.add_member("S006".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S007".to_string());
-                g
-            },
-        ];
-        
-        let result = reorganize_incomplete_groups(groups);
-
-        // Should not have any single-person groups
-        for group in &result {
-            assert!(group.members.len() >= 2, "No group should have less than 2 members");
-        }
-        
-        // Total should still be 7
-        let total: usize = result.iter().map(|g| g.members.len()).sum();
-        assert_eq!(total, 7);
+/// Compare two exported groupings. Groups keep the labels of their own file.
+fn diff(options: &cli::Options, lang: Lang) -> Result<(), String> {
+    let (old, mut names, old_metadata) = load_exported(&options.result_files[0])?;
+    let (new, new_names, new_metadata) = load_exported(&options.result_files[1])?;
+    for (id, name) in new_names {
+        names.entry(id).or_insert(name);
     }
-
-    #[test]
-    fn test_no_single_person_groups_with_ten_students() {
-        // Test with 10 students all incomplete (would normally be 3+3+3+1)
-        let groups = vec![
-            {
-                let mut g = Group::new();
-                g.add_member("S001".to_string());
-                g.add_member("S002".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S003".to_string());
-                g.add_member("S004".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S005".to_string());
-                g.add_member("S006".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S007".to_string());
-                g.add_member("S008".to_string());
-                g
-            },
-            {
-                let mut g = Group::new();
-                g.add_member("S009".to_string());
-                g.add_member("S010".to_string());
-                g
-            },
-        ];
-
-        let result = reorganize_incomplete_groups(groups);
-
-        // Should not have any single-person groups
-        for group in &result {
-            assert!(group.members.len() >= 2, "No group should have less than 2 members");
-        }
-        
-        // Total should still be 10
-        let total: usize = result.iter().map(|g| g.members.len()).sum();
-        assert_eq!(total, 10);
+    let diff = grouping_tool::diff::diff(&old, &new);
+    let (old_labels, new_labels) = (&old_metadata.group_names, &new_metadata.group_names);
+    let rendered = match options.format {
+        OutputFormat::Json => format!("{}\n", grouping_tool::diff::to_json(&diff, old_labels, new_labels)),
+        _ => {
+            let metadata = Metadata {
+                lang,
+                ..Default::default()
+            };
+            let labels = MemberLabels::new(&names, &metadata);
+            grouping_tool::diff::render_text(&diff, old_labels, new_labels, |id| labels.label(&Student::new(id)), lang)
+        }
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered).map_err(|e| e.to_string())?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+        }
+        None => print!("{}", rendered),
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_single_student_with_complete_group() {
-        // Test 1 incomplete student with 1 complete group
-        let mut complete_group = Group::new();
-        complete_group.add_member("S001".to_string());
-        complete_group.add_member("S002".to_string());
-        complete_group.add_member("S003".to_string());
-
-        let mut single_group = Group::new();
-        single_group.add_member("S004".to_string());
-
-        let groups = vec![complete_group, single_group];
-        let result = reorganize_incomplete_groups(groups);
+/// Names of the students in the database roster; none without `--db`.
+fn recorded_names(db: Option<&Database>) -> Result<HashMap<StudentId, String>, String> {
+    match db {
+        Some(db) => Ok(db
+            .students()?
+            .into_iter()
+            .filter_map(|entry| entry.name.map(|name| (entry.id, name)))
+            .collect()),
+        None => Ok(HashMap::new()),
+    }
+}
 
-        // Should create two 2-person groups instead of one 4-person group
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].members.len(), 2);
-        assert_eq!(result[1].members.len(), 2);
+/// Give points to a group of a recorded session, in the database or the history file.
+fn score(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+    group_names: GroupNames,
+    lang: Lang,
+) -> Result<(), String> {
+    let session = recorded_session(options, db, history_path)?;
+    let label = options.score_group.as_deref().unwrap_or_default();
+    let group = group_names
+        .index_of(label)
+        .filter(|&i| i < session.groups.len())
+        .ok_or_else(|| i18n::message(Msg::SessionNoGroup, &[&session.id, &label]))?;
+    let points = options.points.unwrap_or(0);
+    let timestamp = grouping_tool::time::now_rfc3339();
+    match (db, history_path) {
+        (Some(db), _) => db.record_score(session.id, group, points, &timestamp)?,
+        (None, Some(path)) => history::append_score(path, session.id, group, points, &timestamp)
+            .map_err(|e| i18n::message(Msg::CannotWrite, &[&path.display(), &e]))?,
+        (None, None) => unreachable!("the session was read from one of them"),
     }
+    let total = session.scores[group] + points;
+    println!(
+        "{}",
+        lang.format(Msg::ScoreRecorded, &[&session.id, &group_names.label(group), &points, &total])
+    );
+    Ok(())
+}
 
-    #[test]
-    fn test_atomic_flag_behavior() {
-        // Test that the atomic flag works correctly
-        let running = Arc::new(AtomicBool::new(true));
-        assert!(running.load(Ordering::SeqCst));
+/// Rank the groups of a recorded session by their points, or with
+/// `--by-student` the students over every session.
+fn leaderboard(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+    group_names: GroupNames,
+    lang: Lang,
+) -> Result<(), String> {
+    let (title, standings) = if options.by_student {
+        let sessions = recorded_sessions(db, history_path)?;
+        let names = recorded_names(db)?;
+        let metadata = Metadata {
+            lang,
+            ..Default::default()
+        };
+        let labels = MemberLabels::new(&names, &metadata);
+        let standings = leaderboard::student_standings(&sessions, |id| labels.label(&Student::new(id)));
+        (lang.format(Msg::LeaderboardStudents, &[&sessions.len()]), standings)
+    } else {
+        let session = recorded_session(options, db, history_path)?;
+        let standings = leaderboard::group_standings(&session, |i| group_names.label(i));
+        (lang.format(Msg::LeaderboardSession, &[&session.id]), standings)
+    };
+    let rendered = match options.format {
+        OutputFormat::Json => format!("{}\n", leaderboard::to_json(&standings)),
+        _ if options.by_student => leaderboard::render_text(&title, &standings, lang),
+        _ => {
+            let standings: Vec<_> = standings
+                .into_iter()
+                .map(|standing| Standing {
+                    label: lang.format(Msg::GroupHeading, &[&standing.label]),
+                    ..standing
+                })
+                .collect();
+            leaderboard::render_text(&title, &standings, lang)
+        }
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered).map_err(|e| e.to_string())?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
 
-        running.store(false, Ordering::SeqCst);
-        assert!(!running.load(Ordering::SeqCst));
+/// Write a tournament bracket between the groups of a recorded session.
+fn bracket(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+    group_names: GroupNames,
+    lang: Lang,
+) -> Result<(), String> {
+    let session = recorded_session(options, db, history_path)?;
+    let elimination = if options.double_elimination {
+        Elimination::Double
+    } else {
+        Elimination::Single
+    };
+    let bracket = Bracket::new(session.groups.len(), elimination)
+        .ok_or_else(|| i18n::message(Msg::BracketTooFew, &[]))?;
+    let rendered = match options.format {
+        OutputFormat::Json => format!("{}\n", bracket::to_json(&bracket, &session.groups, &group_names)),
+        _ => bracket::render_text(&bracket, &group_names, lang),
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered).map_err(|e| e.to_string())?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
 
-        running.store(true, Ordering::SeqCst);
-        assert!(running.load(Ordering::SeqCst));
+/// Shuffle the groups of a recorded session into a presentation order.
+fn order(
+    options: &cli::Options,
+    db: Option<&Database>,
+    history_path: Option<&Path>,
+    group_names: GroupNames,
+    lang: Lang,
+) -> Result<(), String> {
+    let session = recorded_session(options, db, history_path)?;
+    let seed = options.seed().unwrap_or_else(grouping::random_seed);
+    let order = presentation::shuffle(session.groups.len(), &mut rand::rngs::StdRng::seed_from_u64(seed));
+    let rendered = match options.format {
+        OutputFormat::Json => format!("{}\n", presentation::to_json(&order, &group_names, options.slot_minutes)),
+        _ => presentation::render_text(&order, &group_names, options.slot_minutes, lang),
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered).map_err(|e| e.to_string())?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+        }
+        None => print!("{}", rendered),
     }
+    say(options, &lang.format(Msg::Seed, &[&seed, &seed]));
+    Ok(())
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_five_members() {
-        // Test that 5 members (5 % 3 == 2) creates 1 three-person + 1 two-person group
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+/// Fill in names and attributes of typed-in IDs from the database roster.
+fn fill_from_db(db: &Database, names: &mut HashMap<StudentId, String>, students: &mut HashMap<StudentId, Student>, lang: Lang) {
+    match db.students() {
+        Ok(entries) => {
+            for entry in entries {
+                if let Some(name) = entry.name {
+                    names.entry(entry.id.clone()).or_insert(name);
+                }
+                students.entry(entry.id.clone()).or_insert(Student {
+                    id: entry.id,
+                    name: None,
+                    attributes: entry.attributes,
+                });
+            }
+        }
+        Err(message) => eprintln!("{}", lang.format(Msg::DbRosterFailed, &[&message])),
+    }
+}
 
-        let mut group2 = Group::new();
-        group2.members = vec!["D".to_string(), "E".to_string()];
+/// Give the members of `groups` the attributes and names known for them,
+/// from the roster, typed-in lines or the database.
+fn attach_students(groups: &mut [Group], students: &HashMap<StudentId, Student>, names: &HashMap<StudentId, String>) {
+    for member in groups.iter_mut().flat_map(|g| g.members.iter_mut()) {
+        if let Some(student) = students.get(&member.id) {
+            *member = student.clone();
+        }
+        member.fill_name(names);
+    }
+}
 
-        let groups = vec![group1, group2];
-        let result = reorganize_batch_groups(groups);
+/// Turn roster entries into one batch group with their names and attributes.
+fn roster_input(entries: Vec<RosterEntry>) -> Input {
+    let mut names = HashMap::new();
+    let mut students = HashMap::new();
+    let mut members = Vec::new();
+    for entry in entries {
+        if let Some(name) = &entry.name {
+            names.insert(entry.id.clone(), name.clone());
+        }
+        let student = Student {
+            id: entry.id,
+            name: entry.name,
+            attributes: entry.attributes,
+        };
+        students.insert(student.id.clone(), student.clone());
+        members.push(student);
+    }
 
-        // 5 members should become 3+2 (optimal grouping)
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].members.len(), 3);
-        assert_eq!(result[1].members.len(), 2);
+    let groups = if members.is_empty() {
+        Vec::new()
+    } else {
+        vec![Group::from_members(members)]
+    };
+    // A roster is treated like piped input
+    Input {
+        groups,
+        batch_mode: true,
+        names,
+        students,
+        sizes: HashMap::new(),
+        pins: HashSet::new(),
+        rejected: 0,
+        edits: None,
     }
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_three_members() {
-        // Test that 3 members form a single 3-person group
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string(), "B".to_string()];
+/// Print the final grouping, or write it to the `--out` file when given.
+fn print_groups(result: &GroupingResult, names: &HashMap<StudentId, String>, options: &cli::Options) -> io::Result<()> {
+    let lang = result.metadata.lang;
+    let anonymized = anonymized(&result.groups, &result.metadata, options)?;
+    let anonymized = anonymized.map(|(groups, metadata)| GroupingResult::new(groups, metadata));
+    let no_names = HashMap::new();
+    let (result, names) = match &anonymized {
+        Some(anonymized) => (anonymized, &no_names),
+        None => (result, names),
+    };
+    let sorted = sorted_members(&result.groups, options);
+    let sorted = sorted.map(|groups| GroupingResult::new(groups, result.metadata.clone()));
+    let result = sorted.as_ref().unwrap_or(result);
+    let (groups, metadata) = (&result.groups[..], &result.metadata);
+    let template = options.template.as_deref().map(load_template).transpose();
+    let template = template.map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+    let rendered = match &template {
+        Some(template) => output::render_template(template, result, names),
+        None => output::render(result, names, options.format),
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered)?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+            sign_written(Path::new(path), options, lang)?;
+        }
+        // Files and the clipboard get the plain text
+        None if options.format == OutputFormat::Text && template.is_none() => {
+            print!("{}", output::render_text_styled(groups, names, metadata, terminal_style(options)))
+        }
+        None => print!("{}", rendered),
+    }
+    // The text on the terminal already ends with it
+    if options.out.is_some() || options.format != OutputFormat::Text || template.is_some() {
+        say(options, &lang.format(Msg::ContentHash, &[&output::content_hash(groups)]));
+    }
 
-        let mut group2 = Group::new();
-        group2.members = vec!["C".to_string()]; // singleton
+    if options.clipboard {
+        // A missing clipboard tool should not lose the result that was already printed
+        match clipboard::copy(rendered.trim_start()) {
+            Ok(_) => say(options, lang.text(Msg::Copied)),
+            Err(e) => eprintln!("{}", lang.format(Msg::CopyFailed, &[&e])),
+        }
+    }
 
-        let groups = vec![group1, group2];
-        let result = reorganize_batch_groups(groups);
+    if let Some(path) = &options.output_html {
+        std::fs::write(path, output::render_html(groups, names, metadata))?;
+        say(options, &lang.format(Msg::HtmlSaved, &[path]));
+        sign_written(Path::new(path), options, lang)?;
+    }
 
-        // 3 members should form one 3-person group
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].members.len(), 3);
-        assert!(result[0].members.contains(&"A".to_string()));
-        assert!(result[0].members.contains(&"B".to_string()));
-        assert!(result[0].members.contains(&"C".to_string()));
+    if let Some(path) = &options.pdf {
+        std::fs::write(
+            path,
+            pdf::render_pdf(groups, names, metadata, options.pdf_per_group),
+        )?;
+        say(options, &lang.format(Msg::PdfSaved, &[path]));
+        sign_written(Path::new(path), options, lang)?;
     }
 
-    #[test]
-    fn test_reorganize_batch_groups_three_members_reverse_order() {
-        // Test that 3 members form a single 3-person group (different input order)
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string()]; // singleton at start
+    if let Some(path) = &options.xlsx {
+        std::fs::write(path, xlsx::render_xlsx(groups, names, metadata))?;
+        say(options, &lang.format(Msg::XlsxSaved, &[path]));
+        sign_written(Path::new(path), options, lang)?;
+    }
+
+    if let Some(spec) = &options.seating {
+        // Too few seats should not lose the result that was already written
+        match load_layout(spec).and_then(|layout| seating::assign(&layout, groups)) {
+            Ok(chart) => {
+                say(options, &seating::render_text(&chart, &metadata.group_names, lang));
+                if let Some(path) = &options.seating_out {
+                    std::fs::write(path, seating::render_csv(&chart, names, metadata))?;
+                    say(options, &lang.format(Msg::SeatingSaved, &[path]));
+                }
+            }
+            Err(message) => eprintln!("{}", lang.format(Msg::SeatingFailed, &[&message])),
+        }
+    }
+    Ok(())
+}
 
-        let mut group2 = Group::new();
-        group2.members = vec!["B".to_string(), "C".to_string()];
+/// The key of `--sign`, made as a new random key when the file does not exist yet.
+fn signing_key(options: &cli::Options, lang: Lang) -> io::Result<Option<Vec<u8>>> {
+    let Some(path) = options.sign.as_deref().map(Path::new) else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        signature::create_key(path)?;
+        say(options, &lang.format(Msg::KeyCreated, &[&path.display()]));
+    }
+    signature::read_key(path)
+        .map(Some)
+        .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+}
 
-        let groups = vec![group1, group2];
-        let result = reorganize_batch_groups(groups);
+/// With `--sign`, sign the file just written at `path`.
+fn sign_written(path: &Path, options: &cli::Options, lang: Lang) -> io::Result<()> {
+    if let Some(key) = signing_key(options, lang)? {
+        let signature = signature::sign_file(path, &key)?;
+        say(options, &lang.format(Msg::Signed, &[&signature.display()]));
+    }
+    Ok(())
+}
 
-        // 3 members should form one 3-person group
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].members.len(), 3);
+/// Check the result file given to `verify` against the signature `--sign`
+/// wrote next to it. `Ok(false)` when it does not match.
+fn verify(options: &cli::Options, lang: Lang) -> Result<bool, String> {
+    let file = &options.result_files[0];
+    let key = signature::read_key(Path::new(options.sign.as_deref().unwrap_or_default()))?;
+    let data = std::fs::read(file).map_err(|e| i18n::message(Msg::CannotOpen, &[&file, &e]))?;
+    let signature_path = signature::signature_path(Path::new(file));
+    let signature = std::fs::read_to_string(&signature_path)
+        .map_err(|e| i18n::message(Msg::CannotOpenSignature, &[&signature_path.display(), &e]))?;
+    let valid = signature::verify(&key, &data, &signature).map_err(|e| format!("{}: {}", signature_path.display(), e))?;
+    if !valid {
+        eprintln!("{}", lang.format(Msg::SignatureInvalid, &[file]));
+        return Ok(false);
+    }
+    println!("{}", lang.format(Msg::SignatureValid, &[file]));
+    // A JSON result also tells which grouping it holds
+    if let Ok((groups, _, metadata)) = load_exported(file) {
+        println!("{}", lang.format(Msg::ContentHash, &[&output::content_hash(&groups)]));
+        if let Some(seed) = metadata.seed {
+            println!("{}", lang.format(Msg::SeedFooter, &[&seed]));
+        }
     }
+    Ok(true)
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_four_members() {
-        // Test that 4 members (4 % 3 == 1) creates 2 two-person groups
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string(), "B".to_string()];
+/// The mapping file of `--anonymize`.
+fn pseudonym_path(options: &cli::Options) -> io::Result<PathBuf> {
+    options
+        .pseudonym_file
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(pseudonym::default_path)
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, i18n::message(Msg::PseudonymsUnknown, &[]))
+        })
+}
 
-        let mut group2 = Group::new();
-        group2.members = vec!["C".to_string()]; // singleton
+/// Run `f` with the pseudonyms of `--anonymize`, then save the ones it gave out.
+fn with_pseudonyms<T>(options: &cli::Options, f: impl FnOnce(&mut Pseudonyms) -> T) -> io::Result<T> {
+    let path = pseudonym_path(options)?;
+    let mut pseudonyms = Pseudonyms::load(&path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let result = f(&mut pseudonyms);
+    pseudonyms.save(&path)?;
+    Ok(result)
+}
 
-        let mut group3 = Group::new();
-        group3.members = vec!["D".to_string()]; // singleton
+/// With `--anonymize`, `groups` and `metadata` with pseudonyms in place of
+/// the student IDs, including the leaders. Names are then left out by the caller.
+fn anonymized(groups: &[Group], metadata: &Metadata, options: &cli::Options) -> io::Result<Option<(Vec<Group>, Metadata)>> {
+    if !options.anonymize {
+        return Ok(None);
+    }
+    with_pseudonyms(options, |pseudonyms| {
+        let metadata = Metadata {
+            leaders: metadata.leaders.iter().map(|id| pseudonyms.pseudonym(id)).collect(),
+            ..metadata.clone()
+        };
+        Some((pseudonyms.groups(groups), metadata))
+    })
+}
 
-        let groups = vec![group1, group2, group3];
-        let result = reorganize_batch_groups(groups);
+/// The groups with their members in natural ID order for `--sort-members`,
+/// applied to what is written out (the pseudonyms with `--anonymize`).
+fn sorted_members(groups: &[Group], options: &cli::Options) -> Option<Vec<Group>> {
+    options.sort_members.then(|| groups.iter().map(Group::sorted).collect())
+}
 
-        // 4 members should become 2+2 (two two-person groups)
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].members.len(), 2);
-        assert_eq!(result[1].members.len(), 2);
-        // Order is preserved from input
-        assert!(result[0].members.contains(&"A".to_string()));
-        assert!(result[0].members.contains(&"B".to_string()));
-        assert!(result[1].members.contains(&"C".to_string()));
-        assert!(result[1].members.contains(&"D".to_string()));
+/// Read and parse the `--template` file.
+fn load_template(path: &str) -> Result<Template, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+    Template::parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// The classroom given with `--seating`: a `ROWSxCOLUMNS` grid or a template file.
+fn load_layout(spec: &str) -> Result<Layout, String> {
+    if let Some(layout) = Layout::parse_grid(spec) {
+        return Ok(layout);
     }
+    let text = std::fs::read_to_string(spec).map_err(|e| i18n::message(Msg::CannotOpen, &[&spec, &e]))?;
+    Layout::parse(&text).map_err(|e| format!("{}: {}", spec, e))
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_splits_large_group() {
-        // Test that a 4-person group is split into 2+2
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string(), "B".to_string(), "C".to_string(), "D".to_string()];
+/// Read student IDs from stdin line by line and write each group as soon as
+/// it is formed (`--stream`), so that a huge input is never held in memory;
+/// only the IDs read so far are kept, to skip duplicates. Returns the exit
+/// status for the input: [`EXIT_NO_INPUT`], [`EXIT_INVALID_ID`] or 0.
+fn stream_groups<'a>(
+    options: &cli::Options,
+    running: Arc<AtomicBool>,
+    id_pattern: Option<&Pattern>,
+    names: &'a HashMap<StudentId, String>,
+    metadata: &'a Metadata,
+) -> io::Result<i32> {
+    let lang = metadata.lang;
+    let mut split = StreamSplit::new(options.sizing()).expect("--stream rejects --groups");
+    // Given out as the groups are written, and saved once at the end
+    let pseudonym_path = options.anonymize.then(|| pseudonym_path(options)).transpose()?;
+    let mut pseudonyms = match &pseudonym_path {
+        Some(path) => Some(Pseudonyms::load(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?),
+        None => None,
+    };
+    // Opened with the first group, so that empty input writes nothing
+    let mut writer: Option<GroupWriter<'a, Box<dyn Write>>> = None;
+    let emit = |group: Group, writer: &mut Option<GroupWriter<'a, Box<dyn Write>>>| -> io::Result<()> {
+        if writer.is_none() {
+            let out: Box<dyn Write> = match &options.out {
+                Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout()),
+            };
+            *writer = Some(GroupWriter::new(out, options.format, names, metadata)?);
+        }
+        let group = if options.sort_members { group.sorted() } else { group };
+        writer.as_mut().expect("opened above").write(&group)
+    };
 
-        let groups = vec![group1];
-        let result = reorganize_batch_groups(groups);
+    // As in batch mode, duplicates are reported with the blank-line separated group they were read in.
+    // This is the one thing that grows with the input: an entry per distinct ID, for the whole run.
+    let mut group_of: HashMap<StudentId, usize> = HashMap::new();
+    let mut input_group = 0;
+    let mut group_started = false;
+    let mut rejected = 0;
+    for (line_number, line) in io::stdin().lock().lines().enumerate() {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let line = line?;
+        let student_id = line.trim();
+        if student_id.is_empty() {
+            if group_started {
+                input_group += 1;
+                group_started = false;
+            }
+            continue;
+        }
+        if let Some(pattern) = id_pattern.filter(|p| !p.is_match(student_id)) {
+            eprintln!("{}", lang.format(Msg::InvalidIdSkipped, &[&(line_number + 1), &student_id, pattern]));
+            rejected += 1;
+            continue;
+        }
+        if let Some(&group) = group_of.get(student_id) {
+            let label = metadata.group_names.label(group);
+            eprintln!("{}", lang.format(Msg::DuplicateSkipped, &[&(line_number + 1), &student_id, &label]));
+            continue;
+        }
+        group_of.insert(student_id.to_string(), input_group);
+        group_started = true;
+        if let Some(group) = split.push(Student::new(student_id)) {
+            emit(anonymize_group(&mut pseudonyms, group), &mut writer)?;
+        }
+    }
+    for group in split.finish() {
+        emit(anonymize_group(&mut pseudonyms, group), &mut writer)?;
+    }
+    if let (Some(pseudonyms), Some(path)) = (&pseudonyms, &pseudonym_path) {
+        pseudonyms.save(path)?;
+    }
 
-        // 4-person group should be split into 2+2
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].members.len(), 2);
-        assert_eq!(result[1].members.len(), 2);
+    match writer {
+        Some(writer) => {
+            writer.finish()?;
+            if let Some(path) = &options.out {
+                say(options, &lang.format(Msg::SavedTo, &[path]));
+            }
+            Ok(warning_status(rejected, false).unwrap_or(0))
+        }
+        None => {
+            say(options, lang.text(Msg::NoInput));
+            Ok(EXIT_NO_INPUT)
+        }
     }
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_splits_five_person_group() {
-        // Test that a 5-person group is split into 3+2
-        let mut group1 = Group::new();
-        group1.members = vec!["A".to_string(), "B".to_string(), "C".to_string(), "D".to_string(), "E".to_string()];
+/// `group` with pseudonyms when `--anonymize` loaded them.
+fn anonymize_group(pseudonyms: &mut Option<Pseudonyms>, group: Group) -> Group {
+    match pseudonyms {
+        Some(pseudonyms) => pseudonyms.group(&group),
+        None => group,
+    }
+}
 
-        let groups = vec![group1];
-        let result = reorganize_batch_groups(groups);
+/// Post the final grouping to the Slack and Discord webhooks, if any.
+fn post_groups(groups: &[Group], names: &HashMap<StudentId, String>, options: &cli::Options, metadata: &Metadata) {
+    let lang = metadata.lang;
+    let anonymized = match anonymized(groups, metadata, options) {
+        Ok(anonymized) => anonymized,
+        Err(e) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&e]));
+            return;
+        }
+    };
+    let no_names = HashMap::new();
+    let (groups, names, metadata) = match &anonymized {
+        Some((groups, metadata)) => (groups.as_slice(), &no_names, metadata),
+        None => (groups, names, metadata),
+    };
+    let sorted = sorted_members(groups, options);
+    let groups = sorted.as_deref().unwrap_or(groups);
+    if let Some(url) = &options.slack_webhook {
+        // Like the clipboard, a failed post should not hide the printed result
+        match webhook::post_slack(url, groups, names, metadata) {
+            Ok(()) => say(options, lang.text(Msg::SlackPosted)),
+            Err(e) => eprintln!("{}", lang.format(Msg::SlackFailed, &[&e])),
+        }
+    }
 
-        // 5-person group should be split into 3+2
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].members.len(), 3);
-        assert_eq!(result[1].members.len(), 2);
+    if let Some(url) = &options.discord_webhook {
+        match webhook::post_discord(url, groups, names, metadata) {
+            Ok(()) => say(options, lang.text(Msg::DiscordPosted)),
+            Err(e) => eprintln!("{}", lang.format(Msg::DiscordFailed, &[&e])),
+        }
     }
 
-    #[test]
-    fn test_reorganize_batch_groups_splits_seven_person_group() {
-        // Test that a 7-person group is split into 3+2+2
-        let mut group1 = Group::new();
-        group1.members = vec![
-            "A".to_string(), "B".to_string(), "C".to_string(), "D".to_string(),
-            "E".to_string(), "F".to_string(), "G".to_string()
-        ];
+    if let Some(url) = &options.teams_webhook {
+        match webhook::post_teams(url, groups, names, metadata) {
+            Ok(()) => say(options, lang.text(Msg::TeamsPosted)),
+            Err(e) => eprintln!("{}", lang.format(Msg::TeamsFailed, &[&e])),
+        }
+    }
+}
 
-        let groups = vec![group1];
-        let result = reorganize_batch_groups(groups);
+/// Regroup the entered `groups` and choose leaders, warning about constraints
+/// that could not be met and, when no grouping could meet them, which ones conflict.
+fn make_groups(
+    groups: &[Group],
+    config: &GroupingConfig,
+    options: &cli::Options,
+    history: Option<&History>,
+    lang: Lang,
+) -> Result<(Vec<Group>, Vec<StudentId>), GroupingError> {
+    warn_shortfall(config.sizing, groups.iter().map(|g| g.members.len()).sum(), lang);
+    let final_groups = grouping::regroup(groups.to_vec(), config.clone())?;
+    // With --avoid-repeats, students who led before are passed over
+    let leaders = if options.assign_leader {
+        leader::choose_leaders(&final_groups, history, &mut config.rng())
+    } else {
+        Vec::new()
+    };
+    let violations = config.constraints.unsatisfied(&final_groups);
+    for violation in &violations {
+        eprintln!("{}", lang.format(Msg::Unsatisfied, &[violation]));
+    }
+    if !violations.is_empty() {
+        let students: Vec<Student> = final_groups.iter().flat_map(|g| g.members.clone()).collect();
+        if let Some(conflict) = solver::explain(&students, config.sizing, &config.constraints) {
+            eprintln!("{}", lang.text(Msg::ConstraintConflict));
+            for line in conflict.to_string().lines() {
+                eprintln!("  {}", line);
+            }
+        }
+    }
+    Ok((final_groups, leaders))
+}
 
-        // 7-person group should be split into 3+2+2
-        assert_eq!(result.len(), 3);
-        let sizes: Vec<usize> = result.iter().map(|g| g.members.len()).collect();
-        assert_eq!(sizes, vec![3, 2, 2]);
+/// Warn when `n` students cannot be split as `sizing` asks.
+fn warn_shortfall(sizing: Sizing, n: usize, lang: Lang) {
+    match sizing.shortfall(n) {
+        Some(Shortfall::Groups { requested, made }) => {
+            eprintln!("{}", lang.format(Msg::FewerGroups, &[&n, &requested, &made]));
+        }
+        Some(Shortfall::Max { max, largest }) => {
+            eprintln!("{}", lang.format(Msg::OverMax, &[&n, &sizing.min_size(), &max, &largest]));
+        }
+        Some(Shortfall::Min { smallest, .. }) => {
+            eprintln!("{}", lang.format(Msg::UnderMin, &[&n, &smallest]));
+        }
+        None => {}
     }
+}
 
-    #[test]
-    fn test_reorganize_batch_groups_no_single_person_groups() {
-        // Verify that no single-person groups are ever created
-        for total in 2..=20 {
-            let mut group = Group::new();
-            for i in 0..total {
-                group.members.push(format!("S{:03}", i));
+/// [`make_groups`], exiting with the reason when the groups cannot be formed.
+fn make_groups_or_exit(
+    groups: &[Group],
+    config: &GroupingConfig,
+    options: &cli::Options,
+    history: Option<&History>,
+    lang: Lang,
+) -> (Vec<Group>, Vec<StudentId>) {
+    make_groups(groups, config, options, history, lang).unwrap_or_else(|e| {
+        eprintln!("{}", lang.format(Msg::GroupingFailed, &[&e]));
+        std::process::exit(1);
+    })
+}
+
+/// Group the roster at `path` and print the result again each time the file
+/// is saved (`--watch`), until Ctrl+C. Results are not recorded in the history.
+fn watch(
+    path: &str,
+    options: &cli::Options,
+    config: GroupingConfig,
+    group_names: &GroupNames,
+    db: Option<&Database>,
+    running: Arc<AtomicBool>,
+    lang: Lang,
+) {
+    say(options, &lang.format(Msg::WatchStarted, &[&path]));
+    // `None` until the first poll, so that a roster missing from the start is reported too
+    let mut last_modified = None;
+    while running.load(Ordering::SeqCst) {
+        let modified = Some(std::fs::metadata(path).and_then(|m| m.modified()).ok());
+        if modified == last_modified {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            continue;
+        }
+        last_modified = modified;
+
+        let timestamp = grouping_tool::time::now_rfc3339();
+        say(options, &lang.format(Msg::WatchReloaded, &[&path, &timestamp]));
+        // A roster that is briefly missing or broken while being saved is reported and waited out
+        let Input { mut groups, mut names, mut students, .. } = match read_roster_file(options, path) {
+            Ok(entries) => roster_input(entries),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                continue;
             }
-            let groups = vec![group];
-            let result = reorganize_batch_groups(groups);
-            
-            for g in &result {
-                assert!(g.members.len() >= 2, "Group with {} members found for total {}", g.members.len(), total);
-                assert!(g.members.len() <= 3, "Group with {} members found for total {}", g.members.len(), total);
+        };
+        if groups.is_empty() {
+            say(options, lang.text(Msg::NoInput));
+            continue;
+        }
+        if let Some(db) = db {
+            fill_from_db(db, &mut names, &mut students, lang);
+        }
+
+        attach_students(&mut groups, &students, &names);
+        let (final_groups, leaders) = match make_groups(&groups, &config, options, config.history.as_ref(), lang) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{}", lang.format(Msg::GroupingFailed, &[&e]));
+                continue;
             }
-            
-            let total_after: usize = result.iter().map(|g| g.members.len()).sum();
-            assert_eq!(total_after, total, "Total members should be preserved");
+        };
+        let shuffled = config.uses_rng() || options.assign_leader;
+        let metadata = Metadata {
+            timestamp,
+            seed: config.seed.filter(|_| shuffled),
+            group_names: group_names.clone(),
+            leaders,
+            lang,
+            mode: Some(config.mode),
+            config: config.settings(),
+        };
+        let result = GroupingResult::new(final_groups, metadata);
+        if let Err(e) = print_groups(&result, &names, options) {
+            eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+        }
+        archive_result(&result, &groups, &names, options);
+        if let Some(seed) = result.metadata.seed {
+            say(options, &lang.format(Msg::Seed, &[&seed, &seed]));
         }
     }
+}
 
-    #[test]
-    fn test_two_person_groups_only_when_not_divisible_by_three() {
-        // Verify that 2-person groups are only created when total % 3 != 0
-        // When total % 3 == 0: 0 two-person groups
-        // When total % 3 == 1: 2 two-person groups (to avoid 1-person groups)
-        // When total % 3 == 2: 1 two-person group
-        for total in 2..=30 {
-            let mut group = Group::new();
-            for i in 0..total {
-                group.members.push(format!("S{:03}", i));
-            }
-            let groups = vec![group];
-            let result = reorganize_batch_groups(groups);
-            
-            let two_person_count = result.iter().filter(|g| g.members.len() == 2).count();
-            let remainder = total % 3;
-            
-            let expected_two_person_groups = match remainder {
-                0 => 0, // Divisible by 3, no 2-person groups
-                1 => 2, // 1 remainder -> need 2 two-person groups (e.g., 4 = 2+2)
-                2 => 1, // 2 remainder -> need 1 two-person group (e.g., 5 = 3+2)
-                _ => unreachable!(),
+/// With `--archive`, keep a dated copy of `result`, made from the `input`
+/// groups. A folder that cannot be written is reported without stopping the
+/// run, since the result was already written.
+fn archive_result(result: &GroupingResult, input: &[Group], names: &HashMap<StudentId, String>, options: &cli::Options) {
+    let Some(dir) = &options.archive else {
+        return;
+    };
+    let lang = result.metadata.lang;
+    let written = anonymized(&result.groups, &result.metadata, options).and_then(|anonymized| match anonymized {
+        Some((groups, metadata)) => archive::write(Path::new(dir), &GroupingResult::new(groups, metadata), &HashMap::new(), input),
+        None => archive::write(Path::new(dir), result, names, input),
+    });
+    let signed = written.and_then(|path| {
+        say(options, &lang.format(Msg::Archived, &[&path.display()]));
+        sign_written(&path, options, lang)
+    });
+    if let Err(e) = signed {
+        eprintln!("{}", lang.format(Msg::ArchiveFailed, &[dir, &e]));
+    }
+}
+
+/// Answer to the `--confirm` question.
+enum Confirm {
+    Accept,
+    Reshuffle,
+    Edit,
+}
+
+/// Ask whether to accept the previewed grouping, on the terminal even when
+/// stdin is piped. An empty line or EOF accepts it.
+fn ask_confirm(lang: Lang) -> Confirm {
+    let mut reader: Box<dyn BufRead> = match File::open("/dev/tty") {
+        Ok(tty) if cfg!(unix) => Box::new(BufReader::new(tty)),
+        _ => Box::new(BufReader::new(io::stdin())),
+    };
+    loop {
+        eprint!("\n{}", lang.text(Msg::ConfirmPrompt));
+        let _ = io::stderr().flush();
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            eprintln!();
+            return Confirm::Accept;
+        }
+        match line.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Confirm::Accept,
+            "r" | "reshuffle" => return Confirm::Reshuffle,
+            "e" | "edit" => return Confirm::Edit,
+            _ => eprintln!("{}", lang.text(Msg::ConfirmUsage)),
+        }
+    }
+}
+
+/// After the result of interactive entry, let the user redo groups with
+/// `reroll B [C ...]` until an empty line (or EOF) accepts the grouping.
+fn reroll_prompt(
+    result: &mut GroupingResult,
+    names: &HashMap<StudentId, String>,
+    options: &cli::Options,
+    constraints: &Constraints,
+    history: Option<&History>,
+) -> io::Result<()> {
+    let lang = result.metadata.lang;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(grouping::random_seed());
+    let mut reader: Box<dyn BufRead> = match File::open("/dev/tty") {
+        Ok(tty) if cfg!(unix) => Box::new(BufReader::new(tty)),
+        _ => Box::new(BufReader::new(io::stdin())),
+    };
+
+    loop {
+        eprint!("\n{}", lang.text(Msg::RerollPrompt));
+        io::stderr().flush()?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let Some(args) = line.strip_prefix("reroll").filter(|args| !args.trim().is_empty()) else {
+            eprintln!("{}", lang.text(Msg::RerollUsage));
+            continue;
+        };
+        let mut targets = Vec::new();
+        for label in args.split([' ', ',']).filter(|label| !label.is_empty()) {
+            match result.metadata.group_names.index_of(label).filter(|&i| i < result.groups.len()) {
+                Some(index) => targets.push(index),
+                None => {
+                    eprintln!("{}", lang.format(Msg::NoSuchGroup, &[&label]));
+                    targets.clear();
+                    break;
+                }
+            }
+        }
+        if targets.is_empty() {
+            continue;
+        }
+        // Incomplete groups are always reshuffled along with the chosen ones
+        if grouping::reroll_pool(&result.groups, &targets, options.sizing()).len() < 2 {
+            eprintln!("{}", lang.format(Msg::RerollAlone, &[&result.metadata.group_names.label(targets[0])]));
+            continue;
+        }
+
+        result.groups = grouping::reroll(&result.groups, &targets, options.sizing(), &mut rng);
+        // The seed no longer reproduces the result
+        result.metadata.seed = None;
+        if options.assign_leader {
+            result.metadata.leaders = leader::update_leaders(&result.groups, &result.metadata.leaders, history, &mut rng);
+        }
+        let labels: Vec<String> = targets.iter().map(|&i| result.metadata.group_names.label(i)).collect();
+        eprintln!("{}", lang.format(Msg::Rerolled, &[&labels.join(", ")]));
+        for violation in constraints.unsatisfied(&result.groups) {
+            eprintln!("{}", lang.format(Msg::Unsatisfied, &[&violation]));
+        }
+        print_groups(result, names, options)?;
+    }
+}
+
+/// Print a multi-session plan, or write it to the `--out` file when given.
+fn print_schedule(
+    plan: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    options: &cli::Options,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let lang = metadata.lang;
+    let rendered = match options.anonymize {
+        true => {
+            let plan = with_pseudonyms(options, |pseudonyms| {
+                plan.iter().map(|session| pseudonyms.groups(session)).collect::<Vec<_>>()
+            })?;
+            render_plan(&plan, &HashMap::new(), options, metadata)
+        }
+        false => render_plan(plan, names, options, metadata),
+    };
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, &rendered)?;
+            say(options, &lang.format(Msg::SavedTo, &[path]));
+        }
+        None => print!("{}", rendered),
+    }
+
+    if options.clipboard {
+        match clipboard::copy(rendered.trim_start()) {
+            Ok(_) => say(options, lang.text(Msg::Copied)),
+            Err(e) => eprintln!("{}", lang.format(Msg::CopyFailed, &[&e])),
+        }
+    }
+
+    Ok(())
+}
+
+/// The sessions of a plan, one after another or with `--per-student` student by student.
+fn render_plan(plan: &[Vec<Group>], names: &HashMap<StudentId, String>, options: &cli::Options, metadata: &Metadata) -> String {
+    match options.per_student {
+        true => output::render_per_student(plan, names, options.format, metadata),
+        false => output::render_schedule(plan, names, options.format, metadata),
+    }
+}
+
+/// Write a term made by `plan` into the `--out` folder ([`DEFAULT_PLAN_DIR`]
+/// by default): each session as the result of a single run, so that it can
+/// be handed out or read back like one, the whole schedule and, with
+/// `--per-student`, the schedule of every student.
+fn write_plan(
+    plan: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    options: &cli::Options,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let lang = metadata.lang;
+    let dir = PathBuf::from(options.out.as_deref().unwrap_or(DEFAULT_PLAN_DIR));
+    std::fs::create_dir_all(&dir)?;
+    let (plan, names) = match options.anonymize {
+        true => {
+            let plan = with_pseudonyms(options, |pseudonyms| {
+                plan.iter().map(|session| pseudonyms.groups(session)).collect::<Vec<_>>()
+            })?;
+            (plan, HashMap::new())
+        }
+        false => (plan.to_vec(), names.clone()),
+    };
+
+    let extension = options.format.extension();
+    for (n, groups) in plan.iter().enumerate() {
+        let result = GroupingResult::new(groups.clone(), metadata.clone());
+        let path = dir.join(format!("session-{:02}.{}", n + 1, extension));
+        std::fs::write(path, output::render(&result, &names, options.format))?;
+    }
+    let overview = output::render_schedule(&plan, &names, options.format, metadata);
+    std::fs::write(dir.join(format!("schedule.{}", extension)), overview)?;
+    if options.per_student {
+        let listing = output::render_per_student(&plan, &names, options.format, metadata);
+        std::fs::write(dir.join(format!("students.{}", extension)), listing)?;
+    }
+    say(options, &lang.format(Msg::PlanWritten, &[&plan.len(), &dir.display()]));
+    Ok(())
+}
+
+/// Write a schedule or plan with dates to the `--ics` file, one event per session.
+fn write_calendar(
+    plan: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    options: &cli::Options,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let (Some(path), Some(start)) = (&options.ics, options.start_date) else {
+        return Ok(());
+    };
+    let dates = ical::Dates {
+        start,
+        interval: if options.weekly { 7 } else { 1 },
+    };
+    let calendar = match options.anonymize {
+        true => {
+            let plan = with_pseudonyms(options, |pseudonyms| {
+                plan.iter().map(|session| pseudonyms.groups(session)).collect::<Vec<_>>()
+            })?;
+            ical::render(&plan, &HashMap::new(), dates, metadata)
+        }
+        false => ical::render(plan, names, dates, metadata),
+    };
+    std::fs::write(path, calendar)?;
+    say(options, &metadata.lang.format(Msg::CalendarWritten, &[path]));
+    Ok(())
+}
+
+/// Where to read the help of the subcommand being run, shown after an argument error.
+fn usage_hint(lang: Lang) -> String {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match cli::Command::of_args(&args).name() {
+        "" => String::new(),
+        name => format!(" {}", name),
+    };
+    lang.format(Msg::UsageHint, &[&command])
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    i18n::set_lang(cli::lang_of_args(&args).unwrap_or_else(Lang::detect));
+    let options = match cli::parse_args(args) {
+        Ok(options) => options,
+        Err(message) => {
+            let lang = Lang::current();
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            eprintln!("{}", usage_hint(lang));
+            std::process::exit(2);
+        }
+    };
+    // Settings from the configuration file go before the typed flags, which win
+    let options = match config::load(&options) {
+        Ok(defaults) if defaults.is_empty() => options,
+        Ok(defaults) => match cli::parse_args_with_defaults(defaults, std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(message) => {
+                let lang = options.lang.unwrap_or_else(Lang::detect);
+                eprintln!("{}", lang.format(Msg::ConfigInvalid, &[&message]));
+                std::process::exit(2);
+            }
+        },
+        Err(message) => {
+            let lang = options.lang.unwrap_or_else(Lang::detect);
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(2);
+        }
+    };
+    let lang = options.lang.unwrap_or_else(Lang::detect);
+    i18n::set_lang(lang);
+    if options.help {
+        println!("{}", cli::usage(options.command, lang));
+        return;
+    }
+    grouping_tool::log::set_verbosity(options.verbose);
+    if options.command == cli::Command::Interactive && !stdin_is_tty() {
+        eprintln!("{}", lang.text(Msg::NeedsTerminal));
+        std::process::exit(2);
+    }
+
+    if options.command == cli::Command::Serve {
+        let addr = options.listen.as_deref().unwrap_or("127.0.0.1:8080");
+        eprintln!("{}", lang.format(Msg::Listening, &[&addr]));
+
+        if let Err(e) = server::serve(addr) {
+            eprintln!("{}", lang.format(Msg::ListenFailed, &[&addr, &e]));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Load constraints before any input is typed so mistakes surface immediately
+    let mut constraints = match &options.constraints {
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))
+            .and_then(|text| Constraints::parse(&text).map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(constraints) => constraints,
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        },
+        None => Constraints::default(),
+    };
+    if let Some(path) = &options.avoid_file {
+        match std::fs::read_to_string(path)
+            .map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))
+            .and_then(|text| Constraints::parse_pairs(&text).map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(pairs) => constraints.apart.extend(pairs),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        }
+    }
+    let preferences = match &options.prefer_file {
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))
+            .and_then(|text| Constraints::parse_pairs(&text).map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(pairs) => pairs,
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    constraints.prefer(preferences.clone());
+    let wishes = match &options.preferences {
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))
+            .and_then(|text| Preferences::parse(&text).map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(wishes) => Some(wishes),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    // The same for the classroom and the template; they are read again whenever the groups are written
+    if let Some(Err(message)) = options.seating.as_deref().map(load_layout) {
+        eprintln!("{}", lang.format(Msg::Error, &[&message]));
+        std::process::exit(1);
+    }
+    if let Some(Err(message)) = options.template.as_deref().map(load_template) {
+        eprintln!("{}", lang.format(Msg::Error, &[&message]));
+        std::process::exit(1);
+    }
+    let exported = match options.append.as_deref().map(load_exported) {
+        Some(Ok(exported)) => Some(exported),
+        Some(Err(message)) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    // An empty --id-pattern turns the check off
+    let id_pattern = match options.id_pattern.as_deref().unwrap_or(DEFAULT_ID_PATTERN) {
+        "" => None,
+        source => match Pattern::new(source) {
+            Ok(pattern) => Some(pattern),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&format!("--id-pattern: {}", message)]));
+                std::process::exit(2);
+            }
+        },
+    };
+
+    let group_names = match group_names(&options, lang) {
+        Ok(group_names) => group_names,
+        Err(message) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+    };
+
+    let db = match options.db.as_deref().map(|path| Database::open(Path::new(path))) {
+        Some(Ok(db)) => Some(db),
+        Some(Err(message)) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    if let (cli::Command::Query, Some(db)) = (options.command, &db) {
+        if let Err(message) = print_query(db, options.student.as_deref()) {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // With --db, past sessions live in the database instead of the history file
+    let history_path = options
+        .history_file
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(history::default_path);
+    if options.command == cli::Command::Verify {
+        match verify(&options, lang) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(EXIT_BAD_SIGNATURE),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        }
+    }
+    // Subcommands that read exported results instead of the history
+    let compared = match options.command {
+        cli::Command::Merge => Some(merge(&options, group_names.clone(), lang)),
+        cli::Command::Diff => Some(diff(&options, lang)),
+        _ => None,
+    };
+    if let Some(result) = compared {
+        if let Err(message) = result {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Subcommands that work on a recorded session
+    let recorded: Option<RecordedCommand> = match options.command {
+        cli::Command::Export => Some(export),
+        cli::Command::Bracket => Some(bracket),
+        cli::Command::Order => Some(order),
+        cli::Command::Score => Some(score),
+        cli::Command::Leaderboard => Some(leaderboard),
+        _ => None,
+    };
+    if let Some(run) = recorded {
+        if let Err(message) = run(&options, db.as_ref(), history_path.as_deref(), group_names, lang) {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let replayed = match options.replay {
+        Some(seed) => match replayed_session(seed, db.as_ref(), history_path.as_deref()) {
+            Ok(None) => {
+                eprintln!("{}", lang.format(Msg::ReplayNotRecorded, &[&seed]));
+                None
+            }
+            Ok(replayed) => replayed,
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // --stats compares with the history without avoiding repeats
+    let past = if options.uses_history() || options.stats {
+        let loaded = match (&replayed, &db, &history_path) {
+            // A replay avoids the pairings known when the replayed run was made
+            (Some((earlier, _)), _, _) => Ok(History::from_sessions(earlier)),
+            (None, Some(db), _) => db.history(),
+            (None, None, Some(path)) => History::load(path),
+            // Exported results alone make a history
+            (None, None, None) if options.history_dir.is_some() => Ok(History::default()),
+            (None, None, None) => Err(i18n::message(Msg::HistoryUnknown, &[])),
+        };
+        let loaded = match (loaded, &options.history_dir) {
+            (Ok(mut history), Some(dir)) => history::read_exports(Path::new(dir)).map(|sessions| {
+                say(&options, &lang.format(Msg::ExportsRead, &[dir, &sessions.len()]));
+                for session in &sessions {
+                    history.record(&session.groups);
+                    history.record_leaders(&session.leaders);
+                }
+                history
+            }),
+            (loaded, _) => loaded,
+        };
+        match loaded {
+            Ok(history) => Some(history),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let history = past.clone().filter(|_| options.uses_history());
+
+    let mixes_strata = options.stratify.is_some() && !options.within_strata;
+    if constraints.has_hard() && (!options.balance.is_empty() || options.spread_weights.is_some() || mixes_strata) {
+        eprintln!("{}", lang.text(Msg::BalanceIgnored));
+    }
+    let weighs = options.optimize || options.strategy.as_deref() == Some("genetic") || options.uses_history();
+    if !constraints.soft.is_empty() && !weighs {
+        eprintln!("{}", lang.text(Msg::SoftIgnored));
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+
+    // Set up Ctrl+C handler
+    let interrupted = if options.watch.is_some() { Msg::WatchStopped } else { Msg::Interrupted };
+    let quiet = options.quiet;
+    ctrlc::set_handler(move || {
+        if !quiet {
+            eprintln!("{}", lang.text(interrupted));
+        }
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let google_token = if options.sheet.is_some() || options.classroom.is_some() {
+        let credentials = options
+            .google_credentials
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(sheets::default_credentials_path);
+        match sheets::access_token(credentials.as_deref()) {
+            Ok(token) => Some(token),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let roster = read_roster(&options, google_token.as_deref());
+    if options.command == cli::Command::Import {
+        let result = roster
+            .unwrap_or_else(|| Err(i18n::message(Msg::NoRoster, &[])))
+            .and_then(|entries| db.as_ref().expect("import requires --db").import_students(&entries));
+        match result {
+            Ok(count) => say(&options, &lang.format(Msg::DbImported, &[&count])),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &options.watch {
+        // The seed is kept across reloads, so that an edit only moves the students it has to
+        let config = GroupingConfig {
+            sizing: options.sizing(),
+            mode: Mode::Batch,
+            seed: Some(options.seed().unwrap_or_else(rand::random)),
+            constraints,
+            balance: options.balance.clone(),
+            spread_weights: options.spread_weights.clone(),
+            stratify: options.stratify.clone(),
+            within_strata: options.within_strata,
+            skill: options.skill.clone(),
+            group_by: options.group_by.clone(),
+            no_shuffle: options.no_shuffle,
+            history,
+            optimize_iterations: options.optimize_iterations(),
+            optimize_time: options.optimize_time(),
+            population: options.population,
+            generations: options.generations,
+            preferences: wishes,
+            strategy: options.strategy(),
+            size_overrides: HashMap::new(),
+            pinned: HashSet::new(),
+        };
+        watch(path, &options, config, &group_names, db.as_ref(), running, lang);
+        return;
+    }
+
+    let name_file = match options.names.as_deref().map(read_names) {
+        Some(Ok(names)) => names,
+        Some(Err(message)) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        None => HashMap::new(),
+    };
+
+    if options.stream {
+        if options.command != cli::Command::Batch && stdin_is_tty() {
+            eprintln!("{}", lang.text(Msg::StreamNeedsPipe));
+            std::process::exit(2);
+        }
+        let metadata = Metadata {
+            timestamp: grouping_tool::time::now_rfc3339(),
+            group_names,
+            lang,
+            ..Default::default()
+        };
+        // Names are not written with --anonymize
+        let names = if options.anonymize { HashMap::new() } else { name_file };
+        match stream_groups(&options, running, id_pattern.as_ref(), &names, &metadata) {
+            Ok(0) => {}
+            Ok(status) => std::process::exit(status),
+            Err(e) => {
+                eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+                std::process::exit(EXIT_IO);
+            }
+        }
+        return;
+    }
+
+    // replay enters the students of a logged entry instead of reading them
+    let logged = match options.command {
+        cli::Command::Replay => match read_log(&options.result_files[0]) {
+            Ok(replay) => Some(replay),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    if let Some(replay) = &logged {
+        let count: usize = replay.entered().iter().map(|g| g.members.len()).sum();
+        say(&options, &lang.format(Msg::LogReplayed, &[&options.result_files[0], &count]));
+    }
+
+    let Input {
+        mut groups,
+        batch_mode,
+        mut names,
+        mut students,
+        sizes,
+        pins,
+        rejected,
+        mut edits,
+    } = match roster
+        .map(|entries| entries.map(roster_input))
+        .or_else(|| logged.as_ref().map(|replay| Ok(logged_input(replay))))
+    {
+        Some(Ok(input)) => input,
+        Some(Err(message)) => {
+            eprintln!("{}", lang.format(Msg::Error, &[&message]));
+            std::process::exit(1);
+        }
+        None => {
+            let initial = match &options.resume {
+                Some(path) => match Session::load(path) {
+                    Ok(session) => {
+                        let count: usize = session.groups.iter().chain([&session.current]).map(|g| g.members.len()).sum();
+                        eprintln!("{}", lang.format(Msg::SessionResumed, &[path, &count]));
+                        session
+                    }
+                    Err(message) => {
+                        eprintln!("{}", lang.format(Msg::Error, &[&message]));
+                        std::process::exit(1);
+                    }
+                },
+                None => Session::default(),
+            };
+            read_entered(&options, running.clone(), id_pattern.as_ref(), &group_names, lang, initial, &name_file)
+        }
+    };
+
+    if groups.is_empty() {
+        say(&options, lang.text(Msg::NoInput));
+        std::process::exit(EXIT_NO_INPUT);
+    }
+
+    if let Some(db) = &db {
+        fill_from_db(db, &mut names, &mut students, lang);
+    }
+    // Names in the roster or the database take precedence over `--names`
+    for (id, name) in &name_file {
+        names.entry(id.clone()).or_insert_with(|| name.clone());
+    }
+    attach_students(&mut groups, &students, &names);
+    if let Some(key) = &options.spread_weights {
+        for student in groups.iter().flat_map(|g| &g.members) {
+            if let Some(value) = student.attribute(key).filter(|_| student.weight(key).is_none()) {
+                eprintln!("{}", lang.format(Msg::WeightInvalid, &[&student.id, &value]));
+            }
+        }
+    }
+    if let Some(key) = &options.skill {
+        for student in groups.iter().flat_map(|g| &g.members) {
+            if let Some(value) = student.attribute(key).filter(|_| student.weight(key).is_none()) {
+                eprintln!("{}", lang.format(Msg::SkillInvalid, &[&student.id, &value]));
+            }
+        }
+    }
+
+    if let Some((exported_groups, exported_names, exported_metadata)) = exported {
+        for (id, name) in exported_names {
+            names.entry(id).or_insert(name);
+        }
+        let metadata = Metadata {
+            timestamp: grouping_tool::time::now_rfc3339(),
+            group_names,
+            leaders: exported_metadata.leaders,
+            lang,
+            ..Default::default()
+        };
+        append_late(exported_groups, &groups, &names, &options, metadata);
+        if rejected > 0 {
+            std::process::exit(EXIT_INVALID_ID);
+        }
+        return;
+    }
+
+    // Use different reorganization logic based on mode
+    // Pick a seed up front so that it can be shown and reused with --seed
+    // A replay shuffles with the logged seed, so that it can come out the same
+    let logged_seed = logged.as_ref().and_then(|replay| replay.seed);
+    let mut seed = options.seed().or(logged_seed).unwrap_or_else(rand::random);
+    let mut config = GroupingConfig {
+        sizing: options.sizing(),
+        mode: if batch_mode {
+            Mode::Batch
+        } else {
+            Mode::Interactive
+        },
+        seed: Some(seed),
+        constraints: constraints.clone(),
+        balance: options.balance.clone(),
+        spread_weights: options.spread_weights.clone(),
+        stratify: options.stratify.clone(),
+        within_strata: options.within_strata,
+        skill: options.skill.clone(),
+        group_by: options.group_by.clone(),
+        no_shuffle: options.no_shuffle,
+        history: history.clone(),
+        optimize_iterations: options.optimize_iterations(),
+        optimize_time: options.optimize_time(),
+        population: options.population,
+        generations: options.generations,
+        preferences: wishes.clone(),
+        strategy: options.strategy(),
+        size_overrides: HashMap::new(),
+        pinned: HashSet::new(),
+    };
+    if matches!(options.command, cli::Command::Schedule | cli::Command::Plan) {
+        let sessions = options.sessions.unwrap_or(1);
+        let students: Vec<Student> = groups.iter().flat_map(|g| g.members.clone()).collect();
+        warn_shortfall(config.sizing, students.len(), lang);
+        let planned = match options.command {
+            cli::Command::Plan => schedule::plan(students, sessions, &config),
+            _ => schedule::schedule(students, sessions, &config),
+        };
+        let plan = match planned {
+            Ok(plan) => plan,
+            Err(e) => {
+                eprintln!("{}", lang.format(Msg::GroupingFailed, &[&e]));
+                std::process::exit(1);
+            }
+        };
+        let mut unsatisfied = false;
+        for (n, session) in plan.iter().enumerate() {
+            for violation in constraints.unsatisfied(session) {
+                eprintln!("{}", lang.format(Msg::UnsatisfiedInSession, &[&(n + 1), &violation]));
+                unsatisfied = true;
+            }
+        }
+        let metadata = Metadata {
+            timestamp: grouping_tool::time::now_rfc3339(),
+            seed: Some(seed),
+            group_names,
+            lang,
+            mode: Some(config.mode),
+            config: config.settings(),
+            ..Default::default()
+        };
+        let written = match options.command {
+            cli::Command::Plan => write_plan(&plan, &names, &options, &metadata),
+            _ => print_schedule(&plan, &names, &options, &metadata),
+        }
+        .and_then(|_| write_calendar(&plan, &names, &options, &metadata));
+        if let Err(e) = written {
+            eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+            std::process::exit(EXIT_IO);
+        }
+
+        let (distinct, repeated) = schedule::pair_summary(&plan);
+        say(&options, &lang.format(Msg::PairSummary, &[&distinct, &repeated]));
+        say(&options, &lang.format(Msg::Seed, &[&seed, &seed]));
+        // A schedule is a plan for future sessions, so it is not written to the history
+        if let Some(status) = warning_status(rejected, unsatisfied) {
+            std::process::exit(status);
+        }
+        return;
+    }
+    // Sizes given in the input apply to its groups, which a schedule does not keep
+    config.size_overrides = sizes;
+    // A group of one cannot be kept as it is, so its pin is dropped
+    config.pinned = pins
+        .into_iter()
+        .filter(|&i| match groups.get(i) {
+            Some(group) if group.members.len() == 1 => {
+                eprintln!("{}", lang.format(Msg::PinIgnored, &[&group_names.label(i)]));
+                false
+            }
+            group => group.is_some(),
+        })
+        .collect();
+
+    let shuffled = config.uses_rng() || options.assign_leader;
+    let (mut final_groups, mut leaders) = make_groups_or_exit(&groups, &config, &options, history.as_ref(), lang);
+    // With --confirm, nothing is written until the grouping is accepted
+    if options.confirm {
+        loop {
+            let preview = Metadata {
+                group_names: group_names.clone(),
+                leaders: leaders.clone(),
+                lang,
+                ..Default::default()
             };
-            
-            assert_eq!(
-                two_person_count, expected_two_person_groups,
-                "For {} members (remainder {}), expected {} two-person groups but got {}",
-                total, remainder, expected_two_person_groups, two_person_count
-            );
+            eprint!("{}", output::render_text_styled(&final_groups, &names, &preview, message_style(&options)));
+            match ask_confirm(lang) {
+                Confirm::Accept => break,
+                Confirm::Reshuffle if !shuffled => eprintln!("{}", lang.text(Msg::ConfirmSameOrder)),
+                Confirm::Reshuffle => {
+                    seed = rand::random();
+                    config.seed = Some(seed);
+                }
+                Confirm::Edit if batch_mode => eprintln!("{}", lang.text(Msg::ConfirmNoEdit)),
+                Confirm::Edit => {
+                    let input = read_entered(
+                        &options,
+                        running.clone(),
+                        id_pattern.as_ref(),
+                        &group_names,
+                        lang,
+                        Session::with_groups(groups),
+                        &names,
+                    );
+                    groups = input.groups;
+                    students.extend(input.students);
+                    if let (Some(edits), Some(more)) = (&mut edits, input.edits) {
+                        *edits += more;
+                    }
+                    if let Some(db) = &db {
+                        fill_from_db(db, &mut names, &mut students, lang);
+                    }
+                    attach_students(&mut groups, &students, &names);
+                }
+            }
+            (final_groups, leaders) = make_groups_or_exit(&groups, &config, &options, history.as_ref(), lang);
+        }
+    }
+    let metadata = Metadata {
+        timestamp: grouping_tool::time::now_rfc3339(),
+        seed: if shuffled { Some(seed) } else { None },
+        group_names,
+        leaders,
+        lang,
+        mode: Some(config.mode),
+        config: config.settings(),
+    };
+    let mut result = GroupingResult::new(final_groups, metadata);
+    if let Err(e) = print_groups(&result, &names, &options) {
+        eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+        std::process::exit(EXIT_IO);
+    }
+    let replaying = logged.is_some();
+    if !batch_mode && !replaying && !options.quiet && stdin_is_tty() && result.groups.len() > 1 {
+        let rerolled = reroll_prompt(&mut result, &names, &options, &constraints, history.as_ref());
+        if let Err(e) = rerolled {
+            eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+            std::process::exit(EXIT_IO);
+        }
+    }
+    post_groups(&result.groups, &names, &options, &result.metadata);
+    archive_result(&result, &groups, &names, &options);
+    // The result ends the entry in the session log, for replay to check against
+    let mut audit = AuditLog::new(options.session_log.as_deref().filter(|_| !batch_mode && !replaying));
+    let logged_result = Event::Result {
+        seed: result.metadata.seed,
+        hash: output::content_hash(&result.groups),
+    };
+    log_edit(&mut audit, logged_result, lang);
+    if let (Some(spreadsheet_id), Some(range), Some(token)) = (&options.sheet, &options.sheet_output, &google_token) {
+        let rows = match anonymized(&result.groups, &result.metadata, &options) {
+            Ok(Some((groups, metadata))) => {
+                let sorted = sorted_members(&groups, &options);
+                output::to_rows(sorted.as_deref().unwrap_or(&groups), &HashMap::new(), &metadata)
+            }
+            Ok(None) => {
+                let sorted = sorted_members(&result.groups, &options);
+                output::to_rows(sorted.as_deref().unwrap_or(&result.groups), &names, &result.metadata)
+            }
+            Err(e) => {
+                eprintln!("{}", lang.format(Msg::SheetWriteFailed, &[&e]));
+                std::process::exit(1);
+            }
+        };
+        match sheets::write_rows(spreadsheet_id, range, &rows, token) {
+            Ok(()) => say(&options, &lang.format(Msg::SheetWritten, &[range])),
+            Err(message) => {
+                eprintln!("{}", lang.format(Msg::SheetWriteFailed, &[&message]));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Plain batch input is not shuffled, so the seed would be meaningless there.
+    // Keep it out of machine-readable output on stdout.
+    let recorded = !options.no_save_history && options.replay.is_none() && !replaying;
+    if let Some(seed) = result.metadata.seed {
+        let message = if recorded { Msg::SeedReplay } else { Msg::Seed };
+        say(&options, &lang.format(message, &[&seed, &seed]));
+    }
+    if let Some((_, session)) = &replayed {
+        let same = result.groups.len() == session.groups.len()
+            && result.groups.iter().zip(&session.groups).all(|(a, b)| a.ids().eq(b.ids()));
+        match same {
+            true => say(&options, &lang.format(Msg::ReplayMatches, &[&session.id])),
+            false => eprintln!("{}", lang.format(Msg::ReplayDiffers, &[&session.id])),
+        }
+    }
+    let mut log_differs = false;
+    if let Some(replay) = &logged {
+        let hash = output::content_hash(&result.groups);
+        match &replay.hash {
+            Some(logged) if *logged == hash => say(&options, lang.text(Msg::LogMatches)),
+            Some(logged) => {
+                eprintln!("{}", lang.format(Msg::LogDiffers, &[logged, &hash]));
+                log_differs = true;
+            }
+            None => say(&options, lang.text(Msg::LogWithoutResult)),
+        }
+    }
+    if options.stats {
+        let mut stats = Stats::new(&result.groups, past.as_ref(), &constraints);
+        // The score the search lowered, for judging whether it needs more time or generations
+        if config.optimize_time.is_some() || config.strategy.is_some_and(|s| s.name() == "genetic") {
+            stats.fitness = Some(anneal::score(&result.groups, &config));
+        }
+        stats.edits = edits;
+        say(&options, &stats.render(lang));
+    } else if let Some(history) = &history {
+        say(
+            &options,
+            &lang.format(
+                Msg::RepeatSummary,
+                &[&history.sessions(), &history.repeat_count(&result.groups)],
+            ),
+        );
+    }
+    // The stats above already include it
+    if !constraints.soft.is_empty() && !options.stats {
+        let broken = constraints.unsatisfied_soft(&result.groups);
+        let penalty: u64 = broken.iter().map(|(_, weight)| weight).sum();
+        say(&options, &lang.format(Msg::SoftPenalty, &[&penalty, &broken.len()]));
+    }
+    if !preferences.is_empty() {
+        let honored: Vec<bool> = preferences
+            .iter()
+            .map(|ids| constraints::kept_together(ids, &result.groups))
+            .collect();
+        let count = honored.iter().filter(|&&kept| kept).count();
+        say(&options, &lang.format(Msg::PreferencesHonored, &[&count, &preferences.len()]));
+        for (ids, kept) in preferences.iter().zip(honored) {
+            let mark = if kept { "○" } else { "×" };
+            say(&options, &format!("  {} {}", mark, ids.join(", ")));
+        }
+    }
+    if let Some(wishes) = &wishes {
+        let satisfaction = wishes.satisfaction(&result.groups);
+        let granted: usize = satisfaction.iter().map(|s| s.granted).sum();
+        let listed: usize = satisfaction.iter().map(|s| s.listed).sum();
+        say(&options, &lang.format(Msg::WishesGranted, &[&granted, &listed]));
+        for student in &satisfaction {
+            say(&options, &format!("  {}: {} / {}", student.id, student.granted, student.listed));
+        }
+    }
+
+    if recorded {
+        if let Some(db) = &db {
+            match db.record_session(&result.groups, &result.metadata.leaders, &result.metadata.timestamp, result.metadata.seed) {
+                Ok(session) => say(&options, &lang.format(Msg::DbRecorded, &[&session])),
+                Err(message) => eprintln!("{}", lang.format(Msg::DbRecordFailed, &[&message])),
+            }
+        } else if let Some(path) = &history_path {
+            if let Err(e) = history::append(path, &result.groups, &result.metadata.leaders, &result.metadata.timestamp, result.metadata.seed) {
+                eprintln!("{}", lang.format(Msg::HistorySaveFailed, &[&path.display(), &e]));
+            }
         }
     }
+    // A replay is run to check the log, so a different result matters most
+    if log_differs {
+        std::process::exit(EXIT_REPLAY_DIFFERS);
+    }
+    if let Some(status) = warning_status(rejected, !constraints.unsatisfied(&result.groups).is_empty()) {
+        std::process::exit(status);
+    }
+}
+
+/// The exit status of a grouping that was written despite `rejected` skipped
+/// input lines or `unsatisfied` constraints, the input coming first.
+fn warning_status(rejected: usize, unsatisfied: bool) -> Option<i32> {
+    if rejected > 0 {
+        Some(EXIT_INVALID_ID)
+    } else if unsatisfied {
+        Some(EXIT_UNSATISFIED)
+    } else {
+        None
+    }
+}
+
+/// Read a session log written with `--session-log` for `replay`.
+fn read_log(path: &str) -> Result<Replay, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+    Replay::parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// The students of the entry made again from a session log.
+fn logged_input(replay: &Replay) -> Input {
+    Input {
+        groups: replay.entered(),
+        batch_mode: false,
+        names: HashMap::new(),
+        students: replay.students.clone(),
+        sizes: HashMap::new(),
+        pins: replay.pins.clone(),
+        rejected: 0,
+        edits: Some(replay.edits),
+    }
+}
+
+/// Read a grouping written with `--format json` for `--append`.
+fn load_exported(path: &str) -> Result<output::Exported, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| i18n::message(Msg::CannotOpen, &[&path, &e]))?;
+    let value = grouping_tool::json::Value::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+    output::from_json(&value).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Add the students of `entered` to the exported `groups` and write the result.
+/// The original grouping is already in the history, so nothing is recorded.
+fn append_late(
+    groups: Vec<Group>,
+    entered: &[Group],
+    names: &HashMap<StudentId, String>,
+    options: &cli::Options,
+    metadata: Metadata,
+) {
+    let lang = metadata.lang;
+    let mut late = Vec::new();
+    for id in entered.iter().flat_map(|g| &g.members) {
+        match groups.iter().position(|g| g.members.contains(id)) {
+            Some(i) => eprintln!("{}", lang.format(Msg::AppendAlreadyPlaced, &[id, &metadata.group_names.label(i)])),
+            None => late.push(id.clone()),
+        }
+    }
+    let result = GroupingResult::new(grouping::append_late(groups, late.clone(), options.sizing()), metadata);
+    if let Err(e) = print_groups(&result, names, options) {
+        eprintln!("{}", lang.format(Msg::WriteFailed, &[&e]));
+        std::process::exit(EXIT_IO);
+    }
+    post_groups(&result.groups, names, options, &result.metadata);
+    archive_result(&result, entered, names, options);
+    for id in &late {
+        if let Some(i) = result.groups.iter().position(|g| g.members.contains(id)) {
+            say(options, &lang.format(Msg::AppendJoined, &[id, &result.metadata.group_names.label(i)]));
+        }
+    }
+}
+
+/// Print a prompt, a confirmation or a notice, unless `--quiet` is given.
+/// They go to stderr, so that stdout holds nothing but the result and
+/// `grouping-tool < ids.txt > result.txt` captures it cleanly.
+fn say(options: &cli::Options, message: &str) {
+    if !options.quiet {
+        eprintln!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_flag_behavior() {
+        // Test that the atomic flag works correctly
+        let running = Arc::new(AtomicBool::new(true));
+        assert!(running.load(Ordering::SeqCst));
+
+        running.store(false, Ordering::SeqCst);
+        assert!(!running.load(Ordering::SeqCst));
+
+        running.store(true, Ordering::SeqCst);
+        assert!(running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_read_names() {
+        let path = std::env::temp_dir().join(format!("grouping-tool-names-{}.csv", std::process::id()));
+        std::fs::write(&path, "S001,山田太郎\nS002\n\"S003\",\"Smith, Jane\"\n").unwrap();
+        let names = read_names(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(names.get("S001").map(String::as_str), Some("山田太郎"));
+        assert_eq!(names.get("S003").map(String::as_str), Some("Smith, Jane"));
+        assert_eq!(names.len(), 2);
+        assert!(read_names("/nonexistent/names.csv").is_err());
+    }
+
+    #[test]
+    fn test_size_header() {
+        assert_eq!(size_header("@size=4"), Some(Some(4)));
+        assert_eq!(size_header("@SIZE= 5"), Some(Some(5)));
+        assert_eq!(size_header("#2"), Some(Some(2)));
+        assert_eq!(size_header("@size=1"), Some(None));
+        assert_eq!(size_header("@size=four"), Some(None));
+        assert_eq!(size_header("S001"), None);
+        assert_eq!(size_header("#A1"), None);
+    }
+
+    #[test]
+    fn test_warning_status() {
+        assert_eq!(warning_status(0, false), None);
+        assert_eq!(warning_status(0, true), Some(EXIT_UNSATISFIED));
+        assert_eq!(warning_status(2, true), Some(EXIT_INVALID_ID));
+    }
 
     #[test]
-    fn test_batch_mode_maximizes_three_person_groups() {
-        // Test that batch mode now maximizes 3-person groups even with user-defined 2-person groups
-        // 12 members with user-defined 2-person groups should become 4 three-person groups
-        let groups: Vec<Group> = (0..6).map(|i| {
-            let mut g = Group::new();
-            g.members = vec![
-                format!("S{:03}", i * 2),
-                format!("S{:03}", i * 2 + 1),
-            ];
-            g
-        }).collect();
-        
-        let result = reorganize_batch_groups(groups);
-        
-        // 12 members (12 % 3 == 0) should all be 3-person groups
-        let three_person_count = result.iter().filter(|g| g.members.len() == 3).count();
-        let two_person_count = result.iter().filter(|g| g.members.len() == 2).count();
-        
-        assert_eq!(three_person_count, 4, "Should have 4 three-person groups");
-        assert_eq!(two_person_count, 0, "Should have 0 two-person groups when divisible by 3");
-        
-        // Verify total is preserved
-        let total: usize = result.iter().map(|g| g.members.len()).sum();
-        assert_eq!(total, 12);
+    fn test_pin_command() {
+        assert_eq!(pin_command("!"), Some((true, None)));
+        assert_eq!(pin_command("pin:"), Some((true, None)));
+        assert_eq!(pin_command("PIN: B"), Some((true, Some("B"))));
+        assert_eq!(pin_command("unpin:C"), Some((false, Some("C"))));
+        assert_eq!(pin_command("S001"), None);
+        assert_eq!(pin_command("move:S001:B"), None);
     }
 }
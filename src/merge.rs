@@ -0,0 +1,110 @@
+//! Combining groupings exported with `--format json`, such as those of two
+//! lab sections, into one result (`merge` subcommand).
+//!
+//! The groups are kept as they are and numbered again in order, so that the
+//! groups of the first file come first and no two groups share a label.
+
+use crate::group::{Group, MAX_GROUP_SIZE};
+use crate::grouping::Sizing;
+use crate::i18n::{message, Msg};
+use crate::output::{Exported, Metadata};
+
+/// Put the groups of `parts` one after another. Names are merged and the
+/// leaders kept; a student found in more than one part is an error.
+pub fn merge(parts: Vec<Exported>) -> Result<Exported, String> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut names = std::collections::HashMap::new();
+    let mut leaders = Vec::new();
+    for (part_groups, part_names, part_metadata) in parts {
+        for id in part_groups.iter().flat_map(|g| &g.members) {
+            if groups.iter().any(|g| g.members.contains(id)) {
+                return Err(message(Msg::MergeDuplicate, &[&id]));
+            }
+        }
+        groups.extend(part_groups);
+        for (id, name) in part_names {
+            names.entry(id).or_insert(name);
+        }
+        leaders.extend(part_metadata.leaders);
+    }
+    let metadata = Metadata {
+        leaders,
+        ..Default::default()
+    };
+    Ok((groups, names, metadata))
+}
+
+/// Indices of the groups of one (or smaller than `--min`), and of groups larger
+/// than `sizing` ever makes (3, `--max`, or the largest of the even sizes with `--groups`).
+pub fn unusual_groups(groups: &[Group], sizing: Sizing) -> Vec<usize> {
+    let largest = match sizing {
+        Sizing::Groups(_) => {
+            let total = groups.iter().map(|g| g.members.len()).sum();
+            sizing.sizes(total).into_iter().max().unwrap_or(0)
+        }
+        Sizing::Range { max, .. } => {
+            let total = groups.iter().map(|g| g.members.len()).sum();
+            sizing.largest_size(total).max(max)
+        }
+        Sizing::Triples | Sizing::Pairs | Sizing::Even => MAX_GROUP_SIZE,
+    };
+    (0..groups.len())
+        .filter(|&i| !(sizing.min_size()..=largest).contains(&groups[i].members.len()))
+        .collect()
+}
+
+/// The group sizes, largest first, and those `sizing` would make of the same
+/// students when they differ (for example more 2-person groups than needed).
+pub fn size_difference(groups: &[Group], sizing: Sizing) -> Option<(Vec<usize>, Vec<usize>)> {
+    let mut sizes: Vec<usize> = groups.iter().map(|g| g.members.len()).collect();
+    sizes.sort_by(|a, b| b.cmp(a));
+    let mut expected = sizing.sizes(sizes.iter().sum());
+    expected.sort_by(|a, b| b.cmp(a));
+    (sizes != expected).then_some((sizes, expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn part(groups: &[&[&str]]) -> Exported {
+        let groups = groups
+            .iter()
+            .map(|g| Group::from_members(g.iter().map(|m| m.to_string()).collect()))
+            .collect();
+        (groups, HashMap::new(), Metadata::default())
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = part(&[&["S001", "S002", "S003"], &["S004", "S005"]]);
+        first.1.insert("S001".to_string(), "山田".to_string());
+        first.2.leaders = vec!["S002".to_string()];
+        let second = part(&[&["T001", "T002"], &["T003", "T004"]]);
+
+        let (groups, names, metadata) = merge(vec![first.clone(), second]).unwrap();
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups[2].members, vec!["T001", "T002"]);
+        assert_eq!(names.get("S001").map(String::as_str), Some("山田"));
+        assert_eq!(metadata.leaders, vec!["S002"]);
+
+        assert!(merge(vec![first, part(&[&["S005", "T009"]])]).is_err());
+    }
+
+    #[test]
+    fn test_sizes() {
+        // 4 + 5 students split as 2+2 and 3+2: fine on their own, but 9 would be 3+3+3
+        let (groups, _, _) = merge(vec![
+            part(&[&["S001", "S002"], &["S003", "S004"]]),
+            part(&[&["T001", "T002", "T003"], &["T004", "T005"]]),
+        ])
+        .unwrap();
+        assert!(unusual_groups(&groups, Sizing::Triples).is_empty());
+        assert_eq!(size_difference(&groups, Sizing::Triples), Some((vec![3, 2, 2, 2], vec![3, 3, 3])));
+        assert_eq!(size_difference(&groups, Sizing::Groups(4)), None);
+
+        let (groups, _, _) = part(&[&["S001"], &["S002", "S003", "S004", "S005"], &["S006", "S007"]]);
+        assert_eq!(unusual_groups(&groups, Sizing::Triples), vec![0, 1]);
+    }
+}
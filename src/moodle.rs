@@ -0,0 +1,161 @@
+//! Rosters exported from Moodle (`--from moodle`).
+//!
+//! The participants page, the bulk user download and the grade export all
+//! write the student number to the "ID number" column, with the name split
+//! into first and last name. The column names follow the language Moodle is
+//! used in ("IDナンバー", "姓", "名" in Japanese), and the plain-text grade
+//! export lets the teacher pick the separator and the text encoding, so
+//! these are worked out from the file instead of being given with options.
+
+use crate::i18n::{message, Msg};
+use crate::roster::{self, RosterEntry};
+use std::collections::BTreeMap;
+
+/// Header names of the ID number column, written as compared by [`normalize`].
+const ID_HEADERS: &[&str] = &["idnumber", "idナンバー", "id番号"];
+const FULL_NAME_HEADERS: &[&str] = &["fullname", "氏名", "フルネーム"];
+const FIRST_NAME_HEADERS: &[&str] = &["firstname", "名"];
+const LAST_NAME_HEADERS: &[&str] = &["lastname", "姓"];
+
+/// Lower-case without spaces or underscores, so that "ID number", "idnumber"
+/// and "ID ナンバー" compare equal.
+fn normalize(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The text of the file: UTF-8 with or without a BOM, or UTF-16 with a BOM
+/// (what Excel writes as "Unicode text").
+fn decode(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]])).collect();
+        String::from_utf16(&units).map_err(|_| message(Msg::MoodleUtf16, &[]))
+    };
+    if let Some(rest) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        return utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        return utf16(rest, u16::from_be_bytes);
+    }
+    let bytes = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+    String::from_utf8(bytes.to_vec()).map_err(|_| message(Msg::MoodleNotUtf8, &[]))
+}
+
+/// The separator used in the header line: whichever of comma, tab, semicolon
+/// and colon appears most.
+fn delimiter(header: &str) -> char {
+    [',', '\t', ';', ':']
+        .into_iter()
+        .max_by_key(|&c| (header.matches(c).count(), c == ','))
+        .unwrap_or(',')
+}
+
+/// Read a Moodle export. The ID number becomes the student ID, the first and
+/// last name the name, and the other columns attributes (e.g. "Groups" for
+/// `--stratify`). Students without an ID number are skipped.
+pub fn read_roster(bytes: &[u8]) -> Result<Vec<RosterEntry>, String> {
+    let text = decode(bytes)?;
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    let Some(header_line) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let delimiter = delimiter(header_line);
+    let header: Vec<String> = roster::parse_delimited_line(header_line, delimiter)
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    let find = |names: &[&str]| header.iter().position(|h| names.contains(&normalize(h).as_str()));
+
+    let id_index = find(ID_HEADERS).ok_or_else(|| message(Msg::MoodleNoIdColumn, &[]))?;
+    let full_name = find(FULL_NAME_HEADERS);
+    let first_name = find(FIRST_NAME_HEADERS);
+    let last_name = find(LAST_NAME_HEADERS);
+    // Japanese Moodle writes the family name first
+    let family_first = last_name.is_some_and(|i| header[i] == "姓");
+    let name_columns: Vec<usize> = [id_index]
+        .into_iter()
+        .chain(full_name)
+        .chain(first_name)
+        .chain(last_name)
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut without_id = 0;
+    for line in lines {
+        let fields = roster::parse_delimited_line(line, delimiter);
+        if fields.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let field = |i: Option<usize>| {
+            i.and_then(|i| fields.get(i))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let Some(id) = field(Some(id_index)) else {
+            without_id += 1;
+            continue;
+        };
+        let name = match (field(full_name), field(first_name), field(last_name)) {
+            (Some(full), _, _) => Some(full.to_string()),
+            (None, Some(first), Some(last)) if family_first => Some(format!("{} {}", last, first)),
+            (None, Some(first), Some(last)) => Some(format!("{} {}", first, last)),
+            (None, first, last) => first.or(last).map(String::from),
+        };
+        let attributes: BTreeMap<String, String> = fields
+            .iter()
+            .enumerate()
+            .filter(|(i, value)| !name_columns.contains(i) && !value.trim().is_empty())
+            .filter_map(|(i, value)| Some((header.get(i)?.clone(), value.trim().to_string())))
+            .collect();
+        entries.push(RosterEntry {
+            id: id.to_string(),
+            name,
+            attributes,
+        });
+    }
+    if entries.is_empty() && without_id > 0 {
+        return Err(message(Msg::MoodleNoIds, &[&without_id]));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_participants_export() {
+        let csv = "\u{feff}First name,Last name,ID number,Email address,Groups\r\n\
+                   Taro,Yamada,S001,taro@example.com,\"Lab A, Lab B\"\r\n\
+                   Guest,User,,guest@example.com,\r\n\
+                   Hanako,Suzuki,S002,hanako@example.com,Lab C\r\n";
+        let entries = read_roster(csv.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, "S001");
+        assert_eq!(entries[0].name.as_deref(), Some("Taro Yamada"));
+        assert_eq!(entries[0].attributes.get("Groups").map(String::as_str), Some("Lab A, Lab B"));
+        assert!(!entries[0].attributes.contains_key("First name"));
+    }
+
+    #[test]
+    fn test_japanese_grade_export_in_utf16_with_tabs() {
+        let text = "姓\t名\tIDナンバー\t所属組織\n山田\t太郎\tS001\t工学部\n";
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        let entries = read_roster(&bytes).unwrap();
+        assert_eq!(entries[0].id, "S001");
+        assert_eq!(entries[0].name.as_deref(), Some("山田 太郎"));
+        assert_eq!(entries[0].attributes.get("所属組織").map(String::as_str), Some("工学部"));
+    }
+
+    #[test]
+    fn test_unusable_exports() {
+        assert!(read_roster(b"First name,Last name,Email address\nTaro,Yamada,a@example.com\n").is_err());
+        assert!(read_roster(b"idnumber;firstname\n;Taro\n").is_err());
+        // "姓" in Shift_JIS
+        assert!(read_roster(&[0x90, 0xa9, b'\n']).is_err());
+    }
+}
@@ -0,0 +1,1246 @@
+//! Rendering the final grouping in the supported output formats.
+
+use crate::group::{natural_cmp, Group, GroupNames, Student, StudentId};
+use crate::digest;
+use crate::grouping::Mode;
+use crate::i18n::{message, Lang, Msg};
+use crate::json::Value;
+use crate::style::Style;
+use crate::template::Template;
+use crate::yaml;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Output format of the final grouping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text (the default)
+    #[default]
+    Text,
+    /// One row per student with `student_id,group` columns
+    Csv,
+    /// A JSON document with group labels, members, and run metadata
+    Json,
+    /// A Markdown table (Group | Members)
+    Markdown,
+    /// One JSON object per group and line, written as soon as the group is formed
+    JsonLines,
+    /// The JSON document as YAML, for tools such as Ansible
+    Yaml,
+}
+
+/// Information about the run that produced a grouping.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// When the grouping was made (RFC 3339, UTC)
+    pub timestamp: String,
+    /// Seed used for the random shuffle, if any shuffling took place
+    pub seed: Option<u64>,
+    /// Labels of the groups (`--group-names`)
+    pub group_names: GroupNames,
+    /// Group leaders chosen with `--assign-leader`, if any
+    pub leaders: Vec<StudentId>,
+    /// Language of headings and labels (`--lang`)
+    pub lang: Lang,
+    /// Whether the groups were made in one go or confirmed one by one, when
+    /// they were made by this run rather than read back or merged
+    pub mode: Option<Mode>,
+    /// Settings the groups were made with, by configuration file key
+    /// (see [`GroupingConfig::settings`](crate::grouping::GroupingConfig::settings))
+    pub config: Vec<(String, Value)>,
+}
+
+/// A finished grouping with the provenance every export carries: the seed,
+/// the timestamp, the mode and the settings, so that it can be made again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupingResult {
+    pub groups: Vec<Group>,
+    pub metadata: Metadata,
+}
+
+impl GroupingResult {
+    pub fn new(groups: Vec<Group>, metadata: Metadata) -> Self {
+        GroupingResult { groups, metadata }
+    }
+}
+
+/// How members are shown in listings: the ID, the name when known, and a
+/// mark for the group leader. Built once per rendering, so that looking up
+/// the leaders stays fast for large classes.
+pub struct MemberLabels<'a> {
+    names: &'a HashMap<StudentId, String>,
+    leaders: HashSet<&'a str>,
+    lang: Lang,
+}
+
+impl<'a> MemberLabels<'a> {
+    pub fn new(names: &'a HashMap<StudentId, String>, metadata: &'a Metadata) -> Self {
+        MemberLabels {
+            names,
+            leaders: metadata.leaders.iter().map(String::as_str).collect(),
+            lang: metadata.lang,
+        }
+    }
+
+    /// Whether `id` is the leader of their group.
+    pub fn is_leader(&self, id: &str) -> bool {
+        self.leaders.contains(id)
+    }
+
+    /// The name of `member`: their own, or else the one given in `names`.
+    pub fn name<'b>(&'b self, member: &'b Student) -> Option<&'b str> {
+        member.name.as_deref().or_else(|| self.names.get(&member.id).map(String::as_str))
+    }
+
+    /// The label of `member`.
+    pub fn label(&self, member: &Student) -> String {
+        let mut label = match self.name(member) {
+            Some(name) => format!("{} {}", member.id, name),
+            None => member.id.clone(),
+        };
+        if self.is_leader(&member.id) {
+            label.push(' ');
+            label.push_str(self.lang.text(Msg::Leader));
+        }
+        label
+    }
+}
+
+/// Hex digits of the SHA-256 kept in [`content_hash`].
+pub const CONTENT_HASH_LEN: usize = 12;
+
+/// A short hash of who is grouped with whom, so that two people can check
+/// they are looking at the same result: the SHA-256 of one line per group
+/// with its sorted IDs joined by commas, the lines sorted, cut to
+/// [`CONTENT_HASH_LEN`] hex digits. Labels, names, leaders and the order of
+/// groups and members do not change it.
+pub fn content_hash<'a>(groups: impl IntoIterator<Item = &'a Group>) -> String {
+    let mut lines: Vec<String> = groups
+        .into_iter()
+        .map(|group| {
+            let mut ids: Vec<&str> = group.ids().map(String::as_str).collect();
+            ids.sort_unstable();
+            ids.join(",")
+        })
+        .collect();
+    lines.sort_unstable();
+    let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    let mut hash = digest::to_hex(&digest::sha256(text.as_bytes()));
+    hash.truncate(CONTENT_HASH_LEN);
+    hash
+}
+
+impl OutputFormat {
+    /// File extension for results written in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Markdown => "md",
+            OutputFormat::JsonLines => "jsonl",
+            OutputFormat::Yaml => "yaml",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "jsonl" | "ndjson" => Ok(OutputFormat::JsonLines),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            _ => {
+                let formats = Lang::current().list(&["text", "csv", "json", "markdown", "jsonl", "yaml"]);
+                Err(message(Msg::OneOf, &[&"--format", &formats, &s]))
+            }
+        }
+    }
+}
+
+/// Render the result in the given format. `names` maps student IDs to display names.
+pub fn render(result: &GroupingResult, names: &HashMap<StudentId, String>, format: OutputFormat) -> String {
+    let (groups, metadata) = (&result.groups[..], &result.metadata);
+    match format {
+        OutputFormat::Text => render_text(groups, names, metadata),
+        OutputFormat::Csv => render_csv(groups, names, metadata),
+        OutputFormat::Json => render_json(groups, names, metadata),
+        OutputFormat::Markdown => render_markdown(groups, names, metadata),
+        OutputFormat::JsonLines => render_jsonl(groups, names, metadata),
+        OutputFormat::Yaml => yaml::to_string(&to_json(groups, names, metadata)),
+    }
+}
+
+/// Render the groups as the human-readable result listing.
+pub fn render_text(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    render_text_styled(groups, names, metadata, Style::default())
+}
+
+/// [`render_text`] with the title, group headers and total colored for a terminal.
+pub fn render_text_styled(
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+    style: Style,
+) -> String {
+    let lang = metadata.lang;
+    let mut out = String::new();
+    out.push_str(&format!("\n{}\n", style.title(&format!("=== {} ===", lang.text(Msg::ResultTitle)))));
+    push_text_groups(&mut out, groups, names, metadata, style);
+    out.push_str(&format!("\n{}\n", style.dim(&lang.format(Msg::TotalGroups, &[&groups.len()]))));
+    out.push_str(&format!("{}\n", style.dim(&lang.format(Msg::ContentHash, &[&content_hash(groups)]))));
+    out
+}
+
+/// Append the `グループ A: 3 人` listing of `groups` to `out`.
+fn push_text_groups(
+    out: &mut String,
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+    style: Style,
+) {
+    let labels = MemberLabels::new(names, metadata);
+    for (i, group) in groups.iter().enumerate() {
+        push_text_group(out, i, group, &labels, metadata, style);
+    }
+}
+
+/// Append the listing of the group at `index` to `out`.
+fn push_text_group(
+    out: &mut String,
+    index: usize,
+    group: &Group,
+    labels: &MemberLabels,
+    metadata: &Metadata,
+    style: Style,
+) {
+    out.push_str(&style.heading(&metadata.lang.format(
+        Msg::GroupLine,
+        &[&metadata.group_names.label(index), &group.members.len()],
+    )));
+    out.push('\n');
+    for member in &group.members {
+        out.push_str(&format!("  - {}\n", labels.label(member)));
+    }
+}
+
+/// Render one row per student. A `name` column is added when names are known,
+/// and every row ends with the [`content_hash`] of the whole grouping.
+pub fn render_csv(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let hash = content_hash(groups);
+    let mut out = String::new();
+    for (i, mut row) in to_rows(groups, names, metadata).into_iter().enumerate() {
+        row.push(if i == 0 { "hash".to_string() } else { hash.clone() });
+        push_csv_row(&mut out, &row);
+    }
+    out
+}
+
+pub(crate) fn push_csv_row(out: &mut String, row: &[String]) {
+    let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+    out.push_str(&fields.join(","));
+    out.push('\n');
+}
+
+/// The rows of the CSV output, starting with the header row. A `leader`
+/// column is added when leaders were chosen.
+pub fn to_rows(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<Vec<String>> {
+    let mut rows = vec![header_row(names, metadata)];
+    let labels = MemberLabels::new(names, metadata);
+    for (i, group) in groups.iter().enumerate() {
+        rows.extend(group_rows(i, group, &labels, metadata));
+    }
+    rows
+}
+
+fn header_row(names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<String> {
+    let mut header = vec!["student_id".to_string()];
+    if !names.is_empty() {
+        header.push("name".to_string());
+    }
+    header.push("group".to_string());
+    if !metadata.leaders.is_empty() {
+        header.push("leader".to_string());
+    }
+    header
+}
+
+/// One row per member of the group at `index`, with the columns of [`header_row`].
+fn group_rows(index: usize, group: &Group, labels: &MemberLabels, metadata: &Metadata) -> Vec<Vec<String>> {
+    let label = metadata.group_names.label(index);
+    group
+        .members
+        .iter()
+        .map(|member| {
+            let mut row = vec![member.id.clone()];
+            if !labels.names.is_empty() {
+                row.push(labels.name(member).unwrap_or_default().to_string());
+            }
+            row.push(label.clone());
+            if !metadata.leaders.is_empty() {
+                row.push(labels.is_leader(&member.id).to_string());
+            }
+            row
+        })
+        .collect()
+}
+
+/// Render the groups as a Markdown table with one row per group.
+pub fn render_markdown(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let mut out = String::from(MARKDOWN_HEADER);
+    let labels = MemberLabels::new(names, metadata);
+    for (i, group) in groups.iter().enumerate() {
+        push_markdown_row(&mut out, i, group, &labels, metadata);
+    }
+    out.push_str(&markdown_footer(&content_hash(groups), metadata.lang));
+    out
+}
+
+/// The line under the Markdown table with the [`content_hash`].
+fn markdown_footer(hash: &str, lang: Lang) -> String {
+    format!("\n{}\n", lang.format(Msg::ContentHash, &[&format!("`{}`", hash)]))
+}
+
+const MARKDOWN_HEADER: &str = "| Group | Members |\n|---|---|\n";
+
+/// Append the table row of the group at `index` to `out`.
+fn push_markdown_row(out: &mut String, index: usize, group: &Group, labels: &MemberLabels, metadata: &Metadata) {
+    let members: Vec<String> = group
+        .members
+        .iter()
+        .map(|member| markdown_cell(&labels.label(member)))
+        .collect();
+    out.push_str(&format!(
+        "| {} | {} |\n",
+        markdown_cell(&metadata.group_names.label(index)),
+        members.join(", ")
+    ));
+}
+
+/// Writes groups one at a time as they are formed (`--stream`), in the same
+/// layout as [`render`] gives for all of them at once. JSON and YAML cannot
+/// be written this way, since the number of groups comes before them; JSON
+/// Lines is the form made for it.
+pub struct GroupWriter<'a, W: Write> {
+    out: W,
+    format: OutputFormat,
+    labels: MemberLabels<'a>,
+    metadata: &'a Metadata,
+    written: usize,
+    /// The groups written so far, for the [`content_hash`] in the footer
+    groups: Vec<Group>,
+}
+
+impl<'a, W: Write> GroupWriter<'a, W> {
+    /// Start the output, writing its header to `out`.
+    pub fn new(
+        mut out: W,
+        format: OutputFormat,
+        names: &'a HashMap<StudentId, String>,
+        metadata: &'a Metadata,
+    ) -> io::Result<Self> {
+        let header = match format {
+            OutputFormat::Text => format!("\n=== {} ===\n", metadata.lang.text(Msg::ResultTitle)),
+            OutputFormat::Csv => {
+                let mut header = String::new();
+                push_csv_row(&mut header, &header_row(names, metadata));
+                header
+            }
+            OutputFormat::Markdown => MARKDOWN_HEADER.to_string(),
+            OutputFormat::JsonLines => String::new(),
+            OutputFormat::Json | OutputFormat::Yaml => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "JSON cannot be written group by group"))
+            }
+        };
+        out.write_all(header.as_bytes())?;
+        Ok(GroupWriter {
+            out,
+            format,
+            labels: MemberLabels::new(names, metadata),
+            metadata,
+            written: 0,
+            groups: Vec::new(),
+        })
+    }
+
+    /// Write the next group.
+    pub fn write(&mut self, group: &Group) -> io::Result<()> {
+        let mut text = String::new();
+        match self.format {
+            OutputFormat::Text => {
+                push_text_group(&mut text, self.written, group, &self.labels, self.metadata, Style::default())
+            }
+            OutputFormat::Csv => {
+                for row in group_rows(self.written, group, &self.labels, self.metadata) {
+                    push_csv_row(&mut text, &row);
+                }
+            }
+            OutputFormat::Markdown => push_markdown_row(&mut text, self.written, group, &self.labels, self.metadata),
+            // The hash of the whole grouping is not known until the end
+            OutputFormat::JsonLines => {
+                push_jsonl_line(&mut text, self.written, group, &self.labels, self.metadata, None)
+            }
+            OutputFormat::Json | OutputFormat::Yaml => unreachable!("rejected in GroupWriter::new"),
+        }
+        self.written += 1;
+        self.groups.push(group.clone());
+        self.out.write_all(text.as_bytes())
+    }
+
+    /// Write the footer and hand back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        let lang = self.metadata.lang;
+        let footer = match self.format {
+            OutputFormat::Text => format!(
+                "\n{}\n{}\n",
+                lang.format(Msg::TotalGroups, &[&self.written]),
+                lang.format(Msg::ContentHash, &[&content_hash(&self.groups)])
+            ),
+            OutputFormat::Markdown => markdown_footer(&content_hash(&self.groups), lang),
+            _ => String::new(),
+        };
+        self.out.write_all(footer.as_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Render a standalone HTML report with one card per group.
+pub fn render_html(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let lang = metadata.lang;
+    let title = lang.text(Msg::ResultTitle);
+    let total_students: usize = groups.iter().map(|g| g.members.len()).sum();
+    let mut out = String::new();
+    out.push_str(&format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n",
+        lang.code(),
+        title
+    ));
+    out.push_str(
+        r#"<style>
+  body { font-family: sans-serif; margin: 2rem; background: #f5f5f5; color: #222; }
+  h1 { margin-bottom: 0.25rem; }
+  .summary { color: #555; margin-bottom: 1.5rem; }
+  .groups { display: grid; grid-template-columns: repeat(auto-fill, minmax(14rem, 1fr)); gap: 1rem; }
+  .card { background: #fff; border-radius: 0.5rem; box-shadow: 0 1px 3px rgba(0, 0, 0, 0.2); padding: 1rem 1.25rem; }
+  .card h2 { margin: 0 0 0.5rem; font-size: 1.5rem; }
+  .card .count { color: #777; font-size: 0.9rem; }
+  .card ul { margin: 0; padding-left: 1.25rem; font-size: 1.2rem; line-height: 1.6; }
+</style>
+</head>
+<body>
+"#,
+    );
+    out.push_str(&format!("<h1>{}</h1>\n", title));
+    let seed = metadata.seed.map(|seed| format!(", {}", lang.format(Msg::SeedFooter, &[&seed])));
+    out.push_str(&format!(
+        "<p class=\"summary\">{} ({}{}, {})</p>\n",
+        lang.format(Msg::Summary, &[&groups.len(), &total_students]),
+        html_escape(&metadata.timestamp),
+        seed.unwrap_or_default(),
+        lang.format(Msg::ContentHash, &[&content_hash(groups)])
+    ));
+    out.push_str("<div class=\"groups\">\n");
+    let labels = MemberLabels::new(names, metadata);
+    for (i, group) in groups.iter().enumerate() {
+        out.push_str("<section class=\"card\">\n");
+        out.push_str(&format!(
+            "<h2>{} <span class=\"count\">{}</span></h2>\n<ul>\n",
+            lang.format(Msg::GroupHeading, &[&html_escape(&metadata.group_names.label(i))]),
+            lang.format(Msg::Members, &[&group.members.len()])
+        ));
+        for member in &group.members {
+            out.push_str(&format!("<li>{}</li>\n", html_escape(&labels.label(member))));
+        }
+        out.push_str("</ul>\n</section>\n");
+    }
+    out.push_str("</div>\n</body>\n</html>\n");
+    out
+}
+
+/// The fields of the group at `index` in the JSON output.
+fn group_pairs(index: usize, group: &Group, labels: &MemberLabels, metadata: &Metadata) -> Vec<(&'static str, Value)> {
+    let mut pairs = vec![
+        ("label", Value::from(metadata.group_names.label(index))),
+        ("size", Value::from(group.members.len())),
+        (
+            "members",
+            Value::Array(group.ids().map(|m| Value::from(m.as_str())).collect()),
+        ),
+    ];
+    if let Some(leader) = group.ids().find(|m| labels.is_leader(m)) {
+        pairs.push(("leader", Value::from(leader.as_str())));
+    }
+    pairs
+}
+
+/// Build the JSON document describing the grouping.
+pub fn to_json(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Value {
+    let labels = MemberLabels::new(names, metadata);
+    let group_values = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| Value::object(group_pairs(i, group, &labels, metadata)))
+        .collect();
+
+    let mut pairs = vec![
+        ("timestamp", Value::from(metadata.timestamp.as_str())),
+        ("seed", Value::from(metadata.seed)),
+        ("hash", Value::from(content_hash(groups).as_str())),
+        (
+            "total_students",
+            Value::from(groups.iter().map(|g| g.members.len()).sum::<usize>()),
+        ),
+        ("total_groups", Value::from(groups.len())),
+        ("groups", Value::Array(group_values)),
+    ];
+    pairs.splice(2..2, provenance_pairs(metadata));
+    if let Some(names) = names_json(names) {
+        pairs.push(("names", names));
+    }
+    Value::object(pairs)
+}
+
+/// The `mode` and `config` fields that follow the seed, for groupings made by this run.
+fn provenance_pairs(metadata: &Metadata) -> Vec<(&'static str, Value)> {
+    match metadata.mode {
+        Some(mode) => vec![
+            ("mode", Value::from(mode.name())),
+            ("config", Value::Object(metadata.config.clone())),
+        ],
+        None => Vec::new(),
+    }
+}
+
+/// A grouping read back by [`from_json`]: the groups, the names and the metadata.
+pub type Exported = (Vec<Group>, HashMap<StudentId, String>, Metadata);
+
+/// Read back a grouping written by [`to_json`]: the groups, the names, and
+/// the timestamp, seed, mode, settings, leaders and group labels.
+pub fn from_json(value: &Value) -> Result<Exported, String> {
+    let groups = value
+        .get("groups")
+        .and_then(Value::as_array)
+        .ok_or_else(|| message(Msg::ResultNoGroups, &[]))?;
+    let mut leaders = Vec::new();
+    let labels: Option<Vec<String>> = groups
+        .iter()
+        .map(|group| group.get("label").and_then(Value::as_str).map(String::from))
+        .collect();
+    let mut groups = groups
+        .iter()
+        .map(|group| {
+            let members = group
+                .get("members")
+                .and_then(Value::as_array)
+                .ok_or_else(|| message(Msg::ResultNoMembers, &[]))?
+                .iter()
+                .map(|m| m.as_str().map(String::from).ok_or_else(|| message(Msg::ResultMembers, &[])))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(leader) = group.get("leader").and_then(Value::as_str) {
+                leaders.push(leader.to_string());
+            }
+            Ok(Group::from_members(members))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let names = match value.get("names") {
+        Some(Value::Object(pairs)) => pairs
+            .iter()
+            .filter_map(|(id, name)| name.as_str().map(|name| (id.clone(), name.to_string())))
+            .collect(),
+        _ => HashMap::new(),
+    };
+    for member in groups.iter_mut().flat_map(|g| g.members.iter_mut()) {
+        member.fill_name(&names);
+    }
+    let metadata = Metadata {
+        timestamp: value.get("timestamp").and_then(Value::as_str).unwrap_or_default().to_string(),
+        seed: value.get("seed").and_then(Value::as_u64),
+        mode: value.get("mode").and_then(Value::as_str).and_then(Mode::from_name),
+        config: match value.get("config") {
+            Some(Value::Object(pairs)) => pairs.clone(),
+            _ => Vec::new(),
+        },
+        leaders,
+        group_names: labels.map(GroupNames::from_labels).unwrap_or_default(),
+        ..Default::default()
+    };
+    Ok((groups, names, metadata))
+}
+
+/// The `names` map of the JSON output, sorted by ID, or `None` when no names are known.
+fn names_json(names: &HashMap<StudentId, String>) -> Option<Value> {
+    if names.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<_> = names.iter().collect();
+    sorted.sort();
+    Some(Value::Object(
+        sorted
+            .into_iter()
+            .map(|(id, name)| (id.clone(), Value::from(name.as_str())))
+            .collect(),
+    ))
+}
+
+/// Render the groups as a pretty-printed JSON document.
+pub fn render_json(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let mut out = to_json(groups, names, metadata).to_pretty_string();
+    out.push('\n');
+    out
+}
+
+/// Render the groups as JSON Lines: one object per group, with the fields of
+/// the groups of [`to_json`], the names of the members when known and the
+/// [`content_hash`] of the whole grouping.
+pub fn render_jsonl(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let labels = MemberLabels::new(names, metadata);
+    let hash = content_hash(groups);
+    let mut out = String::new();
+    for (i, group) in groups.iter().enumerate() {
+        push_jsonl_line(&mut out, i, group, &labels, metadata, Some(&hash));
+    }
+    out
+}
+
+/// Append the JSON Lines object of the group at `index` to `out`.
+fn push_jsonl_line(
+    out: &mut String,
+    index: usize,
+    group: &Group,
+    labels: &MemberLabels,
+    metadata: &Metadata,
+    hash: Option<&str>,
+) {
+    out.push_str(&jsonl_value(index, group, labels, metadata, Vec::new(), hash).to_string());
+    out.push('\n');
+}
+
+/// The JSON Lines object of a group, after the fields already in `pairs` and
+/// ending with `hash` when it is known.
+fn jsonl_value(
+    index: usize,
+    group: &Group,
+    labels: &MemberLabels,
+    metadata: &Metadata,
+    mut pairs: Vec<(&'static str, Value)>,
+    hash: Option<&str>,
+) -> Value {
+    pairs.extend(group_pairs(index, group, labels, metadata));
+    let names: Vec<(String, Value)> = group
+        .members
+        .iter()
+        .filter_map(|m| labels.name(m).map(|name| (m.id.clone(), Value::from(name))))
+        .collect();
+    if !names.is_empty() {
+        pairs.push(("names", Value::Object(names)));
+    }
+    if let Some(hash) = hash {
+        pairs.push(("hash", Value::from(hash)));
+    }
+    Value::object(pairs)
+}
+
+/// The values available to `--template`: the fields of [`to_json`], plus the
+/// group number and, for each member, the name and the label of the text listing.
+pub fn template_data(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Value {
+    let labels = MemberLabels::new(names, metadata);
+    let group_values = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let members = group
+                .members
+                .iter()
+                .map(|m| {
+                    Value::object(vec![
+                        ("id", Value::from(m.id.as_str())),
+                        ("name", Value::from(labels.name(m))),
+                        ("label", Value::from(labels.label(m))),
+                        ("leader", Value::Bool(labels.is_leader(&m.id))),
+                    ])
+                })
+                .collect();
+            Value::object(vec![
+                ("label", Value::from(metadata.group_names.label(i))),
+                ("number", Value::from(i + 1)),
+                ("size", Value::from(group.members.len())),
+                ("leader", Value::from(group.ids().find(|m| labels.is_leader(m)).map(String::as_str))),
+                ("members", Value::Array(members)),
+            ])
+        })
+        .collect();
+    let mut pairs = vec![
+        ("timestamp", Value::from(metadata.timestamp.as_str())),
+        ("seed", Value::from(metadata.seed)),
+        ("hash", Value::from(content_hash(groups).as_str())),
+        (
+            "total_students",
+            Value::from(groups.iter().map(|g| g.members.len()).sum::<usize>()),
+        ),
+        ("total_groups", Value::from(groups.len())),
+        ("groups", Value::Array(group_values)),
+    ];
+    pairs.splice(2..2, provenance_pairs(metadata));
+    Value::object(pairs)
+}
+
+/// Render the result with a `--template` template.
+pub fn render_template(template: &Template, result: &GroupingResult, names: &HashMap<StudentId, String>) -> String {
+    template.render(&template_data(&result.groups, names, &result.metadata))
+}
+
+/// Render a multi-session schedule (one grouping per session) in the given format.
+/// Where one student is in each session of a plan: the index of their
+/// group and the others in it, or `None` for a session without them.
+type Placements<'a> = Vec<Option<(usize, Vec<&'a Student>)>>;
+
+/// Every student of `sessions` in ID order, with their [`Placements`].
+fn placements(sessions: &[Vec<Group>]) -> Vec<(&Student, Placements<'_>)> {
+    let mut students: Vec<&Student> = Vec::new();
+    let mut seen = HashSet::new();
+    for member in sessions.iter().flatten().flat_map(|g| &g.members) {
+        if seen.insert(member.id.as_str()) {
+            students.push(member);
+        }
+    }
+    students.sort_by(|a, b| natural_cmp(&a.id, &b.id));
+    students
+        .into_iter()
+        .map(|student| {
+            let placed = sessions
+                .iter()
+                .map(|groups| {
+                    let index = groups.iter().position(|g| g.contains(&student.id))?;
+                    let partners = groups[index].members.iter().filter(|m| m.id != student.id).collect();
+                    Some((index, partners))
+                })
+                .collect();
+            (student, placed)
+        })
+        .collect()
+}
+
+/// Render a plan student by student (`--per-student`): for every student,
+/// their group and partners in each session, so that each can be handed
+/// their own part.
+pub fn render_per_student(
+    sessions: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> String {
+    let lang = metadata.lang;
+    let labels = MemberLabels::new(names, metadata);
+    let students = placements(sessions);
+    let partner_labels = |partners: &[&Student]| partners.iter().map(|m| labels.label(m)).collect::<Vec<_>>();
+    let student_value = |student: &Student, placed: &Placements| {
+        let sessions = placed
+            .iter()
+            .enumerate()
+            .filter_map(|(n, place)| {
+                let (index, partners) = place.as_ref()?;
+                Some(Value::object(vec![
+                    ("session", Value::from(n + 1)),
+                    ("group", Value::from(metadata.group_names.label(*index).as_str())),
+                    ("partners", Value::Array(partners.iter().map(|m| Value::from(m.id.as_str())).collect())),
+                ]))
+            })
+            .collect();
+        let mut pairs = vec![("id", Value::from(student.id.as_str()))];
+        if let Some(name) = labels.name(student) {
+            pairs.push(("name", Value::from(name)));
+        }
+        pairs.push(("sessions", Value::Array(sessions)));
+        Value::object(pairs)
+    };
+
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            out.push_str(&format!("\n=== {} ===\n", lang.format(Msg::PerStudentTitle, &[&sessions.len()])));
+            for (student, placed) in &students {
+                out.push_str(&format!("\n--- {} ---\n", labels.label(student)));
+                for (n, place) in placed.iter().enumerate() {
+                    let Some((index, partners)) = place else { continue };
+                    let group = lang.format(Msg::GroupHeading, &[&metadata.group_names.label(*index)]);
+                    let session = lang.format(Msg::SessionHeading, &[&(n + 1)]);
+                    match partners.is_empty() {
+                        true => out.push_str(&format!("{}: {}\n", session, group)),
+                        false => out.push_str(&format!("{}: {} ({})\n", session, group, partner_labels(partners).join(", "))),
+                    }
+                }
+            }
+        }
+        OutputFormat::Csv => {
+            let mut header = vec!["student_id".to_string()];
+            if !names.is_empty() {
+                header.push("name".to_string());
+            }
+            header.extend(["session", "group", "partners"].map(String::from));
+            push_csv_row(&mut out, &header);
+            for (student, placed) in &students {
+                for (n, place) in placed.iter().enumerate() {
+                    let Some((index, partners)) = place else { continue };
+                    let mut row = vec![student.id.clone()];
+                    if !names.is_empty() {
+                        row.push(labels.name(student).unwrap_or_default().to_string());
+                    }
+                    row.push((n + 1).to_string());
+                    row.push(metadata.group_names.label(*index));
+                    row.push(partners.iter().map(|m| m.id.as_str()).collect::<Vec<_>>().join(" "));
+                    push_csv_row(&mut out, &row);
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let mut pairs = vec![
+                ("timestamp", Value::from(metadata.timestamp.as_str())),
+                ("seed", Value::from(metadata.seed)),
+                ("total_sessions", Value::from(sessions.len())),
+                (
+                    "students",
+                    Value::Array(students.iter().map(|(student, placed)| student_value(student, placed)).collect()),
+                ),
+            ];
+            pairs.splice(2..2, provenance_pairs(metadata));
+            out = match format {
+                OutputFormat::Yaml => yaml::to_string(&Value::object(pairs)),
+                _ => Value::object(pairs).to_pretty_string() + "\n",
+            };
+        }
+        OutputFormat::JsonLines => {
+            for (student, placed) in &students {
+                out.push_str(&student_value(student, placed).to_string());
+                out.push('\n');
+            }
+        }
+        OutputFormat::Markdown => {
+            for (i, (student, placed)) in students.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                out.push_str(&format!("## {}\n\n", markdown_cell(&labels.label(student))));
+                out.push_str("| Session | Group | Partners |\n|---|---|---|\n");
+                for (n, place) in placed.iter().enumerate() {
+                    let Some((index, partners)) = place else { continue };
+                    let partners = partner_labels(partners).join(", ");
+                    out.push_str(&format!(
+                        "| {} | {} | {} |\n",
+                        n + 1,
+                        markdown_cell(&metadata.group_names.label(*index)),
+                        markdown_cell(&partners)
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+pub fn render_schedule(
+    sessions: &[Vec<Group>],
+    names: &HashMap<StudentId, String>,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> String {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            let lang = metadata.lang;
+            out.push_str(&format!("\n=== {} ===\n", lang.format(Msg::ScheduleTitle, &[&sessions.len()])));
+            for (n, groups) in sessions.iter().enumerate() {
+                out.push_str(&format!("\n--- {} ---\n", lang.format(Msg::SessionHeading, &[&(n + 1)])));
+                push_text_groups(&mut out, groups, names, metadata, Style::default());
+            }
+        }
+        OutputFormat::Csv => {
+            // Same columns as the single-run CSV, with the session number in front
+            for (n, groups) in sessions.iter().enumerate() {
+                let mut lines = render_csv(groups, names, metadata).lines().map(String::from).collect::<Vec<_>>();
+                let header = lines.remove(0);
+                if n == 0 {
+                    out.push_str(&format!("session,{}\n", header));
+                }
+                for line in lines {
+                    out.push_str(&format!("{},{}\n", n + 1, line));
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let session_values = sessions
+                .iter()
+                .enumerate()
+                .map(|(n, groups)| {
+                    let groups_json = to_json(groups, &HashMap::new(), metadata);
+                    Value::object(vec![
+                        ("session", Value::from(n + 1)),
+                        ("hash", Value::from(content_hash(groups).as_str())),
+                        ("groups", groups_json.get("groups").cloned().unwrap_or(Value::Null)),
+                    ])
+                })
+                .collect();
+            let mut pairs = vec![
+                ("timestamp", Value::from(metadata.timestamp.as_str())),
+                ("seed", Value::from(metadata.seed)),
+                ("total_sessions", Value::from(sessions.len())),
+                ("sessions", Value::Array(session_values)),
+            ];
+            pairs.splice(2..2, provenance_pairs(metadata));
+            if let Some(names) = names_json(names) {
+                pairs.push(("names", names));
+            }
+            out = match format {
+                OutputFormat::Yaml => yaml::to_string(&Value::object(pairs)),
+                _ => Value::object(pairs).to_pretty_string() + "\n",
+            };
+        }
+        OutputFormat::JsonLines => {
+            // One line per group, with the session in front
+            let labels = MemberLabels::new(names, metadata);
+            for (n, groups) in sessions.iter().enumerate() {
+                let hash = content_hash(groups);
+                for (i, group) in groups.iter().enumerate() {
+                    let session = vec![("session", Value::from(n + 1))];
+                    out.push_str(&jsonl_value(i, group, &labels, metadata, session, Some(&hash)).to_string());
+                    out.push('\n');
+                }
+            }
+        }
+        OutputFormat::Markdown => {
+            for (n, groups) in sessions.iter().enumerate() {
+                if n > 0 {
+                    out.push('\n');
+                }
+                out.push_str(&format!("## {}\n\n", metadata.lang.format(Msg::SessionHeading, &[&(n + 1)])));
+
+                out.push_str(&render_markdown(groups, names, metadata));
+            }
+        }
+    }
+    out
+}
+
+/// Escape text for use in HTML element content.
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape characters that would break a Markdown table cell.
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Quote a CSV field if it contains a comma, quote, or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_groups() -> Vec<Group> {
+        vec![
+            Group::from_members(vec!["S001".to_string(), "S002".to_string(), "S003".to_string()]),
+            Group::from_members(vec!["S004".to_string(), "S005".to_string()]),
+        ]
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let csv = render_csv(&sample_groups(), &HashMap::new(), &Metadata::default());
+        assert_eq!(
+            csv,
+            "student_id,group,hash\nS001,A,16be628861ec\nS002,A,16be628861ec\nS003,A,16be628861ec\nS004,B,16be628861ec\nS005,B,16be628861ec\n"
+        );
+    }
+
+    #[test]
+    fn test_render_csv_with_names_escapes_fields() {
+        let mut names = HashMap::new();
+        names.insert("S001".to_string(), "田中, 一郎".to_string());
+        let csv = render_csv(&sample_groups()[..1], &names, &Metadata::default());
+        assert_eq!(
+            csv,
+            "student_id,name,group,hash\nS001,\"田中, 一郎\",A,4f4467735dca\nS002,,A,4f4467735dca\nS003,,A,4f4467735dca\n"
+        );
+    }
+
+    #[test]
+    fn test_group_writer_matches_render() {
+        let mut names = HashMap::new();
+        names.insert("S002".to_string(), "山田".to_string());
+        let metadata = Metadata {
+            leaders: vec!["S001".to_string(), "S005".to_string()],
+            ..Default::default()
+        };
+        for format in [OutputFormat::Text, OutputFormat::Csv, OutputFormat::Markdown, OutputFormat::JsonLines] {
+            let mut writer = GroupWriter::new(Vec::new(), format, &names, &metadata).unwrap();
+            for group in sample_groups() {
+                writer.write(&group).unwrap();
+            }
+            let streamed = String::from_utf8(writer.finish().unwrap()).unwrap();
+            let result = GroupingResult::new(sample_groups(), metadata.clone());
+            // Rows and lines are written before the hash of the whole grouping is known
+            let rendered = render(&result, &names, format)
+                .replace(",hash", "")
+                .replace(",16be628861ec", "")
+                .replace(r#","hash":"16be628861ec""#, "");
+            assert_eq!(streamed, rendered, "{:?}", format);
+        }
+        assert!(GroupWriter::new(Vec::new(), OutputFormat::Json, &names, &metadata).is_err());
+        assert!(GroupWriter::new(Vec::new(), OutputFormat::Yaml, &names, &metadata).is_err());
+    }
+
+    #[test]
+    fn test_render_json() {
+        let metadata = Metadata {
+            timestamp: "2025-04-01T09:30:00Z".to_string(),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let json = to_json(&sample_groups(), &HashMap::new(), &metadata);
+        assert_eq!(
+            json.to_string(),
+            concat!(
+                r#"{"timestamp":"2025-04-01T09:30:00Z","seed":42,"hash":"16be628861ec","total_students":5,"total_groups":2,"#,
+                r#""groups":[{"label":"A","size":3,"members":["S001","S002","S003"]},"#,
+                r#"{"label":"B","size":2,"members":["S004","S005"]}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_template() {
+        let mut names = HashMap::new();
+        names.insert("S002".to_string(), "山田".to_string());
+        let metadata = Metadata {
+            leaders: vec!["S004".to_string()],
+            ..Default::default()
+        };
+        let template = Template::parse(
+            "{{total_students}}人/{{total_groups}}班\n{{#each groups}}{{number}}. {{label}}({{size}}): \
+             {{#each members}}{{id}}{{#if name}}={{name}}{{/if}}{{#if leader}}*{{/if}} {{/each}}\n{{/each}}",
+        )
+        .unwrap();
+        assert_eq!(
+            render_template(&template, &GroupingResult::new(sample_groups(), metadata), &names),
+            "5人/2班\n1. A(3): S001 S002=山田 S003 \n2. B(2): S004* S005 \n"
+        );
+    }
+
+    #[test]
+    fn test_render_jsonl() {
+        let mut names = HashMap::new();
+        names.insert("S002".to_string(), "山田".to_string());
+        let metadata = Metadata {
+            leaders: vec!["S004".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            render_jsonl(&sample_groups(), &names, &metadata),
+            concat!(
+                r#"{"label":"A","size":3,"members":["S001","S002","S003"],"names":{"S002":"山田"},"hash":"16be628861ec"}"#,
+                "\n",
+                r#"{"label":"B","size":2,"members":["S004","S005"],"leader":"S004","hash":"16be628861ec"}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_json_reads_back_to_json() {
+        let mut names = HashMap::new();
+        names.insert("S002".to_string(), "山田".to_string());
+        let metadata = Metadata {
+            timestamp: "2025-04-01T09:30:00Z".to_string(),
+            seed: Some(42),
+            leaders: vec!["S004".to_string()],
+            group_names: GroupNames::parse("赤,青").unwrap(),
+            ..Default::default()
+        };
+        let json = Value::parse(&render_json(&sample_groups(), &names, &metadata)).unwrap();
+        let (groups, read_names, read) = from_json(&json).unwrap();
+        // The names come back on the members as well
+        let mut expected = sample_groups();
+        expected[0].members[1].name = Some("山田".to_string());
+        assert_eq!((groups, read_names), (expected, names));
+        assert_eq!((read.timestamp, read.seed, read.leaders), (metadata.timestamp, metadata.seed, metadata.leaders));
+        assert_eq!(read.group_names.label(1), "青");
+        assert_eq!((read.mode, read.config), (None, Vec::new()));
+        assert!(from_json(&Value::parse(r#"{"groups": [{"members": [1]}]}"#).unwrap()).is_err());
+        assert!(from_json(&Value::parse("[]").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_provenance_reads_back() {
+        let metadata = Metadata {
+            seed: Some(7),
+            mode: Some(Mode::Interactive),
+            config: vec![("groups".to_string(), Value::from(2usize)), ("balance".to_string(), Value::from("gender"))],
+            ..Default::default()
+        };
+        let json = to_json(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(json.to_string().contains(r#""seed":7,"mode":"interactive","config":{"groups":2,"balance":"gender"},"#));
+        let (_, _, read) = from_json(&json).unwrap();
+        let data = template_data(&sample_groups(), &HashMap::new(), &metadata);
+        assert_eq!(data.get("mode").and_then(Value::as_str), Some("interactive"));
+        assert_eq!((read.mode, read.config), (metadata.mode, metadata.config));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let mut names = HashMap::new();
+        names.insert("S004".to_string(), "山田|太郎".to_string());
+        let markdown = render_markdown(&sample_groups(), &names, &Metadata::default());
+        assert_eq!(
+            markdown,
+            "| Group | Members |\n|---|---|\n| A | S001, S002, S003 |\n| B | S004 山田\\|太郎, S005 |\n\n確認用ハッシュ: `16be628861ec`\n"
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let groups = sample_groups();
+        let hash = content_hash(&groups);
+        assert_eq!(hash.len(), CONTENT_HASH_LEN);
+        // The same grouping listed in another order, or with other labels, has the same hash
+        let reordered = vec![
+            Group::from_members(vec!["S005".to_string(), "S004".to_string()]),
+            Group::from_members(vec!["S003".to_string(), "S001".to_string(), "S002".to_string()]),
+        ];
+        assert_eq!(content_hash(&reordered), hash);
+        let moved = vec![
+            Group::from_members(vec!["S001".to_string(), "S002".to_string()]),
+            Group::from_members(vec!["S003".to_string(), "S004".to_string(), "S005".to_string()]),
+        ];
+        assert_ne!(content_hash(&moved), hash);
+        let json = to_json(&reordered, &HashMap::new(), &Metadata::default());
+        assert_eq!(json.get("hash").and_then(Value::as_str), Some(hash.as_str()));
+    }
+
+    #[test]
+    fn test_render_with_group_names() {
+        let metadata = Metadata {
+            group_names: GroupNames::parse("Red,Blue").unwrap(),
+            ..Default::default()
+        };
+        let csv = render_csv(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(csv.contains("S001,Red,"));
+        assert!(csv.contains("S005,Blue,"));
+        let text = render_text(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(text.contains("グループ Blue: 2 人"));
+    }
+
+    #[test]
+    fn test_render_marks_leaders() {
+        let metadata = Metadata {
+            leaders: vec!["S002".to_string(), "S004".to_string()],
+            ..Default::default()
+        };
+        let csv = render_csv(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(csv.starts_with("student_id,group,leader,hash\nS001,A,false,16be628861ec\nS002,A,true,16be628861ec\n"));
+        let text = render_text(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(text.contains("  - S004 (リーダー)\n"));
+        let json = to_json(&sample_groups(), &HashMap::new(), &metadata);
+        let groups = json.get("groups").and_then(Value::as_array).unwrap();
+        assert_eq!(groups[1].get("leader").and_then(Value::as_str), Some("S004"));
+    }
+
+    #[test]
+    fn test_render_in_english() {
+        let metadata = Metadata {
+            leaders: vec!["S001".to_string()],
+            lang: Lang::En,
+            ..Default::default()
+        };
+        let text = render_text(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(text.starts_with("\n=== Grouping result ===\nGroup A: 3 student(s)\n  - S001 (leader)\n"));
+        assert!(text.ends_with("\nTotal: 2 group(s)\nVerification hash: 16be628861ec\n"));
+        let html = render_html(&sample_groups(), &HashMap::new(), &metadata);
+        assert!(html.contains("<html lang=\"en\">"));
+        assert!(html.contains("<h2>Group B <span class=\"count\">2 student(s)</span></h2>"));
+    }
+
+    #[test]
+    fn test_render_html_has_one_card_per_group() {
+        let mut groups = sample_groups();
+        groups[1].members[0] = "<S004>".into();
+        let html = render_html(&groups, &HashMap::new(), &Metadata::default());
+
+        assert_eq!(html.matches("<section class=\"card\">").count(), 2);
+        assert!(html.contains("<li>S001</li>"));
+        assert!(html.contains("<li>&lt;S004&gt;</li>"));
+        assert!(html.contains("合計: 2 グループ / 5 人"));
+    }
+
+    #[test]
+    fn test_render_schedule_csv_and_json() {
+        let sessions = vec![sample_groups(), vec![Group::from_members(vec!["S001".to_string(), "S004".to_string()])]];
+        let csv = render_schedule(&sessions, &HashMap::new(), OutputFormat::Csv, &Metadata::default());
+        assert_eq!(
+            csv,
+            concat!(
+                "session,student_id,group,hash\n",
+                "1,S001,A,16be628861ec\n1,S002,A,16be628861ec\n1,S003,A,16be628861ec\n",
+                "1,S004,B,16be628861ec\n1,S005,B,16be628861ec\n",
+                "2,S001,A,74469f13b4e8\n2,S004,A,74469f13b4e8\n"
+            )
+        );
+
+        let json = render_schedule(&sessions, &HashMap::new(), OutputFormat::Json, &Metadata::default());
+        let parsed = Value::parse(&json).unwrap();
+        assert_eq!(parsed.get("total_sessions").and_then(Value::as_u64), Some(2));
+        let second = &parsed.get("sessions").and_then(Value::as_array).unwrap()[1];
+        assert_eq!(second.get("session").and_then(Value::as_u64), Some(2));
+    }
+
+    #[test]
+    fn test_render_per_student() {
+        let sessions = vec![sample_groups(), vec![Group::from_members(vec!["S001".to_string(), "S004".to_string()])]];
+        let csv = render_per_student(&sessions, &HashMap::new(), OutputFormat::Csv, &Metadata::default());
+        assert_eq!(
+            csv.lines().take(4).collect::<Vec<_>>(),
+            vec!["student_id,session,group,partners", "S001,1,A,S002 S003", "S001,2,A,S004", "S002,1,A,S001 S003"]
+        );
+
+        let text = render_per_student(&sessions, &HashMap::new(), OutputFormat::Text, &Metadata::default());
+        assert!(text.contains("--- S004 ---\n第 1 回: グループ B (S005)\n第 2 回: グループ A (S001)\n"));
+        // Students missing from a session have no line for it
+        assert!(text.ends_with("--- S005 ---\n第 1 回: グループ B (S004)\n"));
+
+        let json = render_per_student(&sessions, &HashMap::new(), OutputFormat::Json, &Metadata::default());
+        let parsed = Value::parse(&json).unwrap();
+        let students = parsed.get("students").and_then(Value::as_array).unwrap();
+        assert_eq!(students.len(), 5);
+        let second = &students[0].get("sessions").and_then(Value::as_array).unwrap()[1];
+        assert_eq!(second.get("partners"), Some(&Value::Array(vec![Value::from("S004")])));
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!("csv".parse::<OutputFormat>(), Ok(OutputFormat::Csv));
+        assert_eq!("TEXT".parse::<OutputFormat>(), Ok(OutputFormat::Text));
+        assert_eq!("json".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+        assert_eq!("markdown".parse::<OutputFormat>(), Ok(OutputFormat::Markdown));
+        assert_eq!("jsonl".parse::<OutputFormat>(), Ok(OutputFormat::JsonLines));
+        assert_eq!("yml".parse::<OutputFormat>(), Ok(OutputFormat::Yaml));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
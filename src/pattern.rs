@@ -0,0 +1,461 @@
+//! A small regular-expression matcher for validating student IDs.
+//!
+//! Supports the common subset: literals, `.`, `^`/`$`, character classes
+//! (`[A-Z]`, `[^0-9]`), `\d` `\w` `\s` (and their negations), groups with
+//! alternation (`(a|b)`), and the quantifiers `*`, `+`, `?`, `{n}`, `{n,}`,
+//! `{n,m}`. Patterns are compiled into a small program that is run over the
+//! text once, following every way it can match at the same time (a Pike VM),
+//! so that a long pasted line or a pattern like `(a|a)*` cannot make matching
+//! take exponential time or run out of stack.
+
+use crate::i18n::{message, Msg};
+use std::fmt;
+
+/// Pattern used by `--id-pattern` when none is given: an uppercase letter
+/// followed by three or more digits, e.g. `S001`.
+pub const DEFAULT_ID_PATTERN: &str = r"^[A-Z]\d{3,}$";
+
+/// Instructions a compiled pattern may take, so that counted repeats such as
+/// `{1000}` of a long group cannot blow up the program.
+const MAX_PROGRAM: usize = 10_000;
+
+/// A compiled pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+/// An instruction of the compiled program. The first three take a character;
+/// the others are followed at once, without taking any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Start,
+    End,
+    /// Go on at both instructions
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+impl Pattern {
+    /// Compile a pattern.
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(message(Msg::PatternUnmatchedParen, &[&source]));
+        }
+        let mut program = Vec::new();
+        compile_alternatives(&alternatives, &mut program)?;
+        push(&mut program, Inst::Match)?;
+        Ok(Pattern {
+            source: source.to_string(),
+            program,
+        })
+    }
+
+    /// Whether the pattern matches anywhere in `text` (use `^...$` to match the whole text).
+    /// Takes time proportional to the length of `text` times that of the pattern.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        for pos in 0..=chars.len() {
+            // A match may start at any position
+            if self.follow(&mut current, 0, pos, chars.len()) {
+                return true;
+            }
+            let Some(&c) = chars.get(pos) else {
+                break;
+            };
+            next.clear();
+            for &pc in &current.list {
+                let taken = match &self.program[pc] {
+                    Inst::Char(expected) => *expected == c,
+                    Inst::Any => true,
+                    Inst::Class { ranges, negated } => in_class(ranges, *negated, c),
+                    _ => false,
+                };
+                if taken && self.follow(&mut next, pc + 1, pos + 1, chars.len()) {
+                    return true;
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    /// Add the instruction at `pc` to `threads`, with every instruction it goes
+    /// on to without taking a character at `pos`. Returns whether one of them
+    /// is the end of the pattern.
+    fn follow(&self, threads: &mut Threads, pc: usize, pos: usize, len: usize) -> bool {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !threads.insert(pc) {
+                continue;
+            }
+            match self.program[pc] {
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == len => stack.push(pc + 1),
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Jump(to) => stack.push(to),
+                Inst::Match => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// `seq|seq|...`, up to a closing `)` or the end.
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('.') => Ok(Node::Any),
+            Some('(') => {
+                // Non-capturing groups are the same thing here
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let alternatives = self.alternatives()?;
+                match self.next() {
+                    Some(')') => Ok(Node::Group(alternatives)),
+                    _ => Err(message(Msg::PatternUnclosedParen, &[])),
+                }
+            }
+            Some('[') => self.class(),
+            Some('\\') => self.escape(),
+            Some(c @ ('*' | '+' | '?' | '{')) => Err(message(Msg::PatternNothingToRepeat, &[&c])),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err(message(Msg::PatternIncomplete, &[])),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or_else(|| message(Msg::PatternTrailingBackslash, &[]))?;
+        let class = |ranges: &[(char, char)], negated: bool| Node::Class {
+            ranges: ranges.to_vec(),
+            negated,
+        };
+        Ok(match c {
+            'd' => class(DIGIT, false),
+            'D' => class(DIGIT, true),
+            'w' => class(WORD, false),
+            'W' => class(WORD, true),
+            's' => class(SPACE, false),
+            'S' => class(SPACE, true),
+            c => Node::Char(c),
+        })
+    }
+
+    /// `[...]` after the opening bracket.
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or_else(|| message(Msg::PatternUnclosedClass, &[]))?;
+            match c {
+                ']' if !first => break,
+                '\\' => match self.next().ok_or_else(|| message(Msg::PatternTrailingBackslash, &[]))? {
+                    'd' => ranges.extend_from_slice(DIGIT),
+                    'w' => ranges.extend_from_slice(WORD),
+                    's' => ranges.extend_from_slice(SPACE),
+                    c => ranges.push((c, c)),
+                },
+                c => {
+                    if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&e| e != ']') {
+                        let end = self.chars[self.pos + 1];
+                        self.pos += 2;
+                        if end < c {
+                            return Err(message(Msg::PatternReversedRange, &[&c, &end]));
+                        }
+                        ranges.push((c, end));
+                    } else {
+                        ranges.push((c, c));
+                    }
+                }
+            }
+            first = false;
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let close = self.chars[self.pos..]
+                    .iter()
+                    .position(|&c| c == '}')
+                    .ok_or_else(|| message(Msg::PatternUnclosedBrace, &[]))?;
+                let inner: String = self.chars[self.pos + 1..self.pos + close].iter().collect();
+                let invalid = || message(Msg::PatternBadCount, &[&format!("{{{}}}", inner)]);
+                let number = |s: &str| s.trim().parse::<usize>().map_err(|_| invalid());
+                let (min, max) = match inner.split_once(',') {
+                    None => {
+                        let n = number(&inner)?;
+                        (n, Some(n))
+                    }
+                    Some((min, max)) if max.trim().is_empty() => (number(min)?, None),
+                    Some((min, max)) => (number(min)?, Some(number(max)?)),
+                };
+                if max.is_some_and(|max| max < min) {
+                    return Err(invalid());
+                }
+                self.pos += close;
+                (min, max)
+            }
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+        if matches!(node, Node::Start | Node::End) {
+            return Err(message(Msg::PatternRepeatedAnchor, &[]));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')];
+
+fn in_class(ranges: &[(char, char)], negated: bool, c: char) -> bool {
+    ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated
+}
+
+/// The instructions reached at one position of the text, each once.
+struct Threads {
+    list: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Threads {
+            list: Vec::new(),
+            seen: vec![false; len],
+        }
+    }
+
+    /// Add `pc`, unless it is already there.
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.seen[pc] {
+            return false;
+        }
+        self.seen[pc] = true;
+        self.list.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for &pc in &self.list {
+            self.seen[pc] = false;
+        }
+        self.list.clear();
+    }
+}
+
+/// Append `inst`, returning its index.
+fn push(program: &mut Vec<Inst>, inst: Inst) -> Result<usize, String> {
+    if program.len() >= MAX_PROGRAM {
+        return Err(message(Msg::PatternTooLong, &[]));
+    }
+    program.push(inst);
+    Ok(program.len() - 1)
+}
+
+/// `a|b|c` as `Split(a, Split(b, c))`, each alternative jumping past the rest.
+fn compile_alternatives(alternatives: &[Vec<Node>], program: &mut Vec<Inst>) -> Result<(), String> {
+    let mut jumps = Vec::new();
+    for (i, alternative) in alternatives.iter().enumerate() {
+        let split = if i + 1 < alternatives.len() {
+            Some(push(program, Inst::Split(program.len() + 1, 0))?)
+        } else {
+            None
+        };
+        for node in alternative {
+            compile_node(node, program)?;
+        }
+        if let Some(split) = split {
+            jumps.push(push(program, Inst::Jump(0))?);
+            program[split] = Inst::Split(split + 1, program.len());
+        }
+    }
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+    Ok(())
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    match node {
+        Node::Char(c) => push(program, Inst::Char(*c)).map(drop),
+        Node::Any => push(program, Inst::Any).map(drop),
+        Node::Class { ranges, negated } => push(
+            program,
+            Inst::Class {
+                ranges: ranges.clone(),
+                negated: *negated,
+            },
+        )
+        .map(drop),
+        Node::Start => push(program, Inst::Start).map(drop),
+        Node::End => push(program, Inst::End).map(drop),
+        Node::Group(alternatives) => compile_alternatives(alternatives, program),
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile_node(node, program)?;
+            }
+            match max {
+                // Loop back for as many more as there are
+                None => {
+                    let split = push(program, Inst::Split(program.len() + 1, 0))?;
+                    compile_node(node, program)?;
+                    push(program, Inst::Jump(split))?;
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                // Each further one is optional
+                Some(max) => {
+                    for _ in *min..*max {
+                        let split = push(program, Inst::Split(program.len() + 1, 0))?;
+                        compile_node(node, program)?;
+                        program[split] = Inst::Split(split + 1, program.len());
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_id_pattern() {
+        let pattern = Pattern::new(DEFAULT_ID_PATTERN).unwrap();
+        assert!(pattern.is_match("S001"));
+        assert!(pattern.is_match("A12345"));
+        assert!(!pattern.is_match("S0O1"));
+        assert!(!pattern.is_match("S01"));
+        assert!(!pattern.is_match("s001"));
+        assert!(!pattern.is_match("S001 "));
+    }
+
+    #[test]
+    fn test_pattern_features() {
+        let pattern = Pattern::new(r"^(B|M)\d{2}-[^_\s]+x?$").unwrap();
+        assert!(pattern.is_match("B23-abc"));
+        assert!(pattern.is_match("M01-zzx"));
+        assert!(!pattern.is_match("D23-abc"));
+        assert!(!pattern.is_match("B23-a c"));
+
+        // Without anchors the pattern may match anywhere
+        assert!(Pattern::new(r"\d+").unwrap().is_match("ab12"));
+        assert!(Pattern::new("a.*c").unwrap().is_match("xxabbbcxx"));
+        assert!(Pattern::new("^$").unwrap().is_match(""));
+    }
+
+    #[test]
+    fn test_long_text_and_nested_repeats() {
+        // Linear in the text, so neither the stack nor the time runs out
+        let pattern = Pattern::new(DEFAULT_ID_PATTERN).unwrap();
+        let long = format!("S{}", "1".repeat(200_000));
+        assert!(pattern.is_match(&long));
+        assert!(!pattern.is_match(&format!("{}x", long)));
+        let nested = Pattern::new("^(a|a)*$").unwrap();
+        assert!(!nested.is_match(&format!("{}b", "a".repeat(90))));
+        assert!(Pattern::new("^(a*)*$").unwrap().is_match("aaaa"));
+        assert!(Pattern::new("^a{2,3}b?$").unwrap().is_match("aaab"));
+        assert!(!Pattern::new("^a{2,3}$").unwrap().is_match("aaaa"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for source in ["(a", "a)", "[a-", "*a", "a{2", "a{3,1}", "[z-a]", "\\", "(abcdefghij){2000}"] {
+            assert!(Pattern::new(source).is_err(), "{}", source);
+        }
+    }
+}
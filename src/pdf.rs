@@ -0,0 +1,247 @@
+//! Printable PDF export of the final grouping.
+//!
+//! The PDF is written by hand to avoid extra dependencies. Text uses the
+//! standard Japanese CID font `HeiseiKakuGo-W5`, which PDF viewers provide
+//! without embedding, so both IDs and Japanese names can be printed.
+
+use crate::group::{Group, Student, StudentId};
+use crate::i18n::Msg;
+use crate::output::{self, Metadata};
+use std::collections::HashMap;
+
+/// A4 portrait in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+/// A line of text placed on a page.
+struct TextLine {
+    x: f32,
+    y: f32,
+    size: f32,
+    text: String,
+}
+
+/// Lays out lines top to bottom, starting a new page when one fills up.
+struct Layout {
+    pages: Vec<Vec<TextLine>>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn new_page(&mut self) {
+        if !self.pages.last().is_some_and(|p| p.is_empty()) {
+            self.pages.push(Vec::new());
+        }
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn line(&mut self, indent: f32, size: f32, text: String) {
+        let height = size * 1.5;
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+        self.y -= height;
+        self.pages.last_mut().unwrap().push(TextLine {
+            x: MARGIN + indent,
+            y: self.y,
+            size,
+            text,
+        });
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+}
+
+/// Render the grouping as a PDF document.
+///
+/// The first page is a summary of all groups. With `per_group`, each group
+/// also gets its own page in large type, suitable as a handout.
+pub fn render_pdf(
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+    per_group: bool,
+) -> Vec<u8> {
+    let labels = output::MemberLabels::new(names, metadata);
+    let display = |member: &Student| labels.label(member);
+    let total_students: usize = groups.iter().map(|g| g.members.len()).sum();
+
+    let lang = metadata.lang;
+    let mut layout = Layout::new();
+    layout.line(0.0, 20.0, lang.text(Msg::ResultTitle).to_string());
+    layout.line(
+        0.0,
+        10.0,
+        format!(
+            "{}  {}  {}",
+            lang.format(Msg::Summary, &[&groups.len(), &total_students]),
+            metadata.timestamp,
+            lang.format(Msg::ContentHash, &[&output::content_hash(groups)])
+        ),
+    );
+    layout.gap(10.0);
+    for (i, group) in groups.iter().enumerate() {
+        layout.line(
+            0.0,
+            13.0,
+            lang.format(Msg::GroupTitle, &[&metadata.group_names.label(i), &group.members.len()]),
+        );
+        for member in &group.members {
+            layout.line(18.0, 11.0, display(member));
+        }
+        layout.gap(6.0);
+    }
+
+    if per_group {
+        for (i, group) in groups.iter().enumerate() {
+            layout.new_page();
+            layout.line(0.0, 36.0, lang.format(Msg::GroupHeading, &[&metadata.group_names.label(i)]));
+
+            layout.gap(12.0);
+            for member in &group.members {
+                layout.line(18.0, 24.0, display(member));
+            }
+        }
+    }
+
+    write_document(&layout.pages)
+}
+
+/// Serialize the laid-out pages into a complete PDF file.
+fn write_document(pages: &[Vec<TextLine>]) -> Vec<u8> {
+    // Objects 1-5 are fixed; each page then uses two objects (page + content)
+    let mut objects: Vec<String> = Vec::new();
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 6 + i * 2).collect();
+
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    objects.push(format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        page_ids
+            .iter()
+            .map(|id| format!("{} 0 R", id))
+            .collect::<Vec<_>>()
+            .join(" "),
+        pages.len()
+    ));
+    objects.push(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /HeiseiKakuGo-W5 /Encoding /UniJIS-UCS2-H \
+         /DescendantFonts [4 0 R] >>"
+            .to_string(),
+    );
+    // CIDs 1-95 are the Latin glyphs, which are half width
+    objects.push(
+        "<< /Type /Font /Subtype /CIDFontType0 /BaseFont /HeiseiKakuGo-W5 \
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (Japan1) /Supplement 2 >> \
+         /FontDescriptor 5 0 R /DW 1000 /W [1 95 500] >>"
+            .to_string(),
+    );
+    objects.push(
+        "<< /Type /FontDescriptor /FontName /HeiseiKakuGo-W5 /Flags 4 \
+         /FontBBox [-92 -250 1010 922] /ItalicAngle 0 /Ascent 752 /Descent -221 \
+         /CapHeight 737 /StemV 114 >>"
+            .to_string(),
+    );
+
+    for (i, page) in pages.iter().enumerate() {
+        let content_id = page_ids[i] + 1;
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT, content_id
+        ));
+
+        let mut stream = String::new();
+        for line in page {
+            stream.push_str(&format!(
+                "BT /F1 {} Tf {:.1} {:.1} Td <{}> Tj ET\n",
+                line.size,
+                line.x,
+                line.y,
+                utf16_hex(&line.text)
+            ));
+        }
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            stream.len(),
+            stream
+        ));
+    }
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+
+    let xref_offset = out.len();
+    out.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        out.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    out.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+    out.into_bytes()
+}
+
+/// Encode text as a hex string of UTF-16BE code units for the UCS2 CMap.
+fn utf16_hex(text: &str) -> String {
+    text.encode_utf16().map(|unit| format!("{:04X}", unit)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_groups() -> Vec<Group> {
+        vec![
+            Group::from_members(vec!["S001".to_string(), "S002".to_string(), "S003".to_string()]),
+            Group::from_members(vec!["S004".to_string(), "S005".to_string()]),
+        ]
+    }
+
+    #[test]
+    fn test_pdf_structure() {
+        let pdf = render_pdf(&sample_groups(), &HashMap::new(), &Metadata::default(), false);
+        let text = String::from_utf8(pdf).unwrap();
+
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 1 >>"));
+        // "S001" in UTF-16BE
+        assert!(text.contains("<0053003000300031>"));
+
+        // The xref table must point at the start of each object
+        let xref = text.rfind("startxref\n").unwrap();
+        let xref_offset: usize = text[xref + 10..].lines().next().unwrap().parse().unwrap();
+        assert!(text[xref_offset..].starts_with("xref\n"));
+        let first_entry = text[xref_offset..].lines().nth(3).unwrap();
+        let offset: usize = first_entry[..10].parse().unwrap();
+        assert!(text[offset..].starts_with("1 0 obj"));
+    }
+
+    #[test]
+    fn test_pdf_per_group_pages() {
+        let pdf = render_pdf(&sample_groups(), &HashMap::new(), &Metadata::default(), true);
+        let text = String::from_utf8(pdf).unwrap();
+        assert!(text.contains("/Count 3 >>"));
+    }
+
+    #[test]
+    fn test_utf16_hex() {
+        assert_eq!(utf16_hex("Aグ"), "004130B0");
+    }
+}
@@ -0,0 +1,335 @@
+//! Preference graphs (`--preferences`): each student lists up to
+//! [`MAX_CHOICES`] classmates they would like to work with, and
+//! [`reorganize_by_preference`] forms the groups that grant as many of those
+//! wishes as it can (`--strategy preferences`).
+//!
+//! The wishes become a graph whose edges weigh how many of two students
+//! asked for each other (two when the wish is mutual). The heaviest edges are
+//! matched first, each pair is filled up with the classmates it wishes for
+//! most, and students are then swapped between groups while that grants more.
+
+use crate::balance;
+use crate::group::{Group, Student, StudentId};
+use crate::grouping::GroupingConfig;
+use crate::i18n::{message, Msg};
+use crate::log;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Classmates a student may list.
+pub const MAX_CHOICES: usize = 3;
+
+/// Passes over every pair of groups made to look for swaps.
+const MAX_PASSES: usize = 50;
+
+/// Wishes read from a preferences file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences {
+    /// Each student who listed classmates, with them in the order listed
+    pub wishes: Vec<(StudentId, Vec<StudentId>)>,
+}
+
+/// How many of a student's wishes a grouping grants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Satisfaction {
+    pub id: StudentId,
+    /// Listed classmates in the student's group
+    pub granted: usize,
+    pub listed: usize,
+}
+
+impl Preferences {
+    /// Parse a preferences file. Each line is a student followed by the
+    /// classmates they would like to work with, such as `S001: S005,S007`;
+    /// blank lines and `#` comments are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut preferences = Preferences::default();
+        let mut seen = HashSet::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at_line = |msg: Msg, args: &[&dyn fmt::Display]| message(Msg::AtLine, &[&(line_no + 1), &message(msg, args)]);
+
+            let (id, list) = line
+                .split_once(':')
+                .ok_or_else(|| at_line(Msg::PreferenceForm, &[]))?;
+            let id = id.trim().to_string();
+            if id.is_empty() {
+                return Err(at_line(Msg::PreferenceNoChoices, &[]));
+            }
+            if !seen.insert(id.clone()) {
+                return Err(at_line(Msg::PreferenceRepeated, &[&id]));
+            }
+            let mut listed: Vec<StudentId> = Vec::new();
+            for partner in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                if partner == id {
+                    return Err(at_line(Msg::PreferenceSelf, &[]));
+                }
+                if !listed.iter().any(|p| p == partner) {
+                    listed.push(partner.to_string());
+                }
+            }
+            if listed.len() > MAX_CHOICES {
+                return Err(at_line(Msg::PreferenceTooMany, &[&MAX_CHOICES]));
+            }
+            preferences.wishes.push((id, listed));
+        }
+
+        Ok(preferences)
+    }
+
+    /// Whether nobody listed anyone.
+    pub fn is_empty(&self) -> bool {
+        self.wishes.iter().all(|(_, listed)| listed.is_empty())
+    }
+
+    /// How many of their wishes `groups` grants to each student who listed
+    /// classmates, in the order of the file.
+    pub fn satisfaction(&self, groups: &[Group]) -> Vec<Satisfaction> {
+        let group_of: HashMap<&str, usize> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(i, g)| g.ids().map(move |id| (id.as_str(), i)))
+            .collect();
+        self.wishes
+            .iter()
+            .filter(|(_, listed)| !listed.is_empty())
+            .map(|(id, listed)| {
+                let own = group_of.get(id.as_str());
+                let granted = listed
+                    .iter()
+                    .filter(|partner| own.is_some() && group_of.get(partner.as_str()) == own)
+                    .count();
+                Satisfaction {
+                    id: id.clone(),
+                    granted,
+                    listed: listed.len(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Preferences {
+    /// Writes the wishes in the preferences file format.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, listed) in &self.wishes {
+            writeln!(f, "{}: {}", id, listed.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// The wishes between the students to regroup, by their index.
+struct Graph {
+    /// For each student, the others joined to them and how many of the two
+    /// wished for the other
+    edges: Vec<HashMap<usize, usize>>,
+}
+
+impl Graph {
+    fn new(preferences: &Preferences, pool: &[Student]) -> Self {
+        let index: HashMap<&str, usize> = pool.iter().enumerate().map(|(k, s)| (s.id.as_str(), k)).collect();
+        let mut edges = vec![HashMap::new(); pool.len()];
+        for (id, listed) in &preferences.wishes {
+            let Some(&a) = index.get(id.as_str()) else { continue };
+            for partner in listed {
+                if let Some(&b) = index.get(partner.as_str()) {
+                    *edges[a].entry(b).or_insert(0) += 1;
+                    *edges[b].entry(a).or_insert(0) += 1;
+                }
+            }
+        }
+        Graph { edges }
+    }
+
+    fn weight(&self, a: usize, b: usize) -> usize {
+        self.edges[a].get(&b).copied().unwrap_or(0)
+    }
+
+    /// Wishes granted between `student` and `members`.
+    fn gain(&self, student: usize, members: &[usize]) -> usize {
+        members.iter().map(|&m| self.weight(student, m)).sum()
+    }
+
+    /// Wishes granted within `members`.
+    fn granted(&self, members: &[usize]) -> usize {
+        members.iter().enumerate().map(|(i, &a)| self.gain(a, &members[i + 1..])).sum()
+    }
+}
+
+/// Reorganize groups like [`crate::grouping::regroup`], granting as many of
+/// the wishes in `config.preferences` as possible. Groups have the sizes
+/// `config.sizing` gives; full groups are kept in interactive mode, as with
+/// [`balance::reorganize_balanced`]. Constraints and the history are not
+/// looked at.
+pub fn reorganize_by_preference(groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+    let mut rng = config.rng();
+    let (mut final_groups, mut pool) = balance::open_pool(groups, config.mode, config.sizing);
+    if pool.is_empty() {
+        return final_groups;
+    }
+    // Ties between equally wished classmates are broken at random
+    pool.shuffle(&mut rng);
+    let preferences = config.preferences.clone().unwrap_or_default();
+    let graph = Graph::new(&preferences, &pool);
+    let sizes: Vec<usize> = config.sizing.split(pool.clone()).iter().map(|g| g.members.len()).collect();
+
+    let mut formed = match_greedily(&graph, &sizes);
+    let matched: usize = formed.iter().map(|members| graph.granted(members)).sum();
+    improve(&graph, &mut formed);
+    let granted: usize = formed.iter().map(|members| graph.granted(members)).sum();
+    log::info(|| message(Msg::TraceWishes, &[&matched, &granted]));
+
+    final_groups.extend(
+        formed
+            .into_iter()
+            .map(|members| Group::from_members(members.into_iter().map(|k| pool[k].clone()).collect())),
+    );
+    final_groups
+}
+
+/// Groups of `sizes` started from the pairs joined by the heaviest edges and
+/// filled up with the classmates wished for most, preferring those left
+/// unmatched so that other pairs are kept whole.
+fn match_greedily(graph: &Graph, sizes: &[usize]) -> Vec<Vec<usize>> {
+    let n = graph.edges.len();
+    let mut edges: Vec<(usize, usize, usize)> = (0..n)
+        .flat_map(|a| graph.edges[a].iter().filter(move |(&b, _)| a < b).map(move |(&b, &w)| (w, a, b)))
+        .collect();
+    edges.sort_unstable_by(|x, y| y.cmp(x));
+    let mut partner: Vec<Option<usize>> = vec![None; n];
+    let mut pairs = Vec::new();
+    for (_, a, b) in edges {
+        if partner[a].is_none() && partner[b].is_none() {
+            partner[a] = Some(b);
+            partner[b] = Some(a);
+            pairs.push((a, b));
+        }
+    }
+
+    let mut placed = vec![false; n];
+    let mut pairs = pairs.into_iter();
+    let mut groups = Vec::with_capacity(sizes.len());
+    for &size in sizes {
+        let mut members = Vec::with_capacity(size);
+        if size >= 2 {
+            if let Some((a, b)) = pairs.by_ref().find(|&(a, b)| !placed[a] && !placed[b]) {
+                members.extend([a, b]);
+                placed[a] = true;
+                placed[b] = true;
+            }
+        }
+        while members.len() < size {
+            let Some(next) = (0..n)
+                .filter(|&k| !placed[k])
+                .max_by_key(|&k| (graph.gain(k, &members), partner[k].map_or(true, |p| placed[p]), std::cmp::Reverse(k)))
+            else {
+                break;
+            };
+            members.push(next);
+            placed[next] = true;
+        }
+        groups.push(members);
+    }
+    groups
+}
+
+/// Swap students between groups while a swap grants more wishes.
+fn improve(graph: &Graph, groups: &mut [Vec<usize>]) {
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for g in 0..groups.len() {
+            for h in g + 1..groups.len() {
+                for i in 0..groups[g].len() {
+                    for j in 0..groups[h].len() {
+                        let (a, b) = (groups[g][i], groups[h][j]);
+                        let before = graph.gain(a, &groups[g]) + graph.gain(b, &groups[h]);
+                        let after = graph.gain(b, &groups[g]) + graph.gain(a, &groups[h]) - 2 * graph.weight(a, b);
+                        if after > before {
+                            groups[g][i] = b;
+                            groups[h][j] = a;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grouping::Mode;
+
+    fn students(n: usize) -> Vec<Student> {
+        (1..=n).map(|i| Student::new(format!("S{:03}", i))).collect()
+    }
+
+    #[test]
+    fn test_parse_preferences() {
+        let text = "# wishes\nS001: S002, S003\n\nS002:S001  # mutual\nS004:\n";
+        let preferences = Preferences::parse(text).unwrap();
+        assert_eq!(preferences.wishes.len(), 3);
+        assert_eq!(preferences.wishes[0], ("S001".to_string(), vec!["S002".to_string(), "S003".to_string()]));
+        assert!(preferences.wishes[2].1.is_empty());
+        assert_eq!(Preferences::parse(&preferences.to_string()).unwrap(), preferences);
+
+        assert!(Preferences::parse("S001 S002").is_err());
+        assert!(Preferences::parse("S001: S001").is_err());
+        assert!(Preferences::parse("S001: S002,S003,S004,S005").is_err());
+        assert!(Preferences::parse("S001: S002\nS001: S003").is_err());
+        assert!(Preferences::parse("S004:").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_grants_wishes() {
+        // Three cliques that want each other, listed across the input order
+        let text = "S001: S005,S009\nS005: S001,S009\nS009: S001,S005\n\
+                    S002: S006,S007\nS006: S002,S007\nS007: S002\n\
+                    S003: S004,S008\nS004: S008\nS008: S003";
+        let preferences = Preferences::parse(text).unwrap();
+        let config = GroupingConfig {
+            mode: Mode::Batch,
+            seed: Some(3),
+            preferences: Some(preferences.clone()),
+            ..Default::default()
+        };
+        let groups = reorganize_by_preference(vec![Group::from_members(students(9))], &config);
+
+        assert!(groups.iter().all(|g| g.members.len() == 3));
+        let satisfaction = preferences.satisfaction(&groups);
+        assert!(satisfaction.iter().all(|s| s.granted == s.listed));
+        assert_eq!(satisfaction.iter().map(|s| s.granted).sum::<usize>(), 15);
+    }
+
+    #[test]
+    fn test_keeps_full_groups() {
+        let preferences = Preferences::parse("S001: S004\nS005: S006").unwrap();
+        let config = GroupingConfig {
+            mode: Mode::Interactive,
+            seed: Some(1),
+            preferences: Some(preferences.clone()),
+            ..Default::default()
+        };
+        let entered = vec![
+            Group::from_members(students(3)),
+            Group::from_members(vec!["S004", "S005"]),
+            Group::from_members(vec!["S006", "S007"]),
+        ];
+        let groups = reorganize_by_preference(entered, &config);
+
+        assert!(groups.contains(&Group::from_members(students(3))));
+        let satisfaction = preferences.satisfaction(&groups);
+        assert_eq!(satisfaction[0].granted, 0);
+        assert_eq!(satisfaction[1].granted, 1);
+    }
+}
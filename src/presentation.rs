@@ -0,0 +1,91 @@
+//! Presentation order: the groups in a random order, with an optional time
+//! slot for each (`order` subcommand).
+
+use crate::group::GroupNames;
+use crate::i18n::{Lang, Msg};
+use crate::json::Value;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// The indices of `groups` groups in a random order.
+pub fn shuffle<R: Rng>(groups: usize, rng: &mut R) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..groups).collect();
+    order.shuffle(rng);
+    order
+}
+
+/// Minutes as `H:MM`.
+fn clock(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Render the order as text, one line per group, with the start and end of
+/// each slot when `slot_minutes` is given.
+pub fn render_text(order: &[usize], labels: &GroupNames, slot_minutes: Option<u32>, lang: Lang) -> String {
+    let mut out = format!("\n=== {} ===\n", lang.text(Msg::PresentationTitle));
+    for (n, &group) in order.iter().enumerate() {
+        let line = match slot_minutes {
+            Some(minutes) => {
+                let start = n as u32 * minutes;
+                lang.format(
+                    Msg::PresentationSlotTimed,
+                    &[&(n + 1), &labels.label(group), &clock(start), &clock(start + minutes)],
+                )
+            }
+            None => lang.format(Msg::PresentationSlot, &[&(n + 1), &labels.label(group)]),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    if let Some(minutes) = slot_minutes {
+        out.push_str(&format!("\n{}\n", lang.format(Msg::PresentationTotal, &[&(order.len() as u32 * minutes)])));
+    }
+    out
+}
+
+/// The order as JSON: one object per slot, with `start` and `end` in minutes
+/// from the beginning when `slot_minutes` is given.
+pub fn to_json(order: &[usize], labels: &GroupNames, slot_minutes: Option<u32>) -> Value {
+    let slots = order
+        .iter()
+        .enumerate()
+        .map(|(n, &group)| {
+            let mut fields = vec![("order", Value::from(n + 1)), ("group", Value::from(labels.label(group)))];
+            if let Some(minutes) = slot_minutes {
+                let start = n as u32 * minutes;
+                fields.push(("start", Value::from(start as usize)));
+                fields.push(("end", Value::from((start + minutes) as usize)));
+            }
+            Value::object(fields)
+        })
+        .collect();
+    Value::object(vec![("order", Value::Array(slots))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_shuffle_is_a_permutation() {
+        let mut order = shuffle(6, &mut StdRng::seed_from_u64(1));
+        assert_eq!(order, shuffle(6, &mut StdRng::seed_from_u64(1)));
+        order.sort();
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_render_with_slots() {
+        let text = render_text(&[2, 0, 1], &GroupNames::default(), Some(25), Lang::Ja);
+        assert!(text.contains("1. グループ C（0:00〜0:25）\n2. グループ A（0:25〜0:50）\n3. グループ B（0:50〜1:15）\n"), "{}", text);
+        assert!(text.ends_with("合計: 75 分\n"), "{}", text);
+        assert!(!render_text(&[1, 0], &GroupNames::default(), None, Lang::En).contains("Total"));
+
+        assert_eq!(
+            to_json(&[1, 0], &GroupNames::default(), Some(10)).to_string(),
+            r#"{"order":[{"order":1,"group":"B","start":0,"end":10},{"order":2,"group":"A","start":10,"end":20}]}"#
+        );
+    }
+}
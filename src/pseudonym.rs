@@ -0,0 +1,140 @@
+//! Pseudonyms that stand in for student IDs in shared results (`--anonymize`).
+//!
+//! Each student keeps the same pseudonym from one run to the next, so that
+//! example groupings made on different days still line up. The pairs are
+//! kept in a local CSV file (`S001,P001` per line) that never leaves the machine.
+
+use crate::group::{Group, StudentId};
+use crate::i18n::{message, Msg};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Student IDs and their pseudonyms, in the order they were given out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pseudonyms {
+    pairs: Vec<(StudentId, String)>,
+    index: HashMap<StudentId, usize>,
+    /// Whether pseudonyms were given out since loading
+    changed: bool,
+}
+
+impl Pseudonyms {
+    /// Read the pairs written by [`Pseudonyms::save`]. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut pseudonyms = Pseudonyms::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, pseudonym) = line
+                .split_once(',')
+                .map(|(id, pseudonym)| (id.trim(), pseudonym.trim()))
+                .filter(|(id, pseudonym)| !id.is_empty() && !pseudonym.is_empty())
+                .ok_or_else(|| message(Msg::AtLine, &[&(i + 1), &message(Msg::PseudonymForm, &[])]))?;
+            if pseudonyms.index.contains_key(id) {
+                return Err(message(Msg::AtLine, &[&(i + 1), &message(Msg::IdRepeated, &[&id])]));
+            }
+            pseudonyms.index.insert(id.to_string(), pseudonyms.pairs.len());
+            pseudonyms.pairs.push((id.to_string(), pseudonym.to_string()));
+        }
+        Ok(pseudonyms)
+    }
+
+    /// Load the pairs from `path`; a missing file has none yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Pseudonyms::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Pseudonyms::default()),
+            Err(e) => Err(message(Msg::CannotOpen, &[&path.display(), &e])),
+        }
+    }
+
+    /// Write the pairs to `path` if any were added, creating the directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for (id, pseudonym) in &self.pairs {
+            text.push_str(&format!("{},{}\n", id, pseudonym));
+        }
+        fs::write(path, text)
+    }
+
+    /// The pseudonym of `id`, giving out the next free one (`P001`, `P002`, ...) to a new student.
+    pub fn pseudonym(&mut self, id: &str) -> String {
+        if let Some(&i) = self.index.get(id) {
+            return self.pairs[i].1.clone();
+        }
+        let mut number = self.pairs.len() + 1;
+        let pseudonym = loop {
+            let candidate = format!("P{:03}", number);
+            if !self.pairs.iter().any(|(_, taken)| *taken == candidate) {
+                break candidate;
+            }
+            number += 1;
+        };
+        self.index.insert(id.to_string(), self.pairs.len());
+        self.pairs.push((id.to_string(), pseudonym.clone()));
+        self.changed = true;
+        pseudonym
+    }
+
+    /// `group` with every member replaced by their pseudonym.
+    pub fn group(&mut self, group: &Group) -> Group {
+        Group::from_members(group.members.iter().map(|m| m.renamed(self.pseudonym(&m.id))).collect())
+    }
+
+    /// [`Pseudonyms::group`] for each of `groups`.
+    pub fn groups(&mut self, groups: &[Group]) -> Vec<Group> {
+        groups.iter().map(|group| self.group(group)).collect()
+    }
+}
+
+/// `~/.grouping-tool/pseudonyms.csv`, next to the history file.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".grouping-tool").join("pseudonyms.csv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonyms_are_stable() {
+        let mut pseudonyms = Pseudonyms::parse("# mapping\nS002,P001\nS009,P003\n").unwrap();
+        assert_eq!(pseudonyms.pseudonym("S009"), "P003");
+        // P003 is taken, so the third student gets P004
+        assert_eq!(pseudonyms.pseudonym("S001"), "P004");
+        assert_eq!(pseudonyms.pseudonym("S001"), "P004");
+
+        let groups = vec![Group::from_members(vec!["S002".to_string(), "S005".to_string()])];
+        assert_eq!(pseudonyms.groups(&groups)[0].members, vec!["P001", "P005"]);
+
+        assert!(Pseudonyms::parse("S001").is_err());
+        assert!(Pseudonyms::parse("S001,P001\nS001,P002").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("grouping-tool-pseudonyms-{}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut pseudonyms = Pseudonyms::load(&path).unwrap();
+        assert_eq!(pseudonyms.pseudonym("S010"), "P001");
+        pseudonyms.save(&path).unwrap();
+
+        let mut loaded = Pseudonyms::load(&path).unwrap();
+        assert_eq!(loaded.pseudonym("S010"), "P001");
+        assert_eq!(loaded.pseudonym("S011"), "P002");
+        fs::remove_file(&path).unwrap();
+    }
+}
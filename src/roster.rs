@@ -0,0 +1,243 @@
+//! Reading class rosters exported as CSV (e.g. from an LMS).
+
+use crate::group::StudentId;
+use crate::i18n::{message, Msg};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+/// A column of the roster, given either by 1-based position or by header name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    /// Parse a column spec: a positive number is a 1-based position,
+    /// anything else is a header name.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        match spec.parse::<usize>() {
+            Ok(0) => Err(message(Msg::ColumnFromOne, &[])),
+            Ok(n) => Ok(Column::Index(n - 1)),
+            Err(_) if spec.is_empty() => Err(message(Msg::ColumnEmpty, &[])),
+            Err(_) => Ok(Column::Name(spec.to_string())),
+        }
+    }
+
+    /// Resolve the column to a 0-based index using the header row.
+    fn resolve(&self, header: Option<&[String]>) -> Result<usize, String> {
+        match self {
+            Column::Index(i) => Ok(*i),
+            Column::Name(name) => header
+                .and_then(|h| h.iter().position(|c| c.trim() == name))
+                .ok_or_else(|| message(Msg::ColumnNotFound, &[name])),
+        }
+    }
+}
+
+/// How to read a CSV roster.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Column holding the student ID
+    pub id_column: Column,
+    /// Column holding the student's name, if any
+    pub name_column: Option<Column>,
+    /// Whether the first row is a header row to be skipped
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            id_column: Column::Index(0),
+            name_column: None,
+            has_header: true,
+        }
+    }
+}
+
+/// Export formats of learning management systems read with `--from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosterFormat {
+    /// Moodle's participant, user or grade export (see [`crate::moodle`])
+    Moodle,
+}
+
+impl std::str::FromStr for RosterFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "moodle" => Ok(RosterFormat::Moodle),
+            _ => Err(message(Msg::FromMoodle, &[&s])),
+        }
+    }
+}
+
+/// One student read from the roster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RosterEntry {
+    pub id: StudentId,
+    pub name: Option<String>,
+    /// The remaining columns, keyed by header name (or 1-based column number
+    /// when there is no header)
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Split one CSV line into fields, handling double-quoted fields and `""` escapes.
+pub fn parse_csv_line(line: &str) -> Vec<String> {
+    parse_delimited_line(line, ',')
+}
+
+/// [`parse_csv_line`] with another separator, such as a tab.
+pub fn parse_delimited_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Read roster entries from CSV text. Blank lines and rows with an empty ID are skipped.
+pub fn read_csv_roster<R: BufRead>(reader: R, options: &CsvOptions) -> Result<Vec<RosterEntry>, String> {
+    let mut rows = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        // Spreadsheet exports often start with a UTF-8 BOM
+        rows.push(parse_csv_line(line.trim_start_matches('\u{feff}').trim_end()));
+    }
+    roster_from_rows(rows, options)
+}
+
+/// Build roster entries from rows of cells (e.g. a CSV file or a spreadsheet).
+/// Empty rows and rows with an empty ID are skipped.
+pub fn roster_from_rows(rows: Vec<Vec<String>>, options: &CsvOptions) -> Result<Vec<RosterEntry>, String> {
+    let mut rows = rows.into_iter().enumerate();
+    let mut header: Option<Vec<String>> = None;
+
+    if options.has_header {
+        match rows.next() {
+            Some((_, row)) => header = Some(row),
+            None => return Ok(Vec::new()),
+        }
+    }
+
+    let id_index = options.id_column.resolve(header.as_deref())?;
+    let name_index = match &options.name_column {
+        Some(column) => Some(column.resolve(header.as_deref())?),
+        None => None,
+    };
+
+    let mut entries = Vec::new();
+    for (row_index, fields) in rows {
+        if fields.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+
+        let row = row_index + 1;
+        let id = fields
+            .get(id_index)
+            .map(|f| f.trim())
+            .ok_or_else(|| message(Msg::AtLine, &[&row, &message(Msg::NoIdColumn, &[])]))?;
+        if id.is_empty() {
+            continue;
+        }
+
+        let name = name_index
+            .and_then(|i| fields.get(i))
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty());
+
+        let mut attributes = BTreeMap::new();
+        for (i, value) in fields.iter().enumerate() {
+            let value = value.trim();
+            if i == id_index || Some(i) == name_index || value.is_empty() {
+                continue;
+            }
+            let key = match header.as_ref().and_then(|h| h.get(i)) {
+                Some(column) => column.trim().to_string(),
+                None => (i + 1).to_string(),
+            };
+            attributes.insert(key, value.to_string());
+        }
+
+        entries.push(RosterEntry {
+            id: id.to_string(),
+            name,
+            attributes,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_line_with_quotes() {
+        assert_eq!(parse_csv_line("a,b,c"), vec!["a", "b", "c"]);
+        assert_eq!(parse_csv_line("\"a,b\",c"), vec!["a,b", "c"]);
+        assert_eq!(parse_csv_line("\"say \"\"hi\"\"\","), vec!["say \"hi\"", ""]);
+        assert_eq!(parse_delimited_line("a\t\"b\tc\"", '\t'), vec!["a", "b\tc"]);
+    }
+
+    #[test]
+    fn test_read_roster_by_header_name() {
+        let csv = "\u{feff}氏名,学籍番号,メール\r\n山田太郎,S001,a@example.com\r\n\r\n鈴木花子,S002,b@example.com\r\n";
+        let options = CsvOptions {
+            id_column: Column::parse("学籍番号").unwrap(),
+            name_column: Some(Column::parse("氏名").unwrap()),
+            has_header: true,
+        };
+        let entries = read_csv_roster(csv.as_bytes(), &options).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, "S001");
+        assert_eq!(entries[0].name.as_deref(), Some("山田太郎"));
+        assert_eq!(entries[1].id, "S002");
+        assert_eq!(entries[1].attributes.get("メール").map(String::as_str), Some("b@example.com"));
+        assert_eq!(entries[1].attributes.len(), 1);
+    }
+
+    #[test]
+    fn test_read_roster_by_index_without_header() {
+        let csv = "S001,x\nS002,y\n";
+        let options = CsvOptions {
+            has_header: false,
+            ..Default::default()
+        };
+        let entries = read_csv_roster(csv.as_bytes(), &options).unwrap();
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["S001", "S002"]);
+    }
+
+    #[test]
+    fn test_unknown_column_name_is_an_error() {
+        let options = CsvOptions {
+            id_column: Column::Name("ID".to_string()),
+            ..Default::default()
+        };
+        assert!(read_csv_roster("name,number\n".as_bytes(), &options).is_err());
+        assert!(Column::parse("0").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Multi-session schedules: several rounds of groupings for the same class,
+//! with as many different pairings as possible (in the spirit of the social
+//! golfer problem).
+
+use crate::constraints::{Constraints, SwapCheck};
+use crate::error::GroupingError;
+use crate::group::{Group, Student, StudentId};
+use crate::grouping::{self, GroupingConfig, Mode};
+use crate::history::History;
+use crate::i18n::{message, Msg};
+use crate::log;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Upper bound on improvement passes over one session.
+const MAX_SWAP_PASSES: usize = 20;
+
+/// Swaps tried for each session when a whole term is planned at once.
+pub const PLAN_ITERATIONS_PER_SESSION: usize = 20_000;
+
+/// Temperatures of the term-wide annealing at the first and the last swap.
+const START_TEMPERATURE: f64 = 2.0;
+const END_TEMPERATURE: f64 = 0.05;
+
+/// Generate `sessions` groupings of `students`, one per session.
+///
+/// Sessions are built one after another. Each one is chosen among many
+/// candidates to repeat as few earlier pairings as possible (including
+/// `config.history`, when given), and is then improved by swapping students
+/// between groups. Constraints and balanced attributes in `config` apply to
+/// every session. The whole schedule is reproducible with `config.seed`.
+/// Fails like [`grouping::regroup`].
+pub fn schedule(
+    students: Vec<Student>,
+    sessions: usize,
+    config: &GroupingConfig,
+) -> Result<Vec<Vec<Group>>, GroupingError> {
+    let mut rng = config.rng();
+    let mut seen = config.history.clone().unwrap_or_default();
+    let mut plan = Vec::with_capacity(sessions);
+
+    for _ in 0..sessions {
+        let session_config = GroupingConfig {
+            mode: Mode::Batch,
+            seed: Some(rng.gen()),
+            history: Some(seen.clone()),
+            ..config.clone()
+        };
+        let mut groups = grouping::regroup(vec![Group::from_members(students.clone())], session_config)?;
+        // Swapping would undo the attribute spread or mix strata, so such sessions are kept as they are
+        if config.balance.is_empty() && config.spread_weights.is_none() && config.stratify.is_none() {
+            improve_by_swaps(&mut groups, &seen, &config.constraints);
+        }
+        seen.record(&groups);
+        plan.push(groups);
+    }
+    Ok(plan)
+}
+
+/// Generate `sessions` groupings of `students` like [`schedule`], then
+/// improve the whole term at once (`plan`).
+///
+/// Sessions built one after another only avoid the pairings of the earlier
+/// ones, so the last sessions are left with the pairs nobody used. Here
+/// students are then swapped within any session, by simulated annealing, to
+/// lower the repeated meetings over all sessions and `config.history`
+/// together: a session can give up a pairing that a later one needs more.
+/// Only students with the same balanced attributes and strata are swapped,
+/// and no swap breaks more constraints. Fails like [`grouping::regroup`].
+pub fn plan(students: Vec<Student>, sessions: usize, config: &GroupingConfig) -> Result<Vec<Vec<Group>>, GroupingError> {
+    let mut plan = schedule(students, sessions, config)?;
+    // A stream of its own, so that the first sessions match those of `schedule`
+    let mut rng = config.rng();
+    let seed: u64 = rng.gen();
+    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(seed);
+    improve_term(&mut plan, config, sessions * PLAN_ITERATIONS_PER_SESSION, &mut rng);
+    Ok(plan)
+}
+
+/// How often each pair of students meets over a term, by student index.
+struct Meetings {
+    index: HashMap<StudentId, usize>,
+    counts: Vec<usize>,
+    students: usize,
+}
+
+impl Meetings {
+    fn new(plan: &[Vec<Group>], history: Option<&History>) -> Self {
+        let mut index = HashMap::new();
+        for student in plan.iter().flatten().flat_map(|g| &g.members) {
+            let next = index.len();
+            index.entry(student.id.clone()).or_insert(next);
+        }
+        let students = index.len();
+        let mut meetings = Meetings {
+            index,
+            counts: vec![0; students * students],
+            students,
+        };
+        if let Some(history) = history {
+            let ids: Vec<(&StudentId, usize)> = meetings.index.iter().map(|(id, &k)| (id, k)).collect();
+            for &(a, i) in &ids {
+                for &(b, j) in ids.iter().filter(|&&(_, j)| i < j) {
+                    let count = history.pair_count(a, b);
+                    meetings.counts[i * students + j] = count;
+                    meetings.counts[j * students + i] = count;
+                }
+            }
+        }
+        for groups in plan {
+            for group in groups {
+                for (n, a) in group.members.iter().enumerate() {
+                    for b in &group.members[n + 1..] {
+                        meetings.add(&a.id, &b.id, 1);
+                    }
+                }
+            }
+        }
+        meetings
+    }
+
+    fn count(&self, a: &str, b: &str) -> usize {
+        self.counts[self.index[a] * self.students + self.index[b]]
+    }
+
+    fn add(&mut self, a: &str, b: &str, change: isize) {
+        let (i, j) = (self.index[a], self.index[b]);
+        for at in [i * self.students + j, j * self.students + i] {
+            self.counts[at] = self.counts[at].wrapping_add_signed(change);
+        }
+    }
+
+    /// Meetings repeated over the term, each pair counted as the meetings
+    /// after its first, squared so that meeting three times weighs more than
+    /// two pairs meeting twice: `c * (c - 1) / 2` for a pair meeting `c` times.
+    fn cost(&self) -> usize {
+        (0..self.students)
+            .flat_map(|i| (i + 1..self.students).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let c = self.counts[i * self.students + j];
+                c * c.saturating_sub(1) / 2
+            })
+            .sum()
+    }
+
+    /// Change of [`Meetings::cost`] when `x` of `from` and `y` of `to` trade places.
+    fn swap_delta(&self, x: &str, from: &Group, y: &str, to: &Group) -> isize {
+        let leave = |a: &str, b: &str| -(self.count(a, b).saturating_sub(1) as isize);
+        let join = |a: &str, b: &str| self.count(a, b) as isize;
+        let from_side: isize = from.ids().filter(|m| *m != x).map(|m| leave(x, m) + join(y, m)).sum();
+        let to_side: isize = to.ids().filter(|m| *m != y).map(|m| leave(y, m) + join(x, m)).sum();
+        from_side + to_side
+    }
+
+    /// Record that `x` of `from` and `y` of `to` traded places.
+    fn swapped(&mut self, x: &str, from: &Group, y: &str, to: &Group) {
+        for m in from.ids().filter(|m| *m != x) {
+            self.add(x, m, -1);
+            self.add(y, m, 1);
+        }
+        for m in to.ids().filter(|m| *m != y) {
+            self.add(y, m, -1);
+            self.add(x, m, 1);
+        }
+    }
+}
+
+/// Anneal swaps over every session of `plan` for `iterations` tries, keeping
+/// the term with the fewest repeated meetings seen.
+fn improve_term(plan: &mut [Vec<Group>], config: &GroupingConfig, iterations: usize, rng: &mut StdRng) {
+    let sessions: Vec<usize> = (0..plan.len()).filter(|&s| plan[s].len() >= 2).collect();
+    if sessions.is_empty() {
+        return;
+    }
+    let mut keys = config.balance_keys();
+    keys.extend(config.spread_weights.clone());
+    keys.extend(config.stratify.clone());
+    let profile = |student: &Student| -> Vec<Option<String>> {
+        keys.iter()
+            .map(|key| student.attribute(key).map(str::to_lowercase))
+            .collect()
+    };
+
+    let mut meetings = Meetings::new(plan, config.history.as_ref());
+    let mut checks: Vec<SwapCheck> = plan.iter().map(|groups| SwapCheck::new(&config.constraints, groups)).collect();
+    let mut violations: Vec<usize> = plan.iter().map(|groups| config.constraints.unsatisfied(groups).len()).collect();
+    let mut current = meetings.cost();
+    let start = current;
+    let mut best = (current, plan.to_vec());
+
+    let mut tries = 0;
+    while tries < iterations && best.0 > 0 {
+        let progress = tries as f64 / iterations as f64;
+        let temperature = START_TEMPERATURE * (END_TEMPERATURE / START_TEMPERATURE).powf(progress);
+        tries += 1;
+
+        let s = *sessions.choose(rng).expect("some session has two groups");
+        let groups = &mut plan[s];
+        let (i, j) = (rng.gen_range(0..groups.len()), rng.gen_range(0..groups.len()));
+        let (i, j) = (i.min(j), i.max(j));
+        if i == j || groups[i].members.is_empty() || groups[j].members.is_empty() {
+            continue;
+        }
+        let a = rng.gen_range(0..groups[i].members.len());
+        let b = rng.gen_range(0..groups[j].members.len());
+        if !keys.is_empty() && profile(&groups[i].members[a]) != profile(&groups[j].members[b]) {
+            continue;
+        }
+        let (x, y) = (groups[i].members[a].id.clone(), groups[j].members[b].id.clone());
+        let delta = meetings.swap_delta(&x, &groups[i], &y, &groups[j]);
+        if delta > 0 && rng.gen::<f64>() >= (-(delta as f64) / temperature).exp() {
+            continue;
+        }
+
+        let check = &mut checks[s];
+        let around = check.violations_around(&x, &y);
+        check.swapped(&x, j, &y, i);
+        let swapped_violations = violations[s] - around + check.violations_around(&x, &y);
+        if swapped_violations > violations[s] {
+            check.swapped(&x, i, &y, j);
+            continue;
+        }
+        meetings.swapped(&x, &groups[i], &y, &groups[j]);
+        swap_members(groups, (i, a), (j, b));
+        violations[s] = swapped_violations;
+        current = current.wrapping_add_signed(delta);
+        if current < best.0 {
+            best = (current, plan.to_vec());
+        }
+    }
+    log::info(|| message(Msg::TraceTermSwaps, &[&tries, &start, &best.0]));
+    plan.clone_from_slice(&best.1);
+}
+
+/// Number of distinct pairs that meet somewhere in the schedule, and the
+/// number of times a pair meets again after its first meeting.
+pub fn pair_summary(plan: &[Vec<Group>]) -> (usize, usize) {
+    let mut history = History::default();
+    let mut repeated = 0;
+    for groups in plan {
+        repeated += history.repeat_count(groups);
+        history.record(groups);
+    }
+    (history.distinct_pairs(), repeated)
+}
+
+/// Swap students between groups while that lowers the number of repeated
+/// pairings without breaking more constraints. Only students who repeat a
+/// pairing in their group are moved, so a large class with few repeats is
+/// quick to improve.
+fn improve_by_swaps(groups: &mut [Group], history: &History, constraints: &Constraints) {
+    let mut violations = constraints.unsatisfied(groups).len();
+    let mut check = SwapCheck::new(constraints, groups);
+
+    for _ in 0..MAX_SWAP_PASSES {
+        let mut improved = false;
+        for i in 0..groups.len() {
+            for a in 0..groups[i].members.len() {
+                if history.repeats_with(&groups[i].members[a].id, &groups[i], &groups[i].members[a].id) == 0 {
+                    continue;
+                }
+                for j in (0..groups.len()).filter(|&j| j != i) {
+                    for b in 0..groups[j].members.len() {
+                        let x = &groups[i].members[a].id;
+                        let y = &groups[j].members[b].id;
+                        let before = history.repeats_with(x, &groups[i], x) + history.repeats_with(y, &groups[j], y);
+                        let after = history.repeats_with(y, &groups[i], x) + history.repeats_with(x, &groups[j], y);
+                        if after >= before {
+                            continue;
+                        }
+
+                        let around = check.violations_around(x, y);
+                        check.swapped(x, j, y, i);
+                        let swapped_violations = violations - around + check.violations_around(x, y);
+                        if swapped_violations > violations {
+                            check.swapped(x, i, y, j);
+                        } else {
+                            swap_members(groups, (i, a), (j, b));
+                            violations = swapped_violations;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+fn swap_members(groups: &mut [Group], first: (usize, usize), second: (usize, usize)) {
+    let ((i, a), (j, b)) = if first.0 < second.0 { (first, second) } else { (second, first) };
+    let (left, right) = groups.split_at_mut(j);
+    std::mem::swap(&mut left[i].members[a], &mut right[0].members[b]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn students(n: usize) -> Vec<Student> {
+        (1..=n).map(|i| Student::new(format!("S{:03}", i))).collect()
+    }
+
+    #[test]
+    fn test_every_session_has_everyone() {
+        let config = GroupingConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let plan = schedule(students(8), 3, &config).unwrap();
+
+        assert_eq!(plan.len(), 3);
+        for groups in &plan {
+            let mut members: Vec<Student> = groups.iter().flat_map(|g| g.members.clone()).collect();
+            members.sort();
+            assert_eq!(members, students(8));
+            let sizes: Vec<usize> = groups.iter().map(|g| g.members.len()).collect();
+            assert_eq!(sizes, vec![3, 3, 2]);
+        }
+    }
+
+    #[test]
+    fn test_sessions_avoid_repeated_pairs() {
+        let config = GroupingConfig {
+            seed: Some(3),
+            ..Default::default()
+        };
+        let plan = schedule(students(12), 3, &config).unwrap();
+        let (distinct, repeated) = pair_summary(&plan);
+
+        assert_eq!(repeated, 0, "{:?}", plan);
+        assert_eq!(distinct, 3 * 4 * 3);
+    }
+
+    #[test]
+    fn test_plan_repeats_less_than_schedule() {
+        let config = GroupingConfig {
+            seed: Some(8),
+            ..Default::default()
+        };
+        let greedy = schedule(students(12), 6, &config).unwrap();
+        let joint = plan(students(12), 6, &config).unwrap();
+
+        assert_eq!(joint.len(), 6);
+        for groups in &joint {
+            let mut members: Vec<Student> = groups.iter().flat_map(|g| g.members.clone()).collect();
+            members.sort();
+            assert_eq!(members, students(12));
+        }
+        assert!(Meetings::new(&joint, None).cost() < Meetings::new(&greedy, None).cost());
+        assert_eq!(plan(students(12), 6, &config).unwrap(), joint);
+    }
+
+    #[test]
+    fn test_plan_keeps_constraints() {
+        let config = GroupingConfig {
+            seed: Some(2),
+            constraints: Constraints::parse("together: S001,S002\napart: S003,S004").unwrap(),
+            ..Default::default()
+        };
+        for groups in plan(students(12), 5, &config).unwrap() {
+            assert!(config.constraints.unsatisfied(&groups).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_schedule_is_reproducible_with_seed() {
+        let config = GroupingConfig {
+            seed: Some(42),
+            ..Default::default()
+        };
+        assert_eq!(schedule(students(10), 4, &config).unwrap(), schedule(students(10), 4, &config).unwrap());
+    }
+}
@@ -0,0 +1,243 @@
+//! Seating charts: groups placed into adjacent seats of a classroom (`--seating`).
+//!
+//! The room is either a grid (`5x6`: 5 rows of 6 seats) or a template file
+//! drawn row by row from the front, with `o` for a seat and `.` or a space
+//! for an aisle:
+//!
+//! ```text
+//! ooo.ooo
+//! ooo.ooo
+//! ```
+//!
+//! Seats are filled in a snake order, left to right along the front row and
+//! back the other way along the next, so that a group that does not fit in
+//! the rest of a row carries on in the seats just behind. When there are
+//! seats to spare, a group that would be split by an aisle starts after it.
+
+use crate::group::{Group, GroupNames, Student, StudentId};
+use crate::i18n::{message, Lang, Msg};
+use crate::output::{self, MemberLabels, Metadata};
+use std::collections::HashMap;
+
+/// Seats of a classroom, row by row from the front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    rows: Vec<Vec<bool>>,
+}
+
+impl Layout {
+    /// `rows` rows of `columns` seats without aisles.
+    pub fn grid(rows: usize, columns: usize) -> Self {
+        Layout {
+            rows: vec![vec![true; columns]; rows],
+        }
+    }
+
+    /// Read a template: `o` (or `O`) is a seat, `.`, `_` and spaces are not.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rows = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row = line
+                .chars()
+                .map(|c| match c {
+                    'o' | 'O' => Ok(true),
+                    '.' | '_' | ' ' => Ok(false),
+                    _ => Err(message(Msg::AtLine, &[&(i + 1), &message(Msg::SeatingChar, &[&c])])),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+        let layout = Layout { rows };
+        if layout.capacity() == 0 {
+            return Err(message(Msg::NoSeats, &[]));
+        }
+        Ok(layout)
+    }
+
+    /// A grid written as `ROWSxCOLUMNS` (`5x6`), or `None` for anything else.
+    pub fn parse_grid(spec: &str) -> Option<Self> {
+        let (rows, columns) = spec.split_once(['x', 'X', '×'])?;
+        let rows: usize = rows.trim().parse().ok().filter(|&n| n > 0)?;
+        let columns: usize = columns.trim().parse().ok().filter(|&n| n > 0)?;
+        Some(Layout::grid(rows, columns))
+    }
+
+    /// Number of seats.
+    pub fn capacity(&self) -> usize {
+        self.rows.iter().flatten().filter(|&&seat| seat).count()
+    }
+
+    /// Seats as (row, column) in snake order.
+    fn snake(&self) -> Vec<(usize, usize)> {
+        let mut seats = Vec::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            let mut columns: Vec<usize> = (0..row.len()).filter(|&c| row[c]).collect();
+            if r % 2 == 1 {
+                columns.reverse();
+            }
+            seats.extend(columns.into_iter().map(|c| (r, c)));
+        }
+        seats
+    }
+}
+
+/// What is at one place of the chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    /// No seat (an aisle, or beyond the end of a short row)
+    Aisle,
+    /// A seat nobody was given
+    Empty,
+    /// A seat taken by a member of the group at this index
+    Taken(usize, Student),
+}
+
+/// Where everyone sits, row by row from the front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeatingChart {
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// Seat the members of `groups` so that each group sits together.
+pub fn assign(layout: &Layout, groups: &[Group]) -> Result<SeatingChart, String> {
+    let students: usize = groups.iter().map(|g| g.members.len()).sum();
+    if students > layout.capacity() {
+        return Err(message(Msg::TooFewSeats, &[&layout.capacity(), &students]));
+    }
+    let mut rows: Vec<Vec<Cell>> = layout
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|&seat| if seat { Cell::Empty } else { Cell::Aisle }).collect())
+        .collect();
+
+    let seats = layout.snake();
+    // Where each run of seats that touch one another in snake order ends
+    let mut run_end = vec![seats.len(); seats.len()];
+    for i in (0..seats.len().saturating_sub(1)).rev() {
+        let ((r1, c1), (r2, c2)) = (seats[i], seats[i + 1]);
+        let touching = (r1 == r2 && c1.abs_diff(c2) == 1) || (r2 == r1 + 1 && c1 == c2);
+        run_end[i] = if touching { run_end[i + 1] } else { i + 1 };
+    }
+
+    let mut next = 0;
+    let mut left = students;
+    for (g, group) in groups.iter().enumerate() {
+        let size = group.members.len();
+        if size == 0 {
+            continue;
+        }
+        // Skip the rest of a run that is too short, while the seats after it still fit everyone
+        let rest = run_end[next] - next;
+        if rest < size && seats.len() - run_end[next] >= left {
+            next = run_end[next];
+        }
+        for member in &group.members {
+            let (r, c) = seats[next];
+            rows[r][c] = Cell::Taken(g, member.clone());
+            next += 1;
+        }
+        left -= size;
+    }
+    Ok(SeatingChart { rows })
+}
+
+/// The chart as a grid of group labels, with the front of the room on top.
+pub fn render_text(chart: &SeatingChart, labels: &GroupNames, lang: Lang) -> String {
+    let label = |cell: &Cell| match cell {
+        Cell::Aisle => String::new(),
+        Cell::Empty => "-".to_string(),
+        Cell::Taken(g, _) => labels.label(*g),
+    };
+    // Full-width characters take two columns
+    let width_of = |text: &str| text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum::<usize>();
+    let width = chart.rows.iter().flatten().map(|cell| width_of(&label(cell))).max().unwrap_or(1);
+
+    let mut out = format!("\n=== {} ===\n{}\n", lang.text(Msg::SeatingTitle), lang.text(Msg::SeatingFront));
+    for row in &chart.rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|cell| {
+                let text = label(cell);
+                format!("{}{}", text, " ".repeat(width - width_of(&text)))
+            })
+            .collect();
+        out.push_str(cells.join(" ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// The chart as CSV, one line per row of seats, each seat holding the group
+/// label and the student. Aisles and empty seats are blank.
+pub fn render_csv(chart: &SeatingChart, names: &HashMap<StudentId, String>, metadata: &Metadata) -> String {
+    let labels = MemberLabels::new(names, metadata);
+    let mut out = String::new();
+    for row in &chart.rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|cell| match cell {
+                Cell::Taken(g, id) => format!("{} {}", metadata.group_names.label(*g), labels.label(id)),
+                Cell::Aisle | Cell::Empty => String::new(),
+            })
+            .collect();
+        output::push_csv_row(&mut out, &cells);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(sizes: &[usize]) -> Vec<Group> {
+        let mut next = 0;
+        sizes
+            .iter()
+            .map(|&size| {
+                let members = (next..next + size).map(|i| format!("S{:03}", i + 1)).collect();
+                next += size;
+                Group::from_members(members)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        assert_eq!(Layout::parse_grid("5x6").map(|l| l.capacity()), Some(30));
+        assert_eq!(Layout::parse_grid("2×3"), Some(Layout::grid(2, 3)));
+        assert_eq!(Layout::parse_grid("0x6"), None);
+        assert_eq!(Layout::parse_grid("room.txt"), None);
+
+        let layout = Layout::parse("# front\nooo.ooo\n\noo  ooo\n").unwrap();
+        assert_eq!(layout.capacity(), 11);
+        assert!(Layout::parse("oox").is_err());
+        assert!(Layout::parse("...").is_err());
+    }
+
+    #[test]
+    fn test_groups_sit_together() {
+        // Two rows of 4: the second group turns the corner into the seats behind
+        let chart = assign(&Layout::grid(2, 4), &groups(&[3, 3, 2])).unwrap();
+        let text = render_text(&chart, &GroupNames::default(), Lang::Ja);
+        assert!(text.ends_with("A A A B\nC C B B\n"), "{}", text);
+
+        assert!(assign(&Layout::grid(2, 2), &groups(&[3, 2])).is_err());
+    }
+
+    #[test]
+    fn test_groups_are_not_split_by_an_aisle() {
+        // With seats to spare, B starts after the aisle instead of straddling it
+        let layout = Layout::parse("oooo.oooo\noooo.oooo").unwrap();
+        let chart = assign(&layout, &groups(&[3, 3, 3])).unwrap();
+        let text = render_text(&chart, &GroupNames::default(), Lang::Ja);
+        assert!(text.ends_with("A A A -   B B B C\n- - - -   - - C C\n"), "{}", text);
+
+        let csv = render_csv(&chart, &HashMap::new(), &Metadata::default());
+        assert!(csv.starts_with("A S001,A S002,A S003,,,B S004,"), "{}", csv);
+    }
+}
@@ -0,0 +1,212 @@
+//! A small HTTP server exposing the grouping as a JSON REST API.
+//!
+//! Endpoints:
+//! - `POST /group`: group the students in the request body and return the
+//!   result in the same shape as `--format json`
+//! - `GET /health`: returns `{"status":"ok"}`
+
+use crate::api;
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+use crate::time;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// An HTTP response with a JSON body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: Value::object(vec![("error", Value::from(message.into()))]),
+        }
+    }
+}
+
+/// Answer one request. `body` is the raw request body.
+pub fn handle(method: &str, path: &str, body: &str) -> Response {
+    // Ignore any query string
+    let path = path.split('?').next().unwrap_or(path);
+    match (method, path) {
+        ("GET", "/health") => Response::ok(Value::object(vec![("status", Value::from("ok"))])),
+        ("POST", "/group") => {
+            let result = Value::parse(body).and_then(|request| api::group(&request, &time::now_rfc3339()));
+            match result {
+                Ok(value) => Response::ok(value),
+                Err(message) => Response::error(400, message),
+            }
+        }
+        (_, "/health") | (_, "/group") => Response::error(405, message(Msg::MethodNotAllowed, &[&method])),
+        _ => Response::error(404, message(Msg::PathNotFound, &[&path])),
+    }
+}
+
+/// Listen on `addr` (e.g. `127.0.0.1:8080`) and serve requests until the process ends.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream) {
+                        eprintln!("{}", message(Msg::RequestHandleFailed, &[&e]));
+                    }
+                });
+            }
+            Err(e) => eprintln!("{}", message(Msg::AcceptFailed, &[&e])),
+        }
+    }
+    Ok(())
+}
+
+/// Read one HTTP/1.1 request from `stream` and write the response.
+fn handle_connection(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("/").to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let response = if method == "OPTIONS" {
+        // CORS preflight from a browser dashboard
+        None
+    } else if content_length > MAX_BODY {
+        Some(Response::error(413, message(Msg::RequestTooLarge, &[])))
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        Some(match String::from_utf8(body) {
+            Ok(body) => handle(&method, &path, &body),
+            Err(_) => Response::error(400, message(Msg::RequestNotUtf8, &[])),
+        })
+    };
+    write_response(stream, response.as_ref())
+}
+
+fn write_response(mut stream: TcpStream, response: Option<&Response>) -> io::Result<()> {
+    let (status, body) = match response {
+        Some(response) => (response.status, format!("{}\n", response.body.to_pretty_string())),
+        None => (204, String::new()),
+    };
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels_and_members(response: &Response) -> Vec<Vec<String>> {
+        response
+            .body
+            .get("groups")
+            .and_then(Value::as_array)
+            .unwrap()
+            .iter()
+            .map(|g| {
+                g.get("members")
+                    .and_then(Value::as_array)
+                    .unwrap()
+                    .iter()
+                    .map(|m| m.as_str().unwrap().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_post_group_with_ids() {
+        let response = handle("POST", "/group", r#"{"students": ["S001", "S002", "S003", "S004"]}"#);
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            labels_and_members(&response),
+            vec![vec!["S001", "S002"], vec!["S003", "S004"]]
+        );
+        // Plain batch grouping uses no randomness, so no seed is reported
+        assert!(response.body.get("seed").unwrap().is_null());
+    }
+
+    #[test]
+    fn test_post_group_with_names_and_seed() {
+        let body = r#"{
+            "students": [{"id": "S001", "name": "山田"}, {"id": "S002", "attributes": {"skill": "a"}}, "S003", "S004", "S005"],
+            "balance": ["skill"],
+            "seed": 7
+        }"#;
+        let first = handle("POST", "/group", body);
+        let second = handle("POST", "/group", body);
+        assert_eq!(first.status, 200);
+        assert_eq!(first.body.get("seed").and_then(Value::as_u64), Some(7));
+        assert_eq!(labels_and_members(&first), labels_and_members(&second));
+        assert_eq!(
+            first.body.get("names").and_then(|n| n.get("S001")).and_then(Value::as_str),
+            Some("山田")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(handle("POST", "/group", "not json").status, 400);
+        assert_eq!(handle("POST", "/group", r#"{"students": []}"#).status, 400);
+        assert_eq!(handle("GET", "/group", "").status, 405);
+        assert_eq!(handle("GET", "/nothing", "").status, 404);
+        assert_eq!(handle("GET", "/health?x=1", "").status, 200);
+    }
+
+    #[test]
+    fn test_deeply_nested_body() {
+        // Would overflow the stack of a connection thread without the depth limit
+        let body = "[".repeat(100_000);
+        let response = handle("POST", "/group", &body);
+        assert_eq!(response.status, 400);
+        assert!(response.body.get("error").and_then(Value::as_str).is_some());
+    }
+}
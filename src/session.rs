@@ -0,0 +1,145 @@
+//! Groups being entered, saved to a file (`--save-session`) so that entry can
+//! be continued later (`--resume`).
+
+use grouping_tool::i18n::{message, Msg};
+use grouping_tool::json::Value;
+use grouping_tool::student::Student;
+use grouping_tool::{Group, StudentId};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A checkpoint of interactive entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    /// Completed groups
+    pub groups: Vec<Group>,
+    /// The group students were being added to
+    pub current: Group,
+    /// Students entered with attributes, keyed by ID
+    pub students: HashMap<StudentId, Student>,
+}
+
+impl Session {
+    /// Start entry after `groups`, which count as completed.
+    pub fn with_groups(groups: Vec<Group>) -> Self {
+        Session {
+            groups,
+            ..Default::default()
+        }
+    }
+
+    /// The session as a JSON object. `group_index` is the index of the current
+    /// group, kept so that the file is easy to read.
+    pub fn to_json(&self) -> Value {
+        let ids = |group: &Group| Value::Array(group.ids().map(|m| Value::from(m.as_str())).collect());
+        let mut students: Vec<&Student> = self.students.values().filter(|s| !s.attributes.is_empty()).collect();
+        students.sort_by(|a, b| a.id.cmp(&b.id));
+        let attributes = students
+            .into_iter()
+            .map(|student| {
+                let values = student
+                    .attributes
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+                    .collect();
+                (student.id.clone(), Value::Object(values))
+            })
+            .collect();
+
+        Value::object(vec![
+            ("groups", Value::Array(self.groups.iter().map(ids).collect())),
+            ("current_group", ids(&self.current)),
+            ("group_index", Value::from(self.groups.len())),
+            ("attributes", Value::Object(attributes)),
+        ])
+    }
+
+    /// Read a session written by [`Session::to_json`].
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let ids = |value: Option<&Value>| -> Result<Group, String> {
+            let members = value
+                .and_then(Value::as_array)
+                .ok_or_else(|| message(Msg::SessionGroupIds, &[]))?
+                .iter()
+                .map(|id| id.as_str().map(String::from).ok_or_else(|| message(Msg::ApiIdString, &[])))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Group::from_members(members))
+        };
+
+        let groups = value
+            .get("groups")
+            .and_then(Value::as_array)
+            .ok_or_else(|| message(Msg::MissingKey, &[&"groups"]))?
+            .iter()
+            .map(|group| ids(Some(group)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let current = match value.get("current_group") {
+            Some(group) => ids(Some(group))?,
+            None => Group::new(),
+        };
+        if let Some(index) = value.get("group_index").filter(|v| !v.is_null()) {
+            if index.as_u64() != Some(groups.len() as u64) {
+                return Err(message(Msg::SessionGroupIndex, &[]));
+            }
+        }
+
+        let mut students = HashMap::new();
+        if let Some(Value::Object(entries)) = value.get("attributes") {
+            for (id, attributes) in entries {
+                let mut student = Student::new(id.as_str());
+                if let Value::Object(attributes) = attributes {
+                    for (name, value) in attributes {
+                        if let Some(value) = value.as_str() {
+                            student.attributes.insert(name.clone(), value.to_string());
+                        }
+                    }
+                }
+                students.insert(id.clone(), student);
+            }
+        }
+
+        Ok(Session {
+            groups,
+            current,
+            students,
+        })
+    }
+
+    /// Read the session saved at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| message(Msg::CannotOpen, &[&path, &e]))?;
+        Value::parse(&text)
+            .and_then(|value| Session::from_json(&value))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Write the session to `path`. The file is replaced in one step, so an
+    /// interruption never leaves half a checkpoint behind.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, self.to_json().to_pretty_string() + "\n")
+            .and_then(|_| std::fs::rename(&temporary, Path::new(path)))
+            .map_err(|e| message(Msg::CannotSave, &[&path, &e]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let group = |ids: &[&str]| Group::from_members(ids.iter().map(|s| s.to_string()).collect());
+        let mut session = Session::with_groups(vec![group(&["S001", "S002", "S003"])]);
+        session.current = group(&["S004"]);
+        session.students.insert("S004".to_string(), Student::parse_line("S004,female", &["gender".to_string()]).unwrap());
+
+        let json = session.to_json();
+        assert_eq!(json.get("group_index").and_then(Value::as_u64), Some(1));
+        assert_eq!(Session::from_json(&json).unwrap(), session);
+
+        let broken = Value::parse(r#"{"groups": [["S001"]], "group_index": 2}"#).unwrap();
+        assert!(Session::from_json(&broken).is_err());
+        assert!(Session::from_json(&Value::parse(r#"{"groups": [[1]]}"#).unwrap()).is_err());
+    }
+}
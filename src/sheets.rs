@@ -0,0 +1,222 @@
+//! Reading rosters from and writing results to Google Sheets with the Sheets API.
+//!
+//! An OAuth access token is taken from `GOOGLE_OAUTH_ACCESS_TOKEN` when set.
+//! Otherwise a credentials file holding `client_id`, `client_secret` and
+//! `refresh_token` (e.g. from the OAuth Playground or `gcloud`) is used to
+//! obtain a fresh access token for each run.
+
+use crate::http;
+use crate::i18n::{self, message, Msg};
+use crate::json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const API_URL: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Environment variable holding a ready-to-use access token.
+pub const TOKEN_ENV: &str = "GOOGLE_OAUTH_ACCESS_TOKEN";
+
+/// The default credentials file, `~/.grouping-tool/google-oauth.json`.
+pub fn default_credentials_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".grouping-tool").join("google-oauth.json"))
+}
+
+/// Get an access token from the environment or by refreshing the token in
+/// the credentials file.
+pub fn access_token(credentials: Option<&Path>) -> Result<String, String> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        if !token.trim().is_empty() {
+            return Ok(token.trim().to_string());
+        }
+    }
+
+    let path = credentials.ok_or_else(|| message(Msg::NoGoogleCredentials, &[&TOKEN_ENV]))?;
+    let text = fs::read_to_string(path).map_err(|e| message(Msg::CannotOpen, &[&path.display(), &e]))?;
+    let credentials = Value::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let field = |key: &str| {
+        credentials
+            .get(key)
+            .and_then(Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| message(Msg::MissingKeyIn, &[&path.display(), &key]))
+    };
+
+    let form = format!(
+        "client_id={}&client_secret={}&refresh_token={}&grant_type=refresh_token",
+        http::percent_encode(&field("client_id")?),
+        http::percent_encode(&field("client_secret")?),
+        http::percent_encode(&field("refresh_token")?),
+    );
+    let response = http::send(
+        "POST",
+        TOKEN_URL,
+        &[("Content-Type", "application/x-www-form-urlencoded")],
+        Some(&form),
+    )?;
+    let body = check(response)?;
+    body.get("access_token")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| message(Msg::NoAccessToken, &[]))
+}
+
+/// Read the cells of `range` (e.g. `名簿!A:D`) as rows of strings.
+pub fn read_rows(spreadsheet_id: &str, range: &str, token: &str) -> Result<Vec<Vec<String>>, String> {
+    let url = format!(
+        "{}/{}/values/{}",
+        API_URL,
+        http::percent_encode(spreadsheet_id),
+        http::percent_encode(range)
+    );
+    let auth = format!("Bearer {}", token);
+    let response = http::send("GET", &url, &[("Authorization", &auth)], None)?;
+    Ok(rows_from_json(&check(response)?))
+}
+
+/// Replace the cells of `range` (e.g. `結果!A1`) with `rows`. The range is
+/// cleared first so that rows from an earlier, larger result do not remain.
+pub fn write_rows(spreadsheet_id: &str, range: &str, rows: &[Vec<String>], token: &str) -> Result<(), String> {
+    let base = format!(
+        "{}/{}/values/{}",
+        API_URL,
+        http::percent_encode(spreadsheet_id),
+        http::percent_encode(&clear_range(range))
+    );
+    let auth = format!("Bearer {}", token);
+    let headers = [("Authorization", auth.as_str()), ("Content-Type", "application/json")];
+    check(http::send("POST", &format!("{}:clear", base), &headers, Some("{}"))?)?;
+
+    let url = format!(
+        "{}/{}/values/{}?valueInputOption=RAW",
+        API_URL,
+        http::percent_encode(spreadsheet_id),
+        http::percent_encode(range)
+    );
+    let body = rows_to_json(range, rows).to_string();
+    check(http::send("PUT", &url, &headers, Some(&body))?)?;
+    Ok(())
+}
+
+/// The cells to clear before writing at `range`: from the start cell to the
+/// right and down, e.g. `結果!A1` → `結果!A1:Z`. A sheet name alone or an
+/// explicit `A1:C10` range is cleared as given.
+fn clear_range(range: &str) -> String {
+    let (sheet, cell) = match range.rsplit_once('!') {
+        Some((sheet, cell)) => (Some(sheet), cell),
+        None => (None, range),
+    };
+    if !is_cell(cell) {
+        return range.to_string();
+    }
+    match sheet {
+        Some(sheet) => format!("{}!{}:Z", sheet, cell),
+        None => format!("{}:Z", cell),
+    }
+}
+
+/// Whether `value` is a single cell in A1 notation.
+fn is_cell(value: &str) -> bool {
+    let digits = value.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    digits.len() < value.len() && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Parse a successful API response, turning API errors into messages.
+pub(crate) fn check(response: http::Response) -> Result<Value, String> {
+    let body = Value::parse(&response.body).unwrap_or(Value::Null);
+    if response.is_success() {
+        return Ok(body);
+    }
+    // The Google APIs nest the message; the token endpoint uses `error_description`
+    let message = body
+        .get("error")
+        .and_then(|e| e.get("message"))
+        .or_else(|| body.get("error_description"))
+        .or_else(|| body.get("error"))
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| response.body.trim().to_string());
+    Err(i18n::message(Msg::ServiceError, &[&"Google API", &response.status, &message]))
+}
+
+/// The `values` of a ValueRange response as strings.
+fn rows_from_json(value: &Value) -> Vec<Vec<String>> {
+    value
+        .get("values")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .map(|row| {
+            row.as_array()
+                .unwrap_or_default()
+                .iter()
+                .map(|cell| match cell {
+                    Value::String(s) => s.clone(),
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn rows_to_json(range: &str, rows: &[Vec<String>]) -> Value {
+    Value::object(vec![
+        ("range", Value::from(range)),
+        ("majorDimension", Value::from("ROWS")),
+        (
+            "values",
+            Value::Array(
+                rows.iter()
+                    .map(|row| Value::Array(row.iter().map(|cell| Value::from(cell.as_str())).collect()))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_from_json() {
+        let response = Value::parse(
+            r#"{"range": "名簿!A1:C3", "majorDimension": "ROWS", "values": [["学籍番号", "氏名"], ["S001", "山田", 3], []]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rows_from_json(&response),
+            vec![vec!["学籍番号", "氏名"], vec!["S001", "山田", "3"], vec![]]
+        );
+        // An empty range has no `values`
+        assert!(rows_from_json(&Value::parse("{}").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_rows_to_json_and_clear_range() {
+        let rows = vec![vec!["student_id".to_string(), "group".to_string()]];
+        assert_eq!(
+            rows_to_json("結果!A1", &rows).to_string(),
+            r#"{"range":"結果!A1","majorDimension":"ROWS","values":[["student_id","group"]]}"#
+        );
+        assert_eq!(clear_range("結果!A1"), "結果!A1:Z");
+        assert_eq!(clear_range("B3"), "B3:Z");
+        assert_eq!(clear_range("結果"), "結果");
+        assert_eq!(clear_range("結果!A1:C10"), "結果!A1:C10");
+    }
+
+    #[test]
+    fn test_api_error_message() {
+        let response = http::Response {
+            status: 403,
+            body: r#"{"error": {"code": 403, "message": "The caller does not have permission"}}"#.to_string(),
+        };
+        assert_eq!(
+            check(response).unwrap_err(),
+            "Google API エラー (403): The caller does not have permission"
+        );
+    }
+}
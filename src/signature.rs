@@ -0,0 +1,121 @@
+//! Signatures of written results (`--sign <KEYFILE>`, `verify`).
+//!
+//! Each file a result is written to gets a `.sig` file next to it with the
+//! HMAC-SHA256 of its bytes under a secret key kept on the teacher's
+//! machine. Checking the file later with the same key shows whether it is
+//! byte for byte the one written then: a single changed character, or a
+//! signature made with another key, no longer matches. Since the key both
+//! signs and checks, it should not be handed out with the results.
+
+use crate::digest;
+use crate::i18n::{message, Msg};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The scheme written in front of every signature.
+pub const SCHEME: &str = "hmac-sha256";
+
+/// Bytes of randomness in a key made by [`create_key`].
+pub const KEY_LEN: usize = 32;
+
+/// The signature file of `path`: `result.json` is signed in `result.json.sig`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// Read the key in the file at `path`: its text without surrounding whitespace.
+pub fn read_key(path: &Path) -> Result<Vec<u8>, String> {
+    let text = fs::read_to_string(path).map_err(|e| message(Msg::KeyUnreadable, &[&path.display(), &e]))?;
+    match text.trim() {
+        "" => Err(message(Msg::KeyEmpty, &[&path.display()])),
+        key => Ok(key.as_bytes().to_vec()),
+    }
+}
+
+/// Write a new random key to `path`, which must not exist yet, readable only
+/// by its owner where the file system allows it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn create_key(path: &Path) -> io::Result<()> {
+    use std::io::Write;
+
+    let key: [u8; KEY_LEN] = rand::random();
+    let mut open = fs::OpenOptions::new();
+    open.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut open, 0o600);
+    let mut file = open.open(path)?;
+    file.write_all(format!("{}\n", digest::to_hex(&key)).as_bytes())
+}
+
+/// The signature of `data` under `key`, as written to the signature file.
+pub fn sign(key: &[u8], data: &[u8]) -> String {
+    format!("{} {}\n", SCHEME, digest::to_hex(&digest::hmac_sha256(key, data)))
+}
+
+/// Whether `signature`, as read from a signature file, was made for `data` with `key`.
+pub fn verify(key: &[u8], data: &[u8], signature: &str) -> Result<bool, String> {
+    let (scheme, hex) = signature
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| message(Msg::SignatureForm, &[]))?;
+    if scheme != SCHEME {
+        return Err(message(Msg::SignatureScheme, &[&scheme]));
+    }
+    let expected = digest::to_hex(&digest::hmac_sha256(key, data));
+    let given = hex.trim().to_ascii_lowercase();
+    // Compare every byte, so that the time taken does not tell how much matched
+    let differences = expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    Ok(expected.len() == given.len() && differences == 0)
+}
+
+/// Sign the file at `path` with `key`, writing [`signature_path`], which is returned.
+pub fn sign_file(path: &Path, key: &[u8]) -> io::Result<PathBuf> {
+    let data = fs::read(path)?;
+    let signature = signature_path(path);
+    fs::write(&signature, sign(key, &data))?;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let data = b"{\"groups\": [[\"S001\", \"S002\"]]}\n";
+        let signature = sign(b"secret", data);
+        assert!(signature.starts_with("hmac-sha256 "));
+        assert_eq!(verify(b"secret", data, &signature), Ok(true));
+        // Another key, or a changed result, no longer matches
+        assert_eq!(verify(b"other", data, &signature), Ok(false));
+        assert_eq!(verify(b"secret", b"{\"groups\": [[\"S001\", \"S003\"]]}\n", &signature), Ok(false));
+        assert_eq!(verify(b"secret", data, "hmac-sha256 00"), Ok(false));
+        assert!(verify(b"secret", data, "garbage").is_err());
+        assert!(verify(b"secret", data, "md5 00").is_err());
+    }
+
+    #[test]
+    fn test_sign_file() {
+        let dir = std::env::temp_dir().join(format!("grouping-tool-signature-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key_path = dir.join("key");
+        create_key(&key_path).unwrap();
+        assert!(create_key(&key_path).is_err());
+        let key = read_key(&key_path).unwrap();
+        assert_eq!(key.len(), KEY_LEN * 2);
+
+        let result = dir.join("result.csv");
+        fs::write(&result, "student_id,group\nS001,A\n").unwrap();
+        let signature = sign_file(&result, &key).unwrap();
+        assert_eq!(signature, dir.join("result.csv.sig"));
+        let written = fs::read_to_string(&signature).unwrap();
+        assert_eq!(verify(&key, &fs::read(&result).unwrap(), &written), Ok(true));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+//! Exhaustive search for a grouping that satisfies every constraint.
+//!
+//! The greedy packing in [`crate::constraints`] is fast but can miss a grouping
+//! when `together` and `apart` sets only fit one way into the group sizes.
+//! [`find`] backtracks over every placement instead, and [`explain`] names the
+//! constraints that cannot be satisfied together when there is no way at all.
+
+use crate::constraints::{self, Constraints};
+use crate::group::{Group, Student};
+use crate::grouping::Sizing;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// Upper bound on placements tried by one search, so that a large instance
+/// gives up instead of hanging.
+const MAX_STEPS: usize = 100_000;
+
+/// Upper bound on the units that constraints touch, beyond which the search gives up.
+const MAX_UNITS: usize = 5_000;
+
+/// What a search concluded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Found(Vec<Group>),
+    /// Every placement was tried and none satisfies the constraints
+    Infeasible,
+    /// The step budget ran out first
+    GaveUp,
+}
+
+/// Group sizes that `sizing` allows for `n` students, preferred first. For 2-3
+/// person groups the plans run from the most 3-person groups downwards
+/// (trading two 3-person groups for three 2-person groups keeps the total);
+/// with [`Sizing::Pairs`] there is at most one 3-person group, and with
+/// [`Sizing::Even`] an even count that is not divisible by 3 starts from all
+/// pairs and runs upwards. The plans are made as they are needed, since a
+/// large class has thousands of them.
+pub fn size_plans(n: usize, sizing: Sizing) -> impl Iterator<Item = Vec<usize>> {
+    let (only, max_threes) = match (sizing, n % 3) {
+        _ if n <= 1 => (Some(vec![n; n]), 0),
+        (Sizing::Groups(_) | Sizing::Range { .. }, _) => (Some(sizing.sizes(n)), 0),
+        // Pairs only, with one 3-person group for an odd count
+        (Sizing::Pairs, _) => (None, n % 2),
+        (Sizing::Triples | Sizing::Even, 0) => (None, n / 3),
+        (Sizing::Triples | Sizing::Even, 1) => (None, (n - 4) / 3),
+        (Sizing::Triples | Sizing::Even, _) => (None, (n - 2) / 3),
+    };
+    let count = if only.is_some() { 0 } else { max_threes / 2 + 1 };
+    let ascending = sizing == Sizing::Even && n % 3 != 0 && n % 2 == 0;
+    only.into_iter().chain((0..count).map(move |k| {
+        let threes = if ascending { max_threes % 2 + 2 * k } else { max_threes - 2 * k };
+        let mut sizes = vec![3; threes];
+        sizes.resize(threes + (n - 3 * threes) / 2, 2);
+        sizes
+    }))
+}
+
+/// Search for a grouping of `pool` into the sizes of `sizing` that satisfies
+/// every constraint. `None` when there is none, or when the search is too
+/// large to finish.
+pub fn find(pool: &[Student], sizing: Sizing, constraints: &Constraints) -> Option<Vec<Group>> {
+    match search(pool, sizing, &constraints.restricted_to(pool)) {
+        Outcome::Found(groups) => Some(groups),
+        Outcome::Infeasible | Outcome::GaveUp => None,
+    }
+}
+
+/// When no grouping of `pool` satisfies the constraints, the constraints that
+/// conflict: a subset that is still unsatisfiable, but would be satisfiable
+/// without any one of its constraints. `None` when a grouping exists or the
+/// search cannot tell.
+///
+/// Only the students of `pool` are taken into account, as in
+/// [`Constraints::unsatisfied`].
+pub fn explain(pool: &[Student], sizing: Sizing, constraints: &Constraints) -> Option<Constraints> {
+    let mut core = constraints.restricted_to(pool);
+    if search(pool, sizing, &core) != Outcome::Infeasible {
+        return None;
+    }
+
+    // Drop each constraint in turn, keeping it out whenever the rest still conflict
+    let mut i = 0;
+    while i < core.together.len() {
+        let mut without = core.clone();
+        without.together.remove(i);
+        if search(pool, sizing, &without) == Outcome::Infeasible {
+            core = without;
+        } else {
+            i += 1;
+        }
+    }
+    let mut i = 0;
+    while i < core.apart.len() {
+        let mut without = core.clone();
+        without.apart.remove(i);
+        if search(pool, sizing, &without) == Outcome::Infeasible {
+            core = without;
+        } else {
+            i += 1;
+        }
+    }
+    Some(core)
+}
+
+/// Try every size plan for `pool` in turn, placing whole `together` units.
+///
+/// Only units that some constraint touches are searched over; the other
+/// students fill whatever room is left afterwards, so a large class with a
+/// few constraints is as quick to solve as a small one.
+fn search(pool: &[Student], sizing: Sizing, constraints: &Constraints) -> Outcome {
+    let apart_ids: HashSet<&str> = constraints.apart.iter().flatten().map(String::as_str).collect();
+    let (mut units, free): (Vec<Vec<Student>>, Vec<Vec<Student>>) =
+        constraints::merge_together(pool, constraints)
+            .into_iter()
+            .partition(|unit| unit.len() > 1 || apart_ids.contains(unit[0].id.as_str()));
+    // Each unit is one level of recursion
+    if units.len() > MAX_UNITS {
+        return Outcome::GaveUp;
+    }
+    // A unit that holds two students who must be apart fits nowhere
+    if units.iter().any(|unit| constraints.conflicts(unit, unit)) {
+        return Outcome::Infeasible;
+    }
+    // Placing large units first fails early
+    units.sort_by_key(|unit| Reverse(unit.len()));
+    if units.first().is_some_and(|unit| unit.len() > sizing.largest_size(pool.len())) {
+        return Outcome::Infeasible;
+    }
+
+    let unit_of: HashMap<&str, usize> = units
+        .iter()
+        .enumerate()
+        .flat_map(|(i, unit)| unit.iter().map(move |s| (s.id.as_str(), i)))
+        .collect();
+    let mut apart = vec![Vec::new(); units.len()];
+    for ids in &constraints.apart {
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                if let (Some(&ua), Some(&ub)) = (unit_of.get(a.as_str()), unit_of.get(b.as_str())) {
+                    apart[ua].push(ub);
+                    apart[ub].push(ua);
+                }
+            }
+        }
+    }
+
+    let mut placement = Placement {
+        units: &units,
+        apart,
+        bins: Vec::new(),
+        room: Vec::new(),
+        steps: 0,
+    };
+    for sizes in size_plans(pool.len(), sizing) {
+        // Setting up a plan costs a step per group, so a large class does not try thousands of them
+        placement.steps += sizes.len();
+        if placement.steps > MAX_STEPS {
+            return Outcome::GaveUp;
+        }
+        placement.bins = vec![Vec::new(); sizes.len()];
+        placement.room = sizes;
+        match placement.place(0) {
+            Some(true) => {
+                let mut free = free.iter().flatten();
+                let groups = placement
+                    .bins
+                    .iter()
+                    .zip(&placement.room)
+                    .map(|(bin, &room)| {
+                        let mut members: Vec<Student> = bin.iter().flat_map(|&u| units[u].clone()).collect();
+                        members.extend(free.by_ref().take(room).cloned());
+                        Group::from_members(members)
+                    })
+                    .collect();
+                return Outcome::Found(groups);
+            }
+            Some(false) => {}
+            None => return Outcome::GaveUp,
+        }
+    }
+    Outcome::Infeasible
+}
+
+/// Backtracking state: units placed so far in each bin, and the room left.
+struct Placement<'a> {
+    units: &'a [Vec<Student>],
+    /// Units holding students who must be apart from those of each unit
+    apart: Vec<Vec<usize>>,
+    bins: Vec<Vec<usize>>,
+    room: Vec<usize>,
+    steps: usize,
+}
+
+impl Placement<'_> {
+    /// Place units `next..` into the bins. `Some(false)` when they do not fit
+    /// and `None` when the step budget runs out.
+    fn place(&mut self, next: usize) -> Option<bool> {
+        if next == self.units.len() {
+            return Some(true);
+        }
+        let size = self.units[next].len();
+        // Empty bins of the same size are interchangeable; trying one is enough
+        let mut tried_empty: Vec<usize> = Vec::new();
+        for bin in 0..self.bins.len() {
+            if self.room[bin] < size || self.bins[bin].iter().any(|u| self.apart[next].contains(u)) {
+                continue;
+            }
+            if self.bins[bin].is_empty() {
+                let capacity = self.room[bin];
+                if tried_empty.contains(&capacity) {
+                    continue;
+                }
+                tried_empty.push(capacity);
+            }
+
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return None;
+            }
+            self.bins[bin].push(next);
+            self.room[bin] -= size;
+            if self.place(next + 1)? {
+                return Some(true);
+            }
+            self.bins[bin].pop();
+            self.room[bin] += size;
+        }
+        Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::StudentId;
+
+    fn ids(range: std::ops::RangeInclusive<usize>) -> Vec<StudentId> {
+        range.map(|i| format!("S{:03}", i)).collect()
+    }
+
+    fn students(range: std::ops::RangeInclusive<usize>) -> Vec<Student> {
+        ids(range).into_iter().map(Student::new).collect()
+    }
+
+    #[test]
+    fn test_size_plans() {
+        let plans = |n, sizing| size_plans(n, sizing).collect::<Vec<_>>();
+        assert_eq!(plans(7, Sizing::Triples), vec![vec![3, 2, 2]]);
+        assert_eq!(plans(9, Sizing::Triples), vec![vec![3, 3, 3], vec![3, 2, 2, 2]]);
+        assert_eq!(plans(7, Sizing::Pairs), vec![vec![3, 2, 2]]);
+        assert_eq!(plans(8, Sizing::Even), vec![vec![2, 2, 2, 2], vec![3, 3, 2]]);
+        assert_eq!(plans(9, Sizing::Even), plans(9, Sizing::Triples));
+        assert_eq!(plans(10, Sizing::Groups(3)), vec![vec![4, 3, 3]]);
+        assert_eq!(plans(1, Sizing::Triples), vec![vec![1]]);
+        assert_eq!(plans(0, Sizing::Triples), vec![Vec::<usize>::new()]);
+        // A large class has many plans; the first is ready without making the rest
+        assert_eq!(size_plans(100_000, Sizing::Triples).count(), 16_667);
+        assert_eq!(size_plans(100_000, Sizing::Triples).next().map(|sizes| sizes.len()), Some(33_334));
+    }
+
+    #[test]
+    fn test_find_satisfies_tight_constraints() {
+        // Two pairs and a trio among 7 students only fit as 3+2+2
+        let text = "together: S001,S002\ntogether: S003,S004\ntogether: S005,S006,S007\napart: S001,S003";
+        let constraints = Constraints::parse(text).unwrap();
+        let groups = find(&students(1..=7), Sizing::Triples, &constraints).unwrap();
+
+        assert!(constraints.unsatisfied(&groups).is_empty(), "{:?}", groups);
+        let sizes: Vec<usize> = groups.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn test_explain_names_the_conflict() {
+        // S001 and S002 must be together and apart; the other lines are fine
+        let text = "together: S001,S002\napart: S003,S004\napart: S001,S002,S005\ntogether: S006,S099";
+        let constraints = Constraints::parse(text).unwrap();
+        let core = explain(&students(1..=6), Sizing::Triples, &constraints).unwrap();
+        assert_eq!(core.to_string(), "together: S001,S002\napart: S001,S002,S005\n");
+
+        // Four students who must all be apart do not fit into two groups
+        let constraints = Constraints::parse("apart: S001,S002,S003,S004\ntogether: S005,S006").unwrap();
+        let core = explain(&students(1..=6), Sizing::Triples, &constraints).unwrap();
+        assert_eq!(core.apart, vec![ids(1..=4)]);
+        assert!(core.together.is_empty());
+
+        assert_eq!(explain(&students(1..=6), Sizing::Triples, &Constraints::default()), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Figures for judging the quality of a grouping (`--stats`).
+
+use crate::constraints::Constraints;
+use crate::group::Group;
+use crate::history::History;
+use crate::i18n::{Lang, Msg};
+
+/// Summary of one grouping.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub students: usize,
+    /// Number of groups of each size, largest size first
+    pub sizes: Vec<(usize, usize)>,
+    /// Pairs sharing a group in this grouping
+    pub pairs: usize,
+    /// Repeats of past pairings when a history was given
+    pub repeats: Option<Repeats>,
+    /// Constraints satisfied, out of all constraints
+    pub constraints: (usize, usize),
+    /// Soft constraints broken and their total weight, when there are any
+    pub soft: Option<(usize, u64)>,
+    /// Final score of a search such as `--strategy genetic`, lower being
+    /// better ([`crate::anneal::score`]); not shown when `None`
+    pub fitness: Option<f64>,
+    /// Edits made while the students were entered interactively; not shown when `None`
+    pub edits: Option<Edits>,
+}
+
+/// How this grouping compares with the history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Repeats {
+    /// Sessions in the history
+    pub sessions: usize,
+    /// Pairs of this grouping that shared a group before
+    pub pairs: usize,
+    /// Past meetings of those pairs, counting every session
+    pub meetings: usize,
+}
+
+/// Counts of the edits made while entering students, as written to the
+/// session log (`--session-log`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Edits {
+    pub added: usize,
+    pub deleted: usize,
+    pub moved: usize,
+    pub swapped: usize,
+    /// Groups completed, by filling up or by the user
+    pub completed: usize,
+    pub undone: usize,
+}
+
+impl std::ops::AddAssign for Edits {
+    fn add_assign(&mut self, other: Edits) {
+        self.added += other.added;
+        self.deleted += other.deleted;
+        self.moved += other.moved;
+        self.swapped += other.swapped;
+        self.completed += other.completed;
+        self.undone += other.undone;
+    }
+}
+
+impl Stats {
+    pub fn new(groups: &[Group], history: Option<&History>, constraints: &Constraints) -> Self {
+        let mut sizes: Vec<(usize, usize)> = Vec::new();
+        for group in groups.iter().filter(|g| !g.members.is_empty()) {
+            match sizes.iter_mut().find(|(size, _)| *size == group.members.len()) {
+                Some((_, count)) => *count += 1,
+                None => sizes.push((group.members.len(), 1)),
+            }
+        }
+        sizes.sort_by_key(|&(size, _)| std::cmp::Reverse(size));
+
+        let pairs_of = |group: &Group| {
+            let n = group.members.len();
+            n * n.saturating_sub(1) / 2
+        };
+        let repeats = history.map(|history| {
+            let mut pairs = 0;
+            for group in groups {
+                for (i, a) in group.members.iter().enumerate() {
+                    pairs += group.members[i + 1..]
+                        .iter()
+                        .filter(|b| history.pair_count(&a.id, &b.id) > 0)
+                        .count();
+                }
+            }
+            Repeats {
+                sessions: history.sessions(),
+                pairs,
+                meetings: history.repeat_count(groups),
+            }
+        });
+
+        // A constraint counts once however many of its students it separates
+        let single = |together: &[_], apart: &[_]| Constraints {
+            together: together.to_vec(),
+            apart: apart.to_vec(),
+            soft: Vec::new(),
+        };
+        let satisfied = constraints
+            .together
+            .iter()
+            .map(|ids| single(std::slice::from_ref(ids), &[]))
+            .chain(constraints.apart.iter().map(|ids| single(&[], std::slice::from_ref(ids))))
+            .filter(|constraint| constraint.unsatisfied(groups).is_empty())
+            .count();
+
+        Stats {
+            students: groups.iter().map(|g| g.members.len()).sum(),
+            sizes,
+            pairs: groups.iter().map(pairs_of).sum(),
+            repeats,
+            constraints: (satisfied, constraints.together.len() + constraints.apart.len()),
+            soft: (!constraints.soft.is_empty()).then(|| {
+                let broken = constraints.unsatisfied_soft(groups);
+                (broken.len(), broken.iter().map(|(_, weight)| weight).sum())
+            }),
+            fitness: None,
+            edits: None,
+        }
+    }
+
+    /// The report printed after the result, one figure per line.
+    pub fn render(&self, lang: Lang) -> String {
+        let groups: usize = self.sizes.iter().map(|(_, count)| count).sum();
+        let sizes: Vec<String> = self
+            .sizes
+            .iter()
+            .map(|(size, count)| lang.format(Msg::StatsSize, &[size, count]))
+            .collect();
+
+        let mut lines = vec![
+            format!("\n{}", lang.text(Msg::StatsTitle)),
+            lang.format(Msg::StatsCounts, &[&self.students, &groups]),
+            lang.format(Msg::StatsSizes, &[&sizes.join(", ")]),
+        ];
+        lines.push(match self.repeats {
+            Some(repeats) => lang.format(
+                Msg::StatsRepeats,
+                &[&repeats.pairs, &self.pairs, &repeats.meetings, &repeats.sessions],
+            ),
+            None => lang.text(Msg::StatsNoHistory).to_string(),
+        });
+        lines.push(match self.constraints {
+            (_, 0) => lang.text(Msg::StatsNoConstraints).to_string(),
+            (satisfied, total) => lang.format(Msg::StatsConstraints, &[&satisfied, &total]),
+        });
+        if let Some((broken, penalty)) = self.soft {
+            lines.push(lang.format(Msg::SoftPenalty, &[&penalty, &broken]));
+        }
+        if let Some(fitness) = self.fitness {
+            lines.push(lang.format(Msg::StatsFitness, &[&format!("{:.2}", fitness)]));
+        }
+        if let Some(edits) = self.edits {
+            lines.push(lang.format(
+                Msg::StatsEdits,
+                &[&edits.added, &edits.deleted, &edits.moved, &edits.swapped, &edits.completed, &edits.undone],
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(ids: &[&str]) -> Group {
+        Group::from_members(ids.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_stats() {
+        let groups = vec![group(&["S001", "S002", "S003"]), group(&["S004", "S005", "S006"]), group(&["S007", "S008"])];
+        let mut history = History::default();
+        history.record(&[group(&["S001", "S002", "S004"])]);
+        history.record(&[group(&["S001", "S002"])]);
+        let constraints = Constraints::parse("together: S001,S002\napart: S001,S003,S004\napart: S005,S007").unwrap();
+
+        let stats = Stats::new(&groups, Some(&history), &constraints);
+        assert_eq!(stats.students, 8);
+        assert_eq!(stats.sizes, vec![(3, 2), (2, 1)]);
+        assert_eq!(stats.pairs, 7);
+        assert_eq!(
+            stats.repeats,
+            Some(Repeats {
+                sessions: 2,
+                pairs: 1,
+                meetings: 2,
+            })
+        );
+        assert_eq!(stats.constraints, (2, 3));
+        assert!(stats.render(Lang::En).contains("Constraints satisfied: 2 / 3"));
+        assert!(!stats.render(Lang::En).contains("Score"));
+        assert_eq!(stats.soft, None);
+
+        let soft = Constraints::parse("apart: S004,S005 weight=3\ntogether: S001,S008 weight=2").unwrap();
+        let stats = Stats::new(&groups, None, &soft);
+        assert_eq!(stats.constraints, (0, 0));
+        assert_eq!(stats.soft, Some((2, 5)));
+        let stats = Stats {
+            fitness: Some(1.5),
+            edits: Some(Edits {
+                added: 9,
+                deleted: 1,
+                moved: 2,
+                swapped: 1,
+                completed: 3,
+                undone: 1,
+            }),
+            ..stats
+        };
+        assert!(stats.render(Lang::En).contains("Score: 1.50"));
+        assert!(stats.render(Lang::En).ends_with("Edits during entry: 9 added / 1 deleted / 2 moved / 1 swapped / 3 groups completed / 1 undone"));
+    }
+}
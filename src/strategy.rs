@@ -0,0 +1,309 @@
+//! Grouping strategies selectable with `--strategy`.
+//!
+//! A new algorithm implements [`GroupingStrategy`] and is added to
+//! [`STRATEGIES`]; the command line, configuration file and HTTP API then
+//! accept its name.
+
+use crate::balance;
+use crate::constraints;
+use crate::genetic;
+use crate::group::{Group, Student};
+use crate::grouping::{self, GroupingConfig, Mode};
+use crate::history;
+use crate::i18n::{message, Lang, Msg};
+use crate::log;
+use crate::preference;
+use rand::seq::SliceRandom;
+use std::fmt;
+
+/// An algorithm that forms the final groups.
+pub trait GroupingStrategy: fmt::Debug + Sync {
+    /// Name given to `--strategy`.
+    fn name(&self) -> &'static str;
+
+    /// Form the final groups from the groups entered by hand (in batch mode,
+    /// one group holding everyone). Full groups entered by hand should be kept
+    /// in [`Mode::Interactive`], as the other strategies do.
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group>;
+
+    /// Whether the strategy needs the pairing history in `config.history`, so
+    /// that it is loaded even without `--avoid-repeats`.
+    fn uses_history(&self) -> bool {
+        false
+    }
+}
+
+/// Every strategy, in the order they are listed in the usage text.
+pub static STRATEGIES: &[&dyn GroupingStrategy] = &[&Auto, &Random, &Balanced, &Optimized, &MixedSkill, &Similar, &Genetic, &Preferred];
+
+/// The strategy called `name`.
+pub fn by_name(name: &str) -> Option<&'static dyn GroupingStrategy> {
+    STRATEGIES.iter().copied().find(|strategy| strategy.name() == name)
+}
+
+/// Names of every strategy, for messages.
+pub fn names() -> Vec<&'static str> {
+    STRATEGIES.iter().map(|strategy| strategy.name()).collect()
+}
+
+/// The default: picks an algorithm from the configuration. The history is
+/// avoided when given, then constraints take priority over balancing, and
+/// with neither, batch input is split in order. Incomplete groups entered
+/// interactively are shuffled, unless `config.no_shuffle` keeps their order.
+#[derive(Debug, Clone, Copy)]
+pub struct Auto;
+
+impl GroupingStrategy for Auto {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        if let Some(past) = &config.history {
+            log::info(|| message(Msg::TraceAvoidRepeats, &[]));
+            return history::reorganize_avoiding_repeats(groups, config, past);
+        }
+        if config.constraints.has_hard() {
+            log::info(|| message(Msg::TraceConstraints, &[]));
+            return constraints::reorganize_with_constraints(
+                groups,
+                config.mode,
+                config.sizing,
+                &config.constraints,
+                &mut config.rng(),
+            );
+        }
+        let keys = config.balance_keys();
+        if !keys.is_empty() || config.spread_weights.is_some() {
+            log::info(|| message(Msg::TraceBalance, &[]));
+            return Balanced.assign(groups, config);
+        }
+        match config.mode {
+            // Batch mode: regroup everyone in input order
+            Mode::Batch => split_in_order(groups, config.sizing),
+            // Interactive mode: reorganize incomplete groups
+            Mode::Interactive if config.no_shuffle => grouping::reorganize_incomplete_in_order(groups, config.sizing),
+            Mode::Interactive => grouping::reorganize_incomplete_sized(groups, config.sizing, &mut config.rng()),
+        }
+    }
+}
+
+/// Shuffle students into groups, in batch mode too. Constraints are honored;
+/// attributes and the history are not looked at.
+#[derive(Debug, Clone, Copy)]
+pub struct Random;
+
+impl GroupingStrategy for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        let mut rng = config.rng();
+        let groups = match config.mode {
+            Mode::Batch => {
+                let mut members: Vec<Student> = groups.into_iter().flat_map(|g| g.members).collect();
+                members.shuffle(&mut rng);
+                log::info(|| message(Msg::TraceShuffle, &[&members.len()]));
+                vec![Group::from_members(members)]
+            }
+            Mode::Interactive => groups,
+        };
+        if config.constraints.has_hard() {
+            return constraints::reorganize_with_constraints(
+                groups,
+                config.mode,
+                config.sizing,
+                &config.constraints,
+                &mut rng,
+            );
+        }
+        match config.mode {
+            Mode::Batch => split_in_order(groups, config.sizing),
+            Mode::Interactive => grouping::reorganize_incomplete_sized(groups, config.sizing, &mut rng),
+        }
+    }
+}
+
+/// Put everyone in `groups` together and split them in order with `sizing`.
+fn split_in_order(groups: Vec<Group>, sizing: grouping::Sizing) -> Vec<Group> {
+    let members: Vec<Student> = groups.into_iter().flat_map(|g| g.members).collect();
+    let count = members.len();
+    let split = sizing.split(members);
+    log::info(|| message(Msg::TraceSplit, &[&count, &log::sizes(&split)]));
+    for (i, group) in split.iter().enumerate() {
+        log::debug(|| log::describe(i, group));
+    }
+    split
+}
+
+/// Spread the balanced attributes and weights across groups with
+/// [`balance::reorganize_balanced`], even when constraints are given.
+#[derive(Debug, Clone, Copy)]
+pub struct Balanced;
+
+impl GroupingStrategy for Balanced {
+    fn name(&self) -> &'static str {
+        "balanced"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        balance::reorganize_balanced(
+            groups,
+            config.mode,
+            config.sizing,
+            &config.balance_keys(),
+            config.spread_weights.as_deref(),
+            &mut config.rng(),
+        )
+    }
+}
+
+/// Deal one student of each skill tier into every group with
+/// [`balance::reorganize_mixed_skill`], so that no group is made of novices
+/// only. The rating is the numeric attribute `config.skill`; constraints and
+/// the history are not looked at.
+#[derive(Debug, Clone, Copy)]
+pub struct MixedSkill;
+
+impl GroupingStrategy for MixedSkill {
+    fn name(&self) -> &'static str {
+        "mixed-skill"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        log::info(|| {
+            let skill = config.skill.clone().unwrap_or_else(|| Lang::current().text(Msg::TraceSkill).to_string());
+            message(Msg::TraceSkillTiers, &[&skill])
+        });
+        balance::reorganize_mixed_skill(groups, config.mode, config.sizing, config.skill.as_deref(), &mut config.rng())
+    }
+}
+
+/// Put students with similar values of `config.group_by` (e.g. the same
+/// level or topic) together with [`balance::reorganize_similar`]: the
+/// opposite of [`Balanced`]. Constraints and the history are not looked at.
+#[derive(Debug, Clone, Copy)]
+pub struct Similar;
+
+impl GroupingStrategy for Similar {
+    fn name(&self) -> &'static str {
+        "similar"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        log::info(|| message(Msg::TraceSimilar, &[&config.group_by.join(", ")]));
+        balance::reorganize_similar(groups, config.mode, config.sizing, &config.group_by, &mut config.rng())
+    }
+}
+
+/// Minimize repeated pairings: the candidates of the default strategy are
+/// compared against the history and then improved by
+/// [`history::optimize_repeats`], with `config.optimize_iterations` swaps
+/// ([`history::DEFAULT_ITERATIONS`] when not given).
+#[derive(Debug, Clone, Copy)]
+pub struct Optimized;
+
+impl GroupingStrategy for Optimized {
+    fn name(&self) -> &'static str {
+        "optimized"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        let past = config.history.clone().unwrap_or_default();
+        let config = GroupingConfig {
+            strategy: None,
+            optimize_iterations: Some(config.optimize_iterations.unwrap_or(history::DEFAULT_ITERATIONS)),
+            ..config.clone()
+        };
+        history::reorganize_avoiding_repeats(groups, &config, &past)
+    }
+
+    fn uses_history(&self) -> bool {
+        true
+    }
+}
+
+/// Breed groupings with [`genetic::reorganize_genetic`], for large classes
+/// with many constraints and attributes to balance, where swaps alone stall.
+#[derive(Debug, Clone, Copy)]
+pub struct Genetic;
+
+impl GroupingStrategy for Genetic {
+    fn name(&self) -> &'static str {
+        "genetic"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        log::info(|| {
+            let population = config.population.unwrap_or(genetic::DEFAULT_POPULATION);
+            let generations = config.generations.unwrap_or(genetic::DEFAULT_GENERATIONS);
+            message(Msg::TraceGenetic, &[&population, &generations])
+        });
+        genetic::reorganize_genetic(groups, config)
+    }
+}
+
+/// Grant as many of the wishes in `config.preferences` as possible with
+/// [`preference::reorganize_by_preference`]. Constraints and the history are
+/// not looked at.
+#[derive(Debug, Clone, Copy)]
+pub struct Preferred;
+
+impl GroupingStrategy for Preferred {
+    fn name(&self) -> &'static str {
+        "preferences"
+    }
+
+    fn assign(&self, groups: Vec<Group>, config: &GroupingConfig) -> Vec<Group> {
+        log::info(|| message(Msg::TracePreferences, &[]));
+        preference::reorganize_by_preference(groups, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+
+    fn students(n: usize) -> Vec<Student> {
+        (1..=n).map(|i| Student::new(format!("S{:03}", i))).collect()
+    }
+
+    #[test]
+    fn test_by_name() {
+        assert_eq!(names(), vec!["auto", "random", "balanced", "optimized", "mixed-skill", "similar", "genetic", "preferences"]);
+        assert_eq!(by_name("random").map(|s| s.name()), Some("random"));
+        assert!(by_name("annealing").is_none());
+        assert!(by_name("optimized").unwrap().uses_history());
+        assert!(!by_name("auto").unwrap().uses_history());
+    }
+
+    #[test]
+    fn test_random_shuffles_batch_input() {
+        let config = GroupingConfig {
+            seed: Some(4),
+            strategy: by_name("random"),
+            ..Default::default()
+        };
+        let result = grouping::assign(students(9), config.clone()).unwrap();
+        assert_ne!(result, grouping::split_into_small_groups(students(9)));
+        assert_eq!(result.iter().map(|g| g.members.len()).collect::<Vec<_>>(), vec![3, 3, 3]);
+        // The same seed gives the same groups
+        assert_eq!(grouping::assign(students(9), config).unwrap(), result);
+    }
+
+    #[test]
+    fn test_optimized_avoids_history() {
+        let mut past = History::default();
+        past.record(&grouping::split_into_small_groups(students(9)));
+        let config = GroupingConfig {
+            seed: Some(2),
+            strategy: by_name("optimized"),
+            history: Some(past.clone()),
+            ..Default::default()
+        };
+        let result = grouping::assign(students(9), config).unwrap();
+        assert_eq!(past.repeat_count(&result), 0);
+    }
+}
@@ -0,0 +1,174 @@
+//! Students: the members of groups, with their display name and the
+//! attributes (e.g. gender, skill level) used for balancing.
+
+use crate::error::GroupingError;
+use crate::group::StudentId;
+use crate::roster::parse_csv_line;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A student, with the name and attributes given for them in the input.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Student {
+    pub id: StudentId,
+    /// Display name from a roster, `--names` or the database
+    pub name: Option<String>,
+    /// Attribute values keyed by attribute name (e.g. `gender` → `female`)
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Student {
+    /// Create a student without a name or attributes.
+    pub fn new(id: impl Into<StudentId>) -> Self {
+        Student {
+            id: id.into(),
+            name: None,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    /// The student under another ID, such as a pseudonym, without the name
+    /// and attributes that could identify them.
+    pub fn renamed(&self, id: impl Into<StudentId>) -> Self {
+        Student::new(id)
+    }
+
+    /// Fill in the name from `names` when the student has none yet.
+    pub fn fill_name(&mut self, names: &HashMap<StudentId, String>) {
+        if self.name.is_none() {
+            self.name = names.get(&self.id).cloned();
+        }
+    }
+
+    /// Value of the attribute `name`, if given.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    /// Numeric value of the attribute `name`, such as a participation score.
+    /// `None` when it is missing or not a number.
+    pub fn weight(&self, name: &str) -> Option<f64> {
+        self.attribute(name)?.parse().ok().filter(|w: &f64| w.is_finite())
+    }
+
+    /// Parse an input line such as `S001,female,advanced`. The fields after the
+    /// ID are named by `attribute_names` in order. Fails when the ID is empty
+    /// or when there are more fields than names, rather than dropping them.
+    pub fn parse_line(line: &str, attribute_names: &[String]) -> Result<Self, GroupingError> {
+        let fields: Vec<String> = parse_csv_line(line).into_iter().map(|f| f.trim().to_string()).collect();
+        let mut fields = fields.into_iter();
+        let id = fields.next().unwrap_or_default();
+        if id.is_empty() {
+            return Err(GroupingError::MissingId);
+        }
+        let values: Vec<String> = fields.collect();
+        if values.len() > attribute_names.len() {
+            return Err(GroupingError::ExtraFields {
+                id,
+                expected: attribute_names.len(),
+                found: values.len(),
+            });
+        }
+        let mut student = Student::new(id);
+        for (name, value) in attribute_names.iter().zip(values) {
+            if !value.is_empty() {
+                student.attributes.insert(name.clone(), value);
+            }
+        }
+        Ok(student)
+    }
+}
+
+/// The IDs of `students` joined with `sep`, for messages and logs.
+pub fn join_ids(students: &[Student], sep: &str) -> String {
+    students.iter().map(|s| s.id.as_str()).collect::<Vec<_>>().join(sep)
+}
+
+impl From<StudentId> for Student {
+    fn from(id: StudentId) -> Self {
+        Student::new(id)
+    }
+}
+
+impl From<&str> for Student {
+    fn from(id: &str) -> Self {
+        Student::new(id)
+    }
+}
+
+/// Students compare equal to their ID, so that `group.members == ["S001", "S002"]`.
+impl PartialEq<str> for Student {
+    fn eq(&self, id: &str) -> bool {
+        self.id == id
+    }
+}
+
+impl PartialEq<&str> for Student {
+    fn eq(&self, id: &&str) -> bool {
+        self.id == *id
+    }
+}
+
+impl PartialEq<StudentId> for Student {
+    fn eq(&self, id: &StudentId) -> bool {
+        self.id == *id
+    }
+}
+
+/// A student is shown by their ID.
+impl fmt::Display for Student {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let names = vec!["gender".to_string(), "skill".to_string()];
+        let student = Student::parse_line("S001, female ,advanced", &names).unwrap();
+        assert_eq!(student.id, "S001");
+        assert_eq!(student.attribute("gender"), Some("female"));
+        assert_eq!(student.attribute("skill"), Some("advanced"));
+
+        let plain = Student::parse_line("S002", &names).unwrap();
+        assert_eq!(plain, Student::new("S002"));
+
+        let names = vec!["score".to_string()];
+        assert_eq!(Student::parse_line("S003,7.5", &names).unwrap().weight("score"), Some(7.5));
+        assert_eq!(Student::parse_line("S004,high", &names).unwrap().weight("score"), None);
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        let names = vec!["gender".to_string()];
+        assert_eq!(
+            Student::parse_line("S001,female,extra", &names),
+            Err(GroupingError::ExtraFields {
+                id: "S001".to_string(),
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(Student::parse_line(" ,female", &names), Err(GroupingError::MissingId));
+    }
+
+    #[test]
+    fn test_names_and_ids() {
+        let names: HashMap<StudentId, String> = [("S001".to_string(), "山田".to_string())].into_iter().collect();
+        let mut students = vec![Student::new("S001"), Student::new("S002")];
+        for student in &mut students {
+            student.fill_name(&names);
+        }
+        assert_eq!(students[0].name.as_deref(), Some("山田"));
+        assert_eq!(students[1].name, None);
+        // A student is compared and shown by their ID
+        assert_eq!(students[0], "S001");
+        assert_eq!(students[0].to_string(), "S001");
+        assert_eq!(students[0].renamed("P1"), Student::new("P1"));
+        assert_eq!(join_ids(&students, ", "), "S001, S002");
+    }
+}
@@ -0,0 +1,101 @@
+//! Colors for terminal output, so that results stay easy to read when the
+//! screen is projected in class.
+//!
+//! Styling is only turned on for a terminal; files, pipes and the clipboard
+//! always get plain text.
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const HEADING: &str = "\x1b[1;36m";
+const SUCCESS: &str = "\x1b[32m";
+const ERROR: &str = "\x1b[31m";
+const WARNING: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+
+/// Whether to write ANSI colors. The default writes plain text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    pub fn new(enabled: bool) -> Self {
+        Style { enabled }
+    }
+
+    /// Colors for a terminal, unless turned off with `--no-color` or the
+    /// `NO_COLOR` environment variable (https://no-color.org), or the
+    /// terminal cannot show them.
+    pub fn detect(no_color: bool, is_terminal: bool) -> Self {
+        let env_off = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+            || std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        Style::new(!no_color && !env_off && is_terminal)
+    }
+
+    pub fn is_enabled(self) -> bool {
+        self.enabled
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.enabled && !text.is_empty() {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A title such as `=== グループ分け結果 ===`.
+    pub fn title(self, text: &str) -> String {
+        self.paint(BOLD, text)
+    }
+
+    /// A group header such as `グループ A: 3 人`.
+    pub fn heading(self, text: &str) -> String {
+        self.paint(HEADING, text)
+    }
+
+    /// Text of less importance, such as totals and hints.
+    pub fn dim(self, text: &str) -> String {
+        self.paint(DIM, text)
+    }
+
+    /// A message colored by its mark: `✓` in green, `✗` in red and warnings
+    /// (`警告:`, `Warning:`) in yellow. Other messages are left as they are.
+    pub fn message(self, text: &str) -> String {
+        let start = text.trim_start();
+        let code = if start.starts_with('✓') {
+            SUCCESS
+        } else if start.starts_with('✗') {
+            ERROR
+        } else if start.starts_with("警告") || start.starts_with("Warning") {
+            WARNING
+        } else {
+            return text.to_string();
+        };
+        self.paint(code, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_style_leaves_text_alone() {
+        let plain = Style::default();
+        assert_eq!(plain.heading("グループ A: 3 人"), "グループ A: 3 人");
+        assert_eq!(plain.message("  ✗ エラー: S009 は見つかりませんでした"), "  ✗ エラー: S009 は見つかりませんでした");
+        assert_eq!(Style::detect(true, true), plain);
+        assert_eq!(Style::detect(false, false), plain);
+    }
+
+    #[test]
+    fn test_messages_are_colored_by_mark() {
+        let style = Style::new(true);
+        assert_eq!(style.message("  ✓ 移動しました"), "\x1b[32m  ✓ 移動しました\x1b[0m");
+        assert_eq!(style.message("  ✗ エラー"), "\x1b[31m  ✗ エラー\x1b[0m");
+        assert_eq!(style.message("Warning: x"), "\x1b[33mWarning: x\x1b[0m");
+        assert_eq!(style.message("  追加: S001"), "  追加: S001");
+        assert_eq!(style.heading(""), "");
+    }
+}
@@ -0,0 +1,326 @@
+//! Minimal Handlebars-style templates for `--template`.
+//!
+//! Supports `{{name}}` and dotted paths such as `{{group.label}}`,
+//! `{{#each list}}...{{/each}}` with `{{this}}`, `{{@index}}` (from 0),
+//! `{{@number}}` (from 1), `{{@first}}` and `{{@last}}`, `{{#if x}}...{{else}}...{{/if}}`,
+//! `{{#unless x}}...{{/unless}}` and `{{! comments }}`. Values come from a JSON
+//! value; names are looked up in the innermost `each` first and then outwards,
+//! and unknown names render as nothing, as in Handlebars. Block tags alone on
+//! their line take the line with them. Nothing is escaped, since the results
+//! are plain text.
+
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Var(String),
+    Each(String, Vec<Node>),
+    /// `#if`, or `#unless` when `negate`
+    If {
+        path: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// A block being parsed, closed by `{{/name}}`.
+struct Open {
+    name: &'static str,
+    path: String,
+    line: usize,
+    nodes: Vec<Node>,
+    /// The nodes before `{{else}}`, once it was seen
+    then: Option<Vec<Node>>,
+}
+
+impl Template {
+    /// Parse a template. Errors name the line of the tag at fault.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut stack: Vec<Open> = Vec::new();
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut rest = text;
+        let line_of = |rest: &str| text[..text.len() - rest.len()].matches('\n').count() + 1;
+
+        while let Some(start) = rest.find("{{") {
+            let line = line_of(&rest[start..]);
+            let tag_text = &rest[start + 2..];
+            // `{{{name}}}` is accepted like `{{name}}`, since nothing is escaped anyway
+            let (tag, mut after) = match tag_text.strip_prefix('{') {
+                Some(inner) => inner.split_once("}}}"),
+                None => tag_text.split_once("}}"),
+            }
+            .ok_or_else(|| error_at(line, Msg::TemplateUnclosedTag, &[]))?;
+            let tag = tag.trim();
+
+            // As in Handlebars, a block tag alone on its line leaves no blank line behind
+            let mut before = &rest[..start];
+            if tag.starts_with(['#', '/', '!']) || tag == "else" {
+                let indent = before.len() - before.trim_end_matches([' ', '\t']).len();
+                let offset = text.len() - rest.len() + start - indent;
+                let line_start = offset == 0 || text[..offset].ends_with('\n');
+                let line_end = after.find('\n').map_or(after, |end| &after[..end]);
+                if line_start && line_end.trim().is_empty() {
+                    before = &before[..before.len() - indent];
+                    after = after.get(line_end.len() + 1..).unwrap_or_default();
+                }
+            }
+            if !before.is_empty() {
+                current_nodes(&mut stack, &mut nodes).push(Node::Text(before.to_string()));
+            }
+            rest = after;
+
+            if tag.starts_with('!') {
+                continue;
+            }
+            if let Some(block) = tag.strip_prefix('#') {
+                let (name, path) = block.split_once(char::is_whitespace).unwrap_or((block, ""));
+                let name = match name {
+                    "each" => "each",
+                    "if" => "if",
+                    "unless" => "unless",
+                    _ => return Err(error_at(line, Msg::TemplateUnknownBlock, &[&name])),
+                };
+                let path = path.trim();
+                if path.is_empty() {
+                    return Err(error_at(line, Msg::TemplateNoName, &[&format!("{{{{#{}}}}}", name)]));
+                }
+                stack.push(Open {
+                    name,
+                    path: path.to_string(),
+                    line,
+                    nodes: Vec::new(),
+                    then: None,
+                });
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                let open = match stack.pop() {
+                    Some(open) if open.name == name.trim() => open,
+                    Some(open) => {
+                        return Err(error_at(line, Msg::TemplateMismatched, &[&open.line, &open.name, &name.trim()]))
+                    }
+                    None => {
+                        return Err(error_at(line, Msg::TemplateUnopened, &[&name.trim()]))
+                    }
+                };
+                let node = match open.name {
+                    "each" => Node::Each(open.path, open.nodes),
+                    name => {
+                        let (then, otherwise) = match open.then {
+                            Some(then) => (then, open.nodes),
+                            None => (open.nodes, Vec::new()),
+                        };
+                        Node::If {
+                            path: open.path,
+                            negate: name == "unless",
+                            then,
+                            otherwise,
+                        }
+                    }
+                };
+                current_nodes(&mut stack, &mut nodes).push(node);
+                continue;
+            }
+            if tag == "else" {
+                match stack.last_mut() {
+                    Some(open) if open.name != "each" && open.then.is_none() => {
+                        open.then = Some(std::mem::take(&mut open.nodes));
+                    }
+                    _ => return Err(error_at(line, Msg::TemplateElse, &[])),
+                }
+                continue;
+            }
+            if tag.is_empty() {
+                return Err(error_at(line, Msg::TemplateNoName, &[&"{{}}"]));
+            }
+            current_nodes(&mut stack, &mut nodes).push(Node::Var(tag.to_string()));
+        }
+        if let Some(open) = stack.last() {
+            return Err(error_at(open.line, Msg::TemplateUnclosed, &[&open.name]));
+        }
+        if !rest.is_empty() {
+            nodes.push(Node::Text(rest.to_string()));
+        }
+        Ok(Template { nodes })
+    }
+
+    /// Fill in the template with the values of `data`.
+    pub fn render(&self, data: &Value) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut vec![Scope { value: data, position: None }], &mut out);
+        out
+    }
+}
+
+/// An error at `line` of the template.
+fn error_at(line: usize, msg: Msg, args: &[&dyn std::fmt::Display]) -> String {
+    message(Msg::TemplateLine, &[&line, &message(msg, args)])
+}
+
+/// The nodes of the innermost open block, or of the template itself.
+fn current_nodes<'a>(stack: &'a mut [Open], nodes: &'a mut Vec<Node>) -> &'a mut Vec<Node> {
+    stack.last_mut().map_or(nodes, |open| &mut open.nodes)
+}
+
+/// A value being rendered, with its position when it is an element of `#each`.
+struct Scope<'a> {
+    value: &'a Value,
+    /// Index and length of the list
+    position: Option<(usize, usize)>,
+}
+
+fn render_nodes<'a>(nodes: &'a [Node], scopes: &mut Vec<Scope<'a>>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(path) => out.push_str(&to_text(&lookup(scopes, path))),
+            Node::Each(path, body) => {
+                let Lookup::Value(Value::Array(items)) = lookup(scopes, path) else {
+                    continue;
+                };
+                for (i, item) in items.iter().enumerate() {
+                    scopes.push(Scope {
+                        value: item,
+                        position: Some((i, items.len())),
+                    });
+                    render_nodes(body, scopes, out);
+                    scopes.pop();
+                }
+            }
+            Node::If {
+                path,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let branch = if is_truthy(&lookup(scopes, path)) != *negate { then } else { otherwise };
+                render_nodes(branch, scopes, out);
+            }
+        }
+    }
+}
+
+/// What a name in a template stands for.
+enum Lookup<'a> {
+    Value(&'a Value),
+    Number(usize),
+    Flag(bool),
+    Missing,
+}
+
+/// Look up `path` in the innermost scope that has its first part, then follow the rest.
+fn lookup<'a>(scopes: &[Scope<'a>], path: &str) -> Lookup<'a> {
+    let position = scopes.iter().rev().find_map(|scope| scope.position);
+    match (path, position) {
+        ("@index", Some((i, _))) => return Lookup::Number(i),
+        ("@number", Some((i, _))) => return Lookup::Number(i + 1),
+        ("@first", Some((i, _))) => return Lookup::Flag(i == 0),
+        ("@last", Some((i, len))) => return Lookup::Flag(i + 1 == len),
+        _ => {}
+    }
+    let mut parts = path.split('.');
+    let first = parts.next().unwrap_or_default();
+    let found = if first == "this" {
+        scopes.last().map(|scope| scope.value)
+    } else {
+        scopes.iter().rev().find_map(|scope| scope.value.get(first))
+    };
+    let Some(mut value) = found else {
+        return Lookup::Missing;
+    };
+    for part in parts {
+        match value.get(part) {
+            Some(next) => value = next,
+            None => return Lookup::Missing,
+        }
+    }
+    Lookup::Value(value)
+}
+
+fn is_truthy(lookup: &Lookup) -> bool {
+    match lookup {
+        Lookup::Value(Value::Null) | Lookup::Missing => false,
+        Lookup::Value(Value::Bool(b)) | Lookup::Flag(b) => *b,
+        Lookup::Value(Value::Number(n)) => n.parse::<f64>() != Ok(0.0),
+        Lookup::Value(Value::String(s)) => !s.is_empty(),
+        Lookup::Value(Value::Array(items)) => !items.is_empty(),
+        Lookup::Value(Value::Object(_)) => true,
+        Lookup::Number(n) => *n != 0,
+    }
+}
+
+fn to_text(lookup: &Lookup) -> String {
+    match lookup {
+        Lookup::Value(value) => value_text(value),
+        Lookup::Number(n) => n.to_string(),
+        Lookup::Flag(b) => b.to_string(),
+        Lookup::Missing => String::new(),
+    }
+}
+
+/// A value as text: lists are joined with `, `, and objects and nulls are left out.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null | Value::Object(_) => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.clone(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Value {
+        Value::parse(
+            r#"{"title": "3組", "groups": [
+                {"label": "A", "members": [{"id": "S001", "name": "山田"}, {"id": "S002", "name": null}]},
+                {"label": "B", "members": [{"id": "S003", "name": "佐藤"}]}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let template = Template::parse(
+            "{{! header }}{{title}}\n{{#each groups}}{{@number}}. {{label}}: \
+             {{#each members}}{{id}}{{#if name}}({{name}}){{/if}}{{#unless @last}}, {{/unless}}{{/each}}\n{{/each}}",
+        )
+        .unwrap();
+        assert_eq!(template.render(&data()), "3組\n1. A: S001(山田), S002\n2. B: S003(佐藤)\n");
+
+        // Outer names are visible inside blocks; unknown ones are left out
+        let template = Template::parse("{{#each groups}}{{title}}-{{label}}{{missing}} {{/each}}").unwrap();
+        assert_eq!(template.render(&data()), "3組-A 3組-B ");
+        let template = Template::parse("{{#if nobody}}yes{{else}}no{{/if}} {{{title}}}").unwrap();
+        assert_eq!(template.render(&data()), "no 3組");
+    }
+
+    #[test]
+    fn test_standalone_block_lines() {
+        let template = Template::parse("{{title}}\n{{#each groups}}\n  {{#if label}}\n- {{label}}\n  {{/if}}\n{{/each}}\nend").unwrap();
+        assert_eq!(template.render(&data()), "3組\n- A\n- B\nend");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Template::parse("{{#each groups}}").unwrap_err().contains("1行目"));
+        assert!(Template::parse("\n{{/each}}").unwrap_err().contains("2行目"));
+        assert!(Template::parse("{{#each groups}}{{/if}}").is_err());
+        assert!(Template::parse("{{#with groups}}{{/with}}").is_err());
+        assert!(Template::parse("{{title").is_err());
+        assert!(Template::parse("{{else}}").is_err());
+    }
+}
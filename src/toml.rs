@@ -0,0 +1,306 @@
+//! Minimal TOML reader for the configuration file.
+//!
+//! Supports `key = value` pairs, `[table]` headers, comments, basic and
+//! literal strings, integers, floats, booleans and arrays (which may span
+//! lines). Dates, inline tables and arrays of tables are not supported.
+//! Values are returned as JSON values, with each table as an object.
+
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+use std::fmt;
+
+/// Parse a TOML document into an object of its top-level keys.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut root: Vec<(String, Value)> = Vec::new();
+    // Table the following keys belong to, `None` for the top level
+    let mut table: Option<String> = None;
+
+    loop {
+        parser.skip_blank_lines();
+        let Some(c) = parser.peek() else {
+            break;
+        };
+        if c == '[' {
+            parser.pos += 1;
+            parser.skip_spaces();
+            let name = parser.key()?;
+            parser.skip_spaces();
+            parser.expect(']')?;
+            parser.end_of_line()?;
+            if root.iter().any(|(k, _)| *k == name) {
+                return Err(parser.error(Msg::TomlDuplicateTable, &[&name]));
+            }
+            root.push((name.clone(), Value::Object(Vec::new())));
+            table = Some(name);
+            continue;
+        }
+
+        let key = parser.key()?;
+        parser.skip_spaces();
+        parser.expect('=')?;
+        parser.skip_spaces();
+        let value = parser.value()?;
+        parser.end_of_line()?;
+
+        let pairs = match &table {
+            None => &mut root,
+            Some(name) => match root.iter_mut().find(|(k, _)| k == name) {
+                Some((_, Value::Object(pairs))) => pairs,
+                _ => unreachable!("tables are added when their header is read"),
+            },
+        };
+        if pairs.iter().any(|(k, _)| *k == key) {
+            return Err(parser.error(Msg::TomlDuplicateKey, &[&key]));
+        }
+        pairs.push((key, value));
+    }
+    Ok(Value::Object(root))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, msg: Msg, args: &[&dyn fmt::Display]) -> String {
+        message(Msg::AtLine, &[&self.line, &message(msg, args)])
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(Msg::TomlExpected, &[&expected, &c])),
+            None => Err(self.error(Msg::TomlExpectedEnd, &[&expected])),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while self.peek().is_some_and(|c| c != '\n') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skip whitespace, comments and line breaks.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => {
+                    self.pos += 1;
+                    self.line += 1;
+                }
+                Some('\r') => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// Only a comment may follow a value or a table header on its line.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n' | '\r') => Ok(()),
+            Some(c) => Err(self.error(Msg::TomlTrailing, &[&c])),
+        }
+    }
+
+    /// A bare key (`group_names`) or a quoted one (`"group names"`).
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(self.error(Msg::TomlNoKey, &[]));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('t' | 'f') => {
+                let word: String = self.chars[self.pos..].iter().take_while(|c| c.is_ascii_alphabetic()).collect();
+                self.pos += word.len();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(self.error(Msg::TomlBadValue, &[&word])),
+                }
+            }
+            Some(c) if c == '+' || c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(Msg::TomlBadValue, &[&format!("'{}'", c)])),
+            None => Err(self.error(Msg::TomlNoValue, &[])),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | '_' | 'e' | 'E'))
+        {
+            self.pos += 1;
+        }
+        let literal: String = self.chars[start..self.pos].iter().filter(|&&c| c != '_').collect();
+        let literal = literal.strip_prefix('+').unwrap_or(&literal).to_string();
+        if literal.parse::<f64>().is_err() {
+            return Err(self.error(Msg::TomlBadNumber, &[&literal]));
+        }
+        Ok(Value::Number(literal))
+    }
+
+    /// `"..."` with backslash escapes.
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some(c @ ('u' | 'U')) => {
+                            let len = if c == 'u' { 4 } else { 8 };
+                            let hex: String = self.chars.iter().skip(self.pos + 1).take(len).collect();
+                            self.pos += len;
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .filter(|_| hex.len() == len)
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error(Msg::TomlBadEscape, &[&format!("\\{}{}", c, hex)]))?
+                        }
+                        Some(c) => return Err(self.error(Msg::TomlBadEscape, &[&format!("\\{}", c)])),
+                        None => return Err(self.error(Msg::TomlUnclosedString, &[])),
+                    };
+                    self.pos += 1;
+                    out.push(escaped);
+                }
+                Some('\n') | None => return Err(self.error(Msg::TomlUnclosedString, &[])),
+                Some(c) => {
+                    self.pos += 1;
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    /// `'...'` taken as is, which suits regular expressions and Windows paths.
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != '\'' && c != '\n') {
+            self.pos += 1;
+        }
+        let text = self.chars[start..self.pos].iter().collect();
+        self.expect('\'')?;
+        Ok(text)
+    }
+
+    /// `[a, b, ...]`, possibly spread over several lines with comments.
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err(self.error(Msg::TomlArrayComma, &[])),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        let text = r#"
+# Defaults for my class
+format = "csv"   # comment after a value
+seed = 42
+id_pattern = '^B\d{5}$'
+avoid_repeats = true
+balance = [
+  "gender",  # one per line
+  "skill",
+]
+
+[slack]
+"webhook url" = "https://example.com/é"
+"#;
+        let value = parse(text).unwrap();
+        assert_eq!(value.get("format").and_then(Value::as_str), Some("csv"));
+        assert_eq!(value.get("seed").and_then(Value::as_u64), Some(42));
+        assert_eq!(value.get("id_pattern").and_then(Value::as_str), Some(r"^B\d{5}$"));
+        assert_eq!(value.get("avoid_repeats").and_then(Value::as_bool), Some(true));
+        let balance = value.get("balance").and_then(Value::as_array).unwrap();
+        assert_eq!(balance, &[Value::from("gender"), Value::from("skill")]);
+        let slack = value.get("slack").unwrap();
+        assert_eq!(slack.get("webhook url").and_then(Value::as_str), Some("https://example.com/é"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, line) in [
+            ("format = \"csv", 1),
+            ("seed = 4 2", 1),
+            ("\n\nseed = 1\nseed = 2", 4),
+            ("balance = [\"a\" \"b\"]", 1),
+            ("= 1", 1),
+            ("on = yes", 1),
+        ] {
+            let message = parse(text).unwrap_err();
+            assert!(message.starts_with(&format!("{} 行目", line)), "{}: {}", text, message);
+        }
+    }
+}
@@ -0,0 +1,881 @@
+//! Full-screen terminal UI for entering students interactively.
+//!
+//! The screen shows the groups as they are being built. Student IDs are typed
+//! into the input line; the group list can be focused with Tab to delete
+//! students or move them between groups with the arrow keys. `swap:ID:ID` and
+//! `add:ID:GROUP` typed into the input line exchange two students and add one
+//! to an earlier group with room. The terminal is driven directly with
+//! termios and ANSI escape sequences.
+
+use crate::audit::{AuditLog, Event};
+use crate::cli;
+use crate::session::Session;
+use grouping_tool::i18n::{Lang, Msg};
+use grouping_tool::pattern::Pattern;
+use grouping_tool::stats::Edits;
+use grouping_tool::student::Student;
+use grouping_tool::{Group, GroupNames, StudentId};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// A key press decoded from terminal input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    CtrlC,
+    CtrlD,
+    CtrlU,
+}
+
+/// Which part of the screen receives key presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Input,
+    List,
+}
+
+/// Everything shown on the screen.
+struct State<'a> {
+    /// Groups entered so far; students are always added to the last one
+    groups: Vec<Group>,
+    input: String,
+    focus: Focus,
+    /// Index of the selected student, counting across all groups
+    selected: usize,
+    message: String,
+    attribute_names: &'a [String],
+    /// Pattern typed IDs must match, if any
+    id_pattern: Option<&'a Pattern>,
+    labels: &'a GroupNames,
+    lang: Lang,
+    /// Display names shown after the IDs
+    names: &'a HashMap<StudentId, String>,
+    /// Number of students at which a group is complete; `None` when students
+    /// are split into groups only after entry
+    group_size: Option<usize>,
+    students: HashMap<StudentId, Student>,
+    /// Groups before each change, most recent last, for `undo`
+    undo_log: Vec<Vec<Group>>,
+    /// Edits not yet written to the session log
+    events: Vec<Event>,
+}
+
+impl<'a> State<'a> {
+    fn new(
+        attribute_names: &'a [String],
+        id_pattern: Option<&'a Pattern>,
+        labels: &'a GroupNames,
+        lang: Lang,
+        initial: Session,
+        group_size: Option<usize>,
+        names: &'a HashMap<StudentId, String>,
+    ) -> Self {
+        let mut groups = initial.groups;
+        groups.push(initial.current);
+        State {
+            groups,
+            input: String::new(),
+            focus: Focus::Input,
+            selected: 0,
+            message: String::new(),
+            attribute_names,
+            id_pattern,
+            labels,
+            lang,
+            names,
+            group_size,
+            students: initial.students,
+            undo_log: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Positions `(group, member)` of all students, in display order.
+    fn positions(&self) -> Vec<(usize, usize)> {
+        self.groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, group)| (0..group.members.len()).map(move |m| (g, m)))
+            .collect()
+    }
+
+    /// Handle one key press. Returns `false` when input is finished.
+    fn handle(&mut self, key: Key) -> bool {
+        match key {
+            Key::CtrlC | Key::CtrlD => return false,
+            Key::Tab => {
+                self.focus = match self.focus {
+                    Focus::Input if !self.positions().is_empty() => Focus::List,
+                    _ => Focus::Input,
+                };
+                self.clamp_selection();
+                return true;
+            }
+            _ => {}
+        }
+
+        match self.focus {
+            Focus::Input => match key {
+                Key::Char(c) => self.input.push(c),
+                Key::Backspace => {
+                    self.input.pop();
+                }
+                Key::CtrlU => self.input.clear(),
+                Key::Enter => self.submit(),
+                _ => {}
+            },
+            Focus::List => match key {
+                Key::Up => self.selected = self.selected.saturating_sub(1),
+                Key::Down => {
+                    self.selected += 1;
+                    self.clamp_selection();
+                }
+                Key::Left => self.move_selected(false),
+                Key::Right => self.move_selected(true),
+                Key::Char('d') | Key::Delete | Key::Backspace => self.delete_selected(),
+                Key::Char('u') => self.undo(),
+                Key::Char('q') => return false,
+                Key::Esc | Key::Char('i') => self.focus = Focus::Input,
+                _ => {}
+            },
+        }
+        true
+    }
+
+    /// Add the typed student, or close the current group when the line is empty.
+    /// `add:<ID>:<GROUP>` adds to an earlier group with room instead.
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        let current = self.groups.len() - 1;
+
+        if line.is_empty() {
+            if self.groups[current].members.is_empty() {
+                self.message = self.lang.text(Msg::TuiEnterId).to_string();
+            } else {
+                self.message = self.lang.format(
+                    Msg::TuiGroupSaved,
+                    &[&self.labels.label(current), &self.groups[current].members.len()],
+                );
+                self.events.push(Event::Complete {
+                    group: current,
+                    size: self.groups[current].members.len(),
+                });
+                self.undo_log.push(self.groups.clone());
+                self.groups.push(Group::new());
+            }
+            return;
+        }
+        if line.eq_ignore_ascii_case("undo") {
+            self.undo();
+            return;
+        }
+        if line.to_lowercase().starts_with("swap:") {
+            self.swap(&line[5..]);
+            return;
+        }
+
+        // `add:S001:B` adds to group B instead of the group being entered
+        let typed = line;
+        let (line, target) = if line.to_lowercase().starts_with("add:") {
+            let Some((id, label)) = line[4..].rsplit_once(':').filter(|(id, _)| !id.trim().is_empty()) else {
+                self.message = self.lang.text(Msg::TuiAddUsage).to_string();
+                return;
+            };
+            let Some(target) = self.labels.index_of(label).filter(|&g| g <= current) else {
+                self.message = self.lang.format(Msg::TuiNoSuchGroup, &[&label.trim()]);
+                return;
+            };
+            if self.is_full(&self.groups[target]) {
+                self.message = self.lang.format(Msg::TuiGroupFull, &[&self.labels.label(target)]);
+                return;
+            }
+            (id.trim(), target)
+        } else {
+            (line, current)
+        };
+
+        // Split off attributes such as `S001,female,advanced`
+        let student = if self.attribute_names.is_empty() {
+            None
+        } else {
+            match Student::parse_line(line, self.attribute_names) {
+                Ok(student) => Some(student),
+                Err(e) => {
+                    // Keep the line so it can be corrected
+                    self.message = e.to_string();
+                    self.input = typed.to_string();
+                    return;
+                }
+            }
+        };
+        let id = student.as_ref().map_or_else(|| line.to_string(), |s| s.id.clone());
+
+        // Keep a mistyped ID in the input line so it can be corrected
+        if let Some(pattern) = self.id_pattern.filter(|p| !p.is_match(&id)) {
+            self.message = self.lang.format(Msg::TuiInvalidId, &[&id, pattern]);
+            self.input = typed.to_string();
+            return;
+        }
+        if let Some(g) = self.groups.iter().position(|group| group.contains(&id)) {
+            self.message = self.lang.format(Msg::TuiDuplicate, &[&id, &self.labels.label(g)]);
+            return;
+        }
+        if let Some(student) = &student {
+            self.students.insert(id.clone(), student.clone());
+        }
+        let student = student.unwrap_or_else(|| Student::new(id.clone()));
+
+        self.undo_log.push(self.groups.clone());
+        self.events.push(Event::Add {
+            student: student.clone(),
+            group: target,
+        });
+        self.groups[target].members.push(student);
+        if target != current {
+            self.message = self.lang.format(Msg::TuiAddedTo, &[&self.display(&id), &self.labels.label(target)]);
+            return;
+        }
+        self.message = self.lang.format(Msg::TuiAdded, &[&self.display(&id)]);
+        if self.is_full(&self.groups[current]) {
+            let size = self.groups[current].members.len();
+            self.events.push(Event::Complete { group: current, size });
+            self.message = self.lang.format(Msg::TuiGroupCompleted, &[&self.labels.label(current), &size]);
+            self.groups.push(Group::new());
+        }
+    }
+
+    /// Exchange the two students of `swap:<ID>:<ID>`, given as `<ID>:<ID>`.
+    /// Neither group changes size, so none is completed by it.
+    fn swap(&mut self, args: &str) {
+        let Some((first, second)) = args.split_once(':').filter(|(a, b)| !a.trim().is_empty() && !b.trim().is_empty())
+        else {
+            self.message = self.lang.text(Msg::TuiSwapUsage).to_string();
+            return;
+        };
+        let (first, second) = (first.trim(), second.trim());
+        let find = |id: &str| {
+            self.groups
+                .iter()
+                .enumerate()
+                .find_map(|(g, group)| group.members.iter().position(|member| member == id).map(|m| (g, m)))
+        };
+        let (Some(a), Some(b)) = (find(first), find(second)) else {
+            let missing = if find(first).is_none() { first } else { second };
+            self.message = self.lang.format(Msg::TuiNotFound, &[&missing]);
+            return;
+        };
+        if a.0 == b.0 {
+            self.message = self.lang.format(Msg::TuiSameGroupSwap, &[&first, &second, &self.labels.label(a.0)]);
+            return;
+        }
+        self.undo_log.push(self.groups.clone());
+        let student = self.groups[a.0].members[a.1].clone();
+        let other = std::mem::replace(&mut self.groups[b.0].members[b.1], student);
+        self.groups[a.0].members[a.1] = other;
+        self.events.push(Event::Swap {
+            id: first.to_string(),
+            group: a.0,
+            with: second.to_string(),
+            with_group: b.0,
+        });
+        self.message = self.lang.format(
+            Msg::TuiSwapped,
+            &[&first, &second, &self.labels.label(a.0), &self.labels.label(b.0)],
+        );
+    }
+
+    fn delete_selected(&mut self) {
+        let Some(&(g, m)) = self.positions().get(self.selected) else {
+            return;
+        };
+        self.undo_log.push(self.groups.clone());
+        let id = self.groups[g].members.remove(m);
+        self.events.push(Event::Delete { id: id.id.clone(), group: g });
+        self.message = self.lang.format(Msg::TuiDeleted, &[&id, &self.labels.label(g)]);
+        self.remove_empty_groups();
+        self.clamp_selection();
+    }
+
+    /// Move the selected student to the previous or next group. Moving past
+    /// the last group starts a new one.
+    fn move_selected(&mut self, forward: bool) {
+        let Some(&(g, m)) = self.positions().get(self.selected) else {
+            return;
+        };
+        let target = match (forward, g) {
+            (false, 0) => return,
+            (false, g) => g - 1,
+            (true, g) => g + 1,
+        };
+        if self.groups.get(target).is_some_and(|group| self.is_full(group)) {
+            self.message = self.lang.format(Msg::TuiGroupFull, &[&self.labels.label(target)]);
+            return;
+        }
+        self.undo_log.push(self.groups.clone());
+        if target == self.groups.len() {
+            self.groups.push(Group::new());
+        }
+
+        let id = self.groups[g].members.remove(m);
+        self.groups[target].members.push(id.clone());
+        self.events.push(Event::Move { id: id.id.clone(), from: g, to: target });
+        // Filling the group being entered completes it
+        if target + 1 == self.groups.len() && self.is_full(&self.groups[target]) {
+            let size = self.groups[target].members.len();
+            self.events.push(Event::Complete { group: target, size });
+        }
+        self.message = self.lang.format(Msg::TuiMoved, &[&id, &self.labels.label(target)]);
+        self.remove_empty_groups();
+        // Keep the moved student selected; it is the last member of its new group
+        let positions = self.positions();
+        if let Some(position) = positions
+            .iter()
+            .position(|&(pg, pm)| pm + 1 == self.groups[pg].members.len() && self.groups[pg].members[pm] == id)
+        {
+            self.selected = position;
+        }
+    }
+
+    /// Restore the groups as they were before the last add, delete, move, swap
+    /// or group completion.
+    fn undo(&mut self) {
+        match self.undo_log.pop() {
+            Some(groups) => {
+                self.groups = groups;
+                self.events.push(Event::Undo);
+                self.message = self.lang.text(Msg::TuiUndone).to_string();
+                self.clamp_selection();
+            }
+            None => self.message = self.lang.text(Msg::TuiNothingToUndo).to_string(),
+        }
+    }
+
+    /// Drop groups emptied by deleting or moving, keeping one group at the
+    /// end for new input.
+    fn remove_empty_groups(&mut self) {
+        let last = self.groups.len() - 1;
+        let mut index = 0;
+        self.groups.retain(|group| {
+            let keep = index == last || !group.members.is_empty();
+            index += 1;
+            keep
+        });
+        if self.groups.last().map_or(true, |group| self.is_full(group)) {
+            self.groups.push(Group::new());
+        }
+    }
+
+    /// `id` followed by the student's name, if known.
+    fn display(&self, id: &str) -> String {
+        match self.names.get(id) {
+            Some(name) => format!("{} {}", id, name),
+            None => id.to_string(),
+        }
+    }
+
+    fn is_full(&self, group: &Group) -> bool {
+        self.group_size.is_some_and(|size| group.members.len() >= size)
+    }
+
+    fn clamp_selection(&mut self) {
+        let count = self.positions().len();
+        if count == 0 {
+            self.selected = 0;
+            self.focus = Focus::Input;
+        } else if self.selected >= count {
+            self.selected = count - 1;
+        }
+    }
+
+    /// Record the edits made since the last call in `audit` (`--session-log`).
+    fn write_events(&mut self, audit: &mut AuditLog) {
+        for event in std::mem::take(&mut self.events) {
+            if let Err(e) = audit.record(&event) {
+                self.message = self.lang.format(Msg::SessionLogFailed, &[&e]);
+            }
+        }
+    }
+
+    /// The entry so far, for `--save-session`.
+    fn session(&self) -> Session {
+        let (current, groups) = self.groups.split_last().expect("there is always a current group");
+        Session {
+            groups: groups.to_vec(),
+            current: current.clone(),
+            students: self.students.clone(),
+        }
+    }
+
+    /// The groups to hand over to the grouping, without empty ones.
+    fn into_input(self) -> (Vec<Group>, HashMap<StudentId, Student>) {
+        let groups = self.groups.into_iter().filter(|g| !g.members.is_empty()).collect();
+        (groups, self.students)
+    }
+
+    /// Draw the whole screen.
+    fn render(&self) -> String {
+        let mut out = String::from("\x1b[H\x1b[2J");
+        out.push_str(&format!(
+            "\x1b[1m{}\x1b[0m {}\r\n\r\n",
+            self.lang.text(Msg::TuiTitle),
+            match self.group_size {
+                Some(size) => self.lang.format(Msg::TuiTitleNote, &[&size]),
+                None => self.lang.text(Msg::TuiTitleNoteLater).to_string(),
+            }
+        ));
+
+        let positions = self.positions();
+        let selected = match self.focus {
+            Focus::List => positions.get(self.selected).copied(),
+            Focus::Input => None,
+        };
+        let current = self.groups.len() - 1;
+        for (g, group) in self.groups.iter().enumerate() {
+            out.push_str(&format!(" {}:", self.lang.format(Msg::GroupHeading, &[&self.labels.label(g)])));
+            for (m, member) in group.members.iter().enumerate() {
+                if selected == Some((g, m)) {
+                    out.push_str(&format!(" \x1b[7m{}\x1b[0m", self.display(&member.id)));
+                } else {
+                    out.push_str(&format!(" {}", self.display(&member.id)));
+                }
+            }
+            if g == current && self.focus == Focus::Input {
+                out.push_str(&format!(" \x1b[2m{}\x1b[0m", self.lang.text(Msg::TuiEntering)));
+            }
+            out.push_str("\r\n");
+        }
+
+        out.push_str(&format!("\r\n{}\r\n", self.message));
+        let help = match self.focus {
+            Focus::Input => Msg::TuiInputHelp,
+            Focus::List => Msg::TuiListHelp,
+        };
+        out.push_str(&format!("{}\r\n", self.lang.text(help)));
+        out.push_str(&format!("\r\n{}{}", self.lang.text(Msg::TuiPrompt), self.input));
+        if self.focus == Focus::Input {
+            out.push_str("\x1b[?25h");
+        } else {
+            out.push_str("\x1b[?25l");
+        }
+        out
+    }
+}
+
+/// Decode raw terminal input into key presses.
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        let key = match b {
+            0x1b => match (bytes.get(i), bytes.get(i + 1)) {
+                (Some(b'[') | Some(b'O'), Some(&c)) => {
+                    i += 2;
+                    match c {
+                        b'A' => Key::Up,
+                        b'B' => Key::Down,
+                        b'C' => Key::Right,
+                        b'D' => Key::Left,
+                        b'3' if bytes.get(i) == Some(&b'~') => {
+                            i += 1;
+                            Key::Delete
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => Key::Esc,
+            },
+            b'\r' | b'\n' => Key::Enter,
+            0x7f | 0x08 => Key::Backspace,
+            b'\t' => Key::Tab,
+            0x03 => Key::CtrlC,
+            0x04 => Key::CtrlD,
+            0x15 => Key::CtrlU,
+            0x00..=0x1f => continue,
+            _ => {
+                // A UTF-8 character: find its length from the lead byte
+                let len = match b {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => 1,
+                };
+                let end = (i - 1 + len).min(bytes.len());
+                let c = std::str::from_utf8(&bytes[i - 1..end]).ok().and_then(|s| s.chars().next());
+                i = end;
+                match c {
+                    Some(c) => Key::Char(c),
+                    None => continue,
+                }
+            }
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// Puts the terminal into raw mode and restores it when dropped.
+pub struct RawMode {
+    fd: i32,
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: i32) -> io::Result<Self> {
+        // Ctrl+C arrives as a key instead of a signal while the screen is shown
+        Self::set(fd, libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN, libc::IXON | libc::ICRNL)
+    }
+
+    /// Key presses one at a time without echo, for editing a line. Ctrl+C is
+    /// still a signal and Enter still reads as a newline.
+    pub fn keys(fd: i32) -> io::Result<Self> {
+        Self::set(fd, libc::ICANON | libc::ECHO | libc::IEXTEN, 0)
+    }
+
+    fn set(fd: i32, lflag_off: libc::tcflag_t, iflag_off: libc::tcflag_t) -> io::Result<Self> {
+        let mut original = std::mem::MaybeUninit::<libc::termios>::uninit();
+        if unsafe { libc::tcgetattr(fd, original.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = unsafe { original.assume_init() };
+
+        let mut raw = original;
+        raw.c_lflag &= !lflag_off;
+        raw.c_iflag &= !iflag_off;
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Wait until `fd` has input to read. Returns `false` when nothing arrived
+/// within `timeout` (`--timeout`); without one, it returns at once.
+pub fn wait_for_input(fd: i32, timeout: Option<Duration>) -> io::Result<bool> {
+    let Some(timeout) = timeout else {
+        return Ok(true);
+    };
+    let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+    let mut poll = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut poll, 1, millis) } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Run the full-screen input on the controlling terminal and return the
+/// entered groups along with any attributes given after the IDs. Entry
+/// continues from `initial`, whose groups can be edited as well, and a group
+/// is complete at the size of `options.sizing()`, if any. After each change the
+/// entry is saved to `--save-session`, if given. Students are shown with their
+/// `names`, if known. With `--timeout`, the entry finishes when no key is
+/// pressed for that long. Edits are appended to `--session-log`, if given,
+/// and returned counted.
+pub fn run(
+    options: &cli::Options,
+    id_pattern: Option<&Pattern>,
+    labels: &GroupNames,
+    lang: Lang,
+    initial: Session,
+    names: &HashMap<StudentId, String>,
+) -> io::Result<(Vec<Group>, HashMap<StudentId, Student>, Edits)> {
+    let mut tty: File = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let raw_mode = RawMode::enable(tty.as_raw_fd())?;
+    // Switch to the alternate screen so the shell's scrollback is left intact
+    write!(tty, "\x1b[?1049h")?;
+
+    let attribute_names = options.attribute_names();
+    let group_size = options.sizing().max_size();
+    let mut state = State::new(&attribute_names, id_pattern, labels, lang, initial, group_size, names);
+    let mut saved = state.session();
+    let mut audit = AuditLog::new(options.session_log.as_deref());
+    state.events.push(Event::Start {
+        limit: group_size,
+        screen: true,
+        groups: state.groups.len() - 1,
+        students: state.groups.iter().map(|g| g.members.len()).sum(),
+    });
+    state.write_events(&mut audit);
+    let timeout = options.idle_timeout();
+    let mut timed_out = false;
+
+    let result = (|| -> io::Result<()> {
+        let mut buf = [0u8; 64];
+        loop {
+            tty.write_all(state.render().as_bytes())?;
+            tty.flush()?;
+            if !wait_for_input(tty.as_raw_fd(), timeout)? {
+                timed_out = true;
+                return Ok(());
+            }
+            let n = tty.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            let mut finished = false;
+            for key in parse_keys(&buf[..n]) {
+                if !state.handle(key) {
+                    finished = true;
+                    break;
+                }
+            }
+            state.write_events(&mut audit);
+            if let Some(path) = options.session_file() {
+                let session = state.session();
+                if session != saved {
+                    if let Err(e) = session.save(path) {
+                        state.message = lang.format(Msg::SessionSaveFailed, &[&e]);
+                    }
+                    saved = session;
+                }
+            }
+            if finished {
+                return Ok(());
+            }
+        }
+    })();
+
+    write!(tty, "\x1b[?25h\x1b[?1049l")?;
+    tty.flush()?;
+    drop(raw_mode);
+    result?;
+    if let (true, Some(seconds), false) = (timed_out, options.timeout, options.quiet) {
+        writeln!(tty, "{}", lang.format(Msg::TimedOut, &[&seconds]))?;
+    }
+    let (groups, students) = state.into_input();
+    Ok((groups, students, audit.edits()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_line(state: &mut State, line: &str) {
+        for c in line.chars() {
+            state.handle(Key::Char(c));
+        }
+        state.handle(Key::Enter);
+    }
+
+    fn members<'a>(state: &'a State) -> Vec<Vec<&'a str>> {
+        state
+            .groups
+            .iter()
+            .map(|g| g.ids().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"a\x1b[A\x1b[3~\r\x7f\t\x04\x15"),
+            vec![Key::Char('a'), Key::Up, Key::Delete, Key::Enter, Key::Backspace, Key::Tab, Key::CtrlD, Key::CtrlU]
+        );
+        assert_eq!(parse_keys("学".as_bytes()), vec![Key::Char('学')]);
+        assert_eq!(parse_keys(b"\x1b"), vec![Key::Esc]);
+    }
+
+    #[test]
+    fn test_typing_fills_groups_of_three() {
+        let labels = GroupNames::default();
+        let names = HashMap::from([("S005".to_string(), "山田太郎".to_string())]);
+        let mut state = State::new(&[], None, &labels, Lang::Ja, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S004"] {
+            type_line(&mut state, id);
+        }
+        // An empty line closes the current group
+        state.handle(Key::Enter);
+        type_line(&mut state, "S005");
+        assert_eq!(state.message, "追加: S005 山田太郎");
+
+        assert_eq!(members(&state), vec![vec!["S001", "S002", "S003"], vec!["S004"], vec!["S005"]]);
+        assert!(!state.handle(Key::CtrlD));
+    }
+
+    #[test]
+    fn test_rejects_ids_not_matching_pattern() {
+        let pattern = Pattern::new(r"^[A-Z]\d{3,}$").unwrap();
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], Some(&pattern), &labels, Lang::Ja, Session::default(), Some(3), &names);
+        type_line(&mut state, "S0O1");
+        assert_eq!(members(&state), vec![Vec::<&str>::new()]);
+        assert_eq!(state.input, "S0O1");
+
+        // Fix the typo and submit again
+        state.handle(Key::Backspace);
+        state.handle(Key::Backspace);
+        type_line(&mut state, "01");
+        assert_eq!(members(&state), vec![vec!["S001"]]);
+    }
+
+    #[test]
+    fn test_rejects_duplicate_ids() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::Ja, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S001"] {
+            type_line(&mut state, id);
+        }
+        assert_eq!(members(&state), vec![vec!["S001", "S002", "S003"], vec![]]);
+        assert!(state.message.contains("グループ A"));
+    }
+
+    #[test]
+    fn test_undo() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::Ja, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S004"] {
+            type_line(&mut state, id);
+        }
+        type_line(&mut state, "undo");
+        type_line(&mut state, "undo");
+        assert_eq!(members(&state), vec![vec!["S001", "S002"]]);
+
+        state.handle(Key::Tab);
+        state.handle(Key::Char('d'));
+        assert_eq!(members(&state), vec![vec!["S002"]]);
+        state.handle(Key::Char('u'));
+        assert_eq!(members(&state), vec![vec!["S001", "S002"]]);
+    }
+
+    #[test]
+    fn test_delete_and_move_in_list() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::Ja, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S004"] {
+            type_line(&mut state, id);
+        }
+        state.handle(Key::Tab);
+        assert_eq!(state.focus, Focus::List);
+
+        // Move S002 into the second group, then delete S001
+        state.handle(Key::Down);
+        state.handle(Key::Right);
+        assert_eq!(members(&state), vec![vec!["S001", "S003"], vec!["S004", "S002"]]);
+        state.handle(Key::Up);
+        state.handle(Key::Up);
+        state.handle(Key::Up);
+        state.handle(Key::Char('d'));
+        assert_eq!(members(&state), vec![vec!["S003"], vec!["S004", "S002"]]);
+
+        let (groups, _) = state.into_input();
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    fn test_swap_between_groups() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::En, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S004"] {
+            type_line(&mut state, id);
+        }
+        type_line(&mut state, "swap:S001:S004");
+        assert_eq!(members(&state), vec![vec!["S004", "S002", "S003"], vec!["S001"]]);
+        assert_eq!(state.message, "Swapped S001 and S004 (groups A ⇄ B)");
+        assert_eq!(
+            state.events.last(),
+            Some(&Event::Swap { id: "S001".to_string(), group: 0, with: "S004".to_string(), with_group: 1 })
+        );
+
+        type_line(&mut state, "swap:S002:S003");
+        assert_eq!(state.message, "S002 and S003 are both in group A");
+        type_line(&mut state, "swap:S002:S999");
+        assert_eq!(state.message, "S999 was not found");
+        type_line(&mut state, "swap:S002");
+        assert_eq!(state.message, "Type swap:ID:ID (e.g. swap:S001:S014)");
+
+        type_line(&mut state, "undo");
+        assert_eq!(members(&state), vec![vec!["S001", "S002", "S003"], vec!["S004"]]);
+    }
+
+    #[test]
+    fn test_add_to_group_with_room() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::En, Session::default(), Some(3), &names);
+        for id in ["S001", "S002", "S003", "S004"] {
+            type_line(&mut state, id);
+        }
+        type_line(&mut state, "add:S005:A");
+        assert_eq!(state.message, "Group A is full");
+        type_line(&mut state, "add:S005:C");
+        assert_eq!(state.message, "Group C does not exist");
+        type_line(&mut state, "add:S005");
+        assert_eq!(state.message, "Type add:ID:GROUP (e.g. add:S001:B)");
+
+        state.handle(Key::Tab);
+        state.handle(Key::Char('d'));
+        state.handle(Key::Esc);
+        type_line(&mut state, "add:S005:A");
+        assert_eq!(members(&state), vec![vec!["S002", "S003", "S005"], vec!["S004"]]);
+        assert_eq!(state.message, "Added: S005 (group A)");
+        assert_eq!(state.events.last(), Some(&Event::Add { student: Student::new("S005"), group: 0 }));
+        // The group being entered is not completed by it
+        type_line(&mut state, "add:S006:b");
+        assert_eq!(members(&state), vec![vec!["S002", "S003", "S005"], vec!["S004", "S006"]]);
+    }
+
+    #[test]
+    fn test_edits_are_logged() {
+        let labels = GroupNames::default();
+        let names = HashMap::new();
+        let mut state = State::new(&[], None, &labels, Lang::Ja, Session::default(), Some(2), &names);
+        for id in ["S001", "S002", "S003"] {
+            type_line(&mut state, id);
+        }
+        state.handle(Key::Tab);
+        state.handle(Key::Char('d'));
+        state.handle(Key::Char('u'));
+        let actions: Vec<&str> = state
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Add { .. } => "add",
+                Event::Complete { .. } => "complete",
+                Event::Delete { .. } => "delete",
+                Event::Undo => "undo",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(actions, vec!["add", "add", "complete", "add", "delete", "undo"]);
+        assert_eq!(state.events[2], Event::Complete { group: 0, size: 2 });
+
+        let mut audit = AuditLog::new(None);
+        state.write_events(&mut audit);
+        assert!(state.events.is_empty());
+        assert_eq!((audit.edits().added, audit.edits().undone), (3, 1));
+    }
+}
@@ -0,0 +1,91 @@
+//! WebAssembly entry points, so the same grouping can run in a browser.
+//!
+//! Build with `cargo build --lib --release --target wasm32-unknown-unknown`
+//! and load the module with `web/grouping.js`. Strings cross the boundary as
+//! UTF-8 bytes in the module's memory: the caller allocates a buffer with
+//! [`grouping_alloc`], writes the request JSON into it, and passes it to
+//! [`group_students`]. The result is a 4-byte little-endian length followed
+//! by the JSON text, released with [`grouping_free`].
+
+use crate::api;
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+
+/// Allocate `len` bytes in the module's memory for the caller to write into.
+#[no_mangle]
+pub extern "C" fn grouping_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Release a buffer returned by [`grouping_alloc`] or [`group_students`].
+///
+/// # Safety
+///
+/// `ptr` must come from one of those functions with the same `len`
+/// (for results, the length prefix plus 4), and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn grouping_free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+/// Group students described by the request JSON at `ptr` (see
+/// [`api::group`]; a `timestamp` string may be added since the module cannot
+/// read the clock). Returns `{"error": "..."}` when the request is invalid.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` initialized bytes.
+#[no_mangle]
+pub unsafe extern "C" fn group_students(ptr: *const u8, len: usize) -> *mut u8 {
+    let bytes = std::slice::from_raw_parts(ptr, len);
+    let result = std::str::from_utf8(bytes)
+        .map_err(|_| message(Msg::RequestNotUtf8, &[]))
+        .and_then(group_students_json);
+    let json = match result {
+        Ok(json) => json,
+        Err(message) => Value::object(vec![("error", Value::from(message))]).to_string(),
+    };
+
+    let mut out = Vec::with_capacity(json.len() + 4);
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(json.as_bytes());
+    // A boxed slice has no spare capacity, so `len + 4` is enough to free it
+    Box::into_raw(out.into_boxed_slice()) as *mut u8
+}
+
+/// The request/response handling behind [`group_students`], without raw memory.
+pub fn group_students_json(request: &str) -> Result<String, String> {
+    let request = Value::parse(request)?;
+    let timestamp = request.get("timestamp").and_then(Value::as_str).unwrap_or("");
+    api::group(&request, timestamp).map(|result| result.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_students_through_memory() {
+        let request = br#"{"students": ["S001", "S002", "S003", "S004", "S005"], "timestamp": "2025-04-01T09:00:00Z"}"#;
+        let input = grouping_alloc(request.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(request.as_ptr(), input, request.len());
+            let output = group_students(input, request.len());
+            grouping_free(input, request.len());
+
+            let len = u32::from_le_bytes(std::slice::from_raw_parts(output, 4).try_into().unwrap()) as usize;
+            let json = std::str::from_utf8(std::slice::from_raw_parts(output.add(4), len)).unwrap().to_string();
+            grouping_free(output, len + 4);
+
+            let result = Value::parse(&json).unwrap();
+            assert_eq!(result.get("total_groups").and_then(Value::as_u64), Some(2));
+            assert_eq!(result.get("timestamp").and_then(Value::as_str), Some("2025-04-01T09:00:00Z"));
+        }
+    }
+
+    #[test]
+    fn test_invalid_request_returns_error() {
+        assert!(group_students_json("{").is_err());
+        assert!(group_students_json(r#"{"students": "S001"}"#).is_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Posting the final grouping to chat services through incoming webhooks.
+
+use crate::group::{Group, StudentId};
+use crate::http;
+use crate::i18n::{message, Msg};
+use crate::json::Value;
+use crate::output::{MemberLabels, Metadata};
+use std::collections::HashMap;
+
+/// Slack accepts at most 50 blocks per message; the header and footer take two.
+const SLACK_GROUPS_PER_MESSAGE: usize = 48;
+
+/// Discord accepts at most 10 embeds per message.
+const DISCORD_GROUPS_PER_MESSAGE: usize = 10;
+
+/// Teams rejects webhook messages over 28 KB; 20 groups of a large class stay well under it.
+const TEAMS_GROUPS_PER_MESSAGE: usize = 20;
+
+/// Colors cycled through the Discord embeds, so neighbouring groups stand apart.
+const DISCORD_COLORS: [u64; 6] = [0x5865f2, 0x57f287, 0xfee75c, 0xeb459e, 0xed4245, 0x3ba55d];
+
+/// Post the groups to a Slack channel, one section block per group.
+pub fn post_slack(
+    url: &str,
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+) -> Result<(), String> {
+    for payload in slack_payloads(groups, names, metadata) {
+        let response = http::send("POST", url, &[("Content-Type", "application/json")], Some(&payload.to_string()))?;
+        if !response.is_success() {
+            return Err(message(Msg::ServiceError, &[&"Slack", &response.status, &response.body.trim()]));
+        }
+    }
+    Ok(())
+}
+
+/// Messages for Slack's Block Kit. Large classes are split over several messages.
+fn slack_payloads(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<Value> {
+    let total_students: usize = groups.iter().map(|g| g.members.len()).sum();
+    let chunks: Vec<(usize, &[Group])> = groups
+        .chunks(SLACK_GROUPS_PER_MESSAGE)
+        .enumerate()
+        .map(|(i, chunk)| (i * SLACK_GROUPS_PER_MESSAGE, chunk))
+        .collect();
+    let labels = MemberLabels::new(names, metadata);
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(n, (offset, chunk))| {
+            let mut blocks = Vec::new();
+            if n == 0 {
+                blocks.push(Value::object(vec![
+                    ("type", Value::from("header")),
+                    (
+                        "text",
+                        Value::object(vec![
+                            ("type", Value::from("plain_text")),
+                            ("text", Value::from(metadata.lang.text(Msg::ResultTitle))),
+                        ]),
+                    ),
+                ]));
+            }
+            for (i, group) in chunk.iter().enumerate() {
+                let mut text = format!(
+                    "*{}* ({})",
+                    metadata.lang.format(Msg::GroupHeading, &[&metadata.group_names.label(offset + i)]),
+                    metadata.lang.format(Msg::Members, &[&group.members.len()])
+                );
+                for member in &group.members {
+                    text.push_str(&format!("\n• {}", slack_escape(&labels.label(member))));
+                }
+                blocks.push(Value::object(vec![
+                    ("type", Value::from("section")),
+                    ("text", Value::object(vec![("type", Value::from("mrkdwn")), ("text", Value::from(text))])),
+                ]));
+            }
+            if n + 1 == chunks.len() {
+                blocks.push(Value::object(vec![
+                    ("type", Value::from("context")),
+                    (
+                        "elements",
+                        Value::Array(vec![Value::object(vec![
+                            ("type", Value::from("mrkdwn")),
+                            ("text", Value::from(summary(groups.len(), total_students, metadata))),
+                        ])]),
+                    ),
+                ]));
+            }
+            Value::object(vec![
+                // Shown in notifications, where blocks are not rendered
+                ("text", Value::from(headline(groups.len(), total_students, metadata))),
+                ("blocks", Value::Array(blocks)),
+            ])
+        })
+        .collect()
+}
+
+/// Post the groups to a Discord channel, one embed per group.
+pub fn post_discord(
+    url: &str,
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+) -> Result<(), String> {
+    for payload in discord_payloads(groups, names, metadata) {
+        let response = http::send("POST", url, &[("Content-Type", "application/json")], Some(&payload.to_string()))?;
+        if !response.is_success() {
+            return Err(message(Msg::ServiceError, &[&"Discord", &response.status, &response.body.trim()]));
+        }
+    }
+    Ok(())
+}
+
+/// Webhook messages with one embed per group, split over several messages
+/// for large classes.
+fn discord_payloads(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<Value> {
+    let total_students: usize = groups.iter().map(|g| g.members.len()).sum();
+    let chunk_count = groups.chunks(DISCORD_GROUPS_PER_MESSAGE).len();
+    let labels = MemberLabels::new(names, metadata);
+
+    groups
+        .chunks(DISCORD_GROUPS_PER_MESSAGE)
+        .enumerate()
+        .map(|(n, chunk)| {
+            let offset = n * DISCORD_GROUPS_PER_MESSAGE;
+            let embeds = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, group)| {
+                    let index = offset + i;
+                    let description: Vec<String> = group
+                        .members
+                        .iter()
+                        .map(|member| discord_escape(&labels.label(member)))
+                        .collect();
+                    let mut pairs = vec![
+                        (
+                            "title",
+                            Value::from(
+                                metadata
+                                    .lang
+                                    .format(Msg::GroupTitle, &[&metadata.group_names.label(index), &group.members.len()]),
+                            ),
+                        ),
+                        ("description", Value::from(description.join("\n"))),
+                        ("color", Value::from(DISCORD_COLORS[index % DISCORD_COLORS.len()])),
+                    ];
+                    // The summary goes under the very last group
+                    if n + 1 == chunk_count && i + 1 == chunk.len() {
+                        pairs.push((
+                            "footer",
+                            Value::object(vec![("text", Value::from(summary(groups.len(), total_students, metadata)))]),
+                        ));
+                    }
+                    Value::object(pairs)
+                })
+                .collect();
+
+            let mut pairs = Vec::new();
+            if n == 0 {
+                pairs.push((
+                    "content",
+                    Value::from(format!(
+                        "**{}** ({})",
+                        metadata.lang.text(Msg::ResultTitle),
+                        metadata.lang.format(Msg::Counts, &[&groups.len(), &total_students])
+                    )),
+                ));
+            }
+            pairs.push(("embeds", Value::Array(embeds)));
+            // Never ping anyone, even if a name looks like @everyone
+            pairs.push(("allowed_mentions", Value::object(vec![("parse", Value::Array(Vec::new()))])));
+            Value::object(pairs)
+        })
+        .collect()
+}
+
+/// Post the groups to a Microsoft Teams channel as Adaptive Cards, one
+/// container per group.
+pub fn post_teams(
+    url: &str,
+    groups: &[Group],
+    names: &HashMap<StudentId, String>,
+    metadata: &Metadata,
+) -> Result<(), String> {
+    for payload in teams_payloads(groups, names, metadata) {
+        let response = http::send("POST", url, &[("Content-Type", "application/json")], Some(&payload.to_string()))?;
+        if !response.is_success() {
+            return Err(message(Msg::ServiceError, &[&"Teams", &response.status, &response.body.trim()]));
+        }
+    }
+    Ok(())
+}
+
+/// Webhook messages each holding one Adaptive Card, split over several
+/// messages for large classes.
+fn teams_payloads(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<Value> {
+    let total_students: usize = groups.iter().map(|g| g.members.len()).sum();
+    let chunk_count = groups.chunks(TEAMS_GROUPS_PER_MESSAGE).len();
+    let labels = MemberLabels::new(names, metadata);
+    let text_block = |text: String, extra: Vec<(&str, Value)>| {
+        let mut pairs = vec![
+            ("type", Value::from("TextBlock")),
+            ("text", Value::from(text)),
+            ("wrap", Value::Bool(true)),
+        ];
+        pairs.extend(extra);
+        Value::object(pairs)
+    };
+
+    groups
+        .chunks(TEAMS_GROUPS_PER_MESSAGE)
+        .enumerate()
+        .map(|(n, chunk)| {
+            let mut body = Vec::new();
+            if n == 0 {
+                body.push(text_block(
+                    headline(groups.len(), total_students, metadata),
+                    vec![("size", Value::from("Large")), ("weight", Value::from("Bolder"))],
+                ));
+            }
+            for (i, group) in chunk.iter().enumerate() {
+                let index = n * TEAMS_GROUPS_PER_MESSAGE + i;
+                let mut items = vec![text_block(
+                    metadata
+                        .lang
+                        .format(Msg::GroupTitle, &[&metadata.group_names.label(index), &group.members.len()]),
+                    vec![("weight", Value::from("Bolder"))],
+                )];
+                // TextRuns are shown as written, so names need no Markdown escaping
+                items.extend(group.members.iter().map(|member| {
+                    Value::object(vec![
+                        ("type", Value::from("RichTextBlock")),
+                        ("spacing", Value::from("None")),
+                        (
+                            "inlines",
+                            Value::Array(vec![Value::object(vec![
+                                ("type", Value::from("TextRun")),
+                                ("text", Value::from(labels.label(member))),
+                            ])]),
+                        ),
+                    ])
+                }));
+                body.push(Value::object(vec![
+                    ("type", Value::from("Container")),
+                    ("separator", Value::Bool(true)),
+                    ("items", Value::Array(items)),
+                ]));
+            }
+            if n + 1 == chunk_count {
+                body.push(text_block(
+                    summary(groups.len(), total_students, metadata),
+                    vec![("isSubtle", Value::Bool(true)), ("size", Value::from("Small"))],
+                ));
+            }
+            let card = Value::object(vec![
+                ("$schema", Value::from("http://adaptivecards.io/schemas/adaptive-card.json")),
+                ("type", Value::from("AdaptiveCard")),
+                ("version", Value::from("1.4")),
+                ("body", Value::Array(body)),
+            ]);
+            Value::object(vec![
+                ("type", Value::from("message")),
+                (
+                    "attachments",
+                    Value::Array(vec![Value::object(vec![
+                        ("contentType", Value::from("application/vnd.microsoft.card.adaptive")),
+                        ("contentUrl", Value::Null),
+                        ("content", card),
+                    ])]),
+                ),
+            ])
+        })
+        .collect()
+}
+
+/// The title with the totals, shown in notifications.
+fn headline(total_groups: usize, total_students: usize, metadata: &Metadata) -> String {
+    format!(
+        "{} ({})",
+        metadata.lang.text(Msg::ResultTitle),
+        metadata.lang.format(Msg::Counts, &[&total_groups, &total_students])
+    )
+}
+
+/// The line under the groups: totals, time, and the seed when there was one.
+fn summary(total_groups: usize, total_students: usize, metadata: &Metadata) -> String {
+    let mut text = metadata.lang.format(Msg::Summary, &[&total_groups, &total_students]);
+    if !metadata.timestamp.is_empty() {
+        text.push_str(&format!(" | {}", metadata.timestamp));
+    }
+    if let Some(seed) = metadata.seed {
+        text.push_str(" | ");
+        text.push_str(&metadata.lang.format(Msg::SeedFooter, &[&seed]));
+    }
+    text
+}
+
+/// Escape the characters Slack treats as control sequences in mrkdwn.
+fn slack_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Escape characters that Discord would treat as Markdown.
+fn discord_escape(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(n: usize) -> Vec<Group> {
+        (0..n)
+            .map(|i| Group::from_members(vec![format!("S{:03}", i * 2 + 1), format!("S{:03}", i * 2 + 2)]))
+            .collect()
+    }
+
+    #[test]
+    fn test_slack_payload_has_one_section_per_group() {
+        let mut names = HashMap::new();
+        names.insert("S001".to_string(), "<山田>".to_string());
+        let metadata = Metadata {
+            timestamp: "2025-04-01T09:00:00Z".to_string(),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let payloads = slack_payloads(&groups(2), &names, &metadata);
+
+        assert_eq!(payloads.len(), 1);
+        let blocks = payloads[0].get("blocks").and_then(Value::as_array).unwrap();
+        assert_eq!(blocks.len(), 4);
+        let first = blocks[1].get("text").and_then(|t| t.get("text")).and_then(Value::as_str);
+        assert_eq!(first, Some("*グループ A* (2 人)\n• S001 &lt;山田&gt;\n• S002"));
+        let footer = blocks[3].get("elements").and_then(Value::as_array).unwrap()[0].get("text");
+        assert_eq!(
+            footer.and_then(Value::as_str),
+            Some("合計: 2 グループ / 4 人 | 2025-04-01T09:00:00Z | シード: 42")
+        );
+    }
+
+    #[test]
+    fn test_slack_splits_large_classes() {
+        let payloads = slack_payloads(&groups(50), &HashMap::new(), &Metadata::default());
+        assert_eq!(payloads.len(), 2);
+        let second = payloads[1].get("blocks").and_then(Value::as_array).unwrap();
+        // Two remaining groups (AW, AX) and the footer
+        assert_eq!(second.len(), 3);
+        let label = second[0].get("text").and_then(|t| t.get("text")).and_then(Value::as_str).unwrap();
+        assert!(label.starts_with("*グループ AW*"), "{}", label);
+    }
+
+    #[test]
+    fn test_discord_payload_has_one_embed_per_group() {
+        let mut names = HashMap::new();
+        names.insert("S002".to_string(), "山田_太郎".to_string());
+        let payloads = discord_payloads(&groups(12), &names, &Metadata::default());
+
+        assert_eq!(payloads.len(), 2);
+        let first = payloads[0].get("embeds").and_then(Value::as_array).unwrap();
+        assert_eq!(first.len(), 10);
+        assert_eq!(first[0].get("title").and_then(Value::as_str), Some("グループ A (2 人)"));
+        assert_eq!(first[0].get("description").and_then(Value::as_str), Some("S001\nS002 山田\\_太郎"));
+        assert!(first[9].get("footer").is_none());
+
+        let second = payloads[1].get("embeds").and_then(Value::as_array).unwrap();
+        assert_eq!(second.len(), 2);
+        assert!(payloads[1].get("content").is_none());
+        let footer = second[1].get("footer").and_then(|f| f.get("text")).and_then(Value::as_str);
+        assert_eq!(footer, Some("合計: 12 グループ / 24 人"));
+    }
+
+    #[test]
+    fn test_teams_payload_has_one_container_per_group() {
+        let mut names = HashMap::new();
+        names.insert("S001".to_string(), "山田_太郎".to_string());
+        let payloads = teams_payloads(&groups(22), &names, &Metadata::default());
+        assert_eq!(payloads.len(), 2);
+
+        let body = |payload: &Value| {
+            payload.get("attachments").and_then(Value::as_array).unwrap()[0]
+                .get("content")
+                .and_then(|card| card.get("body"))
+                .and_then(Value::as_array)
+                .unwrap()
+                .to_vec()
+        };
+        let first = body(&payloads[0]);
+        // The title and 20 groups
+        assert_eq!(first.len(), 21);
+        let items = first[1].get("items").and_then(Value::as_array).unwrap();
+        assert_eq!(items[0].get("text").and_then(Value::as_str), Some("グループ A (2 人)"));
+        let run = items[1].get("inlines").and_then(Value::as_array).unwrap()[0].get("text");
+        assert_eq!(run.and_then(Value::as_str), Some("S001 山田_太郎"));
+
+        // The two remaining groups and the summary
+        let second = body(&payloads[1]);
+        assert_eq!(second.len(), 3);
+        assert_eq!(second[2].get("text").and_then(Value::as_str), Some("合計: 22 グループ / 44 人"));
+    }
+}
@@ -0,0 +1,275 @@
+//! Excel workbook (.xlsx) export of the final grouping.
+//!
+//! The workbook is written by hand to avoid extra dependencies: an .xlsx file
+//! is a ZIP archive of XML parts, stored here without compression. It has two
+//! worksheets, the long-format table of the CSV output and a table with one
+//! column per group, each with a bold header row that stays in view.
+
+use crate::group::{Group, StudentId};
+use crate::i18n::Msg;
+use crate::output::{self, Metadata};
+use std::collections::HashMap;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+<Override PartName="/xl/worksheets/sheet2.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>
+</Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>
+</Relationships>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/>
+<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>"#;
+
+/// Style 0 is the default; style 1 is the bold header with a gray fill.
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts>
+<fills count="3"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill><fill><patternFill patternType="solid"><fgColor rgb="FFD9D9D9"/><bgColor indexed="64"/></patternFill></fill></fills>
+<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>
+<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>
+<cellXfs count="2"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="0" fontId="1" fillId="2" borderId="0" xfId="0" applyFont="1" applyFill="1"/></cellXfs>
+<cellStyles count="1"><cellStyle name="Normal" xfId="0" builtinId="0"/></cellStyles>
+</styleSheet>"#;
+
+/// Render the groups as an .xlsx workbook.
+pub fn render_xlsx(groups: &[Group], names: &HashMap<StudentId, String>, metadata: &Metadata) -> Vec<u8> {
+    let lang = metadata.lang;
+    let workbook = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<sheets><sheet name="{}" sheetId="1" r:id="rId1"/><sheet name="{}" sheetId="2" r:id="rId2"/></sheets>
+</workbook>"#,
+        escape(lang.text(Msg::XlsxAssignments)),
+        escape(lang.text(Msg::XlsxGroups)),
+    );
+
+    let assignments = output::to_rows(groups, names, metadata);
+
+    // One column per group, headed by its label
+    let labels = output::MemberLabels::new(names, metadata);
+    let longest = groups.iter().map(|g| g.members.len()).max().unwrap_or(0);
+    let mut by_group = vec![(0..groups.len()).map(|i| metadata.group_names.label(i)).collect::<Vec<_>>()];
+    for row in 0..longest {
+        by_group.push(
+            groups
+                .iter()
+                .map(|group| {
+                    group
+                        .members
+                        .get(row)
+                        .map(|member| labels.label(member))
+                        .unwrap_or_default()
+                })
+                .collect(),
+        );
+    }
+    // Under the groups, after a blank row
+    by_group.push(Vec::new());
+    by_group.push(vec![lang.format(Msg::ContentHash, &[&output::content_hash(groups)])]);
+
+    write_zip(&[
+        ("[Content_Types].xml", CONTENT_TYPES.as_bytes()),
+        ("_rels/.rels", ROOT_RELS.as_bytes()),
+        ("xl/workbook.xml", workbook.as_bytes()),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.as_bytes()),
+        ("xl/styles.xml", STYLES.as_bytes()),
+        ("xl/worksheets/sheet1.xml", worksheet(&assignments).as_bytes()),
+        ("xl/worksheets/sheet2.xml", worksheet(&by_group).as_bytes()),
+    ])
+}
+
+/// A worksheet holding `rows` as text, the first row being the header.
+fn worksheet(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews>
+"#,
+    );
+
+    // Widen each column to its longest cell; full-width characters take two
+    if columns > 0 {
+        out.push_str("<cols>");
+        for column in 0..columns {
+            let width = rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum::<usize>())
+                .max()
+                .unwrap_or(0);
+            out.push_str(&format!(
+                r#"<col min="{0}" max="{0}" width="{1}" customWidth="1"/>"#,
+                column + 1,
+                width.clamp(8, 60) + 2
+            ));
+        }
+        out.push_str("</cols>\n");
+    }
+
+    out.push_str("<sheetData>\n");
+    for (r, row) in rows.iter().enumerate() {
+        out.push_str(&format!(r#"<row r="{}">"#, r + 1));
+        for (c, cell) in row.iter().enumerate().filter(|(_, cell)| !cell.is_empty()) {
+            let style = if r == 0 { r#" s="1""# } else { "" };
+            out.push_str(&format!(
+                r#"<c r="{}{}" t="inlineStr"{}><is><t xml:space="preserve">{}</t></is></c>"#,
+                column_name(c),
+                r + 1,
+                style,
+                escape(cell)
+            ));
+        }
+        out.push_str("</row>\n");
+    }
+    out.push_str("</sheetData>\n</worksheet>");
+    out
+}
+
+/// Spreadsheet column name of a 0-based index: A, B, ..., Z, AA, AB, ...
+fn column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("ASCII letters")
+}
+
+/// Escape text for XML, dropping control characters XML cannot hold.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Pack `files` into a ZIP archive without compression.
+fn write_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    // DOS date 1980-01-01 00:00; the archive does not need real times
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        for field in [20u16, 0, 0, DOS_TIME, DOS_DATE] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        for field in [20u16, 20, 0, 0, DOS_TIME, DOS_DATE] {
+            central.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            central.extend_from_slice(&field.to_le_bytes());
+        }
+        // Name length, extra field, comment, disk number, internal attributes
+        for field in [name.len() as u16, 0, 0, 0, 0] {
+            central.extend_from_slice(&field.to_le_bytes());
+        }
+        // External attributes, offset of the local header
+        for field in [0u32, offset] {
+            central.extend_from_slice(&field.to_le_bytes());
+        }
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    let central_size = central.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    let count = files.len() as u16;
+    for field in [0u16, 0, count, count] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    for field in [central_size, central_offset] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
+
+/// CRC-32 (IEEE) checksum, as used by ZIP.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Lang;
+
+    #[test]
+    fn test_zip_parts() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(27), "AB");
+
+        let groups = vec![
+            Group::from_members(vec!["S001".to_string(), "S002".to_string()]),
+            Group::from_members(vec!["S003".to_string()]),
+        ];
+        let names = HashMap::from([("S001".to_string(), "山田 & 佐藤".to_string())]);
+        let metadata = Metadata {
+            lang: Lang::En,
+            ..Default::default()
+        };
+        let xlsx = render_xlsx(&groups, &names, &metadata);
+        assert!(xlsx.starts_with(b"PK\x03\x04"));
+        // The end of central directory record lists all seven parts
+        let end = &xlsx[xlsx.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 7);
+
+        let text = String::from_utf8_lossy(&xlsx);
+        assert!(text.contains(r#"<sheet name="Assignments""#));
+        assert!(text.contains("S001 山田 &amp; 佐藤"));
+        assert!(text.contains(r#"<c r="B2" t="inlineStr"><is><t xml:space="preserve">S003</t>"#));
+    }
+}
@@ -0,0 +1,146 @@
+//! Minimal YAML writer for `--format yaml`.
+//!
+//! Writes JSON values as block-style YAML: objects as `key: value` lines and
+//! arrays as `- item` lines, indented by two spaces. Strings that YAML would
+//! read as something else (numbers, booleans, dates, `null`) or that contain
+//! special characters are double-quoted, with the escapes of JSON.
+
+use crate::json::Value;
+
+/// Serialize `value` as a YAML document.
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(pairs) if !pairs.is_empty() => push_pairs(&mut out, pairs, 0),
+        Value::Array(items) if !items.is_empty() => push_items(&mut out, items, 0),
+        _ => {
+            out.push_str(&scalar(value));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn push_pairs(out: &mut String, pairs: &[(String, Value)], indent: usize) {
+    for (key, value) in pairs {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&string(key));
+        out.push(':');
+        match value {
+            Value::Object(pairs) if !pairs.is_empty() => {
+                out.push('\n');
+                push_pairs(out, pairs, indent + 2);
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push('\n');
+                push_items(out, items, indent + 2);
+            }
+            _ => {
+                out.push(' ');
+                out.push_str(&scalar(value));
+                out.push('\n');
+            }
+        }
+    }
+}
+
+fn push_items(out: &mut String, items: &[Value], indent: usize) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push('-');
+        match item {
+            Value::Object(pairs) if !pairs.is_empty() => {
+                // The first key goes on the line of the dash
+                let mut nested = String::new();
+                push_pairs(&mut nested, pairs, indent + 2);
+                out.push(' ');
+                out.push_str(&nested[indent + 2..]);
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push('\n');
+                push_items(out, items, indent + 2);
+            }
+            _ => {
+                out.push(' ');
+                out.push_str(&scalar(item));
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// A value that fits on one line; only empty arrays and objects get here.
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.clone(),
+        Value::String(s) => string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+/// `s` as a plain scalar when YAML reads it back as the same string, quoted otherwise.
+fn string(s: &str) -> String {
+    const RESERVED: &[&str] = &["null", "~", "true", "false", "yes", "no", "on", "off", "y", "n"];
+    let Some(first) = s.chars().next() else {
+        return "\"\"".to_string();
+    };
+    let quoted = RESERVED.contains(&s.to_lowercase().as_str())
+        || "-?:,[]{}#&*!|>'\"%@`.+ ".contains(first)
+        // Numbers, and dates such as 2025-04-01 which YAML 1.1 turns into timestamps
+        || first.is_ascii_digit()
+        || s.ends_with([' ', ':'])
+        || s.contains(": ")
+        || s.contains(" #")
+        || s.chars().any(char::is_control);
+    if quoted {
+        Value::from(s).to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_string() {
+        let value = Value::parse(
+            r#"{"timestamp": "2025-04-01T09:30:00Z", "seed": null, "groups": [
+                {"label": "赤", "members": ["S001", "S002"], "leader": "S001"},
+                {"label": "B", "members": [], "names": {"S003": "山田: 花子"}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_string(&value),
+            concat!(
+                "timestamp: \"2025-04-01T09:30:00Z\"\n",
+                "seed: null\n",
+                "groups:\n",
+                "  - label: 赤\n",
+                "    members:\n",
+                "      - S001\n",
+                "      - S002\n",
+                "    leader: S001\n",
+                "  - label: B\n",
+                "    members: []\n",
+                "    names:\n",
+                "      S003: \"山田: 花子\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(string("S001"), "S001");
+        assert_eq!(string("Red 2"), "Red 2");
+        for s in ["", "yes", "No", "null", "42", "3.5", "-1", "#1", "a #b", "a: b", "line\nbreak", " pad"] {
+            assert!(string(s).starts_with('"'), "{:?}", s);
+        }
+        assert_eq!(string("a\"b\nc"), r#""a\"b\nc""#);
+    }
+}
@@ -0,0 +1,50 @@
+// Browser wrapper for the grouping-tool WebAssembly module.
+//
+// Build the module with:
+//   cargo build --lib --release --target wasm32-unknown-unknown
+// and serve target/wasm32-unknown-unknown/release/grouping_tool.wasm next to this file.
+//
+//   import { loadGrouping } from "./grouping.js";
+//   const grouping = await loadGrouping("grouping_tool.wasm");
+//   const result = grouping.groupStudents(["S001", "S002", "S003", "S004"], { balance: ["skill"] });
+
+export async function loadGrouping(url) {
+  const { instance } = await WebAssembly.instantiateStreaming(fetch(url), {});
+  const wasm = instance.exports;
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+
+  // Same request as `POST /group` of `grouping-tool serve`.
+  function group(request) {
+    if (request.seed === undefined) {
+      // The module has no entropy source of its own
+      const words = crypto.getRandomValues(new Uint32Array(2));
+      request = { ...request, seed: Number(((BigInt(words[0]) << 21n) ^ BigInt(words[1])) & 0x1fffffffffffffn) };
+    }
+    request = { timestamp: new Date().toISOString().replace(/\.\d+Z$/, "Z"), ...request };
+
+    const input = encoder.encode(JSON.stringify(request));
+    const inputPtr = wasm.grouping_alloc(input.length);
+    new Uint8Array(wasm.memory.buffer, inputPtr, input.length).set(input);
+    const outputPtr = wasm.group_students(inputPtr, input.length);
+    wasm.grouping_free(inputPtr, input.length);
+
+    const length = new DataView(wasm.memory.buffer).getUint32(outputPtr, true);
+    const json = decoder.decode(new Uint8Array(wasm.memory.buffer, outputPtr + 4, length));
+    wasm.grouping_free(outputPtr, length + 4);
+
+    const result = JSON.parse(json);
+    if (result.error) {
+      throw new Error(result.error);
+    }
+    return result;
+  }
+
+  return {
+    group,
+    // Group a list of student IDs; `options` may hold seed, balance, and constraints.
+    groupStudents(ids, options = {}) {
+      return group({ ...options, students: ids });
+    },
+  };
+}